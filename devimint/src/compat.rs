@@ -0,0 +1,437 @@
+//! Cross-version compatibility matrix
+//!
+//! Starts a federation with one fedimintd version, optionally upgrades half of
+//! its guardians to a second version, and then runs a standard set of
+//! operations for every pairing of the given fedimint-cli and gatewayd
+//! versions. For every pairing the report records the API versions the client
+//! negotiated with the federation, the modules it had to disable because there
+//! is no common API version, and the outcome of every operation.
+//!
+//! `scripts/tests/compat-matrix.sh` runs this for every federation setup of a
+//! list of releases and merges the reports.
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Instant;
+
+use anyhow::{Context, Result, bail};
+use fedimint_api_client::api::ApiVersionSet;
+use fedimint_core::Amount;
+use fedimint_core::core::{ModuleInstanceId, ModuleKind};
+use fedimint_core::encoding::Encodable;
+use fedimint_core::module::ApiVersion;
+use fedimint_core::util::write_overwrite_async;
+use fedimint_ln_server::common::lightning_invoice::Bolt11Invoice;
+use fedimint_lnv2_client::FinalReceiveOperationState;
+use fedimint_logging::LOG_DEVIMINT;
+use futures::Future;
+use serde::Serialize;
+use tracing::{info, warn};
+
+use crate::federation::Client;
+use crate::tests::{ln_invoice, ln_pay, lnv2_receive, lnv2_send};
+use crate::util::ProcessManager;
+use crate::{DevFed, cmd, dev_fed};
+
+/// The binaries of a single [`compat_matrix`] run
+pub struct CompatMatrixBinaries {
+    /// fedimintd that all guardians run DKG with
+    pub fedimintd: PathBuf,
+    /// fedimintd the upper half of the guardians is upgraded to after DKG
+    pub fedimintd_upgrade: Option<PathBuf>,
+    pub fedimint_cli_paths: Vec<PathBuf>,
+    /// Oldest first, since a gateway cannot be downgraded
+    pub gatewayd_paths: Vec<PathBuf>,
+    pub gateway_cli_paths: Vec<PathBuf>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CompatReport {
+    /// fedimintd version of every guardian
+    pub fedimintd: BTreeMap<usize, String>,
+    pub pairings: Vec<PairingReport>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PairingReport {
+    pub fedimint_cli: String,
+    pub gatewayd: String,
+    /// Outcome of the API version discovery, `None` if it failed
+    pub negotiation: Option<Negotiation>,
+    pub operations: Vec<OperationOutcome>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Negotiation {
+    pub core: ApiVersion,
+    /// Negotiated API version of every module, `None` if the client disabled
+    /// the module since it has no API version in common with the federation
+    pub modules: BTreeMap<ModuleInstanceId, (ModuleKind, Option<ApiVersion>)>,
+    pub disabled_modules: Vec<ModuleKind>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct OperationOutcome {
+    pub name: String,
+    pub status: OperationStatus,
+    /// The error of a failed or the reason for a skipped operation
+    pub message: Option<String>,
+    pub duration_ms: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OperationStatus {
+    Succeeded,
+    Failed,
+    Skipped,
+}
+
+impl Negotiation {
+    fn supports(&self, kind: &str) -> bool {
+        self.modules
+            .values()
+            .any(|(module_kind, api)| module_kind.as_str() == kind && api.is_some())
+    }
+}
+
+impl PairingReport {
+    async fn run(&mut self, name: &str, operation: impl Future<Output = Result<()>>) {
+        let start = Instant::now();
+        let result = operation.await;
+        let duration_ms = start.elapsed().as_millis() as u64;
+
+        let (status, message) = match result {
+            Ok(()) => (OperationStatus::Succeeded, None),
+            Err(err) => {
+                warn!(
+                    target: LOG_DEVIMINT,
+                    operation = name,
+                    fedimint_cli = %self.fedimint_cli,
+                    gatewayd = %self.gatewayd,
+                    "Compatibility operation failed: {err:#}"
+                );
+                (OperationStatus::Failed, Some(format!("{err:#}")))
+            }
+        };
+
+        self.operations.push(OperationOutcome {
+            name: name.to_string(),
+            status,
+            message,
+            duration_ms,
+        });
+    }
+
+    fn skip(&mut self, name: &str, reason: &str) {
+        self.operations.push(OperationOutcome {
+            name: name.to_string(),
+            status: OperationStatus::Skipped,
+            message: Some(reason.to_string()),
+            duration_ms: 0,
+        });
+    }
+
+    fn num_failed(&self) -> usize {
+        self.operations
+            .iter()
+            .filter(|operation| operation.status == OperationStatus::Failed)
+            .count()
+    }
+}
+
+/// Runs the compatibility matrix and writes the [`CompatReport`] to `report`.
+/// Fails after writing the report if any operation failed.
+pub async fn compat_matrix(
+    process_mgr: &ProcessManager,
+    binaries: CompatMatrixBinaries,
+    report: PathBuf,
+) -> Result<()> {
+    if binaries.fedimint_cli_paths.is_empty() {
+        bail!("Must provide at least 1 fedimint-cli path");
+    }
+
+    if binaries.gatewayd_paths.is_empty()
+        || binaries.gatewayd_paths.len() != binaries.gateway_cli_paths.len()
+    {
+        bail!("Must provide a gateway-cli path for each of at least 1 gatewayd path");
+    }
+
+    // TODO: Audit that the environment access only happens in single-threaded code.
+    unsafe {
+        std::env::set_var("FM_FEDIMINTD_BASE_EXECUTABLE", &binaries.fedimintd);
+        std::env::set_var("FM_GATEWAYD_BASE_EXECUTABLE", &binaries.gatewayd_paths[0]);
+        std::env::set_var(
+            "FM_GATEWAY_CLI_BASE_EXECUTABLE",
+            &binaries.gateway_cli_paths[0],
+        );
+    };
+
+    let mut dev_fed = dev_fed(process_mgr).await?;
+
+    let fed_size = process_mgr.globals.FM_FED_SIZE;
+    let dkg_version = crate::util::FedimintdCmd::version_or_default().await;
+    let mut fedimintd = (0..fed_size)
+        .map(|peer_id| (peer_id, dkg_version.to_string()))
+        .collect::<BTreeMap<_, _>>();
+
+    if let Some(fedimintd_upgrade) = &binaries.fedimintd_upgrade {
+        for peer_id in fed_size / 2..fed_size {
+            dev_fed
+                .fed
+                .restart_server_with_bin(process_mgr, peer_id, fedimintd_upgrade)
+                .await?;
+        }
+
+        let upgrade_version = crate::util::FedimintdCmd::version_or_default().await;
+        for peer_id in fed_size / 2..fed_size {
+            fedimintd.insert(peer_id, upgrade_version.to_string());
+        }
+    }
+
+    info!(target: LOG_DEVIMINT, ?fedimintd, "Federation for compatibility matrix is running");
+
+    dev_fed
+        .fed
+        .pegin_gateways(1_000_000, vec![&dev_fed.gw_lnd])
+        .await?;
+
+    let mut pairings = vec![];
+
+    for (gateway_index, (gatewayd, gateway_cli)) in binaries
+        .gatewayd_paths
+        .iter()
+        .zip(&binaries.gateway_cli_paths)
+        .enumerate()
+    {
+        if gateway_index > 0 {
+            dev_fed
+                .gw_lnd
+                .restart_with_bin(process_mgr, gatewayd, gateway_cli)
+                .await?;
+            dev_fed.fed.await_gateways_registered().await?;
+        }
+
+        let gatewayd_version = crate::util::Gatewayd::version_or_default().await;
+
+        for (client_index, fedimint_cli) in binaries.fedimint_cli_paths.iter().enumerate() {
+            // TODO: Audit that the environment access only happens in single-threaded code.
+            unsafe { std::env::set_var("FM_FEDIMINT_CLI_BASE_EXECUTABLE", fedimint_cli) };
+
+            let fedimint_cli_version = crate::util::FedimintCli::version_or_default().await;
+
+            info!(
+                target: LOG_DEVIMINT,
+                %fedimint_cli_version,
+                %gatewayd_version,
+                "Running compatibility operations"
+            );
+
+            let pairing = run_pairing(
+                &dev_fed,
+                &format!("compat-client-{client_index}-gateway-{gateway_index}"),
+                PairingReport {
+                    fedimint_cli: fedimint_cli_version.to_string(),
+                    gatewayd: gatewayd_version.to_string(),
+                    negotiation: None,
+                    operations: vec![],
+                },
+            )
+            .await;
+
+            pairings.push(pairing);
+        }
+    }
+
+    let report_json = CompatReport {
+        fedimintd,
+        pairings,
+    };
+
+    for pairing in &report_json.pairings {
+        let disabled = pairing
+            .negotiation
+            .as_ref()
+            .map(|negotiation| negotiation.disabled_modules.clone());
+
+        println!(
+            "### COMPAT fedimint-cli {} gatewayd {}: {} of {} operations failed, disabled modules: {disabled:?}",
+            pairing.fedimint_cli,
+            pairing.gatewayd,
+            pairing.num_failed(),
+            pairing.operations.len(),
+        );
+    }
+
+    write_overwrite_async(&report, serde_json::to_string_pretty(&report_json)?)
+        .await
+        .context("Failed to write compatibility report")?;
+
+    let num_failed = report_json
+        .pairings
+        .iter()
+        .map(PairingReport::num_failed)
+        .sum::<usize>();
+
+    if num_failed > 0 {
+        bail!(
+            "{num_failed} compatibility operations failed, see {}",
+            report.display()
+        );
+    }
+
+    Ok(())
+}
+
+/// Runs the standard operations with a new client of the current fedimint-cli
+/// version against the current gatewayd version
+async fn run_pairing(dev_fed: &DevFed, name: &str, mut report: PairingReport) -> PairingReport {
+    let DevFed {
+        fed,
+        gw_lnd,
+        gw_ldk,
+        ..
+    } = dev_fed;
+
+    let client = match fed.new_joined_client(name).await {
+        Ok(client) => client,
+        Err(err) => {
+            report.run("join", async { Err(err) }).await;
+            return report;
+        }
+    };
+
+    match discover_versions(dev_fed, &client).await {
+        Ok(negotiation) => report.negotiation = Some(negotiation),
+        Err(err) => report.run("discover-version", async { Err(err) }).await,
+    }
+
+    report
+        .run("pegin", fed.pegin_client(100_000, &client))
+        .await;
+
+    report
+        .run("reissue", async {
+            let notes = cmd!(client, "spend", "50000msat").out_json().await?["notes"]
+                .as_str()
+                .context("notes must be a string")?
+                .to_owned();
+            cmd!(client, "reissue", notes).run().await
+        })
+        .await;
+
+    let gw_lnd_client = gw_lnd.client();
+    let gw_ldk_client = gw_ldk.client();
+
+    let supports = |kind: &str| {
+        report
+            .negotiation
+            .as_ref()
+            .is_some_and(|negotiation| negotiation.supports(kind))
+    };
+    let supports_lnv1 = supports("ln");
+    let supports_lnv2 = crate::util::supports_lnv2() && supports("lnv2");
+
+    if supports_lnv1 {
+        report
+            .run("ln-send", async {
+                let invoice = gw_ldk_client.create_invoice(1_000_000).await?;
+                ln_pay(&client, invoice.to_string(), gw_lnd.gateway_id.clone()).await?;
+                gw_ldk_client
+                    .wait_bolt11_invoice(invoice.payment_hash().consensus_encode_to_vec())
+                    .await
+            })
+            .await;
+
+        report
+            .run("ln-receive", async {
+                let response = ln_invoice(
+                    &client,
+                    Amount::from_msats(100_000),
+                    "compat-matrix".to_string(),
+                    gw_lnd.gateway_id.clone(),
+                )
+                .await?;
+                gw_ldk_client
+                    .pay_invoice(Bolt11Invoice::from_str(&response.invoice)?)
+                    .await?;
+                cmd!(client, "await-invoice", response.operation_id.fmt_full())
+                    .run()
+                    .await
+            })
+            .await;
+    } else {
+        report.skip("ln-send", "ln module is not supported");
+        report.skip("ln-receive", "ln module is not supported");
+    }
+
+    if supports_lnv2 {
+        report
+            .run("lnv2-send", async {
+                let invoice = gw_ldk_client.create_invoice(1_000_000).await?;
+                lnv2_send(&client, &gw_lnd_client.address(), &invoice.to_string()).await
+            })
+            .await;
+
+        report
+            .run("lnv2-receive", async {
+                let (invoice, operation_id) =
+                    lnv2_receive(&client, &gw_lnd_client.address(), 100_000).await?;
+                gw_ldk_client.pay_invoice(invoice).await?;
+                let final_state = cmd!(
+                    client,
+                    "module",
+                    "lnv2",
+                    "await-receive",
+                    operation_id.fmt_full()
+                )
+                .out_json()
+                .await?;
+                anyhow::ensure!(
+                    final_state == serde_json::to_value(FinalReceiveOperationState::Claimed)?,
+                    "Receive did not succeed: {final_state}"
+                );
+                Ok(())
+            })
+            .await;
+    } else {
+        report.skip("lnv2-send", "lnv2 module is not supported");
+        report.skip("lnv2-receive", "lnv2 module is not supported");
+    }
+
+    report.run("backup", cmd!(client, "backup").run()).await;
+
+    report
+}
+
+/// Reports the API versions `client` negotiated with the federation
+async fn discover_versions(dev_fed: &DevFed, client: &Client) -> Result<Negotiation> {
+    let versions = cmd!(client, "discover-version").out_json().await?;
+    let versions: ApiVersionSet = serde_json::from_value(versions["versions"].clone())?;
+
+    let modules = dev_fed
+        .fed
+        .client_config()?
+        .modules
+        .into_iter()
+        .map(|(module_id, module)| {
+            (
+                module_id,
+                (module.kind, versions.modules.get(&module_id).copied()),
+            )
+        })
+        .collect::<BTreeMap<_, _>>();
+
+    let disabled_modules = modules
+        .values()
+        .filter(|(_, api)| api.is_none())
+        .map(|(kind, _)| kind.clone())
+        .collect();
+
+    Ok(Negotiation {
+        core: versions.core,
+        modules,
+        disabled_modules,
+    })
+}
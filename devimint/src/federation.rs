@@ -651,6 +651,31 @@ pub async fn restart_all_staggered_with_bin(
         Ok(())
     }
 
+    /// Restarts a single peer using the provided `bin_path`, leaving the other
+    /// peers on their current version to test mixed-version federations.
+    ///
+    /// Peers started afterwards also use `bin_path`.
+    pub async fn restart_server_with_bin(
+        &mut self,
+        process_mgr: &ProcessManager,
+        peer_id: usize,
+        bin_path: &PathBuf,
+    ) -> Result<()> {
+        if self.members.contains_key(&peer_id) {
+            self.terminate_server(peer_id).await?;
+        }
+
+        // TODO: Audit that the environment access only happens in single-threaded code.
+        unsafe { std::env::set_var("FM_FEDIMINTD_BASE_EXECUTABLE", bin_path) };
+
+        self.start_server(process_mgr, peer_id).await?;
+        self.await_peer(peer_id).await?;
+
+        let fedimintd_version = crate::util::FedimintdCmd::version_or_default().await;
+        info!(peer_id, %fedimintd_version, "restarted peer with new fedimintd version");
+        Ok(())
+    }
+
     pub async fn restart_all_with_bin(
         &mut self,
         process_mgr: &ProcessManager,
@@ -30,6 +30,7 @@
 use util::ProcessManager;
 
 pub mod cli;
+pub mod compat;
 pub mod devfed;
 pub mod envs;
 pub mod external;
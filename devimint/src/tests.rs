@@ -1570,7 +1570,11 @@ pub async fn do_try_create_and_pay_invoice(
     Ok(())
 }
 
-async fn ln_pay(client: &Client, invoice: String, gw_id: String) -> anyhow::Result<String> {
+pub(crate) async fn ln_pay(
+    client: &Client,
+    invoice: String,
+    gw_id: String,
+) -> anyhow::Result<String> {
     let value = cmd!(client, "ln-pay", invoice, "--gateway-id", gw_id,)
         .out_json()
         .await?;
@@ -1584,7 +1588,7 @@ async fn ln_pay(client: &Client, invoice: String, gw_id: String) -> anyhow::Resu
     }
 }
 
-async fn ln_invoice(
+pub(crate) async fn ln_invoice(
     client: &Client,
     amount: Amount,
     description: String,
@@ -1607,7 +1611,7 @@ async fn ln_invoice(
     Ok(ln_invoice_response)
 }
 
-async fn lnv2_receive(
+pub(crate) async fn lnv2_receive(
     client: &Client,
     gateway: &str,
     amount: u64,
@@ -1627,7 +1631,11 @@ async fn lnv2_receive(
     )?)
 }
 
-async fn lnv2_send(client: &Client, gateway: &String, invoice: &String) -> anyhow::Result<()> {
+pub(crate) async fn lnv2_send(
+    client: &Client,
+    gateway: &String,
+    invoice: &String,
+) -> anyhow::Result<()> {
     let send_op = serde_json::from_value::<OperationId>(
         cmd!(
             client,
@@ -2622,6 +2630,26 @@ pub enum TestCmd {
         #[arg(long)]
         lnv2: String,
     },
+    /// Run a standard set of operations for every pairing of client and
+    /// gateway versions against a federation of one or two fedimintd versions
+    CompatMatrix {
+        #[arg(long)]
+        fedimintd: PathBuf,
+        /// Upgrade the upper half of the guardians to this fedimintd after DKG
+        #[arg(long)]
+        fedimintd_upgrade: Option<PathBuf>,
+        #[arg(long, num_args=1..)]
+        fedimint_cli_paths: Vec<PathBuf>,
+        #[arg(long, num_args=1..)]
+        gatewayd_paths: Vec<PathBuf>,
+        #[arg(long, num_args=1..)]
+        gateway_cli_paths: Vec<PathBuf>,
+        /// Where to write the JSON report
+        #[arg(long)]
+        report: PathBuf,
+        #[arg(long)]
+        lnv2: String,
+    },
 }
 
 pub async fn handle_command(cmd: TestCmd, common_args: CommonArgs) -> Result<()> {
@@ -2761,6 +2789,31 @@ pub async fn handle_command(cmd: TestCmd, common_args: CommonArgs) -> Result<()>
             let (process_mgr, _) = setup(common_args).await?;
             Box::pin(upgrade_tests(&process_mgr, binary)).await?;
         }
+        TestCmd::CompatMatrix {
+            fedimintd,
+            fedimintd_upgrade,
+            fedimint_cli_paths,
+            gatewayd_paths,
+            gateway_cli_paths,
+            report,
+            lnv2,
+        } => {
+            // TODO: Audit that the environment access only happens in single-threaded code.
+            unsafe { std::env::set_var(FM_ENABLE_MODULE_LNV2_ENV, lnv2) };
+            let (process_mgr, _) = setup(common_args).await?;
+            Box::pin(crate::compat::compat_matrix(
+                &process_mgr,
+                crate::compat::CompatMatrixBinaries {
+                    fedimintd,
+                    fedimintd_upgrade,
+                    fedimint_cli_paths,
+                    gatewayd_paths,
+                    gateway_cli_paths,
+                },
+                report,
+            ))
+            .await?;
+        }
     }
     Ok(())
 }
@@ -135,6 +135,24 @@ pub(crate) enum AdminCmd {
     /// Show an audit across all modules
     Audit,
 
+    /// Compare the on-chain reserves of the federation with its liabilities
+    /// according to the audit. The reserves are the unspent outputs of the
+    /// wallet descriptors as reported by the given esplora instance.
+    ProofOfReserves {
+        /// Esplora instance used to look up the outputs of the descriptors
+        #[arg(long)]
+        esplora: SafeUrl,
+    },
+
+    /// Check that the liabilities committed to by the mint modules are part of
+    /// a session outcome signed by the federation and compare them against
+    /// the totals reported in the audit
+    ProofOfLiabilities {
+        /// Number of most recent sessions to search for the commitment
+        #[arg(long, default_value = "1000")]
+        max_sessions: u64,
+    },
+
     /// Download guardian config to back it up
     GuardianConfigBackup,
 
@@ -17,7 +17,7 @@
 mod visualize;
 
 use core::fmt;
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 use std::fmt::Debug;
 use std::io::{IsTerminal, Read, Write};
 use std::path::{Path, PathBuf};
@@ -35,8 +35,11 @@
 };
 use envs::SALT_FILE;
 use fedimint_aead::{encrypted_read, encrypted_write, get_encryption_key};
-use fedimint_api_client::api::{DynGlobalApi, FederationApiExt, FederationError};
+use fedimint_api_client::api::{
+    DynGlobalApi, FederationApiExt, FederationError, VERSION_THAT_INTRODUCED_GET_SESSION_STATUS_V2,
+};
 use fedimint_bip39::{Bip39RootSecretStrategy, Mnemonic};
+use fedimint_bitcoind::create_esplora_rpc;
 use fedimint_client::db::ApiSecretKey;
 use fedimint_client::module::meta::{FetchKind, LegacyMetaSource, MetaSource};
 use fedimint_client::module::module::init::ClientModuleInit;
@@ -46,12 +49,15 @@
 use fedimint_connectors::ConnectorRegistry;
 use fedimint_core::base32::FEDIMINT_PREFIX;
 use fedimint_core::config::{FederationId, FederationIdPrefix};
-use fedimint_core::core::ModuleInstanceId;
+use fedimint_core::core::{DynModuleConsensusItem, ModuleInstanceId};
 use fedimint_core::db::{Database, DatabaseValue, IDatabaseTransactionOpsCoreTyped as _};
 use fedimint_core::encoding::Decodable;
+use fedimint_core::epoch::ConsensusItem;
 use fedimint_core::invite_code::InviteCode;
+use fedimint_core::module::liabilities::LiabilityRoot;
 use fedimint_core::module::registry::ModuleRegistry;
 use fedimint_core::module::{ApiAuth, ApiRequestErased};
+use fedimint_core::session_outcome::SessionStatus;
 use fedimint_core::setup_code::PeerSetupCode;
 use fedimint_core::transaction::Transaction;
 use fedimint_core::util::{SafeUrl, backoff_util, handle_version_hash_command, retry};
@@ -61,6 +67,7 @@
 use fedimint_ln_client::LightningClientInit;
 use fedimint_logging::{LOG_CLIENT, TracingSetup};
 use fedimint_meta_client::{MetaClientInit, MetaModuleMetaSourceWithFallback};
+use fedimint_mint_client::common::endpoint_constants::LIABILITY_ROOT_ENDPOINT;
 use fedimint_mint_client::{MintClientInit, MintClientModule, OOBNotes};
 use fedimint_wallet_client::api::WalletFederationApi;
 use fedimint_wallet_client::{WalletClientInit, WalletClientModule};
@@ -787,6 +794,173 @@ async fn handle_command(&mut self, cli: Opts) -> CliOutputResult {
                     serde_json::to_value(audit).map_err_cli_msg("invalid response")?,
                 ))
             }
+            Command::Admin(AdminCmd::ProofOfReserves { esplora }) => {
+                let client = self.client_open(&cli).await?;
+
+                let audit = cli
+                    .admin_client(
+                        &client.get_peer_urls().await,
+                        client.api_secret().as_deref(),
+                    )
+                    .await?
+                    .audit(cli.auth()?)
+                    .await?;
+
+                // The wallet modules hold the reserves while every other module, like the
+                // mint and lightning modules, only holds liabilities.
+                let liabilities_msat = -audit
+                    .module_summaries
+                    .values()
+                    .filter(|summary| {
+                        summary.kind != fedimint_wallet_client::KIND.as_str()
+                            && summary.kind != fedimint_walletv2_client::common::KIND.as_str()
+                    })
+                    .map(|summary| summary.net_assets)
+                    .sum::<i64>();
+
+                // We only use descriptors that we verified against our config, hence we do
+                // not have to trust the federation to report its reserves.
+                let mut scripts = BTreeSet::new();
+                let mut unclaimed = Vec::new();
+
+                if let Ok(wallet) = client.get_first_module::<WalletClientModule>() {
+                    for descriptor in wallet.watch_only_descriptors().await.map_err_cli()? {
+                        scripts.insert(wallet.tweaked_script(&descriptor.tweak));
+                    }
+                }
+
+                if let Ok(wallet) =
+                    client.get_first_module::<fedimint_walletv2_client::WalletClientModule>()
+                {
+                    let descriptors = wallet.watch_only_descriptors().await.map_err_cli()?;
+
+                    for descriptor in &descriptors.tweaked {
+                        scripts.insert(wallet.tweaked_script(&descriptor.tweak));
+                    }
+
+                    unclaimed = descriptors.unclaimed;
+                }
+
+                let esplora = create_esplora_rpc(&esplora).map_err_cli()?;
+
+                let mut transactions = BTreeMap::new();
+
+                for script in &scripts {
+                    for tx in esplora.get_script_history(script).await.map_err_cli()? {
+                        transactions.insert(tx.compute_txid(), tx);
+                    }
+                }
+
+                // The history of a script contains the transactions spending its outputs as
+                // well, hence every output we own that is not spent by one of them is unspent.
+                let spent = transactions
+                    .values()
+                    .flat_map(|tx| tx.input.iter().map(|input| input.previous_output))
+                    .collect::<BTreeSet<bitcoin::OutPoint>>();
+
+                let reserves = transactions
+                    .iter()
+                    .flat_map(|(txid, tx)| {
+                        (0..)
+                            .zip(&tx.output)
+                            .map(|(vout, tx_out)| (bitcoin::OutPoint::new(*txid, vout), tx_out))
+                    })
+                    .filter(|(outpoint, tx_out)| {
+                        scripts.contains(&tx_out.script_pubkey) && !spent.contains(outpoint)
+                    })
+                    .map(|(_, tx_out)| tx_out.value)
+                    .sum::<bitcoin::Amount>();
+
+                // Unclaimed deposits cannot be verified against our config and no ecash has
+                // been issued for them yet, hence they are neither reserves nor liabilities.
+                let unclaimed_deposits = unclaimed
+                    .iter()
+                    .map(|deposit| deposit.value)
+                    .sum::<bitcoin::Amount>();
+
+                let reserves_msat =
+                    i64::try_from(reserves.to_sat() * 1000).map_err_cli_msg("reserves overflow")?;
+
+                Ok(CliOutput::Raw(json!({
+                    "onchain_reserves_sat": reserves.to_sat(),
+                    "unclaimed_deposits_sat": unclaimed_deposits.to_sat(),
+                    "liabilities_msat": liabilities_msat,
+                    "surplus_msat": reserves_msat - liabilities_msat,
+                    "solvent": reserves_msat >= liabilities_msat,
+                })))
+            }
+            Command::Admin(AdminCmd::ProofOfLiabilities { max_sessions }) => {
+                let client = self.client_open(&cli).await?;
+
+                let audit = cli
+                    .admin_client(
+                        &client.get_peer_urls().await,
+                        client.api_secret().as_deref(),
+                    )
+                    .await?
+                    .audit(cli.auth()?)
+                    .await?;
+
+                let mut modules = Vec::new();
+
+                for (module_id, summary) in &audit.module_summaries {
+                    if summary.kind != fedimint_mint_client::KIND.as_str()
+                        && summary.kind != fedimint_mintv2_client::common::KIND.as_str()
+                    {
+                        continue;
+                    }
+
+                    let root = client
+                        .api()
+                        .with_module(*module_id)
+                        .request_current_consensus::<Option<LiabilityRoot>>(
+                            LIABILITY_ROOT_ENDPOINT.to_string(),
+                            ApiRequestErased::default(),
+                        )
+                        .await?;
+
+                    let Some(root) = root else {
+                        modules.push(json!({
+                            "module_id": module_id,
+                            "kind": summary.kind,
+                            "committed": false,
+                            "audit_liabilities_msat": -summary.net_assets,
+                            "consistent": false,
+                        }));
+
+                        continue;
+                    };
+
+                    let outstanding = root
+                        .outstanding()
+                        .map_err_cli_msg("Outstanding amount overflowed")?;
+
+                    let signed_session =
+                        find_liability_root_session(&client, *module_id, &root, max_sessions)
+                            .await?;
+
+                    // The audit is taken after the root was committed, so it
+                    // only matches if there was no issuance or redemption since
+                    let consistent = root.is_well_formed()
+                        && signed_session.is_some()
+                        && outstanding.msats as i64 == -summary.net_assets;
+
+                    modules.push(json!({
+                        "module_id": module_id,
+                        "kind": summary.kind,
+                        "committed": true,
+                        "well_formed": root.is_well_formed(),
+                        "signed_session": signed_session,
+                        "events": root.events,
+                        "tiers": root.tiers.len(),
+                        "outstanding_msat": outstanding.msats,
+                        "audit_liabilities_msat": -summary.net_assets,
+                        "consistent": consistent,
+                    }));
+                }
+
+                Ok(CliOutput::Raw(json!({ "modules": modules })))
+            }
             Command::Admin(AdminCmd::Status) => {
                 let client = self.client_open(&cli).await?;
 
@@ -1444,6 +1618,75 @@ async fn handle_admin_setup_command(
     }
 }
 
+/// Searches the most recent session outcomes signed by the federation for the
+/// consensus item with which the given mint module committed to its liability
+/// root and returns the index of that session.
+async fn find_liability_root_session(
+    client: &Client,
+    module_id: ModuleInstanceId,
+    root: &LiabilityRoot,
+    max_sessions: u64,
+) -> CliResult<Option<u64>> {
+    let core_api_version = client.core_api_version().await;
+
+    if core_api_version < VERSION_THAT_INTRODUCED_GET_SESSION_STATUS_V2 {
+        return Err(CliError {
+            error: "Federation does not serve signed session outcomes".to_string(),
+        });
+    }
+
+    let broadcast_public_keys = client
+        .config()
+        .await
+        .global
+        .broadcast_public_keys
+        .ok_or_cli_msg("Federation does not publish the keys its sessions are signed with")?;
+
+    let commits_root = |item: &DynModuleConsensusItem| {
+        item.module_instance_id() == module_id
+            && (matches!(
+                item.as_any().downcast_ref(),
+                Some(fedimint_mint_client::MintConsensusItem::LiabilityRoot(committed))
+                    if committed == root
+            ) || matches!(
+                item.as_any().downcast_ref(),
+                Some(fedimint_mintv2_client::common::MintConsensusItem::LiabilityRoot(committed))
+                    if committed == root
+            ))
+    };
+
+    let decoders = client.decoders().clone().with_fallback();
+    let session_count = client.api().session_count().await?;
+
+    for session_idx in (session_count.saturating_sub(max_sessions)..session_count).rev() {
+        // Only complete sessions are signed, the signatures are verified
+        // against the broadcast public keys before the outcome is returned
+        let SessionStatus::Complete(outcome) = client
+            .api()
+            .get_session_status(
+                session_idx,
+                &decoders,
+                core_api_version,
+                Some(&broadcast_public_keys),
+            )
+            .await
+            .map_err_cli()?
+        else {
+            continue;
+        };
+
+        if outcome
+            .items
+            .iter()
+            .any(|item| matches!(&item.item, ConsensusItem::Module(item) if commits_root(item)))
+        {
+            return Ok(Some(session_idx));
+        }
+    }
+
+    Ok(None)
+}
+
 async fn log_expiration_notice(client: &Client) {
     client.get_meta_expiration_timestamp().await;
     if let Some(expiration_time) = client.get_meta_expiration_timestamp().await {
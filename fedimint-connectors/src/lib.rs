@@ -37,6 +37,23 @@
     dyn Fn() -> Pin<Box<dyn Future<Output = anyhow::Result<DynConnector>> + Send>> + Send + Sync,
 >;
 
+/// Wraps every [`Connector`] created by a [`ConnectorRegistry`], see
+/// [`ConnectorRegistryBuilder::with_connector_wrapper`]
+#[derive(Clone)]
+pub struct ConnectorWrapper(Arc<dyn Fn(DynConnector) -> DynConnector + Send + Sync>);
+
+impl ConnectorWrapper {
+    pub fn new(wrapper: impl Fn(DynConnector) -> DynConnector + Send + Sync + 'static) -> Self {
+        Self(Arc::new(wrapper))
+    }
+}
+
+impl Debug for ConnectorWrapper {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ConnectorWrapper")
+    }
+}
+
 /// Builder for [`ConnectorRegistry`]
 ///
 /// See [`ConnectorRegistry::build_from_client_env`] and similar
@@ -65,6 +82,9 @@ pub struct ConnectorRegistryBuilder {
 
     // Enable HTTP
     http_enable: bool,
+
+    /// Wraps the connectors, e.g. to inject faults in tests
+    connector_wrapper: Option<ConnectorWrapper>,
 }
 
 impl ConnectorRegistryBuilder {
@@ -83,8 +103,10 @@ pub async fn bind(self) -> anyhow::Result<ConnectorRegistry> {
         let builder_ws = self.clone();
         let ws_connector_init = Arc::new(move || {
             let builder = builder_ws.clone();
-            Box::pin(async move { builder.build_ws_connector().await })
-                as Pin<Box<dyn Future<Output = anyhow::Result<DynConnector>> + Send>>
+            Box::pin(async move {
+                let connector = builder.build_ws_connector().await?;
+                Ok(builder.wrap_connector(connector))
+            }) as Pin<Box<dyn Future<Output = anyhow::Result<DynConnector>> + Send>>
         });
         connectors_lazy.insert("ws".into(), (ws_connector_init.clone(), OnceCell::new()));
         connectors_lazy.insert("wss".into(), (ws_connector_init.clone(), OnceCell::new()));
@@ -98,7 +120,10 @@ pub async fn bind(self) -> anyhow::Result<ConnectorRegistry> {
                 Arc::new(move || {
                     let builder = builder_iroh.clone();
                     let path_change = path_change_iroh.clone();
-                    Box::pin(async move { builder.build_iroh_connector(path_change).await })
+                    Box::pin(async move {
+                        let connector = builder.build_iroh_connector(path_change).await?;
+                        Ok(builder.wrap_connector(connector))
+                    })
                         as Pin<Box<dyn Future<Output = anyhow::Result<DynConnector>> + Send>>
                 }),
                 OnceCell::new(),
@@ -108,8 +133,10 @@ pub async fn bind(self) -> anyhow::Result<ConnectorRegistry> {
         let builder_http = self.clone();
         let http_connector_init = Arc::new(move || {
             let builder = builder_http.clone();
-            Box::pin(async move { builder.build_http_connector() })
-                as Pin<Box<dyn Future<Output = anyhow::Result<DynConnector>> + Send>>
+            Box::pin(async move {
+                let connector = builder.build_http_connector()?;
+                Ok(builder.wrap_connector(connector))
+            }) as Pin<Box<dyn Future<Output = anyhow::Result<DynConnector>> + Send>>
         });
 
         connectors_lazy.insert(
@@ -177,6 +204,21 @@ pub fn build_http_connector(&self) -> anyhow::Result<DynConnector> {
         Ok(Arc::new(crate::http::HttpConnector::default()) as DynConnector)
     }
 
+    fn wrap_connector(&self, connector: DynConnector) -> DynConnector {
+        match &self.connector_wrapper {
+            Some(wrapper) => (wrapper.0)(connector),
+            None => connector,
+        }
+    }
+
+    /// Wraps every connector of the registry, e.g. to inject faults in tests
+    pub fn with_connector_wrapper(self, wrapper: ConnectorWrapper) -> Self {
+        Self {
+            connector_wrapper: Some(wrapper),
+            ..self
+        }
+    }
+
     pub fn iroh_pkarr_dht(self, enable: bool) -> Self {
         Self {
             iroh_pkarr_dht: enable,
@@ -284,6 +326,7 @@ pub fn build_from_client_defaults() -> ConnectorRegistryBuilder {
             http_enable: true,
 
             connection_overrides: BTreeMap::default(),
+            connector_wrapper: None,
         }
     }
 
@@ -300,6 +343,7 @@ pub fn build_from_server_defaults() -> ConnectorRegistryBuilder {
             http_enable: false,
 
             connection_overrides: BTreeMap::default(),
+            connector_wrapper: None,
         }
     }
 
@@ -316,6 +360,7 @@ pub fn build_from_testing_defaults() -> ConnectorRegistryBuilder {
             http_enable: true,
 
             connection_overrides: BTreeMap::default(),
+            connector_wrapper: None,
         }
     }
 
@@ -66,6 +66,13 @@ impl MemDatabase {
     pub fn new() -> Self {
         Self::default()
     }
+
+    /// Returns an independent copy of the current state of the database
+    pub fn snapshot(&self) -> Self {
+        Self {
+            data: std::sync::RwLock::new(self.data.read().expect("Poisoned rwlock").clone()),
+        }
+    }
 }
 
 #[apply(async_trait_maybe_send!)]
@@ -1,6 +1,8 @@
 use super::MemDatabase;
 use crate::core::ModuleInstanceId;
-use crate::db::{Database, IRawDatabaseExt};
+use crate::db::{
+    Database, IDatabaseTransactionOpsCore, IRawDatabase, IRawDatabaseExt, IRawDatabaseTransaction,
+};
 
 fn database() -> Database {
     MemDatabase::new().into()
@@ -80,3 +82,23 @@ async fn test_module_dbtx() {
 async fn test_module_db() {
     fedimint_core::db::verify_module_db(database(), module_database(1)).await;
 }
+
+#[test_log::test(tokio::test)]
+async fn test_snapshot_is_independent() {
+    let mem_db = MemDatabase::new();
+
+    let mut dbtx = mem_db.begin_transaction().await;
+    dbtx.raw_insert_bytes(&[1], &[1]).await.unwrap();
+    dbtx.commit_tx().await.unwrap();
+
+    let snapshot = mem_db.snapshot();
+
+    let mut dbtx = mem_db.begin_transaction().await;
+    dbtx.raw_insert_bytes(&[1], &[2]).await.unwrap();
+    dbtx.raw_insert_bytes(&[2], &[2]).await.unwrap();
+    dbtx.commit_tx().await.unwrap();
+
+    let mut dbtx = snapshot.begin_transaction().await;
+    assert_eq!(dbtx.raw_get_bytes(&[1]).await.unwrap(), Some(vec![1]));
+    assert_eq!(dbtx.raw_get_bytes(&[2]).await.unwrap(), None);
+}
@@ -28,6 +28,22 @@
 pub const FM_ENABLE_MODULE_WALLET_ENV: &str = "FM_ENABLE_MODULE_WALLET";
 pub const FM_ENABLE_MODULE_WALLETV2_ENV: &str = "FM_ENABLE_MODULE_WALLETV2";
 
+/// Generate a WalletV2 config that uses a taproot descriptor with threshold
+/// Schnorr signatures instead of a P2WSH multisig
+pub const FM_WALLETV2_TAPROOT_ENV: &str = "FM_WALLETV2_TAPROOT";
+
+/// Generate a WalletV2 config that batches peg-outs over the given number of
+/// blocks instead of sending every peg-out in its own transaction
+pub const FM_WALLETV2_PEGOUT_BATCH_WINDOW_ENV: &str = "FM_WALLETV2_PEGOUT_BATCH_WINDOW";
+
+/// Maximum number of peg-outs in a single batch of a WalletV2 config that
+/// batches peg-outs
+pub const FM_WALLETV2_PEGOUT_BATCH_MAX_PEGOUTS_ENV: &str = "FM_WALLETV2_PEGOUT_BATCH_MAX_PEGOUTS";
+
+/// Generate a WalletV2 config that bumps the fee of federation transactions
+/// which remain unconfirmed for the given number of blocks
+pub const FM_WALLETV2_FEE_BUMP_DELAY_ENV: &str = "FM_WALLETV2_FEE_BUMP_DELAY";
+
 /// Disable mint base fees for testing and development environments
 pub const FM_DISABLE_BASE_FEES_ENV: &str = "FM_DISABLE_BASE_FEES";
 
@@ -7,8 +7,8 @@
 use serde::{Deserialize, Serialize};
 
 use crate::db::{
-    DatabaseKey, DatabaseLookup, DatabaseRecord, DatabaseTransaction,
-    IDatabaseTransactionOpsCoreTyped,
+    DatabaseKey, DatabaseKeyPrefix, DatabaseLookup, DatabaseRecord, DatabaseTransaction,
+    DatabaseValue, IDatabaseTransactionOpsCoreTyped,
 };
 use crate::task::{MaybeSend, MaybeSync};
 
@@ -18,11 +18,16 @@ pub struct Audit {
 }
 
 impl Audit {
+    pub fn items(&self) -> &[AuditItem] {
+        &self.items
+    }
+
     pub fn net_assets(&self) -> Option<AuditItem> {
         Some(AuditItem {
             name: "Net assets (sats)".to_string(),
             milli_sat: calculate_net_assets(self.items.iter())?,
             module_instance_id: None,
+            record: None,
         })
     }
 
@@ -42,11 +47,16 @@ pub async fn add_items<KP, F>(
             .await
             .map(|(key, value)| {
                 let name = format!("{key:?}");
+                let record = (
+                    DatabaseKeyPrefix::to_bytes(&key),
+                    DatabaseValue::to_bytes(&value),
+                );
                 let milli_sat = to_milli_sat(key, value);
                 AuditItem {
                     name,
                     milli_sat,
                     module_instance_id: Some(module_instance_id),
+                    record: Some(record),
                 }
             })
             .collect::<Vec<AuditItem>>()
@@ -73,6 +83,9 @@ pub struct AuditItem {
     pub name: String,
     pub milli_sat: i64,
     pub module_instance_id: Option<ModuleInstanceId>,
+    /// The encoded key and value of the module database record the item was
+    /// derived from
+    pub record: Option<(Vec<u8>, Vec<u8>)>,
 }
 
 impl Display for AuditItem {
@@ -157,6 +170,7 @@ fn create_empty_module_placeholder(module_instance_id: ModuleInstanceId) -> Audi
         name: "Module placeholder".to_string(),
         milli_sat: 0,
         module_instance_id: Some(module_instance_id),
+        record: None,
     }
 }
 
@@ -168,36 +182,43 @@ fn creates_audit_summary_from_audit() {
                 name: "ContractKey(...)".to_string(),
                 milli_sat: -101_000,
                 module_instance_id: Some(0),
+                record: None,
             },
             AuditItem {
                 name: "IssuanceTotal".to_string(),
                 milli_sat: -50_100_000,
                 module_instance_id: Some(1),
+                record: None,
             },
             AuditItem {
                 name: "Redemption(...)".to_string(),
                 milli_sat: 101_000,
                 module_instance_id: Some(1),
+                record: None,
             },
             AuditItem {
                 name: "RedemptionTotal".to_string(),
                 milli_sat: 100_000,
                 module_instance_id: Some(1),
+                record: None,
             },
             AuditItem {
                 name: "UTXOKey(...)".to_string(),
                 milli_sat: 20_000_000,
                 module_instance_id: Some(2),
+                record: None,
             },
             AuditItem {
                 name: "UTXOKey(...)".to_string(),
                 milli_sat: 10_000_000,
                 module_instance_id: Some(2),
+                record: None,
             },
             AuditItem {
                 name: "UTXOKey(...)".to_string(),
                 milli_sat: 20_000_000,
                 module_instance_id: Some(2),
+                record: None,
             },
         ],
     };
@@ -0,0 +1,342 @@
+//! Proof of liabilities for ecash modules
+//!
+//! For every denomination a mint appends a leaf for every note it issues to an
+//! append-only forest of perfect Merkle sum trees, where every node commits to
+//! the hashes and the summed amounts of its children. Since a spent note can
+//! not be linked to its issuance the mint only counts the redeemed notes of
+//! every denomination, such that the outstanding ecash of a denomination is
+//! the number of notes issued minus the number of notes redeemed times the
+//! denomination.
+//!
+//! The guardians vote on the resulting [`LiabilityRoot`] with a consensus item
+//! which every guardian checks against its own state. Hence an accepted root
+//! is part of a session outcome signed by the federation. A user can request
+//! an inclusion proof for the blind nonce of one of their notes to check that
+//! it has been counted towards the committed liabilities.
+//!
+//! The forests are stored by the modules themselves, this module only defines
+//! the hashing scheme and the positions of the nodes required for appending
+//! leaves, computing the peaks and constructing inclusion proofs.
+
+use std::collections::BTreeMap;
+
+use bitcoin::hashes::{Hash as BitcoinHash, HashEngine, sha256};
+use serde::{Deserialize, Serialize};
+
+use crate::Amount;
+use crate::encoding::{Decodable, Encodable};
+
+/// A node of a Merkle sum tree committing to the amounts of all leaves below
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Encodable, Decodable, Serialize, Deserialize)]
+pub struct LiabilityNode {
+    pub hash: sha256::Hash,
+    pub sum: Amount,
+}
+
+impl LiabilityNode {
+    pub fn leaf<T: Encodable>(leaf: &T, amount: Amount) -> Self {
+        let mut engine = sha256::Hash::engine();
+
+        engine.input(&[0]);
+        engine.input(&leaf.consensus_encode_to_vec());
+        engine.input(&amount.consensus_encode_to_vec());
+
+        Self {
+            hash: sha256::Hash::from_engine(engine),
+            sum: amount,
+        }
+    }
+
+    /// Returns `None` if the sum of the children overflows.
+    pub fn parent(left: &Self, right: &Self) -> Option<Self> {
+        let mut engine = sha256::Hash::engine();
+
+        engine.input(&[1]);
+        engine.input(&left.consensus_encode_to_vec());
+        engine.input(&right.consensus_encode_to_vec());
+
+        Some(Self {
+            hash: sha256::Hash::from_engine(engine),
+            sum: left.sum.checked_add(right.sum)?,
+        })
+    }
+}
+
+/// The position of a node in the forest, leaves have a height of zero
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Encodable, Decodable, Serialize, Deserialize)]
+pub struct NodePosition {
+    pub height: u8,
+    pub index: u64,
+}
+
+impl NodePosition {
+    pub fn leaf(index: u64) -> Self {
+        Self { height: 0, index }
+    }
+
+    pub fn sibling(&self) -> Self {
+        Self {
+            height: self.height,
+            index: self.index ^ 1,
+        }
+    }
+
+    pub fn parent(&self) -> Self {
+        Self {
+            height: self.height + 1,
+            index: self.index >> 1,
+        }
+    }
+
+    /// Whether the node is a right child, in which case its left sibling has
+    /// been completed before and the parent can be computed.
+    pub fn is_right_child(&self) -> bool {
+        self.index & 1 == 1
+    }
+
+    fn contains_leaf(&self, index: u64) -> bool {
+        index >> self.height == self.index
+    }
+}
+
+/// The positions of the roots of the perfect trees in a forest with the given
+/// number of leaves, from left to right.
+pub fn peak_positions(leaves: u64) -> Vec<NodePosition> {
+    let mut offset = 0;
+
+    (0..64)
+        .rev()
+        .filter(|height| leaves & (1 << height) != 0)
+        .map(|height| {
+            let position = NodePosition {
+                height,
+                index: offset >> height,
+            };
+
+            offset += 1 << height;
+
+            position
+        })
+        .collect()
+}
+
+/// The positions of the siblings on the path from the given leaf to its peak.
+pub fn proof_positions(index: u64, leaves: u64) -> Option<Vec<NodePosition>> {
+    let peak = peak_positions(leaves)
+        .into_iter()
+        .find(|peak| peak.contains_leaf(index))?;
+
+    Some(
+        (0..peak.height)
+            .map(|height| {
+                NodePosition {
+                    height,
+                    index: index >> height,
+                }
+                .sibling()
+            })
+            .collect(),
+    )
+}
+
+/// The liabilities of a mint in a single denomination
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Encodable, Decodable, Serialize, Deserialize)]
+pub struct TierLiabilities {
+    /// The number of notes issued so far, which is the number of leaves
+    pub issued: u64,
+    /// The number of notes redeemed so far
+    pub redeemed: u64,
+    /// The roots of the perfect trees in the forest, from left to right
+    pub peaks: Vec<LiabilityNode>,
+}
+
+/// The commitment of a mint to its liabilities
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Encodable, Decodable, Serialize, Deserialize)]
+pub struct LiabilityRoot {
+    /// The number of issuances and redemptions accounted for, which orders the
+    /// roots of a mint
+    pub events: u64,
+    /// The liabilities of every denomination the mint has issued notes in
+    pub tiers: BTreeMap<Amount, TierLiabilities>,
+}
+
+impl LiabilityRoot {
+    /// Checks that the peaks of every denomination sum up to the notes issued
+    /// in it and that no more notes have been redeemed than issued, such that
+    /// [`Self::outstanding`] is backed by the forests.
+    pub fn is_well_formed(&self) -> bool {
+        self.tiers.iter().all(|(denomination, tier)| {
+            let positions = peak_positions(tier.issued);
+
+            tier.redeemed <= tier.issued
+                && positions.len() == tier.peaks.len()
+                && positions.iter().zip(&tier.peaks).all(|(position, peak)| {
+                    denomination
+                        .msats
+                        .checked_mul(1 << position.height)
+                        .is_some_and(|sum| peak.sum == Amount::from_msats(sum))
+                })
+        })
+    }
+
+    /// The amount of ecash that has been issued but not redeemed yet, which
+    /// is what the mint reports in the audit. Returns `None` on overflow.
+    pub fn outstanding(&self) -> Option<Amount> {
+        self.tiers
+            .iter()
+            .try_fold(Amount::ZERO, |sum, (denomination, tier)| {
+                let notes = tier.issued.checked_sub(tier.redeemed)?;
+
+                sum.checked_add(Amount::from_msats(denomination.msats.checked_mul(notes)?))
+            })
+    }
+}
+
+/// Proves that a leaf is included in the forest of its denomination committed
+/// to by a [`LiabilityRoot`]
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Encodable, Decodable, Serialize, Deserialize)]
+pub struct LiabilityProof {
+    pub index: u64,
+    /// The siblings on the path from the leaf to its peak, bottom up
+    pub siblings: Vec<LiabilityNode>,
+}
+
+impl LiabilityProof {
+    pub fn verify(&self, root: &LiabilityRoot, leaf: &LiabilityNode) -> bool {
+        let Some(tier) = root.tiers.get(&leaf.sum) else {
+            return false;
+        };
+
+        let peaks = peak_positions(tier.issued);
+
+        if peaks.len() != tier.peaks.len() {
+            return false;
+        }
+
+        let Some((position, peak)) = peaks
+            .iter()
+            .zip(&tier.peaks)
+            .find(|(position, _)| position.contains_leaf(self.index))
+        else {
+            return false;
+        };
+
+        if self.siblings.len() != position.height as usize {
+            return false;
+        }
+
+        let mut position = NodePosition::leaf(self.index);
+        let mut node = *leaf;
+
+        for sibling in &self.siblings {
+            let parent = if position.is_right_child() {
+                LiabilityNode::parent(sibling, &node)
+            } else {
+                LiabilityNode::parent(&node, sibling)
+            };
+
+            let Some(parent) = parent else {
+                return false;
+            };
+
+            node = parent;
+            position = position.parent();
+        }
+
+        node == *peak
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    const DENOMINATION: Amount = Amount::from_msats(1024);
+
+    fn append(forest: &mut HashMap<NodePosition, LiabilityNode>, index: u64) {
+        let mut position = NodePosition::leaf(index);
+        let mut node = LiabilityNode::leaf(&index, DENOMINATION);
+
+        forest.insert(position, node);
+
+        while position.is_right_child() {
+            node = LiabilityNode::parent(&forest[&position.sibling()], &node).unwrap();
+            position = position.parent();
+
+            forest.insert(position, node);
+        }
+    }
+
+    #[test]
+    fn test_inclusion_proofs() {
+        let mut forest = HashMap::new();
+
+        for issued in 1..=33 {
+            append(&mut forest, issued - 1);
+
+            let tier = TierLiabilities {
+                issued,
+                redeemed: issued / 2,
+                peaks: peak_positions(issued)
+                    .iter()
+                    .map(|position| forest[position])
+                    .collect(),
+            };
+
+            let root = LiabilityRoot {
+                events: issued + issued / 2,
+                tiers: BTreeMap::from([(DENOMINATION, tier.clone())]),
+            };
+
+            assert!(root.is_well_formed());
+
+            assert_eq!(
+                root.outstanding(),
+                Some(DENOMINATION * (issued - issued / 2))
+            );
+
+            for index in 0..issued {
+                let proof = LiabilityProof {
+                    index,
+                    siblings: proof_positions(index, issued)
+                        .unwrap()
+                        .iter()
+                        .map(|position| forest[position])
+                        .collect(),
+                };
+
+                let leaf = LiabilityNode::leaf(&index, DENOMINATION);
+
+                assert!(proof.verify(&root, &leaf));
+
+                let inflated = LiabilityNode::leaf(&index, DENOMINATION * 2);
+
+                assert!(!proof.verify(&root, &inflated));
+            }
+
+            assert_eq!(proof_positions(issued, issued), None);
+
+            let overspent = LiabilityRoot {
+                events: root.events,
+                tiers: BTreeMap::from([(
+                    DENOMINATION,
+                    TierLiabilities {
+                        redeemed: issued + 1,
+                        ..tier.clone()
+                    },
+                )]),
+            };
+
+            assert!(!overspent.is_well_formed());
+
+            let mislabeled = LiabilityRoot {
+                events: root.events,
+                tiers: BTreeMap::from([(DENOMINATION * 2, tier)]),
+            };
+
+            assert!(!mislabeled.is_well_formed());
+        }
+    }
+}
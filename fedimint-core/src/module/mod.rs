@@ -17,6 +17,7 @@
 //! * `ClientModuleInit` (in `fedimint_client`)
 //! * `ClientModule` (in `fedimint_client`)
 pub mod audit;
+pub mod liabilities;
 pub mod registry;
 
 use std::collections::{BTreeMap, BTreeSet};
@@ -54,7 +54,15 @@ pub fn sign<C: secp256k1::Signing>(
         key: &secp256k1::Keypair,
     ) -> SignedApiAnnouncement {
         let msg = Message::from_digest(*self.tagged_hash().as_ref());
-        let signature = ctx.sign_schnorr(&msg, key);
+        self.with_signature(ctx.sign_schnorr(&msg, key))
+    }
+
+    /// Attaches a signature over [`Self::tagged_hash`] created elsewhere, for
+    /// example by a guardian signer
+    pub fn with_signature(
+        &self,
+        signature: secp256k1::schnorr::Signature,
+    ) -> SignedApiAnnouncement {
         SignedApiAnnouncement {
             api_announcement: self.clone(),
             signature,
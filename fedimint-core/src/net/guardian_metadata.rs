@@ -145,24 +145,37 @@ pub fn new(api_urls: Vec<SafeUrl>, pkarr_id_z32: String, timestamp_secs: u64) ->
         }
     }
 
+    /// The tagged hash of the JSON encoding, which the guardian signs
+    pub fn tagged_hash(&self) -> sha256::Hash {
+        compute_tagged_hash(&self.to_json_bytes())
+    }
+
     pub fn sign<C: secp256k1::Signing>(
         &self,
         ctx: &secp256k1::Secp256k1<C>,
         key: &secp256k1::Keypair,
     ) -> SignedGuardianMetadata {
-        // Serialize to JSON and compute tagged hash
-        let bytes = serde_json::to_vec(self).expect("JSON serialization should not fail");
-        let tagged_hash = compute_tagged_hash(&bytes);
+        let msg = Message::from_digest(*self.tagged_hash().as_ref());
 
-        let msg = Message::from_digest(*tagged_hash.as_ref());
-        let signature = ctx.sign_schnorr(&msg, key);
+        self.with_signature(ctx.sign_schnorr(&msg, key))
+    }
 
+    /// Attaches a signature over [`Self::tagged_hash`] created elsewhere, for
+    /// example by a guardian signer
+    pub fn with_signature(
+        &self,
+        signature: secp256k1::schnorr::Signature,
+    ) -> SignedGuardianMetadata {
         SignedGuardianMetadata {
-            bytes,
+            bytes: self.to_json_bytes(),
             value: self.clone(),
             signature,
         }
     }
+
+    fn to_json_bytes(&self) -> Vec<u8> {
+        serde_json::to_vec(self).expect("JSON serialization should not fail")
+    }
 }
 
 impl SignedGuardianMetadata {
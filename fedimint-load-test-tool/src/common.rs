@@ -160,6 +160,8 @@ pub async fn build_client(
     client_builder.with_module(MintClientInit);
     client_builder.with_module(LightningClientInit::default());
     client_builder.with_module(WalletClientInit::default());
+    client_builder.with_module(fedimint_lnv2_client::LightningClientInit::default());
+    client_builder.with_module(fedimint_walletv2_client::WalletClientInit);
     let client_secret = Client::load_or_generate_client_secret(&db).await?;
     let root_secret =
         RootSecret::StandardDoubleDerive(PlainRootSecretStrategy::to_root_secret(&client_secret));
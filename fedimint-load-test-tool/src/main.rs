@@ -44,7 +44,9 @@
 use crate::common::{
     build_client, do_spend_notes, get_invite_code_cli, remint_denomination, try_get_notes_cli,
 };
+use crate::scenario::{Scenario, run_scenario};
 pub mod common;
+pub mod scenario;
 
 #[derive(Parser, Clone)]
 #[command(version)]
@@ -113,6 +115,11 @@ enum Command {
     /// we can keep making the payments in a loop
     #[command()]
     LnCircularLoadTest(LnCircularLoadTestArgs),
+    /// Run a load test described by a scenario file, with populations of
+    /// users performing a mix of operations at a given rate. The number of
+    /// users is taken from the scenario file instead of --users.
+    #[command()]
+    Scenario(ScenarioArgs),
 }
 
 #[derive(Args, Clone)]
@@ -236,6 +243,30 @@ struct LnCircularLoadTestArgs {
     strategy: LnCircularStrategy,
 }
 
+#[derive(Args, Clone)]
+struct ScenarioArgs {
+    #[arg(long, help = "Scenario file in TOML format")]
+    scenario: PathBuf,
+
+    #[arg(
+        long,
+        help = "Federation invite code. If none given, we assume the client already has a config downloaded in DB"
+    )]
+    invite_code: Option<InviteCode>,
+
+    #[arg(
+        long,
+        help = "Notes for the test. If none and no funds on archive, will call fedimint-cli spend"
+    )]
+    initial_notes: Option<OOBNotes>,
+
+    #[arg(
+        long,
+        help = "Output with the scenario report, including latency percentiles per operation, in JSON format"
+    )]
+    report_json_output: Option<PathBuf>,
+}
+
 #[derive(Debug, Clone, Copy, ValueEnum)]
 enum LnCircularStrategy {
     /// The user will pay its own invoice
@@ -392,6 +423,19 @@ async fn main() -> anyhow::Result<()> {
             )
             .await?
         }
+        Command::Scenario(args) => {
+            let scenario = Scenario::load(&args.scenario).await?;
+            let invite_code = invite_code_or_fallback(args.invite_code).await;
+            run_scenario(
+                scenario,
+                opts.archive_dir,
+                invite_code,
+                args.initial_notes,
+                args.report_json_output,
+                event_sender.clone(),
+            )
+            .await?
+        }
     };
 
     let result = futures::future::join_all(futures).await;
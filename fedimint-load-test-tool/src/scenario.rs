@@ -0,0 +1,862 @@
+//! Declarative load test scenarios
+//!
+//! A scenario file describes one or more populations of users. Every user of a
+//! population performs operations drawn from the population's workload mix,
+//! one after the other, with exponentially distributed pauses such that the
+//! user on average performs `ops_per_minute` operations. Users join the test
+//! evenly spread over the ramp-up period and all of them stop once the
+//! scenario duration has elapsed.
+//!
+//! The latency of every operation is recorded and summarized per operation
+//! type into a [`ScenarioReport`] which can be exported as JSON and is
+//! compared against the previous report of the same scenario if an archive
+//! directory is given.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+use anyhow::{Context, bail, ensure};
+use devimint::cmd;
+use devimint::util::BitcoinCli;
+use fedimint_client::ClientHandleArc;
+use fedimint_client::backup::Metadata;
+use fedimint_core::Amount;
+use fedimint_core::bitcoin::{self, Address};
+use fedimint_core::invite_code::InviteCode;
+use fedimint_core::util::{BoxFuture, SafeUrl};
+use fedimint_lnv2_client::common::Bolt11InvoiceDescription;
+use fedimint_lnv2_client::{FinalReceiveOperationState, FinalSendOperationState};
+use fedimint_mint_client::{MintClientModule, OOBNotes};
+use fedimint_walletv2_client::FinalSendOperationState as FinalWalletSendOperationState;
+use futures::StreamExt;
+use rand::distributions::{Distribution, WeightedIndex};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+use tracing::{info, warn};
+
+use crate::common::{do_spend_notes, ldk_create_invoice, ldk_pay_invoice, reissue_notes};
+use crate::{
+    MetricEvent, get_coordinator_client, get_db_path, get_required_notes, get_user_client,
+    print_coordinator_notes, reissue_initial_notes,
+};
+
+/// How long to wait for a peg-in to be credited before it counts as failed
+const PEGIN_TIMEOUT: Duration = Duration::from_mins(5);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Scenario {
+    /// Identifies the scenario in reports and in the archive
+    pub name: String,
+    /// For how many seconds the users perform operations, including the
+    /// ramp-up
+    pub duration_secs: u64,
+    /// Over how many seconds the users join the test
+    #[serde(default)]
+    pub ramp_up_secs: u64,
+    /// Seed for the random pauses and workload mix, such that runs of the
+    /// same scenario issue the same sequence of operations
+    #[serde(default)]
+    pub seed: u64,
+    /// The gateway used for lnv2 payments. If none is given, the client
+    /// selects one. Invoices are created and paid by the LDK node of the
+    /// devimint gateway, so this should be a different gateway.
+    #[serde(default)]
+    pub lnv2_gateway: Option<SafeUrl>,
+    /// How many blocks to mine after a walletv2 peg-in
+    #[serde(default = "default_pegin_blocks")]
+    pub pegin_blocks: u64,
+    #[serde(rename = "population")]
+    pub populations: Vec<Population>,
+}
+
+fn default_pegin_blocks() -> u64 {
+    21
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Population {
+    pub name: String,
+    pub users: u16,
+    /// Average number of operations per minute of every user
+    pub ops_per_minute: f64,
+    /// The ecash every user is funded with before joining the test
+    pub initial_balance_msat: Amount,
+    /// The amount of ecash spent, reissued or sent over lightning by a
+    /// single operation
+    pub amount_msat: Amount,
+    /// The amount pegged in or out by a single onchain operation
+    #[serde(default)]
+    pub onchain_amount_sat: u64,
+    /// Relative weights of the operations performed by the users
+    pub mix: BTreeMap<Operation, u32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Operation {
+    /// Reissue notes spent by another user of the population, or by the user
+    /// itself if no such notes are available
+    Reissue,
+    /// Spend notes out-of-band to be reissued by another user of the
+    /// population
+    SpendOob,
+    /// Pay an invoice of the LDK node through an lnv2 gateway
+    Lnv2Send,
+    /// Receive a payment from the LDK node through an lnv2 gateway
+    Lnv2Receive,
+    /// Peg-in from the bitcoind wallet until the ecash has been issued
+    Walletv2Pegin,
+    /// Peg-out to the bitcoind wallet until the transaction has been
+    /// broadcast
+    Walletv2Pegout,
+    /// Upload an encrypted backup to the federation
+    Backup,
+}
+
+impl Scenario {
+    pub async fn load(path: &Path) -> anyhow::Result<Self> {
+        let content = tokio::fs::read_to_string(path)
+            .await
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+
+        let scenario: Self = toml::from_str(&content)
+            .with_context(|| format!("Failed to parse {}", path.display()))?;
+
+        scenario.validate()?;
+
+        Ok(scenario)
+    }
+
+    fn validate(&self) -> anyhow::Result<()> {
+        ensure!(self.duration_secs > 0, "duration_secs must be positive");
+        ensure!(
+            self.ramp_up_secs <= self.duration_secs,
+            "ramp_up_secs must not exceed duration_secs"
+        );
+        ensure!(
+            !self.populations.is_empty(),
+            "A scenario needs at least one population"
+        );
+
+        for population in &self.populations {
+            let name = &population.name;
+
+            ensure!(population.users > 0, "Population {name} has no users");
+            ensure!(
+                population.ops_per_minute > 0.0,
+                "ops_per_minute of population {name} must be positive"
+            );
+            ensure!(
+                population.mix.values().any(|weight| *weight > 0),
+                "Workload mix of population {name} is empty"
+            );
+
+            let onchain = [Operation::Walletv2Pegin, Operation::Walletv2Pegout];
+
+            if onchain.iter().any(|op| population.weight(*op) > 0) {
+                ensure!(
+                    population.onchain_amount_sat > 0,
+                    "Population {name} performs onchain operations but has no onchain_amount_sat"
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    fn total_users(&self) -> u64 {
+        self.populations.iter().map(|p| u64::from(p.users)).sum()
+    }
+}
+
+impl Population {
+    fn weight(&self, operation: Operation) -> u32 {
+        self.mix.get(&operation).copied().unwrap_or(0)
+    }
+}
+
+/// The outcome of a single operation
+#[derive(Debug)]
+struct Sample {
+    operation: Operation,
+    duration: Duration,
+    success: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScenarioReport {
+    pub scenario: Scenario,
+    pub timestamp_seconds: u64,
+    pub elapsed_ms: u128,
+    pub operations: BTreeMap<Operation, OperationReport>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OperationReport {
+    pub n: u64,
+    pub failures: u64,
+    /// Successful operations per second over the whole run
+    pub throughput: f64,
+    pub min_ms: u128,
+    pub avg_ms: u128,
+    pub p50_ms: u128,
+    pub p90_ms: u128,
+    pub p99_ms: u128,
+    pub max_ms: u128,
+}
+
+impl OperationReport {
+    /// Summarizes the latencies of the successful operations
+    fn new(samples: &[&Sample], elapsed: Duration) -> Self {
+        let mut latencies = samples
+            .iter()
+            .filter(|sample| sample.success)
+            .map(|sample| sample.duration)
+            .collect::<Vec<_>>();
+
+        latencies.sort();
+
+        let n = latencies.len();
+        let sum: Duration = latencies.iter().sum();
+
+        Self {
+            n: n as u64,
+            failures: (samples.len() - n) as u64,
+            throughput: n as f64 / elapsed.as_secs_f64(),
+            min_ms: latencies.first().copied().unwrap_or_default().as_millis(),
+            avg_ms: sum.checked_div(n as u32).unwrap_or_default().as_millis(),
+            p50_ms: percentile(&latencies, 50).as_millis(),
+            p90_ms: percentile(&latencies, 90).as_millis(),
+            p99_ms: percentile(&latencies, 99).as_millis(),
+            max_ms: latencies.last().copied().unwrap_or_default().as_millis(),
+        }
+    }
+}
+
+/// Nearest-rank percentile of sorted latencies
+fn percentile(sorted: &[Duration], p: usize) -> Duration {
+    if sorted.is_empty() {
+        return Duration::ZERO;
+    }
+
+    let rank = (sorted.len() * p).div_ceil(100).max(1);
+
+    sorted[rank - 1]
+}
+
+impl std::fmt::Display for ScenarioReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "Scenario {} finished in {:?}",
+            self.scenario.name,
+            Duration::from_millis(self.elapsed_ms as u64)
+        )?;
+
+        for (operation, report) in &self.operations {
+            writeln!(
+                f,
+                "{operation:?}: n {}, failures {}, {:.2} ops/s, p50 {}ms, p90 {}ms, p99 {}ms, min {}ms, avg {}ms, max {}ms",
+                report.n,
+                report.failures,
+                report.throughput,
+                report.p50_ms,
+                report.p90_ms,
+                report.p99_ms,
+                report.min_ms,
+                report.avg_ms,
+                report.max_ms,
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Notes spent out-of-band by the users of a population waiting to be
+/// reissued by another user
+type Inbox = Arc<Mutex<Vec<OOBNotes>>>;
+
+/// Funds the users of the scenario and returns a single future running the
+/// scenario to completion and writing its report
+pub async fn run_scenario(
+    scenario: Scenario,
+    archive_dir: Option<PathBuf>,
+    invite_code: Option<InviteCode>,
+    initial_notes: Option<OOBNotes>,
+    report_json_output: Option<PathBuf>,
+    event_sender: mpsc::UnboundedSender<MetricEvent>,
+) -> anyhow::Result<Vec<BoxFuture<'static, anyhow::Result<()>>>> {
+    let db_path = get_db_path(&archive_dir);
+    let (coordinator, invite_code) = get_coordinator_client(&db_path, &invite_code).await?;
+
+    let minimum_amount_required = scenario
+        .populations
+        .iter()
+        .map(|p| p.initial_balance_msat * u64::from(p.users))
+        .sum();
+
+    reissue_initial_notes(initial_notes, &coordinator, &event_sender).await?;
+    get_required_notes(&coordinator, minimum_amount_required, &event_sender).await?;
+    print_coordinator_notes(&coordinator).await?;
+
+    info!(
+        "Preparing {} users for scenario {}",
+        scenario.total_users(),
+        scenario.name
+    );
+
+    let mut users = vec![];
+    let mut user_index = 0;
+
+    for population in &scenario.populations {
+        let inbox = Inbox::default();
+
+        for _ in 0..population.users {
+            let (client, _) = get_user_client(user_index, &db_path, &invite_code).await?;
+
+            check_supported(&client, population)?;
+
+            let (_, notes) = do_spend_notes(&coordinator, population.initial_balance_msat).await?;
+
+            users.push(User {
+                prefix: format!("{} user {user_index}:", population.name),
+                index: user_index,
+                client,
+                notes,
+                population: population.clone(),
+                inbox: inbox.clone(),
+            });
+
+            user_index += 1;
+        }
+    }
+
+    let f: BoxFuture<_> = Box::pin(async move {
+        let (sample_sender, mut sample_receiver) = mpsc::unbounded_channel();
+
+        let start = fedimint_core::time::now();
+        let total_users = scenario.total_users();
+
+        info!("Starting scenario {}", scenario.name);
+
+        let tasks = users.into_iter().map(|user| {
+            // Spread the users evenly over the ramp-up period
+            let join_after = Duration::from_secs(scenario.ramp_up_secs)
+                .mul_f64(f64::from(user.index) / total_users as f64);
+
+            user.run(&scenario, start, join_after, &event_sender, &sample_sender)
+        });
+
+        let results = futures::future::join_all(tasks).await;
+
+        drop(sample_sender);
+
+        let elapsed = start.elapsed()?;
+
+        let mut samples = vec![];
+
+        while let Some(sample) = sample_receiver.recv().await {
+            samples.push(sample);
+        }
+
+        let report = ScenarioReport {
+            scenario,
+            timestamp_seconds: fedimint_core::time::duration_since_epoch().as_secs(),
+            elapsed_ms: elapsed.as_millis(),
+            operations: summarize(&samples, elapsed),
+        };
+
+        println!("{report}");
+
+        write_report(
+            &report,
+            archive_dir.as_deref(),
+            report_json_output.as_deref(),
+        )
+        .await?;
+
+        results.into_iter().collect::<anyhow::Result<Vec<()>>>()?;
+
+        Ok(())
+    });
+
+    Ok(vec![f])
+}
+
+/// Fails early if the federation lacks a module required by the workload mix
+fn check_supported(client: &ClientHandleArc, population: &Population) -> anyhow::Result<()> {
+    for (operation, weight) in &population.mix {
+        if *weight == 0 {
+            continue;
+        }
+
+        let supported = match operation {
+            Operation::Reissue | Operation::SpendOob | Operation::Backup => {
+                client.get_first_module::<MintClientModule>().is_ok()
+            }
+            Operation::Lnv2Send | Operation::Lnv2Receive => client
+                .get_first_module::<fedimint_lnv2_client::LightningClientModule>()
+                .is_ok(),
+            Operation::Walletv2Pegin | Operation::Walletv2Pegout => client
+                .get_first_module::<fedimint_walletv2_client::WalletClientModule>()
+                .is_ok(),
+        };
+
+        if !supported {
+            bail!(
+                "Population {} performs {operation:?} but the federation lacks the required module",
+                population.name
+            );
+        }
+    }
+
+    Ok(())
+}
+
+fn summarize(samples: &[Sample], elapsed: Duration) -> BTreeMap<Operation, OperationReport> {
+    let mut by_operation = BTreeMap::<_, Vec<_>>::new();
+
+    for sample in samples {
+        by_operation
+            .entry(sample.operation)
+            .or_default()
+            .push(sample);
+    }
+
+    by_operation
+        .into_iter()
+        .map(|(operation, samples)| (operation, OperationReport::new(&samples, elapsed)))
+        .collect()
+}
+
+/// Writes the report to the given output and to the archive, comparing it to
+/// the latest archived report of the same scenario
+async fn write_report(
+    report: &ScenarioReport,
+    archive_dir: Option<&Path>,
+    report_json_output: Option<&Path>,
+) -> anyhow::Result<()> {
+    let report_json = serde_json::to_string_pretty(report).expect("to be serializable");
+
+    if let Some(report_json_output) = report_json_output {
+        tokio::fs::write(report_json_output, &report_json)
+            .await
+            .with_context(|| format!("Failed to write {}", report_json_output.display()))?;
+    }
+
+    if let Some(archive_dir) = archive_dir {
+        let archive_reports = archive_dir.join("scenarios").join(&report.scenario.name);
+
+        tokio::fs::create_dir_all(&archive_reports).await?;
+
+        match latest_report(&archive_reports).await {
+            Ok(Some(previous)) => print_comparison(report, &previous),
+            Ok(None) => {}
+            Err(e) => warn!("Failed to load previous report: {e:?}"),
+        }
+
+        tokio::fs::write(
+            archive_reports.join(format!("{}.json", report.timestamp_seconds)),
+            &report_json,
+        )
+        .await?;
+    }
+
+    Ok(())
+}
+
+async fn latest_report(archive_reports: &Path) -> anyhow::Result<Option<ScenarioReport>> {
+    let mut latest = None;
+
+    let mut entries = tokio::fs::read_dir(archive_reports).await?;
+
+    while let Some(entry) = entries.next_entry().await? {
+        let modified = entry.metadata().await?.modified()?;
+
+        if latest
+            .as_ref()
+            .is_none_or(|(latest_modified, _)| *latest_modified < modified)
+        {
+            latest = Some((modified, entry.path()));
+        }
+    }
+
+    let Some((_, path)) = latest else {
+        return Ok(None);
+    };
+
+    let content = tokio::fs::read_to_string(&path).await?;
+
+    Ok(Some(serde_json::from_str(&content)?))
+}
+
+/// Relative change of a latency compared to the previous run
+fn to_percent(current: u128, previous: u128) -> String {
+    if previous == 0 {
+        // A latency below a millisecond leaves us nothing to compare against
+        return if current == 0 {
+            "+0.00%".to_string()
+        } else {
+            format!("+{current}ms")
+        };
+    }
+
+    let gain = current as f64 / previous as f64;
+
+    if gain >= 1.0 {
+        format!("+{:.2}%", (gain - 1.0) * 100.0)
+    } else {
+        format!("-{:.2}%", (1.0 - gain) * 100.0)
+    }
+}
+
+fn print_comparison(current: &ScenarioReport, previous: &ScenarioReport) {
+    println!(
+        "Compared to the run of {} at {}:",
+        previous.scenario.name, previous.timestamp_seconds
+    );
+
+    for (operation, report) in &current.operations {
+        let Some(previous) = previous.operations.get(operation) else {
+            continue;
+        };
+
+        println!(
+            "{operation:?}: p50 {}, p90 {}, p99 {}, failures {} (previously {})",
+            to_percent(report.p50_ms, previous.p50_ms),
+            to_percent(report.p90_ms, previous.p90_ms),
+            to_percent(report.p99_ms, previous.p99_ms),
+            report.failures,
+            previous.failures,
+        );
+    }
+}
+
+struct User {
+    prefix: String,
+    index: u16,
+    client: ClientHandleArc,
+    notes: OOBNotes,
+    population: Population,
+    inbox: Inbox,
+}
+
+impl User {
+    async fn run(
+        self,
+        scenario: &Scenario,
+        start: SystemTime,
+        join_after: Duration,
+        event_sender: &mpsc::UnboundedSender<MetricEvent>,
+        sample_sender: &mpsc::UnboundedSender<Sample>,
+    ) -> anyhow::Result<()> {
+        let prefix = &self.prefix;
+
+        fedimint_core::task::sleep(join_after).await;
+
+        reissue_notes(&self.client, self.notes.clone(), event_sender)
+            .await
+            .with_context(|| format!("{prefix} while reissuing initial notes"))?;
+
+        let mut rng = StdRng::seed_from_u64(scenario.seed.wrapping_add(u64::from(self.index)));
+
+        let operations = self.population.mix.keys().copied().collect::<Vec<_>>();
+        let mix = WeightedIndex::new(self.population.mix.values())
+            .expect("Validated when loading the scenario");
+
+        let duration = Duration::from_secs(scenario.duration_secs);
+        let mean_pause_secs = 60.0 / self.population.ops_per_minute;
+
+        loop {
+            let pause = Duration::from_secs_f64(-(1.0 - rng.r#gen::<f64>()).ln() * mean_pause_secs);
+
+            if start.elapsed()? + pause >= duration {
+                break;
+            }
+
+            fedimint_core::task::sleep(pause).await;
+
+            let operation = operations[mix.sample(&mut rng)];
+
+            let operation_start = fedimint_core::time::now();
+
+            let result = self.perform(operation, scenario).await;
+
+            let duration = operation_start.elapsed()?;
+
+            if let Err(e) = &result {
+                warn!("{prefix} {operation:?} failed after {duration:?}: {e:?}");
+            }
+
+            sample_sender.send(Sample {
+                operation,
+                duration,
+                success: result.is_ok(),
+            })?;
+        }
+
+        Ok(())
+    }
+
+    async fn perform(&self, operation: Operation, scenario: &Scenario) -> anyhow::Result<()> {
+        let amount = self.population.amount_msat;
+        let onchain_amount = bitcoin::Amount::from_sat(self.population.onchain_amount_sat);
+
+        match operation {
+            Operation::Reissue => {
+                let notes = self.inbox.lock().expect("locking failed").pop();
+
+                let notes = match notes {
+                    Some(notes) => notes,
+                    None => do_spend_notes(&self.client, amount).await?.1,
+                };
+
+                self.reissue(notes).await
+            }
+            Operation::SpendOob => {
+                let (_, notes) = do_spend_notes(&self.client, amount).await?;
+
+                self.inbox.lock().expect("locking failed").push(notes);
+
+                Ok(())
+            }
+            Operation::Lnv2Send => {
+                let lnv2 = self
+                    .client
+                    .get_first_module::<fedimint_lnv2_client::LightningClientModule>()?;
+
+                let invoice = ldk_create_invoice(amount).await?;
+
+                let operation_id = lnv2
+                    .send(
+                        invoice,
+                        scenario.lnv2_gateway.clone(),
+                        serde_json::Value::Null,
+                    )
+                    .await?;
+
+                match lnv2.await_final_send_operation_state(operation_id).await? {
+                    FinalSendOperationState::Success => Ok(()),
+                    state => bail!("Payment ended in state {state:?}"),
+                }
+            }
+            Operation::Lnv2Receive => {
+                let lnv2 = self
+                    .client
+                    .get_first_module::<fedimint_lnv2_client::LightningClientModule>()?;
+
+                let (invoice, operation_id) = lnv2
+                    .receive(
+                        amount,
+                        3600,
+                        Bolt11InvoiceDescription::Direct(String::new()),
+                        scenario.lnv2_gateway.clone(),
+                        serde_json::Value::Null,
+                    )
+                    .await?;
+
+                ldk_pay_invoice(invoice).await?;
+
+                match lnv2
+                    .await_final_receive_operation_state(operation_id)
+                    .await?
+                {
+                    FinalReceiveOperationState::Claimed => Ok(()),
+                    state => bail!("Payment ended in state {state:?}"),
+                }
+            }
+            Operation::Walletv2Pegin => {
+                let wallet = self
+                    .client
+                    .get_first_module::<fedimint_walletv2_client::WalletClientModule>()?;
+
+                let balance_before = self.client.get_balance_for_btc().await?;
+
+                let address = wallet.receive().await;
+
+                cmd!(
+                    BitcoinCli,
+                    "sendtoaddress",
+                    address,
+                    onchain_amount.to_btc()
+                )
+                .run()
+                .await?;
+
+                mine_blocks(scenario.pegin_blocks).await?;
+
+                // The fees of the federation are deducted from the deposit
+                let expected = balance_before + Amount::from_sats(onchain_amount.to_sat() * 9 / 10);
+
+                let deadline = fedimint_core::time::now() + PEGIN_TIMEOUT;
+
+                while self.client.get_balance_for_btc().await? < expected {
+                    ensure!(
+                        fedimint_core::time::now() < deadline,
+                        "Peg-in was not credited within {PEGIN_TIMEOUT:?}"
+                    );
+
+                    fedimint_core::task::sleep(Duration::from_millis(500)).await;
+                }
+
+                Ok(())
+            }
+            Operation::Walletv2Pegout => {
+                let wallet = self
+                    .client
+                    .get_first_module::<fedimint_walletv2_client::WalletClientModule>()?;
+
+                let address = cmd!(BitcoinCli, "getnewaddress").out_string().await?;
+
+                let operation_id = wallet
+                    .send(Address::from_str(&address)?, onchain_amount, None)
+                    .await?;
+
+                match wallet.await_final_send_operation_state(operation_id).await {
+                    FinalWalletSendOperationState::Success(_) => Ok(()),
+                    state => bail!("Peg-out ended in state {state:?}"),
+                }
+            }
+            #[allow(deprecated)]
+            Operation::Backup => self.client.backup_to_federation(Metadata::empty()).await,
+        }
+    }
+
+    async fn reissue(&self, notes: OOBNotes) -> anyhow::Result<()> {
+        let mint = self.client.get_first_module::<MintClientModule>()?;
+
+        let operation_id = mint.reissue_external_notes(notes, ()).await?;
+
+        let mut updates = mint
+            .subscribe_reissue_external_notes(operation_id)
+            .await?
+            .into_stream();
+
+        while let Some(update) = updates.next().await {
+            if let fedimint_mint_client::ReissueExternalNotesState::Failed(e) = update {
+                bail!("Reissue failed: {e}")
+            }
+        }
+
+        Ok(())
+    }
+}
+
+async fn mine_blocks(blocks: u64) -> anyhow::Result<()> {
+    let address = cmd!(BitcoinCli, "getnewaddress").out_string().await?;
+
+    cmd!(BitcoinCli, "generatetoaddress", blocks, address)
+        .run()
+        .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{Scenario, percentile, to_percent};
+
+    const SCENARIO: &str = r#"
+        name = "test"
+        duration_secs = 60
+        ramp_up_secs = 10
+
+        [[population]]
+        name = "users"
+        users = 2
+        ops_per_minute = 6.0
+        initial_balance_msat = 100000
+        amount_msat = 1000
+        onchain_amount_sat = 10000
+        mix = { reissue = 1, walletv2_pegin = 1 }
+    "#;
+
+    fn test_scenario() -> Scenario {
+        toml::from_str(SCENARIO).expect("Failed to parse scenario")
+    }
+
+    #[test]
+    fn percentile_is_nearest_rank() {
+        let latencies = (1..=10).map(Duration::from_millis).collect::<Vec<_>>();
+
+        assert_eq!(percentile(&latencies, 0), Duration::from_millis(1));
+        assert_eq!(percentile(&latencies, 10), Duration::from_millis(1));
+        assert_eq!(percentile(&latencies, 11), Duration::from_millis(2));
+        assert_eq!(percentile(&latencies, 50), Duration::from_millis(5));
+        assert_eq!(percentile(&latencies, 90), Duration::from_millis(9));
+        assert_eq!(percentile(&latencies, 99), Duration::from_millis(10));
+        assert_eq!(percentile(&latencies, 100), Duration::from_millis(10));
+
+        let single = [Duration::from_millis(7)];
+
+        assert_eq!(percentile(&single, 0), Duration::from_millis(7));
+        assert_eq!(percentile(&single, 99), Duration::from_millis(7));
+
+        assert_eq!(percentile(&[], 50), Duration::ZERO);
+    }
+
+    #[test]
+    fn valid_scenario_passes_validation() {
+        test_scenario()
+            .validate()
+            .expect("Scenario should be valid");
+
+        toml::from_str::<Scenario>(include_str!("../scenarios/mixed.toml"))
+            .expect("Failed to parse scenario")
+            .validate()
+            .expect("Scenario should be valid");
+    }
+
+    #[test]
+    fn invalid_scenarios_fail_validation() {
+        let mut scenario = test_scenario();
+        scenario.duration_secs = 0;
+        scenario.ramp_up_secs = 0;
+        assert!(scenario.validate().is_err());
+
+        let mut scenario = test_scenario();
+        scenario.ramp_up_secs = scenario.duration_secs + 1;
+        assert!(scenario.validate().is_err());
+
+        let mut scenario = test_scenario();
+        scenario.populations.clear();
+        assert!(scenario.validate().is_err());
+
+        let mut scenario = test_scenario();
+        scenario.populations[0].users = 0;
+        assert!(scenario.validate().is_err());
+
+        let mut scenario = test_scenario();
+        scenario.populations[0].ops_per_minute = 0.0;
+        assert!(scenario.validate().is_err());
+
+        let mut scenario = test_scenario();
+        scenario.populations[0]
+            .mix
+            .values_mut()
+            .for_each(|weight| *weight = 0);
+        assert!(scenario.validate().is_err());
+
+        let mut scenario = test_scenario();
+        scenario.populations[0].onchain_amount_sat = 0;
+        assert!(scenario.validate().is_err());
+    }
+
+    #[test]
+    fn to_percent_handles_zero_previous_latency() {
+        assert_eq!(to_percent(150, 100), "+50.00%");
+        assert_eq!(to_percent(50, 100), "-50.00%");
+        assert_eq!(to_percent(0, 0), "+0.00%");
+        assert_eq!(to_percent(5, 0), "+5ms");
+    }
+}
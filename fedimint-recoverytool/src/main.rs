@@ -146,7 +146,9 @@ async fn main() -> anyhow::Result<()> {
             .get_module_config_typed(wallet_module_id)
             .expect("Malformed wallet config");
         let base_descriptor = wallet_cfg.consensus.peg_in_descriptor;
-        let base_key = wallet_cfg.private.peg_in_key;
+        let base_key = wallet_cfg.private.peg_in_key.expect(
+            "The wallet key has been exported to a guardian signer, use --descriptor and --key",
+        );
         let network = wallet_cfg.consensus.network.0;
 
         (base_descriptor, base_key, network, wallet_module_id)
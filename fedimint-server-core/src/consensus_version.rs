@@ -0,0 +1,100 @@
+//! Automatic activation of new module consensus versions
+//!
+//! A module that changes its consensus rules bumps its module consensus
+//! version and only applies the new rules once the federation voted to
+//! activate the new version. Votes are cast once every peer reports that it
+//! supports the new version, since peers that did not upgrade yet would
+//! otherwise diverge from the rest of the federation.
+
+use std::time::Duration;
+
+use fedimint_api_client::api::{DynModuleApi, FederationApiExt as _};
+use fedimint_core::PeerId;
+use fedimint_core::envs::is_running_in_test_env;
+use fedimint_core::module::{ApiRequestErased, ModuleConsensusVersion};
+use fedimint_core::task::{TaskGroup, sleep};
+use fedimint_core::util::FmtCompact as _;
+use fedimint_logging::LOG_SERVER;
+use futures::future::join_all;
+use tokio::sync::watch;
+use tracing::{debug, trace};
+
+/// Periodically fetches the module consensus version every peer supports from
+/// the given endpoint and reports the lowest one, or `None` as long as not all
+/// peers responded. Peers that do not know the endpoint yet run a version from
+/// before the voting was introduced and keep the result at `None`.
+pub fn spawn_peer_supported_consensus_version_task(
+    api_client: DynModuleApi,
+    task_group: &TaskGroup,
+    our_peer_id: PeerId,
+    supported_consensus_version: ModuleConsensusVersion,
+    endpoint: &'static str,
+) -> watch::Receiver<Option<ModuleConsensusVersion>> {
+    let (sender, receiver) = watch::channel(None);
+
+    task_group.spawn_cancellable("fetch-peer-consensus-versions", async move {
+        loop {
+            let request_futures = api_client.all_peers().iter().filter_map(|&peer| {
+                if peer == our_peer_id {
+                    return None;
+                }
+
+                let api_client = api_client.clone();
+
+                Some(async move {
+                    api_client
+                        .request_single_peer::<ModuleConsensusVersion>(
+                            endpoint.to_owned(),
+                            ApiRequestErased::default(),
+                            peer,
+                        )
+                        .await
+                        .inspect_err(|err| {
+                            debug!(
+                                target: LOG_SERVER,
+                                %peer,
+                                %endpoint,
+                                err = %err.fmt_compact(),
+                                "Failed to fetch supported consensus version from peer"
+                            );
+                        })
+                        .ok()
+                })
+            });
+
+            let versions = join_all(request_futures)
+                .await
+                .into_iter()
+                .flatten()
+                .chain(std::iter::once(supported_consensus_version))
+                .collect::<Vec<_>>();
+
+            let all_peers_supported_version = if versions.len() == api_client.all_peers().len() {
+                versions.into_iter().min()
+            } else {
+                trace!(
+                    target: LOG_SERVER,
+                    %endpoint,
+                    ?versions,
+                    "Not all peers have reported their consensus version yet"
+                );
+
+                None
+            };
+
+            #[allow(clippy::disallowed_methods)]
+            if sender.send(all_peers_supported_version).is_err() {
+                break;
+            }
+
+            if is_running_in_test_env() {
+                // Even in tests we don't want to spam the federation with requests about it
+                sleep(Duration::from_secs(5)).await;
+            } else {
+                sleep(Duration::from_mins(10)).await;
+            }
+        }
+    });
+
+    receiver
+}
@@ -0,0 +1,905 @@
+//! Delegation of operations on the private keys of a guardian
+//!
+//! Instead of signing with the keys in its config, fedimintd can send its
+//! signing requests to a [`RemoteGuardianSigner`] - a separate local process
+//! listening on a Unix socket via [`serve_guardian_signer`]. That process may
+//! be backed by an HSM or a hardened enclave and checks every request against
+//! its [`IGuardianSigningPolicy`] before performing it, which by default is
+//! the [`KeyUsagePolicy`].
+//!
+//! Every module operation on a secret key goes through a signer: Schnorr and
+//! ECDSA signatures, blind signature and decryption shares, ECDH shares with
+//! a proof of correctness and partial threshold signatures. A guardian using
+//! a remote signer therefore does not need any secret key in its config.
+
+use std::collections::BTreeMap;
+use std::fmt::{self, Debug};
+use std::fs::Permissions;
+use std::os::unix::fs::PermissionsExt as _;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+use anyhow::{Context as _, bail, ensure};
+use bitcoin::hashes::{Hash as _, sha256};
+use bls12_381::{G1Affine, Scalar};
+use fedimint_core::core::ModuleInstanceId;
+use fedimint_core::encoding::{Decodable, Encodable};
+use fedimint_core::module::registry::ModuleDecoderRegistry;
+use fedimint_core::runtime::timeout;
+use fedimint_core::secp256k1::{self, Keypair, Message, PublicKey, SecretKey, ecdsa, schnorr};
+use fedimint_core::util::FmtCompactAnyhow as _;
+use fedimint_core::{apply, async_trait_maybe_send};
+use fedimint_logging::LOG_SERVER;
+use rand::Rng as _;
+use rand::rngs::OsRng;
+use tokio::io::{AsyncRead, AsyncReadExt as _, AsyncWrite, AsyncWriteExt as _};
+use tokio::net::{UnixListener, UnixStream};
+use tracing::{debug, info, warn};
+
+/// Upper bound for the length of a single request or response frame
+const MAX_FRAME_LENGTH: u32 = 1 << 20;
+
+/// How long we wait for the remote signer before giving up on a request
+const REMOTE_SIGNER_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long we keep the nonces of a FROST signing session. Signing sessions
+/// are bound to a consensus session, so consensus has moved on to a new
+/// signing session long before.
+const NONCE_SESSION_EXPIRY: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Identifies a private key of the guardian
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Encodable, Decodable)]
+pub enum GuardianKey {
+    /// The key signing the consensus messages of the guardian
+    Broadcast,
+    /// A key of a module instance, named by the module
+    Module {
+        module_instance_id: ModuleInstanceId,
+        name: String,
+    },
+}
+
+impl fmt::Display for GuardianKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GuardianKey::Broadcast => f.write_str("broadcast"),
+            GuardianKey::Module {
+                module_instance_id,
+                name,
+            } => write!(f, "module-{module_instance_id}/{name}"),
+        }
+    }
+}
+
+/// The secret behind a [`GuardianKey`]
+#[derive(Clone, Encodable, Decodable)]
+pub enum GuardianSecret {
+    Secp256k1(SecretKey),
+    Bls12381(Scalar),
+}
+
+/// An operation requiring one of the private keys of the guardian
+#[derive(Debug, Clone, PartialEq, Eq, Encodable, Decodable)]
+pub enum GuardianSigningRequest {
+    /// BIP-340 Schnorr signature over a digest
+    Schnorr { key: GuardianKey, digest: [u8; 32] },
+    /// ECDSA signature over a digest, for example a bitcoin sighash, with the
+    /// secret key plus the public `tweak` of the spent output
+    Ecdsa {
+        key: GuardianKey,
+        tweak: Option<[u8; 32]>,
+        digest: [u8; 32],
+    },
+    /// Multiplication of a point by the secret scalar, which produces blind
+    /// signature shares as well as decryption shares
+    G1Multiply { key: GuardianKey, point: G1Affine },
+    /// Multiplication of a point by the secret key together with a proof
+    /// that the same key is behind our public key, whose challenge is the
+    /// hash of `tag`, the public key, the product, the point and both nonces
+    Secp256k1Dleq {
+        key: GuardianKey,
+        point: PublicKey,
+        tag: String,
+    },
+    /// Commitments to the hiding and binding nonce of a FROST signing
+    /// session. The signer draws the nonces at random on the first request
+    /// for a session and only keeps them in memory, hence a restarted signer
+    /// cannot sign for the sessions it committed to before.
+    NonceCommitment { key: GuardianKey, session: [u8; 32] },
+    /// FROST partial signature `±(hiding + binding_factor * binding) +
+    /// key_factor * sk` with the nonces of the session. The signer erases the
+    /// nonces after signing and from then on only repeats that signature.
+    PartialSignature {
+        key: GuardianKey,
+        session: [u8; 32],
+        binding_factor: [u8; 32],
+        negate_nonce: bool,
+        key_factor: [u8; 32],
+    },
+}
+
+impl GuardianSigningRequest {
+    pub fn key(&self) -> &GuardianKey {
+        match self {
+            GuardianSigningRequest::Schnorr { key, .. }
+            | GuardianSigningRequest::Ecdsa { key, .. }
+            | GuardianSigningRequest::G1Multiply { key, .. }
+            | GuardianSigningRequest::Secp256k1Dleq { key, .. }
+            | GuardianSigningRequest::NonceCommitment { key, .. }
+            | GuardianSigningRequest::PartialSignature { key, .. } => key,
+        }
+    }
+}
+
+/// A product of a secret key and a point with a proof of discrete log
+/// equality to the public key
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Encodable, Decodable)]
+pub struct DleqProduct {
+    pub product: PublicKey,
+    pub challenge: [u8; 32],
+    pub response: [u8; 32],
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Encodable, Decodable)]
+pub enum GuardianSigningResponse {
+    Schnorr(schnorr::Signature),
+    Ecdsa(ecdsa::Signature),
+    G1Multiply(G1Affine),
+    Secp256k1Dleq(DleqProduct),
+    NonceCommitment {
+        hiding: PublicKey,
+        binding: PublicKey,
+    },
+    PartialSignature([u8; 32]),
+    /// The signer refused to perform the operation
+    Rejected(String),
+}
+
+pub type DynGuardianSigner = Arc<dyn IGuardianSigner>;
+
+/// Performs operations with the private keys of a guardian
+#[apply(async_trait_maybe_send!)]
+pub trait IGuardianSigner: Debug + Send + Sync + 'static {
+    async fn sign(
+        &self,
+        request: GuardianSigningRequest,
+    ) -> anyhow::Result<GuardianSigningResponse>;
+
+    fn into_dyn(self) -> DynGuardianSigner
+    where
+        Self: Sized,
+    {
+        Arc::new(self)
+    }
+}
+
+/// Typed helpers on top of [`IGuardianSigner::sign`]
+#[apply(async_trait_maybe_send!)]
+pub trait GuardianSignerExt {
+    async fn sign_schnorr(
+        &self,
+        key: GuardianKey,
+        digest: [u8; 32],
+    ) -> anyhow::Result<schnorr::Signature>;
+
+    async fn sign_ecdsa(
+        &self,
+        key: GuardianKey,
+        tweak: Option<[u8; 32]>,
+        digest: [u8; 32],
+    ) -> anyhow::Result<ecdsa::Signature>;
+
+    async fn multiply_g1(&self, key: GuardianKey, point: G1Affine) -> anyhow::Result<G1Affine>;
+
+    async fn multiply_secp256k1_with_proof(
+        &self,
+        key: GuardianKey,
+        point: PublicKey,
+        tag: String,
+    ) -> anyhow::Result<DleqProduct>;
+
+    async fn nonce_commitment(
+        &self,
+        key: GuardianKey,
+        session: [u8; 32],
+    ) -> anyhow::Result<(PublicKey, PublicKey)>;
+
+    async fn partial_signature(
+        &self,
+        key: GuardianKey,
+        session: [u8; 32],
+        binding_factor: [u8; 32],
+        negate_nonce: bool,
+        key_factor: [u8; 32],
+    ) -> anyhow::Result<[u8; 32]>;
+}
+
+#[apply(async_trait_maybe_send!)]
+impl<T> GuardianSignerExt for T
+where
+    T: IGuardianSigner + ?Sized,
+{
+    async fn sign_schnorr(
+        &self,
+        key: GuardianKey,
+        digest: [u8; 32],
+    ) -> anyhow::Result<schnorr::Signature> {
+        match self
+            .sign(GuardianSigningRequest::Schnorr { key, digest })
+            .await?
+        {
+            GuardianSigningResponse::Schnorr(signature) => Ok(signature),
+            response => unexpected_response(response),
+        }
+    }
+
+    async fn sign_ecdsa(
+        &self,
+        key: GuardianKey,
+        tweak: Option<[u8; 32]>,
+        digest: [u8; 32],
+    ) -> anyhow::Result<ecdsa::Signature> {
+        match self
+            .sign(GuardianSigningRequest::Ecdsa { key, tweak, digest })
+            .await?
+        {
+            GuardianSigningResponse::Ecdsa(signature) => Ok(signature),
+            response => unexpected_response(response),
+        }
+    }
+
+    async fn multiply_g1(&self, key: GuardianKey, point: G1Affine) -> anyhow::Result<G1Affine> {
+        match self
+            .sign(GuardianSigningRequest::G1Multiply { key, point })
+            .await?
+        {
+            GuardianSigningResponse::G1Multiply(point) => Ok(point),
+            response => unexpected_response(response),
+        }
+    }
+
+    async fn multiply_secp256k1_with_proof(
+        &self,
+        key: GuardianKey,
+        point: PublicKey,
+        tag: String,
+    ) -> anyhow::Result<DleqProduct> {
+        match self
+            .sign(GuardianSigningRequest::Secp256k1Dleq { key, point, tag })
+            .await?
+        {
+            GuardianSigningResponse::Secp256k1Dleq(product) => Ok(product),
+            response => unexpected_response(response),
+        }
+    }
+
+    async fn nonce_commitment(
+        &self,
+        key: GuardianKey,
+        session: [u8; 32],
+    ) -> anyhow::Result<(PublicKey, PublicKey)> {
+        match self
+            .sign(GuardianSigningRequest::NonceCommitment { key, session })
+            .await?
+        {
+            GuardianSigningResponse::NonceCommitment { hiding, binding } => Ok((hiding, binding)),
+            response => unexpected_response(response),
+        }
+    }
+
+    async fn partial_signature(
+        &self,
+        key: GuardianKey,
+        session: [u8; 32],
+        binding_factor: [u8; 32],
+        negate_nonce: bool,
+        key_factor: [u8; 32],
+    ) -> anyhow::Result<[u8; 32]> {
+        match self
+            .sign(GuardianSigningRequest::PartialSignature {
+                key,
+                session,
+                binding_factor,
+                negate_nonce,
+                key_factor,
+            })
+            .await?
+        {
+            GuardianSigningResponse::PartialSignature(signature) => Ok(signature),
+            response => unexpected_response(response),
+        }
+    }
+}
+
+fn unexpected_response<T>(response: GuardianSigningResponse) -> anyhow::Result<T> {
+    match response {
+        GuardianSigningResponse::Rejected(reason) => {
+            bail!("Guardian signer rejected the request: {reason}")
+        }
+        response => bail!("Guardian signer sent an unexpected response: {response:?}"),
+    }
+}
+
+/// Decides which requests a [`LocalGuardianSigner`] is allowed to perform
+pub trait IGuardianSigningPolicy: Debug + Send + Sync + 'static {
+    /// Returns an error describing why the request has been rejected
+    fn check(&self, request: &GuardianSigningRequest) -> anyhow::Result<()>;
+}
+
+/// Policy performing every request for a known key
+#[derive(Debug)]
+pub struct AllowAllPolicy;
+
+impl IGuardianSigningPolicy for AllowAllPolicy {
+    fn check(&self, _request: &GuardianSigningRequest) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+/// Policy restricting every key to the operations fedimintd performs with it,
+/// such that a compromised fedimintd can not use a key for anything else
+///
+/// * The broadcast key only signs Schnorr signatures
+/// * Module keys never sign Schnorr signatures
+/// * ECDSA signatures by module keys spend a tweaked descriptor key
+#[derive(Debug)]
+pub struct KeyUsagePolicy;
+
+impl IGuardianSigningPolicy for KeyUsagePolicy {
+    fn check(&self, request: &GuardianSigningRequest) -> anyhow::Result<()> {
+        match (request.key(), request) {
+            (GuardianKey::Broadcast, GuardianSigningRequest::Schnorr { .. }) => Ok(()),
+            (GuardianKey::Broadcast, _) => {
+                bail!("The broadcast key only signs Schnorr signatures")
+            }
+            (GuardianKey::Module { .. }, GuardianSigningRequest::Schnorr { .. }) => {
+                bail!("Module keys do not sign Schnorr signatures")
+            }
+            (GuardianKey::Module { .. }, GuardianSigningRequest::Ecdsa { tweak, .. }) => {
+                ensure!(tweak.is_some(), "Module keys only sign with a tweak");
+
+                Ok(())
+            }
+            (GuardianKey::Module { .. }, _) => Ok(()),
+        }
+    }
+}
+
+/// Signer holding the secrets in memory
+///
+/// This is what fedimintd uses if no remote signer is configured and what a
+/// remote signer process can wrap its key storage with.
+pub struct LocalGuardianSigner {
+    keys: BTreeMap<GuardianKey, GuardianSecret>,
+    policy: Arc<dyn IGuardianSigningPolicy>,
+    /// The nonce sessions with the time they were started at, such that we can
+    /// evict them after [`NONCE_SESSION_EXPIRY`]
+    nonce_sessions: Mutex<BTreeMap<(GuardianKey, [u8; 32]), (SystemTime, NonceSession)>>,
+}
+
+/// The secret nonces of a FROST signing session are used exactly once
+#[derive(Clone, Copy)]
+enum NonceSession {
+    /// We committed to the nonces but have not signed with them yet
+    Committed {
+        hiding: SecretKey,
+        binding: SecretKey,
+    },
+    /// We signed the request with this hash and erased the nonces
+    Used {
+        request_hash: sha256::Hash,
+        signature: [u8; 32],
+    },
+}
+
+impl Debug for LocalGuardianSigner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LocalGuardianSigner")
+            .field("keys", &self.keys.keys().collect::<Vec<_>>())
+            .field("policy", &self.policy)
+            .finish_non_exhaustive()
+    }
+}
+
+impl LocalGuardianSigner {
+    /// Creates a signer enforcing the [`KeyUsagePolicy`]
+    pub fn new(keys: BTreeMap<GuardianKey, GuardianSecret>) -> Self {
+        Self {
+            keys,
+            policy: Arc::new(KeyUsagePolicy),
+            nonce_sessions: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    pub fn with_policy(self, policy: impl IGuardianSigningPolicy) -> Self {
+        Self {
+            policy: Arc::new(policy),
+            ..self
+        }
+    }
+
+    fn perform(
+        &self,
+        request: GuardianSigningRequest,
+        secret: &GuardianSecret,
+    ) -> anyhow::Result<GuardianSigningResponse> {
+        let response = match (request, secret) {
+            (GuardianSigningRequest::Schnorr { digest, .. }, GuardianSecret::Secp256k1(sk)) => {
+                GuardianSigningResponse::Schnorr(
+                    Keypair::from_secret_key(secp256k1::SECP256K1, sk)
+                        .sign_schnorr(Message::from_digest(digest)),
+                )
+            }
+            (
+                GuardianSigningRequest::Ecdsa { tweak, digest, .. },
+                GuardianSecret::Secp256k1(sk),
+            ) => {
+                let sk = match tweak {
+                    Some(tweak) => sk.add_tweak(&secp256k1::Scalar::from_be_bytes(tweak)?)?,
+                    None => *sk,
+                };
+
+                GuardianSigningResponse::Ecdsa(
+                    secp256k1::SECP256K1.sign_ecdsa(&Message::from_digest(digest), &sk),
+                )
+            }
+            (GuardianSigningRequest::G1Multiply { point, .. }, GuardianSecret::Bls12381(sk)) => {
+                GuardianSigningResponse::G1Multiply(G1Affine::from(point * sk))
+            }
+            (
+                GuardianSigningRequest::Secp256k1Dleq { point, tag, .. },
+                GuardianSecret::Secp256k1(sk),
+            ) => GuardianSigningResponse::Secp256k1Dleq(dleq_product(sk, &point, &tag)?),
+            (
+                GuardianSigningRequest::NonceCommitment { key, session },
+                GuardianSecret::Secp256k1(sk),
+            ) => self.nonce_commitment(sk, key, session),
+            (
+                request @ GuardianSigningRequest::PartialSignature { .. },
+                GuardianSecret::Secp256k1(sk),
+            ) => self.partial_signature(sk, request)?,
+            (request, _) => GuardianSigningResponse::Rejected(format!(
+                "Key {} does not support the requested operation",
+                request.key()
+            )),
+        };
+
+        Ok(response)
+    }
+
+    fn nonce_commitment(
+        &self,
+        sk: &SecretKey,
+        key: GuardianKey,
+        session: [u8; 32],
+    ) -> GuardianSigningResponse {
+        let now = fedimint_core::time::now();
+
+        let mut nonce_sessions = self.nonce_sessions.lock().expect("Locking can't fail");
+
+        nonce_sessions.retain(|_, (started, _)| {
+            now.duration_since(*started).unwrap_or_default() < NONCE_SESSION_EXPIRY
+        });
+
+        let (_, nonce_session) = *nonce_sessions.entry((key, session)).or_insert_with(|| {
+            (
+                now,
+                NonceSession::Committed {
+                    hiding: random_nonce(sk, &session),
+                    binding: random_nonce(sk, &session),
+                },
+            )
+        });
+
+        match nonce_session {
+            NonceSession::Committed { hiding, binding } => {
+                GuardianSigningResponse::NonceCommitment {
+                    hiding: hiding.public_key(secp256k1::SECP256K1),
+                    binding: binding.public_key(secp256k1::SECP256K1),
+                }
+            }
+            NonceSession::Used { .. } => GuardianSigningResponse::Rejected(
+                "The nonces of this session have already been used".to_string(),
+            ),
+        }
+    }
+
+    fn partial_signature(
+        &self,
+        sk: &SecretKey,
+        request: GuardianSigningRequest,
+    ) -> anyhow::Result<GuardianSigningResponse> {
+        let request_hash = request.consensus_hash::<sha256::Hash>();
+
+        let GuardianSigningRequest::PartialSignature {
+            key,
+            session,
+            binding_factor,
+            negate_nonce,
+            key_factor,
+        } = request
+        else {
+            unreachable!("Only called for partial signature requests")
+        };
+
+        let mut nonce_sessions = self.nonce_sessions.lock().expect("Locking can't fail");
+
+        let Some((_, nonce_session)) = nonce_sessions.get_mut(&(key, session)) else {
+            return Ok(GuardianSigningResponse::Rejected(
+                "We have not committed to nonces for this session".to_string(),
+            ));
+        };
+
+        let (hiding, binding) = match *nonce_session {
+            NonceSession::Committed { hiding, binding } => (hiding, binding),
+            // Consensus may ask us again for the signature it has not received yet
+            NonceSession::Used {
+                request_hash: used_for,
+                signature,
+            } if used_for == request_hash => {
+                return Ok(GuardianSigningResponse::PartialSignature(signature));
+            }
+            // Signing two different messages with the same nonces reveals the key
+            NonceSession::Used { .. } => {
+                return Ok(GuardianSigningResponse::Rejected(
+                    "The nonces of this session have already been used".to_string(),
+                ));
+            }
+        };
+
+        let nonce = binding
+            .mul_tweak(&secp256k1::Scalar::from_be_bytes(binding_factor)?)?
+            .add_tweak(&hiding.into())?;
+
+        let nonce = if negate_nonce { nonce.negate() } else { nonce };
+
+        let signature = sk
+            .mul_tweak(&secp256k1::Scalar::from_be_bytes(key_factor)?)?
+            .add_tweak(&nonce.into())?
+            .secret_bytes();
+
+        *nonce_session = NonceSession::Used {
+            request_hash,
+            signature,
+        };
+
+        Ok(GuardianSigningResponse::PartialSignature(signature))
+    }
+}
+
+/// Draws a fresh nonce hedged with our secret key and the session, following
+/// the nonce generation of FROST (RFC 9591), such that a weak random number
+/// generator alone does not reveal the nonce.
+fn random_nonce(sk: &SecretKey, session: &[u8; 32]) -> SecretKey {
+    SecretKey::from_slice(
+        &(
+            "fedimint-guardian-signer-nonce",
+            OsRng.r#gen::<[u8; 32]>(),
+            sk,
+            session,
+        )
+            .consensus_hash::<sha256::Hash>()
+            .to_byte_array(),
+    )
+    .expect("Hash is within field order")
+}
+
+fn dleq_product(sk: &SecretKey, point: &PublicKey, tag: &str) -> anyhow::Result<DleqProduct> {
+    let product = point.mul_tweak(secp256k1::SECP256K1, &(*sk).into())?;
+
+    let nonce = SecretKey::from_slice(
+        &(tag, "nonce", sk, point)
+            .consensus_hash::<sha256::Hash>()
+            .to_byte_array(),
+    )?;
+
+    let nonce_point = point.mul_tweak(secp256k1::SECP256K1, &nonce.into())?;
+
+    let challenge = (
+        tag,
+        sk.public_key(secp256k1::SECP256K1),
+        product,
+        point,
+        nonce.public_key(secp256k1::SECP256K1),
+        nonce_point,
+    )
+        .consensus_hash::<sha256::Hash>()
+        .to_byte_array();
+
+    let response = sk
+        .mul_tweak(&secp256k1::Scalar::from_be_bytes(challenge)?)?
+        .add_tweak(&nonce.into())?;
+
+    Ok(DleqProduct {
+        product,
+        challenge,
+        response: response.secret_bytes(),
+    })
+}
+
+#[apply(async_trait_maybe_send!)]
+impl IGuardianSigner for LocalGuardianSigner {
+    async fn sign(
+        &self,
+        request: GuardianSigningRequest,
+    ) -> anyhow::Result<GuardianSigningResponse> {
+        if let Err(e) = self.policy.check(&request) {
+            return Ok(GuardianSigningResponse::Rejected(e.to_string()));
+        }
+
+        let Some(secret) = self.keys.get(request.key()) else {
+            return Ok(GuardianSigningResponse::Rejected(format!(
+                "Unknown key {}",
+                request.key()
+            )));
+        };
+
+        self.perform(request, secret)
+    }
+}
+
+/// Gives a module access to its own keys only
+#[derive(Debug, Clone)]
+pub struct ModuleGuardianSigner {
+    module_instance_id: ModuleInstanceId,
+    signer: DynGuardianSigner,
+}
+
+impl ModuleGuardianSigner {
+    pub fn new(module_instance_id: ModuleInstanceId, signer: DynGuardianSigner) -> Self {
+        Self {
+            module_instance_id,
+            signer,
+        }
+    }
+
+    /// Signer holding the given module keys in memory, as used by tests
+    /// constructing a module directly
+    pub fn local(
+        module_instance_id: ModuleInstanceId,
+        keys: BTreeMap<String, GuardianSecret>,
+    ) -> Self {
+        let keys = keys
+            .into_iter()
+            .map(|(name, secret)| {
+                (
+                    GuardianKey::Module {
+                        module_instance_id,
+                        name,
+                    },
+                    secret,
+                )
+            })
+            .collect();
+
+        Self::new(
+            module_instance_id,
+            LocalGuardianSigner::new(keys).into_dyn(),
+        )
+    }
+
+    pub fn key(&self, name: &str) -> GuardianKey {
+        GuardianKey::Module {
+            module_instance_id: self.module_instance_id,
+            name: name.to_string(),
+        }
+    }
+
+    pub async fn sign_schnorr(
+        &self,
+        name: &str,
+        digest: [u8; 32],
+    ) -> anyhow::Result<schnorr::Signature> {
+        self.signer.sign_schnorr(self.key(name), digest).await
+    }
+
+    pub async fn sign_ecdsa(
+        &self,
+        name: &str,
+        tweak: Option<[u8; 32]>,
+        digest: [u8; 32],
+    ) -> anyhow::Result<ecdsa::Signature> {
+        self.signer.sign_ecdsa(self.key(name), tweak, digest).await
+    }
+
+    pub async fn multiply_g1(&self, name: &str, point: G1Affine) -> anyhow::Result<G1Affine> {
+        self.signer.multiply_g1(self.key(name), point).await
+    }
+
+    pub async fn multiply_secp256k1_with_proof(
+        &self,
+        name: &str,
+        point: PublicKey,
+        tag: &str,
+    ) -> anyhow::Result<DleqProduct> {
+        self.signer
+            .multiply_secp256k1_with_proof(self.key(name), point, tag.to_string())
+            .await
+    }
+
+    pub async fn nonce_commitment(
+        &self,
+        name: &str,
+        session: [u8; 32],
+    ) -> anyhow::Result<(PublicKey, PublicKey)> {
+        self.signer.nonce_commitment(self.key(name), session).await
+    }
+
+    pub async fn partial_signature(
+        &self,
+        name: &str,
+        session: [u8; 32],
+        binding_factor: [u8; 32],
+        negate_nonce: bool,
+        key_factor: [u8; 32],
+    ) -> anyhow::Result<[u8; 32]> {
+        self.signer
+            .partial_signature(
+                self.key(name),
+                session,
+                binding_factor,
+                negate_nonce,
+                key_factor,
+            )
+            .await
+    }
+}
+
+/// Client of a signer process serving requests on a Unix socket
+///
+/// Every request is sent as a length prefixed frame containing the consensus
+/// encoding of a [`GuardianSigningRequest`] and answered with a frame
+/// containing a [`GuardianSigningResponse`].
+#[derive(Debug)]
+pub struct RemoteGuardianSigner {
+    path: PathBuf,
+    stream: tokio::sync::Mutex<Option<UnixStream>>,
+}
+
+impl RemoteGuardianSigner {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            stream: tokio::sync::Mutex::new(None),
+        }
+    }
+
+    async fn connect(&self) -> anyhow::Result<UnixStream> {
+        UnixStream::connect(&self.path).await.with_context(|| {
+            format!(
+                "Failed to connect to guardian signer at {}",
+                self.path.display()
+            )
+        })
+    }
+}
+
+#[apply(async_trait_maybe_send!)]
+impl IGuardianSigner for RemoteGuardianSigner {
+    async fn sign(
+        &self,
+        request: GuardianSigningRequest,
+    ) -> anyhow::Result<GuardianSigningResponse> {
+        let mut stream = self.stream.lock().await;
+
+        // If the signer process has been restarted our connection is stale, so we
+        // reconnect once before giving up
+        let mut last_error = None;
+
+        for _ in 0..2 {
+            let result = timeout(REMOTE_SIGNER_TIMEOUT, async {
+                if stream.is_none() {
+                    *stream = Some(self.connect().await?);
+                }
+
+                exchange(stream.as_mut().expect("Connected above"), &request).await
+            })
+            .await
+            .unwrap_or_else(|_| Err(anyhow::anyhow!("Guardian signer timed out")));
+
+            match result {
+                Ok(response) => return Ok(response),
+                Err(e) => {
+                    *stream = None;
+                    last_error = Some(e);
+                }
+            }
+        }
+
+        Err(last_error.expect("We tried at least once"))
+    }
+}
+
+async fn exchange(
+    stream: &mut UnixStream,
+    request: &GuardianSigningRequest,
+) -> anyhow::Result<GuardianSigningResponse> {
+    write_frame(stream, &request.consensus_encode_to_vec()).await?;
+
+    let response = read_frame(stream).await?;
+
+    Ok(GuardianSigningResponse::consensus_decode_whole(
+        &response,
+        &ModuleDecoderRegistry::default(),
+    )?)
+}
+
+async fn write_frame(stream: &mut (impl AsyncWrite + Unpin), frame: &[u8]) -> anyhow::Result<()> {
+    stream.write_u32(u32::try_from(frame.len())?).await?;
+    stream.write_all(frame).await?;
+
+    Ok(())
+}
+
+async fn read_frame(stream: &mut (impl AsyncRead + Unpin)) -> anyhow::Result<Vec<u8>> {
+    let length = stream.read_u32().await?;
+
+    if MAX_FRAME_LENGTH < length {
+        bail!("Guardian signer frame of {length} bytes is too large");
+    }
+
+    let mut frame = vec![0; length as usize];
+    stream.read_exact(&mut frame).await?;
+
+    Ok(frame)
+}
+
+/// Serves the requests of fedimintd on a Unix socket at `path` until an error
+/// occurs while accepting connections, as run by the separate signer process
+pub async fn serve_guardian_signer(path: &Path, signer: DynGuardianSigner) -> anyhow::Result<()> {
+    let listener = UnixListener::bind(path).with_context(|| {
+        format!(
+            "Failed to bind guardian signer socket at {}",
+            path.display()
+        )
+    })?;
+
+    // Anyone able to connect to the socket can use the keys of the guardian
+    std::fs::set_permissions(path, Permissions::from_mode(0o600)).with_context(|| {
+        format!(
+            "Failed to restrict permissions of guardian signer socket at {}",
+            path.display()
+        )
+    })?;
+
+    info!(target: LOG_SERVER, path = %path.display(), "Guardian signer listening");
+
+    loop {
+        let (stream, _) = listener.accept().await?;
+
+        let signer = signer.clone();
+
+        fedimint_core::runtime::spawn("guardian-signer-connection", async move {
+            if let Err(e) = serve_connection(stream, signer).await {
+                debug!(
+                    target: LOG_SERVER,
+                    err = %e.fmt_compact_anyhow(),
+                    "Guardian signer connection closed"
+                );
+            }
+        });
+    }
+}
+
+async fn serve_connection(mut stream: UnixStream, signer: DynGuardianSigner) -> anyhow::Result<()> {
+    loop {
+        let request = GuardianSigningRequest::consensus_decode_whole(
+            &read_frame(&mut stream).await?,
+            &ModuleDecoderRegistry::default(),
+        )?;
+
+        let response = signer.sign(request.clone()).await.unwrap_or_else(|e| {
+            warn!(
+                target: LOG_SERVER,
+                key = %request.key(),
+                err = %e.fmt_compact_anyhow(),
+                "Guardian signer failed to perform request"
+            );
+
+            GuardianSigningResponse::Rejected(e.to_string())
+        });
+
+        write_frame(&mut stream, &response.consensus_encode_to_vec()).await?;
+    }
+}
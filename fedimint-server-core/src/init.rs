@@ -23,6 +23,7 @@
 
 use crate::bitcoin_rpc::ServerBitcoinRpcMonitor;
 use crate::config::PeerHandleOps;
+use crate::guardian_signer::{GuardianSecret, ModuleGuardianSigner};
 use crate::migration::{
     DynServerDbMigrationFn, ServerDbMigrationFnContext, ServerModuleDbMigrationContext,
     ServerModuleDbMigrationFn,
@@ -79,6 +80,7 @@ async fn init(
         our_peer_id: PeerId,
         module_api: DynModuleApi,
         server_bitcoin_rpc_monitor: ServerBitcoinRpcMonitor,
+        guardian_signer: ModuleGuardianSigner,
     ) -> anyhow::Result<DynServerModule>;
 
     fn trusted_dealer_gen(
@@ -114,6 +116,12 @@ fn get_client_config(
 
     /// Returns documentation for every environment variable this module reads.
     fn get_documented_env_vars(&self) -> Vec<EnvVarDoc>;
+
+    /// See [`ServerModuleInit::guardian_keys`]
+    fn guardian_keys(
+        &self,
+        config: &ServerModuleConfig,
+    ) -> anyhow::Result<BTreeMap<String, GuardianSecret>>;
 }
 
 /// A type that can be used as module-shared value inside
@@ -133,6 +141,7 @@ pub struct ServerModuleInitArgs<S>
     num_peers: NumPeers,
     module_api: DynModuleApi,
     server_bitcoin_rpc_monitor: ServerBitcoinRpcMonitor,
+    guardian_signer: ModuleGuardianSigner,
     // ClientModuleInitArgs needs a bound because sometimes we need
     // to pass associated-types data, so let's just put it here right away
     _marker: marker::PhantomData<S>,
@@ -169,6 +178,13 @@ pub fn module_api(&self) -> &DynModuleApi {
     pub fn server_bitcoin_rpc_monitor(&self) -> ServerBitcoinRpcMonitor {
         self.server_bitcoin_rpc_monitor.clone()
     }
+
+    /// Performs operations with the keys returned by
+    /// [`ServerModuleInit::guardian_keys`], which might be held by a remote
+    /// signer instead of this process
+    pub fn guardian_signer(&self) -> &ModuleGuardianSigner {
+        &self.guardian_signer
+    }
 }
 /// Module Generation trait with associated types
 ///
@@ -258,6 +274,16 @@ fn is_enabled_by_default(&self) -> bool {
     fn get_documented_env_vars(&self) -> Vec<EnvVarDoc> {
         vec![]
     }
+
+    /// Returns the private keys of the module by name, so they can be loaded
+    /// into a guardian signer. Modules performing operations with their keys
+    /// through [`ServerModuleInitArgs::guardian_signer`] must return them here.
+    fn guardian_keys(
+        &self,
+        _config: &ServerModuleConfig,
+    ) -> anyhow::Result<BTreeMap<String, GuardianSecret>> {
+        Ok(BTreeMap::new())
+    }
 }
 
 #[apply(async_trait_maybe_send!)]
@@ -282,6 +308,7 @@ async fn init(
         our_peer_id: PeerId,
         module_api: DynModuleApi,
         server_bitcoin_rpc_monitor: ServerBitcoinRpcMonitor,
+        guardian_signer: ModuleGuardianSigner,
     ) -> anyhow::Result<DynServerModule> {
         let module = <Self as ServerModuleInit>::init(
             self,
@@ -294,6 +321,7 @@ async fn init(
                 _marker: PhantomData,
                 module_api,
                 server_bitcoin_rpc_monitor,
+                guardian_signer,
             },
         )
         .await?;
@@ -360,6 +388,13 @@ fn is_enabled_by_default(&self) -> bool {
     fn get_documented_env_vars(&self) -> Vec<EnvVarDoc> {
         <Self as ServerModuleInit>::get_documented_env_vars(self)
     }
+
+    fn guardian_keys(
+        &self,
+        config: &ServerModuleConfig,
+    ) -> anyhow::Result<BTreeMap<String, GuardianSecret>> {
+        <Self as ServerModuleInit>::guardian_keys(self, config)
+    }
 }
 
 dyn_newtype_define!(
@@ -7,7 +7,9 @@
 
 pub mod bitcoin_rpc;
 pub mod config;
+pub mod consensus_version;
 pub mod dashboard_ui;
+pub mod guardian_signer;
 mod init;
 pub mod migration;
 pub mod setup_ui;
@@ -0,0 +1,242 @@
+use std::collections::BTreeMap;
+use std::os::unix::fs::PermissionsExt as _;
+use std::path::Path;
+use std::time::Duration;
+
+use anyhow::ensure;
+use bls12_381::{G1Affine, Scalar};
+use fedimint_core::secp256k1::{self, Message, SecretKey};
+use fedimint_core::task::sleep;
+use fedimint_server::core::guardian_signer::{
+    GuardianKey, GuardianSecret, GuardianSignerExt, GuardianSigningRequest, IGuardianSigner,
+    IGuardianSigningPolicy, LocalGuardianSigner, RemoteGuardianSigner, serve_guardian_signer,
+};
+use rand::rngs::OsRng;
+
+const MODULE_KEY_NAME: &str = "tbs_sk/1024";
+
+/// Only signs digests with a leading zero byte, standing in for the checks a
+/// hardened signer would apply
+#[derive(Debug)]
+struct LeadingZeroPolicy;
+
+impl IGuardianSigningPolicy for LeadingZeroPolicy {
+    fn check(&self, request: &GuardianSigningRequest) -> anyhow::Result<()> {
+        if let GuardianSigningRequest::Schnorr { digest, .. } = request {
+            ensure!(digest[0] == 0, "Digest does not start with a zero byte");
+        }
+
+        Ok(())
+    }
+}
+
+fn module_key() -> GuardianKey {
+    GuardianKey::Module {
+        module_instance_id: 0,
+        name: MODULE_KEY_NAME.to_string(),
+    }
+}
+
+async fn spawn_signer(path: &Path, broadcast_sk: SecretKey, module_sk: Scalar) {
+    let signer = LocalGuardianSigner::new(BTreeMap::from([
+        (
+            GuardianKey::Broadcast,
+            GuardianSecret::Secp256k1(broadcast_sk),
+        ),
+        (module_key(), GuardianSecret::Bls12381(module_sk)),
+    ]))
+    .with_policy(LeadingZeroPolicy)
+    .into_dyn();
+
+    let path = path.to_path_buf();
+
+    tokio::spawn(async move { serve_guardian_signer(&path, signer).await });
+
+    while !path.exists() {
+        sleep(Duration::from_millis(10)).await;
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn remote_guardian_signer_performs_allowed_requests() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("signer.sock");
+
+    let (broadcast_sk, broadcast_pk) = secp256k1::generate_keypair(&mut OsRng);
+    let module_sk = Scalar::from(42);
+
+    spawn_signer(&path, broadcast_sk, module_sk).await;
+
+    // only the user running the signer may connect to it
+    assert_eq!(
+        std::fs::metadata(&path).unwrap().permissions().mode() & 0o777,
+        0o600
+    );
+
+    let signer = RemoteGuardianSigner::new(path);
+
+    let digest = [0; 32];
+
+    let signature = signer
+        .sign_schnorr(GuardianKey::Broadcast, digest)
+        .await
+        .unwrap();
+
+    secp256k1::SECP256K1
+        .verify_schnorr(
+            &signature,
+            &Message::from_digest(digest),
+            &broadcast_pk.x_only_public_key().0,
+        )
+        .unwrap();
+
+    let point = signer
+        .multiply_g1(module_key(), G1Affine::generator())
+        .await
+        .unwrap();
+
+    assert_eq!(point, G1Affine::from(G1Affine::generator() * module_sk));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn remote_guardian_signer_rejects_disallowed_requests() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("signer.sock");
+
+    let (broadcast_sk, _) = secp256k1::generate_keypair(&mut OsRng);
+
+    spawn_signer(&path, broadcast_sk, Scalar::from(42)).await;
+
+    let signer = RemoteGuardianSigner::new(path);
+
+    // rejected by the policy
+    assert!(
+        signer
+            .sign_schnorr(GuardianKey::Broadcast, [1; 32])
+            .await
+            .is_err()
+    );
+
+    // the broadcast key can not be used for blind signatures
+    assert!(
+        signer
+            .multiply_g1(GuardianKey::Broadcast, G1Affine::generator())
+            .await
+            .is_err()
+    );
+
+    // the signer does not know the key
+    assert!(
+        signer
+            .sign_ecdsa(
+                GuardianKey::Module {
+                    module_instance_id: 1,
+                    name: MODULE_KEY_NAME.to_string(),
+                },
+                None,
+                [0; 32],
+            )
+            .await
+            .is_err()
+    );
+
+    // rejections do not break the connection for subsequent requests
+    assert!(
+        signer
+            .sign_schnorr(GuardianKey::Broadcast, [0; 32])
+            .await
+            .is_ok()
+    );
+}
+
+#[tokio::test]
+async fn local_guardian_signer_refuses_to_reuse_nonces() {
+    let key = GuardianKey::Module {
+        module_instance_id: 0,
+        name: "bitcoin_sk".to_string(),
+    };
+
+    let signer = LocalGuardianSigner::new(BTreeMap::from([(
+        key.clone(),
+        GuardianSecret::Secp256k1(SecretKey::new(&mut OsRng)),
+    )]));
+
+    let session = [7; 32];
+
+    signer.nonce_commitment(key.clone(), session).await.unwrap();
+
+    let signature = signer
+        .partial_signature(key.clone(), session, [1; 32], false, [2; 32])
+        .await
+        .unwrap();
+
+    // repeating the exact same request is safe and yields the same signature
+    assert_eq!(
+        signer
+            .partial_signature(key.clone(), session, [1; 32], false, [2; 32])
+            .await
+            .unwrap(),
+        signature
+    );
+
+    // signing anything else with the nonces of this session would leak the key
+    assert!(
+        signer
+            .partial_signature(key.clone(), session, [3; 32], false, [2; 32])
+            .await
+            .is_err()
+    );
+
+    // a fresh session is unaffected
+    assert!(
+        signer
+            .partial_signature(key, [8; 32], [3; 32], false, [2; 32])
+            .await
+            .is_ok()
+    );
+}
+
+#[tokio::test]
+async fn local_guardian_signer_restricts_key_usage_by_default() {
+    let key = GuardianKey::Module {
+        module_instance_id: 0,
+        name: "bitcoin_sk".to_string(),
+    };
+
+    let signer = LocalGuardianSigner::new(BTreeMap::from([
+        (
+            GuardianKey::Broadcast,
+            GuardianSecret::Secp256k1(SecretKey::new(&mut OsRng)),
+        ),
+        (
+            key.clone(),
+            GuardianSecret::Secp256k1(SecretKey::new(&mut OsRng)),
+        ),
+    ]));
+
+    assert!(
+        signer
+            .sign_schnorr(GuardianKey::Broadcast, [0; 32])
+            .await
+            .is_ok()
+    );
+
+    assert!(
+        signer
+            .sign_ecdsa(key.clone(), Some([1; 32]), [0; 32])
+            .await
+            .is_ok()
+    );
+
+    // the broadcast key only signs consensus messages
+    assert!(
+        signer
+            .sign_ecdsa(GuardianKey::Broadcast, None, [0; 32])
+            .await
+            .is_err()
+    );
+
+    // module keys do not sign arbitrary digests
+    assert!(signer.sign_schnorr(key.clone(), [0; 32]).await.is_err());
+    assert!(signer.sign_ecdsa(key, None, [0; 32]).await.is_err());
+}
@@ -0,0 +1,120 @@
+use std::time::Duration;
+
+use fedimint_core::core::{DynInput, DynOutput};
+use fedimint_core::encoding::Encodable as _;
+use fedimint_core::module::AmountUnit;
+use fedimint_core::secp256k1::{Keypair, Message, SECP256K1};
+use fedimint_core::transaction::{Transaction, TransactionSignature};
+use fedimint_core::{Amount, BitcoinHash as _};
+use fedimint_dummy_client::DummyClientInit;
+use fedimint_dummy_common::{DummyInput, DummyOutput};
+use fedimint_dummy_server::DummyInit;
+use fedimint_testing::fixtures::Fixtures;
+
+#[test]
+fn sessions_stay_consistent_under_network_faults() {
+    Fixtures::new_primary(DummyClientInit, DummyInit)
+        .new_simulation()
+        .run_seeds(0..4, |simulation| async move {
+            simulation
+                .faults()
+                .set_latency(Duration::from_millis(10), Duration::from_millis(100));
+            simulation.faults().set_drop_rate(0.05);
+
+            simulation.await_session_count(2).await;
+
+            simulation.assert_consistent().await;
+        });
+}
+
+#[test]
+fn runs_with_the_same_seed_produce_the_same_session_outcomes() {
+    const SESSIONS: usize = 3;
+
+    let builder = Fixtures::new_primary(DummyClientInit, DummyInit).new_simulation();
+
+    let run = || {
+        builder.clone().run(|simulation| async move {
+            simulation
+                .faults()
+                .set_latency(Duration::from_millis(10), Duration::from_millis(100));
+
+            for index in 0..4 {
+                simulation
+                    .submit_transaction(dummy_transaction(simulation.seed(), index))
+                    .await;
+            }
+
+            simulation.await_session_count(SESSIONS as u64).await;
+
+            let mut outcomes = simulation.all_session_outcomes().await;
+
+            // Guardians may have completed further sessions in the meantime
+            for outcomes in outcomes.values_mut() {
+                outcomes.truncate(SESSIONS);
+            }
+
+            (simulation.seed(), outcomes)
+        })
+    };
+
+    let (seed, outcomes) = run();
+
+    assert!(
+        outcomes
+            .values()
+            .flatten()
+            .any(|outcome| !outcome.items.is_empty()),
+        "No transaction was ordered with seed {seed}"
+    );
+
+    assert_eq!(
+        outcomes,
+        run().1,
+        "Session outcomes differ between two runs of seed {seed}"
+    );
+}
+
+/// A transaction of the dummy module that is derived from the seed, such that
+/// both runs of a simulation submit the same transactions
+fn dummy_transaction(seed: u64, index: u64) -> Transaction {
+    let keypair = Keypair::from_seckey_slice(
+        SECP256K1,
+        &(seed, index).consensus_hash_sha256().to_byte_array(),
+    )
+    .expect("Hash is a valid secret key");
+
+    let amount = Amount::from_sats(1000 + index);
+
+    let inputs = vec![DynInput::from_typed(
+        0,
+        DummyInput {
+            amount,
+            unit: AmountUnit::BITCOIN,
+            pub_key: keypair.public_key(),
+        },
+    )];
+
+    let outputs = vec![DynOutput::from_typed(
+        0,
+        DummyOutput {
+            amount,
+            unit: AmountUnit::BITCOIN,
+        },
+    )];
+
+    let nonce = [0; 8];
+
+    let message = Message::from_digest(
+        Transaction::tx_hash_from_parts(&inputs, &outputs, nonce).to_byte_array(),
+    );
+
+    Transaction {
+        inputs,
+        outputs,
+        nonce,
+        signatures: TransactionSignature::NaiveMultisig(vec![
+            SECP256K1.sign_schnorr_no_aux_rand(&message, &keypair),
+        ]),
+    }
+}
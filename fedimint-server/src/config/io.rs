@@ -1,15 +1,21 @@
+use std::collections::BTreeMap;
 use std::fmt::Display;
 use std::fs;
 use std::fs::OpenOptions;
 use std::io::Write;
 use std::path::{Path, PathBuf};
 
-use anyhow::ensure;
-use fedimint_aead::{LessSafeKey, encrypted_read, encrypted_write, get_encryption_key};
+use anyhow::{Context as _, ensure};
+use fedimint_aead::{
+    LessSafeKey, encrypted_read, encrypted_write, get_encryption_key, random_salt,
+};
+use fedimint_core::encoding::{Decodable as _, Encodable as _};
 use fedimint_core::module::ApiAuth;
+use fedimint_core::module::registry::ModuleDecoderRegistry;
 use fedimint_core::util::write_new;
 use fedimint_logging::LOG_CORE;
 use fedimint_server_core::ServerModuleInitRegistry;
+use fedimint_server_core::guardian_signer::{GuardianKey, GuardianSecret};
 use serde::Serialize;
 use serde::de::DeserializeOwned;
 use tracing::{debug, info, warn};
@@ -47,6 +53,9 @@
 
 pub const NEW_VERSION_FILE_EXT: &str = "new";
 
+/// Extension of the salt stored next to an exported guardian keys file
+pub const GUARDIAN_KEYS_SALT_EXT: &str = "salt";
+
 /// Reads the server from the local, private, and consensus cfg files
 pub fn read_server_config(password: &str, path: &Path) -> anyhow::Result<ServerConfig> {
     let salt = fs::read_to_string(path.join(SALT_FILE))?;
@@ -303,3 +312,88 @@ pub fn finalize_password_change(data_dir: &Path) -> anyhow::Result<()> {
 
     Ok(())
 }
+
+/// Moves the guardian keys out of the private config in `data_dir` into the
+/// encrypted keys file of a remote guardian signer at `keys_path`, whose salt
+/// is stored next to it. Afterwards fedimintd has to be started with the
+/// socket of a signer serving this file.
+///
+/// Backups of the data dir taken before still contain the keys.
+pub fn export_guardian_keys(
+    data_dir: &Path,
+    registry: &ServerModuleInitRegistry,
+    keys_path: &Path,
+    keys_password: &str,
+) -> anyhow::Result<()> {
+    recover_interrupted_password_change(data_dir)?;
+
+    let password = fs::read_to_string(data_dir.join(PLAINTEXT_PASSWORD))
+        .context("Exporting the guardian keys requires the password file")?;
+    let password = trim_password(&password);
+
+    let mut cfg = read_server_config(password, data_dir)?;
+
+    let keys = cfg.guardian_keys(registry)?;
+
+    ensure!(
+        !keys.is_empty(),
+        "The config does not contain guardian keys"
+    );
+
+    let keys_salt = random_salt();
+    write_new(keys_path.with_extension(GUARDIAN_KEYS_SALT_EXT), &keys_salt)?;
+    encrypted_write(
+        keys.consensus_encode_to_vec(),
+        &get_encryption_key(keys_password, &keys_salt)?,
+        keys_path.to_path_buf(),
+    )?;
+
+    ensure!(
+        read_guardian_keys(keys_path, keys_password)?.len() == keys.len(),
+        "Failed to read back the exported guardian keys"
+    );
+
+    cfg.strip_guardian_keys(registry)?;
+
+    let salt = fs::read_to_string(data_dir.join(SALT_FILE))?;
+    let key = get_encryption_key(password, &salt)?;
+
+    // We do not keep a backup since the whole point is to remove the keys. If we
+    // are interrupted before the rename the stripped config is moved into place
+    // by `recover_interrupted_password_change` on startup.
+    let private_config_path = data_dir.join(PRIVATE_CONFIG).with_extension(ENCRYPTED_EXT);
+    let temp_private_config_path = private_config_path.with_extension(NEW_VERSION_FILE_EXT);
+
+    encrypted_write(
+        serde_json::to_string(&cfg.private)?.into_bytes(),
+        &key,
+        temp_private_config_path.clone(),
+    )?;
+
+    OpenOptions::new().read(true).open(data_dir)?.sync_all()?;
+
+    fs::rename(&temp_private_config_path, &private_config_path)?;
+
+    info!(
+        target: LOG_CORE,
+        keys = keys.len(),
+        path = %keys_path.display(),
+        "Exported guardian keys"
+    );
+
+    Ok(())
+}
+
+/// Reads the keys written by [`export_guardian_keys`]
+pub fn read_guardian_keys(
+    keys_path: &Path,
+    keys_password: &str,
+) -> anyhow::Result<BTreeMap<GuardianKey, GuardianSecret>> {
+    let salt = fs::read_to_string(keys_path.with_extension(GUARDIAN_KEYS_SALT_EXT))?;
+    let key = get_encryption_key(keys_password, &salt)?;
+
+    Ok(BTreeMap::consensus_decode_whole(
+        &encrypted_read(&key, keys_path.to_path_buf())?,
+        &ModuleDecoderRegistry::default(),
+    )?)
+}
@@ -1,5 +1,6 @@
 use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::net::SocketAddr;
+use std::path::PathBuf;
 use std::sync::Arc;
 use std::time::Duration;
 
@@ -23,6 +24,9 @@
 use fedimint_core::{NumPeersExt, PeerId, secp256k1, timing};
 use fedimint_logging::LOG_NET_PEER_DKG;
 use fedimint_server_core::config::PeerHandleOpsExt as _;
+use fedimint_server_core::guardian_signer::{
+    DynGuardianSigner, GuardianKey, GuardianSecret, IGuardianSigner as _, LocalGuardianSigner,
+};
 use fedimint_server_core::{ConfigGenModuleArgs, DynServerModuleInit, ServerModuleInitRegistry};
 use futures::future::select_all;
 use hex::{FromHex, ToHex};
@@ -35,6 +39,7 @@
 use tracing::{error, info, warn};
 
 use crate::fedimint_core::encoding::Encodable;
+use crate::net::api::pkarr_publish::derive_pkarr_seed;
 use crate::net::p2p::P2PStatusReceivers;
 use crate::net::p2p_connector::TlsConfig;
 
@@ -42,6 +47,7 @@
 pub mod dkg_g1;
 pub mod dkg_g2;
 pub mod io;
+pub mod offline;
 pub mod peer_handle;
 pub mod setup;
 
@@ -80,7 +86,7 @@ pub fn iter_module_instances(
         self.consensus.iter_module_instances()
     }
 
-    pub(crate) fn supported_api_versions_summary(
+    pub fn supported_api_versions_summary(
         modules: &BTreeMap<ModuleInstanceId, ServerModuleConsensusConfig>,
         module_inits: &ServerModuleInitRegistry,
     ) -> SupportedApiVersionsSummary {
@@ -114,8 +120,13 @@ pub struct ServerConfigPrivate {
     /// Optional secret key for our iroh p2p endpoint
     #[serde(default)]
     pub iroh_p2p_sk: Option<iroh::SecretKey>,
-    /// Secret key for the atomic broadcast to sign messages
-    pub broadcast_secret_key: SecretKey,
+    /// Secret key for the atomic broadcast to sign messages, absent once it
+    /// has been exported to a remote guardian signer
+    pub broadcast_secret_key: Option<SecretKey>,
+    /// Seed of our pkarr identity, kept in place of the broadcast secret key it
+    /// is derived from once that has been exported
+    #[serde(default)]
+    pub pkarr_seed: Option<[u8; 32]>,
     /// Secret material from modules
     pub modules: BTreeMap<ModuleInstanceId, JsonWithKind>,
 }
@@ -222,6 +233,12 @@ pub struct ConfigGenSettings {
     pub available_modules: BTreeSet<ModuleKind>,
     /// Modules that should be enabled by default in the setup UI
     pub default_modules: BTreeSet<ModuleKind>,
+    /// Directory to exchange the DKG messages through as signed files instead
+    /// of over p2p, see [`offline`]
+    pub offline_dkg_dir: Option<PathBuf>,
+    /// Unix socket of a separate process holding our private keys, which
+    /// performs all signing operations instead of us
+    pub guardian_signer_socket: Option<PathBuf>,
 }
 
 #[derive(Debug, Clone)]
@@ -360,7 +377,8 @@ pub fn from(
                 .map(|key| key.secret_der().to_vec().encode_hex()),
             iroh_api_sk: params.iroh_api_sk,
             iroh_p2p_sk: params.iroh_p2p_sk,
-            broadcast_secret_key,
+            broadcast_secret_key: Some(broadcast_secret_key),
+            pkarr_seed: None,
             modules: modules
                 .iter()
                 .map(|(peer, cfg)| (*peer, cfg.private.clone()))
@@ -451,10 +469,14 @@ pub fn validate_config(
         let consensus = self.consensus.clone();
         let private = self.private.clone();
 
-        let my_public_key = private.broadcast_secret_key.public_key(&Secp256k1::new());
+        // Without the secret key we verify a signature from the guardian signer
+        // holding it on startup instead
+        if let Some(broadcast_secret_key) = private.broadcast_secret_key {
+            let my_public_key = broadcast_secret_key.public_key(&Secp256k1::new());
 
-        if Some(&my_public_key) != consensus.broadcast_public_keys.get(identity) {
-            bail!("Broadcast secret key doesn't match corresponding public key");
+            if Some(&my_public_key) != consensus.broadcast_public_keys.get(identity) {
+                bail!("Broadcast secret key doesn't match corresponding public key");
+            }
         }
         if endpoints.keys().max().copied().map(PeerId::to_usize) != Some(endpoints.len() - 1) {
             bail!("Peer ids are not indexed from 0");
@@ -479,6 +501,80 @@ pub fn validate_config(
         Ok(())
     }
 
+    /// Collects our private keys, for example to load them into a guardian
+    /// signer
+    pub fn guardian_keys(
+        &self,
+        registry: &ServerModuleInitRegistry,
+    ) -> anyhow::Result<BTreeMap<GuardianKey, GuardianSecret>> {
+        let mut keys = BTreeMap::new();
+
+        if let Some(broadcast_secret_key) = self.private.broadcast_secret_key {
+            keys.insert(
+                GuardianKey::Broadcast,
+                GuardianSecret::Secp256k1(broadcast_secret_key),
+            );
+        }
+
+        for (module_id, module_cfg) in &self.consensus.modules {
+            let module_keys = registry
+                .get(&module_cfg.kind)
+                .ok_or_else(|| format_err!("module config gen not found {}", module_cfg.kind))?
+                .guardian_keys(&self.get_module_config(*module_id)?)?;
+
+            for (name, secret) in module_keys {
+                keys.insert(
+                    GuardianKey::Module {
+                        module_instance_id: *module_id,
+                        name,
+                    },
+                    secret,
+                );
+            }
+        }
+
+        Ok(keys)
+    }
+
+    /// Removes the keys returned by [`Self::guardian_keys`] from the private
+    /// config once they have been exported to a remote guardian signer
+    pub fn strip_guardian_keys(
+        &mut self,
+        registry: &ServerModuleInitRegistry,
+    ) -> anyhow::Result<()> {
+        if let Some(broadcast_secret_key) = self.private.broadcast_secret_key.take() {
+            self.private.pkarr_seed = Some(derive_pkarr_seed(&broadcast_secret_key));
+        }
+
+        for (module_id, module_cfg) in &self.consensus.modules {
+            let has_keys = !registry
+                .get(&module_cfg.kind)
+                .ok_or_else(|| format_err!("module config gen not found {}", module_cfg.kind))?
+                .guardian_keys(&self.get_module_config(*module_id)?)?
+                .is_empty();
+
+            // The private config of a module only consists of its keys, which
+            // deserialize from an empty object once stripped
+            if has_keys {
+                self.private.modules.insert(
+                    *module_id,
+                    JsonWithKind::new(module_cfg.kind.clone(), serde_json::json!({})),
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Signer holding our private keys in memory, used unless a remote signer
+    /// is configured
+    pub fn local_guardian_signer(
+        &self,
+        registry: &ServerModuleInitRegistry,
+    ) -> anyhow::Result<DynGuardianSigner> {
+        Ok(LocalGuardianSigner::new(self.guardian_keys(registry)?).into_dyn())
+    }
+
     pub fn trusted_dealer_gen(
         params: &HashMap<PeerId, ConfigGenParams>,
         registry: &ServerModuleInitRegistry,
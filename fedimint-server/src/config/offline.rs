@@ -0,0 +1,720 @@
+//! Offline transport for the distributed key generation
+//!
+//! Guardians that keep their keys air-gapped can not be connected to each
+//! other over p2p while running the DKG. Instead, every message of the
+//! ceremony is exported as a signed file into an outbox directory, carried to
+//! the other guardians - for example as a fountain-coded sequence of QR codes -
+//! and imported into their inbox directory, see [`export_fragments`] and
+//! [`import_fragments`]. Since [`FileP2PConnections`]
+//! implements [`IP2PConnections`] the unmodified
+//! [`ServerConfig::distributed_gen`](super::ServerConfig::distributed_gen) runs
+//! on top of it and produces the same config as an online DKG.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use anyhow::{Context, bail, ensure};
+use async_trait::async_trait;
+use bitcoin::hashes::sha256;
+use fedimint_core::PeerId;
+use fedimint_core::config::P2PMessage;
+use fedimint_core::encoding::{Decodable, Encodable};
+use fedimint_core::module::registry::ModuleDecoderRegistry;
+use fedimint_core::net::peers::{IP2PConnections, Recipient};
+use fedimint_core::task::sleep;
+use fedimint_fountain::{FountainDecoder, FountainEncoder, Fragment};
+use fedimint_logging::LOG_NET_PEER_DKG;
+use fedimint_server_core::dashboard_ui::P2PConnectionStatus;
+use tokio::sync::watch;
+use tracing::{info, warn};
+
+use super::ConfigGenParams;
+use crate::net::p2p::P2PStatusReceivers;
+
+/// How often we check the inbox for newly imported messages
+const INBOX_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+const OUTBOX_DIR: &str = "outbox";
+const INBOX_DIR: &str = "inbox";
+const MESSAGE_FILE_EXTENSION: &str = "dkg";
+
+/// A single DKG message exchanged between two guardians as a file
+#[derive(Debug, Clone, PartialEq, Eq, Encodable, Decodable)]
+pub struct OfflineDkgMessage {
+    /// Hash of the setup codes of all guardians, which prevents messages of
+    /// different ceremonies from being mixed up
+    pub ceremony: sha256::Hash,
+    /// Random nonce of the sender's attempt to run the DKG, which prevents
+    /// messages of a previous attempt with the same setup codes from being
+    /// mixed in
+    pub run: [u8; 16],
+    pub sender: PeerId,
+    pub recipient: PeerId,
+    /// Position of the message in the stream from sender to recipient
+    pub sequence: u64,
+    pub message: P2PMessage,
+    /// Signature by the sender's iroh p2p key over all of the above
+    pub signature: [u8; 64],
+}
+
+impl OfflineDkgMessage {
+    fn new(
+        secret_key: &iroh::SecretKey,
+        ceremony: sha256::Hash,
+        run: [u8; 16],
+        sender: PeerId,
+        recipient: PeerId,
+        sequence: u64,
+        message: P2PMessage,
+    ) -> Self {
+        let signature = secret_key
+            .sign(&Self::signed_bytes(
+                ceremony, run, sender, recipient, sequence, &message,
+            ))
+            .to_bytes();
+
+        Self {
+            ceremony,
+            run,
+            sender,
+            recipient,
+            sequence,
+            message,
+            signature,
+        }
+    }
+
+    fn signed_bytes(
+        ceremony: sha256::Hash,
+        run: [u8; 16],
+        sender: PeerId,
+        recipient: PeerId,
+        sequence: u64,
+        message: &P2PMessage,
+    ) -> Vec<u8> {
+        (ceremony, run, sender, recipient, sequence, message.clone()).consensus_encode_to_vec()
+    }
+
+    /// Checks that the message belongs to the ceremony of the given setup
+    /// codes and was signed by the iroh p2p key of its sender
+    pub fn verify(&self, params: &ConfigGenParams) -> anyhow::Result<()> {
+        ensure!(
+            self.ceremony == params.peers.consensus_hash_sha256(),
+            "Message belongs to a different ceremony"
+        );
+
+        let endpoints = params.iroh_endpoints();
+
+        let sender_pk = endpoints
+            .get(&self.sender)
+            .with_context(|| format!("Unknown sender {}", self.sender))?
+            .p2p_pk;
+
+        ensure!(
+            endpoints.contains_key(&self.recipient),
+            "Unknown recipient {}",
+            self.recipient
+        );
+
+        sender_pk
+            .verify(
+                &Self::signed_bytes(
+                    self.ceremony,
+                    self.run,
+                    self.sender,
+                    self.recipient,
+                    self.sequence,
+                    &self.message,
+                ),
+                &iroh_base::Signature::from_bytes(&self.signature),
+            )
+            .context("Invalid signature")
+    }
+
+    /// The name under which the message is stored in an outbox or inbox
+    pub fn file_name(&self) -> String {
+        message_file_name(self.ceremony, self.sender, self.recipient, self.sequence)
+    }
+
+    /// Splits the message into fountain-coded fragments, for example to be
+    /// displayed as a sequence of QR codes. The receiving guardian decodes
+    /// them with a [`FountainDecoder`] and imports the result via
+    /// [`OfflineDkgMessage::import`].
+    pub fn fountain_encoder(&self, max_fragment_length: usize) -> FountainEncoder {
+        FountainEncoder::new(self.clone(), max_fragment_length)
+    }
+
+    /// Places the message into the inbox of the offline DKG directory `dir` of
+    /// the recipient. The running DKG verifies the signature once it reads
+    /// the message.
+    pub fn import(&self, dir: &Path) -> anyhow::Result<()> {
+        let inbox = dir.join(INBOX_DIR);
+
+        ensure!(
+            inbox.is_dir(),
+            "{} does not exist, start the offline DKG before importing messages",
+            inbox.display()
+        );
+
+        let path = inbox.join(self.file_name());
+
+        // A message of a previous attempt would otherwise silently replace the
+        // current one or vice versa
+        if path.exists() {
+            ensure!(
+                Self::read_from(&path)? == *self,
+                "A different message was imported as {} already",
+                path.display()
+            );
+
+            return Ok(());
+        }
+
+        write_atomically(&path, &self.consensus_encode_to_vec())
+    }
+
+    /// Reads a message from a file exported by another guardian
+    pub fn read_from(path: &Path) -> anyhow::Result<Self> {
+        let bytes =
+            std::fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+
+        Self::consensus_decode_whole(&bytes, &ModuleDecoderRegistry::default())
+            .with_context(|| format!("Failed to decode {}", path.display()))
+    }
+}
+
+/// Returns `count` hex encoded fountain fragments of the message exported to
+/// `path`, each of which is short enough to be displayed as a QR code. If
+/// `count` is `None` we return twice the minimal number of fragments, which
+/// almost always suffices to decode the message.
+pub fn export_fragments(
+    path: &Path,
+    max_fragment_length: usize,
+    count: Option<usize>,
+) -> anyhow::Result<Vec<String>> {
+    ensure!(max_fragment_length > 0, "Fragments can not be empty");
+
+    let message = OfflineDkgMessage::read_from(path)?;
+
+    let count = count.unwrap_or_else(|| {
+        2 * message
+            .consensus_encode_to_vec()
+            .len()
+            .div_ceil(max_fragment_length)
+    });
+
+    let mut encoder = message.fountain_encoder(max_fragment_length);
+
+    Ok((0..count)
+        .map(|_| encoder.next_fragment().consensus_encode_to_hex())
+        .collect())
+}
+
+/// Decodes hex encoded fountain fragments as returned by [`export_fragments`]
+/// until the message is complete and imports it into the inbox of the offline
+/// DKG directory `dir`
+pub fn import_fragments(
+    dir: &Path,
+    fragments: impl IntoIterator<Item = String>,
+) -> anyhow::Result<OfflineDkgMessage> {
+    let mut decoder = FountainDecoder::<OfflineDkgMessage>::default();
+
+    for fragment in fragments {
+        let fragment = fragment.trim();
+
+        if fragment.is_empty() {
+            continue;
+        }
+
+        let fragment = Fragment::consensus_decode_hex(fragment, &ModuleDecoderRegistry::default())
+            .context("Failed to decode fragment")?;
+
+        if let Some(message) = decoder.add_fragment(&fragment) {
+            message.import(dir)?;
+
+            return Ok(message);
+        }
+    }
+
+    bail!("Not enough fragments to decode the message")
+}
+
+fn message_file_name(
+    ceremony: sha256::Hash,
+    sender: PeerId,
+    recipient: PeerId,
+    sequence: u64,
+) -> String {
+    format!("{ceremony}-{sender}-{recipient}-{sequence:08}.{MESSAGE_FILE_EXTENSION}")
+}
+
+/// Writes to a temporary file first so a guardian copying files out of the
+/// directory never picks up a partially written message
+fn write_atomically(path: &Path, bytes: &[u8]) -> anyhow::Result<()> {
+    let tmp_path = path.with_extension("tmp");
+
+    std::fs::write(&tmp_path, bytes)
+        .with_context(|| format!("Failed to write {}", tmp_path.display()))?;
+
+    std::fs::rename(&tmp_path, path)
+        .with_context(|| format!("Failed to rename {}", tmp_path.display()))
+}
+
+/// Connections which exchange the DKG messages as signed files
+///
+/// Outgoing messages are written to `<dir>/outbox`, incoming messages are
+/// expected in `<dir>/inbox` and are only accepted with a valid signature of
+/// their sender.
+pub struct FileP2PConnections {
+    params: ConfigGenParams,
+    secret_key: iroh::SecretKey,
+    ceremony: sha256::Hash,
+    run: [u8; 16],
+    dir: PathBuf,
+    send_sequence: Mutex<BTreeMap<PeerId, u64>>,
+    receive_sequence: Mutex<BTreeMap<PeerId, u64>>,
+    /// The attempt of every peer we accepted the first message of
+    peer_runs: Mutex<BTreeMap<PeerId, [u8; 16]>>,
+}
+
+impl FileP2PConnections {
+    pub fn new(params: &ConfigGenParams, dir: PathBuf) -> anyhow::Result<Self> {
+        let Some(secret_key) = params.iroh_p2p_sk.clone() else {
+            bail!("Offline DKG requires iroh keys to sign the exchanged messages");
+        };
+
+        if params.iroh_endpoints().len() != params.peers.len() {
+            bail!("Offline DKG requires all guardians to use iroh setup codes");
+        }
+
+        for sub_dir in [OUTBOX_DIR, INBOX_DIR] {
+            std::fs::create_dir_all(dir.join(sub_dir))
+                .with_context(|| format!("Failed to create {}", dir.join(sub_dir).display()))?;
+
+            // Messages of a previous attempt must not be carried to or read by
+            // the other guardians
+            let stale = std::fs::read_dir(dir.join(sub_dir))
+                .with_context(|| format!("Failed to read {}", dir.join(sub_dir).display()))?
+                .filter_map(Result::ok)
+                .any(|entry| {
+                    entry
+                        .path()
+                        .extension()
+                        .is_some_and(|e| e == MESSAGE_FILE_EXTENSION)
+                });
+
+            ensure!(
+                !stale,
+                "{} contains messages of a previous attempt, please remove them",
+                dir.join(sub_dir).display()
+            );
+        }
+
+        info!(
+            target: LOG_NET_PEER_DKG,
+            dir = %dir.display(),
+            "Running offline DKG, carry the files in the outbox to their recipient with fedimint-offline-dkg"
+        );
+
+        Ok(Self {
+            params: params.clone(),
+            secret_key,
+            ceremony: params.peers.consensus_hash_sha256(),
+            run: rand::random(),
+            dir,
+            send_sequence: Mutex::new(BTreeMap::new()),
+            receive_sequence: Mutex::new(BTreeMap::new()),
+            peer_runs: Mutex::new(BTreeMap::new()),
+        })
+    }
+
+    fn send_to_peer(&self, recipient: PeerId, message: P2PMessage) {
+        let sequence = {
+            let mut send_sequence = self.send_sequence.lock().expect("locking failed");
+            let next = send_sequence.entry(recipient).or_default();
+            let sequence = *next;
+            *next += 1;
+            sequence
+        };
+
+        let message = OfflineDkgMessage::new(
+            &self.secret_key,
+            self.ceremony,
+            self.run,
+            self.params.identity,
+            recipient,
+            sequence,
+            message,
+        );
+
+        let path = self.dir.join(OUTBOX_DIR).join(message.file_name());
+
+        if let Err(e) = write_atomically(&path, &message.consensus_encode_to_vec()) {
+            warn!(target: LOG_NET_PEER_DKG, err = %e, "Failed to export DKG message");
+        }
+    }
+
+    /// Takes the next message from peer out of the inbox if it has been
+    /// imported already
+    fn try_receive_from_peer(&self, peer: PeerId) -> Option<P2PMessage> {
+        let mut receive_sequence = self.receive_sequence.lock().expect("locking failed");
+        let sequence = receive_sequence.entry(peer).or_default();
+
+        let path = self.dir.join(INBOX_DIR).join(message_file_name(
+            self.ceremony,
+            peer,
+            self.params.identity,
+            *sequence,
+        ));
+
+        if !path.exists() {
+            return None;
+        }
+
+        let message = OfflineDkgMessage::read_from(&path).and_then(|message| {
+            message.verify(&self.params)?;
+
+            ensure!(
+                message.sender == peer
+                    && message.recipient == self.params.identity
+                    && message.sequence == *sequence,
+                "Message does not match its file name"
+            );
+
+            let mut peer_runs = self.peer_runs.lock().expect("locking failed");
+
+            ensure!(
+                *peer_runs.entry(peer).or_insert(message.run) == message.run,
+                "Message belongs to a previous attempt of the DKG"
+            );
+
+            Ok(message)
+        });
+
+        match message {
+            Ok(message) => {
+                *sequence += 1;
+
+                Some(message.message)
+            }
+            Err(e) => {
+                warn!(
+                    target: LOG_NET_PEER_DKG,
+                    err = %e,
+                    path = %path.display(),
+                    "Rejected DKG message, please import it again"
+                );
+
+                std::fs::rename(&path, path.with_extension("rejected")).ok();
+
+                None
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl IP2PConnections<P2PMessage> for FileP2PConnections {
+    fn send(&self, recipient: Recipient, msg: P2PMessage) {
+        match recipient {
+            Recipient::Everyone => {
+                for peer in self.params.peer_ids() {
+                    if peer != self.params.identity {
+                        self.send_to_peer(peer, msg.clone());
+                    }
+                }
+            }
+            Recipient::Peer(peer) => self.send_to_peer(peer, msg),
+        }
+    }
+
+    async fn receive(&self) -> Option<(PeerId, P2PMessage)> {
+        loop {
+            for peer in self.params.peer_ids() {
+                if peer == self.params.identity {
+                    continue;
+                }
+
+                if let Some(message) = self.try_receive_from_peer(peer) {
+                    return Some((peer, message));
+                }
+            }
+
+            sleep(INBOX_POLL_INTERVAL).await;
+        }
+    }
+
+    async fn receive_from_peer(&self, peer: PeerId) -> Option<P2PMessage> {
+        loop {
+            if let Some(message) = self.try_receive_from_peer(peer) {
+                return Some(message);
+            }
+
+            sleep(INBOX_POLL_INTERVAL).await;
+        }
+    }
+}
+
+/// Status receivers reporting all peers as connected, since the offline DKG
+/// does not need to wait for any connection to be established
+pub fn offline_status_receivers(peers: Vec<PeerId>) -> P2PStatusReceivers {
+    peers
+        .into_iter()
+        .map(|peer| {
+            let (_, receiver) = watch::channel(Some(P2PConnectionStatus {
+                conn_type: None,
+                rtt: None,
+            }));
+
+            (peer, receiver)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{BTreeMap, BTreeSet};
+    use std::path::PathBuf;
+
+    use fedimint_core::PeerId;
+    use fedimint_core::config::P2PMessage;
+    use fedimint_core::encoding::Encodable;
+    use fedimint_core::module::ApiAuth;
+    use fedimint_core::net::peers::IP2PConnections;
+    use fedimint_core::setup_code::{PeerEndpoints, PeerSetupCode};
+    use fedimint_fountain::FountainDecoder;
+    use fedimint_server_core::ServerModuleInitRegistry;
+    use futures::future::join_all;
+    use rand::rngs::OsRng;
+
+    use super::{
+        FileP2PConnections, INBOX_DIR, OUTBOX_DIR, OfflineDkgMessage, export_fragments,
+        import_fragments, offline_status_receivers,
+    };
+    use crate::config::{ConfigGenParams, ServerConfig};
+
+    fn config_gen_params(num_peers: u16) -> Vec<ConfigGenParams> {
+        let keys = (0..num_peers)
+            .map(|_| {
+                (
+                    iroh::SecretKey::generate(&mut OsRng),
+                    iroh::SecretKey::generate(&mut OsRng),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        let peers = keys
+            .iter()
+            .enumerate()
+            .map(|(i, (api_sk, p2p_sk))| {
+                let setup_code = PeerSetupCode {
+                    name: format!("peer-{i}"),
+                    endpoints: PeerEndpoints::Iroh {
+                        api_pk: api_sk.public(),
+                        p2p_pk: p2p_sk.public(),
+                    },
+                    federation_name: Some("offline".to_string()),
+                    disable_base_fees: None,
+                    enabled_modules: None,
+                    federation_size: None,
+                };
+
+                (PeerId::from(i as u16), setup_code)
+            })
+            .collect::<BTreeMap<PeerId, PeerSetupCode>>();
+
+        keys.into_iter()
+            .enumerate()
+            .map(|(i, (api_sk, p2p_sk))| ConfigGenParams {
+                identity: PeerId::from(i as u16),
+                tls_key: None,
+                iroh_api_sk: Some(api_sk),
+                iroh_p2p_sk: Some(p2p_sk),
+                api_auth: ApiAuth::new("pass".to_string()),
+                peers: peers.clone(),
+                meta: BTreeMap::new(),
+                disable_base_fees: false,
+                enabled_modules: BTreeSet::new(),
+                network: bitcoin::Network::Regtest,
+            })
+            .collect()
+    }
+
+    /// Plays the role of the guardians carrying files between the machines
+    fn carry_files(dirs: &[PathBuf]) {
+        for dir in dirs {
+            for entry in std::fs::read_dir(dir.join(OUTBOX_DIR)).unwrap() {
+                let path = entry.unwrap().path();
+
+                let Ok(message) = OfflineDkgMessage::read_from(&path) else {
+                    continue;
+                };
+
+                let inbox = dirs[message.recipient.to_usize()].join(INBOX_DIR);
+
+                if !inbox.join(message.file_name()).exists() {
+                    std::fs::copy(&path, inbox.join(message.file_name())).unwrap();
+                }
+            }
+        }
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn test_offline_dkg_produces_identical_configs() {
+        let params = config_gen_params(4);
+
+        let tmp = tempfile::tempdir().unwrap();
+
+        let dirs = params
+            .iter()
+            .map(|p| tmp.path().join(p.identity.to_string()))
+            .collect::<Vec<_>>();
+
+        let dkgs = params.iter().zip(dirs.clone()).map(|(p, dir)| async move {
+            ServerConfig::distributed_gen(
+                p,
+                ServerModuleInitRegistry::default(),
+                "test".to_string(),
+                FileP2PConnections::new(p, dir).unwrap().into_dyn(),
+                offline_status_receivers(p.peer_ids()),
+            )
+            .await
+        });
+
+        let courier = tokio::spawn(async move {
+            loop {
+                carry_files(&dirs);
+
+                fedimint_core::task::sleep(std::time::Duration::from_millis(100)).await;
+            }
+        });
+
+        let configs = join_all(dkgs)
+            .await
+            .into_iter()
+            .collect::<anyhow::Result<Vec<ServerConfig>>>()
+            .unwrap();
+
+        courier.abort();
+
+        for cfg in &configs {
+            assert_eq!(
+                cfg.consensus.consensus_hash_sha256(),
+                configs[0].consensus.consensus_hash_sha256()
+            );
+        }
+    }
+
+    fn checksum_message(
+        params: &[ConfigGenParams],
+        run: [u8; 16],
+        sequence: u64,
+    ) -> OfflineDkgMessage {
+        OfflineDkgMessage::new(
+            params[0].iroh_p2p_sk.as_ref().unwrap(),
+            params[0].peers.consensus_hash_sha256(),
+            run,
+            PeerId::from(0),
+            PeerId::from(1),
+            sequence,
+            P2PMessage::Checksum(params[0].peers.consensus_hash_sha256()),
+        )
+    }
+
+    #[test]
+    fn test_offline_dkg_message_verification() {
+        let params = config_gen_params(4);
+
+        let message = checksum_message(&params, [0; 16], 0);
+
+        message.verify(&params[1]).unwrap();
+
+        let mut decoder = FountainDecoder::<OfflineDkgMessage>::default();
+        let mut encoder = message.fountain_encoder(32);
+
+        let decoded = loop {
+            if let Some(decoded) = decoder.add_fragment(&encoder.next_fragment()) {
+                break decoded;
+            }
+        };
+
+        assert_eq!(decoded, message);
+
+        let mut forged = message.clone();
+        forged.sender = PeerId::from(2);
+        assert!(forged.verify(&params[1]).is_err());
+
+        let mut tampered = message.clone();
+        tampered.sequence = 1;
+        assert!(tampered.verify(&params[1]).is_err());
+
+        let mut replayed = message.clone();
+        replayed.run = [1; 16];
+        assert!(replayed.verify(&params[1]).is_err());
+    }
+
+    #[test]
+    fn test_offline_dkg_message_export_and_import() {
+        let params = config_gen_params(4);
+
+        let message = checksum_message(&params, [0; 16], 0);
+
+        let tmp = tempfile::tempdir().unwrap();
+        let exported = tmp.path().join(message.file_name());
+        std::fs::write(&exported, message.consensus_encode_to_vec()).unwrap();
+
+        let fragments = export_fragments(&exported, 32, None).unwrap();
+        assert!(fragments.len() > 2);
+
+        let dir = tmp.path().join("recipient");
+
+        // The inbox is created by the running DKG
+        assert!(import_fragments(&dir, fragments.clone()).is_err());
+
+        std::fs::create_dir_all(dir.join(INBOX_DIR)).unwrap();
+
+        assert_eq!(import_fragments(&dir, fragments.clone()).unwrap(), message);
+        assert_eq!(
+            OfflineDkgMessage::read_from(&dir.join(INBOX_DIR).join(message.file_name())).unwrap(),
+            message
+        );
+
+        // Importing the same message twice is harmless
+        assert_eq!(import_fragments(&dir, fragments).unwrap(), message);
+
+        // A message of a previous attempt does not replace the current one
+        assert!(checksum_message(&params, [1; 16], 0).import(&dir).is_err());
+
+        assert!(import_fragments(&dir, Vec::new()).is_err());
+    }
+
+    #[test]
+    fn test_offline_dkg_rejects_messages_of_previous_attempts() {
+        let params = config_gen_params(4);
+
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().to_path_buf();
+
+        let connections = FileP2PConnections::new(&params[1], dir.clone()).unwrap();
+
+        checksum_message(&params, [0; 16], 0).import(&dir).unwrap();
+
+        assert!(connections.try_receive_from_peer(PeerId::from(0)).is_some());
+
+        let stale = checksum_message(&params, [1; 16], 1);
+        stale.import(&dir).unwrap();
+
+        assert!(connections.try_receive_from_peer(PeerId::from(0)).is_none());
+        assert!(
+            dir.join(INBOX_DIR)
+                .join(stale.file_name())
+                .with_extension("rejected")
+                .exists()
+        );
+
+        // We refuse to start another attempt while the files of this one exist
+        assert!(FileP2PConnections::new(&params[1], dir).is_err());
+    }
+}
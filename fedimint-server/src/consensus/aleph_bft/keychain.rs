@@ -1,12 +1,19 @@
 use std::collections::BTreeMap;
 use std::io::Write;
+use std::time::Duration;
 
 use aleph_bft::Keychain as KeychainTrait;
 use bitcoin::hashes::Hash;
 use fedimint_core::encoding::Encodable;
+use fedimint_core::runtime::{block_in_place, block_on, sleep};
+use fedimint_core::util::FmtCompactAnyhow as _;
 use fedimint_core::{NumPeersExt, PeerId, secp256k1};
+use fedimint_logging::LOG_CONSENSUS;
+use fedimint_server_core::guardian_signer::{DynGuardianSigner, GuardianKey, GuardianSignerExt};
+use futures::FutureExt as _;
 use secp256k1::hashes::sha256;
-use secp256k1::{Keypair, Message, PublicKey, schnorr};
+use secp256k1::{Message, PublicKey, schnorr};
+use tracing::warn;
 
 use crate::config::ServerConfig;
 
@@ -15,26 +22,23 @@ pub struct Keychain {
     identity: PeerId,
     pks: BTreeMap<PeerId, PublicKey>,
     message_tag: sha256::Hash,
-    keypair: Keypair,
+    signer: DynGuardianSigner,
 }
 
 impl Keychain {
-    pub fn new(cfg: &ServerConfig) -> Self {
+    pub fn new(cfg: &ServerConfig, signer: DynGuardianSigner) -> Self {
         Keychain {
             identity: cfg.local.identity,
             pks: cfg.consensus.broadcast_public_keys.clone(),
             message_tag: cfg.consensus.broadcast_public_keys.consensus_hash(),
-            keypair: cfg
-                .private
-                .broadcast_secret_key
-                .keypair(secp256k1::SECP256K1),
+            signer,
         }
     }
 
     // Tagging messages with the hash of the public key set ensures that peers with
     // an incorrect public key set cannot create signatures that are accepted by
     // their peers.
-    fn tagged_message(&self, message: &[u8]) -> Message {
+    fn tagged_digest(&self, message: &[u8]) -> [u8; 32] {
         let mut engine = sha256::HashEngine::default();
 
         engine
@@ -45,13 +49,46 @@ fn tagged_message(&self, message: &[u8]) -> Message {
             .write_all(message)
             .expect("Writing to a hash engine can not fail");
 
-        let hash = sha256::Hash::from_engine(engine);
+        sha256::Hash::from_engine(engine).to_byte_array()
+    }
 
-        Message::from_digest(*hash.as_ref())
+    fn tagged_message(&self, message: &[u8]) -> Message {
+        Message::from_digest(self.tagged_digest(message))
     }
 
-    pub fn sign_schnorr(&self, message: &[u8]) -> schnorr::Signature {
-        self.keypair.sign_schnorr(self.tagged_message(message))
+    // Consensus can not make progress without our signature, so if the signer is
+    // unavailable we keep retrying until it is back.
+    pub async fn sign_schnorr(&self, message: &[u8]) -> schnorr::Signature {
+        let digest = self.tagged_digest(message);
+
+        loop {
+            match self
+                .signer
+                .sign_schnorr(GuardianKey::Broadcast, digest)
+                .await
+            {
+                Ok(signature) if self.verify_schnorr(message, &signature, self.identity) => {
+                    return signature;
+                }
+                Ok(..) => {
+                    warn!(
+                        target: LOG_CONSENSUS,
+                        "Guardian signer returned an invalid signature for our broadcast key, retrying..."
+                    );
+
+                    sleep(Duration::from_secs(1)).await;
+                }
+                Err(e) => {
+                    warn!(
+                        target: LOG_CONSENSUS,
+                        err = %e.fmt_compact_anyhow(),
+                        "Failed to sign with our broadcast key, retrying..."
+                    );
+
+                    sleep(Duration::from_secs(1)).await;
+                }
+            }
+        }
     }
 
     pub fn verify_schnorr(
@@ -88,7 +125,17 @@ fn node_count(&self) -> aleph_bft::NodeCount {
     }
 
     fn sign(&self, message: &[u8]) -> Self::Signature {
-        self.sign_schnorr(message).serialize()
+        let mut signature = Box::pin(self.sign_schnorr(message));
+
+        // A local signer completes without yielding, which also allows the
+        // simulation to run the consensus on a current thread runtime
+        if let Some(signature) = signature.as_mut().now_or_never() {
+            return signature.serialize();
+        }
+
+        // The trait is synchronous while the guardian signer may have to talk to
+        // another process, so we block this worker thread instead of the runtime
+        block_in_place(|| block_on(signature)).serialize()
     }
 
     fn verify(
@@ -55,12 +55,13 @@
     SerdeTransaction, Transaction, TransactionError, TransactionSubmissionOutcome,
 };
 use fedimint_core::util::{FmtCompact, SafeUrl};
-use fedimint_core::{ChainId, OutPoint, OutPointRange, PeerId, TransactionId, secp256k1};
+use fedimint_core::{ChainId, OutPoint, OutPointRange, PeerId, TransactionId};
 use fedimint_logging::LOG_NET_API;
 use fedimint_server_core::bitcoin_rpc::ServerBitcoinRpcMonitor;
 use fedimint_server_core::dashboard_ui::{
     IDashboardApi, P2PConnectionStatus, ServerBitcoinRpcStatus,
 };
+use fedimint_server_core::guardian_signer::DynGuardianSigner;
 use fedimint_server_core::{DynServerModule, ServerModuleRegistry, ServerModuleRegistryExt};
 use futures::StreamExt;
 use tokio::sync::watch::{self, Receiver, Sender};
@@ -76,7 +77,10 @@
 use crate::consensus::transaction::{TxProcessingMode, process_transaction_with_dbtx};
 use crate::metrics::{BACKUP_WRITE_SIZE_BYTES, STORED_BACKUPS_COUNT};
 use crate::net::api::HasApiContext;
-use crate::net::api::announcement::{ApiAnnouncementKey, ApiAnnouncementPrefix};
+use crate::net::api::announcement::{
+    ApiAnnouncementKey, ApiAnnouncementPrefix, sign_api_announcement,
+};
+use crate::net::api::guardian_metadata::sign_guardian_metadata;
 use crate::net::p2p::P2PStatusReceivers;
 
 #[derive(Clone)]
@@ -103,6 +107,8 @@ pub struct ConsensusApi {
     pub supported_api_versions: SupportedApiVersionsSummary,
     pub code_version_str: String,
     pub task_group: TaskGroup,
+    /// Signs with our broadcast key, which might be held by a remote signer
+    pub guardian_signer: DynGuardianSigner,
 }
 
 impl ConsensusApi {
@@ -525,7 +531,10 @@ async fn submit_api_announcement(
             })
     }
 
-    async fn sign_api_announcement(&self, new_url: SafeUrl) -> SignedApiAnnouncement {
+    async fn sign_api_announcement(
+        &self,
+        new_url: SafeUrl,
+    ) -> Result<SignedApiAnnouncement, ApiError> {
         self.db
             .autocommit(
                 |dbtx, _| {
@@ -539,9 +548,14 @@ async fn sign_api_announcement(&self, new_url: SafeUrl) -> SignedApiAnnouncement
                             api_url: new_url_inner,
                             nonce: new_nonce,
                         };
-                        let ctx = secp256k1::Secp256k1::new();
-                        let signed_announcement = announcement
-                            .sign(&ctx, &self.cfg.private.broadcast_secret_key.keypair(&ctx));
+                        let signed_announcement =
+                            sign_api_announcement(&self.guardian_signer, &announcement)
+                                .await
+                                .map_err(|e| {
+                                    ApiError::server_error(format!(
+                                        "Failed to sign announcement: {e}"
+                                    ))
+                                })?;
 
                         dbtx.insert_entry(
                             &ApiAnnouncementKey(self.cfg.local.identity),
@@ -549,13 +563,18 @@ async fn sign_api_announcement(&self, new_url: SafeUrl) -> SignedApiAnnouncement
                         )
                         .await;
 
-                        Result::<_, ()>::Ok(signed_announcement)
+                        Result::<_, ApiError>::Ok(signed_announcement)
                     })
                 },
                 None,
             )
             .await
-            .expect("Will not terminate on error")
+            .map_err(|e| match e {
+                fedimint_core::db::AutocommitError::ClosureError { error, .. } => error,
+                fedimint_core::db::AutocommitError::CommitFailed { last_error, .. } => {
+                    ApiError::server_error(format!("Database commit failed: {last_error}"))
+                }
+            })
     }
 
     async fn guardian_metadata_list(
@@ -621,12 +640,12 @@ async fn submit_guardian_metadata(
     async fn sign_guardian_metadata(
         &self,
         new_metadata: fedimint_core::net::guardian_metadata::GuardianMetadata,
-    ) -> fedimint_core::net::guardian_metadata::SignedGuardianMetadata {
+    ) -> Result<fedimint_core::net::guardian_metadata::SignedGuardianMetadata, ApiError> {
         use crate::net::api::guardian_metadata::GuardianMetadataKey;
 
-        let ctx = secp256k1::Secp256k1::new();
-        let signed_metadata =
-            new_metadata.sign(&ctx, &self.cfg.private.broadcast_secret_key.keypair(&ctx));
+        let signed_metadata = sign_guardian_metadata(&self.guardian_signer, &new_metadata)
+            .await
+            .map_err(|e| ApiError::server_error(format!("Failed to sign metadata: {e}")))?;
 
         self.db
             .autocommit(
@@ -645,7 +664,9 @@ async fn sign_guardian_metadata(
                 None,
             )
             .await
-            .expect("Will not terminate on error")
+            .expect("Will not terminate on error");
+
+        Ok(signed_metadata)
     }
 
     /// Changes the guardian password by re-encrypting the private config and
@@ -1047,7 +1068,7 @@ pub fn server_endpoints() -> Vec<ApiEndpoint<ConsensusApi>> {
             ApiVersion::new(0, 3),
             async |fedimint: &ConsensusApi, context, new_url: SafeUrl| -> SignedApiAnnouncement {
                 check_auth(context)?;
-                Ok(fedimint.sign_api_announcement(new_url).await)
+                fedimint.sign_api_announcement(new_url).await
             }
         },
         api_endpoint! {
@@ -1069,7 +1090,7 @@ pub fn server_endpoints() -> Vec<ApiEndpoint<ConsensusApi>> {
             ApiVersion::new(0, 9),
             async |fedimint: &ConsensusApi, context, metadata: fedimint_core::net::guardian_metadata::GuardianMetadata| -> fedimint_core::net::guardian_metadata::SignedGuardianMetadata {
                 check_auth(context)?;
-                Ok(fedimint.sign_guardian_metadata(metadata).await)
+                fedimint.sign_guardian_metadata(metadata).await
             }
         },
         api_endpoint! {
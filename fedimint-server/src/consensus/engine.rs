@@ -1,7 +1,7 @@
 use std::collections::BTreeMap;
 use std::fs;
 use std::path::{Path, PathBuf};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
 
 use aleph_bft::Keychain as KeychainTrait;
@@ -12,7 +12,7 @@
 use fedimint_core::config::P2PMessage;
 use fedimint_core::core::{DynOutput, MODULE_INSTANCE_ID_GLOBAL};
 use fedimint_core::db::{Database, DatabaseTransaction, IDatabaseTransactionOpsCoreTyped};
-use fedimint_core::encoding::Decodable;
+use fedimint_core::encoding::{Decodable, Encodable as _};
 use fedimint_core::endpoint_constants::AWAIT_SIGNED_SESSION_OUTCOME_ENDPOINT;
 use fedimint_core::epoch::ConsensusItem;
 use fedimint_core::module::audit::Audit;
@@ -25,11 +25,13 @@
 use fedimint_core::task::{TaskGroup, TaskHandle, sleep};
 use fedimint_core::timing::TimeReporter;
 use fedimint_core::util::{FmtCompact as _, FmtCompactAnyhow as _};
-use fedimint_core::{NumPeers, NumPeersExt, PeerId, timing};
+use fedimint_core::{BitcoinHash as _, NumPeers, NumPeersExt, PeerId, timing};
+use fedimint_server_core::guardian_signer::DynGuardianSigner;
 use fedimint_server_core::{ServerModuleRegistry, ServerModuleRegistryExt};
 use futures::StreamExt;
-use rand::Rng;
+use rand::rngs::StdRng;
 use rand::seq::IteratorRandom;
+use rand::{Rng, SeedableRng};
 use tokio::sync::watch;
 use tracing::{Level, debug, error, info, instrument, trace, warn};
 
@@ -72,6 +74,10 @@ pub struct ConsensusEngine {
     pub task_group: TaskGroup,
     pub data_dir: PathBuf,
     pub db_checkpoint_retention: u64,
+    pub guardian_signer: DynGuardianSigner,
+    /// Seeds the randomness of the broadcast schedule, such that a simulation
+    /// of the federation can be replayed
+    pub rng_seed: Option<u64>,
 }
 
 impl ConsensusEngine {
@@ -127,7 +133,9 @@ pub async fn run_single_guardian(&self, task_handle: TaskHandle) -> anyhow::Resu
             };
 
             let header = session_outcome.header(session_index);
-            let signature = Keychain::new(&self.cfg).sign_schnorr(&header);
+            let signature = Keychain::new(&self.cfg, self.guardian_signer.clone())
+                .sign_schnorr(&header)
+                .await;
             let signatures = BTreeMap::from_iter([(self.identity(), signature)]);
 
             self.complete_session(
@@ -227,18 +235,33 @@ pub async fn run_session(
 
         let mut delay_config = aleph_bft::default_delay_config();
 
+        let rng = Mutex::new(self.session_rng(session_index));
+
         delay_config.unit_creation_delay = Arc::new(move |round_index| {
             let delay = if round_index == 0 {
                 0.0
             } else {
                 round_delay
                     * BASE.powf(round_index.saturating_sub(rounds_per_session as usize) as f64)
-                    * rand::thread_rng().gen_range(0.5..=1.5)
+                    * rng.lock().expect("locking failed").gen_range(0.5..=1.5)
             };
 
             Duration::from_millis(delay.round() as u64)
         });
 
+        if self.rng_seed.is_some() {
+            // Aleph bft draws from the thread local rng to pick the peers it requests
+            // missing units from and to jitter the rebroadcast of its units. Requesting
+            // from all peers and pinning the rebroadcast interval makes these draws
+            // irrelevant for the schedule of the broadcast.
+            let num_other_peers = self.num_peers().total() - 1;
+
+            delay_config.unit_rebroadcast_interval_max =
+                delay_config.unit_rebroadcast_interval_min + Duration::from_millis(1);
+            delay_config.coord_request_recipients = Arc::new(move |_| num_other_peers);
+            delay_config.parent_request_recipients = Arc::new(move |_| num_other_peers);
+        }
+
         let config = aleph_bft::create_config(
             self.num_peers().total().into(),
             self.identity().to_usize().into(),
@@ -285,7 +308,7 @@ pub async fn run_session(
                     signatures_sender,
                     self.db.clone(),
                 ),
-                Keychain::new(&self.cfg),
+                Keychain::new(&self.cfg, self.guardian_signer.clone()),
                 Spawner::new(self.task_group.make_subgroup()),
                 aleph_bft::Terminator::create_root(terminator_receiver, "Terminator"),
             ),
@@ -559,9 +582,9 @@ pub async fn complete_signed_session_outcome(
             "Signing session header..."
         );
 
-        let keychain = Keychain::new(&self.cfg);
+        let keychain = Keychain::new(&self.cfg, self.guardian_signer.clone());
 
-        let our_signature = keychain.sign_schnorr(&header);
+        let our_signature = keychain.sign_schnorr(&header).await;
 
         // Send our own signature to the data provider to be submitted to AlephBFT
         #[allow(clippy::disallowed_methods)]
@@ -700,6 +723,19 @@ pub async fn complete_signed_session_outcome(
         })
     }
 
+    /// Returns the rng for the broadcast schedule of a session, which is derived
+    /// from our identity and the seed if one is set
+    fn session_rng(&self, session_index: u64) -> StdRng {
+        match self.rng_seed {
+            Some(seed) => StdRng::from_seed(
+                (seed, self.identity(), session_index)
+                    .consensus_hash_sha256()
+                    .to_byte_array(),
+            ),
+            None => StdRng::from_entropy(),
+        }
+    }
+
     /// Returns a random peer ID excluding ourselves
     #[allow(unused)]
     fn random_peer(&self) -> PeerId {
@@ -720,7 +756,7 @@ fn validate_signed_session_outcome(
             return false;
         }
 
-        let keychain = Keychain::new(&self.cfg);
+        let keychain = Keychain::new(&self.cfg, self.guardian_signer.clone());
         let header = outcome.session_outcome.header(session_index);
 
         outcome
@@ -1070,7 +1106,7 @@ async fn request_signed_session_outcome(
         index: u64,
     ) -> SignedSessionOutcome {
         let decoders = self.decoders();
-        let keychain = Keychain::new(&self.cfg);
+        let keychain = Keychain::new(&self.cfg, self.guardian_signer.clone());
         let threshold = self.num_peers().threshold();
 
         let filter_map = move |response: SerdeModuleEncoding<SignedSessionOutcome>| {
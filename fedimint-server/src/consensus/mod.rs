@@ -31,6 +31,7 @@
 use fedimint_logging::{LOG_CONSENSUS, LOG_CORE, LOG_NET_API};
 use fedimint_server_core::bitcoin_rpc::{DynServerBitcoinRpc, ServerBitcoinRpcMonitor};
 use fedimint_server_core::dashboard_ui::IDashboardApi;
+use fedimint_server_core::guardian_signer::{DynGuardianSigner, ModuleGuardianSigner};
 use fedimint_server_core::migration::apply_migrations_server_dbtx;
 use fedimint_server_core::{DynServerModule, ServerModuleInitRegistry};
 use futures::FutureExt;
@@ -80,6 +81,8 @@ pub async fn run(
     dashboard_ui_router: DashboardUiRouter,
     db_checkpoint_retention: u64,
     iroh_api_limits: ConnectionLimits,
+    guardian_signer: DynGuardianSigner,
+    rng_seed: Option<u64>,
 ) -> anyhow::Result<()> {
     cfg.validate_config(&cfg.local.identity, &module_init_registry)?;
 
@@ -160,6 +163,7 @@ pub async fn run(
                         cfg.local.identity,
                         global_api.with_module(*module_id),
                         bitcoin_rpc_connection.clone(),
+                        ModuleGuardianSigner::new(*module_id, guardian_signer.clone()),
                     )
                     .await?;
 
@@ -207,6 +211,7 @@ pub async fn run(
         force_api_secret: force_api_secrets.get_active(),
         code_version_str,
         task_group: task_group.clone(),
+        guardian_signer: guardian_signer.clone(),
     };
 
     info!(target: LOG_CONSENSUS, "Starting Consensus Api...");
@@ -309,6 +314,8 @@ pub async fn run(
         task_group: task_group.clone(),
         data_dir,
         db_checkpoint_retention,
+        guardian_signer,
+        rng_seed,
     }
     .run()
     .await?;
@@ -328,6 +335,19 @@ async fn start_consensus_api(
     force_api_secrets: ApiSecrets,
     api_bind: SocketAddr,
 ) -> ServerHandle {
+    net::api::spawn(
+        "consensus",
+        api_bind,
+        consensus_rpc_module(api),
+        cfg.max_connections,
+        force_api_secrets,
+    )
+    .await
+}
+
+/// Builds the json-rpc module dispatching requests to the core endpoints and
+/// the endpoints of our modules
+pub fn consensus_rpc_module(api: ConsensusApi) -> RpcModule<ConsensusApi> {
     let mut rpc_module = RpcModule::new(api.clone());
 
     net::api::attach_endpoints(&mut rpc_module, api::server_endpoints(), None);
@@ -336,14 +356,7 @@ async fn start_consensus_api(
         net::api::attach_endpoints(&mut rpc_module, module.api_endpoints(), Some(id));
     }
 
-    net::api::spawn(
-        "consensus",
-        api_bind,
-        rpc_module,
-        cfg.max_connections,
-        force_api_secrets,
-    )
-    .await
+    rpc_module
 }
 
 const CONSENSUS_PROPOSAL_TIMEOUT: Duration = Duration::from_secs(30);
@@ -28,7 +28,8 @@
 use std::path::{Path, PathBuf};
 use std::time::Duration;
 
-use anyhow::Context;
+use anyhow::{Context, ensure};
+use bitcoin::hashes::{Hash as _, sha256};
 use config::ServerConfig;
 use config::io::{PLAINTEXT_PASSWORD, read_server_config};
 pub use connection_limits::ConnectionLimits;
@@ -38,6 +39,7 @@
 use fedimint_core::db::{Database, DatabaseTransaction, IDatabaseTransactionOpsCoreTyped as _};
 use fedimint_core::epoch::ConsensusItem;
 use fedimint_core::net::peers::DynP2PConnections;
+use fedimint_core::secp256k1;
 use fedimint_core::task::{TaskGroup, sleep};
 use fedimint_core::util::write_new;
 use fedimint_logging::LOG_CONSENSUS;
@@ -45,6 +47,10 @@
 use fedimint_server_core::ServerModuleInitRegistry;
 use fedimint_server_core::bitcoin_rpc::DynServerBitcoinRpc;
 use fedimint_server_core::dashboard_ui::DynDashboardApi;
+use fedimint_server_core::guardian_signer::{
+    DynGuardianSigner, GuardianKey, GuardianSignerExt as _, IGuardianSigner as _,
+    RemoteGuardianSigner,
+};
 use fedimint_server_core::setup_ui::{DynSetupApi, ISetupApi};
 use jsonrpsee::RpcModule;
 use net::api::ApiSecrets;
@@ -58,6 +64,7 @@
     SALT_FILE, finalize_password_change, recover_interrupted_password_change, trim_password,
     write_server_config,
 };
+use crate::config::offline::{FileP2PConnections, offline_status_receivers};
 use crate::config::setup::SetupApi;
 use crate::db::{ServerInfo, ServerInfoKey};
 use crate::fedimint_core::net::peers::IP2PConnections;
@@ -165,8 +172,47 @@ pub async fn run(
 
     initialize_gauge_metrics(&task_group, &db).await;
 
-    start_api_announcement_service(&db, &task_group, &cfg, force_api_secrets.get_active()).await?;
-    start_guardian_metadata_service(&db, &task_group, &cfg, force_api_secrets.get_active()).await?;
+    let guardian_signer = match settings.guardian_signer_socket {
+        Some(path) => {
+            info!(target: LOG_CONSENSUS, path = %path.display(), "Using remote guardian signer");
+
+            ensure!(
+                cfg.guardian_keys(&module_init_registry)?.is_empty(),
+                "The config still contains guardian keys, export them to the remote guardian signer first"
+            );
+
+            let guardian_signer = RemoteGuardianSigner::new(path).into_dyn();
+
+            check_remote_guardian_signer(&cfg, &guardian_signer).await?;
+
+            guardian_signer
+        }
+        None => {
+            ensure!(
+                cfg.private.broadcast_secret_key.is_some(),
+                "The guardian keys have been exported, start with the socket of the remote guardian signer"
+            );
+
+            cfg.local_guardian_signer(&module_init_registry)?
+        }
+    };
+
+    start_api_announcement_service(
+        &db,
+        &task_group,
+        &cfg,
+        &guardian_signer,
+        force_api_secrets.get_active(),
+    )
+    .await?;
+    start_guardian_metadata_service(
+        &db,
+        &task_group,
+        &cfg,
+        &guardian_signer,
+        force_api_secrets.get_active(),
+    )
+    .await?;
     start_pkarr_publish_service(&db, &task_group, &cfg).await?;
 
     info!(target: LOG_CONSENSUS, "Starting consensus...");
@@ -194,6 +240,8 @@ pub async fn run(
         dashboard_ui_router,
         db_checkpoint_retention,
         iroh_api_limits,
+        guardian_signer,
+        None,
     ))
     .await?;
 
@@ -204,6 +252,32 @@ pub async fn run(
     Ok(())
 }
 
+/// Makes sure the remote guardian signer holds our broadcast key before we
+/// start consensus, since we can not check this against the config anymore
+async fn check_remote_guardian_signer(
+    cfg: &ServerConfig,
+    guardian_signer: &DynGuardianSigner,
+) -> anyhow::Result<()> {
+    let digest = sha256::Hash::hash(b"fedimint-guardian-signer-check").to_byte_array();
+
+    let signature = guardian_signer
+        .sign_schnorr(GuardianKey::Broadcast, digest)
+        .await
+        .context("Remote guardian signer failed to sign with our broadcast key")?;
+
+    secp256k1::SECP256K1
+        .verify_schnorr(
+            &signature,
+            &secp256k1::Message::from_digest(digest),
+            &cfg.consensus.broadcast_public_keys[&cfg.local.identity]
+                .x_only_public_key()
+                .0,
+        )
+        .context("Remote guardian signer does not hold our broadcast key")?;
+
+    Ok(())
+}
+
 async fn update_server_info_version_dbtx(
     dbtx: &mut DatabaseTransaction<'_>,
     code_version_str: &str,
@@ -306,6 +380,8 @@ pub async fn run_config_gen(
         .await
         .context("Failed to shutdown UI server after config gen")?;
 
+    let offline_dkg_dir = settings.offline_dkg_dir.clone();
+
     let connector = if cg_params.iroh_endpoints().is_empty() {
         TlsTcpConnector::new(
             cg_params.tls_config(),
@@ -341,12 +417,21 @@ pub async fn run_config_gen(
     )
     .into_dyn();
 
+    // The p2p connections are still needed to run consensus afterwards
+    let (dkg_connections, dkg_status_receivers) = match offline_dkg_dir {
+        Some(dir) => (
+            FileP2PConnections::new(&cg_params, dir)?.into_dyn(),
+            offline_status_receivers(cg_params.peer_ids()),
+        ),
+        None => (connections.clone(), p2p_status_receivers.clone()),
+    };
+
     let cfg = ServerConfig::distributed_gen(
         &cg_params,
         module_init_registry.clone(),
         code_version_str.clone(),
-        connections.clone(),
-        p2p_status_receivers.clone(),
+        dkg_connections,
+        dkg_status_receivers,
     )
     .await?;
 
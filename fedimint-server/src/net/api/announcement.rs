@@ -1,6 +1,7 @@
 use std::collections::BTreeMap;
 use std::time::Duration;
 
+use bitcoin::hashes::Hash as _;
 use fedimint_api_client::api::DynGlobalApi;
 use fedimint_connectors::ConnectorRegistry;
 use fedimint_core::db::{Database, IDatabaseTransactionOpsCoreTyped};
@@ -10,8 +11,11 @@
 use fedimint_core::net::guardian_metadata::SignedGuardianMetadata;
 use fedimint_core::task::{TaskGroup, sleep};
 use fedimint_core::util::{FmtCompact, SafeUrl};
-use fedimint_core::{PeerId, impl_db_lookup, impl_db_record, secp256k1};
+use fedimint_core::{PeerId, impl_db_lookup, impl_db_record};
 use fedimint_logging::LOG_NET_API;
+use fedimint_server_core::guardian_signer::{
+    DynGuardianSigner, GuardianKey, GuardianSignerExt as _,
+};
 use futures::future::join_all;
 use futures::stream::StreamExt;
 use tokio::select;
@@ -42,17 +46,19 @@ pub async fn start_api_announcement_service(
     db: &Database,
     tg: &TaskGroup,
     cfg: &ServerConfig,
+    guardian_signer: &DynGuardianSigner,
     api_secret: Option<String>,
 ) -> anyhow::Result<()> {
     const INITIAL_DEALY_SECONDS: u64 = 5;
     const FAILURE_RETRY_SECONDS: u64 = 60;
     const SUCCESS_RETRY_SECONDS: u64 = 600;
 
-    let initial_delay = if insert_signed_api_announcement_if_not_present(db, cfg).await {
-        Duration::ZERO
-    } else {
-        Duration::from_secs(INITIAL_DEALY_SECONDS)
-    };
+    let initial_delay =
+        if insert_signed_api_announcement_if_not_present(db, cfg, guardian_signer).await? {
+            Duration::ZERO
+        } else {
+            Duration::from_secs(INITIAL_DEALY_SECONDS)
+        };
 
     let db = db.clone();
     // FIXME: (@leonardo) how should we handle the connector here ?
@@ -134,14 +140,18 @@ pub async fn start_api_announcement_service(
 /// identity in the database and creates one if not.
 ///
 /// Return `true` fresh announcements were inserted because it was not present
-async fn insert_signed_api_announcement_if_not_present(db: &Database, cfg: &ServerConfig) -> bool {
+async fn insert_signed_api_announcement_if_not_present(
+    db: &Database,
+    cfg: &ServerConfig,
+    guardian_signer: &DynGuardianSigner,
+) -> anyhow::Result<bool> {
     let mut dbtx = db.begin_transaction().await;
     if dbtx
         .get_value(&ApiAnnouncementKey(cfg.local.identity))
         .await
         .is_some()
     {
-        return false;
+        return Ok(false);
     }
 
     let api_announcement = ApiAnnouncement::new(
@@ -150,9 +160,7 @@ async fn insert_signed_api_announcement_if_not_present(db: &Database, cfg: &Serv
             .clone(),
         0,
     );
-    let ctx = secp256k1::Secp256k1::new();
-    let signed_announcement =
-        api_announcement.sign(&ctx, &cfg.private.broadcast_secret_key.keypair(&ctx));
+    let signed_announcement = sign_api_announcement(guardian_signer, &api_announcement).await?;
 
     dbtx.insert_entry(
         &ApiAnnouncementKey(cfg.local.identity),
@@ -161,7 +169,23 @@ async fn insert_signed_api_announcement_if_not_present(db: &Database, cfg: &Serv
     .await;
     dbtx.commit_tx().await;
 
-    true
+    Ok(true)
+}
+
+/// Signs an announcement with our broadcast key, which might be held by a
+/// remote guardian signer
+pub async fn sign_api_announcement(
+    guardian_signer: &DynGuardianSigner,
+    api_announcement: &ApiAnnouncement,
+) -> anyhow::Result<SignedApiAnnouncement> {
+    let signature = guardian_signer
+        .sign_schnorr(
+            GuardianKey::Broadcast,
+            api_announcement.tagged_hash().to_byte_array(),
+        )
+        .await?;
+
+    Ok(api_announcement.with_signature(signature))
 }
 
 /// Returns a list of all peers and their respective API URLs taking into
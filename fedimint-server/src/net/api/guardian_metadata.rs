@@ -1,5 +1,6 @@
 use std::time::{Duration, UNIX_EPOCH};
 
+use bitcoin::hashes::Hash as _;
 use fedimint_api_client::api::DynGlobalApi;
 use fedimint_connectors::ConnectorRegistry;
 use fedimint_core::db::{Database, IDatabaseTransactionOpsCoreTyped};
@@ -8,8 +9,11 @@
 use fedimint_core::net::guardian_metadata::{GuardianMetadata, SignedGuardianMetadata};
 use fedimint_core::task::{TaskGroup, sleep};
 use fedimint_core::util::FmtCompact;
-use fedimint_core::{PeerId, impl_db_lookup, impl_db_record, secp256k1};
+use fedimint_core::{PeerId, impl_db_lookup, impl_db_record};
 use fedimint_logging::LOG_NET_API;
+use fedimint_server_core::guardian_signer::{
+    DynGuardianSigner, GuardianKey, GuardianSignerExt as _,
+};
 use futures::future::join_all;
 use futures::stream::StreamExt;
 use tokio::select;
@@ -39,17 +43,19 @@ pub async fn start_guardian_metadata_service(
     db: &Database,
     tg: &TaskGroup,
     cfg: &ServerConfig,
+    guardian_signer: &DynGuardianSigner,
     api_secret: Option<String>,
 ) -> anyhow::Result<()> {
     const INITIAL_DELAY_SECONDS: u64 = 5;
     const FAILURE_RETRY_SECONDS: u64 = 60;
     const SUCCESS_RETRY_SECONDS: u64 = 600;
 
-    let initial_delay = if insert_signed_guardian_metadata_if_not_present(db, cfg).await {
-        Duration::ZERO
-    } else {
-        Duration::from_secs(INITIAL_DELAY_SECONDS)
-    };
+    let initial_delay =
+        if insert_signed_guardian_metadata_if_not_present(db, cfg, guardian_signer).await? {
+            Duration::ZERO
+        } else {
+            Duration::from_secs(INITIAL_DELAY_SECONDS)
+        };
 
     let db = db.clone();
     let api_client = DynGlobalApi::new(
@@ -139,14 +145,18 @@ pub async fn start_guardian_metadata_service(
 /// in the database and creates one if not.
 ///
 /// Return `true` fresh metadata was inserted because it was not present
-async fn insert_signed_guardian_metadata_if_not_present(db: &Database, cfg: &ServerConfig) -> bool {
+async fn insert_signed_guardian_metadata_if_not_present(
+    db: &Database,
+    cfg: &ServerConfig,
+    guardian_signer: &DynGuardianSigner,
+) -> anyhow::Result<bool> {
     let mut dbtx = db.begin_transaction().await;
     if dbtx
         .get_value(&GuardianMetadataKey(cfg.local.identity))
         .await
         .is_some()
     {
-        return false;
+        return Ok(false);
     }
 
     let timestamp_secs = fedimint_core::time::now()
@@ -160,16 +170,30 @@ async fn insert_signed_guardian_metadata_if_not_present(db: &Database, cfg: &Ser
             .get(&cfg.local.identity)
             .map(|endpoint| vec![endpoint.url.clone()])
             .unwrap_or_default(),
-        super::pkarr_publish::pkarr_id_z32(&cfg.private.broadcast_secret_key),
+        super::pkarr_publish::pkarr_id_z32(cfg)?,
         timestamp_secs,
     );
-    let ctx = secp256k1::Secp256k1::new();
-    let signed_metadata =
-        guardian_metadata.sign(&ctx, &cfg.private.broadcast_secret_key.keypair(&ctx));
+    let signed_metadata = sign_guardian_metadata(guardian_signer, &guardian_metadata).await?;
 
     dbtx.insert_entry(&GuardianMetadataKey(cfg.local.identity), &signed_metadata)
         .await;
     dbtx.commit_tx().await;
 
-    true
+    Ok(true)
+}
+
+/// Signs guardian metadata with our broadcast key, which might be held by a
+/// remote guardian signer
+pub async fn sign_guardian_metadata(
+    guardian_signer: &DynGuardianSigner,
+    guardian_metadata: &GuardianMetadata,
+) -> anyhow::Result<SignedGuardianMetadata> {
+    let signature = guardian_signer
+        .sign_schnorr(
+            GuardianKey::Broadcast,
+            guardian_metadata.tagged_hash().to_byte_array(),
+        )
+        .await?;
+
+    Ok(guardian_metadata.with_signature(signature))
 }
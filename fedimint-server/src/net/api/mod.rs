@@ -81,6 +81,9 @@ pub fn none() -> ApiSecrets {
 /// How long to wait before timing out client connections
 const API_ENDPOINT_TIMEOUT: Duration = Duration::from_mins(1);
 
+/// Error message returned in place of the response of a panicking handler
+pub const API_HANDLER_PANICKED: &str = "API handler panicked";
+
 /// Has the context necessary for serving API endpoints
 ///
 /// Returns the specific `State` the endpoint requires and the
@@ -167,7 +170,7 @@ pub fn attach_endpoints<State, T>(
                         target: LOG_NET_API,
                         path, "API handler panicked, DO NOT IGNORE, FIX IT!!!"
                     );
-                    ErrorObject::owned(500, "API handler panicked", None::<()>)
+                    ErrorObject::owned(500, API_HANDLER_PANICKED, None::<()>)
                 })?
                 .map_err(|tokio::time::error::Elapsed { .. }| {
                     // TODO: find a better error for this, the error we used before:
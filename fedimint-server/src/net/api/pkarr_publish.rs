@@ -22,22 +22,35 @@
 const INITIAL_DELAY_SECS: u64 = 10;
 const TXT_RECORD_TTL: u32 = 1800;
 
-/// Derive a pkarr keypair deterministically from the server's broadcast secret
-/// key.
+/// Derive the seed of our pkarr keypair deterministically from the server's
+/// broadcast secret key.
 ///
 /// Uses HKDF-based derivation with domain separation to produce an ed25519
 /// seed.
-pub fn derive_pkarr_keypair(broadcast_sk: &SecretKey) -> pkarr::Keypair {
+pub fn derive_pkarr_seed(broadcast_sk: &SecretKey) -> [u8; 32] {
     let root = DerivableSecret::new_root(&broadcast_sk.secret_bytes(), b"fedimint-pkarr");
     let pkarr_child = root.child_key(PKARR_IDENTITY_CHILD_ID);
-    let seed: [u8; 32] = pkarr_child.to_random_bytes();
-    pkarr::Keypair::from_secret_key(&seed)
+    pkarr_child.to_random_bytes()
+}
+
+/// Our pkarr keypair, derived from the broadcast secret key or from the seed
+/// kept in its place once it has been exported to a remote guardian signer.
+pub fn pkarr_keypair(cfg: &ServerConfig) -> anyhow::Result<pkarr::Keypair> {
+    let seed = match (cfg.private.broadcast_secret_key, cfg.private.pkarr_seed) {
+        (Some(broadcast_sk), _) => derive_pkarr_seed(&broadcast_sk),
+        (None, Some(seed)) => seed,
+        (None, None) => {
+            anyhow::bail!("Private config contains neither broadcast key nor pkarr seed")
+        }
+    };
+
+    Ok(pkarr::Keypair::from_secret_key(&seed))
 }
 
 /// Get the z-base32 encoded pkarr public key derived from the broadcast secret
 /// key.
-pub fn pkarr_id_z32(broadcast_sk: &SecretKey) -> String {
-    derive_pkarr_keypair(broadcast_sk).to_z32()
+pub fn pkarr_id_z32(cfg: &ServerConfig) -> anyhow::Result<String> {
+    Ok(pkarr_keypair(cfg)?.to_z32())
 }
 
 /// Spawn a background task that periodically publishes this guardian's API
@@ -47,7 +60,7 @@ pub async fn start_pkarr_publish_service(
     tg: &TaskGroup,
     cfg: &ServerConfig,
 ) -> anyhow::Result<()> {
-    let keypair = derive_pkarr_keypair(&cfg.private.broadcast_secret_key);
+    let keypair = pkarr_keypair(cfg)?;
 
     let pkarr_enabled =
         fedimint_core::envs::is_env_var_set_opt(FM_PKARR_ENABLE_ENV).unwrap_or(true);
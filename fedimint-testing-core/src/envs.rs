@@ -22,3 +22,6 @@
 // This is necessary instead of `FM_FORCE_BITCOIN_RPC_URL_ENV` since that
 // overrides both the wallet client and server's Bitcoin RPC.
 pub const FM_TEST_BACKEND_BITCOIN_RPC_URL_ENV: &str = "FM_TEST_BACKEND_BITCOIN_RPC_URL";
+
+// Replays the consensus simulation of `fedimint-testing` with the given seed
+pub const FM_SIMULATION_SEED_ENV: &str = "FM_SIMULATION_SEED";
@@ -0,0 +1,491 @@
+//! Invariant checks of the module audits of an in-process federation
+//!
+//! The consensus engine checkpoints the database of a guardian right after it
+//! completed a session. [`SessionSnapshots`] records an in-memory copy of the
+//! database whenever that happens, such that the audit of every guardian can be
+//! checked against its database contents and compared to the audits of the
+//! other guardians for the same session while the federation keeps running.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write as _;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::Context as _;
+use bitcoin::hex::DisplayHex as _;
+use fedimint_api_client::api::DynGlobalApi;
+use fedimint_connectors::ConnectorRegistry;
+use fedimint_core::core::ModuleInstanceId;
+use fedimint_core::db::mem_impl::{MemDatabase, MemTransaction};
+use fedimint_core::db::{
+    Database, DatabaseResult, DatabaseTransaction, IDatabaseTransactionOpsCore as _, IRawDatabase,
+};
+use fedimint_core::module::audit::Audit;
+use fedimint_core::module::registry::{ModuleDecoderRegistry, ModuleRegistry};
+use fedimint_core::task::TaskGroup;
+use fedimint_core::util::FmtCompactAnyhow as _;
+use fedimint_core::{NumPeers, PeerId, apply, async_trait_maybe_send};
+use fedimint_logging::LOG_TEST;
+use fedimint_server::config::ServerConfig;
+use fedimint_server::consensus::engine::get_finished_session_count_static;
+use fedimint_server::core::ServerModuleInitRegistry;
+use fedimint_server_core::ServerModuleRegistry;
+use fedimint_server_core::bitcoin_rpc::{DynServerBitcoinRpc, ServerBitcoinRpcMonitor};
+use fedimint_server_core::guardian_signer::ModuleGuardianSigner;
+use futures::StreamExt as _;
+use tokio::sync::Notify;
+use tracing::{error, info};
+
+/// The audit items of a guardian by module and name
+type AuditItems = BTreeMap<(ModuleInstanceId, String), i64>;
+
+/// Copies of the guardians' databases at the end of every session that have
+/// not been checked yet
+#[derive(Debug, Clone, Default)]
+pub(crate) struct SessionSnapshots {
+    snapshots: Arc<Mutex<Vec<(PeerId, MemDatabase)>>>,
+    notify: Arc<Notify>,
+}
+
+impl SessionSnapshots {
+    /// Creates the database of a guardian, recording a snapshot whenever the
+    /// consensus engine checkpoints it
+    pub(crate) fn database(&self, peer_id: PeerId, decoders: ModuleDecoderRegistry) -> Database {
+        Database::new(
+            SnapshottingDatabase {
+                db: MemDatabase::new(),
+                peer_id,
+                snapshots: self.clone(),
+            },
+            decoders,
+        )
+    }
+
+    async fn take(&self) -> Vec<(PeerId, MemDatabase)> {
+        loop {
+            let snapshots = std::mem::take(&mut *self.snapshots.lock().expect("locking failed"));
+
+            if !snapshots.is_empty() {
+                return snapshots;
+            }
+
+            self.notify.notified().await;
+        }
+    }
+}
+
+#[derive(Debug)]
+struct SnapshottingDatabase {
+    db: MemDatabase,
+    peer_id: PeerId,
+    snapshots: SessionSnapshots,
+}
+
+#[apply(async_trait_maybe_send!)]
+impl IRawDatabase for SnapshottingDatabase {
+    type Transaction<'a> = MemTransaction<'a>;
+
+    async fn begin_transaction<'a>(&'a self) -> MemTransaction<'a> {
+        self.db.begin_transaction().await
+    }
+
+    fn checkpoint(&self, _backup_path: &Path) -> DatabaseResult<()> {
+        // The session the checkpoint was taken for is read from the snapshot
+        self.snapshots
+            .snapshots
+            .lock()
+            .expect("locking failed")
+            .push((self.peer_id, self.db.snapshot()));
+
+        self.snapshots.notify.notify_one();
+
+        Ok(())
+    }
+}
+
+/// Checks the audits of all guardians after every completed session and
+/// fails the test on drop if an invariant was violated
+pub(crate) struct AuditInvariants {
+    violation: Arc<Mutex<Option<String>>>,
+}
+
+impl AuditInvariants {
+    /// Spawns the checker verifying for every session that
+    ///
+    /// * the net assets of the federation are not negative
+    /// * every audit item is derived from a distinct record of the module's
+    ///   database with the value stored there, and every stored record of an
+    ///   audited type is part of the audit
+    /// * all guardians report the same audit items
+    pub(crate) fn spawn(
+        task_group: &TaskGroup,
+        snapshots: SessionSnapshots,
+        configs: BTreeMap<PeerId, ServerConfig>,
+        server_init: ServerModuleInitRegistry,
+        connectors: ConnectorRegistry,
+        bitcoin_rpc: DynServerBitcoinRpc,
+    ) -> Self {
+        let violation = Arc::new(Mutex::new(None));
+
+        let checker = AuditChecker {
+            snapshots,
+            violation: violation.clone(),
+            audits: BTreeMap::new(),
+        };
+
+        let checker_task_group = task_group.make_subgroup();
+
+        task_group.spawn_cancellable("audit invariant checker", async move {
+            let bitcoin_rpc = ServerBitcoinRpcMonitor::new(
+                bitcoin_rpc,
+                Duration::from_millis(100),
+                &checker_task_group,
+            );
+
+            let mut modules = BTreeMap::new();
+
+            for (peer_id, cfg) in &configs {
+                match init_modules(
+                    cfg,
+                    &server_init,
+                    &connectors,
+                    &bitcoin_rpc,
+                    &checker_task_group,
+                )
+                .await
+                {
+                    Ok(peer_modules) => {
+                        modules.insert(*peer_id, peer_modules);
+                    }
+                    Err(e) => {
+                        error!(
+                            target: LOG_TEST,
+                            %peer_id,
+                            err = %e.fmt_compact_anyhow(),
+                            "Failed to initialize modules for the audit invariant checker"
+                        );
+                        return;
+                    }
+                }
+            }
+
+            checker.run(modules).await;
+        });
+
+        Self { violation }
+    }
+
+    /// Panics if an audit invariant has been violated so far
+    pub(crate) fn assert_no_violation(&self) {
+        if let Some(violation) = self.violation.lock().expect("locking failed").as_ref() {
+            panic!("{violation}");
+        }
+    }
+}
+
+impl Drop for AuditInvariants {
+    fn drop(&mut self) {
+        if !std::thread::panicking() {
+            self.assert_no_violation();
+        }
+    }
+}
+
+/// Initializes the modules of a guardian for auditing its snapshots. The
+/// modules only audit the database transactions passed to them, so they run on
+/// an empty database of their own.
+async fn init_modules(
+    cfg: &ServerConfig,
+    server_init: &ServerModuleInitRegistry,
+    connectors: &ConnectorRegistry,
+    bitcoin_rpc: &ServerBitcoinRpcMonitor,
+    task_group: &TaskGroup,
+) -> anyhow::Result<ServerModuleRegistry> {
+    let db = Database::new(
+        MemDatabase::new(),
+        server_init.available_decoders(cfg.consensus.iter_module_instances())?,
+    );
+
+    let global_api = DynGlobalApi::new(
+        connectors.clone(),
+        cfg.consensus
+            .api_endpoints()
+            .iter()
+            .map(|(&peer_id, url)| (peer_id, url.url.clone()))
+            .collect(),
+        None,
+    )?;
+
+    let guardian_signer = cfg.local_guardian_signer(server_init)?;
+
+    let mut modules = BTreeMap::new();
+
+    for (module_id, module_cfg) in &cfg.consensus.modules {
+        let module = server_init
+            .get(&module_cfg.kind)
+            .with_context(|| format!("Unsupported module kind {}", module_cfg.kind))?
+            .init(
+                NumPeers::from(cfg.consensus.api_endpoints().len()),
+                cfg.get_module_config(*module_id)?,
+                db.with_prefix_module_id(*module_id).0,
+                task_group,
+                cfg.local.identity,
+                global_api.with_module(*module_id),
+                bitcoin_rpc.clone(),
+                ModuleGuardianSigner::new(*module_id, guardian_signer.clone()),
+            )
+            .await?;
+
+        modules.insert(*module_id, (module_cfg.kind.clone(), module));
+    }
+
+    Ok(ModuleRegistry::from(modules))
+}
+
+struct AuditChecker {
+    snapshots: SessionSnapshots,
+    violation: Arc<Mutex<Option<String>>>,
+    /// The audits checked so far by session
+    audits: BTreeMap<u64, BTreeMap<PeerId, AuditItems>>,
+}
+
+impl AuditChecker {
+    async fn run(mut self, modules: BTreeMap<PeerId, ServerModuleRegistry>) {
+        loop {
+            for (peer_id, snapshot) in self.snapshots.take().await {
+                let modules = &modules[&peer_id];
+
+                let db = Database::new(snapshot, modules.decoder_registry());
+
+                if let Err(violation) = self.check(peer_id, modules, &db).await {
+                    error!(target: LOG_TEST, "{violation}");
+
+                    self.violation
+                        .lock()
+                        .expect("locking failed")
+                        .get_or_insert(violation);
+                }
+            }
+        }
+    }
+
+    async fn check(
+        &mut self,
+        peer_id: PeerId,
+        modules: &ServerModuleRegistry,
+        db: &Database,
+    ) -> Result<(), String> {
+        let session_index = get_finished_session_count_static(&mut db.begin_transaction_nc().await)
+            .await
+            .checked_sub(1)
+            .expect("The snapshot is taken after a session completed");
+
+        // Auditing compacts the audit records of some modules, which the
+        // consensus engine commits as well
+        let mut dbtx = db.begin_transaction().await;
+
+        let audit = audit_modules(modules, &mut dbtx.to_ref_nc()).await;
+
+        dbtx.commit_tx().await;
+
+        let net_assets = audit
+            .net_assets()
+            .expect("Overflow while checking balance sheet")
+            .milli_sat;
+
+        if net_assets < 0 {
+            return Err(format!(
+                "Balance sheet of peer {peer_id} has gone negative after session {session_index}:\n{audit}"
+            ));
+        }
+
+        let mismatches = compare_with_database(&audit, &mut db.begin_transaction_nc().await).await;
+
+        if !mismatches.is_empty() {
+            return Err(format!(
+                "Audit of peer {peer_id} does not match its database after session {session_index}:\n{mismatches}"
+            ));
+        }
+
+        info!(
+            target: LOG_TEST,
+            %peer_id,
+            %session_index,
+            %net_assets,
+            "Checked audit invariants"
+        );
+
+        let audits = self.audits.entry(session_index).or_default();
+
+        audits.insert(peer_id, audit_items(&audit));
+
+        if audits.values().collect::<BTreeSet<_>>().len() > 1 {
+            return Err(format!(
+                "Audits of the guardians differ after session {session_index}:\n{}",
+                diff(
+                    &audits
+                        .iter()
+                        .map(|(peer_id, items)| (format!("peer {peer_id}"), items))
+                        .collect::<Vec<_>>()
+                )
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+async fn audit_modules(
+    modules: &ServerModuleRegistry,
+    dbtx: &mut DatabaseTransaction<'_>,
+) -> Audit {
+    let mut audit = Audit::default();
+
+    for (module_instance_id, _, module) in modules.iter_modules() {
+        module
+            .audit(
+                &mut dbtx.to_ref_with_prefix_module_id(module_instance_id).0,
+                &mut audit,
+                module_instance_id,
+            )
+            .await;
+    }
+
+    audit
+}
+
+fn audit_items(audit: &Audit) -> AuditItems {
+    audit
+        .items()
+        .iter()
+        .map(|item| {
+            (
+                (
+                    item.module_instance_id
+                        .expect("Module audit items belong to a module"),
+                    item.name.clone(),
+                ),
+                item.milli_sat,
+            )
+        })
+        .collect()
+}
+
+/// Lists the audit items that are not backed by the record they were derived
+/// from, and the records of an audited type that are missing from the audit
+async fn compare_with_database(audit: &Audit, dbtx: &mut DatabaseTransaction<'_>) -> String {
+    let mut mismatches = String::new();
+
+    // The audited records by module and record type
+    let mut audited = BTreeMap::<(ModuleInstanceId, u8), BTreeMap<&[u8], (&str, &[u8])>>::new();
+
+    for item in audit.items() {
+        let module_instance_id = item
+            .module_instance_id
+            .expect("Module audit items belong to a module");
+
+        let Some((key, value)) = &item.record else {
+            writeln!(
+                mismatches,
+                "module {module_instance_id} | {} | not derived from a database record",
+                item.name
+            )
+            .expect("Writing to string");
+
+            continue;
+        };
+
+        let record_type = *key.first().expect("Record keys start with their prefix");
+
+        if audited
+            .entry((module_instance_id, record_type))
+            .or_default()
+            .insert(key.as_slice(), (item.name.as_str(), value.as_slice()))
+            .is_some()
+        {
+            writeln!(
+                mismatches,
+                "module {module_instance_id} | {} | audited more than once",
+                item.name
+            )
+            .expect("Writing to string");
+        }
+    }
+
+    for ((module_instance_id, record_type), records) in audited {
+        let stored = dbtx
+            .to_ref_with_prefix_module_id(module_instance_id)
+            .0
+            .raw_find_by_prefix(&[record_type])
+            .await
+            .expect("Reading the snapshot failed")
+            .collect::<BTreeMap<Vec<u8>, Vec<u8>>>()
+            .await;
+
+        for (key, (name, value)) in &records {
+            match stored.get(*key) {
+                Some(stored) if stored == value => Ok(()),
+                Some(stored) => writeln!(
+                    mismatches,
+                    "module {module_instance_id} | {name} | audited value {} | stored value {}",
+                    value.as_hex(),
+                    stored.as_hex()
+                ),
+                None => writeln!(
+                    mismatches,
+                    "module {module_instance_id} | {name} | audited but not stored"
+                ),
+            }
+            .expect("Writing to string");
+        }
+
+        for key in stored.keys() {
+            if !records.contains_key(key.as_slice()) {
+                writeln!(
+                    mismatches,
+                    "module {module_instance_id} | record {} | stored but not audited",
+                    key.as_hex()
+                )
+                .expect("Writing to string");
+            }
+        }
+    }
+
+    mismatches
+}
+
+/// Lists the audit items that are not reported identically by all audits
+fn diff(audits: &[(String, &AuditItems)]) -> String {
+    let keys = audits
+        .iter()
+        .flat_map(|(_, items)| items.keys())
+        .collect::<BTreeSet<_>>();
+
+    let mut diff = String::new();
+
+    for key @ (module_instance_id, name) in keys {
+        let values = audits
+            .iter()
+            .map(|(_, items)| items.get(key))
+            .collect::<BTreeSet<_>>();
+
+        if values.len() == 1 {
+            continue;
+        }
+
+        write!(diff, "module {module_instance_id} | {name} |").expect("Writing to string");
+
+        for (label, items) in audits {
+            match items.get(key) {
+                Some(milli_sat) => write!(diff, " {label}: {milli_sat} msat |"),
+                None => write!(diff, " {label}: missing |"),
+            }
+            .expect("Writing to string");
+        }
+
+        diff.push('\n');
+    }
+
+    diff
+}
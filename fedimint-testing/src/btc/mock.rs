@@ -41,6 +41,9 @@ struct FakeBitcoinTestInner {
     scripts: BTreeMap<ScriptBuf, Vec<Transaction>>,
     /// Tracks the block height a transaction was included
     txid_to_block_height: BTreeMap<Txid, usize>,
+    /// Number of times blocks were invalidated, used to give blocks replacing
+    /// invalidated ones a different block hash
+    forks: u32,
 }
 
 #[derive(Clone, Debug)]
@@ -63,6 +66,7 @@ pub fn new() -> Self {
             proofs: BTreeMap::new(),
             scripts: BTreeMap::new(),
             txid_to_block_height: BTreeMap::new(),
+            forks: 0,
         };
         let res = FakeBitcoinTest {
             inner: std::sync::RwLock::new(inner).into(),
@@ -101,6 +105,8 @@ fn mine_block(
         blocks: &mut Vec<Block>,
         pending: &mut Vec<Transaction>,
         txid_to_block_height: &mut BTreeMap<Txid, usize>,
+        proofs: &mut BTreeMap<Txid, TxOutProof>,
+        forks: u32,
     ) -> bitcoin::BlockHash {
         debug!(
             "Mining block: {} transactions, {} blocks",
@@ -119,7 +125,8 @@ fn mine_block(
         if pending.is_empty() {
             pending.push(Self::new_transaction(vec![], blocks.len() as u32));
         }
-        let merkle_root = Self::pending_merkle_tree(pending)
+        let merkle_proof = Self::pending_merkle_tree(pending);
+        let merkle_root = merkle_proof
             .extract_matches(&mut vec![], &mut vec![])
             .unwrap();
         let block = Block {
@@ -129,10 +136,19 @@ fn mine_block(
                 merkle_root,
                 time: 0,
                 bits: CompactTarget::from_consensus(0),
-                nonce: 0,
+                nonce: forks,
             },
             txdata: pending.clone(),
         };
+        for tx in pending.iter() {
+            proofs.insert(
+                tx.compute_txid(),
+                TxOutProof {
+                    block_header: block.header,
+                    merkle_proof: merkle_proof.clone(),
+                },
+            );
+        }
         pending.clear();
         blocks.push(block.clone());
         block.block_hash()
@@ -146,11 +162,22 @@ fn mine_blocks_no_async(&self, block_num: u64) -> Vec<bitcoin::BlockHash> {
             ref mut pending,
             ref mut addresses,
             ref mut txid_to_block_height,
+            ref mut proofs,
+            forks,
             ..
         } = *inner;
 
         (1..=block_num)
-            .map(|_| FakeBitcoinTest::mine_block(addresses, blocks, pending, txid_to_block_height))
+            .map(|_| {
+                FakeBitcoinTest::mine_block(
+                    addresses,
+                    blocks,
+                    pending,
+                    txid_to_block_height,
+                    proofs,
+                    forks,
+                )
+            })
             .collect()
     }
 }
@@ -167,6 +194,32 @@ async fn mine_blocks(&self, block_num: u64) -> Vec<bitcoin::BlockHash> {
         self.mine_blocks_no_async(block_num)
     }
 
+    async fn mine_empty_blocks(&self, block_num: u64) -> Vec<bitcoin::BlockHash> {
+        let mut inner = self.inner.write().unwrap();
+
+        let FakeBitcoinTestInner {
+            ref mut blocks,
+            ref mut addresses,
+            ref mut txid_to_block_height,
+            ref mut proofs,
+            forks,
+            ..
+        } = *inner;
+
+        (1..=block_num)
+            .map(|_| {
+                FakeBitcoinTest::mine_block(
+                    addresses,
+                    blocks,
+                    &mut vec![],
+                    txid_to_block_height,
+                    proofs,
+                    forks,
+                )
+            })
+            .collect()
+    }
+
     async fn prepare_funding_wallet(&self) {
         // In fake wallet this might not be technically necessary,
         // but it makes it behave more like the `RealBitcoinTest`.
@@ -195,24 +248,25 @@ async fn send_and_mine_block(
             .insert(transaction.compute_txid(), amount.into());
 
         inner.pending.push(transaction.clone());
-        let merkle_proof = FakeBitcoinTest::pending_merkle_tree(&inner.pending);
 
         let FakeBitcoinTestInner {
             ref mut blocks,
             ref mut pending,
             ref mut addresses,
             ref mut txid_to_block_height,
+            ref mut proofs,
+            forks,
             ..
         } = *inner;
-        FakeBitcoinTest::mine_block(addresses, blocks, pending, txid_to_block_height);
-        let block_header = inner.blocks.last().unwrap().header;
-        let proof = TxOutProof {
-            block_header,
-            merkle_proof,
-        };
-        inner
-            .proofs
-            .insert(transaction.compute_txid(), proof.clone());
+        FakeBitcoinTest::mine_block(
+            addresses,
+            blocks,
+            pending,
+            txid_to_block_height,
+            proofs,
+            forks,
+        );
+        let proof = inner.proofs[&transaction.compute_txid()].clone();
         inner
             .scripts
             .insert(address.script_pubkey(), vec![transaction.clone()]);
@@ -291,6 +345,45 @@ async fn get_mempool_tx(&self, txid: &Txid) -> Option<bitcoin::Transaction> {
             .find(|tx| tx.compute_txid() == *txid)
             .map(std::borrow::ToOwned::to_owned)
     }
+
+    async fn invalidate_blocks(&self, depth: u64) {
+        let mut inner = self.inner.write().unwrap();
+
+        // The block at height one determines the ChainId and must never change
+        assert!(
+            depth < inner.blocks.len() as u64 - 1,
+            "Can't invalidate the genesis block or the chain id block"
+        );
+
+        let FakeBitcoinTestInner {
+            ref mut blocks,
+            ref mut pending,
+            ref mut txid_to_block_height,
+            ref mut proofs,
+            ref mut forks,
+            ..
+        } = *inner;
+
+        debug!("Invalidating {depth} of {} blocks", blocks.len());
+
+        let mut disconnected = vec![];
+        for block in blocks.split_off(blocks.len() - depth as usize) {
+            for tx in block.txdata {
+                txid_to_block_height.remove(&tx.compute_txid());
+                proofs.remove(&tx.compute_txid());
+
+                // Empty blocks contain a placeholder transaction without outputs that
+                // never was in the mempool
+                if !tx.output.is_empty() {
+                    disconnected.push(tx);
+                }
+            }
+        }
+
+        disconnected.append(pending);
+        *pending = disconnected;
+        *forks += 1;
+    }
 }
 
 #[async_trait]
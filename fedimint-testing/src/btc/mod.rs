@@ -15,6 +15,10 @@ pub trait BitcoinTest {
     /// Mines a given number of blocks
     async fn mine_blocks(&self, block_num: u64) -> Vec<bitcoin::BlockHash>;
 
+    /// Mines a given number of blocks that do not include any transactions of
+    /// the mempool, as if their feerate was too low to be mined.
+    async fn mine_empty_blocks(&self, block_num: u64) -> Vec<bitcoin::BlockHash>;
+
     /// Prepare funding wallet
     ///
     /// If needed will mine initial 100 blocks for `send_and_mine_block` to
@@ -54,4 +58,23 @@ async fn send_and_mine_block(
 
     /// Returns a transaction with the provided txid if it exists in the mempool
     async fn get_mempool_tx(&self, txid: &Txid) -> Option<bitcoin::Transaction>;
+
+    /// Invalidates the last `depth` blocks, decreasing the block count by
+    /// `depth`. Like `invalidateblock` in bitcoind, the transactions of the
+    /// invalidated blocks return to the mempool.
+    ///
+    /// Against a shared bitcoind this should only be called while holding
+    /// [`BitcoinTest::lock_exclusive`] and only for blocks mined while holding
+    /// it, since it would disconnect transactions of other tests otherwise.
+    async fn invalidate_blocks(&self, depth: u64);
+
+    /// Replaces the last `depth` blocks with `depth + 1` new blocks, such that
+    /// the new chain becomes the best chain. Returns the new block hashes.
+    ///
+    /// Transactions of the replaced blocks are mined again in the first new
+    /// block, but at a different block hash.
+    async fn reorg(&self, depth: u64) -> Vec<bitcoin::BlockHash> {
+        self.invalidate_blocks(depth).await;
+        self.mine_blocks(depth + 1).await
+    }
 }
@@ -37,6 +37,20 @@ fn generate_to_address(
         block_in_place(|| self.inner.generate_to_address(block_num, address))
     }
 
+    fn generate_empty_block(
+        &self,
+        address: &Address,
+    ) -> Result<bitcoin::BlockHash, bitcoincore_rpc::Error> {
+        let result: serde_json::Value = block_in_place(|| {
+            self.inner.call(
+                "generateblock",
+                &[address.to_string().into(), serde_json::json!([])],
+            )
+        })?;
+
+        Ok(serde_json::from_value(result["hash"].clone())?)
+    }
+
     fn get_block_header_info(
         &self,
         hash: &bitcoin::BlockHash,
@@ -108,6 +122,10 @@ fn get_block_info(
     ) -> Result<bitcoincore_rpc::json::GetBlockResult, bitcoincore_rpc::Error> {
         block_in_place(|| self.inner.get_block_info(hash))
     }
+
+    fn invalidate_block(&self, hash: &bitcoin::BlockHash) -> Result<(), bitcoincore_rpc::Error> {
+        block_in_place(|| self.inner.invalidate_block(hash))
+    }
 }
 
 /// Fixture implementing bitcoin node under test by talking to a `bitcoind` with
@@ -206,6 +224,32 @@ async fn mine_blocks(&self, block_num: u64) -> Vec<bitcoin::BlockHash> {
         mined_block_hashes
     }
 
+    async fn mine_empty_blocks(&self, block_num: u64) -> Vec<bitcoin::BlockHash> {
+        let new_address = self.get_new_address().await;
+
+        let mined_block_hashes = (0..block_num)
+            .map(|_| {
+                self.client
+                    .generate_empty_block(&new_address)
+                    .expect(Self::ERROR)
+            })
+            .collect();
+
+        // The RPC function is confusingly named and actually returns the block height
+        let expected_block_count = self.client.get_block_count().expect(Self::ERROR) + 1;
+
+        // waits for the rpc client to catch up to bitcoind
+        while self.rpc.get_block_count().await.expect("rpc failed") < expected_block_count {
+            sleep_in_test(
+                "waiting for empty blocks to be mined",
+                Duration::from_millis(200),
+            )
+            .await;
+        }
+
+        mined_block_hashes
+    }
+
     async fn prepare_funding_wallet(&self) {
         let block_count = self.client.get_block_count().expect("should not fail");
         if block_count < 100 {
@@ -307,6 +351,50 @@ async fn get_block_count(&self) -> u64 {
     async fn get_mempool_tx(&self, txid: &Txid) -> Option<bitcoin::Transaction> {
         self.client.get_raw_transaction(txid, None).ok()
     }
+
+    async fn invalidate_blocks(&self, depth: u64) {
+        if depth == 0 {
+            return;
+        }
+
+        // The RPC function is confusingly named and actually returns the block height
+        let tip_height = self.client.get_block_count().expect(Self::ERROR);
+        assert!(
+            depth < tip_height,
+            "Can't invalidate the genesis block or the chain id block"
+        );
+
+        // Invalidating a block also invalidates all of its descendants
+        let expected_block_count = tip_height + 1 - depth;
+        let block_hash = self
+            .client
+            .get_block_hash(expected_block_count)
+            .expect(Self::ERROR);
+        self.client
+            .invalidate_block(&block_hash)
+            .expect(Self::ERROR);
+
+        // waits for the rpc client to observe the shorter chain
+        loop {
+            let current_block_count = self.rpc.get_block_count().await.expect("rpc failed");
+            if expected_block_count < current_block_count {
+                debug!(
+                    target: LOG_TEST,
+                    %depth,
+                    %expected_block_count,
+                    %current_block_count,
+                    "Waiting for blocks to be invalidated"
+                );
+                sleep_in_test(
+                    "waiting for blocks to be invalidated",
+                    Duration::from_millis(200),
+                )
+                .await;
+            } else {
+                break;
+            }
+        }
+    }
 }
 
 /// Fixture implementing bitcoin node under test by talking to a `bitcoind` -
@@ -372,6 +460,11 @@ async fn mine_blocks(&self, block_num: u64) -> Vec<bitcoin::BlockHash> {
         self.inner.mine_blocks(block_num).await
     }
 
+    async fn mine_empty_blocks(&self, block_num: u64) -> Vec<bitcoin::BlockHash> {
+        let _lock = self.lock_exclusive().await;
+        self.inner.mine_empty_blocks(block_num).await
+    }
+
     async fn prepare_funding_wallet(&self) {
         let _lock = self.lock_exclusive().await;
         self.inner.prepare_funding_wallet().await;
@@ -415,6 +508,16 @@ async fn get_mempool_tx(&self, txid: &Txid) -> Option<bitcoin::Transaction> {
         let _lock = self.lock_exclusive().await;
         self.inner.get_mempool_tx(txid).await
     }
+
+    async fn invalidate_blocks(&self, depth: u64) {
+        let _lock = self.lock_exclusive().await;
+        self.inner.invalidate_blocks(depth).await;
+    }
+
+    async fn reorg(&self, depth: u64) -> Vec<bitcoin::BlockHash> {
+        let _lock = self.lock_exclusive().await;
+        self.inner.reorg(depth).await
+    }
 }
 
 #[async_trait]
@@ -431,6 +534,10 @@ async fn mine_blocks(&self, block_num: u64) -> Vec<bitcoin::BlockHash> {
         mined_block_hashes
     }
 
+    async fn mine_empty_blocks(&self, block_num: u64) -> Vec<bitcoin::BlockHash> {
+        self.inner.mine_empty_blocks(block_num).await
+    }
+
     async fn prepare_funding_wallet(&self) {
         self.inner.prepare_funding_wallet().await;
     }
@@ -466,4 +573,11 @@ async fn get_block_count(&self) -> u64 {
     async fn get_mempool_tx(&self, txid: &Txid) -> Option<bitcoin::Transaction> {
         self.inner.get_mempool_tx(txid).await
     }
+
+    async fn invalidate_blocks(&self, depth: u64) {
+        let pre = self.inner.client.get_block_count().unwrap();
+        self.inner.invalidate_blocks(depth).await;
+        let post = self.inner.client.get_block_count().unwrap();
+        assert_eq!(pre - post, depth);
+    }
 }
@@ -0,0 +1,449 @@
+//! Network fault injection for in-process federations
+//!
+//! [`NetworkFaults`] is shared between the p2p connections of all guardians of
+//! a [`crate::federation::FederationTest`] and the connectors of its clients.
+//! Changing it takes effect immediately, so tests can inject latency, packet
+//! drops and partitions while the federation is running. Together with
+//! guardian crashes and restarts, faults can be scripted along the progress of
+//! consensus with a [`FaultSchedule`].
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::anyhow;
+use async_trait::async_trait;
+use fedimint_connectors::error::ServerError;
+use fedimint_connectors::{
+    Connectivity, Connector, DynConnector, DynGatewayConnection, DynGuaridianConnection,
+    IConnection, IGuardianConnection, ServerResult,
+};
+use fedimint_core::encoding::Encodable as _;
+use fedimint_core::module::{ApiMethod, ApiRequestErased};
+use fedimint_core::net::peers::{DynP2PConnections, IP2PConnections, Recipient};
+use fedimint_core::runtime::sleep;
+use fedimint_core::task::TaskGroup;
+use fedimint_core::util::SafeUrl;
+use fedimint_core::{BitcoinHash as _, PeerId, apply, async_trait_maybe_send};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde_json::Value;
+
+#[derive(Debug)]
+struct FaultState {
+    latency: Duration,
+    jitter: Duration,
+    drop_rate: f64,
+    /// Peers can only talk to peers within the same group
+    partitions: Option<Vec<BTreeSet<PeerId>>>,
+    isolated_from_clients: BTreeSet<PeerId>,
+    seed: u64,
+    /// Draws for client requests
+    rng: StdRng,
+    /// Draws for the messages of every link between two guardians, such that
+    /// the fate of a message does not depend on the traffic of other links
+    link_rngs: BTreeMap<(PeerId, PeerId), StdRng>,
+}
+
+/// Decides the fate of a single message or request
+enum Delivery {
+    Drop,
+    Delay(Duration),
+}
+
+impl FaultState {
+    fn new(seed: u64) -> Self {
+        FaultState {
+            latency: Duration::ZERO,
+            jitter: Duration::ZERO,
+            drop_rate: 0.0,
+            partitions: None,
+            isolated_from_clients: BTreeSet::new(),
+            seed,
+            rng: StdRng::seed_from_u64(seed),
+            link_rngs: BTreeMap::new(),
+        }
+    }
+
+    fn is_partitioned(&self, a: PeerId, b: PeerId) -> bool {
+        let Some(partitions) = &self.partitions else {
+            return false;
+        };
+
+        !partitions
+            .iter()
+            .any(|group| group.contains(&a) && group.contains(&b))
+    }
+
+    fn link_rng(&mut self, from: PeerId, to: PeerId) -> &mut StdRng {
+        let seed = self.seed;
+
+        self.link_rngs.entry((from, to)).or_insert_with(|| {
+            StdRng::from_seed((seed, from, to).consensus_hash_sha256().to_byte_array())
+        })
+    }
+
+    fn p2p_delivery(&mut self, from: PeerId, to: PeerId) -> Delivery {
+        let (latency, jitter, drop_rate) = (self.latency, self.jitter, self.drop_rate);
+
+        delivery(self.link_rng(from, to), latency, jitter, drop_rate)
+    }
+
+    fn client_delivery(&mut self) -> Delivery {
+        delivery(&mut self.rng, self.latency, self.jitter, self.drop_rate)
+    }
+}
+
+fn delivery(rng: &mut StdRng, latency: Duration, jitter: Duration, drop_rate: f64) -> Delivery {
+    if drop_rate > 0.0 && rng.gen_bool(drop_rate) {
+        return Delivery::Drop;
+    }
+
+    let jitter = if jitter.is_zero() {
+        Duration::ZERO
+    } else {
+        rng.gen_range(Duration::ZERO..=jitter)
+    };
+
+    Delivery::Delay(latency + jitter)
+}
+
+/// Faults applied to the network of an in-process federation
+///
+/// Cloning returns a handle to the same faults.
+#[derive(Debug, Clone)]
+pub struct NetworkFaults {
+    state: Arc<Mutex<FaultState>>,
+}
+
+impl Default for NetworkFaults {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl NetworkFaults {
+    /// Creates a network without any faults
+    pub fn new() -> Self {
+        Self::with_seed(rand::random())
+    }
+
+    /// Creates a network without any faults whose random drops and delays
+    /// are derived from `seed`
+    pub fn with_seed(seed: u64) -> Self {
+        NetworkFaults {
+            state: Arc::new(Mutex::new(FaultState::new(seed))),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, FaultState> {
+        self.state.lock().expect("locking failed")
+    }
+
+    /// Delays every message and client request by `latency` plus a random
+    /// duration of up to `jitter`. A non-zero jitter reorders messages.
+    pub fn set_latency(&self, latency: Duration, jitter: Duration) {
+        let mut state = self.lock();
+        state.latency = latency;
+        state.jitter = jitter;
+    }
+
+    /// Drops messages and client requests with the given probability
+    pub fn set_drop_rate(&self, drop_rate: f64) {
+        assert!(
+            (0.0..=1.0).contains(&drop_rate),
+            "drop rate has to be a probability"
+        );
+
+        self.lock().drop_rate = drop_rate;
+    }
+
+    /// Splits the guardians into groups that can not talk to each other.
+    /// Guardians not contained in any group are cut off from everyone.
+    pub fn partition(&self, groups: Vec<BTreeSet<PeerId>>) {
+        self.lock().partitions = Some(groups);
+    }
+
+    /// Makes the api of a guardian unreachable for clients
+    pub fn isolate_from_clients(&self, peer: PeerId) {
+        self.lock().isolated_from_clients.insert(peer);
+    }
+
+    /// Removes all faults
+    pub fn heal(&self) {
+        let mut state = self.lock();
+        state.latency = Duration::ZERO;
+        state.jitter = Duration::ZERO;
+        state.drop_rate = 0.0;
+        state.partitions = None;
+        state.isolated_from_clients.clear();
+    }
+
+    /// Returns true if the guardians can currently not talk to each other
+    pub fn is_partitioned(&self, a: PeerId, b: PeerId) -> bool {
+        self.lock().is_partitioned(a, b)
+    }
+
+    /// Returns true if clients can currently not reach the guardian
+    pub fn is_isolated_from_clients(&self, peer: PeerId) -> bool {
+        self.lock().isolated_from_clients.contains(&peer)
+    }
+
+    fn p2p_delivery(&self, from: PeerId, to: PeerId) -> Delivery {
+        let mut state = self.lock();
+
+        if state.is_partitioned(from, to) {
+            return Delivery::Drop;
+        }
+
+        state.p2p_delivery(from, to)
+    }
+
+    fn client_delivery(&self, peer: PeerId) -> Delivery {
+        let mut state = self.lock();
+
+        if state.isolated_from_clients.contains(&peer) {
+            return Delivery::Drop;
+        }
+
+        state.client_delivery()
+    }
+
+    /// Wraps the connectors of a client, see
+    /// [`fedimint_connectors::ConnectorRegistryBuilder::with_connector_wrapper`]
+    pub fn wrap_connector(
+        &self,
+        connector: DynConnector,
+        peers_by_url: BTreeMap<SafeUrl, PeerId>,
+    ) -> DynConnector {
+        Arc::new(FaultInjectingConnector {
+            inner: connector,
+            faults: self.clone(),
+            peers_by_url,
+        })
+    }
+}
+
+/// Wraps the p2p connections of a guardian to apply [`NetworkFaults`]
+pub struct FaultInjectingP2PConnections<M> {
+    inner: DynP2PConnections<M>,
+    identity: PeerId,
+    peers: Vec<PeerId>,
+    faults: NetworkFaults,
+    task_group: TaskGroup,
+}
+
+impl<M> FaultInjectingP2PConnections<M> {
+    /// Delayed messages are delivered by tasks spawned on `task_group`, so
+    /// they are lost if the guardian crashes
+    pub fn new(
+        inner: DynP2PConnections<M>,
+        identity: PeerId,
+        peers: Vec<PeerId>,
+        faults: NetworkFaults,
+        task_group: &TaskGroup,
+    ) -> Self {
+        FaultInjectingP2PConnections {
+            inner,
+            identity,
+            peers,
+            faults,
+            task_group: task_group.clone(),
+        }
+    }
+
+    fn send_to_peer(&self, peer: PeerId, message: M)
+    where
+        M: Send + 'static,
+    {
+        match self.faults.p2p_delivery(self.identity, peer) {
+            Delivery::Drop => {}
+            Delivery::Delay(delay) if delay.is_zero() => {
+                self.inner.send(Recipient::Peer(peer), message);
+            }
+            Delivery::Delay(delay) => {
+                let inner = self.inner.clone();
+
+                self.task_group
+                    .spawn_cancellable_silent("delayed-p2p-message", async move {
+                        sleep(delay).await;
+
+                        inner.send(Recipient::Peer(peer), message);
+                    });
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl<M: Clone + Send + 'static> IP2PConnections<M> for FaultInjectingP2PConnections<M> {
+    fn send(&self, recipient: Recipient, message: M) {
+        match recipient {
+            Recipient::Everyone => {
+                for peer in &self.peers {
+                    self.send_to_peer(*peer, message.clone());
+                }
+            }
+            Recipient::Peer(peer) => self.send_to_peer(peer, message),
+        }
+    }
+
+    async fn receive(&self) -> Option<(PeerId, M)> {
+        loop {
+            let (peer, message) = self.inner.receive().await?;
+
+            // Messages that were in flight when the partition started are lost
+            if !self.faults.is_partitioned(peer, self.identity) {
+                return Some((peer, message));
+            }
+        }
+    }
+
+    async fn receive_from_peer(&self, peer: PeerId) -> Option<M> {
+        loop {
+            let message = self.inner.receive_from_peer(peer).await?;
+
+            if !self.faults.is_partitioned(peer, self.identity) {
+                return Some(message);
+            }
+        }
+    }
+}
+
+/// Wraps a client connector to apply [`NetworkFaults`] to the connections to
+/// the guardians
+struct FaultInjectingConnector {
+    inner: DynConnector,
+    faults: NetworkFaults,
+    peers_by_url: BTreeMap<SafeUrl, PeerId>,
+}
+
+impl fmt::Debug for FaultInjectingConnector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FaultInjectingConnector")
+            .field("inner", &self.inner)
+            .finish_non_exhaustive()
+    }
+}
+
+#[async_trait]
+impl Connector for FaultInjectingConnector {
+    async fn connect_guardian(
+        &self,
+        url: &SafeUrl,
+        api_secret: Option<&str>,
+    ) -> ServerResult<DynGuaridianConnection> {
+        let connection = self.inner.connect_guardian(url, api_secret).await?;
+
+        let Some(peer) = self.peers_by_url.get(url).copied() else {
+            return Ok(connection);
+        };
+
+        if self.faults.is_isolated_from_clients(peer) {
+            return Err(ServerError::Connection(anyhow!(
+                "Guardian {peer} is isolated from clients"
+            )));
+        }
+
+        Ok(FaultInjectingGuardianConnection {
+            inner: connection,
+            peer,
+            faults: self.faults.clone(),
+        }
+        .into_dyn())
+    }
+
+    async fn connect_gateway(&self, url: &SafeUrl) -> anyhow::Result<DynGatewayConnection> {
+        self.inner.connect_gateway(url).await
+    }
+
+    fn connectivity(&self, url: &SafeUrl) -> Connectivity {
+        self.inner.connectivity(url)
+    }
+}
+
+#[derive(Debug)]
+struct FaultInjectingGuardianConnection {
+    inner: DynGuaridianConnection,
+    peer: PeerId,
+    faults: NetworkFaults,
+}
+
+#[apply(async_trait_maybe_send!)]
+impl IConnection for FaultInjectingGuardianConnection {
+    fn is_connected(&self) -> bool {
+        // Forces the connection pool to reconnect, which fails while isolated
+        self.inner.is_connected() && !self.faults.is_isolated_from_clients(self.peer)
+    }
+
+    async fn await_disconnection(&self) {
+        self.inner.await_disconnection().await;
+    }
+}
+
+#[async_trait]
+impl IGuardianConnection for FaultInjectingGuardianConnection {
+    async fn request(&self, method: ApiMethod, request: ApiRequestErased) -> ServerResult<Value> {
+        match self.faults.client_delivery(self.peer) {
+            Delivery::Drop => Err(ServerError::Transport(anyhow!(
+                "Request to guardian {} was dropped",
+                self.peer
+            ))),
+            Delivery::Delay(delay) => {
+                sleep(delay).await;
+
+                self.inner.request(method, request).await
+            }
+        }
+    }
+}
+
+/// A fault applied to an in-process federation during a test
+#[derive(Debug, Clone)]
+pub enum Fault {
+    /// See [`NetworkFaults::set_latency`]
+    Latency { latency: Duration, jitter: Duration },
+    /// See [`NetworkFaults::set_drop_rate`]
+    DropRate(f64),
+    /// See [`NetworkFaults::partition`]
+    Partition(Vec<BTreeSet<PeerId>>),
+    /// See [`NetworkFaults::isolate_from_clients`]
+    IsolateFromClients(PeerId),
+    /// Stops all tasks of a guardian while keeping its database
+    Crash(PeerId),
+    /// Restarts a crashed guardian from its database
+    Restart(PeerId),
+    /// See [`NetworkFaults::heal`]
+    Heal,
+}
+
+/// Faults to apply once the federation completed a given number of sessions
+/// since the start of the schedule
+///
+/// Driving the schedule by consensus progress rather than by wall clock time
+/// keeps every fault active for the same number of sessions on slow machines.
+#[derive(Debug, Clone, Default)]
+pub struct FaultSchedule {
+    steps: Vec<(u64, Fault)>,
+}
+
+impl FaultSchedule {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Applies `fault` once the federation completed `sessions` sessions
+    /// since the start of the schedule
+    pub fn at(mut self, sessions: u64, fault: Fault) -> Self {
+        self.steps.push((sessions, fault));
+        self
+    }
+
+    /// Returns the steps sorted by their number of sessions
+    pub fn into_steps(mut self) -> Vec<(u64, Fault)> {
+        // the sort is stable, so faults after the same session keep their order
+        self.steps.sort_by_key(|(sessions, _)| *sessions);
+        self.steps
+    }
+}
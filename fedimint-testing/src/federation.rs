@@ -1,5 +1,5 @@
 use std::collections::BTreeMap;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
 use fedimint_api_client::api::{DynGlobalApi, FederationApiExt};
@@ -7,7 +7,7 @@
 use fedimint_client::{Client, ClientHandleArc, RootSecret};
 use fedimint_client_module::AdminCreds;
 use fedimint_client_module::secret::{PlainRootSecretStrategy, RootSecretStrategy};
-use fedimint_connectors::ConnectorRegistry;
+use fedimint_connectors::{ConnectorRegistry, ConnectorWrapper};
 use fedimint_core::PeerId;
 use fedimint_core::config::{ClientConfig, FederationId};
 use fedimint_core::core::ModuleKind;
@@ -24,6 +24,7 @@
 use fedimint_logging::LOG_TEST;
 use fedimint_rocksdb::RocksDb;
 use fedimint_server::config::ServerConfig;
+use fedimint_server::consensus::engine::get_finished_session_count_static;
 use fedimint_server::core::ServerModuleInitRegistry;
 use fedimint_server::net::api::ApiSecrets;
 use fedimint_server::net::p2p::{ReconnectP2PConnections, p2p_status_channels};
@@ -33,16 +34,28 @@
 use fedimint_testing_core::config::local_config_gen_params;
 use tracing::info;
 
+use crate::audit::{AuditInvariants, SessionSnapshots};
+use crate::faults::{Fault, FaultInjectingP2PConnections, FaultSchedule, NetworkFaults};
+
 /// Test fixture for a running fedimint federation
 #[derive(Clone)]
 pub struct FederationTest {
     configs: BTreeMap<PeerId, ServerConfig>,
     server_init: ServerModuleInitRegistry,
     client_init: ClientModuleInitRegistry,
-    _task: TaskGroup,
+    task_group: TaskGroup,
+    /// Task groups of the running peers, shutting one down crashes the peer
+    peer_task_groups: Arc<Mutex<BTreeMap<PeerId, TaskGroup>>>,
+    /// Databases of all peers, kept across crashes
+    dbs: BTreeMap<PeerId, Database>,
+    bitcoin_rpc_connection: DynServerBitcoinRpc,
+    base_port: u16,
     num_peers: u16,
     num_offline: u16,
+    faults: NetworkFaults,
     connectors: ConnectorRegistry,
+    /// Checks the audits of the peers after every session
+    audit_invariants: Arc<AuditInvariants>,
 }
 
 impl FederationTest {
@@ -228,14 +241,255 @@ pub async fn connect_gateway(&self, gw: &Gateway) {
 
     /// Return all online PeerIds
     pub fn online_peer_ids(&self) -> impl Iterator<Item = PeerId> + use<> {
-        // we can assume this ordering since peers are started in ascending order
-        (0..(self.num_peers - self.num_offline)).map(PeerId::from)
+        self.peer_task_groups
+            .lock()
+            .expect("locking failed")
+            .keys()
+            .copied()
+            .collect::<Vec<_>>()
+            .into_iter()
     }
 
     /// Returns true if the federation is running in a degraded state
     pub fn is_degraded(&self) -> bool {
         self.num_offline > 0
     }
+
+    /// Faults applied to the p2p network of the peers and to the connections
+    /// of the clients
+    pub fn faults(&self) -> &NetworkFaults {
+        &self.faults
+    }
+
+    /// Stops all tasks of a peer, its database is kept for
+    /// [`Self::restart_peer`]
+    pub async fn crash_peer(&self, peer_id: PeerId) {
+        let task_group = self
+            .peer_task_groups
+            .lock()
+            .expect("locking failed")
+            .remove(&peer_id)
+            .expect("Peer is not running");
+
+        info!(target: LOG_TEST, %peer_id, "Crashing peer");
+
+        task_group
+            .shutdown_join_all(Duration::from_secs(30))
+            .await
+            .expect("Failed to shut down peer");
+    }
+
+    /// Restarts a crashed peer from its database and waits for its api
+    pub async fn restart_peer(&self, peer_id: PeerId) {
+        info!(target: LOG_TEST, %peer_id, "Restarting peer");
+
+        self.start_peer(peer_id).await;
+        self.await_session_count_of(peer_id, 0).await;
+    }
+
+    /// Applies a single fault to the federation
+    pub async fn apply_fault(&self, fault: Fault) {
+        info!(target: LOG_TEST, ?fault, "Applying fault");
+
+        match fault {
+            Fault::Latency { latency, jitter } => self.faults.set_latency(latency, jitter),
+            Fault::DropRate(drop_rate) => self.faults.set_drop_rate(drop_rate),
+            Fault::Partition(groups) => self.faults.partition(groups),
+            Fault::IsolateFromClients(peer_id) => self.faults.isolate_from_clients(peer_id),
+            Fault::Crash(peer_id) => self.crash_peer(peer_id).await,
+            Fault::Restart(peer_id) => self.restart_peer(peer_id).await,
+            Fault::Heal => self.faults.heal(),
+        }
+    }
+
+    /// Applies the faults of the schedule once the federation completed their
+    /// number of sessions since the start of the schedule, returns after the
+    /// last one was applied
+    pub async fn run_fault_schedule(&self, schedule: FaultSchedule) {
+        let start = self.consensus_session_count().await;
+
+        for (sessions, fault) in schedule.into_steps() {
+            while self.consensus_session_count().await < start + sessions {
+                sleep_in_test(
+                    format!(
+                        "Waiting for the federation to complete {} sessions",
+                        start + sessions
+                    ),
+                    Duration::from_millis(100),
+                )
+                .await;
+            }
+
+            self.apply_fault(fault).await;
+        }
+    }
+
+    /// Returns the number of sessions completed by the peer furthest ahead.
+    /// This is read from the databases, as faults may stall or crash peers
+    /// and cut off their api.
+    async fn consensus_session_count(&self) -> u64 {
+        let mut session_count = 0;
+
+        for db in self.dbs.values() {
+            let count =
+                get_finished_session_count_static(&mut db.begin_transaction_nc().await).await;
+
+            session_count = session_count.max(count);
+        }
+
+        session_count
+    }
+
+    /// Returns the number of sessions completed by a peer
+    pub async fn session_count(&self, peer_id: PeerId) -> u64 {
+        self.await_session_count_of(peer_id, 0).await
+    }
+
+    /// Waits until every running peer completed at least `session_count`
+    /// sessions
+    pub async fn await_session_count(&self, session_count: u64) {
+        for peer_id in self.online_peer_ids() {
+            self.await_session_count_of(peer_id, session_count).await;
+        }
+
+        self.assert_audit_invariants();
+    }
+
+    /// Panics if the module audits of a peer checked after a session violated
+    /// an invariant so far: the net assets have to be non-negative, the audit
+    /// items have to match the module records in the database and all peers
+    /// have to agree on them. The check also runs once the federation is
+    /// dropped.
+    pub fn assert_audit_invariants(&self) {
+        self.audit_invariants.assert_no_violation();
+    }
+
+    async fn await_session_count_of(&self, peer_id: PeerId, session_count: u64) -> u64 {
+        let api = self
+            .new_admin_api(peer_id)
+            .await
+            .expect("Failed to create admin api");
+
+        loop {
+            match api
+                .request_admin_no_auth::<u64>(SESSION_COUNT_ENDPOINT, ApiRequestErased::default())
+                .await
+            {
+                Ok(count) if count >= session_count => return count,
+                Ok(count) => {
+                    sleep_in_test(
+                        format!(
+                            "Waiting for peer {peer_id} to complete {session_count} sessions, completed {count}"
+                        ),
+                        Duration::from_millis(500),
+                    )
+                    .await;
+                }
+                Err(e) => {
+                    sleep_in_test(
+                        format!("Waiting for api of peer {peer_id} to come online: {e}"),
+                        Duration::from_millis(500),
+                    )
+                    .await;
+                }
+            }
+        }
+    }
+
+    async fn start_peer(&self, peer_id: PeerId) {
+        let cfg = self.configs[&peer_id].clone();
+        let peer_port = self.base_port + u16::from(peer_id) * 3;
+
+        let p2p_bind = format!("127.0.0.1:{peer_port}").parse().unwrap();
+        let api_bind = format!("127.0.0.1:{}", peer_port + 1).parse().unwrap();
+        let ui_bind = format!("127.0.0.1:{}", peer_port + 2).parse().unwrap();
+
+        let db = self.dbs[&peer_id].clone();
+        let module_init_registry = self.server_init.clone();
+        let peer_task_group = self.task_group.make_subgroup();
+        let subgroup = peer_task_group.make_subgroup();
+        let checkpoint_dir = tempfile::Builder::new().tempdir().unwrap().keep();
+        let code_version_str = env!("CARGO_PKG_VERSION");
+
+        let connector = TlsTcpConnector::new(
+            cfg.tls_config(),
+            p2p_bind,
+            cfg.local.p2p_endpoints.clone(),
+            cfg.local.identity,
+        )
+        .await
+        .into_dyn();
+
+        let peers = connector.peers();
+
+        let (p2p_status_senders, p2p_status_receivers) = p2p_status_channels(peers.clone());
+
+        let connections = ReconnectP2PConnections::new(
+            cfg.local.identity,
+            connector,
+            &peer_task_group,
+            p2p_status_senders,
+        )
+        .into_dyn();
+
+        let connections = FaultInjectingP2PConnections::new(
+            connections,
+            cfg.local.identity,
+            peers,
+            self.faults.clone(),
+            &peer_task_group,
+        )
+        .into_dyn();
+
+        let bitcoin_rpc_connection = self.bitcoin_rpc_connection.clone();
+
+        let guardian_signer = cfg
+            .local_guardian_signer(&module_init_registry)
+            .expect("Failed to collect guardian keys");
+
+        // cancellable, so crashing the peer does not wait for the session to end
+        peer_task_group.spawn_cancellable("fedimintd", async move {
+            Box::pin(consensus::run(
+                ConnectorRegistry::build_from_testing_env()
+                    .unwrap()
+                    .bind()
+                    .await
+                    .unwrap(),
+                connections,
+                p2p_status_receivers,
+                api_bind,
+                None,
+                vec![],
+                cfg.clone(),
+                db,
+                module_init_registry,
+                &subgroup,
+                ApiSecrets::default(),
+                checkpoint_dir,
+                code_version_str.to_string(),
+                bitcoin_rpc_connection,
+                ui_bind,
+                Box::new(|_| axum::Router::new()),
+                1,
+                ConnectionLimits {
+                    max_connections: 1000,
+                    max_requests_per_connection: 100,
+                },
+                guardian_signer,
+                None,
+            ))
+            .await
+            .expect("Could not initialise consensus");
+        });
+
+        let previous = self
+            .peer_task_groups
+            .lock()
+            .expect("locking failed")
+            .insert(peer_id, peer_task_group);
+
+        assert!(previous.is_none(), "Peer {peer_id} is already running");
+    }
 }
 
 /// Builder struct for creating a `FederationTest`.
@@ -325,122 +579,74 @@ pub async fn build(self) -> FederationTest {
         let configs =
             ServerConfig::trusted_dealer_gen(&params, &self.server_init, &self.version_hash);
 
-        let task_group = TaskGroup::new();
-        for (peer_id, cfg) in configs.clone() {
-            let peer_port = self.base_port + u16::from(peer_id) * 3;
-
-            let p2p_bind = format!("127.0.0.1:{peer_port}").parse().unwrap();
-            let api_bind = format!("127.0.0.1:{}", peer_port + 1).parse().unwrap();
-            let ui_bind = format!("127.0.0.1:{}", peer_port + 2).parse().unwrap();
+        let snapshots = SessionSnapshots::default();
 
-            if u16::from(peer_id) >= self.num_peers - self.num_offline {
-                continue;
-            }
+        let dbs = configs
+            .iter()
+            .map(|(peer_id, cfg)| {
+                let instances = cfg.consensus.iter_module_instances();
+                let decoders = self.server_init.available_decoders(instances).unwrap();
 
-            let instances = cfg.consensus.iter_module_instances();
-            let decoders = self.server_init.available_decoders(instances).unwrap();
-            let db = Database::new(MemDatabase::new(), decoders);
-            let module_init_registry = self.server_init.clone();
-            let subgroup = task_group.make_subgroup();
-            let checkpoint_dir = tempfile::Builder::new().tempdir().unwrap().keep();
-            let code_version_str = env!("CARGO_PKG_VERSION");
-
-            let connector = TlsTcpConnector::new(
-                cfg.tls_config(),
-                p2p_bind,
-                cfg.local.p2p_endpoints.clone(),
-                cfg.local.identity,
-            )
-            .await
-            .into_dyn();
+                (*peer_id, snapshots.database(*peer_id, decoders))
+            })
+            .collect();
 
-            let (p2p_status_senders, p2p_status_receivers) = p2p_status_channels(connector.peers());
+        let faults = NetworkFaults::new();
 
-            let connections = ReconnectP2PConnections::new(
-                cfg.local.identity,
-                connector,
-                &task_group,
-                p2p_status_senders,
-            )
-            .into_dyn();
-
-            let bitcoin_rpc_connection = self.bitcoin_rpc_connection.clone();
-
-            task_group.spawn("fedimintd", move |_| async move {
-                Box::pin(consensus::run(
-                    ConnectorRegistry::build_from_testing_env()
-                        .unwrap()
-                        .bind()
-                        .await
-                        .unwrap(),
-                    connections,
-                    p2p_status_receivers,
-                    api_bind,
-                    None,
-                    vec![],
-                    cfg.clone(),
-                    db.clone(),
-                    module_init_registry,
-                    &subgroup,
-                    ApiSecrets::default(),
-                    checkpoint_dir,
-                    code_version_str.to_string(),
-                    bitcoin_rpc_connection,
-                    ui_bind,
-                    Box::new(|_| axum::Router::new()),
-                    1,
-                    ConnectionLimits {
-                        max_connections: 1000,
-                        max_requests_per_connection: 100,
-                    },
-                ))
-                .await
-                .expect("Could not initialise consensus");
-            });
-        }
+        let peers_by_url = configs[&PeerId::from(0)]
+            .consensus
+            .api_endpoints()
+            .into_iter()
+            .map(|(peer_id, endpoint)| (endpoint.url, peer_id))
+            .collect::<BTreeMap<_, _>>();
+
+        let connector_faults = faults.clone();
+        let connectors = ConnectorRegistry::build_from_testing_env()
+            .expect("Failed to initialize endpoints for testing (env)")
+            .with_connector_wrapper(ConnectorWrapper::new(move |connector| {
+                connector_faults.wrap_connector(connector, peers_by_url.clone())
+            }))
+            .bind()
+            .await
+            .expect("Failed to initialize endpoints for testing");
 
-        for (peer_id, config) in configs.clone() {
-            if u16::from(peer_id) >= self.num_peers - self.num_offline {
-                continue;
-            }
+        let task_group = TaskGroup::new();
 
-            let connectors = ConnectorRegistry::build_from_testing_env()
-                .unwrap()
-                .bind()
-                .await
-                .unwrap();
-            let api = DynGlobalApi::new_admin(
-                connectors,
-                peer_id,
-                config.consensus.api_endpoints()[&peer_id].url.clone(),
-                None,
-            )
-            .unwrap();
+        let audit_invariants = Arc::new(AuditInvariants::spawn(
+            &task_group,
+            snapshots,
+            configs.clone(),
+            self.server_init.clone(),
+            connectors.clone(),
+            self.bitcoin_rpc_connection.clone(),
+        ));
 
-            while let Err(e) = api
-                .request_admin_no_auth::<u64>(SESSION_COUNT_ENDPOINT, ApiRequestErased::default())
-                .await
-            {
-                sleep_in_test(
-                    format!("Waiting for api of peer {peer_id} to come online: {e}"),
-                    Duration::from_millis(500),
-                )
-                .await;
-            }
-        }
-
-        FederationTest {
+        let fed = FederationTest {
             configs,
             server_init: self.server_init,
             client_init: self.client_init,
-            _task: task_group,
+            task_group,
+            peer_task_groups: Arc::new(Mutex::new(BTreeMap::new())),
+            dbs,
+            bitcoin_rpc_connection: self.bitcoin_rpc_connection,
+            base_port: self.base_port,
             num_peers: self.num_peers,
             num_offline: self.num_offline,
-            connectors: ConnectorRegistry::build_from_testing_env()
-                .expect("Failed to initialize endpoints for testing (env)")
-                .bind()
-                .await
-                .expect("Failed to initialize endpoints for testing"),
+            faults,
+            connectors,
+            audit_invariants,
+        };
+
+        let online_peers = (0..(self.num_peers - self.num_offline)).map(PeerId::from);
+
+        for peer_id in online_peers.clone() {
+            fed.start_peer(peer_id).await;
         }
+
+        for peer_id in online_peers {
+            fed.await_session_count_of(peer_id, 0).await;
+        }
+
+        fed
     }
 }
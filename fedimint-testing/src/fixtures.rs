@@ -39,6 +39,8 @@
 };
 use crate::federation::{FederationTest, FederationTestBuilder};
 use crate::ln::FakeLightningTest;
+use crate::ln::mock::MockLightningNode;
+use crate::simulation::SimulationBuilder;
 
 /// A default timeout for things happening in tests
 pub const TIMEOUT: Duration = Duration::from_secs(10);
@@ -192,8 +194,32 @@ pub fn new_fed_builder(&self, num_offline: u16) -> FederationTestBuilder {
         )
     }
 
+    /// Creates a new `SimulationBuilder` to run the consensus of a federation
+    /// with these modules in a simulation, see [`crate::simulation`].
+    pub fn new_simulation(&self) -> SimulationBuilder {
+        SimulationBuilder::new(self.servers.clone(), self.server_bitcoin_rpc())
+    }
+
     /// Creates a new Gateway that can be used for module tests.
     pub async fn new_gateway(&self) -> Gateway {
+        self.build_gateway(Arc::new(FakeLightningTest::new())).await
+    }
+
+    /// Creates a new Gateway connected to a node of a
+    /// [`MockLightningNetwork`](crate::ln::mock::MockLightningNetwork) that
+    /// intercepts the HTLCs sent to it by the other nodes of the network.
+    pub async fn new_gateway_with_mock_lightning(&self, node: &MockLightningNode) -> Gateway {
+        let gateway = self.build_gateway(Arc::new(node.clone())).await;
+
+        gateway
+            .route_htlcs_from(Box::new(node.clone()))
+            .await
+            .expect("Failed to route HTLCs from mock lightning node");
+
+        gateway
+    }
+
+    async fn build_gateway(&self, ln_client: Arc<dyn ILnRpcClient>) -> Gateway {
         // Use server_gens.iter() to match the alphabetical order used by the server
         // when assigning module instance IDs (BTreeMap iteration order)
         let module_kinds: Vec<_> = self
@@ -231,12 +257,10 @@ pub async fn new_gateway(&self) -> Gateway {
 
         // Create federation client builder for the gateway
         let client_builder: GatewayClientBuilder =
-            GatewayClientBuilder::new(path.clone(), registry, DatabaseBackend::RocksDb)
+            GatewayClientBuilder::new(path.clone(), registry, DatabaseBackend::RocksDb, false)
                 .await
                 .expect("Failed to initialize gateway");
 
-        let ln_client: Arc<dyn ILnRpcClient> = Arc::new(FakeLightningTest::new());
-
         let LightningInfo::Connected {
             public_key: lightning_public_key,
             alias: lightning_alias,
@@ -286,8 +310,8 @@ pub async fn new_gateway(&self) -> Gateway {
         .network(bitcoin::Network::Regtest)
         .num_route_hints(0)
         // Manually set the gateway's state to `Running`. In tests, we don't run the
-        // webserver or intercept HTLCs, so this is necessary for instructing the
-        // gateway that it is connected to the mock Lightning node.
+        // webserver or connect to a lightning node, so this is necessary for
+        // instructing the gateway that it is connected to the mock Lightning node.
         .gateway_state(fedimint_gateway_server::GatewayState::Running { lightning_context })
         .chain_source(ChainSource::Esplora {
             server_url: esplora_server_url,
@@ -8,9 +8,12 @@
 #![allow(clippy::return_self_not_must_use)]
 #![allow(clippy::large_futures)]
 
+mod audit;
 pub mod btc;
+pub mod faults;
 pub mod federation;
 pub mod fixtures;
 pub mod ln;
+pub mod simulation;
 pub use fedimint_gateway_server::Gateway;
 pub use fedimint_testing_core::{db, envs};
@@ -0,0 +1,1181 @@
+//! An in-memory lightning network of mock nodes implementing [`ILnRpcClient`]
+//!
+//! Nodes are connected by simulated channels that track the liquidity on both
+//! sides. A payment is sent over a direct channel either to the node that
+//! created the invoice, which settles it if it knows the preimage, or to the
+//! last node of the invoice's route hints that is part of the network. If that
+//! node routes HTLCs via [`ILnRpcClient::route_htlcs`] the HTLC is intercepted
+//! and held until it is completed via [`ILnRpcClient::complete_htlc`], just
+//! like the lightning node of a gateway would do.
+//!
+//! No bitcoind is required: onchain funds are credited with
+//! [`MockLightningNode::fund_onchain`] and channels are usable as soon as they
+//! have been opened.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::num::NonZeroU64;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, SystemTime};
+
+use async_stream::stream;
+use async_trait::async_trait;
+use bitcoin::hashes::{Hash, sha256};
+use bitcoin::key::Keypair;
+use bitcoin::secp256k1::{self, PublicKey};
+use bitcoin::{Address, CompressedPublicKey, Network, OutPoint, Txid};
+use fedimint_core::task::TaskGroup;
+use fedimint_core::util::BoxStream;
+use fedimint_core::{Amount, BitcoinAmountOrAll};
+use fedimint_gateway_common::{
+    ChannelInfo, CloseChannelsWithPeerRequest, CloseChannelsWithPeerResponse, GetInvoiceRequest,
+    GetInvoiceResponse, ListTransactionsResponse, OpenChannelRequest, PaymentDetails,
+    PaymentDirection, PaymentKind, PaymentStatus, SendOnchainRequest,
+};
+use fedimint_lightning::{
+    CreateInvoiceRequest, CreateInvoiceResponse, GetBalancesResponse, GetLnOnchainAddressResponse,
+    GetNodeInfoResponse, GetRouteHintsResponse, ILnRpcClient, InterceptPaymentRequest,
+    InterceptPaymentResponse, InvoiceDescription, LightningRpcError, ListChannelsResponse,
+    OfferPayment, OpenChannelResponse, PayInvoiceResponse, PaymentAction, RouteHtlcStream,
+    RouteProbe, SendOnchainResponse,
+};
+use fedimint_ln_common::PrunedInvoice;
+use fedimint_ln_common::contracts::Preimage;
+use fedimint_ln_common::route_hints::{RouteHint, RouteHintHop};
+use fedimint_logging::LOG_TEST;
+use lightning::offers::offer::{self, Offer, OfferBuilder, Quantity};
+use lightning_invoice::{Bolt11Invoice, Currency, InvoiceBuilder, PaymentSecret};
+use rand::rngs::OsRng;
+use tokio::sync::{mpsc, oneshot};
+use tracing::{debug, info};
+
+/// The virtual size of a transaction spending one input to two outputs, used
+/// to charge fees for onchain transactions
+const ONCHAIN_TX_VSIZE: u64 = 141;
+
+/// A lightning network connecting [`MockLightningNode`]s
+#[derive(Debug, Clone, Default)]
+pub struct MockLightningNetwork {
+    state: Arc<Mutex<NetworkState>>,
+}
+
+#[derive(Debug, Default)]
+struct NetworkState {
+    nodes: BTreeMap<PublicKey, NodeState>,
+    channels: BTreeMap<u64, Channel>,
+    /// Source of channel and HTLC ids
+    next_id: u64,
+    block_height: u32,
+}
+
+#[derive(Debug)]
+struct NodeState {
+    alias: String,
+    secret_key: secp256k1::SecretKey,
+    onchain_balance_sats: u64,
+    /// Invoices and offer payments this node can receive by payment hash
+    invoices: BTreeMap<sha256::Hash, InvoiceState>,
+    /// Ids of the offers created by this node
+    offers: BTreeSet<String>,
+    offer_payments: Vec<OfferPayment>,
+    outgoing: BTreeMap<sha256::Hash, OutgoingState>,
+    transactions: Vec<PaymentDetails>,
+    interceptor: Option<mpsc::UnboundedSender<InterceptPaymentRequest>>,
+    intercepted: BTreeMap<(u64, u64), oneshot::Sender<PaymentAction>>,
+    payment_delay: Duration,
+    failing_payments: u64,
+    failing_payment_hashes: BTreeSet<sha256::Hash>,
+}
+
+#[derive(Debug)]
+struct InvoiceState {
+    /// Unknown if the payment has to be settled by intercepting the HTLC
+    preimage: Option<Preimage>,
+    amount: Amount,
+    created_at: SystemTime,
+    status: PaymentStatus,
+    offer_id: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+enum OutgoingState {
+    Pending,
+    Succeeded(Preimage),
+}
+
+#[derive(Debug)]
+struct Channel {
+    node_a: PublicKey,
+    node_b: PublicKey,
+    capacity_sats: u64,
+    balance_a_msat: u64,
+    funding_outpoint: OutPoint,
+}
+
+impl Channel {
+    fn peer(&self, node: &PublicKey) -> Option<PublicKey> {
+        if self.node_a == *node {
+            Some(self.node_b)
+        } else if self.node_b == *node {
+            Some(self.node_a)
+        } else {
+            None
+        }
+    }
+
+    fn local_balance_msat(&self, node: &PublicKey) -> u64 {
+        if self.node_a == *node {
+            self.balance_a_msat
+        } else {
+            self.capacity_sats * 1000 - self.balance_a_msat
+        }
+    }
+
+    /// Moves `amount_msat` from `from` to the other side of the channel
+    fn transfer(&mut self, from: &PublicKey, amount_msat: u64) {
+        if self.node_a == *from {
+            self.balance_a_msat -= amount_msat;
+        } else {
+            self.balance_a_msat += amount_msat;
+        }
+    }
+}
+
+/// Everything needed to route a payment, common to BOLT11 invoices, pruned
+/// invoices and BOLT12 offers
+struct PaymentTarget {
+    payment_hash: sha256::Hash,
+    amount: Amount,
+    destination: PublicKey,
+    /// The source nodes and short channel ids of the route hints' hops
+    hints: Vec<(PublicKey, u64)>,
+    kind: PaymentKind,
+}
+
+impl PaymentTarget {
+    fn from_invoice(invoice: &Bolt11Invoice) -> Result<Self, LightningRpcError> {
+        if invoice.is_expired() {
+            return Err(LightningRpcError::FailedPayment {
+                failure_reason: "Invoice has expired".to_string(),
+            });
+        }
+
+        Ok(Self {
+            payment_hash: *invoice.payment_hash(),
+            amount: Amount::from_msats(invoice.amount_milli_satoshis().ok_or(
+                LightningRpcError::FailedPayment {
+                    failure_reason: "Invoice is missing an amount".to_string(),
+                },
+            )?),
+            destination: invoice.recover_payee_pub_key(),
+            hints: invoice
+                .route_hints()
+                .iter()
+                .flat_map(|hint| hint.0.iter())
+                .map(|hop| (hop.src_node_id, hop.short_channel_id))
+                .collect(),
+            kind: PaymentKind::Bolt11,
+        })
+    }
+
+    fn from_pruned_invoice(invoice: &PrunedInvoice) -> Self {
+        Self {
+            payment_hash: invoice.payment_hash,
+            amount: invoice.amount,
+            destination: invoice.destination,
+            hints: invoice
+                .route_hints
+                .iter()
+                .flat_map(|hint| hint.0.iter())
+                .map(|hop| (hop.src_node_id, hop.short_channel_id))
+                .collect(),
+            kind: PaymentKind::Bolt11,
+        }
+    }
+}
+
+/// The node of the network a payment is sent to over a direct channel
+struct Hop {
+    node: PublicKey,
+    channel_id: u64,
+    short_channel_id: Option<u64>,
+}
+
+impl MockLightningNetwork {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a node without any funds or channels to the network
+    pub fn add_node(&self, alias: &str) -> MockLightningNode {
+        info!(target: LOG_TEST, %alias, "Adding mock lightning node");
+
+        let keypair = Keypair::new(secp256k1::SECP256K1, &mut OsRng);
+
+        self.lock().nodes.insert(
+            keypair.public_key(),
+            NodeState {
+                alias: alias.to_string(),
+                secret_key: keypair.secret_key(),
+                onchain_balance_sats: 0,
+                invoices: BTreeMap::new(),
+                offers: BTreeSet::new(),
+                offer_payments: vec![],
+                outgoing: BTreeMap::new(),
+                transactions: vec![],
+                interceptor: None,
+                intercepted: BTreeMap::new(),
+                payment_delay: Duration::ZERO,
+                failing_payments: 0,
+                failing_payment_hashes: BTreeSet::new(),
+            },
+        );
+
+        MockLightningNode {
+            network: self.clone(),
+            pub_key: keypair.public_key(),
+        }
+    }
+
+    /// Sets the block height reported by all nodes
+    pub fn set_block_height(&self, block_height: u32) {
+        self.lock().block_height = block_height;
+    }
+
+    fn lock(&self) -> MutexGuard<'_, NetworkState> {
+        self.state.lock().expect("locking failed")
+    }
+}
+
+impl NetworkState {
+    fn node(&mut self, pub_key: &PublicKey) -> &mut NodeState {
+        self.nodes
+            .get_mut(pub_key)
+            .expect("Mock lightning nodes are never removed")
+    }
+
+    fn next_id(&mut self) -> u64 {
+        self.next_id += 1;
+        self.next_id
+    }
+
+    /// Finds the node the payer sends the payment to over a direct channel
+    /// with sufficient outbound liquidity
+    fn route(&self, payer: &PublicKey, target: &PaymentTarget) -> Result<Hop, String> {
+        let (node, short_channel_id) = if self.nodes.contains_key(&target.destination) {
+            (target.destination, None)
+        } else {
+            // The hop closest to the destination is the one that intercepts
+            // the HTLC, like the node of a gateway
+            target
+                .hints
+                .iter()
+                .rev()
+                .find(|(node, _)| self.nodes.contains_key(node))
+                .map(|(node, scid)| (*node, Some(*scid)))
+                .ok_or("No route to the destination".to_string())?
+        };
+
+        if node == *payer {
+            return Err("Cannot pay ourselves".to_string());
+        }
+
+        let channel_id = self
+            .channels
+            .iter()
+            .find(|(_, channel)| {
+                channel.peer(payer) == Some(node)
+                    && target.amount.msats <= channel.local_balance_msat(payer)
+            })
+            .map(|(channel_id, _)| *channel_id)
+            .ok_or("No channel with sufficient liquidity".to_string())?;
+
+        Ok(Hop {
+            node,
+            channel_id,
+            short_channel_id,
+        })
+    }
+
+    fn record_transaction(
+        &mut self,
+        node: &PublicKey,
+        target: &PaymentTarget,
+        preimage: Option<Preimage>,
+        direction: PaymentDirection,
+        status: PaymentStatus,
+    ) {
+        self.node(node).transactions.push(PaymentDetails {
+            payment_hash: Some(target.payment_hash),
+            preimage: preimage.map(|preimage| fedimint_core::hex::encode(preimage.0)),
+            payment_kind: target.kind.clone(),
+            amount: target.amount,
+            direction,
+            status,
+            timestamp_secs: fedimint_core::time::duration_since_epoch().as_secs(),
+        });
+    }
+
+    /// Moves the payment's amount over the channel to the hop, so it cannot be
+    /// spent by another payment while the HTLC is pending
+    fn lock_htlc(&mut self, payer: &PublicKey, hop: &Hop, target: &PaymentTarget) {
+        if let Some(channel) = self.channels.get_mut(&hop.channel_id) {
+            channel.transfer(payer, target.amount.msats);
+        }
+    }
+
+    /// Returns the amount of a failed HTLC to the payer
+    fn refund_htlc(&mut self, hop: &Hop, target: &PaymentTarget) {
+        if let Some(channel) = self.channels.get_mut(&hop.channel_id) {
+            channel.transfer(&hop.node, target.amount.msats);
+        }
+    }
+
+    /// Completes the payment at the receiving node
+    fn settle(&mut self, hop: &Hop, target: &PaymentTarget, preimage: Preimage) {
+        let node = self.node(&hop.node);
+
+        if let Some(invoice) = node.invoices.get_mut(&target.payment_hash) {
+            invoice.status = PaymentStatus::Succeeded;
+
+            if let Some(offer_id) = invoice.offer_id.clone() {
+                node.offer_payments.push(OfferPayment {
+                    offer_id,
+                    payment_hash: target.payment_hash,
+                    amount: target.amount,
+                    timestamp_secs: fedimint_core::time::duration_since_epoch().as_secs(),
+                });
+            }
+        }
+
+        self.record_transaction(
+            &hop.node,
+            target,
+            Some(preimage),
+            PaymentDirection::Inbound,
+            PaymentStatus::Succeeded,
+        );
+    }
+}
+
+/// A node of a [`MockLightningNetwork`]
+#[derive(Debug, Clone)]
+pub struct MockLightningNode {
+    network: MockLightningNetwork,
+    pub_key: PublicKey,
+}
+
+impl MockLightningNode {
+    pub fn pub_key(&self) -> PublicKey {
+        self.pub_key
+    }
+
+    /// Credits onchain funds to the node's wallet
+    pub fn fund_onchain(&self, amount_sats: u64) {
+        self.network.lock().node(&self.pub_key).onchain_balance_sats += amount_sats;
+    }
+
+    /// Opens a channel to the peer without spending onchain funds, pushing
+    /// `push_amount_sats` to the peer's side. Returns the channel id.
+    pub fn connect(
+        &self,
+        peer: &MockLightningNode,
+        channel_size_sats: u64,
+        push_amount_sats: u64,
+    ) -> u64 {
+        assert!(push_amount_sats <= channel_size_sats);
+
+        let mut state = self.network.lock();
+
+        let channel_id = state.next_id();
+
+        state.channels.insert(
+            channel_id,
+            Channel {
+                node_a: self.pub_key,
+                node_b: peer.pub_key,
+                capacity_sats: channel_size_sats,
+                balance_a_msat: (channel_size_sats - push_amount_sats) * 1000,
+                funding_outpoint: OutPoint {
+                    txid: Txid::from_byte_array(rand::random()),
+                    vout: 0,
+                },
+            },
+        );
+
+        channel_id
+    }
+
+    /// Delays every outgoing payment of this node
+    pub fn set_payment_delay(&self, delay: Duration) {
+        self.network.lock().node(&self.pub_key).payment_delay = delay;
+    }
+
+    /// Fails the next `count` outgoing payments of this node
+    pub fn fail_next_payments(&self, count: u64) {
+        self.network.lock().node(&self.pub_key).failing_payments = count;
+    }
+
+    /// Fails every outgoing payment of this node for the given payment hash
+    pub fn fail_payments_to(&self, payment_hash: sha256::Hash) {
+        self.network
+            .lock()
+            .node(&self.pub_key)
+            .failing_payment_hashes
+            .insert(payment_hash);
+    }
+
+    /// Creates an invoice this node settles itself once it is paid
+    pub fn invoice(&self, amount: Amount) -> Bolt11Invoice {
+        let preimage = Preimage(rand::random());
+
+        self.create_invoice_with_preimage(
+            sha256::Hash::hash(&preimage.0),
+            Some(preimage),
+            amount,
+            None,
+            3600,
+        )
+        .expect("Invoice creation failed")
+    }
+
+    fn onchain_address(&self) -> Address {
+        Address::p2wpkh(&CompressedPublicKey(self.pub_key), Network::Regtest)
+    }
+
+    fn create_invoice_with_preimage(
+        &self,
+        payment_hash: sha256::Hash,
+        preimage: Option<Preimage>,
+        amount: Amount,
+        description: Option<InvoiceDescription>,
+        expiry_secs: u32,
+    ) -> Result<Bolt11Invoice, LightningRpcError> {
+        let mut state = self.network.lock();
+        let node = state.node(&self.pub_key);
+
+        let builder = InvoiceBuilder::new(Currency::Regtest);
+
+        let builder = match description {
+            Some(InvoiceDescription::Direct(description)) => builder.description(description),
+            Some(InvoiceDescription::Hash(hash)) => builder.description_hash(hash),
+            None => builder.description(String::new()),
+        };
+
+        let invoice = builder
+            .payment_hash(payment_hash)
+            .current_timestamp()
+            .min_final_cltv_expiry_delta(18)
+            .payment_secret(PaymentSecret(rand::random()))
+            .amount_milli_satoshis(amount.msats)
+            .expiry_time(Duration::from_secs(u64::from(expiry_secs)))
+            .build_signed(|m| secp256k1::SECP256K1.sign_ecdsa_recoverable(m, &node.secret_key))
+            .map_err(|e| LightningRpcError::FailedToGetInvoice {
+                failure_reason: e.to_string(),
+            })?;
+
+        node.invoices.insert(
+            payment_hash,
+            InvoiceState {
+                preimage,
+                amount,
+                created_at: fedimint_core::time::now(),
+                status: PaymentStatus::Pending,
+                offer_id: None,
+            },
+        );
+
+        Ok(invoice)
+    }
+
+    /// Sends a payment, waiting for an in-flight payment with the same payment
+    /// hash to complete instead of paying twice. Failed payments may be
+    /// retried.
+    async fn send_payment(&self, target: &PaymentTarget) -> Result<Preimage, LightningRpcError> {
+        let (delay, fail) = loop {
+            let existing = {
+                let mut state = self.network.lock();
+                let node = state.node(&self.pub_key);
+
+                match node.outgoing.get(&target.payment_hash) {
+                    Some(OutgoingState::Pending) => None,
+                    Some(OutgoingState::Succeeded(preimage)) => Some(preimage.clone()),
+                    None => {
+                        node.outgoing
+                            .insert(target.payment_hash, OutgoingState::Pending);
+
+                        let fail = node.failing_payment_hashes.contains(&target.payment_hash)
+                            || node.failing_payments > 0;
+
+                        node.failing_payments = node.failing_payments.saturating_sub(1);
+
+                        break (node.payment_delay, fail);
+                    }
+                }
+            };
+
+            if let Some(preimage) = existing {
+                return Ok(preimage);
+            }
+
+            fedimint_core::task::sleep(Duration::from_millis(10)).await;
+        };
+
+        fedimint_core::task::sleep(delay).await;
+
+        let result = if fail {
+            Err("Payment failed as configured".to_string())
+        } else {
+            self.route_payment(target).await
+        };
+
+        let mut state = self.network.lock();
+
+        let (status, preimage) = match &result {
+            Ok(preimage) => {
+                state.node(&self.pub_key).outgoing.insert(
+                    target.payment_hash,
+                    OutgoingState::Succeeded(preimage.clone()),
+                );
+
+                (PaymentStatus::Succeeded, Some(preimage.clone()))
+            }
+            Err(_) => {
+                state
+                    .node(&self.pub_key)
+                    .outgoing
+                    .remove(&target.payment_hash);
+
+                (PaymentStatus::Failed, None)
+            }
+        };
+
+        state.record_transaction(
+            &self.pub_key,
+            target,
+            preimage,
+            PaymentDirection::Outbound,
+            status,
+        );
+
+        result.map_err(|failure_reason| LightningRpcError::FailedPayment { failure_reason })
+    }
+
+    async fn route_payment(&self, target: &PaymentTarget) -> Result<Preimage, String> {
+        let (hop, receiver) = {
+            let mut state = self.network.lock();
+
+            let hop = state.route(&self.pub_key, target)?;
+
+            state.lock_htlc(&self.pub_key, &hop, target);
+
+            let known_preimage = state
+                .node(&hop.node)
+                .invoices
+                .get(&target.payment_hash)
+                .and_then(|invoice| invoice.preimage.clone());
+
+            if let Some(preimage) = known_preimage {
+                state.settle(&hop, target, preimage.clone());
+
+                return Ok(preimage);
+            }
+
+            let htlc_id = state.next_id();
+
+            let request = InterceptPaymentRequest {
+                payment_hash: target.payment_hash,
+                amount_msat: target.amount.msats,
+                expiry: state.block_height + 144,
+                incoming_chan_id: hop.channel_id,
+                short_channel_id: hop.short_channel_id,
+                htlc_id,
+            };
+
+            let node = state.node(&hop.node);
+
+            let intercepted = node
+                .interceptor
+                .as_ref()
+                .is_some_and(|interceptor| interceptor.send(request).is_ok());
+
+            if !intercepted {
+                state.refund_htlc(&hop, target);
+
+                return Err("Payee does not know the preimage".to_string());
+            }
+
+            let (sender, receiver) = oneshot::channel();
+
+            state
+                .node(&hop.node)
+                .intercepted
+                .insert((hop.channel_id, htlc_id), sender);
+
+            debug!(target: LOG_TEST, payment_hash = %target.payment_hash, "Intercepted HTLC at mock lightning node");
+
+            (hop, receiver)
+        };
+
+        let result = match receiver.await {
+            Ok(PaymentAction::Settle(preimage))
+                if sha256::Hash::hash(&preimage.0) == target.payment_hash =>
+            {
+                Ok(preimage)
+            }
+            Ok(PaymentAction::Settle(_)) => {
+                Err("HTLC was settled with an invalid preimage".to_string())
+            }
+            Ok(PaymentAction::Cancel) => Err("HTLC was cancelled".to_string()),
+            Ok(PaymentAction::Forward) => {
+                Err("Forwarding is not supported by the mock lightning network".to_string())
+            }
+            Err(_) => Err("HTLC was dropped".to_string()),
+        };
+
+        let mut state = self.network.lock();
+
+        match &result {
+            Ok(preimage) => state.settle(&hop, target, preimage.clone()),
+            Err(_) => state.refund_htlc(&hop, target),
+        }
+
+        result
+    }
+}
+
+#[async_trait]
+impl ILnRpcClient for MockLightningNode {
+    async fn info(&self) -> Result<GetNodeInfoResponse, LightningRpcError> {
+        let mut state = self.network.lock();
+        let block_height = state.block_height;
+
+        Ok(GetNodeInfoResponse {
+            pub_key: self.pub_key,
+            alias: state.node(&self.pub_key).alias.clone(),
+            network: "regtest".to_string(),
+            block_height,
+            synced_to_chain: true,
+        })
+    }
+
+    async fn routehints(
+        &self,
+        num_route_hints: usize,
+    ) -> Result<GetRouteHintsResponse, LightningRpcError> {
+        let state = self.network.lock();
+
+        let mut channels = state
+            .channels
+            .iter()
+            .filter_map(|(channel_id, channel)| {
+                let peer = channel.peer(&self.pub_key)?;
+
+                Some((channel.local_balance_msat(&peer), peer, *channel_id))
+            })
+            .collect::<Vec<_>>();
+
+        // Order by inbound liquidity
+        channels.sort_by(|a, b| b.0.cmp(&a.0));
+
+        Ok(GetRouteHintsResponse {
+            route_hints: channels
+                .into_iter()
+                .take(num_route_hints)
+                .map(|(_, peer, channel_id)| {
+                    RouteHint(vec![RouteHintHop {
+                        src_node_id: peer,
+                        short_channel_id: channel_id,
+                        base_msat: 0,
+                        proportional_millionths: 0,
+                        cltv_expiry_delta: 144,
+                        htlc_minimum_msat: None,
+                        htlc_maximum_msat: None,
+                    }])
+                })
+                .collect(),
+        })
+    }
+
+    async fn pay(
+        &self,
+        invoice: Bolt11Invoice,
+        _max_delay: u64,
+        _max_fee: Amount,
+    ) -> Result<PayInvoiceResponse, LightningRpcError> {
+        let preimage = self
+            .send_payment(&PaymentTarget::from_invoice(&invoice)?)
+            .await?;
+
+        Ok(PayInvoiceResponse { preimage })
+    }
+
+    fn supports_private_payments(&self) -> bool {
+        true
+    }
+
+    async fn pay_private(
+        &self,
+        invoice: PrunedInvoice,
+        _max_delay: u64,
+        _max_fee: Amount,
+    ) -> Result<PayInvoiceResponse, LightningRpcError> {
+        let preimage = self
+            .send_payment(&PaymentTarget::from_pruned_invoice(&invoice))
+            .await?;
+
+        Ok(PayInvoiceResponse { preimage })
+    }
+
+    async fn route_htlcs<'a>(
+        self: Box<Self>,
+        task_group: &TaskGroup,
+    ) -> Result<(RouteHtlcStream<'a>, Arc<dyn ILnRpcClient>), LightningRpcError> {
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+
+        self.network.lock().node(&self.pub_key).interceptor = Some(sender);
+
+        let shutdown_receiver = task_group.make_handle().make_shutdown_rx();
+
+        let stream: BoxStream<'a, InterceptPaymentRequest> = Box::pin(stream! {
+            let mut shutdown_receiver = std::pin::pin!(shutdown_receiver);
+
+            loop {
+                let htlc = tokio::select! {
+                    htlc = receiver.recv() => htlc,
+                    () = &mut shutdown_receiver => None,
+                };
+
+                match htlc {
+                    Some(htlc) => yield htlc,
+                    None => break,
+                }
+            }
+        });
+
+        Ok((stream, Arc::new(*self)))
+    }
+
+    async fn complete_htlc(&self, htlc: InterceptPaymentResponse) -> Result<(), LightningRpcError> {
+        let sender = self
+            .network
+            .lock()
+            .node(&self.pub_key)
+            .intercepted
+            .remove(&(htlc.incoming_chan_id, htlc.htlc_id))
+            .ok_or(LightningRpcError::FailedToCompleteHtlc {
+                failure_reason: "Unknown HTLC".to_string(),
+            })?;
+
+        // The payer may have given up on the payment already
+        let _ = sender.send(htlc.action);
+
+        Ok(())
+    }
+
+    async fn create_invoice(
+        &self,
+        create_invoice_request: CreateInvoiceRequest,
+    ) -> Result<CreateInvoiceResponse, LightningRpcError> {
+        // Invoices for a given payment hash are settled by intercepting the
+        // HTLC, otherwise the node settles the payment itself
+        let (payment_hash, preimage) = match create_invoice_request.payment_hash {
+            Some(payment_hash) => (payment_hash, None),
+            None => {
+                let preimage = Preimage(rand::random());
+
+                (sha256::Hash::hash(&preimage.0), Some(preimage))
+            }
+        };
+
+        let invoice = self.create_invoice_with_preimage(
+            payment_hash,
+            preimage,
+            Amount::from_msats(create_invoice_request.amount_msat),
+            create_invoice_request.description,
+            create_invoice_request.expiry_secs,
+        )?;
+
+        Ok(CreateInvoiceResponse {
+            invoice: invoice.to_string(),
+        })
+    }
+
+    async fn get_ln_onchain_address(
+        &self,
+    ) -> Result<GetLnOnchainAddressResponse, LightningRpcError> {
+        Ok(GetLnOnchainAddressResponse {
+            address: self.onchain_address().to_string(),
+        })
+    }
+
+    async fn send_onchain(
+        &self,
+        payload: SendOnchainRequest,
+    ) -> Result<SendOnchainResponse, LightningRpcError> {
+        let mut state = self.network.lock();
+
+        let fee_sats = payload.fee_rate_sats_per_vbyte * ONCHAIN_TX_VSIZE;
+        let balance_sats = state.node(&self.pub_key).onchain_balance_sats;
+
+        let amount_sats = match payload.amount {
+            BitcoinAmountOrAll::All => balance_sats.saturating_sub(fee_sats),
+            BitcoinAmountOrAll::Amount(amount) => amount.to_sat(),
+        };
+
+        if balance_sats < amount_sats + fee_sats {
+            return Err(LightningRpcError::FailedToWithdrawOnchain {
+                failure_reason: "Insufficient onchain funds".to_string(),
+            });
+        }
+
+        state.node(&self.pub_key).onchain_balance_sats -= amount_sats + fee_sats;
+
+        let script_pubkey = payload.address.assume_checked_ref().script_pubkey();
+
+        let recipient = state.nodes.keys().copied().find(|node| {
+            Address::p2wpkh(&CompressedPublicKey(*node), Network::Regtest).script_pubkey()
+                == script_pubkey
+        });
+
+        let target = PaymentTarget {
+            payment_hash: sha256::Hash::hash(&rand::random::<[u8; 32]>()),
+            amount: Amount::from_sats(amount_sats),
+            destination: self.pub_key,
+            hints: vec![],
+            kind: PaymentKind::Onchain,
+        };
+
+        state.record_transaction(
+            &self.pub_key,
+            &target,
+            None,
+            PaymentDirection::Outbound,
+            PaymentStatus::Succeeded,
+        );
+
+        if let Some(recipient) = recipient {
+            state.node(&recipient).onchain_balance_sats += amount_sats;
+
+            state.record_transaction(
+                &recipient,
+                &target,
+                None,
+                PaymentDirection::Inbound,
+                PaymentStatus::Succeeded,
+            );
+        }
+
+        Ok(SendOnchainResponse {
+            txid: Txid::from_byte_array(rand::random()).to_string(),
+        })
+    }
+
+    async fn open_channel(
+        &self,
+        payload: OpenChannelRequest,
+    ) -> Result<OpenChannelResponse, LightningRpcError> {
+        let mut state = self.network.lock();
+
+        if payload.pubkey == self.pub_key || !state.nodes.contains_key(&payload.pubkey) {
+            return Err(LightningRpcError::FailedToOpenChannel {
+                failure_reason: "Unknown peer".to_string(),
+            });
+        }
+
+        if payload.channel_size_sats < payload.push_amount_sats {
+            return Err(LightningRpcError::FailedToOpenChannel {
+                failure_reason: "Push amount exceeds the channel size".to_string(),
+            });
+        }
+
+        let node = state.node(&self.pub_key);
+
+        if node.onchain_balance_sats < payload.channel_size_sats {
+            return Err(LightningRpcError::FailedToOpenChannel {
+                failure_reason: "Insufficient onchain funds".to_string(),
+            });
+        }
+
+        node.onchain_balance_sats -= payload.channel_size_sats;
+
+        let channel_id = state.next_id();
+        let funding_outpoint = OutPoint {
+            txid: Txid::from_byte_array(rand::random()),
+            vout: 0,
+        };
+
+        state.channels.insert(
+            channel_id,
+            Channel {
+                node_a: self.pub_key,
+                node_b: payload.pubkey,
+                capacity_sats: payload.channel_size_sats,
+                balance_a_msat: (payload.channel_size_sats - payload.push_amount_sats) * 1000,
+                funding_outpoint,
+            },
+        );
+
+        Ok(OpenChannelResponse {
+            funding_txid: funding_outpoint.txid.to_string(),
+        })
+    }
+
+    async fn close_channels_with_peer(
+        &self,
+        payload: CloseChannelsWithPeerRequest,
+    ) -> Result<CloseChannelsWithPeerResponse, LightningRpcError> {
+        let mut state = self.network.lock();
+
+        let channel_ids = state
+            .channels
+            .iter()
+            .filter(|(_, channel)| channel.peer(&self.pub_key) == Some(payload.pubkey))
+            .map(|(channel_id, _)| *channel_id)
+            .collect::<Vec<_>>();
+
+        for channel_id in &channel_ids {
+            let channel = state
+                .channels
+                .remove(channel_id)
+                .expect("Channel id was just looked up");
+
+            for node in [channel.node_a, channel.node_b] {
+                state.node(&node).onchain_balance_sats += channel.local_balance_msat(&node) / 1000;
+            }
+        }
+
+        Ok(CloseChannelsWithPeerResponse {
+            num_channels_closed: channel_ids.len() as u32,
+        })
+    }
+
+    async fn list_channels(&self) -> Result<ListChannelsResponse, LightningRpcError> {
+        let state = self.network.lock();
+
+        Ok(ListChannelsResponse {
+            channels: state
+                .channels
+                .values()
+                .filter_map(|channel| {
+                    let peer = channel.peer(&self.pub_key)?;
+
+                    Some(ChannelInfo {
+                        remote_pubkey: peer,
+                        channel_size_sats: channel.capacity_sats,
+                        outbound_liquidity_sats: channel.local_balance_msat(&self.pub_key) / 1000,
+                        inbound_liquidity_sats: channel.local_balance_msat(&peer) / 1000,
+                        is_active: true,
+                        funding_outpoint: Some(channel.funding_outpoint),
+                        remote_node_alias: state.nodes.get(&peer).map(|node| node.alias.clone()),
+                        remote_address: None,
+                    })
+                })
+                .collect(),
+        })
+    }
+
+    async fn get_balances(&self) -> Result<GetBalancesResponse, LightningRpcError> {
+        let state = self.network.lock();
+
+        let (lightning_balance_msats, inbound_lightning_liquidity_msats) = state
+            .channels
+            .values()
+            .filter_map(|channel| {
+                let peer = channel.peer(&self.pub_key)?;
+
+                Some((
+                    channel.local_balance_msat(&self.pub_key),
+                    channel.local_balance_msat(&peer),
+                ))
+            })
+            .fold((0, 0), |(local, remote), (l, r)| (local + l, remote + r));
+
+        Ok(GetBalancesResponse {
+            onchain_balance_sats: state.nodes[&self.pub_key].onchain_balance_sats,
+            lightning_balance_msats,
+            inbound_lightning_liquidity_msats,
+        })
+    }
+
+    async fn get_invoice(
+        &self,
+        get_invoice_request: GetInvoiceRequest,
+    ) -> Result<Option<GetInvoiceResponse>, LightningRpcError> {
+        let state = self.network.lock();
+
+        Ok(state.nodes[&self.pub_key]
+            .invoices
+            .get(&get_invoice_request.payment_hash)
+            .map(|invoice| GetInvoiceResponse {
+                preimage: invoice
+                    .preimage
+                    .as_ref()
+                    .map(|preimage| fedimint_core::hex::encode(preimage.0)),
+                payment_hash: Some(get_invoice_request.payment_hash),
+                amount: invoice.amount,
+                created_at: invoice.created_at,
+                status: invoice.status.clone(),
+            }))
+    }
+
+    async fn list_transactions(
+        &self,
+        start_secs: u64,
+        end_secs: u64,
+    ) -> Result<ListTransactionsResponse, LightningRpcError> {
+        let state = self.network.lock();
+
+        Ok(ListTransactionsResponse {
+            transactions: state.nodes[&self.pub_key]
+                .transactions
+                .iter()
+                .filter(|tx| start_secs <= tx.timestamp_secs && tx.timestamp_secs < end_secs)
+                .cloned()
+                .collect(),
+        })
+    }
+
+    fn create_offer(
+        &self,
+        amount: Option<Amount>,
+        description: Option<String>,
+        expiry_secs: Option<u32>,
+        quantity: Option<u64>,
+    ) -> Result<String, LightningRpcError> {
+        let mut builder = OfferBuilder::new(self.pub_key)
+            .chain(Network::Regtest)
+            .description(description.unwrap_or_default());
+
+        if let Some(amount) = amount {
+            builder = builder.amount_msats(amount.msats);
+        }
+
+        if let Some(expiry_secs) = expiry_secs {
+            builder = builder.absolute_expiry(
+                fedimint_core::time::duration_since_epoch()
+                    + Duration::from_secs(u64::from(expiry_secs)),
+            );
+        }
+
+        if let Some(quantity) = quantity.and_then(NonZeroU64::new) {
+            builder = builder.supported_quantity(Quantity::Bounded(quantity));
+        }
+
+        let offer = builder
+            .build()
+            .map_err(|e| LightningRpcError::Bolt12Error {
+                failure_reason: format!("{e:?}"),
+            })?;
+
+        self.network
+            .lock()
+            .node(&self.pub_key)
+            .offers
+            .insert(fedimint_core::hex::encode(offer.id().0));
+
+        Ok(offer.to_string())
+    }
+
+    async fn pay_offer(
+        &self,
+        offer: String,
+        quantity: Option<u64>,
+        amount: Option<Amount>,
+        _payer_note: Option<String>,
+    ) -> Result<Preimage, LightningRpcError> {
+        let offer = Offer::from_str(&offer).map_err(|_| LightningRpcError::Bolt12Error {
+            failure_reason: "Failed to parse Bolt12 Offer".to_string(),
+        })?;
+
+        if offer.is_expired() {
+            return Err(LightningRpcError::Bolt12Error {
+                failure_reason: "Offer has expired".to_string(),
+            });
+        }
+
+        let amount = match (amount, offer.amount()) {
+            (Some(amount), _) => amount,
+            (None, Some(offer::Amount::Bitcoin { amount_msats })) => {
+                Amount::from_msats(amount_msats * quantity.unwrap_or(1))
+            }
+            (None, _) => {
+                return Err(LightningRpcError::Bolt12Error {
+                    failure_reason: "Offer does not specify a bitcoin amount".to_string(),
+                });
+            }
+        };
+
+        let offer_id = fedimint_core::hex::encode(offer.id().0);
+        let preimage = Preimage(rand::random());
+        let payment_hash = sha256::Hash::hash(&preimage.0);
+
+        // The recipient creates an invoice for the payment when requested by
+        // the payer, which we shortcut here
+        let destination = {
+            let mut state = self.network.lock();
+
+            let destination = state
+                .nodes
+                .iter()
+                .find(|(_, node)| node.offers.contains(&offer_id))
+                .map(|(pub_key, _)| *pub_key)
+                .ok_or(LightningRpcError::FailedPayment {
+                    failure_reason: "No route to the offer's issuer".to_string(),
+                })?;
+
+            state.node(&destination).invoices.insert(
+                payment_hash,
+                InvoiceState {
+                    preimage: Some(preimage),
+                    amount,
+                    created_at: fedimint_core::time::now(),
+                    status: PaymentStatus::Pending,
+                    offer_id: Some(offer_id),
+                },
+            );
+
+            destination
+        };
+
+        self.send_payment(&PaymentTarget {
+            payment_hash,
+            amount,
+            destination,
+            hints: vec![],
+            kind: PaymentKind::Bolt12Offer,
+        })
+        .await
+    }
+
+    async fn probe_route(&self, invoice: Bolt11Invoice) -> Result<RouteProbe, LightningRpcError> {
+        let target = PaymentTarget::from_invoice(&invoice)?;
+
+        match self.network.lock().route(&self.pub_key, &target) {
+            Ok(_) => Ok(RouteProbe {
+                fee: Some(Amount::ZERO),
+                success_likelihood: Some(100),
+            }),
+            Err(_) => Ok(RouteProbe {
+                fee: None,
+                success_likelihood: Some(0),
+            }),
+        }
+    }
+
+    async fn list_offer_payments(
+        &self,
+        since_secs: u64,
+    ) -> Result<Vec<OfferPayment>, LightningRpcError> {
+        Ok(self.network.lock().nodes[&self.pub_key]
+            .offer_payments
+            .iter()
+            .filter(|payment| payment.timestamp_secs >= since_secs)
+            .cloned()
+            .collect())
+    }
+
+    fn sync_wallet(&self) -> Result<(), LightningRpcError> {
+        Ok(())
+    }
+}
@@ -1,3 +1,5 @@
+pub mod mock;
+
 use std::sync::Arc;
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::time::Duration;
@@ -17,8 +19,8 @@
 use fedimint_lightning::{
     CreateInvoiceRequest, CreateInvoiceResponse, GetBalancesResponse, GetLnOnchainAddressResponse,
     GetNodeInfoResponse, GetRouteHintsResponse, ILnRpcClient, InterceptPaymentRequest,
-    InterceptPaymentResponse, LightningRpcError, ListChannelsResponse, OpenChannelResponse,
-    PayInvoiceResponse, RouteHtlcStream, SendOnchainResponse,
+    InterceptPaymentResponse, LightningRpcError, ListChannelsResponse, OfferPayment,
+    OpenChannelResponse, PayInvoiceResponse, RouteHtlcStream, RouteProbe, SendOnchainResponse,
 };
 use fedimint_ln_common::PrunedInvoice;
 use fedimint_ln_common::contracts::Preimage;
@@ -342,6 +344,29 @@ async fn pay_offer(
         })
     }
 
+    async fn probe_route(&self, invoice: Bolt11Invoice) -> Result<RouteProbe, LightningRpcError> {
+        if *invoice.payment_secret() == PaymentSecret(INVALID_INVOICE_PAYMENT_SECRET) {
+            return Ok(RouteProbe {
+                fee: None,
+                success_likelihood: Some(0),
+            });
+        }
+
+        Ok(RouteProbe {
+            fee: Some(Amount::ZERO),
+            success_likelihood: Some(100),
+        })
+    }
+
+    async fn list_offer_payments(
+        &self,
+        _since_secs: u64,
+    ) -> Result<Vec<OfferPayment>, LightningRpcError> {
+        Err(LightningRpcError::Bolt12Error {
+            failure_reason: "FakeLightningTest does not support Bolt12".to_string(),
+        })
+    }
+
     fn sync_wallet(&self) -> Result<(), LightningRpcError> {
         Ok(())
     }
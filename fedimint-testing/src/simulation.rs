@@ -0,0 +1,473 @@
+//! Seeded simulation of the consensus of a federation
+//!
+//! A [`Simulation`] runs the consensus of all guardians in-process against
+//! in-memory databases. The guardians exchange their p2p messages over a
+//! simulated network, whose latency, drops and partitions are drawn from the
+//! seed of the simulation via [`NetworkFaults`]. If a simulation fails its seed
+//! is logged and the same run can be replayed by setting `FM_SIMULATION_SEED`.
+//!
+//! All guardians and the test share a single thread on a tokio runtime with
+//! paused time, so the tasks interleave in the same order and the simulated
+//! network delays are independent of the speed of the machine. Every random
+//! draw of the broadcast is derived from the seed as well, such that two runs
+//! of the same seed order the same items into the same sessions.
+//!
+//! The timers of aleph bft are not driven by tokio but by the wall clock.
+//! Since the paused network delivers messages instantly compared to the delay
+//! between two rounds of the broadcast, the timers decide when a unit is
+//! created but not which units it builds upon.
+
+use std::collections::BTreeMap;
+use std::env;
+use std::future::Future;
+
+use async_channel::{Receiver, Sender};
+use async_trait::async_trait;
+use fedimint_api_client::api::{DynGlobalApi, IGlobalFederationApi};
+use fedimint_connectors::ConnectorRegistry;
+use fedimint_core::PeerId;
+use fedimint_core::db::mem_impl::MemDatabase;
+use fedimint_core::db::{Database, IDatabaseTransactionOpsCoreTyped};
+use fedimint_core::module::SerdeModuleEncoding;
+use fedimint_core::net::peers::{IP2PConnections, Recipient};
+use fedimint_core::rustls::install_crypto_provider;
+use fedimint_core::session_outcome::SessionOutcome;
+use fedimint_core::task::TaskGroup;
+use fedimint_core::transaction::{Transaction, TransactionSubmissionOutcome};
+use fedimint_logging::LOG_TEST;
+use fedimint_server::config::ServerConfig;
+use fedimint_server::consensus::db::{SignedSessionOutcomeKey, SignedSessionOutcomePrefix};
+use fedimint_server::core::ServerModuleInitRegistry;
+use fedimint_server::net::api::ApiSecrets;
+use fedimint_server::net::p2p::p2p_status_channels;
+use fedimint_server::{ConnectionLimits, consensus};
+use fedimint_server_core::bitcoin_rpc::DynServerBitcoinRpc;
+use fedimint_testing_core::config::local_config_gen_params;
+use futures::future::select_all;
+use futures::{FutureExt, StreamExt};
+use tracing::{error, info};
+
+use crate::envs::FM_SIMULATION_SEED_ENV;
+use crate::faults::{FaultInjectingP2PConnections, NetworkFaults};
+
+/// Builder for a [`Simulation`]
+#[derive(Clone, Debug)]
+pub struct SimulationBuilder {
+    num_peers: u16,
+    seed: u64,
+    server_init: ServerModuleInitRegistry,
+    bitcoin_rpc_connection: DynServerBitcoinRpc,
+}
+
+impl SimulationBuilder {
+    /// The seed is read from `FM_SIMULATION_SEED` or chosen at random
+    pub fn new(
+        server_init: ServerModuleInitRegistry,
+        bitcoin_rpc_connection: DynServerBitcoinRpc,
+    ) -> SimulationBuilder {
+        let seed = match env::var(FM_SIMULATION_SEED_ENV) {
+            Ok(seed) => seed
+                .parse()
+                .expect("FM_SIMULATION_SEED has to be an unsigned integer"),
+            Err(..) => rand::random(),
+        };
+
+        Self {
+            num_peers: 4,
+            seed,
+            server_init,
+            bitcoin_rpc_connection,
+        }
+    }
+
+    pub fn num_peers(mut self, num_peers: u16) -> SimulationBuilder {
+        self.num_peers = num_peers;
+        self
+    }
+
+    pub fn seed(mut self, seed: u64) -> SimulationBuilder {
+        self.seed = seed;
+        self
+    }
+
+    /// Runs `test` against a new simulation on its own runtime and shuts it
+    /// down afterwards
+    ///
+    /// Has to be called outside of a tokio runtime, so simulation tests are
+    /// plain `#[test]` functions.
+    pub fn run<F, Fut, T>(self, test: F) -> T
+    where
+        F: FnOnce(Simulation) -> Fut,
+        Fut: Future<Output = T>,
+    {
+        let _report = ReportSeedOnPanic(self.seed);
+
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .start_paused(true)
+            .build()
+            .expect("Failed to build the simulation runtime");
+
+        runtime.block_on(async move {
+            let simulation = self.build().await;
+            let task_group = simulation.task_group.clone();
+
+            let output = test(simulation).await;
+
+            task_group
+                .shutdown_join_all(None)
+                .await
+                .expect("Simulation shut down cleanly");
+
+            output
+        })
+    }
+
+    /// Runs `test` against a simulation for every seed, or only for the seed
+    /// in `FM_SIMULATION_SEED` to rerun a failure
+    pub fn run_seeds<F, Fut>(self, seeds: impl IntoIterator<Item = u64>, test: F)
+    where
+        F: Fn(Simulation) -> Fut,
+        Fut: Future<Output = ()>,
+    {
+        if env::var(FM_SIMULATION_SEED_ENV).is_ok() {
+            self.run(test);
+            return;
+        }
+
+        for seed in seeds {
+            self.clone().seed(seed).run(&test);
+        }
+    }
+
+    /// Starts the guardians of a simulation on the current runtime, which has
+    /// to be a current thread runtime with paused time for the simulation to be
+    /// deterministic, see [`SimulationBuilder::run`]
+    pub async fn build(self) -> Simulation {
+        install_crypto_provider().await;
+
+        info!(target: LOG_TEST, seed = self.seed, "Starting simulation");
+
+        let peers = (0..self.num_peers).map(PeerId::from).collect::<Vec<_>>();
+
+        // No guardian is running yet, so blocking the only thread of the runtime is
+        // fine
+        let base_port = fedimint_portalloc::port_alloc(self.num_peers * 3)
+            .expect("Failed to allocate a port range");
+
+        let params = local_config_gen_params(&peers, base_port, true, &self.server_init)
+            .expect("Generates local config");
+
+        let configs = ServerConfig::trusted_dealer_gen(
+            &params,
+            &self.server_init,
+            "fedimint-testing-simulation",
+        );
+
+        let connectors = ConnectorRegistry::build_from_testing_env()
+            .expect("Failed to initialize endpoints for testing (env)")
+            .bind()
+            .await
+            .expect("Failed to initialize endpoints for testing");
+
+        let faults = NetworkFaults::with_seed(self.seed);
+        let task_group = TaskGroup::new();
+        let mut network = simulated_network(&peers);
+        let mut dbs = BTreeMap::new();
+
+        for (peer_id, cfg) in configs.clone() {
+            let instances = cfg.consensus.iter_module_instances();
+            let decoders = self.server_init.available_decoders(instances).unwrap();
+            let db = Database::new(MemDatabase::new(), decoders);
+
+            let peer_port = base_port + u16::from(peer_id) * 3;
+            let api_bind = format!("127.0.0.1:{}", peer_port + 1).parse().unwrap();
+            let ui_bind = format!("127.0.0.1:{}", peer_port + 2).parse().unwrap();
+
+            let peer_task_group = task_group.make_subgroup();
+
+            let connections = FaultInjectingP2PConnections::new(
+                network
+                    .remove(&peer_id)
+                    .expect("Peer is part of the network")
+                    .into_dyn(),
+                peer_id,
+                peers
+                    .iter()
+                    .copied()
+                    .filter(|peer| *peer != peer_id)
+                    .collect(),
+                faults.clone(),
+                &peer_task_group,
+            )
+            .into_dyn();
+
+            let (_, p2p_status_receivers) = p2p_status_channels(
+                peers
+                    .iter()
+                    .copied()
+                    .filter(|peer| *peer != peer_id)
+                    .collect(),
+            );
+
+            let guardian_signer = cfg
+                .local_guardian_signer(&self.server_init)
+                .expect("Failed to collect guardian keys");
+
+            let connectors = connectors.clone();
+            let db_consensus = db.clone();
+            let server_init = self.server_init.clone();
+            let bitcoin_rpc_connection = self.bitcoin_rpc_connection.clone();
+            let subgroup = peer_task_group.make_subgroup();
+            let seed = self.seed;
+
+            peer_task_group.spawn_cancellable("fedimintd", async move {
+                Box::pin(consensus::run(
+                    connectors,
+                    connections,
+                    p2p_status_receivers,
+                    api_bind,
+                    None,
+                    vec![],
+                    cfg,
+                    db_consensus,
+                    server_init,
+                    &subgroup,
+                    ApiSecrets::default(),
+                    tempfile::Builder::new().tempdir().unwrap().keep(),
+                    env!("CARGO_PKG_VERSION").to_string(),
+                    bitcoin_rpc_connection,
+                    ui_bind,
+                    Box::new(|_| axum::Router::new()),
+                    1,
+                    ConnectionLimits {
+                        max_connections: 1000,
+                        max_requests_per_connection: 100,
+                    },
+                    guardian_signer,
+                    Some(seed),
+                ))
+                .await
+                .expect("Could not initialise consensus");
+            });
+
+            dbs.insert(peer_id, db);
+        }
+
+        let api = DynGlobalApi::new(
+            connectors,
+            configs[&PeerId::from(0)]
+                .consensus
+                .api_endpoints()
+                .iter()
+                .map(|(peer_id, endpoint)| (*peer_id, endpoint.url.clone()))
+                .collect(),
+            None,
+        )
+        .expect("Failed to create federation api");
+
+        Simulation {
+            seed: self.seed,
+            configs,
+            dbs,
+            api,
+            faults,
+            task_group,
+        }
+    }
+}
+
+/// Logs how to rerun a simulation that panicked
+struct ReportSeedOnPanic(u64);
+
+impl Drop for ReportSeedOnPanic {
+    fn drop(&mut self) {
+        if std::thread::panicking() {
+            error!(
+                target: LOG_TEST,
+                seed = self.0,
+                "Simulation failed, rerun it with {FM_SIMULATION_SEED_ENV}={}",
+                self.0
+            );
+        }
+    }
+}
+
+/// The guardians of a federation running their consensus in a simulation
+pub struct Simulation {
+    seed: u64,
+    configs: BTreeMap<PeerId, ServerConfig>,
+    dbs: BTreeMap<PeerId, Database>,
+    api: DynGlobalApi,
+    faults: NetworkFaults,
+    task_group: TaskGroup,
+}
+
+impl Simulation {
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Faults applied to the simulated network, drawn from the seed
+    pub fn faults(&self) -> &NetworkFaults {
+        &self.faults
+    }
+
+    pub fn peer_ids(&self) -> impl Iterator<Item = PeerId> + '_ {
+        self.configs.keys().copied()
+    }
+
+    pub fn config(&self, peer_id: PeerId) -> &ServerConfig {
+        &self.configs[&peer_id]
+    }
+
+    /// The database of a guardian, e.g. to inspect the state of a module
+    pub fn db(&self, peer_id: PeerId) -> &Database {
+        &self.dbs[&peer_id]
+    }
+
+    /// The api of the federation, the guardians serve it outside of the
+    /// simulated network
+    pub fn api(&self) -> &DynGlobalApi {
+        &self.api
+    }
+
+    /// Submits a transaction to the federation, like a client would
+    pub async fn submit_transaction(
+        &self,
+        transaction: Transaction,
+    ) -> SerdeModuleEncoding<TransactionSubmissionOutcome> {
+        self.api.submit_transaction(transaction).await
+    }
+    /// Waits until every guardian completed at least `session_count` sessions
+    pub async fn await_session_count(&self, session_count: u64) {
+        let Some(session_index) = session_count.checked_sub(1) else {
+            return;
+        };
+
+        for db in self.dbs.values() {
+            db.wait_key_exists(&SignedSessionOutcomeKey(session_index))
+                .await;
+        }
+    }
+
+    /// Returns the outcomes of the sessions completed by every guardian
+    pub async fn all_session_outcomes(&self) -> BTreeMap<PeerId, Vec<SessionOutcome>> {
+        let mut outcomes = BTreeMap::new();
+
+        for peer_id in self.configs.keys() {
+            outcomes.insert(*peer_id, self.session_outcomes(*peer_id).await);
+        }
+
+        outcomes
+    }
+
+    /// Returns the outcomes of the sessions completed by a guardian
+    pub async fn session_outcomes(&self, peer_id: PeerId) -> Vec<SessionOutcome> {
+        self.dbs[&peer_id]
+            .begin_transaction_nc()
+            .await
+            .find_by_prefix(&SignedSessionOutcomePrefix)
+            .await
+            .map(|(_, signed_session_outcome)| signed_session_outcome.session_outcome)
+            .collect()
+            .await
+    }
+
+    /// Asserts that all guardians agree on the outcomes of the sessions they
+    /// completed
+    pub async fn assert_consistent(&self) {
+        let outcomes = self.all_session_outcomes().await;
+
+        let longest = outcomes
+            .values()
+            .max_by_key(|outcomes| outcomes.len())
+            .cloned()
+            .unwrap_or_default();
+
+        for (peer_id, outcomes) in outcomes {
+            assert_eq!(
+                outcomes,
+                longest[..outcomes.len()],
+                "Session outcomes of peer {peer_id} diverged with seed {}",
+                self.seed
+            );
+        }
+    }
+}
+
+/// Connects every pair of guardians with unbounded channels
+fn simulated_network<M>(peers: &[PeerId]) -> BTreeMap<PeerId, SimulatedP2PConnections<M>> {
+    let mut network = peers
+        .iter()
+        .map(|peer| {
+            (
+                *peer,
+                SimulatedP2PConnections {
+                    outgoing: BTreeMap::new(),
+                    incoming: BTreeMap::new(),
+                },
+            )
+        })
+        .collect::<BTreeMap<_, _>>();
+
+    for sender in peers {
+        for receiver in peers.iter().filter(|peer| *peer != sender) {
+            let (tx, rx) = async_channel::unbounded();
+
+            network
+                .get_mut(sender)
+                .expect("Sender is part of the network")
+                .outgoing
+                .insert(*receiver, tx);
+
+            network
+                .get_mut(receiver)
+                .expect("Receiver is part of the network")
+                .incoming
+                .insert(*sender, rx);
+        }
+    }
+
+    network
+}
+
+struct SimulatedP2PConnections<M> {
+    outgoing: BTreeMap<PeerId, Sender<M>>,
+    incoming: BTreeMap<PeerId, Receiver<M>>,
+}
+
+#[async_trait]
+impl<M: Clone + Send + 'static> IP2PConnections<M> for SimulatedP2PConnections<M> {
+    fn send(&self, recipient: Recipient, message: M) {
+        match recipient {
+            Recipient::Everyone => {
+                for sender in self.outgoing.values() {
+                    sender.try_send(message.clone()).ok();
+                }
+            }
+            Recipient::Peer(peer) => {
+                if let Some(sender) = self.outgoing.get(&peer) {
+                    sender.try_send(message).ok();
+                }
+            }
+        }
+    }
+
+    async fn receive(&self) -> Option<(PeerId, M)> {
+        select_all(self.incoming.iter().map(|(&peer, receiver)| {
+            Box::pin(receiver.recv().map(move |m| m.ok().map(|m| (peer, m))))
+        }))
+        .await
+        .0
+    }
+
+    async fn receive_from_peer(&self, peer: PeerId) -> Option<M> {
+        self.incoming
+            .get(&peer)
+            .expect("No connection found for peer")
+            .recv()
+            .await
+            .ok()
+    }
+}
@@ -30,6 +30,16 @@
 
 pub const FM_ENABLE_IROH_ENV: &str = "FM_ENABLE_IROH";
 
+pub const FM_OFFLINE_DKG_DIR_ENV: &str = "FM_OFFLINE_DKG_DIR";
+
+pub const FM_GUARDIAN_SIGNER_SOCKET_ENV: &str = "FM_GUARDIAN_SIGNER_SOCKET";
+
+pub const FM_GUARDIAN_KEYS_FILE_ENV: &str = "FM_GUARDIAN_KEYS_FILE";
+
+pub const FM_GUARDIAN_KEYS_PASSWORD_ENV: &str = "FM_GUARDIAN_KEYS_PASSWORD";
+
+pub const FM_GUARDIAN_KEYS_PASSWORD_FILE_ENV: &str = "FM_GUARDIAN_KEYS_PASSWORD_FILE";
+
 pub const FM_DB_CHECKPOINT_RETENTION_ENV: &str = "FM_DB_CHECKPOINT_RETENTION";
 
 pub const FM_IROH_API_MAX_CONNECTIONS_ENV: &str = "FM_IROH_API_MAX_CONNECTIONS";
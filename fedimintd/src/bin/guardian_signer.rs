@@ -0,0 +1,4 @@
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    fedimintd::guardian_signer::run_guardian_signer(fedimintd::default_modules()).await
+}
@@ -0,0 +1,3 @@
+fn main() -> anyhow::Result<()> {
+    fedimintd::offline_dkg::run_offline_dkg()
+}
@@ -0,0 +1,99 @@
+//! Command line of the separate signer process holding the private keys of a
+//! guardian, whose socket is passed to fedimintd via
+//! `--guardian-signer-socket`
+//!
+//! The password of the keys file is never accepted on the command line, where
+//! it would be visible to other users of the machine, but only via the
+//! environment or a file.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::Context as _;
+use clap::{Parser, Subcommand};
+use fedimint_logging::TracingSetup;
+use fedimint_server::config::io::{export_guardian_keys, read_guardian_keys};
+use fedimint_server::core::ServerModuleInitRegistry;
+use fedimint_server_core::guardian_signer::{
+    IGuardianSigner as _, LocalGuardianSigner, serve_guardian_signer,
+};
+use fedimintd_envs::{
+    FM_DATA_DIR_ENV, FM_GUARDIAN_KEYS_FILE_ENV, FM_GUARDIAN_KEYS_PASSWORD_ENV,
+    FM_GUARDIAN_KEYS_PASSWORD_FILE_ENV, FM_GUARDIAN_SIGNER_SOCKET_ENV,
+};
+
+#[derive(Parser)]
+#[command(version)]
+struct GuardianSignerOpts {
+    #[command(subcommand)]
+    command: GuardianSignerCommand,
+}
+
+#[derive(Subcommand)]
+enum GuardianSignerCommand {
+    /// Moves the guardian keys out of the config of a stopped fedimintd into
+    /// an encrypted keys file
+    ExportKeys {
+        /// The fedimintd data directory containing the config
+        #[arg(long, env = FM_DATA_DIR_ENV)]
+        data_dir: PathBuf,
+        /// Path of the new keys file, which must not exist yet
+        #[arg(long, env = FM_GUARDIAN_KEYS_FILE_ENV)]
+        keys_file: PathBuf,
+        /// File containing the password the keys file is encrypted with,
+        /// which is read from `FM_GUARDIAN_KEYS_PASSWORD` if not set
+        #[arg(long, env = FM_GUARDIAN_KEYS_PASSWORD_FILE_ENV)]
+        password_file: Option<PathBuf>,
+    },
+    /// Serves the keys of the keys file to fedimintd on a Unix socket
+    Serve {
+        #[arg(long, env = FM_GUARDIAN_KEYS_FILE_ENV)]
+        keys_file: PathBuf,
+        #[arg(long, env = FM_GUARDIAN_SIGNER_SOCKET_ENV)]
+        socket: PathBuf,
+        /// File containing the password the keys file is encrypted with,
+        /// which is read from `FM_GUARDIAN_KEYS_PASSWORD` if not set
+        #[arg(long, env = FM_GUARDIAN_KEYS_PASSWORD_FILE_ENV)]
+        password_file: Option<PathBuf>,
+    },
+}
+
+fn read_password(password_file: Option<&Path>) -> anyhow::Result<String> {
+    match password_file {
+        Some(password_file) => Ok(std::fs::read_to_string(password_file)
+            .context("Failed to read the password file")?
+            .trim()
+            .to_owned()),
+        None => std::env::var(FM_GUARDIAN_KEYS_PASSWORD_ENV).with_context(|| {
+            format!("Either {FM_GUARDIAN_KEYS_PASSWORD_ENV} or --password-file must be set")
+        }),
+    }
+}
+
+/// Runs the guardian signer for a federation with the given modules
+pub async fn run_guardian_signer(modules: ServerModuleInitRegistry) -> anyhow::Result<()> {
+    TracingSetup::default().init()?;
+
+    match GuardianSignerOpts::parse().command {
+        GuardianSignerCommand::ExportKeys {
+            data_dir,
+            keys_file,
+            password_file,
+        } => export_guardian_keys(
+            &data_dir,
+            &modules,
+            &keys_file,
+            &read_password(password_file.as_deref())?,
+        ),
+        GuardianSignerCommand::Serve {
+            keys_file,
+            socket,
+            password_file,
+        } => {
+            let password = read_password(password_file.as_deref())?;
+
+            let signer = LocalGuardianSigner::new(read_guardian_keys(&keys_file, &password)?);
+
+            serve_guardian_signer(&socket, signer.into_dyn()).await
+        }
+    }
+}
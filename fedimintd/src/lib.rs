@@ -6,7 +6,9 @@
 #![allow(clippy::return_self_not_must_use)]
 #![allow(clippy::large_futures)]
 
+pub mod guardian_signer;
 mod metrics;
+pub mod offline_dkg;
 
 use std::convert::Infallible;
 use std::env;
@@ -51,7 +53,8 @@
     FM_BITCOIND_URL_ENV, FM_BITCOIND_URL_PASSWORD_FILE_ENV, FM_BITCOIND_USERNAME_ENV,
     FM_DATA_DIR_ENV, FM_DB_CHECKPOINT_RETENTION_ENV, FM_DISABLE_META_MODULE_ENV,
     FM_ENABLE_IROH_ENV, FM_ESPLORA_URL_ENV, FM_FORCE_API_SECRETS_ENV,
-    FM_IROH_API_MAX_CONNECTIONS_ENV, FM_IROH_API_MAX_REQUESTS_PER_CONNECTION_ENV, FM_P2P_URL_ENV,
+    FM_GUARDIAN_SIGNER_SOCKET_ENV, FM_IROH_API_MAX_CONNECTIONS_ENV,
+    FM_IROH_API_MAX_REQUESTS_PER_CONNECTION_ENV, FM_OFFLINE_DKG_DIR_ENV, FM_P2P_URL_ENV,
 };
 use futures::FutureExt as _;
 #[cfg(all(
@@ -173,6 +176,17 @@ struct ServerOpts {
     #[arg(long, env = FM_IROH_RELAY_ENV, requires = "enable_iroh", value_delimiter = ',')]
     iroh_relays: Vec<SafeUrl>,
 
+    /// Run the DKG by exchanging signed message files through this directory
+    /// instead of over p2p, for guardians keeping their keys air-gapped. The
+    /// files are carried with `fedimint-offline-dkg export` and `import`.
+    #[arg(long, env = FM_OFFLINE_DKG_DIR_ENV, requires = "enable_iroh")]
+    offline_dkg_dir: Option<PathBuf>,
+
+    /// Unix socket of a separate signer process holding the private keys of
+    /// this guardian, which then performs all supported signing operations
+    #[arg(long, env = FM_GUARDIAN_SIGNER_SOCKET_ENV)]
+    guardian_signer_socket: Option<PathBuf>,
+
     /// Number of checkpoints from the current session to retain on disk
     #[arg(long, env = FM_DB_CHECKPOINT_RETENTION_ENV, default_value = "1")]
     db_checkpoint_retention: u64,
@@ -354,6 +368,8 @@ pub async fn run(
         network: server_opts.bitcoin_network,
         available_modules: module_init_registry.kinds(),
         default_modules: module_init_registry.default_modules(),
+        offline_dkg_dir: server_opts.offline_dkg_dir.clone(),
+        guardian_signer_socket: server_opts.guardian_signer_socket.clone(),
     };
 
     let db = Database::new(
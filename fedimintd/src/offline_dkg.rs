@@ -0,0 +1,72 @@
+//! Command line to carry the messages of an offline DKG between guardians,
+//! see `--offline-dkg-dir`
+
+use std::io::BufRead as _;
+use std::path::PathBuf;
+
+use anyhow::Context as _;
+use clap::{Parser, Subcommand};
+use fedimint_logging::{LOG_NET_PEER_DKG, TracingSetup};
+use fedimint_server::config::offline::{export_fragments, import_fragments};
+use fedimintd_envs::FM_OFFLINE_DKG_DIR_ENV;
+use tracing::info;
+
+#[derive(Parser)]
+#[command(version)]
+struct OfflineDkgOpts {
+    #[command(subcommand)]
+    command: OfflineDkgCommand,
+}
+
+#[derive(Subcommand)]
+enum OfflineDkgCommand {
+    /// Prints the fountain-coded fragments of a message from the outbox, one
+    /// per line, for example to be displayed as a sequence of QR codes
+    Export {
+        /// The message file in the outbox of the sending guardian
+        file: PathBuf,
+        /// Maximum number of bytes of the message per fragment
+        #[arg(long, default_value = "200")]
+        max_fragment_length: usize,
+        /// Number of fragments to print, defaults to twice the minimum
+        #[arg(long)]
+        fragments: Option<usize>,
+    },
+    /// Reads fragments from stdin, one per line, until the message is complete
+    /// and places it into the inbox of the receiving guardian
+    Import {
+        /// The offline DKG directory of the running fedimintd
+        #[arg(long, env = FM_OFFLINE_DKG_DIR_ENV)]
+        dir: PathBuf,
+    },
+}
+
+/// Runs the command line to export and import offline DKG messages
+pub fn run_offline_dkg() -> anyhow::Result<()> {
+    TracingSetup::default().init()?;
+
+    match OfflineDkgOpts::parse().command {
+        OfflineDkgCommand::Export {
+            file,
+            max_fragment_length,
+            fragments,
+        } => {
+            for fragment in export_fragments(&file, max_fragment_length, fragments)? {
+                println!("{fragment}");
+            }
+        }
+        OfflineDkgCommand::Import { dir } => {
+            let lines = std::io::stdin()
+                .lock()
+                .lines()
+                .collect::<Result<Vec<String>, _>>()
+                .context("Failed to read fragments from stdin")?;
+
+            let message = import_fragments(&dir, lines)?;
+
+            info!(target: LOG_NET_PEER_DKG, file = %message.file_name(), "Imported DKG message");
+        }
+    }
+
+    Ok(())
+}
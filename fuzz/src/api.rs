@@ -0,0 +1,245 @@
+//! The json-rpc api of a guardian to fuzz the endpoint handlers against
+//!
+//! Requests are dispatched through the same [`RpcModule`] a guardian serves
+//! over websockets, such that every case runs the decoding of the params, the
+//! authentication and the handler of the requested endpoint. The costly setup
+//! happens once per fuzzer process, while the database is cleared before every
+//! fuzz case such that cases can not influence each other.
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use fedimint_api_client::api::DynGlobalApi;
+use fedimint_connectors::ConnectorRegistry;
+use fedimint_core::db::mem_impl::MemDatabase;
+use fedimint_core::db::{Database, IDatabaseTransactionOpsCore as _};
+use fedimint_core::epoch::ConsensusItem;
+use fedimint_core::module::registry::ModuleRegistry;
+use fedimint_core::task::TaskGroup;
+use fedimint_core::{NumPeers, PeerId};
+use fedimint_server::config::ServerConfig;
+use fedimint_server::consensus::api::ConsensusApi;
+use fedimint_server::consensus::consensus_rpc_module;
+use fedimint_server::net::api::API_HANDLER_PANICKED;
+use fedimint_server::net::p2p::p2p_status_channels;
+use fedimint_server_core::ServerModuleInitRegistry;
+use fedimint_server_core::bitcoin_rpc::{IServerBitcoinRpc, ServerBitcoinRpcMonitor};
+use fedimint_server_core::guardian_signer::ModuleGuardianSigner;
+use fedimint_server_core::init::DynServerModuleInit;
+use fedimint_testing::btc::mock::FakeBitcoinTest;
+use fedimint_testing_core::config::local_config_gen_params;
+use jsonrpsee::RpcModule;
+use tempfile::TempDir;
+use tokio::runtime::Runtime;
+use tokio::sync::watch;
+
+/// How long to wait for the response to a request, such that long polling
+/// endpoints do not stall the fuzzer until the api times out
+const REQUEST_TIMEOUT: Duration = Duration::from_millis(100);
+
+pub struct ApiHarness {
+    runtime: Runtime,
+    rpc_module: RpcModule<ConsensusApi>,
+    methods: Vec<&'static str>,
+    db: Database,
+    submission_receiver: async_channel::Receiver<ConsensusItem>,
+    _cfg_dir: TempDir,
+    _task_group: TaskGroup,
+}
+
+impl ApiHarness {
+    /// Serves the core endpoints and the endpoints of the given modules as the
+    /// only guardian of a federation generated with the testing password, such
+    /// that the fuzzer can reach the authenticated endpoints as well
+    #[allow(clippy::too_many_lines)]
+    pub fn new(inits: &[DynServerModuleInit]) -> Self {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .expect("Failed to build runtime");
+
+        let task_group = TaskGroup::new();
+
+        // The only guardian of the fuzzed federation
+        let peer = PeerId::from(0);
+
+        let registry = ServerModuleInitRegistry::from(inits.to_vec());
+
+        let params = local_config_gen_params(&[peer], 10000, false, &registry)
+            .expect("Failed to generate config params");
+
+        let cfg = ServerConfig::trusted_dealer_gen(&params, &registry, "fuzz")
+            .remove(&peer)
+            .expect("Missing config of our peer");
+
+        let decoders = registry
+            .available_decoders(cfg.consensus.iter_module_instances())
+            .expect("Failed to collect decoders");
+
+        let db = Database::new(MemDatabase::new(), decoders);
+
+        // Changing the password writes to the config directory
+        let cfg_dir = TempDir::new().expect("Failed to create config directory");
+
+        let (submission_sender, submission_receiver) = async_channel::unbounded();
+        let (shutdown_sender, shutdown_receiver) = watch::channel(None);
+        let (_, ord_latency_receiver) = watch::channel(None);
+        let (_, p2p_status_receivers) = p2p_status_channels(vec![]);
+
+        let guardian_signer = cfg
+            .local_guardian_signer(&registry)
+            .expect("Failed to create guardian signer");
+
+        let api = runtime.block_on(async {
+            let connectors = ConnectorRegistry::build_from_testing_defaults()
+                .bind()
+                .await
+                .expect("Failed to bind connectors");
+
+            // The endpoints never reach out to the federation as we are its
+            // only guardian
+            let global_api = DynGlobalApi::new(
+                connectors,
+                cfg.consensus
+                    .api_endpoints()
+                    .iter()
+                    .map(|(&peer_id, url)| (peer_id, url.url.clone()))
+                    .collect(),
+                None,
+            )
+            .expect("Failed to create api");
+
+            let bitcoin_rpc_connection = ServerBitcoinRpcMonitor::new(
+                FakeBitcoinTest::new().into_dyn(),
+                Duration::from_secs(1),
+                &task_group,
+            );
+
+            let mut modules = BTreeMap::new();
+
+            for (id, module_cfg) in &cfg.consensus.modules {
+                let init = registry
+                    .get(&module_cfg.kind)
+                    .expect("Missing init of configured module");
+
+                let module = init
+                    .init(
+                        NumPeers::from(1),
+                        cfg.get_module_config(*id)
+                            .expect("Failed to get module config"),
+                        db.with_prefix_module_id(*id).0,
+                        &task_group,
+                        peer,
+                        global_api.with_module(*id),
+                        bitcoin_rpc_connection.clone(),
+                        ModuleGuardianSigner::new(*id, guardian_signer.clone()),
+                    )
+                    .await
+                    .expect("Failed to initialize module");
+
+                modules.insert(*id, (module_cfg.kind.clone(), module));
+            }
+
+            ConsensusApi {
+                cfg: cfg.clone(),
+                cfg_dir: cfg_dir.path().to_path_buf(),
+                db: db.clone(),
+                modules: ModuleRegistry::from(modules),
+                client_cfg: cfg
+                    .consensus
+                    .to_client_config(&registry)
+                    .expect("Failed to create client config"),
+                force_api_secret: None,
+                submission_sender,
+                shutdown_receiver,
+                shutdown_sender,
+                ord_latency_receiver,
+                p2p_status_receivers,
+                ci_status_receivers: BTreeMap::from([(peer, watch::channel(None).1)]),
+                bitcoin_rpc_connection,
+                supported_api_versions: ServerConfig::supported_api_versions_summary(
+                    &cfg.consensus.modules,
+                    &registry,
+                ),
+                code_version_str: "fuzz".to_string(),
+                task_group: task_group.clone(),
+                guardian_signer,
+            }
+        });
+
+        let rpc_module = consensus_rpc_module(api);
+
+        let methods = rpc_module.method_names().collect();
+
+        Self {
+            runtime,
+            rpc_module,
+            methods,
+            db,
+            submission_receiver,
+            _cfg_dir: cfg_dir,
+            _task_group: task_group,
+        }
+    }
+
+    /// Interprets the first byte of `data` as the endpoint to call and the
+    /// remaining bytes as the json params of the request, checking that
+    ///
+    /// * the handler of the endpoint does not panic
+    /// * the response is a well-formed json-rpc response
+    pub fn fuzz_request(&self, data: &[u8]) {
+        let Some((method, params)) = data.split_first() else {
+            return;
+        };
+
+        let method = self.methods[usize::from(*method) % self.methods.len()];
+
+        let Ok(params) = serde_json::from_slice::<serde_json::Value>(params) else {
+            return;
+        };
+
+        let request = serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 0,
+            "method": method,
+            "params": [params],
+        })
+        .to_string();
+
+        self.runtime.block_on(async {
+            let mut dbtx = self.db.begin_transaction().await;
+
+            dbtx.raw_remove_by_prefix(&[])
+                .await
+                .expect("Failed to clear database");
+
+            dbtx.commit_tx().await;
+
+            while self.submission_receiver.try_recv().is_ok() {}
+
+            let Ok(response) = tokio::time::timeout(
+                REQUEST_TIMEOUT,
+                self.rpc_module.raw_json_request(&request, 1),
+            )
+            .await
+            else {
+                return;
+            };
+
+            let (response, _) = response.expect("Failed to parse own request");
+
+            let response = serde_json::from_str::<serde_json::Value>(&response)
+                .expect("Failed to parse response");
+
+            assert_ne!(
+                response["error"]["message"], API_HANDLER_PANICKED,
+                "Handler of {method} panicked on {params}"
+            );
+
+            assert!(
+                response.get("result").is_some() != response.get("error").is_some(),
+                "Malformed response of {method}: {response}"
+            );
+        });
+    }
+}
@@ -0,0 +1,17 @@
+use fedimint_fuzz::api::ApiHarness;
+use fedimint_server_core::init::DynServerModuleInit;
+use honggfuzz::fuzz;
+
+fn main() {
+    let harness = ApiHarness::new(&[
+        DynServerModuleInit::from(fedimint_mint_server::MintInit),
+        DynServerModuleInit::from(fedimint_mintv2_server::MintInit),
+        DynServerModuleInit::from(fedimint_walletv2_server::WalletInit),
+        DynServerModuleInit::from(fedimint_lnv2_server::LightningInit),
+        DynServerModuleInit::from(fedimint_meta_server::MetaInit),
+    ]);
+
+    loop {
+        fuzz!(|data| { harness.fuzz_request(data) });
+    }
+}
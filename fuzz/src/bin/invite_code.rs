@@ -0,0 +1,34 @@
+use std::str::FromStr;
+
+use fedimint_core::encoding::Decodable;
+use fedimint_core::invite_code::InviteCode;
+use fedimint_core::module::registry::ModuleDecoderRegistry;
+use honggfuzz::fuzz;
+
+fn main() {
+    loop {
+        fuzz!(|data| {
+            fedimint_fuzz::test_decodable::<InviteCode>(data);
+
+            if let Ok(s) = std::str::from_utf8(data)
+                && let Ok(invite) = InviteCode::from_str(s)
+            {
+                assert_eq!(InviteCode::from_str(&invite.to_string()).ok(), Some(invite));
+            }
+
+            // The accessors rely on the invariants ensured by the decoding
+            if let Ok(invite) = InviteCode::consensus_decode_partial(
+                &mut &data[..],
+                &ModuleDecoderRegistry::default(),
+            ) {
+                let _ = invite.url();
+                let _ = invite.peer();
+                let _ = invite.peers();
+                let _ = invite.federation_id();
+                let _ = invite.api_secret();
+
+                assert_eq!(InviteCode::from_str(&invite.to_string()).ok(), Some(invite));
+            }
+        });
+    }
+}
@@ -0,0 +1,8 @@
+use fedimint_mint_common::MintInput;
+use honggfuzz::fuzz;
+
+fn main() {
+    loop {
+        fuzz!(|data| { fedimint_fuzz::test_decodable::<MintInput>(data) });
+    }
+}
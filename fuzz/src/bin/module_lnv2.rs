@@ -0,0 +1,12 @@
+use fedimint_fuzz::server::ServerHarness;
+use fedimint_lnv2_server::LightningInit;
+use fedimint_server_core::init::DynServerModuleInit;
+use honggfuzz::fuzz;
+
+fn main() {
+    let harness = ServerHarness::new(&[DynServerModuleInit::from(LightningInit)]);
+
+    loop {
+        fuzz!(|data| { harness.fuzz_module_ops(data) });
+    }
+}
@@ -0,0 +1,12 @@
+use fedimint_fuzz::server::ServerHarness;
+use fedimint_meta_server::MetaInit;
+use fedimint_server_core::init::DynServerModuleInit;
+use honggfuzz::fuzz;
+
+fn main() {
+    let harness = ServerHarness::new(&[DynServerModuleInit::from(MetaInit)]);
+
+    loop {
+        fuzz!(|data| { harness.fuzz_module_ops(data) });
+    }
+}
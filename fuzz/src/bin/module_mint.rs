@@ -0,0 +1,12 @@
+use fedimint_fuzz::server::ServerHarness;
+use fedimint_mint_server::MintInit;
+use fedimint_server_core::init::DynServerModuleInit;
+use honggfuzz::fuzz;
+
+fn main() {
+    let harness = ServerHarness::new(&[DynServerModuleInit::from(MintInit)]);
+
+    loop {
+        fuzz!(|data| { harness.fuzz_module_ops(data) });
+    }
+}
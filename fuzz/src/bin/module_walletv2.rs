@@ -0,0 +1,12 @@
+use fedimint_fuzz::server::ServerHarness;
+use fedimint_server_core::init::DynServerModuleInit;
+use fedimint_walletv2_server::WalletInit;
+use honggfuzz::fuzz;
+
+fn main() {
+    let harness = ServerHarness::new(&[DynServerModuleInit::from(WalletInit)]);
+
+    loop {
+        fuzz!(|data| { harness.fuzz_module_ops(data) });
+    }
+}
@@ -0,0 +1,40 @@
+use std::str::FromStr;
+
+use fedimint_core::encoding::Decodable;
+use fedimint_core::module::registry::ModuleDecoderRegistry;
+use fedimint_mint_client::OOBNotes;
+use fedimint_mint_client::conditional::ConditionalNotes;
+use honggfuzz::fuzz;
+
+fn main() {
+    loop {
+        fuzz!(|data| {
+            fedimint_fuzz::test_decodable::<OOBNotes>(data);
+            fedimint_fuzz::test_decodable::<ConditionalNotes>(data);
+
+            if let Ok(s) = std::str::from_utf8(data) {
+                if let Ok(notes) = OOBNotes::from_str(s) {
+                    assert_eq!(OOBNotes::from_str(&notes.to_string()).ok(), Some(notes));
+                }
+
+                if let Ok(notes) = ConditionalNotes::from_str(s) {
+                    assert_eq!(
+                        ConditionalNotes::from_str(&notes.to_string()).ok(),
+                        Some(notes)
+                    );
+                }
+            }
+
+            // Notes decoded from arbitrary bytes have to survive a round trip
+            // through their string representations
+            if let Ok(notes) = OOBNotes::consensus_decode_partial(
+                &mut &data[..],
+                &ModuleDecoderRegistry::default(),
+            ) {
+                let _ = notes.federation_id_prefix();
+
+                assert_eq!(OOBNotes::from_str(&notes.to_string()).ok(), Some(notes));
+            }
+        });
+    }
+}
@@ -0,0 +1,35 @@
+use fedimint_core::encoding::Decodable;
+use fedimint_core::module::registry::ModuleRegistry;
+use fedimint_mint_common::condition::{SpendingCondition, SpendingWitness};
+use honggfuzz::fuzz;
+
+fn main() {
+    loop {
+        fuzz!(|data| {
+            fedimint_fuzz::test_decodable::<SpendingCondition>(data);
+            fedimint_fuzz::test_decodable::<SpendingWitness>(data);
+
+            let Ok((condition, witness, salt, unix_time)) =
+                <(SpendingCondition, SpendingWitness, [u8; 32], u64)>::consensus_decode_partial(
+                    &mut &data[..],
+                    &ModuleRegistry::default(),
+                )
+            else {
+                return;
+            };
+
+            let nonce = condition.nonce(&salt);
+
+            assert_eq!(nonce, condition.nonce(&salt));
+
+            // A witness satisfying the condition at some time has to satisfy
+            // it at any later time with the same key
+            if let Ok(spend_key) = condition.spend_key(&nonce, &witness, unix_time) {
+                assert_eq!(
+                    condition.spend_key(&nonce, &witness, u64::MAX),
+                    Ok(spend_key)
+                );
+            }
+        });
+    }
+}
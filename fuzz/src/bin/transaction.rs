@@ -0,0 +1,22 @@
+use fedimint_core::transaction::Transaction;
+use fedimint_fuzz::server::ServerHarness;
+use fedimint_server_core::init::DynServerModuleInit;
+use honggfuzz::fuzz;
+
+fn main() {
+    let harness = ServerHarness::new(&[
+        DynServerModuleInit::from(fedimint_mint_server::MintInit),
+        DynServerModuleInit::from(fedimint_mintv2_server::MintInit),
+        DynServerModuleInit::from(fedimint_lnv2_server::LightningInit),
+        DynServerModuleInit::from(fedimint_walletv2_server::WalletInit),
+        DynServerModuleInit::from(fedimint_meta_server::MetaInit),
+    ]);
+
+    loop {
+        fuzz!(|data| {
+            fedimint_fuzz::test_decodable_with_decoders::<Transaction>(data, harness.decoders());
+
+            harness.fuzz_transaction_ops(data);
+        });
+    }
+}
@@ -2,6 +2,9 @@
 #![allow(clippy::missing_panics_doc)]
 #![allow(clippy::must_use_candidate)]
 
+pub mod api;
+pub mod server;
+
 use std::fmt;
 
 use fedimint_core::encoding::{self, Decodable, Encodable};
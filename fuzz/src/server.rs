@@ -0,0 +1,421 @@
+//! In-process server modules to fuzz the consensus logic against
+//!
+//! The modules are instantiated from the trusted dealer config of a single
+//! guardian federation. The costly setup happens once per fuzzer process,
+//! while every fuzz case runs against a fresh in-memory database such that
+//! cases can not influence each other.
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use fedimint_api_client::api::{DynGlobalApi, IGlobalFederationApi};
+use fedimint_connectors::ConnectorRegistry;
+use fedimint_core::bitcoin::Network;
+use fedimint_core::bitcoin::hashes::sha256;
+use fedimint_core::core::{DynInput, DynModuleConsensusItem, DynOutput};
+use fedimint_core::db::mem_impl::MemDatabase;
+use fedimint_core::db::{Database, DatabaseTransaction};
+use fedimint_core::encoding::{Decodable, Encodable};
+use fedimint_core::module::CORE_CONSENSUS_VERSION;
+use fedimint_core::module::audit::Audit;
+use fedimint_core::module::registry::{ModuleDecoderRegistry, ModuleRegistry};
+use fedimint_core::task::TaskGroup;
+use fedimint_core::transaction::Transaction;
+use fedimint_core::util::SafeUrl;
+use fedimint_core::{InPoint, NumPeers, OutPoint, PeerId, TransactionId};
+use fedimint_server::consensus::transaction::{TxProcessingMode, process_transaction_with_dbtx};
+use fedimint_server_core::ServerModuleRegistry;
+use fedimint_server_core::bitcoin_rpc::{IServerBitcoinRpc, ServerBitcoinRpcMonitor};
+use fedimint_server_core::guardian_signer::{
+    GuardianKey, IGuardianSigner as _, LocalGuardianSigner, ModuleGuardianSigner,
+};
+use fedimint_server_core::init::{ConfigGenModuleArgs, DynServerModuleInit};
+use fedimint_testing::btc::mock::FakeBitcoinTest;
+use tokio::runtime::Runtime;
+
+/// A step of a fuzz case run against the modules directly
+#[derive(Debug, Encodable, Decodable)]
+pub enum ModuleOp {
+    Input(DynInput),
+    Output(DynOutput),
+    ConsensusItem(DynModuleConsensusItem),
+}
+
+/// A step of a fuzz case run through the transaction processing of the
+/// consensus engine
+#[derive(Debug, Encodable, Decodable)]
+pub enum TransactionOp {
+    Transaction(Transaction),
+    ConsensusItem(DynModuleConsensusItem),
+}
+
+pub struct ServerHarness {
+    runtime: Runtime,
+    modules: ServerModuleRegistry,
+    decoders: ModuleDecoderRegistry,
+    _task_group: TaskGroup,
+}
+
+impl ServerHarness {
+    /// Instantiates the given modules with consecutive module instance ids
+    /// starting at zero
+    pub fn new(inits: &[DynServerModuleInit]) -> Self {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .expect("Failed to build runtime");
+
+        let task_group = TaskGroup::new();
+
+        // The only guardian of the fuzzed federation
+        let peer = PeerId::from(0);
+
+        let decoders = ModuleDecoderRegistry::new(
+            inits
+                .iter()
+                .zip(0..)
+                .map(|(init, id)| (id, init.module_kind(), init.decoder())),
+        );
+
+        let modules = runtime.block_on(async {
+            let connectors = ConnectorRegistry::build_from_testing_defaults()
+                .bind()
+                .await
+                .expect("Failed to bind connectors");
+
+            // The modules never reach out to the federation while processing
+            // inputs, outputs or consensus items
+            let api = DynGlobalApi::new(
+                connectors,
+                [(
+                    peer,
+                    SafeUrl::parse("ws://127.0.0.1:1").expect("Invalid url"),
+                )]
+                .into(),
+                None,
+            )
+            .expect("Failed to create api");
+
+            let monitor = ServerBitcoinRpcMonitor::new(
+                FakeBitcoinTest::new().into_dyn(),
+                Duration::from_secs(1),
+                &task_group,
+            );
+
+            let db = Database::new(MemDatabase::new(), decoders.clone());
+
+            let args = ConfigGenModuleArgs {
+                network: Network::Regtest,
+                disable_base_fees: false,
+            };
+
+            let mut modules = BTreeMap::new();
+
+            for (init, id) in inits.iter().zip(0..) {
+                let cfg = init
+                    .trusted_dealer_gen(&[peer], &args)
+                    .remove(&peer)
+                    .expect("Missing config of our peer");
+
+                let guardian_signer = LocalGuardianSigner::new(
+                    init.guardian_keys(&cfg)
+                        .expect("Failed to collect guardian keys")
+                        .into_iter()
+                        .map(|(name, secret)| {
+                            (
+                                GuardianKey::Module {
+                                    module_instance_id: id,
+                                    name,
+                                },
+                                secret,
+                            )
+                        })
+                        .collect(),
+                )
+                .into_dyn();
+
+                let module = init
+                    .init(
+                        NumPeers::from(1),
+                        cfg,
+                        db.with_prefix_module_id(id).0,
+                        &task_group,
+                        peer,
+                        api.with_module(id),
+                        monitor.clone(),
+                        ModuleGuardianSigner::new(id, guardian_signer),
+                    )
+                    .await
+                    .expect("Failed to initialize module");
+
+                modules.insert(id, (init.module_kind(), module));
+            }
+
+            ModuleRegistry::from(modules)
+        });
+
+        Self {
+            runtime,
+            modules,
+            decoders,
+            _task_group: task_group,
+        }
+    }
+
+    pub fn decoders(&self) -> &ModuleDecoderRegistry {
+        &self.decoders
+    }
+
+    /// Decodes a sequence of [`ModuleOp`]s from `data` and applies them to
+    /// the modules, checking that
+    ///
+    /// * an input never credits more than the federation gains from it
+    /// * an output never debits less than the federation owes for it
+    /// * an input can not be spent twice
+    pub fn fuzz_module_ops(&self, data: &[u8]) {
+        let Ok(ops) = Vec::<ModuleOp>::consensus_decode_partial(&mut &data[..], &self.decoders)
+        else {
+            return;
+        };
+
+        self.runtime.block_on(async {
+            let db = Database::new(MemDatabase::new(), self.decoders.clone());
+
+            for (op, idx) in ops.iter().zip(0u64..) {
+                let txid = TransactionId::from_raw_hash(op.consensus_hash::<sha256::Hash>());
+
+                match op {
+                    ModuleOp::Input(input) => {
+                        self.fuzz_input(&db, input, InPoint { txid, in_idx: idx })
+                            .await;
+                    }
+                    ModuleOp::Output(output) => {
+                        self.fuzz_output(&db, output, OutPoint { txid, out_idx: idx })
+                            .await;
+                    }
+                    ModuleOp::ConsensusItem(item) => {
+                        self.fuzz_consensus_item(&db, item).await;
+                    }
+                }
+            }
+        });
+    }
+
+    /// Decodes a sequence of [`TransactionOp`]s from `data` and processes
+    /// them like the consensus engine, checking that
+    ///
+    /// * an accepted transaction never decreases the net assets
+    /// * a transaction accepted on submission is accepted by consensus
+    /// * a transaction spending inputs can not be accepted twice
+    /// * the balance sheet never becomes negative
+    pub fn fuzz_transaction_ops(&self, data: &[u8]) {
+        let Ok(ops) =
+            Vec::<TransactionOp>::consensus_decode_partial(&mut &data[..], &self.decoders)
+        else {
+            return;
+        };
+
+        self.runtime.block_on(async {
+            let db = Database::new(MemDatabase::new(), self.decoders.clone());
+
+            for op in &ops {
+                match op {
+                    TransactionOp::Transaction(transaction) => {
+                        self.fuzz_transaction(&db, transaction).await;
+                    }
+                    TransactionOp::ConsensusItem(item) => {
+                        self.fuzz_consensus_item(&db, item).await;
+                    }
+                }
+
+                let mut dbtx = db.begin_transaction_nc().await;
+
+                assert!(
+                    self.net_assets(&mut dbtx).await >= 0,
+                    "Balance sheet of the fed has gone negative after {op:?}"
+                );
+            }
+        });
+    }
+
+    async fn fuzz_input(&self, db: &Database, input: &DynInput, in_point: InPoint) {
+        let id = input.module_instance_id();
+        let module = self.modules.get_expect(id);
+
+        if module.verify_input(input).is_err() {
+            return;
+        }
+
+        let mut dbtx = db.begin_transaction().await;
+
+        let before = self.net_assets(&mut dbtx.to_ref_nc()).await;
+
+        let Ok(meta) = module
+            .process_input(
+                &mut dbtx.to_ref_with_prefix_module_id(id).0.into_nc(),
+                input,
+                in_point,
+            )
+            .await
+        else {
+            return;
+        };
+
+        let after = self.net_assets(&mut dbtx.to_ref_nc()).await;
+
+        assert!(
+            i128::from(after) - i128::from(before)
+                >= i128::from(meta.amount.amounts.get_bitcoin().msats),
+            "Input {input:?} credited {meta:?} but the net assets only changed from {before} to {after}"
+        );
+
+        dbtx.commit_tx().await;
+
+        let mut dbtx = db.begin_transaction_nc().await;
+
+        assert!(
+            module
+                .process_input(
+                    &mut dbtx.to_ref_with_prefix_module_id(id).0,
+                    input,
+                    in_point,
+                )
+                .await
+                .is_err(),
+            "Input {input:?} was spent twice"
+        );
+    }
+
+    async fn fuzz_output(&self, db: &Database, output: &DynOutput, out_point: OutPoint) {
+        let id = output.module_instance_id();
+        let module = self.modules.get_expect(id);
+
+        let mut dbtx = db.begin_transaction().await;
+
+        let before = self.net_assets(&mut dbtx.to_ref_nc()).await;
+
+        let Ok(amounts) = module
+            .process_output(
+                &mut dbtx.to_ref_with_prefix_module_id(id).0.into_nc(),
+                output,
+                out_point,
+            )
+            .await
+        else {
+            return;
+        };
+
+        let after = self.net_assets(&mut dbtx.to_ref_nc()).await;
+
+        assert!(
+            i128::from(before) - i128::from(after)
+                <= i128::from(amounts.amounts.get_bitcoin().msats),
+            "Output {output:?} debited {amounts:?} but the net assets changed from {before} to {after}"
+        );
+
+        dbtx.commit_tx().await;
+    }
+
+    async fn fuzz_consensus_item(&self, db: &Database, item: &DynModuleConsensusItem) {
+        let id = item.module_instance_id();
+
+        let mut dbtx = db.begin_transaction().await;
+
+        if self
+            .modules
+            .get_expect(id)
+            .process_consensus_item(
+                &mut dbtx.to_ref_with_prefix_module_id(id).0.into_nc(),
+                item,
+                PeerId::from(0),
+            )
+            .await
+            .is_ok()
+        {
+            dbtx.commit_tx().await;
+        }
+    }
+
+    async fn fuzz_transaction(&self, db: &Database, transaction: &Transaction) {
+        let submission = {
+            let mut dbtx = db.begin_transaction_nc().await;
+
+            process_transaction_with_dbtx(
+                self.modules.clone(),
+                &mut dbtx,
+                transaction,
+                CORE_CONSENSUS_VERSION,
+                TxProcessingMode::Submission,
+            )
+            .await
+        };
+
+        let mut dbtx = db.begin_transaction().await;
+
+        let before = self.net_assets(&mut dbtx.to_ref_nc()).await;
+
+        let consensus = process_transaction_with_dbtx(
+            self.modules.clone(),
+            &mut dbtx.to_ref_nc(),
+            transaction,
+            CORE_CONSENSUS_VERSION,
+            TxProcessingMode::Consensus,
+        )
+        .await;
+
+        assert!(
+            submission.is_err() || consensus.is_ok(),
+            "Transaction {} was accepted on submission but rejected by consensus: {consensus:?}",
+            transaction.tx_hash()
+        );
+
+        if consensus.is_err() {
+            return;
+        }
+
+        let after = self.net_assets(&mut dbtx.to_ref_nc()).await;
+
+        assert!(
+            before <= after,
+            "Transaction {} decreased the net assets from {before} to {after}",
+            transaction.tx_hash()
+        );
+
+        dbtx.commit_tx().await;
+
+        if transaction.inputs.is_empty() {
+            return;
+        }
+
+        let mut dbtx = db.begin_transaction_nc().await;
+
+        assert!(
+            process_transaction_with_dbtx(
+                self.modules.clone(),
+                &mut dbtx,
+                transaction,
+                CORE_CONSENSUS_VERSION,
+                TxProcessingMode::Consensus,
+            )
+            .await
+            .is_err(),
+            "Transaction {} was accepted twice",
+            transaction.tx_hash()
+        );
+    }
+
+    async fn net_assets(&self, dbtx: &mut DatabaseTransaction<'_>) -> i64 {
+        let mut audit = Audit::default();
+
+        for (id, _, module) in self.modules.iter_modules() {
+            module
+                .audit(&mut dbtx.to_ref_with_prefix_module_id(id).0, &mut audit, id)
+                .await;
+        }
+
+        audit
+            .net_assets()
+            .expect("Overflow while checking balance sheet")
+            .milli_sat
+    }
+}
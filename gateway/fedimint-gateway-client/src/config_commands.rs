@@ -2,8 +2,12 @@
 use fedimint_core::Amount;
 use fedimint_core::config::FederationId;
 use fedimint_core::util::SafeUrl;
-use fedimint_gateway_client::{get_config, get_info, set_fees, set_mnemonic};
-use fedimint_gateway_common::{ConfigPayload, SetFeesPayload, SetMnemonicPayload};
+use fedimint_gateway_client::{
+    get_config, get_info, set_federation_limits, set_fees, set_mnemonic,
+};
+use fedimint_gateway_common::{
+    ConfigPayload, FederationLimits, SetFederationLimitsPayload, SetFeesPayload, SetMnemonicPayload,
+};
 use fedimint_ln_common::client::GatewayApi;
 
 use crate::{CliOutput, CliOutputResult};
@@ -39,6 +43,24 @@ pub enum ConfigCommands {
         #[clap(long)]
         tx_ppm: Option<u64>,
     },
+    /// Set the gateway's spending limits for a federation. Omitted limits are
+    /// not enforced.
+    SetLimits {
+        #[clap(long)]
+        federation_id: FederationId,
+
+        /// Maximum amount of a single incoming or outgoing payment
+        #[clap(long)]
+        max_payment: Option<Amount>,
+
+        /// Maximum total amount of outgoing payments over the last 24 hours
+        #[clap(long)]
+        max_daily_outgoing: Option<Amount>,
+
+        /// Maximum ecash balance held in the federation
+        #[clap(long)]
+        max_balance: Option<Amount>,
+    },
     /// Instructs the gateway to create a new mnemonic or set it to the provided
     /// mnemonic
     SetMnemonic {
@@ -90,6 +112,27 @@ pub async fn handle(self, client: &GatewayApi, base_url: &SafeUrl) -> CliOutputR
                 .await?;
                 Ok(CliOutput::Empty)
             }
+            Self::SetLimits {
+                federation_id,
+                max_payment,
+                max_daily_outgoing,
+                max_balance,
+            } => {
+                set_federation_limits(
+                    client,
+                    base_url,
+                    SetFederationLimitsPayload {
+                        federation_id,
+                        limits: FederationLimits {
+                            max_payment,
+                            max_daily_outgoing,
+                            max_balance,
+                        },
+                    },
+                )
+                .await?;
+                Ok(CliOutput::Empty)
+            }
             Self::SetMnemonic { words } => {
                 set_mnemonic(client, base_url, SetMnemonicPayload { words }).await?;
                 Ok(CliOutput::Empty)
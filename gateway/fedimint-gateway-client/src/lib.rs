@@ -13,18 +13,20 @@
     CREATE_BOLT11_INVOICE_FOR_OPERATOR_ENDPOINT, CREATE_BOLT12_OFFER_FOR_OPERATOR_ENDPOINT,
     ChannelInfo, CloseChannelsWithPeerRequest, CloseChannelsWithPeerResponse, ConfigPayload,
     ConnectFedPayload, CreateInvoiceForOperatorPayload, CreateOfferPayload, CreateOfferResponse,
-    DepositAddressPayload, DepositAddressRecheckPayload, FederationInfo, GATEWAY_INFO_ENDPOINT,
-    GET_BALANCES_ENDPOINT, GET_INVOICE_ENDPOINT, GET_LN_ONCHAIN_ADDRESS_ENDPOINT, GatewayBalances,
-    GatewayFedConfig, GatewayInfo, GetInvoiceRequest, GetInvoiceResponse, INVITE_CODES_ENDPOINT,
-    LEAVE_FED_ENDPOINT, LIST_CHANNELS_ENDPOINT, LIST_TRANSACTIONS_ENDPOINT, LeaveFedPayload,
-    ListTransactionsPayload, ListTransactionsResponse, MNEMONIC_ENDPOINT, MnemonicResponse,
-    OPEN_CHANNEL_ENDPOINT, OPEN_CHANNEL_WITH_PUSH_ENDPOINT, OpenChannelRequest,
+    DepositAddressPayload, DepositAddressRecheckPayload, FailedBolt12OfferPayment, FederationInfo,
+    GATEWAY_INFO_ENDPOINT, GET_BALANCES_ENDPOINT, GET_INVOICE_ENDPOINT,
+    GET_LN_ONCHAIN_ADDRESS_ENDPOINT, GatewayBalances, GatewayFedConfig, GatewayInfo,
+    GetInvoiceRequest, GetInvoiceResponse, INVITE_CODES_ENDPOINT, LEAVE_FED_ENDPOINT,
+    LIST_CHANNELS_ENDPOINT, LIST_FAILED_BOLT12_OFFER_PAYMENTS_ENDPOINT, LIST_TRANSACTIONS_ENDPOINT,
+    LeaveFedPayload, ListTransactionsPayload, ListTransactionsResponse, MNEMONIC_ENDPOINT,
+    MnemonicResponse, OPEN_CHANNEL_ENDPOINT, OPEN_CHANNEL_WITH_PUSH_ENDPOINT, OpenChannelRequest,
     PAY_INVOICE_FOR_OPERATOR_ENDPOINT, PAY_OFFER_FOR_OPERATOR_ENDPOINT, PAYMENT_LOG_ENDPOINT,
     PAYMENT_SUMMARY_ENDPOINT, PEGIN_FROM_ONCHAIN_ENDPOINT, PayInvoiceForOperatorPayload,
     PayOfferPayload, PayOfferResponse, PaymentLogPayload, PaymentLogResponse,
     PaymentSummaryPayload, PaymentSummaryResponse, PeginFromOnchainPayload, RECEIVE_ECASH_ENDPOINT,
-    ReceiveEcashPayload, ReceiveEcashResponse, SEND_ONCHAIN_ENDPOINT, SET_FEES_ENDPOINT,
-    SPEND_ECASH_ENDPOINT, STOP_ENDPOINT, SendOnchainRequest, SetFeesPayload, SetMnemonicPayload,
+    ReceiveEcashPayload, ReceiveEcashResponse, SEND_ONCHAIN_ENDPOINT,
+    SET_FEDERATION_LIMITS_ENDPOINT, SET_FEES_ENDPOINT, SPEND_ECASH_ENDPOINT, STOP_ENDPOINT,
+    SendOnchainRequest, SetFederationLimitsPayload, SetFeesPayload, SetMnemonicPayload,
     SpendEcashPayload, SpendEcashResponse, WITHDRAW_ENDPOINT, WITHDRAW_TO_ONCHAIN_ENDPOINT,
     WithdrawPayload, WithdrawResponse, WithdrawToOnchainPayload,
 };
@@ -143,6 +145,21 @@ pub async fn set_fees(
         .await
 }
 
+pub async fn set_federation_limits(
+    client: &GatewayApi,
+    base_url: &SafeUrl,
+    payload: SetFederationLimitsPayload,
+) -> ServerResult<()> {
+    client
+        .request(
+            base_url,
+            Method::POST,
+            SET_FEDERATION_LIMITS_ENDPOINT,
+            Some(payload),
+        )
+        .await
+}
+
 pub async fn create_invoice_for_self(
     client: &GatewayApi,
     base_url: &SafeUrl,
@@ -360,6 +377,20 @@ pub async fn list_transactions(
         .await
 }
 
+pub async fn list_failed_bolt12_offer_payments(
+    client: &GatewayApi,
+    base_url: &SafeUrl,
+) -> ServerResult<Vec<FailedBolt12OfferPayment>> {
+    client
+        .request::<(), Vec<FailedBolt12OfferPayment>>(
+            base_url,
+            Method::GET,
+            LIST_FAILED_BOLT12_OFFER_PAYMENTS_ENDPOINT,
+            None,
+        )
+        .await
+}
+
 pub async fn create_offer(
     client: &GatewayApi,
     base_url: &SafeUrl,
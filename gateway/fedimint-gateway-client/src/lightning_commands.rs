@@ -5,7 +5,8 @@
 use fedimint_core::Amount;
 use fedimint_gateway_client::{
     close_channels_with_peer, create_invoice_for_self, create_offer, get_invoice, list_channels,
-    list_transactions, open_channel, open_channel_with_push, pay_invoice, pay_offer,
+    list_failed_bolt12_offer_payments, list_transactions, open_channel, open_channel_with_push,
+    pay_invoice, pay_offer,
 };
 use fedimint_gateway_common::{
     CloseChannelsWithPeerRequest, CreateInvoiceForOperatorPayload, CreateOfferPayload,
@@ -113,6 +114,9 @@ pub enum LightningCommands {
         #[clap(long)]
         payer_note: Option<String>,
     },
+    /// List the payments to BOLT12 offers of LNv2 clients that the gateway was
+    /// unable to relay to the client.
+    ListFailedOfferPayments,
 }
 
 fn parse_datetime(s: &str) -> Result<DateTime<Utc>, chrono::ParseError> {
@@ -253,6 +257,10 @@ pub async fn handle(self, client: &GatewayApi, base_url: &SafeUrl) -> CliOutputR
                 .await?;
                 Ok(CliOutput::OfferPayment(response))
             }
+            Self::ListFailedOfferPayments => {
+                let response = list_failed_bolt12_offer_payments(client, base_url).await?;
+                Ok(CliOutput::FailedOfferPayments(response))
+            }
         }
     }
 }
@@ -20,10 +20,11 @@
 use fedimint_core::invite_code::InviteCode;
 use fedimint_core::util::SafeUrl;
 use fedimint_gateway_common::{
-    ChannelInfo, CloseChannelsWithPeerResponse, CreateOfferResponse, FederationConfig,
-    FederationInfo, GatewayBalances, GatewayFedConfig, GatewayInfo, GetInvoiceResponse,
-    ListTransactionsResponse, MnemonicResponse, PayOfferResponse, PaymentLogResponse,
-    PaymentSummaryResponse, ReceiveEcashResponse, SpendEcashResponse, WithdrawResponse,
+    ChannelInfo, CloseChannelsWithPeerResponse, CreateOfferResponse, FailedBolt12OfferPayment,
+    FederationConfig, FederationInfo, GatewayBalances, GatewayFedConfig, GatewayInfo,
+    GetInvoiceResponse, ListTransactionsResponse, MnemonicResponse, PayOfferResponse,
+    PaymentLogResponse, PaymentSummaryResponse, ReceiveEcashResponse, SpendEcashResponse,
+    WithdrawResponse,
 };
 use fedimint_ln_common::client::GatewayApi;
 use fedimint_logging::TracingSetup;
@@ -66,6 +67,7 @@ pub enum CliOutput {
     Transactions(ListTransactionsResponse),
     Offer(CreateOfferResponse),
     OfferPayment(PayOfferResponse),
+    FailedOfferPayments(Vec<FailedBolt12OfferPayment>),
 
     // Ecash commands
     DepositAddress {
@@ -37,8 +37,11 @@
 pub const GET_BALANCES_ENDPOINT: &str = "/balances";
 pub const GET_INVOICE_ENDPOINT: &str = "/get_invoice";
 pub const GET_LN_ONCHAIN_ADDRESS_ENDPOINT: &str = "/get_ln_onchain_address";
+pub const HA_REPLICATE_ENDPOINT: &str = "/ha/replicate";
+pub const HA_STATUS_ENDPOINT: &str = "/ha/status";
 pub const LEAVE_FED_ENDPOINT: &str = "/leave_fed";
 pub const LIST_CHANNELS_ENDPOINT: &str = "/list_channels";
+pub const LIST_FAILED_BOLT12_OFFER_PAYMENTS_ENDPOINT: &str = "/list_failed_bolt12_offer_payments";
 pub const LIST_TRANSACTIONS_ENDPOINT: &str = "/list_transactions";
 pub const MNEMONIC_ENDPOINT: &str = "/mnemonic";
 pub const OPEN_CHANNEL_ENDPOINT: &str = "/open_channel";
@@ -51,6 +54,7 @@
 pub const PEGIN_FROM_ONCHAIN_ENDPOINT: &str = "/pegin_from_onchain";
 pub const RECEIVE_ECASH_ENDPOINT: &str = "/receive_ecash";
 pub const SET_FEES_ENDPOINT: &str = "/set_fees";
+pub const SET_FEDERATION_LIMITS_ENDPOINT: &str = "/set_federation_limits";
 pub const STOP_ENDPOINT: &str = "/stop";
 pub const SEND_ONCHAIN_ENDPOINT: &str = "/send_onchain";
 pub const SPEND_ECASH_ENDPOINT: &str = "/spend_ecash";
@@ -172,6 +176,73 @@ pub struct FederationInfo {
     pub balance_msat: Amount,
     pub config: FederationConfig,
     pub last_backup_time: Option<SystemTime>,
+    #[serde(default)]
+    pub limits: FederationLimits,
+    /// Set if routing for this federation is currently paused by the circuit
+    /// breaker
+    #[serde(default)]
+    pub circuit_breaker: Option<CircuitBreakerReason>,
+}
+
+/// Operator-defined bounds on the risk the gateway takes on in a single
+/// federation. A limit that is `None` is not enforced.
+#[derive(Debug, Clone, Default, Eq, PartialEq, Encodable, Decodable, Serialize, Deserialize)]
+pub struct FederationLimits {
+    /// Maximum amount of a single incoming or outgoing payment
+    pub max_payment: Option<Amount>,
+    /// Maximum total amount of settled outgoing payments over the last 24 hours
+    pub max_daily_outgoing: Option<Amount>,
+    /// Maximum ecash balance the gateway is willing to hold in the federation
+    pub max_balance: Option<Amount>,
+}
+
+/// Reason why the circuit breaker paused routing for a federation
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CircuitBreakerReason {
+    /// Fewer guardians than the consensus threshold answered our health check
+    GuardiansUnreachable { reachable: usize, threshold: usize },
+    /// The federation has not completed a consensus session in a long time
+    ConsensusStalled {
+        session_count: u64,
+        stalled_for: Duration,
+    },
+    /// The liabilities the mint committed to are not consistent
+    MalformedLiabilities,
+    /// The outstanding ecash exceeds the bitcoin held by the federation
+    UnbackedLiabilities { liabilities: Amount, assets: Amount },
+}
+
+impl fmt::Display for CircuitBreakerReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CircuitBreakerReason::GuardiansUnreachable {
+                reachable,
+                threshold,
+            } => write!(
+                f,
+                "Only {reachable} guardians are reachable, {threshold} are required"
+            ),
+            CircuitBreakerReason::ConsensusStalled {
+                session_count,
+                stalled_for,
+            } => write!(
+                f,
+                "Consensus is stuck at session {session_count} for {}s",
+                stalled_for.as_secs()
+            ),
+            CircuitBreakerReason::MalformedLiabilities => {
+                write!(f, "The mint committed to malformed liabilities")
+            }
+            CircuitBreakerReason::UnbackedLiabilities {
+                liabilities,
+                assets,
+            } => write!(
+                f,
+                "Outstanding ecash of {liabilities} exceeds the {assets} held on-chain"
+            ),
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, PartialEq)]
@@ -202,6 +273,12 @@ pub struct SetFeesPayload {
     pub transaction_parts_per_million: Option<u64>,
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SetFederationLimitsPayload {
+    pub federation_id: FederationId,
+    pub limits: FederationLimits,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct CreateInvoiceForOperatorPayload {
     pub amount_msats: u64,
@@ -261,7 +338,8 @@ pub struct MnemonicResponse {
 
     // Legacy federations are federations that the gateway joined prior to v0.5.0
     // and do not derive their secrets from the gateway's mnemonic. They also use
-    // a separate database from the gateway's db.
+    // a separate database from the gateway's db, unless it was migrated into the
+    // gateway's db for high-availability mode.
     pub legacy_federations: Vec<FederationId>,
 }
 
@@ -459,6 +537,16 @@ pub struct PayOfferResponse {
     pub preimage: String,
 }
 
+/// A payment to a BOLT12 offer created on behalf of an LNv2 client that the
+/// lightning node has settled, but the gateway was unable to relay to the
+/// client.
+#[derive(Debug, Serialize, Deserialize, Clone, Eq, PartialEq)]
+pub struct FailedBolt12OfferPayment {
+    pub payment_hash: sha256::Hash,
+    pub federation_id: FederationId,
+    pub amount: Amount,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone, Eq, PartialEq)]
 pub enum PaymentStatus {
     Pending,
@@ -549,3 +637,103 @@ pub enum RegisteredProtocol {
 pub struct SetMnemonicPayload {
     pub words: Option<String>,
 }
+
+/// The role a gateway instance currently plays in an active/standby
+/// high-availability pair.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HaRole {
+    /// The instance holds the leader lease, intercepts HTLCs and signs.
+    Active,
+    /// The instance replicates the active's database and waits to take over.
+    Standby,
+}
+
+/// Time-bounded claim of a gateway instance to be the active member of a
+/// high-availability pair. The lease is stored in a file shared by both
+/// instances, its epoch fences off an instance that lost leadership.
+#[derive(Debug, Clone, Eq, PartialEq, Encodable, Decodable, Serialize, Deserialize)]
+pub struct LeaderLease {
+    /// Operator-assigned identifier of the instance holding the lease
+    pub instance_id: String,
+    /// Monotonically increasing counter, bumped every time leadership changes
+    pub epoch: u64,
+    /// The lease is considered abandoned after this time unless renewed
+    pub expires_at: SystemTime,
+}
+
+impl LeaderLease {
+    pub fn is_valid(&self, now: SystemTime) -> bool {
+        now < self.expires_at
+    }
+
+    /// Returns true if `self` takes precedence over `other`. Higher epochs
+    /// win, ties are broken by the lexicographically smaller instance id.
+    pub fn supersedes(&self, other: &LeaderLease) -> bool {
+        (self.epoch, std::cmp::Reverse(&self.instance_id))
+            > (other.epoch, std::cmp::Reverse(&other.instance_id))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HaStatus {
+    pub instance_id: String,
+    pub role: HaRole,
+    pub lease: Option<LeaderLease>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplicatePayload {
+    /// Identifier of the replication log the standby has been following, if
+    /// any. A mismatch forces a full snapshot.
+    pub log_id: Option<u64>,
+    /// Sequence number of the last commit the standby has applied
+    pub seq: u64,
+}
+
+/// A single raw database write, as observed when a transaction committed on
+/// the active gateway.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReplicatedOp {
+    Insert {
+        #[serde(with = "fedimint_core::hex::serde")]
+        key: Vec<u8>,
+        #[serde(with = "fedimint_core::hex::serde")]
+        value: Vec<u8>,
+    },
+    Remove {
+        #[serde(with = "fedimint_core::hex::serde")]
+        key: Vec<u8>,
+    },
+    RemoveByPrefix {
+        #[serde(with = "fedimint_core::hex::serde")]
+        prefix: Vec<u8>,
+    },
+}
+
+/// All writes of one committed database transaction
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct ReplicatedCommit {
+    pub seq: u64,
+    pub ops: Vec<ReplicatedOp>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReplicateResponse {
+    /// Commits that happened after the sequence number requested by the
+    /// standby
+    Commits {
+        log_id: u64,
+        commits: Vec<ReplicatedCommit>,
+    },
+    /// Full copy of the database, sent when the standby is following a
+    /// different log or has fallen too far behind. Every entry is an
+    /// [`ReplicatedOp::Insert`].
+    Snapshot {
+        log_id: u64,
+        seq: u64,
+        entries: Vec<ReplicatedOp>,
+    },
+}
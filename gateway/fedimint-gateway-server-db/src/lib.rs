@@ -1,3 +1,5 @@
+mod replication;
+
 use std::collections::BTreeMap;
 use std::str::FromStr;
 use std::time::SystemTime;
@@ -14,7 +16,9 @@
 use fedimint_core::module::registry::ModuleDecoderRegistry;
 use fedimint_core::{Amount, impl_db_lookup, impl_db_record, push_db_pair_items, secp256k1};
 use fedimint_gateway_common::envs::FM_GATEWAY_IROH_SECRET_KEY_OVERRIDE_ENV;
-use fedimint_gateway_common::{ConnectorType, FederationConfig, RegisteredProtocol};
+use fedimint_gateway_common::{
+    ConnectorType, FederationConfig, FederationLimits, RegisteredProtocol,
+};
 use fedimint_ln_common::serde_routing_fees;
 use fedimint_lnv2_common::contracts::{IncomingContract, PaymentImage};
 use fedimint_lnv2_common::gateway_api::PaymentFee;
@@ -27,6 +31,8 @@
 use strum::IntoEnumIterator;
 use strum_macros::EnumIter;
 
+pub use crate::replication::{ReplicatedDatabase, ReplicationLog, apply_replicate_response};
+
 pub trait GatewayDbExt {
     fn get_client_database(&self, federation_id: &FederationId) -> Database;
 }
@@ -50,6 +56,16 @@ async fn load_federation_config(
     ) -> Option<FederationConfig>;
     async fn remove_federation_config(&mut self, federation_id: FederationId);
 
+    /// Returns the spending limits configured for a federation, which are
+    /// unrestricted by default
+    async fn load_federation_limits(&mut self, federation_id: FederationId) -> FederationLimits;
+
+    async fn save_federation_limits(
+        &mut self,
+        federation_id: FederationId,
+        limits: &FederationLimits,
+    );
+
     /// Returns the keypair that uniquely identifies the gateway, creating it if
     /// it does not exist. Remember to commit the transaction after calling this
     /// method.
@@ -80,6 +96,48 @@ async fn load_registered_incoming_contract(
         payment_image: PaymentImage,
     ) -> Option<RegisteredIncomingContract>;
 
+    /// Saves a BOLT12 offer the gateway created on behalf of an LNv2 client
+    async fn save_bolt12_offer(&mut self, offer_id: String, offer: &Bolt12Offer);
+
+    async fn load_bolt12_offer(&mut self, offer_id: String) -> Option<Bolt12Offer>;
+
+    async fn save_bolt12_offer_payment(
+        &mut self,
+        payment_hash: sha256::Hash,
+        payment: &Bolt12OfferPayment,
+    );
+
+    async fn load_bolt12_offer_payment(
+        &mut self,
+        payment_hash: sha256::Hash,
+    ) -> Option<Bolt12OfferPayment>;
+
+    /// Returns all payments to BOLT12 offers for which the incoming contract
+    /// has not been funded yet
+    async fn load_pending_bolt12_offer_payments(
+        &mut self,
+    ) -> BTreeMap<sha256::Hash, Bolt12OfferPayment>;
+
+    /// Returns all payments to BOLT12 offers that could not be relayed to the
+    /// recipient and require the attention of the operator
+    async fn load_failed_bolt12_offer_payments(
+        &mut self,
+    ) -> BTreeMap<sha256::Hash, Bolt12OfferPayment>;
+
+    /// Returns the timestamp from which on the lightning node's BOLT12 offer
+    /// payments have not been relayed yet
+    async fn load_bolt12_offer_payment_cursor(&mut self) -> u64;
+
+    async fn save_bolt12_offer_payment_cursor(&mut self, timestamp_secs: u64);
+
+    /// Saves a hold invoice the gateway created on behalf of an LNv2 client
+    async fn save_hold_invoice(&mut self, payment_hash: sha256::Hash, invoice: &HoldInvoice);
+
+    async fn load_hold_invoice(&mut self, payment_hash: sha256::Hash) -> Option<HoldInvoice>;
+
+    /// Returns all hold invoices for which the gateway currently holds an HTLC
+    async fn load_held_hold_invoices(&mut self) -> BTreeMap<sha256::Hash, HoldInvoice>;
+
     /// Reads and serializes structures from the gateway's database for the
     /// purpose for serializing to JSON for inspection.
     async fn dump_database(
@@ -141,6 +199,23 @@ async fn load_federation_config(
     async fn remove_federation_config(&mut self, federation_id: FederationId) {
         self.remove_entry(&FederationConfigKey { id: federation_id })
             .await;
+        self.remove_entry(&FederationLimitsKey { federation_id })
+            .await;
+    }
+
+    async fn load_federation_limits(&mut self, federation_id: FederationId) -> FederationLimits {
+        self.get_value(&FederationLimitsKey { federation_id })
+            .await
+            .unwrap_or_default()
+    }
+
+    async fn save_federation_limits(
+        &mut self,
+        federation_id: FederationId,
+        limits: &FederationLimits,
+    ) {
+        self.insert_entry(&FederationLimitsKey { federation_id }, limits)
+            .await;
     }
 
     async fn load_or_create_gateway_keypair(&mut self, protocol: RegisteredProtocol) -> Keypair {
@@ -204,6 +279,91 @@ async fn load_registered_incoming_contract(
             .await
     }
 
+    async fn save_bolt12_offer(&mut self, offer_id: String, offer: &Bolt12Offer) {
+        self.insert_entry(&Bolt12OfferKey { offer_id }, offer).await;
+    }
+
+    async fn load_bolt12_offer(&mut self, offer_id: String) -> Option<Bolt12Offer> {
+        self.get_value(&Bolt12OfferKey { offer_id }).await
+    }
+
+    async fn save_bolt12_offer_payment(
+        &mut self,
+        payment_hash: sha256::Hash,
+        payment: &Bolt12OfferPayment,
+    ) {
+        self.insert_entry(&Bolt12OfferPaymentKey { payment_hash }, payment)
+            .await;
+    }
+
+    async fn load_bolt12_offer_payment(
+        &mut self,
+        payment_hash: sha256::Hash,
+    ) -> Option<Bolt12OfferPayment> {
+        self.get_value(&Bolt12OfferPaymentKey { payment_hash })
+            .await
+    }
+
+    async fn load_pending_bolt12_offer_payments(
+        &mut self,
+    ) -> BTreeMap<sha256::Hash, Bolt12OfferPayment> {
+        self.find_by_prefix(&Bolt12OfferPaymentPrefix)
+            .await
+            .filter(|(_, payment)| {
+                std::future::ready(matches!(
+                    payment.state,
+                    Bolt12OfferPaymentState::Pending { .. }
+                ))
+            })
+            .map(|(key, payment)| (key.payment_hash, payment))
+            .collect::<BTreeMap<sha256::Hash, Bolt12OfferPayment>>()
+            .await
+    }
+
+    async fn load_failed_bolt12_offer_payments(
+        &mut self,
+    ) -> BTreeMap<sha256::Hash, Bolt12OfferPayment> {
+        self.find_by_prefix(&Bolt12OfferPaymentPrefix)
+            .await
+            .filter(|(_, payment)| {
+                std::future::ready(payment.state == Bolt12OfferPaymentState::Failed)
+            })
+            .map(|(key, payment)| (key.payment_hash, payment))
+            .collect::<BTreeMap<sha256::Hash, Bolt12OfferPayment>>()
+            .await
+    }
+
+    async fn load_bolt12_offer_payment_cursor(&mut self) -> u64 {
+        self.get_value(&Bolt12OfferPaymentCursorKey)
+            .await
+            .unwrap_or(0)
+    }
+
+    async fn save_bolt12_offer_payment_cursor(&mut self, timestamp_secs: u64) {
+        self.insert_entry(&Bolt12OfferPaymentCursorKey, &timestamp_secs)
+            .await;
+    }
+
+    async fn save_hold_invoice(&mut self, payment_hash: sha256::Hash, invoice: &HoldInvoice) {
+        self.insert_entry(&HoldInvoiceKey { payment_hash }, invoice)
+            .await;
+    }
+
+    async fn load_hold_invoice(&mut self, payment_hash: sha256::Hash) -> Option<HoldInvoice> {
+        self.get_value(&HoldInvoiceKey { payment_hash }).await
+    }
+
+    async fn load_held_hold_invoices(&mut self) -> BTreeMap<sha256::Hash, HoldInvoice> {
+        self.find_by_prefix(&HoldInvoicePrefix)
+            .await
+            .filter(|(_, invoice)| {
+                std::future::ready(matches!(invoice.state, HoldInvoiceState::Held { .. }))
+            })
+            .map(|(key, invoice)| (key.payment_hash, invoice))
+            .collect::<BTreeMap<sha256::Hash, HoldInvoice>>()
+            .await
+    }
+
     async fn dump_database(
         &mut self,
         prefix_names: Vec<String>,
@@ -236,6 +396,52 @@ async fn dump_database(
                         "Gateway Public Keys"
                     );
                 }
+                DbKeyPrefix::FederationLimits => {
+                    push_db_pair_items!(
+                        self,
+                        FederationLimitsPrefix,
+                        FederationLimitsKey,
+                        FederationLimits,
+                        gateway_items,
+                        "Federation Limits"
+                    );
+                }
+                DbKeyPrefix::Bolt12Offer => {
+                    push_db_pair_items!(
+                        self,
+                        Bolt12OfferPrefix,
+                        Bolt12OfferKey,
+                        Bolt12Offer,
+                        gateway_items,
+                        "Bolt12 Offers"
+                    );
+                }
+                DbKeyPrefix::Bolt12OfferPayment => {
+                    push_db_pair_items!(
+                        self,
+                        Bolt12OfferPaymentPrefix,
+                        Bolt12OfferPaymentKey,
+                        Bolt12OfferPayment,
+                        gateway_items,
+                        "Bolt12 Offer Payments"
+                    );
+                }
+                DbKeyPrefix::Bolt12OfferPaymentCursor => {
+                    if let Some(cursor) = self.get_value(&Bolt12OfferPaymentCursorKey).await {
+                        gateway_items
+                            .insert("Bolt12 Offer Payment Cursor".to_string(), Box::new(cursor));
+                    }
+                }
+                DbKeyPrefix::HoldInvoice => {
+                    push_db_pair_items!(
+                        self,
+                        HoldInvoicePrefix,
+                        HoldInvoiceKey,
+                        HoldInvoice,
+                        gateway_items,
+                        "Hold Invoices"
+                    );
+                }
                 _ => {}
             }
         }
@@ -294,6 +500,11 @@ enum DbKeyPrefix {
     ClientDatabase = 0x10,
     Iroh = 0x11,
     FederationBackup = 0x12,
+    FederationLimits = 0x14,
+    Bolt12Offer = 0x15,
+    Bolt12OfferPayment = 0x16,
+    HoldInvoice = 0x17,
+    Bolt12OfferPaymentCursor = 0x18,
 }
 
 impl std::fmt::Display for DbKeyPrefix {
@@ -508,6 +719,25 @@ pub struct FederationBackupKey {
     query_prefix = FederationBackupPrefix,
 );
 
+#[derive(Debug, Encodable, Decodable)]
+pub struct FederationLimitsKey {
+    federation_id: FederationId,
+}
+
+#[derive(Debug, Encodable, Decodable)]
+pub struct FederationLimitsPrefix;
+
+impl_db_record!(
+    key = FederationLimitsKey,
+    value = FederationLimits,
+    db_prefix = DbKeyPrefix::FederationLimits,
+);
+
+impl_db_lookup!(
+    key = FederationLimitsKey,
+    query_prefix = FederationLimitsPrefix,
+);
+
 pub fn get_gatewayd_database_migrations() -> BTreeMap<DatabaseVersion, GeneralDbMigrationFn> {
     let mut migrations: BTreeMap<DatabaseVersion, GeneralDbMigrationFn> = BTreeMap::new();
     migrations.insert(
@@ -768,5 +998,125 @@ pub struct RegisteredIncomingContract {
     db_prefix = DbKeyPrefix::RegisteredIncomingContract,
 );
 
+#[derive(Debug, Encodable, Decodable)]
+pub struct Bolt12OfferKey {
+    offer_id: String,
+}
+
+#[derive(Debug, Encodable, Decodable)]
+pub struct Bolt12OfferPrefix;
+
+/// A BOLT12 offer created on behalf of an LNv2 client. Every payment to the
+/// offer funds an incoming contract locked to a key derived from
+/// `recipient_pk`.
+#[derive(Debug, Clone, Eq, PartialEq, Encodable, Decodable, Serialize, Deserialize)]
+pub struct Bolt12Offer {
+    pub federation_id: FederationId,
+    pub recipient_pk: secp256k1::PublicKey,
+}
+
+impl_db_record!(
+    key = Bolt12OfferKey,
+    value = Bolt12Offer,
+    db_prefix = DbKeyPrefix::Bolt12Offer,
+);
+
+impl_db_lookup!(key = Bolt12OfferKey, query_prefix = Bolt12OfferPrefix);
+
+#[derive(Debug, Encodable, Decodable)]
+struct Bolt12OfferPaymentKey {
+    payment_hash: sha256::Hash,
+}
+
+#[derive(Debug, Encodable, Decodable)]
+struct Bolt12OfferPaymentPrefix;
+
+/// A payment the gateway's lightning node received for a [`Bolt12Offer`]. The
+/// contract is persisted before it is funded such that funding it is
+/// idempotent across restarts.
+#[derive(Debug, Clone, Eq, PartialEq, Encodable, Decodable, Serialize, Deserialize)]
+pub struct Bolt12OfferPayment {
+    pub federation_id: FederationId,
+    pub recipient_pk: secp256k1::PublicKey,
+    pub amount: Amount,
+    pub contract: IncomingContract,
+    pub state: Bolt12OfferPaymentState,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Encodable, Decodable, Serialize, Deserialize)]
+pub enum Bolt12OfferPaymentState {
+    /// The contract has not been funded yet, `refunds` counts how often a
+    /// funded contract for this payment has been refunded to the gateway
+    Pending {
+        refunds: u64,
+    },
+    Completed,
+    /// The payment can not be relayed to the recipient, the operator has to
+    /// resolve it manually
+    Failed,
+}
+
+impl_db_record!(
+    key = Bolt12OfferPaymentKey,
+    value = Bolt12OfferPayment,
+    db_prefix = DbKeyPrefix::Bolt12OfferPayment,
+);
+
+impl_db_lookup!(
+    key = Bolt12OfferPaymentKey,
+    query_prefix = Bolt12OfferPaymentPrefix,
+);
+
+/// Timestamp up to which the payments to BOLT12 offers the gateway's lightning
+/// node received have been persisted as [`Bolt12OfferPayment`]s
+#[derive(Debug, Encodable, Decodable)]
+struct Bolt12OfferPaymentCursorKey;
+
+impl_db_record!(
+    key = Bolt12OfferPaymentCursorKey,
+    value = u64,
+    db_prefix = DbKeyPrefix::Bolt12OfferPaymentCursor,
+);
+
+#[derive(Debug, Encodable, Decodable)]
+pub struct HoldInvoiceKey {
+    payment_hash: sha256::Hash,
+}
+
+#[derive(Debug, Encodable, Decodable)]
+pub struct HoldInvoicePrefix;
+
+/// A hold invoice created on behalf of an LNv2 client. The incoming HTLC is
+/// held until the client submits the incoming contract or cancels the invoice,
+/// both authorized by `auth_pk`.
+#[derive(Debug, Clone, Eq, PartialEq, Encodable, Decodable, Serialize, Deserialize)]
+pub struct HoldInvoice {
+    pub federation_id: FederationId,
+    pub amount: Amount,
+    pub hold_expiry_secs: u32,
+    pub auth_pk: secp256k1::PublicKey,
+    pub state: HoldInvoiceState,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Encodable, Decodable, Serialize, Deserialize)]
+pub enum HoldInvoiceState {
+    Open,
+    Held {
+        incoming_chan_id: u64,
+        htlc_id: u64,
+        expiration: u64,
+    },
+    Settled,
+    Cancelled,
+}
+
+impl_db_record!(
+    key = HoldInvoiceKey,
+    value = HoldInvoice,
+    db_prefix = DbKeyPrefix::HoldInvoice,
+);
+
+impl_db_lookup!(key = HoldInvoiceKey, query_prefix = HoldInvoicePrefix);
+
 #[cfg(test)]
 mod migration_tests;
@@ -0,0 +1,406 @@
+//! Streaming replication of the gateway database to a standby instance.
+//!
+//! [`ReplicatedDatabase`] wraps the raw database of the active gateway and
+//! records the writes of every committed transaction in a bounded in-memory
+//! [`ReplicationLog`]. The standby periodically asks for all commits after the
+//! last one it applied and falls back to a full snapshot if the log no longer
+//! contains them (or belongs to a different process).
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::ops::Range;
+use std::path::Path;
+
+use anyhow::ensure;
+use fedimint_core::db::{
+    Database, DatabaseResult, IDatabaseTransactionOps, IDatabaseTransactionOpsCore, IRawDatabase,
+    IRawDatabaseTransaction, PrefixStream,
+};
+use fedimint_core::{apply, async_trait_maybe_send};
+use fedimint_gateway_common::{
+    ReplicatePayload, ReplicateResponse, ReplicatedCommit, ReplicatedOp,
+};
+use futures::StreamExt;
+use rand::Rng;
+use tokio::sync::Mutex;
+
+/// Maximum number of commits kept in memory before the standby has to fall
+/// back to a full snapshot.
+const REPLICATION_LOG_CAPACITY: usize = 10_000;
+
+/// Bounded log of committed writes, shared between the database wrapper and
+/// the replication API.
+pub struct ReplicationLog {
+    inner: Mutex<ReplicationLogInner>,
+}
+
+struct ReplicationLogInner {
+    /// Random identifier of this log, changes on every process start and reset
+    log_id: u64,
+    /// Sequence number of the most recent commit
+    head: u64,
+    commits: VecDeque<ReplicatedCommit>,
+}
+
+impl fmt::Debug for ReplicationLog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ReplicationLog").finish_non_exhaustive()
+    }
+}
+
+impl Default for ReplicationLog {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ReplicationLog {
+    pub fn new() -> Self {
+        Self {
+            inner: Mutex::new(ReplicationLogInner {
+                log_id: rand::thread_rng().r#gen(),
+                head: 0,
+                commits: VecDeque::new(),
+            }),
+        }
+    }
+
+    /// Discards all recorded commits and starts a new log, forcing any
+    /// follower to request a snapshot.
+    pub async fn reset(&self) {
+        let mut inner = self.inner.lock().await;
+        inner.log_id = rand::thread_rng().r#gen();
+        inner.head = 0;
+        inner.commits.clear();
+    }
+
+    /// Answers a standby's replication request, either with the commits it is
+    /// missing or with a full snapshot of `db`.
+    pub async fn replicate(&self, db: &Database, payload: &ReplicatePayload) -> ReplicateResponse {
+        let inner = self.inner.lock().await;
+
+        let oldest_available = inner
+            .commits
+            .front()
+            .map_or(inner.head, |commit| commit.seq - 1);
+
+        if payload.log_id == Some(inner.log_id)
+            && oldest_available <= payload.seq
+            && payload.seq <= inner.head
+        {
+            return ReplicateResponse::Commits {
+                log_id: inner.log_id,
+                commits: inner
+                    .commits
+                    .iter()
+                    .filter(|commit| payload.seq < commit.seq)
+                    .cloned()
+                    .collect(),
+            };
+        }
+
+        // Transactions read from a snapshot taken when they begin. Since commits
+        // have to acquire the lock as well, beginning the transaction while we
+        // hold it makes the snapshot consistent with `head`. Reading the
+        // snapshot does not block commits anymore.
+        let mut dbtx = db.begin_transaction_nc().await;
+        let (log_id, head) = (inner.log_id, inner.head);
+        drop(inner);
+
+        let entries = dbtx
+            .raw_find_by_prefix(&[])
+            .await
+            .expect("Failed to read database snapshot")
+            .map(|(key, value)| ReplicatedOp::Insert { key, value })
+            .collect::<Vec<_>>()
+            .await;
+
+        ReplicateResponse::Snapshot {
+            log_id,
+            seq: head,
+            entries,
+        }
+    }
+}
+
+/// Raw database wrapper that appends the writes of every committed
+/// transaction to a [`ReplicationLog`].
+#[derive(Debug)]
+pub struct ReplicatedDatabase<DB> {
+    inner: DB,
+    log: std::sync::Arc<ReplicationLog>,
+}
+
+impl<DB> ReplicatedDatabase<DB> {
+    pub fn new(inner: DB, log: std::sync::Arc<ReplicationLog>) -> Self {
+        Self { inner, log }
+    }
+}
+
+#[apply(async_trait_maybe_send!)]
+impl<DB> IRawDatabase for ReplicatedDatabase<DB>
+where
+    DB: IRawDatabase,
+{
+    type Transaction<'a> = ReplicatedTransaction<'a, DB::Transaction<'a>>;
+
+    async fn begin_transaction<'a>(&'a self) -> Self::Transaction<'a> {
+        ReplicatedTransaction {
+            inner: self.inner.begin_transaction().await,
+            log: &self.log,
+            ops: Vec::new(),
+        }
+    }
+
+    fn checkpoint(&self, backup_path: &Path) -> DatabaseResult<()> {
+        self.inner.checkpoint(backup_path)
+    }
+}
+
+#[derive(Debug)]
+pub struct ReplicatedTransaction<'a, TX> {
+    inner: TX,
+    log: &'a ReplicationLog,
+    ops: Vec<ReplicatedOp>,
+}
+
+#[apply(async_trait_maybe_send!)]
+impl<TX> IDatabaseTransactionOpsCore for ReplicatedTransaction<'_, TX>
+where
+    TX: IDatabaseTransactionOps,
+{
+    async fn raw_insert_bytes(
+        &mut self,
+        key: &[u8],
+        value: &[u8],
+    ) -> DatabaseResult<Option<Vec<u8>>> {
+        let previous = self.inner.raw_insert_bytes(key, value).await?;
+        self.ops.push(ReplicatedOp::Insert {
+            key: key.to_vec(),
+            value: value.to_vec(),
+        });
+        Ok(previous)
+    }
+
+    async fn raw_get_bytes(&mut self, key: &[u8]) -> DatabaseResult<Option<Vec<u8>>> {
+        self.inner.raw_get_bytes(key).await
+    }
+
+    async fn raw_remove_entry(&mut self, key: &[u8]) -> DatabaseResult<Option<Vec<u8>>> {
+        let previous = self.inner.raw_remove_entry(key).await?;
+        self.ops.push(ReplicatedOp::Remove { key: key.to_vec() });
+        Ok(previous)
+    }
+
+    async fn raw_find_by_prefix(&mut self, key_prefix: &[u8]) -> DatabaseResult<PrefixStream<'_>> {
+        self.inner.raw_find_by_prefix(key_prefix).await
+    }
+
+    async fn raw_find_by_prefix_sorted_descending(
+        &mut self,
+        key_prefix: &[u8],
+    ) -> DatabaseResult<PrefixStream<'_>> {
+        self.inner
+            .raw_find_by_prefix_sorted_descending(key_prefix)
+            .await
+    }
+
+    async fn raw_find_by_range(&mut self, range: Range<&[u8]>) -> DatabaseResult<PrefixStream<'_>> {
+        self.inner.raw_find_by_range(range).await
+    }
+
+    async fn raw_remove_by_prefix(&mut self, key_prefix: &[u8]) -> DatabaseResult<()> {
+        self.inner.raw_remove_by_prefix(key_prefix).await?;
+        self.ops.push(ReplicatedOp::RemoveByPrefix {
+            prefix: key_prefix.to_vec(),
+        });
+        Ok(())
+    }
+}
+
+impl<TX> IDatabaseTransactionOps for ReplicatedTransaction<'_, TX> where TX: IDatabaseTransactionOps {}
+
+#[apply(async_trait_maybe_send!)]
+impl<TX> IRawDatabaseTransaction for ReplicatedTransaction<'_, TX>
+where
+    TX: IRawDatabaseTransaction,
+{
+    async fn commit_tx(self) -> DatabaseResult<()> {
+        // Commits are serialized through the log so that the order of the log
+        // matches the order in which writes became visible.
+        let mut log = self.log.inner.lock().await;
+        self.inner.commit_tx().await?;
+
+        if !self.ops.is_empty() {
+            log.head += 1;
+            let seq = log.head;
+            log.commits
+                .push_back(ReplicatedCommit { seq, ops: self.ops });
+            if log.commits.len() > REPLICATION_LOG_CAPACITY {
+                log.commits.pop_front();
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Applies a replication response from the active gateway to the standby's
+/// database, where `seq` is the sequence number of the last commit applied
+/// previously. Returns the log id and sequence number to request next.
+pub async fn apply_replicate_response(
+    db: &Database,
+    seq: u64,
+    response: ReplicateResponse,
+) -> anyhow::Result<(u64, u64)> {
+    let mut dbtx = db.begin_transaction().await;
+
+    let position = match response {
+        ReplicateResponse::Commits { log_id, commits } => {
+            let mut seq = seq;
+            for commit in commits {
+                ensure!(
+                    commit.seq == seq + 1,
+                    "Replicated commits are not contiguous"
+                );
+                apply_ops(&mut dbtx, commit.ops).await?;
+                seq = commit.seq;
+            }
+            (log_id, seq)
+        }
+        ReplicateResponse::Snapshot {
+            log_id,
+            seq,
+            entries,
+        } => {
+            dbtx.raw_remove_by_prefix(&[]).await?;
+            apply_ops(&mut dbtx, entries).await?;
+            (log_id, seq)
+        }
+    };
+
+    dbtx.commit_tx_result().await?;
+
+    Ok(position)
+}
+
+async fn apply_ops(
+    dbtx: &mut (impl IDatabaseTransactionOpsCore + Send),
+    ops: Vec<ReplicatedOp>,
+) -> anyhow::Result<()> {
+    for op in ops {
+        match op {
+            ReplicatedOp::Insert { key, value } => {
+                dbtx.raw_insert_bytes(&key, &value).await?;
+            }
+            ReplicatedOp::Remove { key } => {
+                dbtx.raw_remove_entry(&key).await?;
+            }
+            ReplicatedOp::RemoveByPrefix { prefix } => {
+                dbtx.raw_remove_by_prefix(&prefix).await?;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use fedimint_core::db::mem_impl::MemDatabase;
+    use fedimint_core::db::{Database, IDatabaseTransactionOpsCore};
+    use fedimint_core::module::registry::ModuleDecoderRegistry;
+    use fedimint_gateway_common::{ReplicatePayload, ReplicateResponse};
+    use futures::StreamExt;
+
+    use super::{ReplicatedDatabase, ReplicationLog, apply_replicate_response};
+
+    async fn dump(db: &Database) -> Vec<(Vec<u8>, Vec<u8>)> {
+        db.begin_transaction_nc()
+            .await
+            .raw_find_by_prefix(&[])
+            .await
+            .unwrap()
+            .collect()
+            .await
+    }
+
+    async fn write(db: &Database, key: &[u8], value: Option<&[u8]>) {
+        let mut dbtx = db.begin_transaction().await;
+        match value {
+            Some(value) => dbtx.raw_insert_bytes(key, value).await.unwrap(),
+            None => dbtx.raw_remove_entry(key).await.unwrap(),
+        };
+        dbtx.commit_tx().await;
+    }
+
+    #[tokio::test]
+    async fn standby_converges_to_active() {
+        let log = Arc::new(ReplicationLog::new());
+        let active = Database::new(
+            ReplicatedDatabase::new(MemDatabase::new(), log.clone()),
+            ModuleDecoderRegistry::default(),
+        );
+        let standby = Database::new(MemDatabase::new(), ModuleDecoderRegistry::default());
+        write(&standby, b"stale", Some(b"entry")).await;
+
+        write(&active, b"a", Some(b"1")).await;
+        write(&active, b"b", Some(b"2")).await;
+
+        // The first request always receives a snapshot
+        let response = log
+            .replicate(
+                &active,
+                &ReplicatePayload {
+                    log_id: None,
+                    seq: 0,
+                },
+            )
+            .await;
+        assert!(matches!(
+            response,
+            ReplicateResponse::Snapshot { seq: 2, .. }
+        ));
+        let (log_id, seq) = apply_replicate_response(&standby, 0, response)
+            .await
+            .unwrap();
+        assert_eq!(dump(&active).await, dump(&standby).await);
+
+        write(&active, b"a", None).await;
+        write(&active, b"c", Some(b"3")).await;
+
+        // Afterwards only the missing commits are sent
+        let response = log
+            .replicate(
+                &active,
+                &ReplicatePayload {
+                    log_id: Some(log_id),
+                    seq,
+                },
+            )
+            .await;
+        let ReplicateResponse::Commits { ref commits, .. } = response else {
+            panic!("Expected incremental commits");
+        };
+        assert_eq!(commits.len(), 2);
+        let (_, seq) = apply_replicate_response(&standby, seq, response)
+            .await
+            .unwrap();
+        assert_eq!(seq, 4);
+        assert_eq!(dump(&active).await, dump(&standby).await);
+
+        // A reset log forces a new snapshot
+        log.reset().await;
+        let response = log
+            .replicate(
+                &active,
+                &ReplicatePayload {
+                    log_id: Some(log_id),
+                    seq,
+                },
+            )
+            .await;
+        assert!(matches!(response, ReplicateResponse::Snapshot { .. }));
+    }
+}
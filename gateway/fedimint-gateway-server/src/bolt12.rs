@@ -0,0 +1,71 @@
+//! Receiving BOLT12 offer payments on behalf of LNv2 clients.
+//!
+//! The gateway's lightning node settles payments to these offers itself, after
+//! which the gateway funds an [`IncomingContract`] locked to a key derived
+//! from the recipient's static public key. The recipient's client discovers
+//! the contract by scanning all incoming contracts, exactly as it does for
+//! lnurl payments created by recurringd.
+
+use std::time::Duration;
+
+use bitcoin::hashes::sha256;
+use fedimint_core::encoding::Encodable;
+use fedimint_core::secp256k1::{self, PublicKey, Scalar};
+use fedimint_core::time::duration_since_epoch;
+use fedimint_core::{Amount, BitcoinHash};
+use fedimint_lnv2_common::contracts::{IncomingContract, PaymentImage};
+use fedimint_lnv2_common::tweak;
+use tpe::AggregatePublicKey;
+
+/// How often the lightning node is polled for new offer payments
+pub const BOLT12_OFFER_PAYMENT_POLL_INTERVAL: Duration = Duration::from_secs(10);
+
+/// How often a payment is relayed with a fresh contract after its funded
+/// contract has been refunded to the gateway before it is marked as failed
+pub const MAX_OFFER_PAYMENT_REFUNDS: u64 = 3;
+
+/// How long the federation accepts the funding transaction of the incoming
+/// contract. Once it expired, a fresh contract is created for the payment.
+const CONTRACT_EXPIRY: Duration = Duration::from_secs(60 * 60);
+
+/// Creates an incoming contract for a payment to a BOLT12 offer that can only
+/// be claimed by the owner of `recipient_pk`.
+pub fn incoming_contract(
+    aggregate_pk: AggregatePublicKey,
+    recipient_pk: PublicKey,
+    refund_pk: PublicKey,
+    amount: Amount,
+) -> IncomingContract {
+    let (ephemeral_tweak, ephemeral_pk) = tweak::generate(recipient_pk);
+
+    let claim_pk = recipient_pk
+        .mul_tweak(
+            secp256k1::SECP256K1,
+            &Scalar::from_be_bytes(ephemeral_tweak).expect("Within curve order"),
+        )
+        .expect("Tweak is valid");
+
+    let encryption_seed = ephemeral_tweak
+        .consensus_hash::<sha256::Hash>()
+        .to_byte_array();
+
+    let preimage = encryption_seed
+        .consensus_hash::<sha256::Hash>()
+        .to_byte_array();
+
+    let expiration = duration_since_epoch()
+        .as_secs()
+        .saturating_add(CONTRACT_EXPIRY.as_secs());
+
+    IncomingContract::new(
+        aggregate_pk,
+        encryption_seed,
+        preimage,
+        PaymentImage::Hash(preimage.consensus_hash()),
+        amount,
+        expiration,
+        claim_pk,
+        refund_pk,
+        ephemeral_pk,
+    )
+}
@@ -0,0 +1,189 @@
+//! Health checks that pause routing for a federation when it looks unsafe to
+//! take on more of its ecash.
+
+use std::time::{Duration, SystemTime};
+
+use fedimint_api_client::api::FederationApiExt;
+use fedimint_client::ClientHandleArc;
+use fedimint_core::endpoint_constants::SESSION_COUNT_ENDPOINT;
+use fedimint_core::module::ApiRequestErased;
+use fedimint_core::module::liabilities::LiabilityRoot;
+use fedimint_core::runtime::timeout;
+use fedimint_core::time::now;
+use fedimint_core::util::FmtCompactAnyhow as _;
+use fedimint_core::{Amount, NumPeersExt};
+use fedimint_gateway_common::CircuitBreakerReason;
+use fedimint_logging::LOG_GATEWAY;
+use fedimint_mint_client::MintClientModule;
+use fedimint_mintv2_client::MintClientModule as MintV2ClientModule;
+use fedimint_wallet_client::WalletClientModule;
+use futures::future::join_all;
+use tracing::debug;
+
+/// How often the health of every connected federation is checked
+pub const CIRCUIT_BREAKER_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// How long we wait for a single guardian to answer the health check
+const PEER_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long the federation may fail to complete a consensus session before
+/// routing is paused. Sessions are produced continuously, even without any
+/// transactions, so this is far above the regular session duration.
+const CONSENSUS_STALL_TIMEOUT: Duration = Duration::from_secs(60 * 60);
+
+/// Tracks what the previous health checks observed for a single federation
+#[derive(Debug, Default)]
+pub struct FederationHealth {
+    progress: Option<ConsensusProgress>,
+    /// Whether the previous audit found the outstanding ecash to be unbacked
+    unbacked: bool,
+}
+
+/// Tracks the consensus progress observed for a single federation
+#[derive(Debug, Clone, Copy)]
+struct ConsensusProgress {
+    session_count: u64,
+    since: SystemTime,
+}
+
+/// Returns a reason to pause routing if the consensus of the federation looks
+/// unhealthy or if its audit does not add up.
+pub async fn check_federation_health(
+    client: &ClientHandleArc,
+    health: &mut FederationHealth,
+) -> Option<CircuitBreakerReason> {
+    if let Some(reason) = check_consensus(client, &mut health.progress).await {
+        return Some(reason);
+    }
+
+    check_audit(client, &mut health.unbacked).await
+}
+
+/// Queries every guardian of the federation for its session count. Returns a
+/// reason to pause routing if less than a threshold of guardians answered or
+/// if the consensus did not progress for longer than
+/// [`CONSENSUS_STALL_TIMEOUT`].
+async fn check_consensus(
+    client: &ClientHandleArc,
+    progress: &mut Option<ConsensusProgress>,
+) -> Option<CircuitBreakerReason> {
+    let api = client.api();
+    let peers = api.all_peers().clone();
+    let threshold = peers.to_num_peers().threshold();
+
+    let session_counts = join_all(peers.iter().map(|peer| {
+        timeout(
+            PEER_TIMEOUT,
+            api.request_single_peer::<u64>(
+                SESSION_COUNT_ENDPOINT.to_string(),
+                ApiRequestErased::default(),
+                *peer,
+            ),
+        )
+    }))
+    .await
+    .into_iter()
+    .filter_map(|response| response.ok()?.ok())
+    .collect::<Vec<u64>>();
+
+    if session_counts.len() < threshold {
+        return Some(CircuitBreakerReason::GuardiansUnreachable {
+            reachable: session_counts.len(),
+            threshold,
+        });
+    }
+
+    let session_count = session_counts
+        .into_iter()
+        .max()
+        .expect("At least a threshold of guardians responded");
+
+    match progress {
+        Some(progress) if progress.session_count == session_count => {
+            let stalled_for = now().duration_since(progress.since).unwrap_or_default();
+            if CONSENSUS_STALL_TIMEOUT < stalled_for {
+                return Some(CircuitBreakerReason::ConsensusStalled {
+                    session_count,
+                    stalled_for,
+                });
+            }
+        }
+        _ => {
+            *progress = Some(ConsensusProgress {
+                session_count,
+                since: now(),
+            });
+        }
+    }
+
+    None
+}
+
+/// Compares the outstanding ecash the mint last committed to against the
+/// bitcoin held by the wallet, since the audit itself is only available to
+/// the guardians. The committed liabilities lag behind the wallet by about a
+/// session, such that a peg-out may briefly look like a deficit, hence we only
+/// report one that persists across two consecutive checks. Federations without
+/// a mint that commits to its liabilities or without a wallet are not audited,
+/// and neither are federations we currently fail to query.
+async fn check_audit(
+    client: &ClientHandleArc,
+    unbacked: &mut bool,
+) -> Option<CircuitBreakerReason> {
+    let root = match committed_liability_root(client).await {
+        Ok(Some(root)) => root,
+        Ok(None) => return None,
+        Err(err) => {
+            debug!(target: LOG_GATEWAY, err = %err.fmt_compact_anyhow(), "Failed to fetch committed liabilities");
+            return None;
+        }
+    };
+
+    let Some(liabilities) = root.outstanding().filter(|_| root.is_well_formed()) else {
+        return Some(CircuitBreakerReason::MalformedLiabilities);
+    };
+
+    let assets = match wallet_holdings(client).await {
+        Ok(Some(assets)) => assets,
+        Ok(None) => return None,
+        Err(err) => {
+            debug!(target: LOG_GATEWAY, err = %err.fmt_compact_anyhow(), "Failed to fetch wallet holdings");
+            return None;
+        }
+    };
+
+    let was_unbacked = std::mem::replace(unbacked, assets < liabilities);
+
+    (was_unbacked && *unbacked).then_some(CircuitBreakerReason::UnbackedLiabilities {
+        liabilities,
+        assets,
+    })
+}
+
+async fn committed_liability_root(
+    client: &ClientHandleArc,
+) -> anyhow::Result<Option<LiabilityRoot>> {
+    if let Ok(mint) = client.get_first_module::<MintClientModule>() {
+        return Ok(mint.committed_liability_root().await?);
+    }
+
+    if let Ok(mint) = client.get_first_module::<MintV2ClientModule>() {
+        return Ok(mint.committed_liability_root().await?);
+    }
+
+    Ok(None)
+}
+
+async fn wallet_holdings(client: &ClientHandleArc) -> anyhow::Result<Option<Amount>> {
+    let holdings = if let Ok(wallet) = client.get_first_module::<WalletClientModule>() {
+        wallet.get_wallet_summary().await?.total_owned_balance()
+    } else if let Ok(wallet) =
+        client.get_first_module::<fedimint_walletv2_client::WalletClientModule>()
+    {
+        wallet.total_value().await?
+    } else {
+        return Ok(None);
+    };
+
+    Ok(Some(Amount::from_sats(holdings.to_sat())))
+}
@@ -1,6 +1,6 @@
 use std::collections::BTreeSet;
 use std::fmt::Debug;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
 use fedimint_bip39::{Bip39RootSecretStrategy, Mnemonic};
@@ -10,13 +10,18 @@
 use fedimint_client_module::secret::{PlainRootSecretStrategy, RootSecretStrategy};
 use fedimint_connectors::ConnectorRegistry;
 use fedimint_core::config::FederationId;
-use fedimint_core::db::{Database, IDatabaseTransactionOpsCoreTyped};
+use fedimint_core::db::{
+    Database, IDatabaseTransactionOpsCore as _, IDatabaseTransactionOpsCoreTyped,
+};
 use fedimint_core::module::registry::ModuleDecoderRegistry;
 use fedimint_derive_secret::DerivableSecret;
 use fedimint_gateway_common::FederationConfig;
 use fedimint_gateway_server_db::GatewayDbExt as _;
 use fedimint_gw_client::GatewayClientInit;
 use fedimint_gwv2_client::GatewayClientInitV2;
+use fedimint_logging::LOG_GATEWAY;
+use futures::StreamExt as _;
+use tracing::info;
 
 use crate::config::DatabaseBackend;
 use crate::error::AdminGatewayError;
@@ -28,6 +33,10 @@ pub struct GatewayClientBuilder {
     registry: ClientModuleInitRegistry,
     db_backend: DatabaseBackend,
     connectors: ConnectorRegistry,
+    /// Set if the gateway database is replicated to a high-availability
+    /// standby, in which case the separate databases of legacy federations
+    /// are migrated into it such that they are replicated as well
+    replicated: bool,
 }
 
 impl GatewayClientBuilder {
@@ -35,12 +44,14 @@ pub async fn new(
         work_dir: PathBuf,
         registry: ClientModuleInitRegistry,
         db_backend: DatabaseBackend,
+        replicated: bool,
     ) -> anyhow::Result<Self> {
         Ok(Self {
             connectors: ConnectorRegistry::build_from_client_env()?.bind().await?,
             work_dir,
             registry,
             db_backend,
+            replicated,
         })
     }
 
@@ -57,6 +68,81 @@ async fn client_plainrootsecret(&self, db: &Database) -> AdminResult<DerivableSe
         Ok(PlainRootSecretStrategy::to_root_secret(&client_secret))
     }
 
+    /// Path of the separate database of a "legacy" federation
+    fn legacy_db_path(&self, federation_id: &FederationId) -> PathBuf {
+        self.work_dir.join(format!("{federation_id}.db"))
+    }
+
+    async fn open_legacy_database(&self, db_path: &Path) -> AdminResult<Database> {
+        let db = match self.db_backend {
+            DatabaseBackend::RocksDb => {
+                let rocksdb = fedimint_rocksdb::RocksDb::build(db_path)
+                    .open()
+                    .await
+                    .map_err(AdminGatewayError::ClientCreationError)?;
+                Database::new(rocksdb, ModuleDecoderRegistry::default())
+            }
+            DatabaseBackend::CursedRedb => {
+                let cursed_redb = fedimint_cursed_redb::MemAndRedb::new(db_path)
+                    .await
+                    .map_err(AdminGatewayError::ClientCreationError)?;
+                Database::new(cursed_redb, ModuleDecoderRegistry::default())
+            }
+        };
+
+        Ok(db)
+    }
+
+    /// Copies the separate database of a "legacy" federation into the client
+    /// database within the gateway database and renames the separate database
+    /// afterwards, such that the copy is used from now on. Since the plain root
+    /// secret is copied along, the federation is still recognized as legacy.
+    async fn migrate_legacy_database(
+        &self,
+        legacy_db: Database,
+        db_path: &Path,
+        client_db: &Database,
+    ) -> AdminResult<()> {
+        let entries = legacy_db
+            .begin_transaction_nc()
+            .await
+            .raw_find_by_prefix(&[])
+            .await
+            .map_err(|e| AdminGatewayError::ClientCreationError(e.into()))?
+            .collect::<Vec<_>>()
+            .await;
+
+        let mut dbtx = client_db.begin_transaction().await;
+
+        for (key, value) in &entries {
+            dbtx.raw_insert_bytes(key, value)
+                .await
+                .map_err(|e| AdminGatewayError::ClientCreationError(e.into()))?;
+        }
+
+        dbtx.commit_tx_result()
+            .await
+            .map_err(|e| AdminGatewayError::ClientCreationError(e.into()))?;
+
+        // Close the separate database before renaming it
+        drop(legacy_db);
+
+        let mut migrated_path = db_path.as_os_str().to_owned();
+        migrated_path.push(".migrated");
+
+        std::fs::rename(db_path, &migrated_path)
+            .map_err(|e| AdminGatewayError::ClientCreationError(e.into()))?;
+
+        info!(
+            target: LOG_GATEWAY,
+            entries = entries.len(),
+            path = %db_path.display(),
+            "Migrated legacy client database into the replicated gateway database"
+        );
+
+        Ok(())
+    }
+
     /// Constructs the client builder with the modules, database, and connector
     /// used to create clients for connected federations.
     async fn create_client_builder(
@@ -128,32 +214,30 @@ pub async fn build(
     ) -> AdminResult<fedimint_client::ClientHandleArc> {
         let invite_code = config.invite_code.clone();
         let federation_id = invite_code.federation_id();
-        let db_path = self.work_dir.join(format!("{federation_id}.db"));
-
-        let (db, root_secret) = if db_path.exists() {
-            let db = match self.db_backend {
-                DatabaseBackend::RocksDb => {
-                    let rocksdb = fedimint_rocksdb::RocksDb::build(db_path.clone())
-                        .open()
-                        .await
-                        .map_err(AdminGatewayError::ClientCreationError)?;
-                    Database::new(rocksdb, ModuleDecoderRegistry::default())
-                }
-                DatabaseBackend::CursedRedb => {
-                    let cursed_redb = fedimint_cursed_redb::MemAndRedb::new(db_path.clone())
-                        .await
-                        .map_err(AdminGatewayError::ClientCreationError)?;
-                    Database::new(cursed_redb, ModuleDecoderRegistry::default())
-                }
-            };
+        let db_path = self.legacy_db_path(&federation_id);
+
+        let (db, root_secret) = if db_path.exists() && !self.replicated {
+            let db = self.open_legacy_database(&db_path).await?;
             let root_secret = RootSecret::Custom(self.client_plainrootsecret(&db).await?);
             (db, root_secret)
         } else {
             let db = gateway.gateway_db.get_client_database(&federation_id);
 
-            let root_secret = RootSecret::StandardDoubleDerive(
-                Bip39RootSecretStrategy::<12>::to_root_secret(mnemonic),
-            );
+            if db_path.exists() {
+                let legacy_db = self.open_legacy_database(&db_path).await?;
+                self.migrate_legacy_database(legacy_db, &db_path, &db)
+                    .await?;
+            }
+
+            // Only the databases of legacy federations contain a plain root
+            // secret, which is the case once they have been migrated
+            let root_secret = if Self::is_legacy_client_database(&db).await? {
+                RootSecret::Custom(self.client_plainrootsecret(&db).await?)
+            } else {
+                RootSecret::StandardDoubleDerive(Bip39RootSecretStrategy::<12>::to_root_secret(
+                    mnemonic,
+                ))
+            };
             (db, root_secret)
         };
 
@@ -190,15 +274,33 @@ async fn verify_client_config(db: &Database, federation_id: FederationId) -> Adm
         Ok(())
     }
 
+    async fn is_legacy_client_database(db: &Database) -> AdminResult<bool> {
+        Ok(Client::load_decodable_client_secret_opt::<[u8; 64]>(db)
+            .await
+            .map_err(AdminGatewayError::ClientCreationError)?
+            .is_some())
+    }
+
     /// Returns a vector of "legacy" federations which did not derive their
     /// client secret's from the gateway's mnemonic.
-    pub fn legacy_federations(&self, all_federations: BTreeSet<FederationId>) -> Vec<FederationId> {
-        all_federations
-            .into_iter()
-            .filter(|federation_id| {
-                let db_path = self.work_dir.join(format!("{federation_id}.db"));
-                db_path.exists()
-            })
-            .collect::<Vec<FederationId>>()
+    pub async fn legacy_federations(
+        &self,
+        gateway_db: &Database,
+        all_federations: BTreeSet<FederationId>,
+    ) -> Vec<FederationId> {
+        let mut legacy_federations = vec![];
+
+        for federation_id in all_federations {
+            let is_legacy = self.legacy_db_path(&federation_id).exists()
+                || Self::is_legacy_client_database(&gateway_db.get_client_database(&federation_id))
+                    .await
+                    .unwrap_or(false);
+
+            if is_legacy {
+                legacy_federations.push(federation_id);
+            }
+        }
+
+        legacy_federations
     }
 }
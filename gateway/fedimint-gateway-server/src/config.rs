@@ -1,6 +1,7 @@
 use std::net::SocketAddr;
 use std::path::PathBuf;
 use std::str::FromStr;
+use std::time::Duration;
 
 use bitcoin::Network;
 use clap::{ArgGroup, Parser};
@@ -12,8 +13,11 @@
 use super::envs;
 use crate::envs::{
     FM_BITCOIND_PASSWORD_ENV, FM_BITCOIND_URL_ENV, FM_BITCOIND_USERNAME_ENV, FM_ESPLORA_URL_ENV,
+    FM_GATEWAY_HA_INSTANCE_ID_ENV, FM_GATEWAY_HA_LEASE_FILE_ENV, FM_GATEWAY_HA_LEASE_TTL_SECS_ENV,
+    FM_GATEWAY_HA_PEER_PASSWORD_ENV, FM_GATEWAY_HA_PEER_URL_ENV, FM_GATEWAY_HA_PREFER_ACTIVE_ENV,
     FM_GATEWAY_METRICS_LISTEN_ADDR_ENV, FM_GATEWAY_SKIP_SETUP_ENV,
 };
+use crate::ha::HaConfig;
 
 #[derive(Debug, Clone, Copy, clap::ValueEnum)]
 pub enum DatabaseBackend {
@@ -134,6 +138,41 @@ pub struct GatewayOpts {
 
     #[arg(long, env = FM_GATEWAY_SKIP_SETUP_ENV, default_value_t = false)]
     skip_setup: bool,
+
+    /// API URL of the other instance of an active/standby gateway pair. Both
+    /// instances must share the same LND node and should be reachable under
+    /// the same public `api-addr`. Enables high-availability mode.
+    #[arg(
+        long,
+        env = FM_GATEWAY_HA_PEER_URL_ENV,
+        requires_all = ["ha_peer_password", "ha_lease_file"]
+    )]
+    ha_peer_url: Option<SafeUrl>,
+
+    /// Admin password of the other gateway instance of the pair
+    #[arg(long, env = FM_GATEWAY_HA_PEER_PASSWORD_ENV)]
+    ha_peer_password: Option<String>,
+
+    /// Stable identifier of this instance within the pair. A random one is
+    /// generated on every start if not set.
+    #[arg(long, env = FM_GATEWAY_HA_INSTANCE_ID_ENV)]
+    ha_instance_id: Option<String>,
+
+    /// Become active on startup unless the peer already holds a valid leader
+    /// lease. Should be set on exactly one instance of the pair.
+    #[arg(long, env = FM_GATEWAY_HA_PREFER_ACTIVE_ENV, default_value_t = false)]
+    ha_prefer_active: bool,
+
+    /// How long the leader lease stays valid without being renewed
+    #[arg(long, env = FM_GATEWAY_HA_LEASE_TTL_SECS_ENV, default_value_t = 30)]
+    ha_lease_ttl_secs: u64,
+
+    /// File holding the leader lease of the pair. Both instances have to
+    /// access the same file, e.g. on a volume shared with the LND node, since
+    /// it decides which instance is active even if they cannot reach each
+    /// other.
+    #[arg(long, env = FM_GATEWAY_HA_LEASE_FILE_ENV)]
+    ha_lease_file: Option<PathBuf>,
 }
 
 impl GatewayOpts {
@@ -161,6 +200,18 @@ pub fn to_gateway_parameters(&self) -> anyhow::Result<GatewayParameters> {
             )
         });
 
+        let ha = self.ha_peer_url.clone().map(|peer_url| HaConfig {
+            peer_url,
+            peer_password: self.ha_peer_password.clone().expect("Enforced by clap"),
+            instance_id: self
+                .ha_instance_id
+                .clone()
+                .unwrap_or_else(|| hex::encode(rand::random::<[u8; 8]>())),
+            prefer_active: self.ha_prefer_active,
+            lease_ttl: Duration::from_secs(self.ha_lease_ttl_secs),
+            lease_file: self.ha_lease_file.clone().expect("Enforced by clap"),
+        });
+
         Ok(GatewayParameters {
             listen: self.listen,
             versioned_api,
@@ -175,6 +226,7 @@ pub fn to_gateway_parameters(&self) -> anyhow::Result<GatewayParameters> {
             iroh_relays: self.iroh_relays.clone(),
             skip_setup: self.skip_setup,
             metrics_listen,
+            ha,
         })
     }
 }
@@ -200,4 +252,5 @@ pub struct GatewayParameters {
     pub iroh_relays: Vec<SafeUrl>,
     pub skip_setup: bool,
     pub metrics_listen: SocketAddr,
+    pub ha: Option<HaConfig>,
 }
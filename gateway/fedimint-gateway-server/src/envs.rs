@@ -75,3 +75,27 @@
 /// Environment variable that instructs the gateway to generate a mnemonic if
 /// one has not already been set.
 pub const FM_GATEWAY_SKIP_SETUP_ENV: &str = "FM_GATEWAY_SKIP_SETUP";
+
+/// Environment variable that enables high-availability mode by specifying the
+/// API URL of the other gateway instance of the active/standby pair.
+pub const FM_GATEWAY_HA_PEER_URL_ENV: &str = "FM_GATEWAY_HA_PEER_URL";
+
+/// Environment variable that specifies the admin password of the other gateway
+/// instance of the high-availability pair.
+pub const FM_GATEWAY_HA_PEER_PASSWORD_ENV: &str = "FM_GATEWAY_HA_PEER_PASSWORD";
+
+/// Environment variable that specifies a stable identifier of this gateway
+/// instance within the high-availability pair.
+pub const FM_GATEWAY_HA_INSTANCE_ID_ENV: &str = "FM_GATEWAY_HA_INSTANCE_ID";
+
+/// Environment variable that instructs this gateway instance to become active
+/// on startup if its peer does not hold a valid leader lease.
+pub const FM_GATEWAY_HA_PREFER_ACTIVE_ENV: &str = "FM_GATEWAY_HA_PREFER_ACTIVE";
+
+/// Environment variable that specifies how long the leader lease of the
+/// high-availability pair is valid without being renewed, in seconds.
+pub const FM_GATEWAY_HA_LEASE_TTL_SECS_ENV: &str = "FM_GATEWAY_HA_LEASE_TTL_SECS";
+
+/// Environment variable that specifies the path of the leader lease file shared
+/// by both instances of the high-availability pair.
+pub const FM_GATEWAY_HA_LEASE_FILE_ENV: &str = "FM_GATEWAY_HA_LEASE_FILE";
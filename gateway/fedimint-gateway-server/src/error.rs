@@ -52,6 +52,8 @@ pub enum PublicGatewayError {
     FederationNotConnected(#[from] FederationNotConnected),
     #[error("Failed to receive ecash: {failure_reason}")]
     ReceiveEcashError { failure_reason: String },
+    #[error("Too many concurrent requests")]
+    RateLimited,
     #[error("Unexpected Error: {}", OptStacktrace(.0))]
     Unexpected(#[from] anyhow::Error),
 }
@@ -70,6 +72,10 @@ fn into_response(self) -> Response {
                 "Failed to receive ecash".to_string(),
                 StatusCode::INTERNAL_SERVER_ERROR,
             ),
+            PublicGatewayError::RateLimited => (
+                "Too many concurrent requests, please try again later".to_string(),
+                StatusCode::TOO_MANY_REQUESTS,
+            ),
             PublicGatewayError::Lightning(_) => (
                 "Lightning Network operation failed".to_string(),
                 StatusCode::INTERNAL_SERVER_ERROR,
@@ -10,7 +10,7 @@
 use fedimint_core::invite_code::InviteCode;
 use fedimint_core::util::{FmtCompactAnyhow as _, Spanned};
 use fedimint_core::{PeerId, TieredCounts};
-use fedimint_gateway_common::FederationInfo;
+use fedimint_gateway_common::{CircuitBreakerReason, FederationInfo};
 use fedimint_gateway_server_db::GatewayDbtxNcExt as _;
 use fedimint_gw_client::GatewayClientModule;
 use fedimint_gwv2_client::GatewayClientModuleV2;
@@ -42,6 +42,10 @@ pub struct FederationManager {
     /// federation, this value is incremented and assigned to the federation
     /// as the `federation_index`
     next_index: AtomicU64,
+
+    /// Federations for which routing is currently paused by the circuit
+    /// breaker, together with the reason.
+    circuit_breakers: BTreeMap<FederationId, CircuitBreakerReason>,
 }
 
 impl FederationManager {
@@ -50,6 +54,7 @@ pub fn new() -> Self {
             clients: BTreeMap::new(),
             index_to_federation: BTreeMap::new(),
             next_index: AtomicU64::new(INITIAL_INDEX),
+            circuit_breakers: BTreeMap::new(),
         }
     }
 
@@ -88,6 +93,7 @@ async fn remove_client(&mut self, federation_id: FederationId) -> AdminResult<()
 
         self.index_to_federation
             .retain(|_, fid| *fid != federation_id);
+        self.circuit_breakers.remove(&federation_id);
 
         match Arc::into_inner(client) {
             Some(client) => {
@@ -202,6 +208,41 @@ pub fn client(&self, federation_id: &FederationId) -> Option<&Spanned<ClientHand
         self.clients.get(federation_id)
     }
 
+    /// Returns the clients of all connected federations
+    pub fn clients(&self) -> Vec<ClientHandleArc> {
+        self.clients
+            .values()
+            .map(|client| client.value().clone())
+            .collect()
+    }
+
+    /// Returns the reason why routing for the federation is paused, if it is
+    pub fn circuit_breaker(&self, federation_id: &FederationId) -> Option<&CircuitBreakerReason> {
+        self.circuit_breakers.get(federation_id)
+    }
+
+    /// Trips the circuit breaker of the federation or resets it if `reason`
+    /// is `None`.
+    pub fn set_circuit_breaker(
+        &mut self,
+        federation_id: FederationId,
+        reason: Option<CircuitBreakerReason>,
+    ) {
+        match reason {
+            Some(reason) => {
+                if !self.circuit_breakers.contains_key(&federation_id) {
+                    warn!(target: LOG_GATEWAY, %federation_id, %reason, "Pausing routing for federation");
+                }
+                self.circuit_breakers.insert(federation_id, reason);
+            }
+            None => {
+                if self.circuit_breakers.remove(&federation_id).is_some() {
+                    info!(target: LOG_GATEWAY, %federation_id, "Resuming routing for federation");
+                }
+            }
+        }
+    }
+
     pub async fn federation_info(
         &self,
         federation_id: FederationId,
@@ -233,6 +274,7 @@ pub async fn federation_info(
                         .ok_or(FederationNotConnected {
                             federation_id_prefix: federation_id.to_prefix(),
                         })?;
+                let limits = dbtx.load_federation_limits(federation_id).await;
 
                 Ok(FederationInfo {
                     federation_id,
@@ -240,6 +282,8 @@ pub async fn federation_info(
                     balance_msat,
                     config,
                     last_backup_time,
+                    limits,
+                    circuit_breaker: self.circuit_breaker(&federation_id).cloned(),
                 })
             })
             .await
@@ -278,6 +322,7 @@ pub async fn federation_info_all_federations(
                 .load_backup_record(*federation_id)
                 .await
                 .unwrap_or_default();
+            let limits = dbtx.load_federation_limits(*federation_id).await;
             if let Some(config) = config {
                 federation_infos.push(FederationInfo {
                     federation_id: *federation_id,
@@ -285,6 +330,8 @@ pub async fn federation_info_all_federations(
                     balance_msat,
                     config,
                     last_backup_time,
+                    limits,
+                    circuit_breaker: self.circuit_breaker(federation_id).cloned(),
                 });
             }
         }
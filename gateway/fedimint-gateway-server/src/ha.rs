@@ -0,0 +1,444 @@
+//! Active/standby high-availability mode for `gatewayd`.
+//!
+//! Two gateway instances share one LND node. Only the instance holding the
+//! [`LeaderLease`] constructs its federation clients, intercepts HTLCs and
+//! signs. The lease lives in a [`SharedLease`] file both instances can access
+//! and which arbitrates between them even if they cannot reach each other.
+//! The epoch of the lease acts as a fencing token: a lease can only be renewed
+//! by the instance that acquired the current epoch.
+//!
+//! The standby continuously replicates the active's database through the
+//! [`HA_REPLICATE_ENDPOINT`] and acquires the lease once the active stops
+//! renewing it, even if it never managed to replicate. After the takeover it
+//! picks up the final changes of its peer if the peer is still reachable.
+//! The active steps down by shutting down if it finds its lease superseded or
+//! if it cannot renew it before it expires. Since the standby only acquires an
+//! expired lease after an additional grace period, the two instances are
+//! never active at the same time. A newly promoted instance re-announces
+//! itself to all federations as part of the regular startup.
+
+use std::fs;
+use std::io::Write as _;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::{Context, anyhow};
+use fedimint_connectors::ConnectorRegistry;
+use fedimint_core::crit;
+use fedimint_core::db::Database;
+use fedimint_core::runtime::timeout;
+use fedimint_core::task::{TaskGroup, block_in_place, sleep};
+use fedimint_core::time::now;
+use fedimint_core::util::{FmtCompactAnyhow, SafeUrl};
+use fedimint_gateway_common::{
+    HA_REPLICATE_ENDPOINT, HaRole, HaStatus, LeaderLease, ReplicatePayload, ReplicateResponse,
+};
+use fedimint_gateway_server_db::{ReplicationLog, apply_replicate_response};
+use fedimint_ln_common::Method;
+use fedimint_ln_common::client::GatewayApi;
+use fedimint_logging::LOG_GATEWAY;
+use fs2::FileExt;
+use thiserror::Error;
+use tracing::{info, warn};
+
+/// High-availability parameters supplied on the command line
+#[derive(Debug, Clone)]
+pub struct HaConfig {
+    pub peer_url: SafeUrl,
+    pub peer_password: String,
+    pub instance_id: String,
+    pub prefer_active: bool,
+    pub lease_ttl: Duration,
+    pub lease_file: PathBuf,
+}
+
+/// Reasons the active instance failed to renew its lease
+#[derive(Debug, Error)]
+pub enum RenewLeaseError {
+    #[error("The lease was superseded by {0:?}")]
+    Superseded(Option<LeaderLease>),
+    #[error("The lease already expired")]
+    Expired,
+    #[error("Failed to access the shared lease: {0}")]
+    Io(anyhow::Error),
+}
+
+/// Leader lease stored in a file on storage shared by both instances of the
+/// pair, usually next to the LND node they share. All reads and writes happen
+/// under an exclusive lock of a sibling lock file, so acquiring and renewing
+/// the lease are atomic compare-and-swap operations.
+#[derive(Debug, Clone)]
+pub struct SharedLease {
+    path: PathBuf,
+}
+
+impl SharedLease {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    fn lock_path(&self) -> PathBuf {
+        self.path.with_extension("lock")
+    }
+
+    /// Runs `f` on the current lease while holding the lock and stores the
+    /// lease it returns, if any
+    fn update<T>(
+        &self,
+        f: impl FnOnce(Option<LeaderLease>) -> (Option<LeaderLease>, T),
+    ) -> anyhow::Result<T> {
+        let lock_file = fs::OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(self.lock_path())
+            .context("Failed to open the lease lock file")?;
+
+        lock_file
+            .lock_exclusive()
+            .context("Failed to lock the lease lock file")?;
+
+        let (lease, result) = f(read_lease(&self.path)?);
+
+        if let Some(lease) = lease {
+            write_lease(&self.path, &lease)?;
+        }
+
+        FileExt::unlock(&lock_file).context("Failed to unlock the lease lock file")?;
+
+        Ok(result)
+    }
+
+    pub async fn load(&self) -> anyhow::Result<Option<LeaderLease>> {
+        block_in_place(|| self.update(|lease| (None, lease)))
+    }
+
+    /// Acquires the lease unless another instance holds it and it did not
+    /// expire more than `grace` ago, which accounts for clock skew between
+    /// the instances. If the lease was never acquired before, it is only
+    /// taken if `initial` is set.
+    pub async fn try_acquire(
+        &self,
+        instance_id: &str,
+        ttl: Duration,
+        grace: Duration,
+        initial: bool,
+    ) -> anyhow::Result<Option<LeaderLease>> {
+        block_in_place(|| {
+            self.update(|current| {
+                let now = now();
+
+                let available = match &current {
+                    None => initial,
+                    Some(lease) => lease.instance_id == instance_id || !lease.is_valid(now - grace),
+                };
+
+                if !available {
+                    return (None, None);
+                }
+
+                let lease = LeaderLease {
+                    instance_id: instance_id.to_string(),
+                    epoch: current.map_or(0, |lease| lease.epoch) + 1,
+                    expires_at: now + ttl,
+                };
+
+                (Some(lease.clone()), Some(lease))
+            })
+        })
+    }
+
+    /// Extends the lease `held` by `ttl` if it is still the current one and
+    /// did not expire yet
+    pub async fn renew(
+        &self,
+        held: &LeaderLease,
+        ttl: Duration,
+    ) -> Result<LeaderLease, RenewLeaseError> {
+        block_in_place(|| {
+            self.update(|current| {
+                let now = now();
+
+                if current.as_ref() != Some(held) {
+                    return (None, Err(RenewLeaseError::Superseded(current)));
+                }
+
+                if !held.is_valid(now) {
+                    return (None, Err(RenewLeaseError::Expired));
+                }
+
+                let lease = LeaderLease {
+                    expires_at: now + ttl,
+                    ..held.clone()
+                };
+
+                (Some(lease.clone()), Ok(lease))
+            })
+        })
+        .map_err(RenewLeaseError::Io)?
+    }
+}
+
+fn read_lease(path: &Path) -> anyhow::Result<Option<LeaderLease>> {
+    match fs::read(path) {
+        Ok(bytes) => Ok(Some(
+            serde_json::from_slice(&bytes).context("Failed to parse the lease file")?,
+        )),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(anyhow!(err).context("Failed to read the lease file")),
+    }
+}
+
+fn write_lease(path: &Path, lease: &LeaderLease) -> anyhow::Result<()> {
+    let tmp_path = path.with_extension("tmp");
+
+    let mut file = fs::File::create(&tmp_path).context("Failed to create the lease file")?;
+    file.write_all(&serde_json::to_vec(lease)?)?;
+    file.sync_all()?;
+
+    fs::rename(&tmp_path, path).context("Failed to replace the lease file")?;
+
+    Ok(())
+}
+
+#[derive(Debug)]
+pub struct HighAvailability {
+    config: HaConfig,
+    log: Arc<ReplicationLog>,
+    peer_api: GatewayApi,
+    shared_lease: SharedLease,
+    /// The lease this instance holds while it is active
+    held_lease: Mutex<Option<LeaderLease>>,
+    /// Set once this instance gave up leadership
+    stepped_down: AtomicBool,
+}
+
+impl HighAvailability {
+    pub fn new(config: HaConfig, log: Arc<ReplicationLog>, connectors: ConnectorRegistry) -> Self {
+        let peer_api = GatewayApi::new(Some(config.peer_password.clone()), connectors);
+        let shared_lease = SharedLease::new(config.lease_file.clone());
+
+        Self {
+            config,
+            log,
+            peer_api,
+            shared_lease,
+            held_lease: Mutex::new(None),
+            stepped_down: AtomicBool::new(false),
+        }
+    }
+
+    pub fn has_stepped_down(&self) -> bool {
+        self.stepped_down.load(Ordering::SeqCst)
+    }
+
+    fn renew_interval(&self) -> Duration {
+        self.config.lease_ttl / 3
+    }
+
+    fn held_lease(&self) -> Option<LeaderLease> {
+        self.held_lease.lock().expect("Locking can't fail").clone()
+    }
+
+    fn set_held_lease(&self, lease: LeaderLease) {
+        *self.held_lease.lock().expect("Locking can't fail") = Some(lease);
+    }
+
+    /// Returns the HA status of this instance
+    pub async fn status(&self) -> HaStatus {
+        let role = match self.held_lease() {
+            Some(lease) if lease.is_valid(now()) && !self.has_stepped_down() => HaRole::Active,
+            _ => HaRole::Standby,
+        };
+
+        let lease = match self.shared_lease.load().await {
+            Ok(lease) => lease,
+            Err(err) => {
+                warn!(target: LOG_GATEWAY, err = %err.fmt_compact_anyhow(), "Failed to read the shared HA lease");
+                self.held_lease()
+            }
+        };
+
+        HaStatus {
+            instance_id: self.config.instance_id.clone(),
+            role,
+            lease,
+        }
+    }
+
+    /// Answers a replication request of the standby
+    pub async fn replicate(&self, db: &Database, payload: &ReplicatePayload) -> ReplicateResponse {
+        self.log.replicate(db, payload).await
+    }
+
+    /// Blocks until this instance holds the leader lease. Until then the local
+    /// database is kept in sync with the active peer.
+    pub async fn wait_for_leadership(&self, db: &Database) -> anyhow::Result<()> {
+        let mut position: Option<(u64, u64)> = None;
+        let mut logged_standby = false;
+
+        loop {
+            // The preferred instance only takes the lease right away if its peer
+            // does not hold it, otherwise it starts as standby like the other one.
+            // The other instance takes over once the lease of its peer expired,
+            // even if it could not replicate the database so far, since the
+            // peer is most likely down.
+            let lease = self
+                .shared_lease
+                .try_acquire(
+                    &self.config.instance_id,
+                    self.config.lease_ttl,
+                    self.renew_interval(),
+                    self.config.prefer_active,
+                )
+                .await;
+
+            match lease {
+                Ok(Some(lease)) => {
+                    info!(
+                        target: LOG_GATEWAY,
+                        epoch = lease.epoch,
+                        instance_id = %lease.instance_id,
+                        "Acquired HA leader lease"
+                    );
+                    self.set_held_lease(lease);
+
+                    // The peer stepped down before its lease expired, so it
+                    // stopped writing and we can safely pick up its last
+                    // changes if it is still reachable
+                    self.resync_from_peer(db, position).await;
+
+                    // Whatever was recorded while following the peer is
+                    // meaningless to our own standby
+                    self.log.reset().await;
+
+                    return Ok(());
+                }
+                Ok(None) => {}
+                Err(err) => {
+                    warn!(target: LOG_GATEWAY, err = %err.fmt_compact_anyhow(), "Failed to acquire the shared HA lease");
+                }
+            }
+
+            if !logged_standby {
+                info!(
+                    target: LOG_GATEWAY,
+                    peer = %self.config.peer_url,
+                    "Running as HA standby, replicating database from peer"
+                );
+                logged_standby = true;
+            }
+
+            match self.replicate_from_peer(db, position).await {
+                Ok(new_position) => position = Some(new_position),
+                Err(err) => {
+                    warn!(target: LOG_GATEWAY, err = %err.fmt_compact_anyhow(), "Failed to replicate from HA peer");
+                }
+            }
+
+            sleep(self.renew_interval()).await;
+        }
+    }
+
+    /// Replicates the final state of the peer that just stepped down, giving
+    /// up after one renewal interval such that an unreachable peer does not
+    /// delay the takeover
+    async fn resync_from_peer(&self, db: &Database, position: Option<(u64, u64)>) {
+        let resync = timeout(
+            self.renew_interval(),
+            self.replicate_from_peer(db, position),
+        )
+        .await;
+
+        match resync {
+            Ok(Ok(_)) => {
+                info!(target: LOG_GATEWAY, "Resynced database from former HA leader");
+            }
+            Ok(Err(err)) => {
+                warn!(target: LOG_GATEWAY, err = %err.fmt_compact_anyhow(), "Failed to resync database from former HA leader");
+            }
+            Err(_) => {
+                warn!(target: LOG_GATEWAY, "Timed out resyncing database from former HA leader");
+            }
+        }
+    }
+
+    async fn replicate_from_peer(
+        &self,
+        db: &Database,
+        position: Option<(u64, u64)>,
+    ) -> anyhow::Result<(u64, u64)> {
+        let payload = ReplicatePayload {
+            log_id: position.map(|(log_id, _)| log_id),
+            seq: position.map_or(0, |(_, seq)| seq),
+        };
+        let payload_seq = payload.seq;
+
+        let response = self
+            .peer_api
+            .request::<ReplicatePayload, ReplicateResponse>(
+                &self.config.peer_url,
+                Method::POST,
+                HA_REPLICATE_ENDPOINT,
+                Some(payload),
+            )
+            .await
+            .context("Failed to fetch replication data from HA peer")?;
+
+        if let ReplicateResponse::Snapshot { entries, .. } = &response {
+            info!(target: LOG_GATEWAY, entries = entries.len(), "Applying database snapshot from HA peer");
+        }
+
+        apply_replicate_response(db, payload_seq, response).await
+    }
+
+    /// Renews the leader lease once, returning false if this instance has to
+    /// step down because its lease was superseded or is about to expire
+    /// without having been renewed.
+    pub async fn renew_lease(&self) -> bool {
+        let Some(held) = self.held_lease() else {
+            crit!(target: LOG_GATEWAY, "HA instance is running without a leader lease");
+            return false;
+        };
+
+        match self.shared_lease.renew(&held, self.config.lease_ttl).await {
+            Ok(lease) => {
+                self.set_held_lease(lease);
+                true
+            }
+            Err(RenewLeaseError::Io(err)) if now() + self.renew_interval() < held.expires_at => {
+                warn!(target: LOG_GATEWAY, err = %err.fmt_compact_anyhow(), "Failed to renew HA leader lease, retrying");
+                true
+            }
+            Err(err) => {
+                crit!(
+                    target: LOG_GATEWAY,
+                    epoch = held.epoch,
+                    err = %err,
+                    "Lost HA leader lease, stepping down"
+                );
+                self.stepped_down.store(true, Ordering::SeqCst);
+                false
+            }
+        }
+    }
+
+    /// Periodically renews the leader lease while this instance is active and
+    /// steps down by shutting down the gateway once it lost the lease.
+    pub fn spawn_lease_renewal(self: &Arc<Self>, task_group: &TaskGroup) {
+        let ha = self.clone();
+        let tg = task_group.clone();
+        task_group.spawn_cancellable("renew HA leader lease", async move {
+            loop {
+                sleep(ha.renew_interval()).await;
+
+                if !ha.renew_lease().await {
+                    tg.shutdown();
+                    return;
+                }
+            }
+        });
+    }
+}
@@ -0,0 +1,32 @@
+//! Hold invoices for LNv2 clients.
+//!
+//! The gateway holds the incoming payment to a hold invoice without funding an
+//! [`IncomingContract`](fedimint_lnv2_common::contracts::IncomingContract),
+//! such that the preimage stays with the recipient. Once the recipient submits
+//! the contract the payment is relayed exactly like a regular LNv2 receive.
+//! If the recipient cancels the invoice or the hold expires, the HTLC is
+//! cancelled and the payer is refunded.
+
+use std::time::Duration;
+
+use fedimint_gateway_server_db::HoldInvoiceState;
+use fedimint_lnv2_common::gateway_api::HoldInvoiceStatus;
+
+/// How often held payments are checked for their expiration
+pub const HOLD_INVOICE_EXPIRY_POLL_INTERVAL: Duration = Duration::from_secs(10);
+
+/// The maximum time a payment may be held. The lightning node fails the HTLC
+/// back on its own once its timelock is about to expire, which for the default
+/// final CLTV delta of the lightning nodes we support is a few hours.
+pub const HOLD_EXPIRY_LIMIT: Duration = Duration::from_secs(60 * 60);
+
+pub fn hold_invoice_status(state: &HoldInvoiceState) -> HoldInvoiceStatus {
+    match state {
+        HoldInvoiceState::Open => HoldInvoiceStatus::Open,
+        HoldInvoiceState::Held { expiration, .. } => HoldInvoiceStatus::Held {
+            expiration: *expiration,
+        },
+        HoldInvoiceState::Settled => HoldInvoiceStatus::Settled,
+        HoldInvoiceState::Cancelled => HoldInvoiceStatus::Cancelled,
+    }
+}
@@ -14,12 +14,16 @@
 #![allow(clippy::large_futures)]
 #![allow(clippy::struct_field_names)]
 
+mod bolt12;
+mod circuit_breaker;
 pub mod client;
 pub mod config;
 pub mod envs;
 mod error;
 mod events;
 mod federation_manager;
+pub mod ha;
+mod hold;
 mod iroh_server;
 mod metrics;
 pub mod rpc_server;
@@ -51,6 +55,7 @@
 use fedimint_client::module_init::ClientModuleInitRegistry;
 use fedimint_client::secret::RootSecretStrategy;
 use fedimint_client::{Client, ClientHandleArc};
+use fedimint_connectors::ConnectorRegistry;
 use fedimint_core::base32::{self, FEDIMINT_PREFIX};
 use fedimint_core::config::FederationId;
 use fedimint_core::core::OperationId;
@@ -60,9 +65,9 @@
 use fedimint_core::module::CommonModuleInit;
 use fedimint_core::module::registry::ModuleDecoderRegistry;
 use fedimint_core::rustls::install_crypto_provider;
-use fedimint_core::secp256k1::PublicKey;
 use fedimint_core::secp256k1::schnorr::Signature;
-use fedimint_core::task::{TaskGroup, TaskHandle, TaskShutdownToken, sleep};
+use fedimint_core::secp256k1::{Message, PublicKey};
+use fedimint_core::task::{TaskGroup, TaskHandle, TaskShutdownToken, sleep, timeout};
 use fedimint_core::time::duration_since_epoch;
 use fedimint_core::util::backoff_util::fibonacci_max_one_hour;
 use fedimint_core::util::{FmtCompact, FmtCompactAnyhow, SafeUrl, Spanned, retry};
@@ -70,22 +75,30 @@
     Amount, BitcoinAmountOrAll, PeerId, TieredCounts, crit, fedimint_build_code_version_env,
     get_network_for_address,
 };
-use fedimint_eventlog::{DBTransactionEventLogExt, EventLogId, StructuredPaymentEvents};
+use fedimint_eventlog::{
+    DBTransactionEventLogExt, Event, EventLogId, StructuredPaymentEvents, filter_events_by_kind,
+    join_events,
+};
 use fedimint_gateway_common::{
     BackupPayload, ChainSource, CloseChannelsWithPeerRequest, CloseChannelsWithPeerResponse,
     ConnectFedPayload, ConnectorType, CreateInvoiceForOperatorPayload, CreateOfferPayload,
     CreateOfferResponse, DepositAddressPayload, DepositAddressRecheckPayload,
-    FederationBalanceInfo, FederationConfig, FederationInfo, GatewayBalances, GatewayFedConfig,
-    GatewayInfo, GetInvoiceRequest, GetInvoiceResponse, LeaveFedPayload, LightningInfo,
-    LightningMode, ListTransactionsPayload, ListTransactionsResponse, MnemonicResponse,
-    OpenChannelRequest, PayInvoiceForOperatorPayload, PayOfferPayload, PayOfferResponse,
-    PaymentLogPayload, PaymentLogResponse, PaymentStats, PaymentSummaryPayload,
-    PaymentSummaryResponse, PeginFromOnchainPayload, ReceiveEcashPayload, ReceiveEcashResponse,
-    RegisteredProtocol, SendOnchainRequest, SetFeesPayload, SetMnemonicPayload, SpendEcashPayload,
-    SpendEcashResponse, V1_API_ENDPOINT, WithdrawPayload, WithdrawPreviewPayload,
-    WithdrawPreviewResponse, WithdrawResponse, WithdrawToOnchainPayload,
+    FailedBolt12OfferPayment, FederationBalanceInfo, FederationConfig, FederationInfo,
+    FederationLimits, GatewayBalances, GatewayFedConfig, GatewayInfo, GetInvoiceRequest,
+    GetInvoiceResponse, HaStatus, LeaveFedPayload, LightningInfo, LightningMode,
+    ListTransactionsPayload, ListTransactionsResponse, MnemonicResponse, OpenChannelRequest,
+    PayInvoiceForOperatorPayload, PayOfferPayload, PayOfferResponse, PaymentLogPayload,
+    PaymentLogResponse, PaymentStats, PaymentSummaryPayload, PaymentSummaryResponse,
+    PeginFromOnchainPayload, ReceiveEcashPayload, ReceiveEcashResponse, RegisteredProtocol,
+    ReplicatePayload, ReplicateResponse, SendOnchainRequest, SetFederationLimitsPayload,
+    SetFeesPayload, SetMnemonicPayload, SpendEcashPayload, SpendEcashResponse, V1_API_ENDPOINT,
+    WithdrawPayload, WithdrawPreviewPayload, WithdrawPreviewResponse, WithdrawResponse,
+    WithdrawToOnchainPayload,
+};
+use fedimint_gateway_server_db::{
+    Bolt12Offer, Bolt12OfferPayment, Bolt12OfferPaymentState, GatewayDbtxNcExt as _, HoldInvoice,
+    HoldInvoiceState, ReplicatedDatabase, ReplicationLog, get_gatewayd_database_migrations,
 };
-use fedimint_gateway_server_db::{GatewayDbtxNcExt as _, get_gatewayd_database_migrations};
 pub use fedimint_gateway_ui::IAdminGateway;
 use fedimint_gw_client::events::compute_lnv1_stats;
 use fedimint_gw_client::pay::{OutgoingPaymentError, OutgoingPaymentErrorType};
@@ -93,15 +106,18 @@
     GatewayClientModule, GatewayExtPayStates, GatewayExtReceiveStates, IGatewayClientV1,
     SwapParameters,
 };
-use fedimint_gwv2_client::events::compute_lnv2_stats;
+use fedimint_gwv2_client::events::{
+    OutgoingPaymentFailed, OutgoingPaymentStarted, compute_lnv2_stats,
+};
 use fedimint_gwv2_client::{
     EXPIRATION_DELTA_MINIMUM_V2, FinalReceiveState, GatewayClientModuleV2, IGatewayClientV2,
+    PaymentDirection,
 };
 use fedimint_lightning::lnd::GatewayLndClient;
 use fedimint_lightning::{
     CreateInvoiceRequest, ILnRpcClient, InterceptPaymentRequest, InterceptPaymentResponse,
     InvoiceDescription, LightningContext, LightningRpcError, LnRpcTracked, PayInvoiceResponse,
-    PaymentAction, RouteHtlcStream, ldk,
+    PaymentAction, RouteHtlcStream, RouteProbe, ldk,
 };
 use fedimint_ln_client::pay::PaymentData;
 use fedimint_ln_common::LightningCommonInit;
@@ -109,11 +125,13 @@
 use fedimint_ln_common::contracts::outgoing::OutgoingContractAccount;
 use fedimint_ln_common::contracts::{IdentifiableContract, Preimage};
 use fedimint_lnurl::VerifyResponse;
-use fedimint_lnv2_common::Bolt11InvoiceDescription;
 use fedimint_lnv2_common::contracts::{IncomingContract, PaymentImage};
 use fedimint_lnv2_common::gateway_api::{
-    CreateBolt11InvoicePayload, PaymentFee, RoutingInfo, SendPaymentPayload,
+    CancelHoldInvoicePayload, CreateBolt11InvoicePayload, CreateBolt12OfferPayload,
+    CreateHoldInvoicePayload, HoldInvoiceStatus, HoldInvoiceStatusPayload, PaymentEstimate,
+    PaymentFee, ProbeInvoicePayload, RoutingInfo, SendPaymentPayload, SettleHoldInvoicePayload,
 };
+use fedimint_lnv2_common::{Bolt11InvoiceDescription, MINIMUM_INCOMING_CONTRACT_AMOUNT};
 use fedimint_logging::LOG_GATEWAY;
 use fedimint_mint_client::{MintClientInit, MintClientModule, OOBNotes};
 use fedimint_mintv2_client::{
@@ -123,13 +141,19 @@
 use futures::stream::StreamExt;
 use lightning_invoice::{Bolt11Invoice, RoutingFees};
 use rand::rngs::OsRng;
-use tokio::sync::RwLock;
+use tokio::sync::{Mutex, RwLock, Semaphore};
 use tracing::{debug, info, info_span, warn};
 
+use crate::bolt12::{BOLT12_OFFER_PAYMENT_POLL_INTERVAL, MAX_OFFER_PAYMENT_REFUNDS};
+use crate::circuit_breaker::{
+    CIRCUIT_BREAKER_CHECK_INTERVAL, FederationHealth, check_federation_health,
+};
 use crate::envs::FM_GATEWAY_MNEMONIC_ENV;
 use crate::error::{AdminGatewayError, LNv1Error, LNv2Error, PublicGatewayError};
 use crate::events::get_events_for_duration;
-use crate::rpc_server::run_webserver;
+use crate::ha::HighAvailability;
+use crate::hold::{HOLD_EXPIRY_LIMIT, HOLD_INVOICE_EXPIRY_POLL_INTERVAL};
+use crate::rpc_server::{run_standby_webserver, run_webserver};
 use crate::types::PrettyInterceptPaymentRequest;
 
 /// How long a gateway announcement stays valid
@@ -139,6 +163,15 @@
 /// invoice creation.
 const DEFAULT_NUM_ROUTE_HINTS: u32 = 1;
 
+/// Maximum number of route probes that unauthenticated clients may have the
+/// gateway's lightning node perform at the same time
+const MAX_CONCURRENT_ROUTE_PROBES: usize = 4;
+
+/// Maximum time the gateway waits for its lightning node to probe a route
+/// before it answers with an estimate based on its default send fee. This is
+/// shorter than the time clients wait for the estimate.
+const ROUTE_PROBE_TIMEOUT: Duration = Duration::from_secs(4);
+
 /// Default Bitcoin network for testing purposes.
 pub const DEFAULT_NETWORK: Network = Network::Regtest;
 
@@ -283,11 +316,13 @@ pub async fn new_with_builder(
                 iroh_relays,
                 skip_setup: true,
                 metrics_listen,
+                ha: None,
             },
             gateway_db,
             client_builder,
             gateway_state,
             chain_source,
+            None,
         )
         .await
     }
@@ -364,6 +399,17 @@ pub struct Gateway {
     /// A map of the network protocols the gateway supports to the data needed
     /// for registering with a federation.
     registrations: BTreeMap<RegisteredProtocol, Registration>,
+
+    /// Leader lease and database replication state when running as part of
+    /// an active/standby pair.
+    ha: Option<Arc<HighAvailability>>,
+
+    /// Limits the number of concurrent route probes requested by clients.
+    route_probes: Arc<Semaphore>,
+
+    /// What the circuit breaker observed about every connected federation in
+    /// its previous health checks.
+    federation_health: Arc<Mutex<BTreeMap<FederationId, FederationHealth>>>,
 }
 
 impl std::fmt::Debug for Gateway {
@@ -549,22 +595,74 @@ pub async fn new_with_default_modules(
         let gateway_parameters = opts.to_gateway_parameters()?;
         let decoders = ModuleDecoderRegistry::default();
 
+        // In high-availability mode all writes are recorded so they can be
+        // streamed to the standby
+        let replication_log = gateway_parameters
+            .ha
+            .as_ref()
+            .map(|_| Arc::new(ReplicationLog::new()));
+
         let db_path = opts.data_dir.join(DB_FILE);
-        let gateway_db = match opts.db_backend {
-            DatabaseBackend::RocksDb => {
+        let gateway_db = match (opts.db_backend, replication_log.clone()) {
+            (DatabaseBackend::RocksDb, None) => {
                 debug!(target: LOG_GATEWAY, "Using RocksDB database backend");
                 Database::new(
                     fedimint_rocksdb::RocksDb::build(db_path).open().await?,
                     decoders,
                 )
             }
-            DatabaseBackend::CursedRedb => {
+            (DatabaseBackend::RocksDb, Some(log)) => {
+                debug!(target: LOG_GATEWAY, "Using replicated RocksDB database backend");
+                Database::new(
+                    ReplicatedDatabase::new(
+                        fedimint_rocksdb::RocksDb::build(db_path).open().await?,
+                        log,
+                    ),
+                    decoders,
+                )
+            }
+            (DatabaseBackend::CursedRedb, None) => {
                 debug!(target: LOG_GATEWAY, "Using CursedRedb database backend");
                 Database::new(
                     fedimint_cursed_redb::MemAndRedb::new(db_path).await?,
                     decoders,
                 )
             }
+            (DatabaseBackend::CursedRedb, Some(log)) => {
+                debug!(target: LOG_GATEWAY, "Using replicated CursedRedb database backend");
+                Database::new(
+                    ReplicatedDatabase::new(
+                        fedimint_cursed_redb::MemAndRedb::new(db_path).await?,
+                        log,
+                    ),
+                    decoders,
+                )
+            }
+        };
+
+        let ha = match (gateway_parameters.ha.clone(), replication_log) {
+            (Some(ha_config), Some(log)) => {
+                ensure!(
+                    matches!(opts.mode, LightningMode::Lnd { .. }),
+                    "High-availability mode requires both gateways to share an LND node"
+                );
+                let connectors = ConnectorRegistry::build_from_client_env()?.bind().await?;
+                let ha = Arc::new(HighAvailability::new(ha_config, log, connectors));
+
+                // The standby does not construct the gateway until it takes over, so
+                // all keys and federation clients are loaded from the replicated
+                // database. Meanwhile it only serves its HA status.
+                let standby_webserver = run_standby_webserver(
+                    gateway_parameters.listen,
+                    gateway_parameters.bcrypt_password_hash.clone(),
+                    ha.clone(),
+                )
+                .await?;
+                ha.wait_for_leadership(&gateway_db).await?;
+                standby_webserver.shutdown_join_all(None).await?;
+                Some(ha)
+            }
+            _ => None,
         };
 
         // Apply database migrations before using the database to ensure old database
@@ -617,8 +715,13 @@ pub async fn new_with_default_modules(
         registry.attach(WalletClientInit::new(dyn_bitcoin_rpc));
         registry.attach(fedimint_walletv2_client::WalletClientInit);
 
-        let client_builder =
-            GatewayClientBuilder::new(opts.data_dir.clone(), registry, opts.db_backend).await?;
+        let client_builder = GatewayClientBuilder::new(
+            opts.data_dir.clone(),
+            registry,
+            opts.db_backend,
+            ha.is_some(),
+        )
+        .await?;
 
         let gateway_state = if Self::load_mnemonic(&gateway_db).await.is_some() {
             GatewayState::Disconnected
@@ -662,6 +765,7 @@ pub async fn new_with_default_modules(
             client_builder,
             gateway_state,
             chain_source,
+            ha,
         )
         .await
     }
@@ -675,6 +779,7 @@ async fn new(
         client_builder: GatewayClientBuilder,
         gateway_state: GatewayState,
         chain_source: ChainSource,
+        ha: Option<Arc<HighAvailability>>,
     ) -> anyhow::Result<Gateway> {
         let num_route_hints = gateway_parameters.num_route_hints;
         let network = gateway_parameters.network;
@@ -722,6 +827,9 @@ async fn new(
             iroh_relays: gateway_parameters.iroh_relays,
             iroh_listen: gateway_parameters.iroh_listen,
             registrations,
+            ha,
+            route_probes: Arc::new(Semaphore::new(MAX_CONCURRENT_ROUTE_PROBES)),
+            federation_health: Arc::new(Mutex::new(BTreeMap::new())),
         })
     }
 
@@ -773,7 +881,13 @@ pub async fn run(
         mnemonic_receiver: tokio::sync::broadcast::Receiver<()>,
     ) -> anyhow::Result<TaskShutdownToken> {
         install_crypto_provider().await;
+        if let Some(ha) = &self.ha {
+            ha.spawn_lease_renewal(&self.task_group);
+        }
         self.register_clients_timer();
+        self.spawn_circuit_breaker_monitor();
+        self.spawn_bolt12_offer_relay();
+        self.spawn_hold_invoice_expiry();
         self.load_clients().await?;
         self.start_gateway(runtime, mnemonic_receiver.resubscribe());
         self.spawn_backup_task();
@@ -911,6 +1025,28 @@ fn start_gateway(
         );
     }
 
+    /// Intercepts HTLCs from the given lightning client without connecting to
+    /// the configured lightning node. Used by tests that run the gateway
+    /// against a mock lightning node instead of calling [`Gateway::run`].
+    pub async fn route_htlcs_from(
+        &self,
+        lnrpc: Box<dyn ILnRpcClient>,
+    ) -> std::result::Result<(), LightningRpcError> {
+        let (stream, ln_client) = lnrpc.route_htlcs(&self.task_group).await?;
+
+        let gateway = self.clone();
+        self.task_group.spawn(
+            "Route intercepted lightning payments",
+            |handle| async move {
+                gateway
+                    .route_lightning_payments(&handle, stream, ln_client)
+                    .await;
+            },
+        );
+
+        Ok(())
+    }
+
     /// Handles a stream of incoming payments from the lightning node after
     /// ensuring the gateway is properly configured. Awaits until the stream
     /// is closed, then returns with the appropriate action to take.
@@ -1117,6 +1253,16 @@ async fn try_handle_lightning_payment_lnv2(
         htlc_request: &InterceptPaymentRequest,
         lightning_context: &LightningContext,
     ) -> Result<()> {
+        if let Some(hold_invoice) = self
+            .gateway_db
+            .begin_transaction_nc()
+            .await
+            .load_hold_invoice(htlc_request.payment_hash)
+            .await
+        {
+            return self.hold_incoming_htlc_v2(htlc_request, hold_invoice).await;
+        }
+
         // If `payment_hash` has been registered as a LNv2 payment, we try to complete
         // the payment by getting the preimage from the federation
         // using the LNv2 protocol. If the `payment_hash` is not registered,
@@ -1607,6 +1753,26 @@ async fn register_federations(
         }
     }
 
+    /// Returns the high-availability status of this instance to its peer.
+    pub async fn handle_ha_status_msg(&self) -> AdminResult<HaStatus> {
+        let ha = self.ha.as_ref().ok_or_else(|| {
+            AdminGatewayError::Unexpected(anyhow!("High-availability mode is not enabled"))
+        })?;
+        Ok(ha.status().await)
+    }
+
+    /// Streams database writes to the standby instance of the
+    /// high-availability pair.
+    pub async fn handle_ha_replicate_msg(
+        &self,
+        payload: ReplicatePayload,
+    ) -> AdminResult<ReplicateResponse> {
+        let ha = self.ha.as_ref().ok_or_else(|| {
+            AdminGatewayError::Unexpected(anyhow!("High-availability mode is not enabled"))
+        })?;
+        Ok(ha.replicate(&self.gateway_db, &payload).await)
+    }
+
     /// Retrieves a `ClientHandleArc` from the Gateway's in memory structures
     /// that keep track of available clients, given a `federation_id`.
     pub async fn select_client(
@@ -1709,6 +1875,77 @@ fn register_clients_timer(&self) {
         }
     }
 
+    /// Periodically checks the health of all connected federations and pauses
+    /// routing for a federation while its guardians are unreachable, its
+    /// consensus is stalled or its audit does not add up.
+    fn spawn_circuit_breaker_monitor(&self) {
+        let gateway = self.clone();
+        self.task_group
+            .spawn_cancellable("circuit breaker monitor", async move {
+                loop {
+                    if let GatewayState::Running { .. } = gateway.get_state().await {
+                        gateway.check_circuit_breakers().await;
+                    }
+
+                    sleep(CIRCUIT_BREAKER_CHECK_INTERVAL).await;
+                }
+            });
+    }
+
+    /// Checks the health of all connected federations once and trips or
+    /// resets their circuit breakers accordingly.
+    pub async fn check_circuit_breakers(&self) {
+        let mut health = self.federation_health.lock().await;
+        let clients = self.federation_manager.read().await.clients();
+        for client in clients {
+            let federation_id = client.federation_id();
+            let reason =
+                check_federation_health(&client, health.entry(federation_id).or_default()).await;
+            self.federation_manager
+                .write()
+                .await
+                .set_circuit_breaker(federation_id, reason);
+        }
+    }
+
+    /// Spawns a task that cancels held payments to hold invoices whose
+    /// recipient neither settled nor cancelled them before the hold expired,
+    /// such that the payer is refunded before the HTLC times out.
+    fn spawn_hold_invoice_expiry(&self) {
+        let gateway = self.clone();
+        self.task_group
+            .spawn_cancellable("cancel expired hold invoices", async move {
+                loop {
+                    if let GatewayState::Running { .. } = gateway.get_state().await
+                        && let Err(err) = gateway.cancel_expired_hold_invoices().await
+                    {
+                        warn!(target: LOG_GATEWAY, err = %err.fmt_compact_anyhow(), "Failed to cancel expired hold invoices");
+                    }
+
+                    sleep(HOLD_INVOICE_EXPIRY_POLL_INTERVAL).await;
+                }
+            });
+    }
+
+    /// Spawns a task that relays payments to BOLT12 offers created on behalf of
+    /// LNv2 clients into incoming contracts.
+    fn spawn_bolt12_offer_relay(&self) {
+        let gateway = self.clone();
+        self.task_group
+            .spawn_cancellable("relay bolt12 offer payments", async move {
+                loop {
+                    if let GatewayState::Running { .. } = gateway.get_state().await
+                        && let Err(err) = gateway.relay_bolt12_offer_payments().await
+                    {
+                        // Not every lightning node supports BOLT12
+                        debug!(target: LOG_GATEWAY, err = %err.fmt_compact_anyhow(), "Failed to relay BOLT12 offer payments");
+                    }
+
+                    sleep(BOLT12_OFFER_PAYMENT_POLL_INTERVAL).await;
+                }
+            });
+    }
+
     /// Verifies that the federation has at least one lightning module (LNv1 or
     /// LNv2) and that the network matches the gateway's network.
     async fn check_federation_network(
@@ -1790,6 +2027,12 @@ pub async fn get_lightning_context(
     /// Iterates through all of the federations the gateway is registered with
     /// and requests to remove the registration record.
     pub async fn unannounce_from_all_federations(&self) {
+        // After stepping down the registrations belong to the new active
+        // instance, which shares our keys.
+        if self.ha.as_ref().is_some_and(|ha| ha.has_stepped_down()) {
+            return;
+        }
+
         if matches!(self.lightning_mode, LightningMode::Lnd { .. }) {
             for registration in self.registrations.values() {
                 self.federation_manager
@@ -2060,6 +2303,8 @@ async fn handle_connect_federation(
             }),
             config: federation_config.clone(),
             last_backup_time: None,
+            limits: FederationLimits::default(),
+            circuit_breaker: None,
         };
 
         Self::check_federation_network(&client, self.network).await?;
@@ -2195,6 +2440,35 @@ async fn handle_set_fees_msg(
         Ok(())
     }
 
+    /// Sets the spending limits of a connected federation.
+    async fn handle_set_federation_limits_msg(
+        &self,
+        SetFederationLimitsPayload {
+            federation_id,
+            limits,
+        }: SetFederationLimitsPayload,
+    ) -> AdminResult<()> {
+        if !self
+            .federation_manager
+            .read()
+            .await
+            .has_federation(federation_id)
+        {
+            return Err(FederationNotConnected {
+                federation_id_prefix: federation_id.to_prefix(),
+            }
+            .into());
+        }
+
+        let mut dbtx = self.gateway_db.begin_transaction().await;
+        dbtx.save_federation_limits(federation_id, &limits).await;
+        dbtx.commit_tx().await;
+
+        info!(target: LOG_GATEWAY, %federation_id, ?limits, "Updated federation spending limits");
+
+        Ok(())
+    }
+
     /// Handles an authenticated request for the gateway's mnemonic. This also
     /// returns a vector of federations that are not using the mnemonic
     /// backup strategy.
@@ -2215,7 +2489,10 @@ async fn handle_mnemonic_msg(&self) -> AdminResult<MnemonicResponse> {
             .keys()
             .copied()
             .collect::<BTreeSet<_>>();
-        let legacy_federations = self.client_builder.legacy_federations(all_federations);
+        let legacy_federations = self
+            .client_builder
+            .legacy_federations(&self.gateway_db, all_federations)
+            .await;
         let mnemonic_response = MnemonicResponse {
             mnemonic: words,
             legacy_federations,
@@ -2894,52 +3171,88 @@ async fn send_payment_v2(
             .map_err(PublicGatewayError::LNv2)
     }
 
-    /// For the LNv2 protocol, this will create an invoice by fetching it from
-    /// the connected Lightning node, then save the payment hash so that
-    /// incoming lightning payments can be matched as a receive attempt to a
-    /// specific federation.
-    async fn create_bolt11_invoice_v2(
-        &self,
-        payload: CreateBolt11InvoicePayload,
-    ) -> Result<Bolt11Invoice> {
-        if !payload.contract.verify() {
-            return Err(PublicGatewayError::LNv2(LNv2Error::IncomingPayment(
-                "The contract is invalid".to_string(),
-            )));
-        }
-
-        let payment_info = self.routing_info_v2(&payload.federation_id).await?.ok_or(
-            LNv2Error::IncomingPayment(format!(
+    /// Estimates the fee this gateway charges to pay an invoice via the LNv2
+    /// protocol by probing the route to the payee. The lightning fee estimate
+    /// is increased by half to leave the node some headroom when the payment
+    /// is eventually routed. If the node cannot estimate the lightning fee we
+    /// fall back to the default send fee.
+    async fn probe_invoice_v2(&self, payload: ProbeInvoicePayload) -> Result<PaymentEstimate> {
+        let routing_info = self.routing_info_v2(&payload.federation_id).await?.ok_or(
+            LNv2Error::OutgoingPayment(anyhow!(
                 "Federation {} does not exist",
                 payload.federation_id
             )),
         )?;
 
-        if payload.contract.commitment.refund_pk != payment_info.module_public_key {
-            return Err(PublicGatewayError::LNv2(LNv2Error::IncomingPayment(
-                "The incoming contract is keyed to another gateway".to_string(),
-            )));
+        let amount_msat =
+            payload
+                .invoice
+                .amount_milli_satoshis()
+                .ok_or(LNv2Error::OutgoingPayment(anyhow!(
+                    "Invoice is missing amount"
+                )))?;
+
+        // Payments to our own node are settled directly via a swap
+        if payload.invoice.get_payee_pub_key() == routing_info.lightning_public_key {
+            return Ok(PaymentEstimate {
+                lightning_fee: Some(Amount::ZERO),
+                send_fee: routing_info.send_fee_minimum,
+                success_likelihood: Some(100),
+            });
         }
 
-        let contract_amount = payment_info.receive_fee.subtract_from(payload.amount.msats);
+        // Probing sends payments over the lightning network, hence we do not allow
+        // unauthenticated clients to have our node probe arbitrary many routes.
+        let _permit = self
+            .route_probes
+            .try_acquire()
+            .map_err(|_| PublicGatewayError::RateLimited)?;
 
-        if contract_amount == Amount::ZERO {
-            return Err(PublicGatewayError::LNv2(LNv2Error::IncomingPayment(
-                "Zero amount incoming contracts are not supported".to_string(),
-            )));
-        }
+        let lnrpc = self.get_lightning_context().await?.lnrpc;
 
-        if contract_amount != payload.contract.commitment.amount {
-            return Err(PublicGatewayError::LNv2(LNv2Error::IncomingPayment(
-                "The contract amount does not pay the correct amount of fees".to_string(),
-            )));
-        }
+        let probe = match timeout(ROUTE_PROBE_TIMEOUT, lnrpc.probe_route(payload.invoice)).await {
+            Ok(probe) => probe?,
+            Err(_) => RouteProbe {
+                fee: None,
+                success_likelihood: None,
+            },
+        };
 
-        if payload.contract.commitment.expiration <= duration_since_epoch().as_secs() {
-            return Err(PublicGatewayError::LNv2(LNv2Error::IncomingPayment(
-                "The contract has already expired".to_string(),
-            )));
-        }
+        let send_fee = match probe.fee {
+            Some(lightning_fee) => {
+                let estimate = PaymentFee {
+                    base: routing_info.send_fee_minimum.fee(amount_msat)
+                        + lightning_fee
+                        + Amount::from_msats(lightning_fee.msats / 2),
+                    parts_per_million: 0,
+                };
+
+                if estimate.fee(amount_msat) < routing_info.send_fee_default.fee(amount_msat) {
+                    estimate
+                } else {
+                    routing_info.send_fee_default
+                }
+            }
+            None => routing_info.send_fee_default,
+        };
+
+        Ok(PaymentEstimate {
+            lightning_fee: probe.fee,
+            send_fee,
+            success_likelihood: probe.success_likelihood,
+        })
+    }
+
+    /// For the LNv2 protocol, this will create an invoice by fetching it from
+    /// the connected Lightning node, then save the payment hash so that
+    /// incoming lightning payments can be matched as a receive attempt to a
+    /// specific federation.
+    async fn create_bolt11_invoice_v2(
+        &self,
+        payload: CreateBolt11InvoicePayload,
+    ) -> Result<Bolt11Invoice> {
+        self.check_incoming_contract_v2(payload.federation_id, &payload.contract, payload.amount)
+            .await?;
 
         let payment_hash = match payload.contract.commitment.payment_image {
             PaymentImage::Hash(payment_hash) => payment_hash,
@@ -2984,85 +3297,846 @@ async fn create_bolt11_invoice_v2(
         Ok(invoice)
     }
 
-    /// Retrieves a BOLT11 invoice from the connected Lightning node with a
-    /// specific `payment_hash`.
-    pub async fn create_invoice_via_lnrpc_v2(
+    /// Checks that an incoming contract submitted by a client is valid, keyed
+    /// to this gateway and pays the correct fee for an invoice of `amount`.
+    async fn check_incoming_contract_v2(
         &self,
-        payment_hash: sha256::Hash,
+        federation_id: FederationId,
+        contract: &IncomingContract,
         amount: Amount,
-        description: Bolt11InvoiceDescription,
-        expiry_time: u32,
-    ) -> std::result::Result<Bolt11Invoice, LightningRpcError> {
-        let lnrpc = self.get_lightning_context().await?.lnrpc;
+    ) -> Result<()> {
+        if !contract.verify() {
+            return Err(PublicGatewayError::LNv2(LNv2Error::IncomingPayment(
+                "The contract is invalid".to_string(),
+            )));
+        }
 
-        let response = match description {
-            Bolt11InvoiceDescription::Direct(description) => {
-                lnrpc
-                    .create_invoice(CreateInvoiceRequest {
-                        payment_hash: Some(payment_hash),
-                        amount_msat: amount.msats,
-                        expiry_secs: expiry_time,
-                        description: Some(InvoiceDescription::Direct(description)),
-                    })
-                    .await?
-            }
-            Bolt11InvoiceDescription::Hash(hash) => {
-                lnrpc
-                    .create_invoice(CreateInvoiceRequest {
-                        payment_hash: Some(payment_hash),
-                        amount_msat: amount.msats,
-                        expiry_secs: expiry_time,
-                        description: Some(InvoiceDescription::Hash(hash)),
-                    })
-                    .await?
-            }
-        };
+        let payment_info =
+            self.routing_info_v2(&federation_id)
+                .await?
+                .ok_or(LNv2Error::IncomingPayment(format!(
+                    "Federation {federation_id} does not exist"
+                )))?;
 
-        Bolt11Invoice::from_str(&response.invoice).map_err(|e| {
-            LightningRpcError::FailedToGetInvoice {
-                failure_reason: e.to_string(),
-            }
-        })
-    }
+        if contract.commitment.refund_pk != payment_info.module_public_key {
+            return Err(PublicGatewayError::LNv2(LNv2Error::IncomingPayment(
+                "The incoming contract is keyed to another gateway".to_string(),
+            )));
+        }
 
-    pub async fn verify_bolt11_preimage_v2(
-        &self,
-        payment_hash: sha256::Hash,
-        wait: bool,
-    ) -> std::result::Result<VerifyResponse, String> {
-        let registered_contract = self
-            .gateway_db
-            .begin_transaction_nc()
-            .await
-            .load_registered_incoming_contract(PaymentImage::Hash(payment_hash))
-            .await
-            .ok_or("Unknown payment hash".to_string())?;
+        let contract_amount = payment_info.receive_fee.subtract_from(amount.msats);
 
-        let client = self
-            .select_client(registered_contract.federation_id)
-            .await
-            .map_err(|_| "Not connected to federation".to_string())?
-            .into_value();
+        if contract_amount == Amount::ZERO {
+            return Err(PublicGatewayError::LNv2(LNv2Error::IncomingPayment(
+                "Zero amount incoming contracts are not supported".to_string(),
+            )));
+        }
 
-        let operation_id = OperationId::from_encodable(&registered_contract.contract);
+        if contract_amount != contract.commitment.amount {
+            return Err(PublicGatewayError::LNv2(LNv2Error::IncomingPayment(
+                "The contract amount does not pay the correct amount of fees".to_string(),
+            )));
+        }
 
-        if !(wait || client.operation_exists(operation_id).await) {
-            return Ok(VerifyResponse {
-                settled: false,
-                preimage: None,
-            });
+        if contract.commitment.expiration <= duration_since_epoch().as_secs() {
+            return Err(PublicGatewayError::LNv2(LNv2Error::IncomingPayment(
+                "The contract has already expired".to_string(),
+            )));
         }
 
-        let state = client
-            .get_first_module::<GatewayClientModuleV2>()
-            .expect("Must have client module")
-            .await_receive(operation_id)
-            .await;
+        Ok(())
+    }
 
-        let preimage = match state {
-            FinalReceiveState::Success(preimage) => Ok(preimage),
-            FinalReceiveState::Failure => Err("Payment has failed".to_string()),
-            FinalReceiveState::Refunded => Err("Payment has been refunded".to_string()),
+    /// For the LNv2 protocol, this will create a hold invoice via the
+    /// connected Lightning node. Incoming payments to the invoice are held by
+    /// [`Gateway::hold_incoming_htlc_v2`] until the client either settles or
+    /// cancels the invoice.
+    pub async fn create_hold_invoice_v2(
+        &self,
+        payload: CreateHoldInvoicePayload,
+    ) -> Result<Bolt11Invoice> {
+        let payment_info = self.routing_info_v2(&payload.federation_id).await?.ok_or(
+            LNv2Error::IncomingPayment(format!(
+                "Federation {} does not exist",
+                payload.federation_id
+            )),
+        )?;
+
+        if u64::from(payload.hold_expiry_secs) > HOLD_EXPIRY_LIMIT.as_secs() {
+            return Err(PublicGatewayError::LNv2(LNv2Error::IncomingPayment(
+                format!(
+                    "The hold expiry exceeds the limit of {} seconds",
+                    HOLD_EXPIRY_LIMIT.as_secs()
+                ),
+            )));
+        }
+
+        if payment_info.receive_fee.subtract_from(payload.amount.msats) == Amount::ZERO {
+            return Err(PublicGatewayError::LNv2(LNv2Error::IncomingPayment(
+                "Zero amount incoming contracts are not supported".to_string(),
+            )));
+        }
+
+        let invoice = self
+            .create_invoice_via_lnrpc_v2(
+                payload.payment_hash,
+                payload.amount,
+                payload.description.clone(),
+                payload.expiry_secs,
+            )
+            .await?;
+
+        let mut dbtx = self.gateway_db.begin_transaction().await;
+
+        if dbtx.load_hold_invoice(payload.payment_hash).await.is_some()
+            || dbtx
+                .load_registered_incoming_contract(PaymentImage::Hash(payload.payment_hash))
+                .await
+                .is_some()
+        {
+            return Err(PublicGatewayError::LNv2(LNv2Error::IncomingPayment(
+                "PaymentHash is already registered".to_string(),
+            )));
+        }
+
+        dbtx.save_hold_invoice(
+            payload.payment_hash,
+            &HoldInvoice {
+                federation_id: payload.federation_id,
+                amount: payload.amount,
+                hold_expiry_secs: payload.hold_expiry_secs,
+                auth_pk: payload.auth_pk,
+                state: HoldInvoiceState::Open,
+            },
+        )
+        .await;
+
+        dbtx.commit_tx_result().await.map_err(|_| {
+            PublicGatewayError::LNv2(LNv2Error::IncomingPayment(
+                "Payment hash is already registered".to_string(),
+            ))
+        })?;
+
+        Ok(invoice)
+    }
+
+    /// Holds an incoming payment to a hold invoice until the client settles
+    /// or cancels the invoice or the hold expires.
+    async fn hold_incoming_htlc_v2(
+        &self,
+        htlc_request: &InterceptPaymentRequest,
+        hold_invoice: HoldInvoice,
+    ) -> Result<()> {
+        let client = self.select_client(hold_invoice.federation_id).await?;
+
+        let module = client
+            .value()
+            .get_first_module::<GatewayClientModuleV2>()
+            .expect("Must have client module");
+
+        match hold_invoice.state {
+            HoldInvoiceState::Open if htlc_request.amount_msat == hold_invoice.amount.msats => {
+                let expiration = duration_since_epoch()
+                    .as_secs()
+                    .saturating_add(u64::from(hold_invoice.hold_expiry_secs));
+
+                let mut dbtx = self.gateway_db.begin_transaction().await;
+
+                dbtx.save_hold_invoice(
+                    htlc_request.payment_hash,
+                    &HoldInvoice {
+                        state: HoldInvoiceState::Held {
+                            incoming_chan_id: htlc_request.incoming_chan_id,
+                            htlc_id: htlc_request.htlc_id,
+                            expiration,
+                        },
+                        ..hold_invoice
+                    },
+                )
+                .await;
+
+                dbtx.commit_tx_result()
+                    .await
+                    .map_err(PublicGatewayError::Unexpected)?;
+
+                module
+                    .hold_incoming_htlc(
+                        htlc_request.payment_hash,
+                        htlc_request.amount_msat,
+                        expiration,
+                    )
+                    .await;
+            }
+            // The lightning node delivers the payment again after a restart
+            HoldInvoiceState::Held {
+                incoming_chan_id,
+                htlc_id,
+                ..
+            } if incoming_chan_id == htlc_request.incoming_chan_id
+                && htlc_id == htlc_request.htlc_id => {}
+            HoldInvoiceState::Settled => {}
+            // Only a single payment is held per invoice, any other payment would
+            // be stuck until its HTLC times out
+            HoldInvoiceState::Held { .. } => {
+                module
+                    .cancel_held_htlc(
+                        htlc_request.payment_hash,
+                        htlc_request.incoming_chan_id,
+                        htlc_request.htlc_id,
+                        "The hold invoice is already paid".to_string(),
+                    )
+                    .await;
+            }
+            HoldInvoiceState::Open | HoldInvoiceState::Cancelled => {
+                module
+                    .cancel_held_htlc(
+                        htlc_request.payment_hash,
+                        htlc_request.incoming_chan_id,
+                        htlc_request.htlc_id,
+                        "The hold invoice is cancelled or the amount is incorrect".to_string(),
+                    )
+                    .await;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Releases the payment held for a hold invoice by funding the incoming
+    /// contract submitted by the client.
+    async fn settle_hold_invoice_v2(&self, payload: SettleHoldInvoicePayload) -> Result<()> {
+        let PaymentImage::Hash(payment_hash) = payload.contract.commitment.payment_image else {
+            return Err(PublicGatewayError::LNv2(LNv2Error::IncomingPayment(
+                "PaymentImage is not a payment hash".to_string(),
+            )));
+        };
+
+        let mut dbtx = self.gateway_db.begin_transaction().await;
+
+        let hold_invoice = Self::load_authorized_hold_invoice(
+            &mut dbtx.to_ref_nc(),
+            payload.federation_id,
+            payment_hash,
+            &SettleHoldInvoicePayload::message(&payload.contract),
+            &payload.auth,
+        )
+        .await?;
+
+        let HoldInvoiceState::Held {
+            incoming_chan_id,
+            htlc_id,
+            ..
+        } = hold_invoice.state
+        else {
+            return Err(PublicGatewayError::LNv2(LNv2Error::IncomingPayment(
+                "No payment is held for the hold invoice".to_string(),
+            )));
+        };
+
+        self.check_incoming_contract_v2(
+            payload.federation_id,
+            &payload.contract,
+            hold_invoice.amount,
+        )
+        .await?;
+
+        dbtx.save_hold_invoice(
+            payment_hash,
+            &HoldInvoice {
+                state: HoldInvoiceState::Settled,
+                ..hold_invoice.clone()
+            },
+        )
+        .await;
+
+        // Fails if the hold invoice has been cancelled or has expired concurrently
+        dbtx.commit_tx_result()
+            .await
+            .map_err(PublicGatewayError::Unexpected)?;
+
+        let client = self.select_client(payload.federation_id).await?;
+
+        let module = client
+            .value()
+            .get_first_module::<GatewayClientModuleV2>()
+            .expect("Must have client module");
+
+        if let Err(err) = module
+            .relay_incoming_htlc(
+                payment_hash,
+                incoming_chan_id,
+                htlc_id,
+                payload.contract,
+                hold_invoice.amount.msats,
+            )
+            .await
+        {
+            warn!(target: LOG_GATEWAY, err = %err.fmt_compact_anyhow(), "Error relaying held lightning payment");
+
+            let mut dbtx = self.gateway_db.begin_transaction().await;
+
+            dbtx.save_hold_invoice(
+                payment_hash,
+                &HoldInvoice {
+                    state: HoldInvoiceState::Cancelled,
+                    ..hold_invoice
+                },
+            )
+            .await;
+
+            dbtx.commit_tx().await;
+
+            module
+                .cancel_held_htlc(payment_hash, incoming_chan_id, htlc_id, err.to_string())
+                .await;
+
+            return Err(PublicGatewayError::LNv2(LNv2Error::IncomingPayment(
+                "Failed to fund the incoming contract".to_string(),
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Cancels a hold invoice on behalf of the client. If a payment is held
+    /// already, the payer is refunded.
+    pub async fn cancel_hold_invoice_v2(&self, payload: CancelHoldInvoicePayload) -> Result<()> {
+        let mut dbtx = self.gateway_db.begin_transaction().await;
+
+        let hold_invoice = Self::load_authorized_hold_invoice(
+            &mut dbtx.to_ref_nc(),
+            payload.federation_id,
+            payload.payment_hash,
+            &CancelHoldInvoicePayload::message(payload.payment_hash),
+            &payload.auth,
+        )
+        .await?;
+
+        let htlc = match hold_invoice.state {
+            HoldInvoiceState::Open => None,
+            HoldInvoiceState::Held {
+                incoming_chan_id,
+                htlc_id,
+                ..
+            } => Some((incoming_chan_id, htlc_id)),
+            HoldInvoiceState::Settled => {
+                return Err(PublicGatewayError::LNv2(LNv2Error::IncomingPayment(
+                    "The hold invoice has already been settled".to_string(),
+                )));
+            }
+            HoldInvoiceState::Cancelled => return Ok(()),
+        };
+
+        dbtx.save_hold_invoice(
+            payload.payment_hash,
+            &HoldInvoice {
+                state: HoldInvoiceState::Cancelled,
+                ..hold_invoice.clone()
+            },
+        )
+        .await;
+
+        dbtx.commit_tx_result()
+            .await
+            .map_err(PublicGatewayError::Unexpected)?;
+
+        if let Some((incoming_chan_id, htlc_id)) = htlc {
+            self.select_client(hold_invoice.federation_id)
+                .await?
+                .value()
+                .get_first_module::<GatewayClientModuleV2>()
+                .expect("Must have client module")
+                .cancel_held_htlc(
+                    payload.payment_hash,
+                    incoming_chan_id,
+                    htlc_id,
+                    "Cancelled by the recipient".to_string(),
+                )
+                .await;
+        }
+
+        Ok(())
+    }
+
+    pub async fn hold_invoice_status_v2(
+        &self,
+        payload: HoldInvoiceStatusPayload,
+    ) -> Option<HoldInvoiceStatus> {
+        self.gateway_db
+            .begin_transaction_nc()
+            .await
+            .load_hold_invoice(payload.payment_hash)
+            .await
+            .filter(|hold_invoice| hold_invoice.federation_id == payload.federation_id)
+            .map(|hold_invoice| hold::hold_invoice_status(&hold_invoice.state))
+    }
+
+    /// Loads the hold invoice for `payment_hash` and verifies that the
+    /// request has been signed by the client that created it.
+    async fn load_authorized_hold_invoice(
+        dbtx: &mut DatabaseTransaction<'_>,
+        federation_id: FederationId,
+        payment_hash: sha256::Hash,
+        message: &Message,
+        auth: &Signature,
+    ) -> Result<HoldInvoice> {
+        let hold_invoice = dbtx
+            .load_hold_invoice(payment_hash)
+            .await
+            .filter(|hold_invoice| hold_invoice.federation_id == federation_id)
+            .ok_or(LNv2Error::IncomingPayment(
+                "Unknown hold invoice".to_string(),
+            ))?;
+
+        if secp256k1::SECP256K1
+            .verify_schnorr(auth, message, &hold_invoice.auth_pk.x_only_public_key().0)
+            .is_err()
+        {
+            return Err(PublicGatewayError::LNv2(LNv2Error::IncomingPayment(
+                "Invalid authentication for hold invoice".to_string(),
+            )));
+        }
+
+        Ok(hold_invoice)
+    }
+
+    /// Cancels all held payments whose hold has expired, such that the payers
+    /// are refunded.
+    pub async fn cancel_expired_hold_invoices(&self) -> anyhow::Result<()> {
+        let now = duration_since_epoch().as_secs();
+
+        let held_invoices = self
+            .gateway_db
+            .begin_transaction_nc()
+            .await
+            .load_held_hold_invoices()
+            .await;
+
+        for (payment_hash, hold_invoice) in held_invoices {
+            if !matches!(hold_invoice.state, HoldInvoiceState::Held { expiration, .. } if expiration <= now)
+            {
+                continue;
+            }
+
+            let mut dbtx = self.gateway_db.begin_transaction().await;
+
+            // The hold invoice may have been settled in the meantime
+            let Some(HoldInvoice {
+                state:
+                    HoldInvoiceState::Held {
+                        incoming_chan_id,
+                        htlc_id,
+                        ..
+                    },
+                ..
+            }) = dbtx.load_hold_invoice(payment_hash).await
+            else {
+                continue;
+            };
+
+            dbtx.save_hold_invoice(
+                payment_hash,
+                &HoldInvoice {
+                    state: HoldInvoiceState::Cancelled,
+                    ..hold_invoice.clone()
+                },
+            )
+            .await;
+
+            if dbtx.commit_tx_result().await.is_err() {
+                continue;
+            }
+
+            self.select_client(hold_invoice.federation_id)
+                .await?
+                .value()
+                .get_first_module::<GatewayClientModuleV2>()
+                .expect("Must have client module")
+                .cancel_held_htlc(
+                    payment_hash,
+                    incoming_chan_id,
+                    htlc_id,
+                    "The hold invoice has expired".to_string(),
+                )
+                .await;
+        }
+
+        Ok(())
+    }
+
+    /// For the LNv2 protocol, this will create a reusable BOLT12 offer via the
+    /// connected Lightning node on behalf of the recipient. Payments to the
+    /// offer are settled by the Lightning node and subsequently relayed into
+    /// incoming contracts by [`Gateway::relay_bolt12_offer_payments`].
+    pub async fn create_bolt12_offer_v2(
+        &self,
+        payload: CreateBolt12OfferPayload,
+    ) -> Result<String> {
+        let payment_info = self.routing_info_v2(&payload.federation_id).await?.ok_or(
+            LNv2Error::IncomingPayment(format!(
+                "Federation {} does not exist",
+                payload.federation_id
+            )),
+        )?;
+
+        if let Some(amount) = payload.amount
+            && payment_info.receive_fee.subtract_from(amount.msats)
+                < MINIMUM_INCOMING_CONTRACT_AMOUNT
+        {
+            return Err(PublicGatewayError::LNv2(LNv2Error::IncomingPayment(
+                "The amount is too small to cover fees".to_string(),
+            )));
+        }
+
+        let lnrpc = self.get_lightning_context().await?.lnrpc;
+
+        let offer = lnrpc.create_offer(
+            payload.amount,
+            payload.description,
+            payload.expiry_secs,
+            None,
+        )?;
+
+        let mut dbtx = self.gateway_db.begin_transaction().await;
+        dbtx.save_bolt12_offer(
+            fedimint_lightning::offer_id(&offer)?,
+            &Bolt12Offer {
+                federation_id: payload.federation_id,
+                recipient_pk: payload.recipient_pk,
+            },
+        )
+        .await;
+        dbtx.commit_tx().await;
+
+        Ok(offer)
+    }
+
+    /// Creates an incoming contract for a payment of `amount` to a BOLT12 offer
+    /// of `recipient_pk`, deducting the gateway's receive fee.
+    async fn bolt12_offer_contract(
+        &self,
+        federation_id: FederationId,
+        recipient_pk: PublicKey,
+        amount: Amount,
+    ) -> Result<IncomingContract> {
+        let payment_info =
+            self.routing_info_v2(&federation_id)
+                .await?
+                .ok_or(LNv2Error::IncomingPayment(format!(
+                    "Federation {federation_id} does not exist"
+                )))?;
+
+        let aggregate_pk = self
+            .select_client(federation_id)
+            .await?
+            .value()
+            .get_first_module::<GatewayClientModuleV2>()
+            .expect("Must have client module")
+            .cfg
+            .tpe_agg_pk;
+
+        Ok(bolt12::incoming_contract(
+            aggregate_pk,
+            recipient_pk,
+            payment_info.module_public_key,
+            payment_info.receive_fee.subtract_from(amount.msats),
+        ))
+    }
+
+    /// Funds an incoming contract for every payment the Lightning node received
+    /// to a BOLT12 offer created by [`Gateway::create_bolt12_offer_v2`]. The
+    /// Lightning node has already settled these payments, hence a payment is
+    /// retried until its contract has been funded, for example after the
+    /// federation's circuit breaker has been reset.
+    ///
+    /// Only payments at or after the persisted cursor are requested from the
+    /// Lightning node. The cursor is not advanced past a payment for which no
+    /// contract could be created yet, such that it is picked up again.
+    pub async fn relay_bolt12_offer_payments(&self) -> anyhow::Result<()> {
+        let lnrpc = self.get_lightning_context().await?.lnrpc;
+
+        let cursor = self
+            .gateway_db
+            .begin_transaction_nc()
+            .await
+            .load_bolt12_offer_payment_cursor()
+            .await;
+
+        let mut next_cursor = cursor;
+        let mut cursor_blocked = false;
+
+        for offer_payment in lnrpc.list_offer_payments(cursor).await? {
+            let mut dbtx = self.gateway_db.begin_transaction().await;
+
+            if !cursor_blocked {
+                next_cursor = next_cursor.max(offer_payment.timestamp_secs);
+            }
+
+            if dbtx
+                .load_bolt12_offer_payment(offer_payment.payment_hash)
+                .await
+                .is_some()
+            {
+                continue;
+            }
+
+            // Offers created by the operator are not relayed to any client
+            let Some(offer) = dbtx.load_bolt12_offer(offer_payment.offer_id).await else {
+                continue;
+            };
+
+            if dbtx
+                .load_federation_config(offer.federation_id)
+                .await
+                .is_none()
+            {
+                crit!(
+                    target: LOG_GATEWAY,
+                    payment_hash = %offer_payment.payment_hash,
+                    federation_id = %offer.federation_id,
+                    "BOLT12 offer payment can not be relayed as the gateway has left the federation"
+                );
+
+                continue;
+            }
+
+            let Ok(contract) = self
+                .bolt12_offer_contract(
+                    offer.federation_id,
+                    offer.recipient_pk,
+                    offer_payment.amount,
+                )
+                .await
+            else {
+                if !cursor_blocked {
+                    next_cursor = offer_payment.timestamp_secs;
+                    cursor_blocked = true;
+                }
+
+                continue;
+            };
+
+            let state = if contract.commitment.amount < MINIMUM_INCOMING_CONTRACT_AMOUNT {
+                crit!(
+                    target: LOG_GATEWAY,
+                    payment_hash = %offer_payment.payment_hash,
+                    amount = %offer_payment.amount,
+                    "BOLT12 offer payment is too small to fund an incoming contract"
+                );
+
+                Bolt12OfferPaymentState::Failed
+            } else {
+                Bolt12OfferPaymentState::Pending { refunds: 0 }
+            };
+
+            dbtx.save_bolt12_offer_payment(
+                offer_payment.payment_hash,
+                &Bolt12OfferPayment {
+                    federation_id: offer.federation_id,
+                    recipient_pk: offer.recipient_pk,
+                    amount: offer_payment.amount,
+                    contract,
+                    state,
+                },
+            )
+            .await;
+            dbtx.commit_tx().await;
+        }
+
+        if next_cursor != cursor {
+            let mut dbtx = self.gateway_db.begin_transaction().await;
+            dbtx.save_bolt12_offer_payment_cursor(next_cursor).await;
+            dbtx.commit_tx().await;
+        }
+
+        let pending_payments = self
+            .gateway_db
+            .begin_transaction_nc()
+            .await
+            .load_pending_bolt12_offer_payments()
+            .await;
+
+        for (payment_hash, payment) in pending_payments {
+            let federation_id = payment.federation_id;
+
+            if let Err(err) = self.relay_bolt12_offer_payment(payment_hash, payment).await {
+                warn!(target: LOG_GATEWAY, %payment_hash, %federation_id, err = %err.fmt_compact_anyhow(), "Unable to relay BOLT12 offer payment, retrying later");
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns the BOLT12 offer payments that could not be relayed to the
+    /// recipient and have to be resolved by the operator.
+    pub async fn handle_list_failed_bolt12_offer_payments_msg(
+        &self,
+    ) -> AdminResult<Vec<FailedBolt12OfferPayment>> {
+        Ok(self
+            .gateway_db
+            .begin_transaction_nc()
+            .await
+            .load_failed_bolt12_offer_payments()
+            .await
+            .into_iter()
+            .map(|(payment_hash, payment)| FailedBolt12OfferPayment {
+                payment_hash,
+                federation_id: payment.federation_id,
+                amount: payment.amount,
+            })
+            .collect())
+    }
+
+    /// Funds the incoming contract of a pending BOLT12 offer payment. If the
+    /// federation rejected the contract or refunded it to the gateway it is
+    /// replaced with a fresh one, which is funded the next time the pending
+    /// payments are relayed. A payment that keeps being refunded or whose
+    /// funding failed is marked as failed for the operator to resolve.
+    async fn relay_bolt12_offer_payment(
+        &self,
+        payment_hash: sha256::Hash,
+        mut payment: Bolt12OfferPayment,
+    ) -> anyhow::Result<()> {
+        let client = self.select_client(payment.federation_id).await?;
+
+        let final_state = client
+            .value()
+            .get_first_module::<GatewayClientModuleV2>()
+            .expect("Must have client module")
+            .relay_direct_swap(payment.contract.clone(), payment.amount.msats)
+            .await?;
+
+        match final_state {
+            FinalReceiveState::Success(_) => {
+                info!(target: LOG_GATEWAY, %payment_hash, federation_id = %payment.federation_id, "Relayed BOLT12 offer payment");
+                payment.state = Bolt12OfferPaymentState::Completed;
+            }
+            FinalReceiveState::Rejected => {
+                // Most likely the contract has expired while we were unable to fund it
+                payment.contract = self
+                    .bolt12_offer_contract(
+                        payment.federation_id,
+                        payment.recipient_pk,
+                        payment.amount,
+                    )
+                    .await?;
+            }
+            FinalReceiveState::Refunded => {
+                let Bolt12OfferPaymentState::Pending { refunds } = payment.state else {
+                    unreachable!("Only pending payments are relayed");
+                };
+
+                if refunds + 1 < MAX_OFFER_PAYMENT_REFUNDS {
+                    warn!(target: LOG_GATEWAY, %payment_hash, federation_id = %payment.federation_id, "BOLT12 offer payment was refunded, retrying with a fresh contract");
+                    payment.contract = self
+                        .bolt12_offer_contract(
+                            payment.federation_id,
+                            payment.recipient_pk,
+                            payment.amount,
+                        )
+                        .await?;
+                    payment.state = Bolt12OfferPaymentState::Pending {
+                        refunds: refunds + 1,
+                    };
+                } else {
+                    crit!(target: LOG_GATEWAY, %payment_hash, federation_id = %payment.federation_id, "BOLT12 offer payment was refunded too often, marking it as failed");
+                    payment.state = Bolt12OfferPaymentState::Failed;
+                }
+            }
+            FinalReceiveState::Failure => {
+                // We do not know whether our funds were lost, so funding another
+                // contract is left to the operator
+                crit!(target: LOG_GATEWAY, %payment_hash, federation_id = %payment.federation_id, "Failed to relay BOLT12 offer payment, marking it as failed");
+                payment.state = Bolt12OfferPaymentState::Failed;
+            }
+        }
+
+        let mut dbtx = self.gateway_db.begin_transaction().await;
+        dbtx.save_bolt12_offer_payment(payment_hash, &payment).await;
+        dbtx.commit_tx().await;
+
+        Ok(())
+    }
+
+    /// Retrieves a BOLT11 invoice from the connected Lightning node with a
+    /// specific `payment_hash`.
+    pub async fn create_invoice_via_lnrpc_v2(
+        &self,
+        payment_hash: sha256::Hash,
+        amount: Amount,
+        description: Bolt11InvoiceDescription,
+        expiry_time: u32,
+    ) -> std::result::Result<Bolt11Invoice, LightningRpcError> {
+        let lnrpc = self.get_lightning_context().await?.lnrpc;
+
+        let response = match description {
+            Bolt11InvoiceDescription::Direct(description) => {
+                lnrpc
+                    .create_invoice(CreateInvoiceRequest {
+                        payment_hash: Some(payment_hash),
+                        amount_msat: amount.msats,
+                        expiry_secs: expiry_time,
+                        description: Some(InvoiceDescription::Direct(description)),
+                    })
+                    .await?
+            }
+            Bolt11InvoiceDescription::Hash(hash) => {
+                lnrpc
+                    .create_invoice(CreateInvoiceRequest {
+                        payment_hash: Some(payment_hash),
+                        amount_msat: amount.msats,
+                        expiry_secs: expiry_time,
+                        description: Some(InvoiceDescription::Hash(hash)),
+                    })
+                    .await?
+            }
+        };
+
+        Bolt11Invoice::from_str(&response.invoice).map_err(|e| {
+            LightningRpcError::FailedToGetInvoice {
+                failure_reason: e.to_string(),
+            }
+        })
+    }
+
+    pub async fn verify_bolt11_preimage_v2(
+        &self,
+        payment_hash: sha256::Hash,
+        wait: bool,
+    ) -> std::result::Result<VerifyResponse, String> {
+        let registered_contract = self
+            .gateway_db
+            .begin_transaction_nc()
+            .await
+            .load_registered_incoming_contract(PaymentImage::Hash(payment_hash))
+            .await
+            .ok_or("Unknown payment hash".to_string())?;
+
+        let client = self
+            .select_client(registered_contract.federation_id)
+            .await
+            .map_err(|_| "Not connected to federation".to_string())?
+            .into_value();
+
+        let operation_id = OperationId::from_encodable(&registered_contract.contract);
+
+        if !(wait || client.operation_exists(operation_id).await) {
+            return Ok(VerifyResponse {
+                settled: false,
+                preimage: None,
+            });
+        }
+
+        let state = client
+            .get_first_module::<GatewayClientModuleV2>()
+            .expect("Must have client module")
+            .await_receive(operation_id)
+            .await;
+
+        let preimage = match state {
+            FinalReceiveState::Success(preimage) => Ok(preimage),
+            FinalReceiveState::Failure => Err("Payment has failed".to_string()),
+            FinalReceiveState::Refunded => Err("Payment has been refunded".to_string()),
             FinalReceiveState::Rejected => Err("Payment has been rejected".to_string()),
         }?;
 
@@ -3179,6 +4253,107 @@ async fn min_contract_amount(
             .add_to(amount))
     }
 
+    async fn check_payment_limits(
+        &self,
+        federation_id: &FederationId,
+        direction: PaymentDirection,
+        amount: Amount,
+    ) -> std::result::Result<(), String> {
+        let client = {
+            let federation_manager = self.federation_manager.read().await;
+            if let Some(reason) = federation_manager.circuit_breaker(federation_id) {
+                return Err(format!("Routing for this federation is paused: {reason}"));
+            }
+            federation_manager
+                .client(federation_id)
+                .map(|client| client.value().clone())
+                .ok_or(format!("Federation {federation_id} is not connected"))?
+        };
+
+        let limits = self
+            .gateway_db
+            .begin_transaction_nc()
+            .await
+            .load_federation_limits(*federation_id)
+            .await;
+
+        if let Some(max_payment) = limits.max_payment
+            && max_payment < amount
+        {
+            return Err(format!(
+                "Payment of {amount} exceeds the maximum payment amount of {max_payment}"
+            ));
+        }
+
+        // Incoming payments reduce the gateway's ecash balance in the federation,
+        // so only the single payment limit applies.
+        if direction == PaymentDirection::Incoming {
+            return Ok(());
+        }
+
+        if let Some(max_balance) = limits.max_balance {
+            let balance = client
+                .get_balance_for_btc()
+                .await
+                .map_err(|err| err.fmt_compact_anyhow().to_string())?;
+            if max_balance < balance + amount {
+                return Err(format!(
+                    "Payment of {amount} would exceed the maximum ecash balance of {max_balance}"
+                ));
+            }
+        }
+
+        if let Some(max_daily_outgoing) = limits.max_daily_outgoing {
+            const ONE_DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+            let end = fedimint_core::time::now();
+            let events = get_events_for_duration(&client, end - ONE_DAY, end).await;
+
+            let start_events = filter_events_by_kind(
+                &events,
+                fedimint_lnv2_common::KIND,
+                OutgoingPaymentStarted::KIND,
+            )
+            .collect::<Vec<_>>();
+            let failure_events = filter_events_by_kind(
+                &events,
+                fedimint_lnv2_common::KIND,
+                OutgoingPaymentFailed::KIND,
+            )
+            .collect::<Vec<_>>();
+
+            // Failed payments have been refunded to us, while pending payments
+            // may still settle, so every payment that has not failed counts
+            // towards the volume together with the one we are checking.
+            let started = start_events
+                .iter()
+                .filter_map(|event| event.as_raw().to_event::<OutgoingPaymentStarted>())
+                .map(|start_event| start_event.invoice_amount)
+                .sum::<Amount>();
+
+            let failed = join_events::<OutgoingPaymentStarted, OutgoingPaymentFailed, Amount>(
+                &start_events,
+                &failure_events,
+                None,
+                |start_event, failure_event, _| {
+                    (start_event.outgoing_contract.payment_image == failure_event.payment_image)
+                        .then_some(start_event.invoice_amount)
+                },
+            )
+            .sum::<Amount>();
+
+            let volume = started.saturating_sub(failed) + amount;
+
+            if max_daily_outgoing < volume {
+                return Err(format!(
+                    "Outgoing volume of {volume} over the last 24 hours exceeds the limit of {max_daily_outgoing}"
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
     async fn is_lnv1_invoice(&self, invoice: &Bolt11Invoice) -> Option<Spanned<ClientHandleArc>> {
         let rhints = invoice.route_hints();
         match rhints.first().and_then(|rh| rh.0.last()) {
@@ -1,4 +1,5 @@
 use std::collections::HashMap;
+use std::net::SocketAddr;
 use std::sync::Arc;
 
 use anyhow::anyhow;
@@ -20,15 +21,17 @@
     CreateInvoiceForOperatorPayload, CreateOfferPayload, DepositAddressPayload,
     DepositAddressRecheckPayload, GATEWAY_INFO_ENDPOINT, GET_BALANCES_ENDPOINT,
     GET_INVOICE_ENDPOINT, GET_LN_ONCHAIN_ADDRESS_ENDPOINT, GetInvoiceRequest,
-    INVITE_CODES_ENDPOINT, LEAVE_FED_ENDPOINT, LIST_CHANNELS_ENDPOINT, LIST_TRANSACTIONS_ENDPOINT,
+    HA_REPLICATE_ENDPOINT, HA_STATUS_ENDPOINT, HaStatus, INVITE_CODES_ENDPOINT, LEAVE_FED_ENDPOINT,
+    LIST_CHANNELS_ENDPOINT, LIST_FAILED_BOLT12_OFFER_PAYMENTS_ENDPOINT, LIST_TRANSACTIONS_ENDPOINT,
     LeaveFedPayload, ListTransactionsPayload, MNEMONIC_ENDPOINT, OPEN_CHANNEL_ENDPOINT,
     OPEN_CHANNEL_WITH_PUSH_ENDPOINT, OpenChannelRequest, PAY_INVOICE_FOR_OPERATOR_ENDPOINT,
     PAY_OFFER_FOR_OPERATOR_ENDPOINT, PAYMENT_LOG_ENDPOINT, PAYMENT_SUMMARY_ENDPOINT,
     PEGIN_FROM_ONCHAIN_ENDPOINT, PayInvoiceForOperatorPayload, PayOfferPayload, PaymentLogPayload,
     PaymentSummaryPayload, PeginFromOnchainPayload, RECEIVE_ECASH_ENDPOINT, ReceiveEcashPayload,
-    SEND_ONCHAIN_ENDPOINT, SET_FEES_ENDPOINT, SPEND_ECASH_ENDPOINT, STOP_ENDPOINT,
-    SendOnchainRequest, SetFeesPayload, SetMnemonicPayload, SpendEcashPayload, V1_API_ENDPOINT,
-    WITHDRAW_ENDPOINT, WITHDRAW_TO_ONCHAIN_ENDPOINT, WithdrawPayload, WithdrawToOnchainPayload,
+    ReplicatePayload, SEND_ONCHAIN_ENDPOINT, SET_FEDERATION_LIMITS_ENDPOINT, SET_FEES_ENDPOINT,
+    SPEND_ECASH_ENDPOINT, STOP_ENDPOINT, SendOnchainRequest, SetFederationLimitsPayload,
+    SetFeesPayload, SetMnemonicPayload, SpendEcashPayload, V1_API_ENDPOINT, WITHDRAW_ENDPOINT,
+    WITHDRAW_TO_ONCHAIN_ENDPOINT, WithdrawPayload, WithdrawToOnchainPayload,
 };
 use fedimint_gateway_ui::IAdminGateway;
 use fedimint_ln_common::gateway_endpoint_constants::{
@@ -36,9 +39,15 @@
 };
 use fedimint_lnurl::LnurlResponse;
 use fedimint_lnv2_common::endpoint_constants::{
-    CREATE_BOLT11_INVOICE_ENDPOINT, ROUTING_INFO_ENDPOINT, SEND_PAYMENT_ENDPOINT,
+    CANCEL_HOLD_INVOICE_ENDPOINT, CREATE_BOLT11_INVOICE_ENDPOINT, CREATE_BOLT12_OFFER_ENDPOINT,
+    CREATE_HOLD_INVOICE_ENDPOINT, HOLD_INVOICE_STATUS_ENDPOINT, PROBE_INVOICE_ENDPOINT,
+    ROUTING_INFO_ENDPOINT, SEND_PAYMENT_ENDPOINT, SETTLE_HOLD_INVOICE_ENDPOINT,
+};
+use fedimint_lnv2_common::gateway_api::{
+    CancelHoldInvoicePayload, CreateBolt11InvoicePayload, CreateBolt12OfferPayload,
+    CreateHoldInvoicePayload, HoldInvoiceStatusPayload, ProbeInvoicePayload, SendPaymentPayload,
+    SettleHoldInvoicePayload,
 };
-use fedimint_lnv2_common::gateway_api::{CreateBolt11InvoicePayload, SendPaymentPayload};
 use fedimint_logging::LOG_GATEWAY;
 use hex::ToHex;
 use serde::de::DeserializeOwned;
@@ -48,6 +57,7 @@
 use tracing::{info, instrument, warn};
 
 use crate::error::{GatewayError, LnurlError};
+use crate::ha::HighAvailability;
 use crate::iroh_server::{Handlers, start_iroh_endpoint};
 use crate::{Gateway, GatewayState};
 
@@ -123,6 +133,60 @@ pub async fn run_webserver(
     Ok(())
 }
 
+/// Serves the high-availability status of a standby instance while it waits
+/// for the leader lease, since the gateway and its webserver are only
+/// constructed once it takes over. Shutting down the returned task group frees
+/// the listen address for [`run_webserver`].
+pub async fn run_standby_webserver(
+    listen: SocketAddr,
+    bcrypt_password_hash: bcrypt::HashParts,
+    ha: Arc<HighAvailability>,
+) -> anyhow::Result<TaskGroup> {
+    let routes = Router::new().route(HA_STATUS_ENDPOINT, get(standby_ha_status));
+    let api = Router::new()
+        .nest(&format!("/{V1_API_ENDPOINT}"), routes.clone())
+        .merge(routes)
+        .layer(middleware::from_fn(standby_auth_middleware))
+        .layer(Extension(Arc::new(bcrypt_password_hash)))
+        .layer(Extension(ha));
+
+    let task_group = TaskGroup::new();
+    let shutdown_rx = task_group.make_handle().make_shutdown_rx();
+    let listener = TcpListener::bind(&listen).await?;
+    let serve = axum::serve(listener, api.into_make_service());
+    task_group.spawn("Gateway Standby Webserver", |_| async {
+        let graceful = serve.with_graceful_shutdown(async {
+            shutdown_rx.await;
+        });
+
+        if let Err(err) = graceful.await {
+            warn!(target: LOG_GATEWAY, err = %err.fmt_compact(), "Error shutting down gatewayd standby webserver");
+        }
+    });
+    info!(target: LOG_GATEWAY, %listen, "Successfully started standby webserver");
+
+    Ok(task_group)
+}
+
+async fn standby_auth_middleware(
+    Extension(bcrypt_password_hash): Extension<Arc<bcrypt::HashParts>>,
+    request: Request,
+    next: Next,
+) -> Result<impl IntoResponse, StatusCode> {
+    let token = extract_bearer_token(&request)?;
+    if bcrypt::verify(token, &bcrypt_password_hash)
+        .expect("Bcrypt hash is valid since we just stringified it")
+    {
+        return Ok(next.run(request).await);
+    }
+
+    Err(StatusCode::UNAUTHORIZED)
+}
+
+async fn standby_ha_status(Extension(ha): Extension<Arc<HighAvailability>>) -> Json<HaStatus> {
+    Json(ha.status().await)
+}
+
 /// Extracts the Bearer token from the Authorization header of the request.
 fn extract_bearer_token(request: &Request) -> Result<String, StatusCode> {
     let headers = request.headers();
@@ -157,7 +221,13 @@ async fn not_configured_middleware(
 
         let is_setup_route = fedimint_gateway_ui::is_allowed_setup_route(path);
 
-        if !is_mnemonic_api && !is_setup_route {
+        // The standby of a high-availability pair has to replicate the database
+        // before the gateway is configured
+        let is_ha_api = [HA_STATUS_ENDPOINT, HA_REPLICATE_ENDPOINT]
+            .iter()
+            .any(|route| path == *route || path == format!("/{V1_API_ENDPOINT}{route}"));
+
+        if !is_mnemonic_api && !is_setup_route && !is_ha_api {
             return Err(StatusCode::NOT_FOUND);
         }
     }
@@ -269,6 +339,48 @@ fn lnv2_routes(handlers: &mut Handlers) -> Router {
         false,
         router,
     );
+    let router = register_post_handler(
+        handlers,
+        CREATE_BOLT12_OFFER_ENDPOINT,
+        create_bolt12_offer_v2,
+        false,
+        router,
+    );
+    let router = register_post_handler(
+        handlers,
+        PROBE_INVOICE_ENDPOINT,
+        probe_invoice_v2,
+        false,
+        router,
+    );
+    let router = register_post_handler(
+        handlers,
+        CREATE_HOLD_INVOICE_ENDPOINT,
+        create_hold_invoice_v2,
+        false,
+        router,
+    );
+    let router = register_post_handler(
+        handlers,
+        SETTLE_HOLD_INVOICE_ENDPOINT,
+        settle_hold_invoice_v2,
+        false,
+        router,
+    );
+    let router = register_post_handler(
+        handlers,
+        CANCEL_HOLD_INVOICE_ENDPOINT,
+        cancel_hold_invoice_v2,
+        false,
+        router,
+    );
+    let router = register_post_handler(
+        handlers,
+        HOLD_INVOICE_STATUS_ENDPOINT,
+        hold_invoice_status_v2,
+        false,
+        router,
+    );
     // Verify endpoint does not have the same signature, it is handled separately
     router.route("/verify/{payment_hash}", get(verify_bolt11_preimage_v2_get))
 }
@@ -477,6 +589,20 @@ fn routes(gateway: Arc<Gateway>, task_group: TaskGroup, handlers: &mut Handlers)
         is_authenticated,
         authenticated_routes,
     );
+    let authenticated_routes = register_get_handler(
+        handlers,
+        LIST_FAILED_BOLT12_OFFER_PAYMENTS_ENDPOINT,
+        list_failed_bolt12_offer_payments,
+        is_authenticated,
+        authenticated_routes,
+    );
+    let authenticated_routes = register_post_handler(
+        handlers,
+        SET_FEDERATION_LIMITS_ENDPOINT,
+        set_federation_limits,
+        is_authenticated,
+        authenticated_routes,
+    );
     let authenticated_routes = register_post_handler(
         handlers,
         CONFIGURATION_ENDPOINT,
@@ -505,6 +631,20 @@ fn routes(gateway: Arc<Gateway>, task_group: TaskGroup, handlers: &mut Handlers)
         is_authenticated,
         authenticated_routes,
     );
+    let authenticated_routes = register_get_handler(
+        handlers,
+        HA_STATUS_ENDPOINT,
+        ha_status,
+        is_authenticated,
+        authenticated_routes,
+    );
+    let authenticated_routes = register_post_handler(
+        handlers,
+        HA_REPLICATE_ENDPOINT,
+        ha_replicate,
+        is_authenticated,
+        authenticated_routes,
+    );
     let authenticated_routes = authenticated_routes.layer(middleware::from_fn(auth_middleware));
 
     Router::new()
@@ -645,6 +785,15 @@ async fn set_fees(
     Ok(Json(json!(())))
 }
 
+#[instrument(target = LOG_GATEWAY, skip_all, err, fields(?payload))]
+async fn set_federation_limits(
+    Extension(gateway): Extension<Arc<Gateway>>,
+    Json(payload): Json<SetFederationLimitsPayload>,
+) -> Result<Json<serde_json::Value>, GatewayError> {
+    gateway.handle_set_federation_limits_msg(payload).await?;
+    Ok(Json(json!(())))
+}
+
 #[instrument(target = LOG_GATEWAY, skip_all, err)]
 async fn get_ln_onchain_address(
     Extension(gateway): Extension<Arc<Gateway>>,
@@ -740,6 +889,15 @@ async fn pay_bolt11_invoice_v2(
     Ok(Json(json!(payment_result)))
 }
 
+#[instrument(target = LOG_GATEWAY, skip_all, err)]
+async fn probe_invoice_v2(
+    Extension(gateway): Extension<Arc<Gateway>>,
+    Json(payload): Json<ProbeInvoicePayload>,
+) -> Result<Json<serde_json::Value>, GatewayError> {
+    let estimate = gateway.probe_invoice_v2(payload).await?;
+    Ok(Json(json!(estimate)))
+}
+
 #[instrument(target = LOG_GATEWAY, skip_all, err)]
 async fn create_bolt11_invoice_v2(
     Extension(gateway): Extension<Arc<Gateway>>,
@@ -749,6 +907,51 @@ async fn create_bolt11_invoice_v2(
     Ok(Json(json!(invoice)))
 }
 
+#[instrument(target = LOG_GATEWAY, skip_all, err)]
+async fn create_hold_invoice_v2(
+    Extension(gateway): Extension<Arc<Gateway>>,
+    Json(payload): Json<CreateHoldInvoicePayload>,
+) -> Result<Json<serde_json::Value>, GatewayError> {
+    let invoice = gateway.create_hold_invoice_v2(payload).await?;
+    Ok(Json(json!(invoice)))
+}
+
+#[instrument(target = LOG_GATEWAY, skip_all, err)]
+async fn settle_hold_invoice_v2(
+    Extension(gateway): Extension<Arc<Gateway>>,
+    Json(payload): Json<SettleHoldInvoicePayload>,
+) -> Result<Json<serde_json::Value>, GatewayError> {
+    gateway.settle_hold_invoice_v2(payload).await?;
+    Ok(Json(json!(())))
+}
+
+#[instrument(target = LOG_GATEWAY, skip_all, err)]
+async fn cancel_hold_invoice_v2(
+    Extension(gateway): Extension<Arc<Gateway>>,
+    Json(payload): Json<CancelHoldInvoicePayload>,
+) -> Result<Json<serde_json::Value>, GatewayError> {
+    gateway.cancel_hold_invoice_v2(payload).await?;
+    Ok(Json(json!(())))
+}
+
+#[instrument(target = LOG_GATEWAY, skip_all, err)]
+async fn hold_invoice_status_v2(
+    Extension(gateway): Extension<Arc<Gateway>>,
+    Json(payload): Json<HoldInvoiceStatusPayload>,
+) -> Result<Json<serde_json::Value>, GatewayError> {
+    let status = gateway.hold_invoice_status_v2(payload).await;
+    Ok(Json(json!(status)))
+}
+
+#[instrument(target = LOG_GATEWAY, skip_all, err)]
+async fn create_bolt12_offer_v2(
+    Extension(gateway): Extension<Arc<Gateway>>,
+    Json(payload): Json<CreateBolt12OfferPayload>,
+) -> Result<Json<serde_json::Value>, GatewayError> {
+    let offer = gateway.create_bolt12_offer_v2(payload).await?;
+    Ok(Json(json!(offer)))
+}
+
 pub(crate) async fn verify_bolt11_preimage_v2_get(
     Extension(gateway): Extension<Arc<Gateway>>,
     Path(payment_hash): Path<sha256::Hash>,
@@ -842,6 +1045,16 @@ async fn list_transactions(
     Ok(Json(json!(transactions)))
 }
 
+#[instrument(target = LOG_GATEWAY, skip_all, err)]
+async fn list_failed_bolt12_offer_payments(
+    Extension(gateway): Extension<Arc<Gateway>>,
+) -> Result<Json<serde_json::Value>, GatewayError> {
+    let payments = gateway
+        .handle_list_failed_bolt12_offer_payments_msg()
+        .await?;
+    Ok(Json(json!(payments)))
+}
+
 #[instrument(target = LOG_GATEWAY, skip_all, err)]
 async fn create_offer_for_operator(
     Extension(gateway): Extension<Arc<Gateway>>,
@@ -869,3 +1082,20 @@ async fn invite_codes(
     let invite_codes = gateway.handle_export_invite_codes().await;
     Ok(Json(json!(invite_codes)))
 }
+
+#[instrument(target = LOG_GATEWAY, skip_all, err)]
+async fn ha_status(
+    Extension(gateway): Extension<Arc<Gateway>>,
+) -> Result<Json<serde_json::Value>, GatewayError> {
+    let status = gateway.handle_ha_status_msg().await?;
+    Ok(Json(json!(status)))
+}
+
+#[instrument(target = LOG_GATEWAY, skip_all, err)]
+async fn ha_replicate(
+    Extension(gateway): Extension<Arc<Gateway>>,
+    Json(payload): Json<ReplicatePayload>,
+) -> Result<Json<serde_json::Value>, GatewayError> {
+    let response = gateway.handle_ha_replicate_msg(payload).await?;
+    Ok(Json(json!(response)))
+}
@@ -12,19 +12,29 @@
     ClientInput, ClientInputBundle, ClientOutput, ClientOutputBundle, TransactionBuilder,
 };
 use fedimint_client_module::module::OutPointRange;
+use fedimint_connectors::ConnectorRegistry;
 use fedimint_core::config::FederationId;
 use fedimint_core::core::{IntoDynInstance, OperationId};
+use fedimint_core::db::Database;
+use fedimint_core::db::mem_impl::MemDatabase;
 use fedimint_core::encoding::Encodable;
+use fedimint_core::module::registry::ModuleDecoderRegistry;
 use fedimint_core::module::{AmountUnit, Amounts};
 use fedimint_core::task::sleep_in_test;
 use fedimint_core::time::now;
 use fedimint_core::util::{NextOrPending, backoff_util, retry};
-use fedimint_core::{Amount, OutPoint, msats, sats, secp256k1};
+use fedimint_core::{Amount, BitcoinAmountOrAll, OutPoint, msats, sats, secp256k1};
 use fedimint_dummy_client::{DummyClientInit, DummyClientModule};
 use fedimint_dummy_server::DummyInit;
 use fedimint_eventlog::Event;
-use fedimint_gateway_common::{PaymentLogPayload, SetFeesPayload};
+use fedimint_gateway_common::{
+    CloseChannelsWithPeerRequest, FailedBolt12OfferPayment, FederationLimits, GetInvoiceRequest,
+    GetInvoiceResponse, HaRole, LeaveFedPayload, OpenChannelRequest, PaymentLogPayload,
+    PaymentStatus, SendOnchainRequest, SetFederationLimitsPayload, SetFeesPayload,
+};
 use fedimint_gateway_server::Gateway;
+use fedimint_gateway_server::ha::{HaConfig, HighAvailability, SharedLease};
+use fedimint_gateway_server_db::ReplicationLog;
 use fedimint_gateway_ui::IAdminGateway;
 use fedimint_gw_client::pay::{
     OutgoingContractError, OutgoingPaymentError, OutgoingPaymentErrorType,
@@ -34,9 +44,12 @@
 };
 use fedimint_gwv2_client::events::{
     CompleteLightningPaymentSucceeded, IncomingPaymentStarted, IncomingPaymentSucceeded,
-    OutgoingPaymentStarted, OutgoingPaymentSucceeded,
+    OutgoingPaymentFailed, OutgoingPaymentStarted, OutgoingPaymentSucceeded,
+};
+use fedimint_gwv2_client::{
+    Cancelled, FinalReceiveState, GatewayClientModuleV2, IGatewayClientV2, PaymentDirection,
 };
-use fedimint_gwv2_client::{FinalReceiveState, GatewayClientModuleV2};
+use fedimint_lightning::{ILnRpcClient, offer_id};
 use fedimint_ln_client::api::LnFederationApi;
 use fedimint_ln_client::pay::{PayInvoicePayload, PaymentData};
 use fedimint_ln_client::{
@@ -50,14 +63,22 @@
 use fedimint_ln_common::{LightningGateway, LightningInput, LightningOutput, PrunedInvoice};
 use fedimint_ln_server::LightningInit;
 use fedimint_lnv2_common::contracts::{IncomingContract, OutgoingContract, PaymentImage};
-use fedimint_lnv2_common::gateway_api::PaymentFee;
+use fedimint_lnv2_common::gateway_api::{
+    CancelHoldInvoicePayload, CreateBolt12OfferPayload, CreateHoldInvoicePayload,
+    HoldInvoiceStatus, HoldInvoiceStatusPayload, PaymentFee,
+};
 use fedimint_logging::LOG_TEST;
+use fedimint_mint_client::MintClientInit;
+use fedimint_mint_server::MintInit;
 use fedimint_testing::btc::BitcoinTest;
 use fedimint_testing::db::BYTE_33;
 use fedimint_testing::federation::FederationTest;
 use fedimint_testing::fixtures::Fixtures;
 use fedimint_testing::ln::FakeLightningTest;
+use fedimint_testing::ln::mock::{MockLightningNetwork, MockLightningNode};
 use fedimint_unknown_server::UnknownInit;
+use fedimint_walletv2_client::WalletClientInit;
+use fedimint_walletv2_server::WalletInit;
 use futures::Future;
 use itertools::Itertools;
 use lightning_invoice::{Bolt11Invoice, Bolt11InvoiceDescription, Description, RoutingFees};
@@ -1300,3 +1321,879 @@ async fn gateway_read_payment_log() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+/// Logs the start of an outgoing payment of `invoice_amount` and, unless it
+/// is still pending, its outcome.
+async fn log_outgoing_payment(
+    client: &ClientHandleArc,
+    payment_image: PaymentImage,
+    invoice_amount: Amount,
+    outcome: Option<Result<(), Cancelled>>,
+) -> anyhow::Result<()> {
+    let lnv2_module_id = client
+        .get_first_instance(&fedimint_lnv2_common::KIND)
+        .expect("lnv2 module not found");
+    let lnv2 = client.get_first_module::<GatewayClientModuleV2>()?;
+
+    let mut dbtx = client.db().begin_transaction().await;
+    let mut module_dbtx = dbtx
+        .to_ref_with_prefix_module_id(lnv2_module_id)
+        .0
+        .into_nc();
+
+    lnv2.client_ctx
+        .log_event(
+            &mut module_dbtx,
+            OutgoingPaymentStarted {
+                outgoing_contract: OutgoingContract {
+                    payment_image: payment_image.clone(),
+                    amount: invoice_amount,
+                    expiration: 120,
+                    claim_pk: Keypair::new(secp256k1::SECP256K1, &mut rand::thread_rng())
+                        .public_key(),
+                    refund_pk: lnv2.keypair.public_key(),
+                    ephemeral_pk: Keypair::new(secp256k1::SECP256K1, &mut rand::thread_rng())
+                        .public_key(),
+                },
+                min_contract_amount: invoice_amount,
+                invoice_amount,
+                operation_start: now(),
+                max_delay: 100,
+            },
+        )
+        .await;
+
+    match outcome {
+        Some(Ok(())) => {
+            lnv2.client_ctx
+                .log_event(
+                    &mut module_dbtx,
+                    OutgoingPaymentSucceeded {
+                        payment_image,
+                        target_federation: None,
+                    },
+                )
+                .await;
+        }
+        Some(Err(error)) => {
+            lnv2.client_ctx
+                .log_event(
+                    &mut module_dbtx,
+                    OutgoingPaymentFailed {
+                        payment_image,
+                        error,
+                    },
+                )
+                .await;
+        }
+        None => {}
+    }
+
+    drop(module_dbtx);
+    dbtx.commit_tx().await;
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn gateway_daily_outgoing_limit_counts_pending_and_settled_payments() -> anyhow::Result<()> {
+    let fixtures = fixtures();
+    let fed = fixtures.new_fed_degraded().await;
+    let gateway = fixtures.new_gateway().await;
+    fed.connect_gateway(&gateway).await;
+
+    gateway
+        .handle_set_federation_limits_msg(SetFederationLimitsPayload {
+            federation_id: fed.id(),
+            limits: FederationLimits {
+                max_daily_outgoing: Some(msats(25_000)),
+                ..FederationLimits::default()
+            },
+        })
+        .await?;
+
+    let client = gateway.select_client(fed.id()).await?.into_value();
+
+    let outcomes = [
+        None,
+        Some(Ok(())),
+        Some(Err(Cancelled::Failure)),
+        Some(Err(Cancelled::Refunded)),
+    ];
+
+    for (i, outcome) in (0u8..).zip(outcomes) {
+        let payment_image = PaymentImage::Hash([i; 32].consensus_hash());
+        log_outgoing_payment(&client, payment_image, msats(10_000), outcome).await?;
+    }
+
+    // Failed payments have been refunded, while the pending payment may still
+    // settle, so the pending and the settled payment count
+    gateway
+        .check_payment_limits(&fed.id(), PaymentDirection::Outgoing, msats(5_000))
+        .await
+        .map_err(|reason| anyhow::anyhow!(reason))?;
+
+    let reason = gateway
+        .check_payment_limits(&fed.id(), PaymentDirection::Outgoing, msats(10_000))
+        .await
+        .expect_err("Pending and settled volume exceeds the daily limit");
+    assert!(reason.contains("Outgoing volume"), "{reason}");
+
+    // The daily limit does not apply to incoming payments
+    gateway
+        .check_payment_limits(&fed.id(), PaymentDirection::Incoming, msats(20_000))
+        .await
+        .map_err(|reason| anyhow::anyhow!(reason))?;
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn gateway_circuit_breaker_pauses_routing_while_guardians_are_unreachable()
+-> anyhow::Result<()> {
+    let fixtures = fixtures();
+    let fed = fixtures.new_fed_degraded().await;
+    let gateway = fixtures.new_gateway().await;
+    fed.connect_gateway(&gateway).await;
+
+    gateway.check_circuit_breakers().await;
+    gateway
+        .check_payment_limits(&fed.id(), PaymentDirection::Incoming, sats(1))
+        .await
+        .map_err(|reason| anyhow::anyhow!(reason))?;
+
+    // The federation is already degraded, so one more crashed guardian leaves
+    // it without a threshold
+    let peer_id = fed.online_peer_ids().next().expect("Federation has peers");
+    fed.crash_peer(peer_id).await;
+
+    gateway.check_circuit_breakers().await;
+    let reason = gateway
+        .check_payment_limits(&fed.id(), PaymentDirection::Outgoing, sats(1))
+        .await
+        .expect_err("Routing is paused");
+    assert!(reason.contains("guardians are reachable"), "{reason}");
+
+    fed.restart_peer(peer_id).await;
+
+    gateway.check_circuit_breakers().await;
+    gateway
+        .check_payment_limits(&fed.id(), PaymentDirection::Outgoing, sats(1))
+        .await
+        .map_err(|reason| anyhow::anyhow!(reason))?;
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn gateway_circuit_breaker_pauses_routing_while_ecash_is_unbacked() -> anyhow::Result<()> {
+    let fixtures = Fixtures::new_primary(MintClientInit, MintInit)
+        .with_module(DummyClientInit, DummyInit)
+        .with_module(
+            LightningClientInit {
+                gateway_conn: Some(Arc::new(MockGatewayConnection)),
+            },
+            LightningInit,
+        )
+        .with_module(
+            fedimint_lnv2_client::LightningClientInit::default(),
+            fedimint_lnv2_server::LightningInit,
+        )
+        .with_module(WalletClientInit, WalletInit);
+    let fed = fixtures.new_fed_degraded().await;
+    let gateway = fixtures.new_gateway().await;
+    fed.connect_gateway(&gateway).await;
+
+    // The dummy module creates ecash out of thin air, which the wallet does
+    // not hold any bitcoin for
+    let client = fed.new_client().await;
+    let operation_id = OperationId::new_random();
+    let outpoint_range = client
+        .finalize_and_submit_transaction(
+            operation_id,
+            "Issue unbacked e-cash via dummy module",
+            |_| (),
+            TransactionBuilder::new().with_inputs(
+                client
+                    .get_first_module::<DummyClientModule>()?
+                    .create_input(sats(1000)),
+            ),
+        )
+        .await?;
+    client
+        .await_primary_bitcoin_module_outputs(operation_id, outpoint_range.into_iter().collect())
+        .await?;
+
+    // The mint commits to its liabilities in one of the following sessions and
+    // the deficit has to be observed by two consecutive checks
+    let reason = loop {
+        gateway.check_circuit_breakers().await;
+
+        if let Err(reason) = gateway
+            .check_payment_limits(&fed.id(), PaymentDirection::Incoming, sats(1))
+            .await
+        {
+            break reason;
+        }
+
+        sleep_in_test(
+            "Waiting for the circuit breaker to trip",
+            Duration::from_millis(100),
+        )
+        .await;
+    };
+
+    assert!(reason.contains("Outstanding ecash"), "{reason}");
+
+    Ok(())
+}
+
+/// Runs a test with a gateway connected to a node of a mock lightning network,
+/// which has a channel with 500,000 sats on either side to the node `alice`.
+async fn mock_lightning_test<B>(
+    f: impl FnOnce(
+        Gateway,
+        MockLightningNode, // Gateway's lightning node
+        MockLightningNode, // Alice's lightning node
+        FederationTest,
+        ClientHandleArc, // User Client
+    ) -> B,
+) -> anyhow::Result<()>
+where
+    B: Future<Output = anyhow::Result<()>>,
+{
+    let fixtures = fixtures();
+    let network = MockLightningNetwork::new();
+    let gateway_node = network.add_node("gateway");
+    let alice = network.add_node("alice");
+    alice.connect(&gateway_node, 1_000_000, 500_000);
+
+    let fed = fixtures.new_fed_degraded().await;
+    let gateway = fixtures
+        .new_gateway_with_mock_lightning(&gateway_node)
+        .await;
+    fed.connect_gateway(&gateway).await;
+    let user_client = fed.new_client().await;
+    user_client
+        .get_first_module::<LightningClientModule>()?
+        .update_gateway_cache()
+        .await?;
+
+    f(gateway, gateway_node, alice, fed, user_client).await
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_gateway_intercepts_htlc_from_mock_lightning_node() -> anyhow::Result<()> {
+    mock_lightning_test(
+        |gateway, gateway_node, alice, fed, user_client| async move {
+            let gateway_client = gateway.select_client(fed.id()).await?.into_value();
+            // Give gateway client initial balance
+            let initial_gateway_balance = sats(1000);
+            gateway_client
+                .get_first_module::<DummyClientModule>()?
+                .mock_receive(initial_gateway_balance, AmountUnit::BITCOIN)
+                .await?;
+
+            // User client creates invoice in federation
+            let ln_module = user_client.get_first_module::<LightningClientModule>()?;
+            let lightning_gateway = ln_module
+                .select_gateway(&gateway.http_gateway_id().await)
+                .await;
+            let desc = Description::new("description".to_string())?;
+            let (receive_op, invoice, _) = ln_module
+                .create_bolt11_invoice(
+                    sats(100),
+                    Bolt11InvoiceDescription::Direct(desc),
+                    None,
+                    "test mock lightning receive",
+                    lightning_gateway,
+                )
+                .await?;
+            let mut receive_sub = ln_module
+                .subscribe_ln_receive(receive_op)
+                .await?
+                .into_stream();
+            assert_eq!(receive_sub.ok().await?, LnReceiveState::Created);
+            assert_matches!(
+                receive_sub.ok().await?,
+                LnReceiveState::WaitingForPayment { .. }
+            );
+
+            // Alice pays the invoice over her channel to the gateway's node, which
+            // intercepts the HTLC and hands it to the gateway
+            let invoice_amount = Amount::from_msats(invoice.amount_milli_satoshis().unwrap());
+            let preimage = alice
+                .pay(invoice.clone(), 1000, Amount::ZERO)
+                .await?
+                .preimage;
+            assert_eq!(sha256(&preimage.0), *invoice.payment_hash());
+
+            assert_eq!(receive_sub.ok().await?, LnReceiveState::Funded);
+            assert_eq!(receive_sub.ok().await?, LnReceiveState::AwaitingFunds);
+            assert_eq!(receive_sub.ok().await?, LnReceiveState::Claimed);
+
+            assert_eq!(
+                initial_gateway_balance.saturating_sub(invoice_amount),
+                gateway_client.get_balance_for_btc().await?
+            );
+            assert_eq!(
+                gateway_node.get_balances().await?.lightning_balance_msats,
+                sats(500_000).msats + invoice_amount.msats
+            );
+
+            Ok(())
+        },
+    )
+    .await
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_gateway_pays_invoice_over_mock_lightning_network() -> anyhow::Result<()> {
+    mock_lightning_test(
+        |gateway, gateway_node, alice, fed, user_client| async move {
+            let gateway_client = gateway.select_client(fed.id()).await?.into_value();
+            // Give user_client initial balance
+            user_client
+                .get_first_module::<DummyClientModule>()?
+                .mock_receive(sats(1000), AmountUnit::BITCOIN)
+                .await?;
+
+            let invoice = alice.invoice(sats(250));
+            gateway_pay_valid_invoice(
+                invoice.clone(),
+                &user_client,
+                &gateway_client,
+                &gateway.http_gateway_id().await,
+            )
+            .await?;
+
+            assert_matches!(
+                alice
+                    .get_invoice(GetInvoiceRequest {
+                        payment_hash: *invoice.payment_hash(),
+                    })
+                    .await?,
+                Some(GetInvoiceResponse {
+                    status: PaymentStatus::Succeeded,
+                    ..
+                })
+            );
+            assert_eq!(
+                gateway_node.get_balances().await?.lightning_balance_msats,
+                sats(500_000 - 250).msats
+            );
+
+            Ok(())
+        },
+    )
+    .await
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_mock_lightning_network_payments_offers_and_onchain() -> anyhow::Result<()> {
+    let network = MockLightningNetwork::new();
+    let alice = network.add_node("alice");
+    let bob = network.add_node("bob");
+
+    alice.fund_onchain(2_000_000);
+    alice
+        .open_channel(OpenChannelRequest {
+            pubkey: bob.pub_key(),
+            host: "127.0.0.1:9735".to_string(),
+            channel_size_sats: 1_000_000,
+            push_amount_sats: 0,
+        })
+        .await?;
+    assert_eq!(alice.get_balances().await?.onchain_balance_sats, 1_000_000);
+
+    // Payments that failed as configured can be retried, and successful payments
+    // are not paid twice
+    let invoice = bob.invoice(sats(1000));
+    alice.fail_next_payments(1);
+    assert!(
+        alice
+            .pay(invoice.clone(), 1000, Amount::ZERO)
+            .await
+            .is_err()
+    );
+    let preimage = alice
+        .pay(invoice.clone(), 1000, Amount::ZERO)
+        .await?
+        .preimage;
+    assert_eq!(
+        alice
+            .pay(invoice.clone(), 1000, Amount::ZERO)
+            .await?
+            .preimage,
+        preimage
+    );
+
+    // Bob has no outbound liquidity to pay alice
+    let unpayable_invoice = alice.invoice(sats(1));
+    assert!(
+        bob.pay(unpayable_invoice, 1000, Amount::ZERO)
+            .await
+            .is_err()
+    );
+
+    let offer = bob.create_offer(Some(sats(500)), Some("coffee".to_string()), None, None)?;
+    alice.pay_offer(offer.clone(), None, None, None).await?;
+    let offer_payments = bob.list_offer_payments(0).await?;
+    assert_eq!(offer_payments.len(), 1);
+    assert_eq!(offer_payments[0].offer_id, offer_id(&offer)?);
+    assert_eq!(offer_payments[0].amount, sats(500));
+
+    let balances = bob.get_balances().await?;
+    assert_eq!(balances.lightning_balance_msats, sats(1500).msats);
+    assert_eq!(
+        balances.inbound_lightning_liquidity_msats,
+        sats(1_000_000 - 1500).msats
+    );
+
+    let address = bob
+        .get_ln_onchain_address()
+        .await?
+        .address
+        .parse::<bitcoin::Address<bitcoin::address::NetworkUnchecked>>()?;
+    alice
+        .send_onchain(SendOnchainRequest {
+            address,
+            amount: BitcoinAmountOrAll::Amount(bitcoin::Amount::from_sat(100_000)),
+            fee_rate_sats_per_vbyte: 1,
+        })
+        .await?;
+    assert_eq!(bob.get_balances().await?.onchain_balance_sats, 100_000);
+
+    // Closing the channel returns both sides' balances onchain
+    let closed = alice
+        .close_channels_with_peer(CloseChannelsWithPeerRequest {
+            pubkey: bob.pub_key(),
+            force: false,
+            sats_per_vbyte: None,
+        })
+        .await?;
+    assert_eq!(closed.num_channels_closed, 1);
+    assert_eq!(bob.get_balances().await?.onchain_balance_sats, 101_500);
+    assert!(alice.list_channels().await?.channels.is_empty());
+
+    Ok(())
+}
+
+/// Lease TTL of the high-availability tests, the lease is renewed every second
+const HA_LEASE_TTL: Duration = Duration::from_secs(3);
+
+/// Creates a BOLT12 offer of 500 sats on behalf of an LNv2 client of the
+/// federation via the gateway and pays it from `payer`.
+async fn pay_bolt12_offer_via_gateway(
+    gateway: &Gateway,
+    payer: &MockLightningNode,
+    federation_id: FederationId,
+) -> anyhow::Result<()> {
+    let offer = gateway
+        .create_bolt12_offer_v2(CreateBolt12OfferPayload {
+            federation_id,
+            recipient_pk: Keypair::new(secp256k1::SECP256K1, &mut rand::thread_rng()).public_key(),
+            amount: Some(sats(500)),
+            description: Some("coffee".to_string()),
+            expiry_secs: None,
+        })
+        .await?;
+
+    payer.pay_offer(offer, None, None, None).await?;
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn gateway_relays_bolt12_offer_payment_once() -> anyhow::Result<()> {
+    mock_lightning_test(|gateway, _, alice, fed, _| async move {
+        send_msats_to_gateway(&gateway, fed.id(), 1_000_000).await;
+
+        pay_bolt12_offer_via_gateway(&gateway, &alice, fed.id()).await?;
+
+        gateway.relay_bolt12_offer_payments().await?;
+
+        let balance = get_balances(&gateway, vec![fed.id()]).await[0];
+        assert!(balance < 1_000_000, "Gateway funded the incoming contract");
+
+        // The payment has been completed, so it is not funded again
+        gateway.relay_bolt12_offer_payments().await?;
+
+        assert_eq!(get_balances(&gateway, vec![fed.id()]).await[0], balance);
+
+        Ok(())
+    })
+    .await
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn gateway_keeps_relaying_bolt12_offer_payments_after_one_fails() -> anyhow::Result<()> {
+    let fixtures = fixtures();
+    let network = MockLightningNetwork::new();
+    let gateway_node = network.add_node("gateway");
+    let alice = network.add_node("alice");
+    alice.connect(&gateway_node, 1_000_000, 500_000);
+
+    let fed1 = fixtures.new_fed_degraded().await;
+    let fed2 = fixtures.new_fed_degraded().await;
+    let gateway = fixtures
+        .new_gateway_with_mock_lightning(&gateway_node)
+        .await;
+    fed1.connect_gateway(&gateway).await;
+    fed2.connect_gateway(&gateway).await;
+
+    // The gateway has no ecash in the first federation to fund the contract
+    send_msats_to_gateway(&gateway, fed2.id(), 1_000_000).await;
+
+    pay_bolt12_offer_via_gateway(&gateway, &alice, fed1.id()).await?;
+    pay_bolt12_offer_via_gateway(&gateway, &alice, fed2.id()).await?;
+
+    gateway.relay_bolt12_offer_payments().await?;
+
+    let balance = get_balances(&gateway, vec![fed2.id()]).await[0];
+    assert!(balance < 1_000_000, "Gateway funded the incoming contract");
+
+    // Payments to offers of a federation the gateway left can not be relayed
+    // anymore, which must not prevent relaying payments to other offers
+    gateway
+        .handle_leave_federation(LeaveFedPayload {
+            federation_id: fed1.id(),
+        })
+        .await?;
+
+    pay_bolt12_offer_via_gateway(&gateway, &alice, fed2.id()).await?;
+
+    gateway.relay_bolt12_offer_payments().await?;
+
+    assert!(get_balances(&gateway, vec![fed2.id()]).await[0] < balance);
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn gateway_lists_bolt12_offer_payments_it_failed_to_relay() -> anyhow::Result<()> {
+    mock_lightning_test(|gateway, _, alice, fed, _| async move {
+        send_msats_to_gateway(&gateway, fed.id(), 1_000_000).await;
+
+        let offer = gateway
+            .create_bolt12_offer_v2(CreateBolt12OfferPayload {
+                federation_id: fed.id(),
+                recipient_pk: Keypair::new(secp256k1::SECP256K1, &mut rand::thread_rng())
+                    .public_key(),
+                amount: None,
+                description: Some("coffee".to_string()),
+                expiry_secs: None,
+            })
+            .await?;
+
+        // The payment is too small to fund an incoming contract
+        let preimage = alice.pay_offer(offer, None, Some(sats(1)), None).await?;
+
+        gateway.relay_bolt12_offer_payments().await?;
+        gateway.relay_bolt12_offer_payments().await?;
+
+        assert_eq!(
+            gateway
+                .handle_list_failed_bolt12_offer_payments_msg()
+                .await?,
+            vec![FailedBolt12OfferPayment {
+                payment_hash: sha256::Hash::hash(&preimage.0),
+                federation_id: fed.id(),
+                amount: sats(1),
+            }]
+        );
+
+        Ok(())
+    })
+    .await
+}
+
+/// Runs a test with a gateway connected to a node of a mock lightning network,
+/// which has channels to the nodes `alice` and `bob`, and a hold invoice of 500
+/// sats created via the gateway for the federation.
+async fn hold_invoice_test<B>(
+    hold_expiry_secs: u32,
+    f: impl FnOnce(
+        Gateway,
+        MockLightningNode, // Alice's lightning node
+        MockLightningNode, // Bob's lightning node
+        FederationTest,
+        Bolt11Invoice,
+        Keypair, // Key authorizing the cancellation of the hold invoice
+    ) -> B,
+) -> anyhow::Result<()>
+where
+    B: Future<Output = anyhow::Result<()>>,
+{
+    let fixtures = fixtures();
+    let network = MockLightningNetwork::new();
+    let gateway_node = network.add_node("gateway");
+    let alice = network.add_node("alice");
+    let bob = network.add_node("bob");
+    alice.connect(&gateway_node, 1_000_000, 500_000);
+    bob.connect(&gateway_node, 1_000_000, 500_000);
+
+    let fed = fixtures.new_fed_degraded().await;
+    let gateway = fixtures
+        .new_gateway_with_mock_lightning(&gateway_node)
+        .await;
+    fed.connect_gateway(&gateway).await;
+
+    let auth_keypair = Keypair::new(secp256k1::SECP256K1, &mut rand::thread_rng());
+    let invoice = gateway
+        .create_hold_invoice_v2(CreateHoldInvoicePayload {
+            federation_id: fed.id(),
+            payment_hash: sha256(&[42; 32]),
+            amount: sats(500),
+            description: Bolt11InvoiceDescription::Direct(Description::new("hold".to_string())?),
+            expiry_secs: 3600,
+            hold_expiry_secs,
+            auth_pk: auth_keypair.public_key(),
+        })
+        .await?;
+
+    f(gateway, alice, bob, fed, invoice, auth_keypair).await
+}
+
+/// Waits until the gateway holds a payment to the hold invoice.
+async fn await_held_payment(
+    gateway: &Gateway,
+    federation_id: FederationId,
+    invoice: &Bolt11Invoice,
+) {
+    let payload = HoldInvoiceStatusPayload {
+        federation_id,
+        payment_hash: *invoice.payment_hash(),
+    };
+
+    while !matches!(
+        gateway.hold_invoice_status_v2(payload.clone()).await,
+        Some(HoldInvoiceStatus::Held { .. })
+    ) {
+        sleep_in_test(
+            "Waiting for the payment to be held",
+            Duration::from_millis(100),
+        )
+        .await;
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn gateway_fails_back_extra_payments_to_held_hold_invoice() -> anyhow::Result<()> {
+    hold_invoice_test(
+        600,
+        |gateway, alice, bob, fed, invoice, auth_keypair| async move {
+            let held_payment = tokio::spawn({
+                let invoice = invoice.clone();
+                async move { alice.pay(invoice, 1000, Amount::ZERO).await }
+            });
+
+            await_held_payment(&gateway, fed.id(), &invoice).await;
+
+            // A second payment to the same invoice is failed back right away instead
+            // of being stuck until its HTLC times out
+            let extra_payment = fedimint_core::runtime::timeout(
+                Duration::from_secs(30),
+                bob.pay(invoice.clone(), 1000, Amount::ZERO),
+            )
+            .await?;
+            assert!(extra_payment.is_err(), "Extra payment has been failed back");
+
+            // The first payment is still held
+            assert_matches!(
+                gateway
+                    .hold_invoice_status_v2(HoldInvoiceStatusPayload {
+                        federation_id: fed.id(),
+                        payment_hash: *invoice.payment_hash(),
+                    })
+                    .await,
+                Some(HoldInvoiceStatus::Held { .. })
+            );
+
+            gateway
+                .cancel_hold_invoice_v2(CancelHoldInvoicePayload {
+                    federation_id: fed.id(),
+                    payment_hash: *invoice.payment_hash(),
+                    auth: auth_keypair
+                        .sign_schnorr(CancelHoldInvoicePayload::message(*invoice.payment_hash())),
+                })
+                .await?;
+
+            assert!(
+                held_payment.await?.is_err(),
+                "Held payment has been refunded"
+            );
+            assert_eq!(
+                gateway
+                    .hold_invoice_status_v2(HoldInvoiceStatusPayload {
+                        federation_id: fed.id(),
+                        payment_hash: *invoice.payment_hash(),
+                    })
+                    .await,
+                Some(HoldInvoiceStatus::Cancelled)
+            );
+
+            Ok(())
+        },
+    )
+    .await
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn gateway_rejects_unauthorized_hold_invoice_cancellation() -> anyhow::Result<()> {
+    hold_invoice_test(600, |gateway, _, _, fed, invoice, _| async move {
+        let keypair = Keypair::new(secp256k1::SECP256K1, &mut rand::thread_rng());
+
+        assert!(
+            gateway
+                .cancel_hold_invoice_v2(CancelHoldInvoicePayload {
+                    federation_id: fed.id(),
+                    payment_hash: *invoice.payment_hash(),
+                    auth: keypair
+                        .sign_schnorr(CancelHoldInvoicePayload::message(*invoice.payment_hash())),
+                })
+                .await
+                .is_err()
+        );
+
+        assert_eq!(
+            gateway
+                .hold_invoice_status_v2(HoldInvoiceStatusPayload {
+                    federation_id: fed.id(),
+                    payment_hash: *invoice.payment_hash(),
+                })
+                .await,
+            Some(HoldInvoiceStatus::Open)
+        );
+
+        Ok(())
+    })
+    .await
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn gateway_refunds_payment_once_hold_expires() -> anyhow::Result<()> {
+    hold_invoice_test(1, |gateway, alice, _, fed, invoice, _| async move {
+        let held_payment = tokio::spawn({
+            let invoice = invoice.clone();
+            async move { alice.pay(invoice, 1000, Amount::ZERO).await }
+        });
+
+        await_held_payment(&gateway, fed.id(), &invoice).await;
+
+        sleep_in_test("Waiting for the hold to expire", Duration::from_secs(2)).await;
+
+        gateway.cancel_expired_hold_invoices().await?;
+
+        assert!(
+            held_payment.await?.is_err(),
+            "Held payment has been refunded"
+        );
+        assert_eq!(
+            gateway
+                .hold_invoice_status_v2(HoldInvoiceStatusPayload {
+                    federation_id: fed.id(),
+                    payment_hash: *invoice.payment_hash(),
+                })
+                .await,
+            Some(HoldInvoiceStatus::Cancelled)
+        );
+
+        Ok(())
+    })
+    .await
+}
+
+/// Creates an instance of a high-availability pair whose peer is unreachable,
+/// as if the instances were partitioned from each other
+async fn partitioned_ha_instance(
+    instance_id: &str,
+    lease_file: &std::path::Path,
+) -> anyhow::Result<Arc<HighAvailability>> {
+    let config = HaConfig {
+        peer_url: "http://127.0.0.1:1".parse()?,
+        peer_password: "password".to_string(),
+        instance_id: instance_id.to_string(),
+        prefer_active: true,
+        lease_ttl: HA_LEASE_TTL,
+        lease_file: lease_file.to_path_buf(),
+    };
+    let connectors = ConnectorRegistry::build_from_testing_env()?.bind().await?;
+
+    Ok(Arc::new(HighAvailability::new(
+        config,
+        Arc::new(ReplicationLog::new()),
+        connectors,
+    )))
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn ha_partitioned_standby_only_takes_over_after_leader_stops_renewing() -> anyhow::Result<()>
+{
+    let dir = tempfile::tempdir()?;
+    let lease_file = dir.path().join("lease.json");
+    let grace = HA_LEASE_TTL / 3;
+
+    let active = partitioned_ha_instance("a", &lease_file).await?;
+    let db = Database::new(MemDatabase::new(), ModuleDecoderRegistry::default());
+    active.wait_for_leadership(&db).await?;
+    assert_eq!(active.status().await.role, HaRole::Active);
+
+    // The standby cannot reach the active, but the shared lease keeps it from
+    // taking over as long as the active renews it
+    let standby = SharedLease::new(lease_file.clone());
+    for _ in 0..6 {
+        sleep_in_test("waiting for the next lease renewal", HA_LEASE_TTL / 3).await;
+        assert!(active.renew_lease().await);
+        assert_eq!(
+            standby.try_acquire("b", HA_LEASE_TTL, grace, false).await?,
+            None
+        );
+    }
+
+    // Once the active stops renewing the standby acquires the next epoch
+    sleep_in_test("waiting for the lease to expire", HA_LEASE_TTL + grace).await;
+    let lease = standby
+        .try_acquire("b", HA_LEASE_TTL, grace, false)
+        .await?
+        .expect("The expired lease is acquired");
+    assert_eq!(lease.epoch, 2);
+
+    // The former active is fenced off by the epoch and steps down
+    assert!(!active.renew_lease().await);
+    assert!(active.has_stepped_down());
+    assert_eq!(active.status().await.role, HaRole::Standby);
+    assert_eq!(active.status().await.lease, Some(lease));
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn ha_leader_that_cannot_renew_steps_down_before_lease_expires() -> anyhow::Result<()> {
+    let dir = tempfile::tempdir()?;
+    let lease_file = dir.path().join("lease.json");
+
+    let active = partitioned_ha_instance("a", &lease_file).await?;
+    let db = Database::new(MemDatabase::new(), ModuleDecoderRegistry::default());
+    active.wait_for_leadership(&db).await?;
+    let lease = active
+        .status()
+        .await
+        .lease
+        .expect("The active holds a lease");
+
+    // The shared storage becomes unavailable
+    dir.close()?;
+
+    // Renewals are retried as long as the lease outlives the next attempt
+    sleep_in_test("waiting for the next lease renewal", HA_LEASE_TTL / 3).await;
+    assert!(active.renew_lease().await);
+    assert!(!active.has_stepped_down());
+
+    sleep_in_test("waiting for the next lease renewal", HA_LEASE_TTL / 3).await;
+    assert!(!active.renew_lease().await);
+    assert!(active.has_stepped_down());
+    assert!(now() < lease.expires_at);
+
+    Ok(())
+}
@@ -13,8 +13,9 @@
 use fedimint_core::invite_code::InviteCode;
 use fedimint_core::{Amount, BitcoinAmountOrAll, PeerId, TieredCounts};
 use fedimint_gateway_common::{
-    DepositAddressPayload, FederationInfo, LeaveFedPayload, ReceiveEcashPayload, SetFeesPayload,
-    SpendEcashPayload, WithdrawPayload, WithdrawPreviewPayload,
+    DepositAddressPayload, FederationInfo, FederationLimits, LeaveFedPayload, ReceiveEcashPayload,
+    SetFederationLimitsPayload, SetFeesPayload, SpendEcashPayload, WithdrawPayload,
+    WithdrawPreviewPayload,
 };
 use fedimint_mint_client::OOBNotes;
 use fedimint_ui_common::UiState;
@@ -26,11 +27,24 @@
 use serde::Deserialize;
 
 use crate::{
-    DEPOSIT_ADDRESS_ROUTE, DynGatewayApi, RECEIVE_ECASH_ROUTE, SET_FEES_ROUTE, SPEND_ECASH_ROUTE,
-    WITHDRAW_CONFIRM_ROUTE, WITHDRAW_PREVIEW_ROUTE, redirect_error, redirect_success,
-    redirect_success_with_export_reminder,
+    DEPOSIT_ADDRESS_ROUTE, DynGatewayApi, RECEIVE_ECASH_ROUTE, SET_FEDERATION_LIMITS_ROUTE,
+    SET_FEES_ROUTE, SPEND_ECASH_ROUTE, WITHDRAW_CONFIRM_ROUTE, WITHDRAW_PREVIEW_ROUTE,
+    redirect_error, redirect_success, redirect_success_with_export_reminder,
 };
 
+/// Spending limits as submitted by the UI, where an empty field means the
+/// limit is not enforced
+#[derive(Deserialize)]
+pub struct SetFederationLimitsForm {
+    pub federation_id: FederationId,
+    #[serde(default)]
+    pub max_payment_msats: String,
+    #[serde(default)]
+    pub max_daily_outgoing_msats: String,
+    #[serde(default)]
+    pub max_balance_msats: String,
+}
+
 #[derive(Deserialize)]
 pub struct ReceiveEcashForm {
     pub notes: String,
@@ -200,6 +214,11 @@ pub fn render<E: Display>(
                         div class="alert alert-secondary py-1 px-2 small" {
                             "Last Backup: " strong { (last_backup_str) }
                         }
+                        @if let Some(reason) = &fed.circuit_breaker {
+                            div class="alert alert-warning py-1 px-2 small" {
+                                "Routing paused: " strong { (reason) }
+                            }
+                        }
 
                         // --- TABS ---
                         ul class="nav nav-tabs" role="tablist" {
@@ -212,6 +231,15 @@ pub fn render<E: Display>(
                                     role="tab"
                                 { "Fees" }
                             }
+                            li class="nav-item" role="presentation" {
+                                button class="nav-link"
+                                    id={(format!("limits-tab-{}", fed.federation_id))}
+                                    data-bs-toggle="tab"
+                                    data-bs-target={(format!("#limits-tab-pane-{}", fed.federation_id))}
+                                    type="button"
+                                    role="tab"
+                                { "Limits" }
+                            }
                             li class="nav-item" role="presentation" {
                                 button class="nav-link"
                                     id={(format!("deposit-tab-{}", fed.federation_id))}
@@ -394,6 +422,70 @@ pub fn render<E: Display>(
                                 }
                             }
 
+                            // ──────────────────────────────────────────
+                            //   TAB: LIMITS
+                            // ──────────────────────────────────────────
+                            div class="tab-pane fade"
+                                id={(format!("limits-tab-pane-{}", fed.federation_id))}
+                                role="tabpanel"
+                                aria-labelledby={(format!("limits-tab-{}", fed.federation_id))} {
+
+                                form
+                                    method="post"
+                                    action={(SET_FEDERATION_LIMITS_ROUTE)}
+                                {
+                                    input type="hidden" name="federation_id" value=(fed.federation_id.to_string());
+                                    table class="table table-sm mb-2" {
+                                        tbody {
+                                            tr {
+                                                th {
+                                                    "Max Payment (msats) "
+                                                    span class="text-muted" data-bs-toggle="tooltip" title="Largest single incoming or outgoing payment the gateway will route for this federation" { "ⓘ" }
+                                                }
+                                                td {
+                                                    input type="number"
+                                                        class="form-control form-control-sm"
+                                                        name="max_payment_msats"
+                                                        placeholder="Unlimited"
+                                                        min="0"
+                                                        value=(limit_msats(fed.limits.max_payment));
+                                                }
+                                            }
+                                            tr {
+                                                th {
+                                                    "Max Daily Outgoing (msats) "
+                                                    span class="text-muted" data-bs-toggle="tooltip" title="Total amount of outgoing payments the gateway will route for this federation over the last 24 hours" { "ⓘ" }
+                                                }
+                                                td {
+                                                    input type="number"
+                                                        class="form-control form-control-sm"
+                                                        name="max_daily_outgoing_msats"
+                                                        placeholder="Unlimited"
+                                                        min="0"
+                                                        value=(limit_msats(fed.limits.max_daily_outgoing));
+                                                }
+                                            }
+                                            tr {
+                                                th {
+                                                    "Max Ecash Balance (msats) "
+                                                    span class="text-muted" data-bs-toggle="tooltip" title="Outgoing payments are refused if they would push the gateway's ecash balance in this federation above this amount" { "ⓘ" }
+                                                }
+                                                td {
+                                                    input type="number"
+                                                        class="form-control form-control-sm"
+                                                        name="max_balance_msats"
+                                                        placeholder="Unlimited"
+                                                        min="0"
+                                                        value=(limit_msats(fed.limits.max_balance));
+                                                }
+                                            }
+                                        }
+                                    }
+
+                                    button type="submit" class="btn btn-sm btn-primary" { "Save Limits" }
+                                }
+                            }
+
                             // ──────────────────────────────────────────
                             //   TAB: DEPOSIT
                             // ──────────────────────────────────────────
@@ -664,6 +756,24 @@ pub fn render<E: Display>(
     )
 }
 
+fn limit_msats(limit: Option<Amount>) -> String {
+    limit
+        .map(|amount| amount.msats.to_string())
+        .unwrap_or_default()
+}
+
+fn parse_limit_msats(value: &str) -> Result<Option<Amount>, String> {
+    let value = value.trim();
+    if value.is_empty() {
+        return Ok(None);
+    }
+
+    value
+        .parse::<u64>()
+        .map(|msats| Some(Amount::from_msats(msats)))
+        .map_err(|_| format!("Invalid amount: {value}"))
+}
+
 fn time_ago(t: SystemTime) -> String {
     let now = fedimint_core::time::now();
     let diff = match now.duration_since(t) {
@@ -724,6 +834,39 @@ pub async fn set_fees_handler<E: Display>(
     }
 }
 
+pub async fn set_federation_limits_handler<E: Display>(
+    State(state): State<UiState<DynGatewayApi<E>>>,
+    _auth: UserAuth,
+    Form(form): Form<SetFederationLimitsForm>,
+) -> impl IntoResponse {
+    let limits = match (
+        parse_limit_msats(&form.max_payment_msats),
+        parse_limit_msats(&form.max_daily_outgoing_msats),
+        parse_limit_msats(&form.max_balance_msats),
+    ) {
+        (Ok(max_payment), Ok(max_daily_outgoing), Ok(max_balance)) => FederationLimits {
+            max_payment,
+            max_daily_outgoing,
+            max_balance,
+        },
+        (Err(err), _, _) | (_, Err(err), _) | (_, _, Err(err)) => {
+            return redirect_error(format!("Failed to update limits: {err}")).into_response();
+        }
+    };
+
+    match state
+        .api
+        .handle_set_federation_limits_msg(SetFederationLimitsPayload {
+            federation_id: form.federation_id,
+            limits,
+        })
+        .await
+    {
+        Ok(_) => redirect_success("Successfully set limits".to_string()).into_response(),
+        Err(err) => redirect_error(format!("Failed to update limits: {err}")).into_response(),
+    }
+}
+
 pub async fn deposit_address_handler<E: Display>(
     State(state): State<UiState<DynGatewayApi<E>>>,
     _auth: UserAuth,
@@ -34,8 +34,8 @@
     LightningMode, ListTransactionsPayload, ListTransactionsResponse, MnemonicResponse,
     OpenChannelRequest, PayInvoiceForOperatorPayload, PayOfferPayload, PayOfferResponse,
     PaymentLogPayload, PaymentLogResponse, PaymentSummaryPayload, PaymentSummaryResponse,
-    ReceiveEcashPayload, ReceiveEcashResponse, SendOnchainRequest, SetFeesPayload,
-    SetMnemonicPayload, SpendEcashPayload, SpendEcashResponse, WithdrawPayload,
+    ReceiveEcashPayload, ReceiveEcashResponse, SendOnchainRequest, SetFederationLimitsPayload,
+    SetFeesPayload, SetMnemonicPayload, SpendEcashPayload, SpendEcashResponse, WithdrawPayload,
     WithdrawPreviewPayload, WithdrawPreviewResponse, WithdrawResponse,
 };
 use fedimint_ln_common::contracts::Preimage;
@@ -52,8 +52,9 @@
 
 use crate::connect_fed::connect_federation_handler;
 use crate::federation::{
-    deposit_address_handler, leave_federation_handler, receive_ecash_handler, set_fees_handler,
-    spend_ecash_handler, withdraw_confirm_handler, withdraw_preview_handler,
+    deposit_address_handler, leave_federation_handler, receive_ecash_handler,
+    set_federation_limits_handler, set_fees_handler, spend_ecash_handler, withdraw_confirm_handler,
+    withdraw_preview_handler,
 };
 use crate::lightning::{
     channels_fragment_handler, close_channel_handler, create_bolt11_invoice_handler,
@@ -73,6 +74,7 @@
 pub(crate) const LEAVE_FEDERATION_ROUTE: &str = "/ui/federations/{id}/leave";
 pub(crate) const CONNECT_FEDERATION_ROUTE: &str = "/ui/federations/join";
 pub(crate) const SET_FEES_ROUTE: &str = "/ui/federation/set-fees";
+pub(crate) const SET_FEDERATION_LIMITS_ROUTE: &str = "/ui/federation/set-limits";
 pub(crate) const SEND_ONCHAIN_ROUTE: &str = "/ui/wallet/send";
 pub(crate) const WALLET_FRAGMENT_ROUTE: &str = "/ui/wallet/fragment";
 pub(crate) const LN_ONCHAIN_ADDRESS_ROUTE: &str = "/ui/wallet/receive";
@@ -155,6 +157,11 @@ async fn handle_connect_federation(
 
     async fn handle_set_fees_msg(&self, payload: SetFeesPayload) -> Result<(), Self::Error>;
 
+    async fn handle_set_federation_limits_msg(
+        &self,
+        payload: SetFederationLimitsPayload,
+    ) -> Result<(), Self::Error>;
+
     async fn handle_mnemonic_msg(&self) -> Result<MnemonicResponse, Self::Error>;
 
     async fn handle_open_channel_msg(
@@ -474,6 +481,10 @@ pub fn router<E: Display + Send + Sync + std::fmt::Debug + 'static>(
         .route(LEAVE_FEDERATION_ROUTE, post(leave_federation_handler))
         .route(CONNECT_FEDERATION_ROUTE, post(connect_federation_handler))
         .route(SET_FEES_ROUTE, post(set_fees_handler))
+        .route(
+            SET_FEDERATION_LIMITS_ROUTE,
+            post(set_federation_limits_handler),
+        )
         .route(SEND_ONCHAIN_ROUTE, post(send_onchain_handler))
         .route(
             LN_ONCHAIN_ADDRESS_ROUTE,
@@ -33,8 +33,8 @@
     CloseChannelsWithPeerRequest, CloseChannelsWithPeerResponse, CreateInvoiceRequest,
     CreateInvoiceResponse, GetBalancesResponse, GetLnOnchainAddressResponse, GetNodeInfoResponse,
     GetRouteHintsResponse, InterceptPaymentRequest, InterceptPaymentResponse, InvoiceDescription,
-    OpenChannelRequest, OpenChannelResponse, PayInvoiceResponse, PaymentAction, SendOnchainRequest,
-    SendOnchainResponse,
+    OfferPayment, OpenChannelRequest, OpenChannelResponse, PayInvoiceResponse, PaymentAction,
+    RouteProbe, SendOnchainRequest, SendOnchainResponse,
 };
 
 pub struct GatewayLdkClient {
@@ -880,6 +880,59 @@ async fn pay_offer(
         }
     }
 
+    async fn probe_route(&self, invoice: Bolt11Invoice) -> Result<RouteProbe, LightningRpcError> {
+        // Probing also trains LDK's scorer, which improves routing of the subsequent
+        // payment. LDK only reports whether a route was found and the probes were
+        // sent, but not whether they reached the payee, hence the likelihood of
+        // success is unknown unless no route was found at all.
+        let success_likelihood = match self.node.bolt11_payment().send_probes(&invoice) {
+            Ok(()) => None,
+            Err(err) => {
+                debug!(target: LOG_LIGHTNING, err = %err.fmt_compact(), payment_hash = %invoice.payment_hash(), "LDK failed to send probes");
+                Some(0)
+            }
+        };
+
+        Ok(RouteProbe {
+            fee: None,
+            success_likelihood,
+        })
+    }
+
+    async fn list_offer_payments(
+        &self,
+        since_secs: u64,
+    ) -> Result<Vec<OfferPayment>, LightningRpcError> {
+        let mut payments = self
+            .node
+            .list_payments_with_filter(|details| {
+                details.direction == PaymentDirection::Inbound
+                    && details.status == PaymentStatus::Succeeded
+                    && details.latest_update_timestamp >= since_secs
+                    && matches!(details.kind, PaymentKind::Bolt12Offer { .. })
+            })
+            .iter()
+            .filter_map(|details| match &details.kind {
+                PaymentKind::Bolt12Offer {
+                    hash: Some(hash),
+                    offer_id,
+                    ..
+                } => Some(OfferPayment {
+                    offer_id: hex::encode(offer_id.0),
+                    payment_hash: sha256::Hash::from_slice(&hash.0)
+                        .expect("Failed to convert payment hash"),
+                    amount: Amount::from_msats(details.amount_msat?),
+                    timestamp_secs: details.latest_update_timestamp,
+                }),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        payments.sort_by_key(|payment| payment.timestamp_secs);
+
+        Ok(payments)
+    }
+
     fn sync_wallet(&self) -> Result<(), LightningRpcError> {
         block_in_place(|| {
             let _ = self.node.sync_wallets();
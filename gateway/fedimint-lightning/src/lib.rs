@@ -76,6 +76,8 @@ pub enum LightningRpcError {
     InvalidMetadata { failure_reason: String },
     #[error("Bolt12 Error: {failure_reason}")]
     Bolt12Error { failure_reason: String },
+    #[error("Failed to probe route: {failure_reason}")]
+    FailedToProbeRoute { failure_reason: String },
 }
 
 /// Represents an active connection to the lightning node.
@@ -251,6 +253,18 @@ async fn pay_offer(
         payer_note: Option<String>,
     ) -> Result<Preimage, LightningRpcError>;
 
+    /// Probes the route to the payee of an invoice without settling a payment
+    /// and estimates the fee and likelihood of successfully paying it.
+    async fn probe_route(&self, invoice: Bolt11Invoice) -> Result<RouteProbe, LightningRpcError>;
+
+    /// Lists the successful payments this node has received for BOLT12 offers
+    /// it created via [`ILnRpcClient::create_offer`] that were last updated at
+    /// or after `since_secs`, ordered by their timestamp.
+    async fn list_offer_payments(
+        &self,
+        since_secs: u64,
+    ) -> Result<Vec<OfferPayment>, LightningRpcError>;
+
     fn sync_wallet(&self) -> Result<(), LightningRpcError>;
 }
 
@@ -410,6 +424,37 @@ pub struct ListChannelsResponse {
     pub channels: Vec<ChannelInfo>,
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RouteProbe {
+    /// The estimated fee to route the payment, if the backend is able to
+    /// determine it
+    pub fee: Option<Amount>,
+    /// The estimated likelihood of a successful payment in percent, if the
+    /// backend is able to determine it
+    pub success_likelihood: Option<u8>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct OfferPayment {
+    /// The hex encoded id of the offer the payment was made to, as returned by
+    /// [`offer_id`]
+    pub offer_id: String,
+    pub payment_hash: sha256::Hash,
+    pub amount: Amount,
+    pub timestamp_secs: u64,
+}
+
+/// Returns the hex encoded id of a BOLT12 offer
+pub fn offer_id(offer: &str) -> Result<String, LightningRpcError> {
+    let offer = lightning::offers::offer::Offer::from_str(offer).map_err(|_| {
+        LightningRpcError::Bolt12Error {
+            failure_reason: "Failed to parse Bolt12 Offer".to_string(),
+        }
+    })?;
+
+    Ok(hex::encode(offer.id().0))
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct GetBalancesResponse {
     pub onchain_balance_sats: u64,
@@ -664,6 +709,21 @@ async fn pay_offer(
         )
     }
 
+    async fn probe_route(&self, invoice: Bolt11Invoice) -> Result<RouteProbe, LightningRpcError> {
+        tracked_call!(self, "probe_route", self.inner.probe_route(invoice).await)
+    }
+
+    async fn list_offer_payments(
+        &self,
+        since_secs: u64,
+    ) -> Result<Vec<OfferPayment>, LightningRpcError> {
+        tracked_call!(
+            self,
+            "list_offer_payments",
+            self.inner.list_offer_payments(since_secs).await
+        )
+    }
+
     fn sync_wallet(&self) -> Result<(), LightningRpcError> {
         tracked_call!(self, "sync_wallet", self.inner.sync_wallet())
     }
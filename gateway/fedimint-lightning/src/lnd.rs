@@ -19,6 +19,7 @@
 use fedimint_ln_common::route_hints::{RouteHint, RouteHintHop};
 use fedimint_logging::LOG_LIGHTNING;
 use hex::ToHex;
+use lightning_invoice::Bolt11Invoice;
 use secp256k1::PublicKey;
 use tokio::sync::mpsc;
 use tokio_stream::wrappers::ReceiverStream;
@@ -35,11 +36,11 @@
     ChanInfoRequest, ChannelBalanceRequest, ChannelPoint, CloseChannelRequest, ConnectPeerRequest,
     GetInfoRequest, Invoice, InvoiceSubscription, LightningAddress, ListChannelsRequest,
     ListInvoiceRequest, ListPaymentsRequest, ListPeersRequest, OpenChannelRequest,
-    SendCoinsRequest, WalletBalanceRequest,
+    PaymentFailureReason, SendCoinsRequest, WalletBalanceRequest,
 };
 use tonic_lnd::routerrpc::{
-    CircuitKey, ForwardHtlcInterceptResponse, ResolveHoldForwardAction, SendPaymentRequest,
-    TrackPaymentRequest,
+    CircuitKey, ForwardHtlcInterceptResponse, ResolveHoldForwardAction, RouteFeeRequest,
+    SendPaymentRequest, TrackPaymentRequest,
 };
 use tonic_lnd::tonic::Code;
 use tonic_lnd::walletrpc::AddrRequest;
@@ -54,14 +55,19 @@
     CloseChannelsWithPeerRequest, CloseChannelsWithPeerResponse, CreateInvoiceRequest,
     CreateInvoiceResponse, GetBalancesResponse, GetInvoiceRequest, GetInvoiceResponse,
     GetLnOnchainAddressResponse, GetNodeInfoResponse, GetRouteHintsResponse,
-    InterceptPaymentRequest, InterceptPaymentResponse, InvoiceDescription, OpenChannelResponse,
-    PayInvoiceResponse, PaymentAction, SendOnchainRequest, SendOnchainResponse,
+    InterceptPaymentRequest, InterceptPaymentResponse, InvoiceDescription, OfferPayment,
+    OpenChannelResponse, PayInvoiceResponse, PaymentAction, RouteProbe, SendOnchainRequest,
+    SendOnchainResponse,
 };
 
 type HtlcSubscriptionSender = mpsc::Sender<InterceptPaymentRequest>;
 
 const LND_PAYMENT_TIMEOUT_SECONDS: i32 = 180;
 
+/// Maximum time LND may spend sending probe payments to estimate a route fee.
+/// Clients wait for the probe before they pay, hence this needs to be short.
+const LND_PROBE_TIMEOUT_SECONDS: u32 = 3;
+
 #[derive(Clone)]
 pub struct GatewayLndClient {
     /// LND client
@@ -1622,6 +1628,53 @@ async fn pay_offer(
         })
     }
 
+    async fn probe_route(&self, invoice: Bolt11Invoice) -> Result<RouteProbe, LightningRpcError> {
+        let mut client = self.connect().await?;
+
+        let response = client
+            .router()
+            .estimate_route_fee(RouteFeeRequest {
+                payment_request: invoice.to_string(),
+                timeout: LND_PROBE_TIMEOUT_SECONDS,
+                ..Default::default()
+            })
+            .await
+            .map_err(|status| LightningRpcError::FailedToProbeRoute {
+                failure_reason: format!("Failed to estimate route fee {status:?}"),
+            })?
+            .into_inner();
+
+        if response.failure_reason != PaymentFailureReason::FailureReasonNone as i32 {
+            debug!(
+                target: LOG_LIGHTNING,
+                payment_hash = %invoice.payment_hash(),
+                failure_reason = response.failure_reason,
+                "LND probe did not reach the payee",
+            );
+            return Ok(RouteProbe {
+                fee: None,
+                success_likelihood: Some(0),
+            });
+        }
+
+        // LND does not report a likelihood of success for the probed route
+        Ok(RouteProbe {
+            fee: u64::try_from(response.routing_fee_msat)
+                .ok()
+                .map(Amount::from_msats),
+            success_likelihood: None,
+        })
+    }
+
+    async fn list_offer_payments(
+        &self,
+        _since_secs: u64,
+    ) -> Result<Vec<OfferPayment>, LightningRpcError> {
+        Err(LightningRpcError::Bolt12Error {
+            failure_reason: "LND Does not support Bolt12".to_string(),
+        })
+    }
+
     fn sync_wallet(&self) -> Result<(), LightningRpcError> {
         // There is nothing explicit needed to do for syncing an LND node
         Ok(())
@@ -125,6 +125,44 @@ impl Event for IncomingPaymentFailed {
     const PERSISTENCE: EventPersistence = EventPersistence::Persistent;
 }
 
+/// Event that is emitted when the gateway holds an incoming payment for a hold
+/// invoice until the recipient settles or cancels it.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct IncomingPaymentHeld {
+    /// The payment image of the hold invoice.
+    pub payment_image: PaymentImage,
+
+    /// The amount requested in the invoice.
+    pub invoice_amount: Amount,
+
+    /// The time in seconds since the unix epoch at which the payment is
+    /// cancelled unless the recipient settles it.
+    pub expiration: u64,
+}
+
+impl Event for IncomingPaymentHeld {
+    const MODULE: Option<ModuleKind> = Some(fedimint_lnv2_common::KIND);
+    const KIND: EventKind = EventKind::from_static("incoming-payment-held");
+    const PERSISTENCE: EventPersistence = EventPersistence::Persistent;
+}
+
+/// Event that is emitted when a held incoming payment is cancelled and the
+/// payer is refunded.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct HeldPaymentCancelled {
+    /// The payment image of the hold invoice.
+    pub payment_image: PaymentImage,
+
+    /// The reason the held payment was cancelled.
+    pub reason: String,
+}
+
+impl Event for HeldPaymentCancelled {
+    const MODULE: Option<ModuleKind> = Some(fedimint_lnv2_common::KIND);
+    const KIND: EventKind = EventKind::from_static("held-payment-cancelled");
+    const PERSISTENCE: EventPersistence = EventPersistence::Persistent;
+}
+
 /// Event that is emitted when a preimage is revealed to the Lightning network.
 /// Only emitted for payments that are received from an external Lightning node,
 /// not internal swaps.
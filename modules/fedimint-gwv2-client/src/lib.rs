@@ -13,7 +13,10 @@
 use async_trait::async_trait;
 use bitcoin::hashes::sha256;
 use bitcoin::secp256k1::Message;
-use events::{IncomingPaymentStarted, OutgoingPaymentStarted};
+use events::{
+    HeldPaymentCancelled, IncomingPaymentHeld, IncomingPaymentStarted, OutgoingPaymentFailed,
+    OutgoingPaymentStarted,
+};
 use fedimint_api_client::api::DynModuleApi;
 use fedimint_client::ClientHandleArc;
 use fedimint_client_module::module::init::{ClientModuleInit, ClientModuleInitArgs};
@@ -35,7 +38,7 @@
 use fedimint_core::time::now;
 use fedimint_core::util::Spanned;
 use fedimint_core::{Amount, PeerId, apply, async_trait_maybe_send, secp256k1};
-use fedimint_lightning::{InterceptPaymentResponse, LightningRpcError};
+use fedimint_lightning::{InterceptPaymentResponse, LightningRpcError, PaymentAction};
 use fedimint_lnv2_common::config::LightningClientConfig;
 use fedimint_lnv2_common::contracts::{IncomingContract, PaymentImage};
 use fedimint_lnv2_common::gateway_api::SendPaymentPayload;
@@ -46,6 +49,7 @@
 use lightning_invoice::Bolt11Invoice;
 use receive_sm::{ReceiveSMState, ReceiveStateMachine};
 use secp256k1::schnorr::Signature;
+pub use send_sm::Cancelled;
 use send_sm::{SendSMState, SendStateMachine};
 use serde::{Deserialize, Serialize};
 use tpe::{AggregatePublicKey, PublicKeyShare};
@@ -100,6 +104,7 @@ async fn init(&self, args: &ClientModuleInitArgs<Self>) -> anyhow::Result<Self::
                 .clone()
                 .to_secp_key(fedimint_core::secp256k1::SECP256K1),
             gateway: self.gateway.clone(),
+            outgoing_limits_lock: Arc::default(),
         })
     }
 }
@@ -113,6 +118,9 @@ pub struct GatewayClientModuleV2 {
     pub module_api: DynModuleApi,
     pub keypair: Keypair,
     pub gateway: Arc<dyn IGatewayClientV2>,
+    /// Serializes the limit check of outgoing payments with the logging of
+    /// their start, such that concurrent payments can not exceed the limits
+    outgoing_limits_lock: Arc<futures::lock::Mutex<()>>,
 }
 
 #[derive(Debug, Clone)]
@@ -238,6 +246,16 @@ fn operation_id(&self) -> OperationId {
     }
 }
 
+/// Direction of a payment from the perspective of the federation's ecash
+/// balance held by the gateway
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum PaymentDirection {
+    /// The gateway pays a Lightning invoice and receives ecash
+    Outgoing,
+    /// The gateway receives a Lightning payment and spends ecash
+    Incoming,
+}
+
 #[derive(Debug, Clone, Eq, PartialEq, Hash, Serialize, Deserialize, Decodable, Encodable)]
 pub enum FinalReceiveState {
     Rejected,
@@ -319,6 +337,33 @@ pub async fn send_payment(
             .min_contract_amount(&payload.federation_id, amount)
             .await?;
 
+        // The limits are only checked once before we start the state machine as
+        // they may have changed by the time the state machine is restarted. The
+        // lock is held until the start of the payment is logged, such that its
+        // amount counts towards the volume checked by concurrent payments.
+        let limits_guard = self.outgoing_limits_lock.lock().await;
+
+        // A concurrent request for the same contract may have started the payment
+        // while we were waiting for the lock
+        if self.client_ctx.operation_exists(operation_id).await {
+            drop(limits_guard);
+
+            return Ok(self.subscribe_send(operation_id).await);
+        }
+
+        let state = match self
+            .gateway
+            .check_payment_limits(
+                &self.federation_id,
+                PaymentDirection::Outgoing,
+                payload.contract.amount,
+            )
+            .await
+        {
+            Ok(()) => SendSMState::Sending,
+            Err(error) => SendSMState::Cancelled(Cancelled::LimitExceeded(error)),
+        };
+
         let send_sm = GatewayClientStateMachinesV2::Send(SendStateMachine {
             common: SendSMCommon {
                 operation_id,
@@ -329,7 +374,7 @@ pub async fn send_payment(
                 invoice: payload.invoice,
                 claim_keypair: self.keypair,
             },
-            state: SendSMState::Sending,
+            state: state.clone(),
         });
 
         let mut dbtx = self.client_ctx.module_db().begin_transaction().await;
@@ -356,8 +401,23 @@ pub async fn send_payment(
                 },
             )
             .await;
+
+        if let SendSMState::Cancelled(error) = state {
+            self.client_ctx
+                .log_event(
+                    &mut dbtx,
+                    OutgoingPaymentFailed {
+                        payment_image: payload.contract.payment_image.clone(),
+                        error,
+                    },
+                )
+                .await;
+        }
+
         dbtx.commit_tx().await;
 
+        drop(limits_guard);
+
         Ok(self.subscribe_send(operation_id).await)
     }
 
@@ -414,6 +474,15 @@ pub async fn relay_incoming_htlc(
             return Ok(());
         }
 
+        self.gateway
+            .check_payment_limits(
+                &self.federation_id,
+                PaymentDirection::Incoming,
+                contract.commitment.amount,
+            )
+            .await
+            .map_err(|reason| anyhow!(reason))?;
+
         let refund_keypair = self.keypair;
 
         let client_output = ClientOutput::<LightningOutput> {
@@ -492,6 +561,15 @@ pub async fn relay_direct_swap(
             return Ok(self.await_receive(operation_id).await);
         }
 
+        self.gateway
+            .check_payment_limits(
+                &self.federation_id,
+                PaymentDirection::Incoming,
+                contract.commitment.amount,
+            )
+            .await
+            .map_err(|reason| anyhow!(reason))?;
+
         let refund_keypair = self.keypair;
 
         let client_output = ClientOutput::<LightningOutput> {
@@ -547,6 +625,59 @@ pub async fn relay_direct_swap(
         Ok(self.await_receive(operation_id).await)
     }
 
+    /// Records that an incoming HTLC for a hold invoice is held until the
+    /// recipient settles it via [`Self::relay_incoming_htlc`] or it is
+    /// cancelled via [`Self::cancel_held_htlc`].
+    pub async fn hold_incoming_htlc(
+        &self,
+        payment_hash: sha256::Hash,
+        amount_msat: u64,
+        expiration: u64,
+    ) {
+        let mut dbtx = self.client_ctx.module_db().begin_transaction().await;
+        self.client_ctx
+            .log_event(
+                &mut dbtx,
+                IncomingPaymentHeld {
+                    payment_image: PaymentImage::Hash(payment_hash),
+                    invoice_amount: Amount::from_msats(amount_msat),
+                    expiration,
+                },
+            )
+            .await;
+        dbtx.commit_tx().await;
+    }
+
+    /// Cancels a held incoming HTLC such that the payer is refunded.
+    pub async fn cancel_held_htlc(
+        &self,
+        payment_hash: sha256::Hash,
+        incoming_chan_id: u64,
+        htlc_id: u64,
+        reason: String,
+    ) {
+        self.gateway
+            .complete_htlc(InterceptPaymentResponse {
+                incoming_chan_id,
+                htlc_id,
+                payment_hash,
+                action: PaymentAction::Cancel,
+            })
+            .await;
+
+        let mut dbtx = self.client_ctx.module_db().begin_transaction().await;
+        self.client_ctx
+            .log_event(
+                &mut dbtx,
+                HeldPaymentCancelled {
+                    payment_image: PaymentImage::Hash(payment_hash),
+                    reason,
+                },
+            )
+            .await;
+        dbtx.commit_tx().await;
+    }
+
     pub async fn await_receive(&self, operation_id: OperationId) -> FinalReceiveState {
         let mut stream = self.notifier.subscribe(operation_id).await;
 
@@ -649,6 +780,16 @@ async fn min_contract_amount(
         amount: u64,
     ) -> anyhow::Result<Amount>;
 
+    /// Checks a payment against the operator's spending limits for the
+    /// federation and whether routing for it is paused by the circuit
+    /// breaker. Returns the reason if the payment has to be refused.
+    async fn check_payment_limits(
+        &self,
+        federation_id: &FederationId,
+        direction: PaymentDirection,
+        amount: Amount,
+    ) -> Result<(), String>;
+
     /// Check if this invoice was created using LNv1 and if the gateway is
     /// connected to the target federation.
     async fn is_lnv1_invoice(&self, invoice: &Bolt11Invoice) -> Option<Spanned<ClientHandleArc>>;
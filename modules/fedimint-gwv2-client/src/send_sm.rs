@@ -93,6 +93,7 @@ pub enum Cancelled {
     Refunded,
     Failure,
     LightningRpcError(String),
+    LimitExceeded(String),
 }
 
 #[cfg_attr(doc, aquamarine::aquamarine)]
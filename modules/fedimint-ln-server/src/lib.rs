@@ -59,6 +59,7 @@
 use fedimint_logging::LOG_MODULE_LN;
 use fedimint_server_core::bitcoin_rpc::ServerBitcoinRpcMonitor;
 use fedimint_server_core::config::PeerHandleOps;
+use fedimint_server_core::guardian_signer::{GuardianSecret, ModuleGuardianSigner};
 use fedimint_server_core::{
     ConfigGenModuleArgs, EnvVarDoc, ServerModule, ServerModuleInit, ServerModuleInitArgs,
 };
@@ -68,7 +69,7 @@
 use strum::IntoEnumIterator;
 use threshold_crypto::poly::Commitment;
 use threshold_crypto::serde_impl::SerdeSecret;
-use threshold_crypto::{PublicKeySet, SecretKeyShare};
+use threshold_crypto::{Ciphertext, DecryptionShare, G1Affine, PublicKeySet, SecretKeyShare};
 use tracing::{debug, error, info, info_span, trace, warn};
 
 use crate::db::{
@@ -235,6 +236,7 @@ async fn init(&self, args: &ServerModuleInitArgs<Self>) -> anyhow::Result<Self::
             cfg: args.cfg().to_typed()?,
             our_peer_id: args.our_peer_id(),
             server_bitcoin_rpc_monitor: args.server_bitcoin_rpc_monitor(),
+            guardian_signer: args.guardian_signer().clone(),
         })
     }
 
@@ -260,7 +262,7 @@ fn trusted_dealer_gen(
                             network: NetworkLegacyEncodingWrapper(args.network),
                         },
                         private: LightningConfigPrivate {
-                            threshold_sec_key: threshold_crypto::serde_impl::SerdeSecret(sk),
+                            threshold_sec_key: Some(threshold_crypto::serde_impl::SerdeSecret(sk)),
                         },
                     }
                     .to_erased(),
@@ -283,7 +285,7 @@ async fn distributed_gen(
                 network: NetworkLegacyEncodingWrapper(args.network),
             },
             private: LightningConfigPrivate {
-                threshold_sec_key: SerdeSecret(SecretKeyShare::from_mut(&mut sks)),
+                threshold_sec_key: Some(SerdeSecret(SecretKeyShare::from_mut(&mut sks))),
             },
         };
 
@@ -292,7 +294,14 @@ async fn distributed_gen(
 
     fn validate_config(&self, identity: &PeerId, config: ServerModuleConfig) -> anyhow::Result<()> {
         let config = config.to_typed::<LightningConfig>()?;
-        if config.private.threshold_sec_key.public_key_share()
+
+        // The secret key is missing once it has been exported to a remote
+        // guardian signer, which verifies it on startup instead
+        let Some(threshold_sec_key) = config.private.threshold_sec_key else {
+            return Ok(());
+        };
+
+        if threshold_sec_key.public_key_share()
             != config
                 .consensus
                 .threshold_pub_keys
@@ -303,6 +312,24 @@ fn validate_config(&self, identity: &PeerId, config: ServerModuleConfig) -> anyh
         Ok(())
     }
 
+    fn guardian_keys(
+        &self,
+        config: &ServerModuleConfig,
+    ) -> anyhow::Result<BTreeMap<String, GuardianSecret>> {
+        Ok(config
+            .to_typed::<LightningConfig>()?
+            .private
+            .threshold_sec_key
+            .into_iter()
+            .map(|sk| {
+                (
+                    THRESHOLD_KEY_NAME.to_string(),
+                    GuardianSecret::Bls12381(sk.0.0.0),
+                )
+            })
+            .collect())
+    }
+
     fn get_client_config(
         &self,
         config: &ServerModuleConsensusConfig,
@@ -340,11 +367,16 @@ fn used_db_prefixes(&self) -> Option<BTreeSet<u8>> {
 ///
 /// [Outgoing]: fedimint_ln_common::contracts::outgoing::OutgoingContract
 /// [Incoming]: fedimint_ln_common::contracts::incoming::IncomingContract
+/// Name of the threshold decryption secret key share in the guardian signer
+const THRESHOLD_KEY_NAME: &str = "threshold_sec_key";
+
 #[derive(Debug)]
 pub struct Lightning {
     cfg: LightningConfig,
     our_peer_id: PeerId,
     server_bitcoin_rpc_monitor: ServerBitcoinRpcMonitor,
+    /// Creates the preimage decryption shares with our secret key share
+    guardian_signer: ModuleGuardianSigner,
 }
 
 #[apply(async_trait_maybe_send!)]
@@ -692,11 +724,8 @@ async fn process_output<'a, 'b>(
                         .expect("offer exists if output is valid");
 
                     let decryption_share = self
-                        .cfg
-                        .private
-                        .threshold_sec_key
-                        .decrypt_share(&incoming.encrypted_preimage.0)
-                        .expect("We checked for decryption share validity on contract creation");
+                        .decrypt_share_with_guardian_signer(&incoming.encrypted_preimage.0)
+                        .await;
 
                     dbtx.insert_new_entry(
                         &ProposeDecryptionShareKey(contract.contract.contract_id()),
@@ -977,6 +1006,52 @@ fn api_endpoints(&self) -> Vec<ApiEndpoint<Self>> {
 }
 
 impl Lightning {
+    /// Every guardian has to create its decryption share to stay in consensus,
+    /// so if the signer is unavailable or returns an invalid share we keep
+    /// retrying until it is back.
+    async fn decrypt_share_with_guardian_signer(&self, ciphertext: &Ciphertext) -> DecryptionShare {
+        let u_bytes: [u8; 48] = ciphertext.to_bytes()[..48]
+            .try_into()
+            .expect("Ciphertext starts with a compressed G1 point");
+        let u = Option::<G1Affine>::from(G1Affine::from_compressed(&u_bytes))
+            .expect("We checked for ciphertext validity on offer creation");
+        let pk_share = self
+            .cfg
+            .consensus
+            .threshold_pub_keys
+            .public_key_share(self.our_peer_id.to_usize());
+
+        loop {
+            match self
+                .guardian_signer
+                .multiply_g1(THRESHOLD_KEY_NAME, u)
+                .await
+            {
+                Ok(point) => {
+                    if let Some(share) = DecryptionShare::from_bytes(&point.to_compressed())
+                        && pk_share.verify_decryption_share(&share, ciphertext)
+                    {
+                        return share;
+                    }
+
+                    warn!(
+                        target: LOG_MODULE_LN,
+                        "Guardian signer returned an invalid decryption share, retrying..."
+                    );
+                }
+                Err(e) => {
+                    warn!(
+                        target: LOG_MODULE_LN,
+                        err = %e.fmt_compact_anyhow(),
+                        "Failed to create decryption share with guardian signer, retrying..."
+                    );
+                }
+            }
+
+            sleep(Duration::from_secs(1)).await;
+        }
+    }
+
     fn get_block_count(&self) -> anyhow::Result<u64> {
         self.server_bitcoin_rpc_monitor
             .status()
@@ -19,6 +19,10 @@ enum Opts {
         invoice: Bolt11Invoice,
         #[arg(long)]
         gateway: Option<SafeUrl>,
+        /// Ask these gateways to probe the route to the payee and pay via the
+        /// cheapest one
+        #[arg(long, num_args = 1.., conflicts_with = "gateway")]
+        cheapest_of: Vec<SafeUrl>,
     },
     /// Await the final state of the send operation.
     AwaitSend { operation_id: OperationId },
@@ -35,6 +39,12 @@ enum Opts {
     /// Lnurl subcommands
     #[command(subcommand)]
     Lnurl(LnurlOpts),
+    /// Bolt12 subcommands
+    #[command(subcommand)]
+    Bolt12(Bolt12Opts),
+    /// Hold invoice subcommands
+    #[command(subcommand)]
+    Hold(HoldOpts),
     /// Gateway subcommands
     #[command(subcommand)]
     Gateways(GatewaysOpts),
@@ -50,6 +60,42 @@ enum LnurlOpts {
     },
 }
 
+#[derive(Clone, Subcommand, Serialize)]
+enum Bolt12Opts {
+    /// Request a reusable offer from a gateway. Payments to the offer are
+    /// received in the background.
+    CreateOffer {
+        #[arg(long)]
+        amount: Option<Amount>,
+        #[arg(long)]
+        description: Option<String>,
+        #[arg(long)]
+        expiry_secs: Option<u32>,
+        #[arg(long)]
+        gateway: Option<SafeUrl>,
+    },
+}
+
+#[derive(Clone, Subcommand, Serialize)]
+enum HoldOpts {
+    /// Request a hold invoice. The payment is held by the gateway until it is
+    /// settled or cancelled.
+    Receive {
+        amount: Amount,
+        /// Seconds the payment is held after it arrived before it is cancelled
+        #[arg(long, default_value_t = 3600)]
+        hold_expiry_secs: u32,
+        #[arg(long)]
+        gateway: Option<SafeUrl>,
+    },
+    /// Release the held payment of a hold invoice.
+    Settle { operation_id: OperationId },
+    /// Cancel a hold invoice and refund the payer.
+    Cancel { operation_id: OperationId },
+    /// Request the status of a hold invoice from the gateway.
+    Status { operation_id: OperationId },
+}
+
 #[derive(Clone, Subcommand, Serialize)]
 enum GatewaysOpts {
     /// Update the mapping from lightning node public keys to gateway api
@@ -79,8 +125,20 @@ pub(crate) async fn handle_cli_command(
     let opts = Opts::parse_from(iter::once(&ffi::OsString::from("lnv2")).chain(args.iter()));
 
     let value = match opts {
-        Opts::Send { gateway, invoice } => {
-            json(lightning.send(invoice, gateway, Value::Null).await?)
+        Opts::Send {
+            gateway,
+            invoice,
+            cheapest_of,
+        } => {
+            if cheapest_of.is_empty() {
+                json(lightning.send(invoice, gateway, Value::Null).await?)
+            } else {
+                json(
+                    lightning
+                        .send_by_cost(invoice, cheapest_of, Value::Null)
+                        .await?,
+                )
+            }
         }
         Opts::AwaitSend { operation_id } => json(
             lightning
@@ -109,6 +167,47 @@ pub(crate) async fn handle_cli_command(
                 gateway,
             } => json(lightning.generate_lnurl(recurringd, gateway).await?),
         },
+        Opts::Bolt12(bolt12_opts) => match bolt12_opts {
+            Bolt12Opts::CreateOffer {
+                amount,
+                description,
+                expiry_secs,
+                gateway,
+            } => json(
+                lightning
+                    .create_bolt12_offer(amount, description, expiry_secs, gateway)
+                    .await?,
+            ),
+        },
+        Opts::Hold(hold_opts) => match hold_opts {
+            HoldOpts::Receive {
+                amount,
+                hold_expiry_secs,
+                gateway,
+            } => json(
+                lightning
+                    .receive_hold(
+                        amount,
+                        3600,
+                        hold_expiry_secs,
+                        Bolt11InvoiceDescription::Direct(String::new()),
+                        gateway,
+                        Value::Null,
+                    )
+                    .await?,
+            ),
+            #[allow(clippy::unit_arg)]
+            HoldOpts::Settle { operation_id } => {
+                json(lightning.settle_hold_invoice(operation_id).await?)
+            }
+            #[allow(clippy::unit_arg)]
+            HoldOpts::Cancel { operation_id } => {
+                json(lightning.cancel_hold_invoice(operation_id).await?)
+            }
+            HoldOpts::Status { operation_id } => {
+                json(lightning.hold_invoice_status(operation_id).await?)
+            }
+        },
         Opts::Gateways(gateway_opts) => match gateway_opts {
             #[allow(clippy::unit_arg)]
             GatewaysOpts::Map => json(lightning.update_gateway_map().await),
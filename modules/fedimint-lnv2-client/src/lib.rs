@@ -14,9 +14,12 @@
 mod receive_sm;
 mod send_sm;
 
+use std::cmp::Reverse;
 use std::collections::{BTreeMap, BTreeSet};
 use std::sync::Arc;
+use std::time::Duration;
 
+use anyhow::{anyhow, bail};
 use async_stream::stream;
 use bitcoin::hashes::{Hash, sha256};
 use bitcoin::secp256k1;
@@ -47,7 +50,9 @@
 use fedimint_lnv2_common::config::LightningClientConfig;
 use fedimint_lnv2_common::contracts::{IncomingContract, OutgoingContract, PaymentImage};
 use fedimint_lnv2_common::gateway_api::{
-    GatewayConnection, PaymentFee, RealGatewayConnection, RoutingInfo,
+    CancelHoldInvoicePayload, CreateBolt12OfferPayload, CreateHoldInvoicePayload,
+    GatewayConnection, HoldInvoiceStatus, PaymentEstimate, PaymentFee, RealGatewayConnection,
+    RoutingInfo, SettleHoldInvoicePayload,
 };
 use fedimint_lnv2_common::{
     Bolt11InvoiceDescription, GatewayApi, KIND, LightningCommonInit, LightningInvoice,
@@ -55,6 +60,7 @@
     lnurl, tweak,
 };
 use futures::StreamExt;
+use futures::future::join_all;
 use lightning_invoice::{Bolt11Invoice, Currency};
 use secp256k1::{Keypair, PublicKey, Scalar, SecretKey, ecdh};
 use serde::{Deserialize, Serialize};
@@ -76,12 +82,17 @@
 /// A two hour buffer in case either the client or gateway go offline
 const CONTRACT_CONFIRMATION_BUFFER: u64 = 12;
 
+/// Maximum time we wait for a gateway to probe the route to the payee before
+/// we rank it by its default send fee instead
+const GATEWAY_PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
 #[allow(clippy::large_enum_variant)]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum LightningOperationMeta {
     Send(SendOperationMeta),
     Receive(ReceiveOperationMeta),
     LnurlReceive(LnurlReceiveOperationMeta),
+    HoldReceive(ReceiveOperationMeta),
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -113,6 +124,12 @@ pub struct ReceiveOperationMeta {
 }
 
 impl ReceiveOperationMeta {
+    pub fn payment_hash(&self) -> sha256::Hash {
+        match &self.invoice {
+            LightningInvoice::Bolt11(invoice) => *invoice.payment_hash(),
+        }
+    }
+
     /// Calculate the absolute fee paid to the gateway on success.
     pub fn gateway_fee(&self) -> Amount {
         match &self.invoice {
@@ -480,6 +497,67 @@ pub async fn select_gateway(
         Err(SelectGatewayError::GatewaysUnresponsive)
     }
 
+    /// Asks each of the given gateways to probe the route to the payee of the
+    /// invoice and selects the online gateway that is able to pay it for the
+    /// lowest fee. Only the gateways passed by the caller are contacted, which
+    /// reveal the invoice to them.
+    pub async fn select_gateway_by_cost(
+        &self,
+        gateways: Vec<SafeUrl>,
+        invoice: &Bolt11Invoice,
+    ) -> Result<(SafeUrl, RoutingInfo, Option<PaymentEstimate>), SelectGatewayError> {
+        if gateways.is_empty() {
+            return Err(SelectGatewayError::NoGatewaysAvailable);
+        }
+
+        let amount = invoice.amount_milli_satoshis().unwrap_or_default();
+
+        join_all(gateways.into_iter().map(|gateway| async move {
+            let routing_info = self.routing_info(&gateway).await.ok()??;
+            let estimate = self.payment_estimate(&gateway, invoice).await;
+
+            Some((gateway, routing_info, estimate))
+        }))
+        .await
+        .into_iter()
+        .flatten()
+        // Gateways that do not support probing are ranked by their default send
+        // fee, while gateways that were unable to find a route are ranked last. On
+        // equal fees a known likelihood of success is preferred over an unknown one.
+        .min_by_key(|(_, routing_info, estimate)| match estimate {
+            Some(estimate) => (
+                estimate.success_likelihood == Some(0),
+                estimate.send_fee.fee(amount),
+                Reverse(estimate.success_likelihood.unwrap_or(0)),
+            ),
+            None => (
+                false,
+                routing_info.send_parameters(invoice).0.fee(amount),
+                Reverse(0),
+            ),
+        })
+        .ok_or(SelectGatewayError::GatewaysUnresponsive)
+    }
+
+    /// Requests an estimate of the fee for paying the invoice from the gateway
+    /// available at the `SafeUrl`. Returns `None` if the gateway does not
+    /// support probing, the request failed or it did not respond within
+    /// [`GATEWAY_PROBE_TIMEOUT`].
+    async fn payment_estimate(
+        &self,
+        gateway: &SafeUrl,
+        invoice: &Bolt11Invoice,
+    ) -> Option<PaymentEstimate> {
+        fedimint_core::runtime::timeout(
+            GATEWAY_PROBE_TIMEOUT,
+            self.gateway_conn
+                .probe_invoice(gateway.clone(), self.federation_id, invoice.clone()),
+        )
+        .await
+        .ok()?
+        .ok()
+    }
+
     /// Sends a request to each peer for their registered gateway list and
     /// returns a `Vec<SafeUrl` of all registered gateways to the client.
     pub async fn list_gateways(
@@ -527,12 +605,41 @@ pub async fn routing_info(
     ///
     /// The absolute fee for a payment can be calculated from the operation meta
     /// to be shown to the user in the transaction history.
-    #[allow(clippy::too_many_lines)]
     pub async fn send(
         &self,
         invoice: Bolt11Invoice,
         gateway: Option<SafeUrl>,
         custom_meta: Value,
+    ) -> Result<OperationId, SendPaymentError> {
+        let selection = match gateway {
+            Some(gateway) => GatewaySelection::Gateway(gateway),
+            None => GatewaySelection::Automatic,
+        };
+
+        self.send_via(invoice, selection, custom_meta).await
+    }
+
+    /// Pay an invoice via the cheapest of the given gateways as selected by
+    /// [`LightningClientModule::select_gateway_by_cost`]. If the selected
+    /// gateway estimates a fee below its default send fee, the payment is
+    /// funded with the estimated fee instead. Otherwise this behaves exactly
+    /// like [`LightningClientModule::send`].
+    pub async fn send_by_cost(
+        &self,
+        invoice: Bolt11Invoice,
+        gateways: Vec<SafeUrl>,
+        custom_meta: Value,
+    ) -> Result<OperationId, SendPaymentError> {
+        self.send_via(invoice, GatewaySelection::CheapestOf(gateways), custom_meta)
+            .await
+    }
+
+    #[allow(clippy::too_many_lines)]
+    async fn send_via(
+        &self,
+        invoice: Bolt11Invoice,
+        selection: GatewaySelection,
+        custom_meta: Value,
     ) -> Result<OperationId, SendPaymentError> {
         let amount = invoice
             .amount_milli_satoshis()
@@ -557,21 +664,37 @@ pub async fn send(
             .expect("32 bytes, within curve order")
             .keypair(secp256k1::SECP256K1);
 
-        let (gateway_api, routing_info) = match gateway {
-            Some(gateway_api) => (
+        let (gateway_api, routing_info, estimate) = match selection {
+            GatewaySelection::Gateway(gateway_api) => (
                 gateway_api.clone(),
                 self.routing_info(&gateway_api)
                     .await
                     .map_err(|e| SendPaymentError::FailedToConnectToGateway(e.to_string()))?
                     .ok_or(SendPaymentError::FederationNotSupported)?,
+                None,
             ),
-            None => self
-                .select_gateway(Some(invoice.clone()))
+            GatewaySelection::Automatic => {
+                let (gateway_api, routing_info) = self
+                    .select_gateway(Some(invoice.clone()))
+                    .await
+                    .map_err(SendPaymentError::SelectGateway)?;
+
+                (gateway_api, routing_info, None)
+            }
+            GatewaySelection::CheapestOf(gateways) => self
+                .select_gateway_by_cost(gateways, &invoice)
                 .await
                 .map_err(SendPaymentError::SelectGateway)?,
         };
 
-        let (send_fee, expiration_delta) = routing_info.send_parameters(&invoice);
+        let (default_send_fee, expiration_delta) = routing_info.send_parameters(&invoice);
+
+        // The gateway's estimate never exceeds its default send fee, hence we only
+        // use it if it is actually cheaper.
+        let send_fee = estimate
+            .map(|estimate| estimate.send_fee)
+            .filter(|send_fee| send_fee.fee(amount) < default_send_fee.fee(amount))
+            .unwrap_or(default_send_fee);
 
         if !send_fee.le(&PaymentFee::SEND_FEE_LIMIT) {
             return Err(SendPaymentError::GatewayFeeExceedsLimit);
@@ -829,6 +952,153 @@ pub async fn receive(
         Ok((invoice, operation_id))
     }
 
+    /// Request a hold invoice. The gateway holds the incoming payment until
+    /// we either settle it with [`Self::settle_hold_invoice`] or cancel it
+    /// with [`Self::cancel_hold_invoice`], which allows to only settle a
+    /// payment once the goods paid for have been delivered. Until then the
+    /// preimage is only known to us. If we do neither within
+    /// `hold_expiry_secs` after the payment arrived, the gateway cancels the
+    /// payment and the payer is refunded.
+    ///
+    /// The fees are the same as for [`Self::receive`].
+    pub async fn receive_hold(
+        &self,
+        amount: Amount,
+        expiry_secs: u32,
+        hold_expiry_secs: u32,
+        description: Bolt11InvoiceDescription,
+        gateway: Option<SafeUrl>,
+        custom_meta: Value,
+    ) -> Result<(Bolt11Invoice, OperationId), ReceiveError> {
+        // The contract is only funded once we settle the payment, hence it has
+        // to remain valid until the hold expires.
+        let (gateway, contract) = self
+            .create_contract(
+                self.keypair.public_key(),
+                amount,
+                expiry_secs.saturating_add(hold_expiry_secs),
+                gateway,
+            )
+            .await?;
+
+        let PaymentImage::Hash(payment_hash) = contract.commitment.payment_image else {
+            unreachable!("The contract has been generated with a payment hash");
+        };
+
+        let invoice = self
+            .gateway_conn
+            .hold_invoice(
+                gateway.clone(),
+                CreateHoldInvoicePayload {
+                    federation_id: self.federation_id,
+                    payment_hash,
+                    amount,
+                    description,
+                    expiry_secs,
+                    hold_expiry_secs,
+                    auth_pk: contract.commitment.claim_pk,
+                },
+            )
+            .await
+            .map_err(|e| ReceiveError::FailedToConnectToGateway(e.to_string()))?;
+
+        Self::verify_invoice(&invoice, &contract, amount)?;
+
+        let operation_id = self
+            .receive_incoming_contract(
+                self.keypair.secret_key(),
+                contract.clone(),
+                LightningOperationMeta::HoldReceive(ReceiveOperationMeta {
+                    gateway,
+                    contract,
+                    invoice: LightningInvoice::Bolt11(invoice.clone()),
+                    custom_meta,
+                }),
+            )
+            .await
+            .expect("The contract has been generated with our public key");
+
+        Ok((invoice, operation_id))
+    }
+
+    /// Releases a payment held for the hold invoice of the given receive
+    /// operation by submitting the incoming contract to the gateway.
+    pub async fn settle_hold_invoice(&self, operation_id: OperationId) -> anyhow::Result<()> {
+        let (meta, auth_keypair) = self.hold_receive_meta(operation_id).await?;
+
+        self.gateway_conn
+            .settle_hold_invoice(
+                meta.gateway,
+                SettleHoldInvoicePayload {
+                    federation_id: self.federation_id,
+                    auth: auth_keypair
+                        .sign_schnorr(SettleHoldInvoicePayload::message(&meta.contract)),
+                    contract: meta.contract,
+                },
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    /// Cancels the hold invoice of the given receive operation. If the
+    /// payment is held by the gateway already, the payer is refunded.
+    pub async fn cancel_hold_invoice(&self, operation_id: OperationId) -> anyhow::Result<()> {
+        let (meta, auth_keypair) = self.hold_receive_meta(operation_id).await?;
+
+        let payment_hash = meta.payment_hash();
+
+        self.gateway_conn
+            .cancel_hold_invoice(
+                meta.gateway,
+                CancelHoldInvoicePayload {
+                    federation_id: self.federation_id,
+                    payment_hash,
+                    auth: auth_keypair
+                        .sign_schnorr(CancelHoldInvoicePayload::message(payment_hash)),
+                },
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    /// Requests the status of the hold invoice of the given receive operation
+    /// from the gateway.
+    pub async fn hold_invoice_status(
+        &self,
+        operation_id: OperationId,
+    ) -> anyhow::Result<HoldInvoiceStatus> {
+        let (meta, _) = self.hold_receive_meta(operation_id).await?;
+
+        self.gateway_conn
+            .hold_invoice_status(
+                meta.gateway.clone(),
+                self.federation_id,
+                meta.payment_hash(),
+            )
+            .await?
+            .ok_or(anyhow!("Gateway does not know the hold invoice"))
+    }
+
+    async fn hold_receive_meta(
+        &self,
+        operation_id: OperationId,
+    ) -> anyhow::Result<(ReceiveOperationMeta, Keypair)> {
+        let operation = self.client_ctx.get_operation(operation_id).await?;
+
+        let LightningOperationMeta::HoldReceive(meta) = operation.meta::<LightningOperationMeta>()
+        else {
+            bail!("Operation is not a hold invoice receive");
+        };
+
+        let (auth_keypair, _) = self
+            .recover_contract_keys(self.keypair.secret_key(), &meta.contract)
+            .expect("The contract has been generated with our public key");
+
+        Ok((meta, auth_keypair))
+    }
+
     /// Create an incoming contract locked to a public key derived from the
     /// recipient's static module public key and fetches the corresponding
     /// invoice.
@@ -840,6 +1110,53 @@ async fn create_contract_and_fetch_invoice(
         description: Bolt11InvoiceDescription,
         gateway: Option<SafeUrl>,
     ) -> Result<(SafeUrl, IncomingContract, Bolt11Invoice), ReceiveError> {
+        let (gateway, contract) = self
+            .create_contract(recipient_static_pk, amount, expiry_secs, gateway)
+            .await?;
+
+        let invoice = self
+            .gateway_conn
+            .bolt11_invoice(
+                gateway.clone(),
+                self.federation_id,
+                contract.clone(),
+                amount,
+                description,
+                expiry_secs,
+            )
+            .await
+            .map_err(|e| ReceiveError::FailedToConnectToGateway(e.to_string()))?;
+
+        Self::verify_invoice(&invoice, &contract, amount)?;
+
+        Ok((gateway, contract, invoice))
+    }
+
+    fn verify_invoice(
+        invoice: &Bolt11Invoice,
+        contract: &IncomingContract,
+        amount: Amount,
+    ) -> Result<(), ReceiveError> {
+        if PaymentImage::Hash(*invoice.payment_hash()) != contract.commitment.payment_image {
+            return Err(ReceiveError::InvalidInvoice);
+        }
+
+        if invoice.amount_milli_satoshis() != Some(amount.msats) {
+            return Err(ReceiveError::IncorrectInvoiceAmount);
+        }
+
+        Ok(())
+    }
+
+    /// Create an incoming contract locked to a public key derived from the
+    /// recipient's static module public key with a gateway of our choice.
+    async fn create_contract(
+        &self,
+        recipient_static_pk: PublicKey,
+        amount: Amount,
+        expiry_secs: u32,
+        gateway: Option<SafeUrl>,
+    ) -> Result<(SafeUrl, IncomingContract), ReceiveError> {
         let (ephemeral_tweak, ephemeral_pk) = tweak::generate(recipient_static_pk);
 
         let encryption_seed = ephemeral_tweak
@@ -897,28 +1214,7 @@ async fn create_contract_and_fetch_invoice(
             ephemeral_pk,
         );
 
-        let invoice = self
-            .gateway_conn
-            .bolt11_invoice(
-                gateway.clone(),
-                self.federation_id,
-                contract.clone(),
-                amount,
-                description,
-                expiry_secs,
-            )
-            .await
-            .map_err(|e| ReceiveError::FailedToConnectToGateway(e.to_string()))?;
-
-        if invoice.payment_hash() != &preimage.consensus_hash() {
-            return Err(ReceiveError::InvalidInvoice);
-        }
-
-        if invoice.amount_milli_satoshis() != Some(amount.msats) {
-            return Err(ReceiveError::IncorrectInvoiceAmount);
-        }
-
-        Ok((gateway, contract, invoice))
+        Ok((gateway, contract))
     }
 
     // Receive an incoming contract locked to a public key derived from our
@@ -1094,6 +1390,52 @@ pub async fn generate_lnurl(
         )))
     }
 
+    /// Request a reusable BOLT12 offer from a gateway. You can optionally
+    /// specify a gateway to use for testing purposes.
+    ///
+    /// For every payment to the offer the gateway funds an incoming contract
+    /// locked to our static lnurl public key, which is claimed by the same
+    /// background task that receives lnurl payments.
+    pub async fn create_bolt12_offer(
+        &self,
+        amount: Option<Amount>,
+        description: Option<String>,
+        expiry_secs: Option<u32>,
+        gateway: Option<SafeUrl>,
+    ) -> Result<String, ReceiveError> {
+        let (gateway, routing_info) = match gateway {
+            Some(gateway) => (
+                gateway.clone(),
+                self.routing_info(&gateway)
+                    .await
+                    .map_err(|e| ReceiveError::FailedToConnectToGateway(e.to_string()))?
+                    .ok_or(ReceiveError::FederationNotSupported)?,
+            ),
+            None => self
+                .select_gateway(None)
+                .await
+                .map_err(ReceiveError::SelectGateway)?,
+        };
+
+        if !routing_info.receive_fee.le(&PaymentFee::RECEIVE_FEE_LIMIT) {
+            return Err(ReceiveError::GatewayFeeExceedsLimit);
+        }
+
+        self.gateway_conn
+            .bolt12_offer(
+                gateway,
+                CreateBolt12OfferPayload {
+                    federation_id: self.federation_id,
+                    recipient_pk: self.lnurl_keypair.public_key(),
+                    amount,
+                    description,
+                    expiry_secs,
+                },
+            )
+            .await
+            .map_err(|e| ReceiveError::FailedToConnectToGateway(e.to_string()))
+    }
+
     fn spawn_receive_lnurl_task(
         &self,
         custom_meta_fn: Arc<dyn Fn() -> Value + Send + Sync>,
@@ -1148,6 +1490,14 @@ async fn receive_lnurl(&self, custom_meta: Value) {
     }
 }
 
+/// How the gateway paying an invoice is chosen by
+/// [`LightningClientModule::send_via`]
+enum GatewaySelection {
+    Gateway(SafeUrl),
+    Automatic,
+    CheapestOf(Vec<SafeUrl>),
+}
+
 #[derive(Error, Debug, Clone, Eq, PartialEq)]
 pub enum SelectGatewayError {
     #[error("Failed to request gateways")]
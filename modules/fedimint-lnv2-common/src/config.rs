@@ -32,7 +32,10 @@ pub struct LightningConfigConsensus {
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LightningConfigPrivate {
-    pub sk: SecretKeyShare,
+    /// Our secret key share for preimage encryption, missing once it has been
+    /// exported to a remote guardian signer
+    #[serde(default)]
+    pub sk: Option<SecretKeyShare>,
 }
 
 #[derive(Debug, Clone, Eq, PartialEq, Hash, Serialize, Deserialize, Encodable, Decodable)]
@@ -169,7 +172,10 @@ fn migrate_config_private(
     config: &fedimint_ln_common::config::LightningConfigPrivate,
 ) -> LightningConfigPrivate {
     LightningConfigPrivate {
-        sk: SecretKeyShare(config.threshold_sec_key.0.0.0),
+        sk: config
+            .threshold_sec_key
+            .as_ref()
+            .map(|sk| SecretKeyShare(sk.0.0.0)),
     }
 }
 
@@ -11,6 +11,12 @@
 
 // Gateway endpoints
 pub const CREATE_BOLT11_INVOICE_ENDPOINT: &str = "/create_bolt11_invoice";
+pub const CREATE_BOLT12_OFFER_ENDPOINT: &str = "/create_bolt12_offer";
+pub const CREATE_HOLD_INVOICE_ENDPOINT: &str = "/create_hold_invoice";
+pub const CANCEL_HOLD_INVOICE_ENDPOINT: &str = "/cancel_hold_invoice";
+pub const HOLD_INVOICE_STATUS_ENDPOINT: &str = "/hold_invoice_status";
+pub const SETTLE_HOLD_INVOICE_ENDPOINT: &str = "/settle_hold_invoice";
 pub const VERIFY_BOLT11_PREIMAGE_ENDPOINT: &str = "/verify_bolt11_preimage";
+pub const PROBE_INVOICE_ENDPOINT: &str = "/probe_invoice";
 pub const ROUTING_INFO_ENDPOINT: &str = "/routing_info";
 pub const SEND_PAYMENT_ENDPOINT: &str = "/send_payment";
@@ -1,8 +1,9 @@
 use std::ops::Add;
 use std::str::FromStr;
 
-use bitcoin::secp256k1::PublicKey;
+use bitcoin::hashes::{Hash, sha256};
 use bitcoin::secp256k1::schnorr::Signature;
+use bitcoin::secp256k1::{Message, PublicKey};
 use fedimint_connectors::error::ServerError;
 use fedimint_core::config::FederationId;
 use fedimint_core::encoding::{Decodable, Encodable};
@@ -15,7 +16,9 @@
 
 use crate::contracts::{IncomingContract, OutgoingContract};
 use crate::endpoint_constants::{
-    CREATE_BOLT11_INVOICE_ENDPOINT, ROUTING_INFO_ENDPOINT, SEND_PAYMENT_ENDPOINT,
+    CANCEL_HOLD_INVOICE_ENDPOINT, CREATE_BOLT11_INVOICE_ENDPOINT, CREATE_BOLT12_OFFER_ENDPOINT,
+    CREATE_HOLD_INVOICE_ENDPOINT, HOLD_INVOICE_STATUS_ENDPOINT, PROBE_INVOICE_ENDPOINT,
+    ROUTING_INFO_ENDPOINT, SEND_PAYMENT_ENDPOINT, SETTLE_HOLD_INVOICE_ENDPOINT,
 };
 use crate::{Bolt11InvoiceDescription, LightningInvoice};
 
@@ -37,6 +40,44 @@ async fn bolt11_invoice(
         expiry_secs: u32,
     ) -> Result<Bolt11Invoice, ServerError>;
 
+    async fn bolt12_offer(
+        &self,
+        gateway_api: SafeUrl,
+        payload: CreateBolt12OfferPayload,
+    ) -> Result<String, ServerError>;
+
+    async fn probe_invoice(
+        &self,
+        gateway_api: SafeUrl,
+        federation_id: FederationId,
+        invoice: Bolt11Invoice,
+    ) -> Result<PaymentEstimate, ServerError>;
+
+    async fn hold_invoice(
+        &self,
+        gateway_api: SafeUrl,
+        payload: CreateHoldInvoicePayload,
+    ) -> Result<Bolt11Invoice, ServerError>;
+
+    async fn settle_hold_invoice(
+        &self,
+        gateway_api: SafeUrl,
+        payload: SettleHoldInvoicePayload,
+    ) -> Result<(), ServerError>;
+
+    async fn cancel_hold_invoice(
+        &self,
+        gateway_api: SafeUrl,
+        payload: CancelHoldInvoicePayload,
+    ) -> Result<(), ServerError>;
+
+    async fn hold_invoice_status(
+        &self,
+        gateway_api: SafeUrl,
+        federation_id: FederationId,
+        payment_hash: sha256::Hash,
+    ) -> Result<Option<HoldInvoiceStatus>, ServerError>;
+
     async fn send_payment(
         &self,
         gateway_api: SafeUrl,
@@ -95,6 +136,104 @@ async fn bolt11_invoice(
             .await
     }
 
+    async fn bolt12_offer(
+        &self,
+        gateway_api: SafeUrl,
+        payload: CreateBolt12OfferPayload,
+    ) -> Result<String, ServerError> {
+        self.api
+            .request(
+                &gateway_api,
+                Method::POST,
+                CREATE_BOLT12_OFFER_ENDPOINT,
+                Some(payload),
+            )
+            .await
+    }
+
+    async fn probe_invoice(
+        &self,
+        gateway_api: SafeUrl,
+        federation_id: FederationId,
+        invoice: Bolt11Invoice,
+    ) -> Result<PaymentEstimate, ServerError> {
+        self.api
+            .request(
+                &gateway_api,
+                Method::POST,
+                PROBE_INVOICE_ENDPOINT,
+                Some(ProbeInvoicePayload {
+                    federation_id,
+                    invoice,
+                }),
+            )
+            .await
+    }
+
+    async fn hold_invoice(
+        &self,
+        gateway_api: SafeUrl,
+        payload: CreateHoldInvoicePayload,
+    ) -> Result<Bolt11Invoice, ServerError> {
+        self.api
+            .request(
+                &gateway_api,
+                Method::POST,
+                CREATE_HOLD_INVOICE_ENDPOINT,
+                Some(payload),
+            )
+            .await
+    }
+
+    async fn settle_hold_invoice(
+        &self,
+        gateway_api: SafeUrl,
+        payload: SettleHoldInvoicePayload,
+    ) -> Result<(), ServerError> {
+        self.api
+            .request(
+                &gateway_api,
+                Method::POST,
+                SETTLE_HOLD_INVOICE_ENDPOINT,
+                Some(payload),
+            )
+            .await
+    }
+
+    async fn cancel_hold_invoice(
+        &self,
+        gateway_api: SafeUrl,
+        payload: CancelHoldInvoicePayload,
+    ) -> Result<(), ServerError> {
+        self.api
+            .request(
+                &gateway_api,
+                Method::POST,
+                CANCEL_HOLD_INVOICE_ENDPOINT,
+                Some(payload),
+            )
+            .await
+    }
+
+    async fn hold_invoice_status(
+        &self,
+        gateway_api: SafeUrl,
+        federation_id: FederationId,
+        payment_hash: sha256::Hash,
+    ) -> Result<Option<HoldInvoiceStatus>, ServerError> {
+        self.api
+            .request(
+                &gateway_api,
+                Method::POST,
+                HOLD_INVOICE_STATUS_ENDPOINT,
+                Some(HoldInvoiceStatusPayload {
+                    federation_id,
+                    payment_hash,
+                }),
+            )
+            .await
+    }
+
     async fn send_payment(
         &self,
         gateway_api: SafeUrl,
@@ -130,6 +269,113 @@ pub struct CreateBolt11InvoicePayload {
     pub expiry_secs: u32,
 }
 
+/// Requests a reusable BOLT12 offer from the gateway. For every payment to the
+/// offer the gateway funds an [`IncomingContract`] locked to a key derived from
+/// `recipient_pk`, just like recurringd does for lnurl payments.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct CreateBolt12OfferPayload {
+    pub federation_id: FederationId,
+    pub recipient_pk: PublicKey,
+    pub amount: Option<Amount>,
+    pub description: Option<String>,
+    pub expiry_secs: Option<u32>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct ProbeInvoicePayload {
+    pub federation_id: FederationId,
+    pub invoice: Bolt11Invoice,
+}
+
+/// The gateway's estimate of the cost and likelihood of success of paying a
+/// specific invoice, obtained by probing the route to the payee.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct PaymentEstimate {
+    /// The estimated fee to route the payment over lightning. This is `None`
+    /// if the gateway's lightning node is unable to estimate it.
+    pub lightning_fee: Option<Amount>,
+    /// The total fee the gateway charges to pay this invoice. It is never
+    /// higher than the `send_fee_default` of the gateway's [`RoutingInfo`].
+    pub send_fee: PaymentFee,
+    /// The estimated likelihood of a successful payment in percent. This is
+    /// `None` if the gateway's lightning node found a route but is unable to
+    /// tell whether it will succeed.
+    pub success_likelihood: Option<u8>,
+}
+
+/// Requests a hold invoice from the gateway. Once the payment arrives the
+/// gateway holds the HTLC until the recipient either submits the
+/// [`IncomingContract`] for it or cancels the invoice, such that the recipient
+/// controls when the preimage is released. If neither happens within
+/// `hold_expiry_secs` after the payment arrived, the HTLC is cancelled and the
+/// payer is refunded.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct CreateHoldInvoicePayload {
+    pub federation_id: FederationId,
+    pub payment_hash: sha256::Hash,
+    pub amount: Amount,
+    pub description: Bolt11InvoiceDescription,
+    pub expiry_secs: u32,
+    pub hold_expiry_secs: u32,
+    /// The key authorizing settlement and cancellation of the hold invoice
+    pub auth_pk: PublicKey,
+}
+
+/// Releases a held payment by funding the submitted contract, whose payment
+/// image has to match the payment hash of the hold invoice.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct SettleHoldInvoicePayload {
+    pub federation_id: FederationId,
+    pub contract: IncomingContract,
+    pub auth: Signature,
+}
+
+impl SettleHoldInvoicePayload {
+    pub fn message(contract: &IncomingContract) -> Message {
+        hold_invoice_message(b"settle-hold-invoice", contract.contract_id().0)
+    }
+}
+
+/// Cancels a hold invoice, refunding the payer if the payment has arrived
+/// already.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct CancelHoldInvoicePayload {
+    pub federation_id: FederationId,
+    pub payment_hash: sha256::Hash,
+    pub auth: Signature,
+}
+
+impl CancelHoldInvoicePayload {
+    pub fn message(payment_hash: sha256::Hash) -> Message {
+        hold_invoice_message(b"cancel-hold-invoice", payment_hash)
+    }
+}
+
+fn hold_invoice_message(tag: &[u8], hash: sha256::Hash) -> Message {
+    let digest = sha256::Hash::hash(&[tag, hash.as_byte_array()].concat());
+
+    Message::from_digest(digest.to_byte_array())
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct HoldInvoiceStatusPayload {
+    pub federation_id: FederationId,
+    pub payment_hash: sha256::Hash,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub enum HoldInvoiceStatus {
+    /// The payment has not arrived yet
+    Open,
+    /// The payment is held by the gateway until the expiration, given in
+    /// seconds since the unix epoch
+    Held { expiration: u64 },
+    /// The gateway is funding the submitted contract
+    Settled,
+    /// The invoice was cancelled or has expired and the payer was refunded
+    Cancelled,
+}
+
 #[derive(Debug, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
 pub struct SendPaymentPayload {
     pub federation_id: FederationId,
@@ -29,9 +29,9 @@
     TransactionItemAmounts, api_endpoint,
 };
 use fedimint_core::net::auth::check_auth;
-use fedimint_core::task::timeout;
+use fedimint_core::task::{sleep, timeout};
 use fedimint_core::time::duration_since_epoch;
-use fedimint_core::util::SafeUrl;
+use fedimint_core::util::{FmtCompactAnyhow as _, SafeUrl};
 use fedimint_core::{
     BitcoinHash, InPoint, NumPeers, NumPeersExt, OutPoint, PeerId, apply, async_trait_maybe_send,
     push_db_pair_items,
@@ -54,6 +54,7 @@
 use fedimint_logging::LOG_MODULE_LNV2;
 use fedimint_server_core::bitcoin_rpc::ServerBitcoinRpcMonitor;
 use fedimint_server_core::config::{PeerHandleOps, eval_poly_g1};
+use fedimint_server_core::guardian_signer::{GuardianSecret, ModuleGuardianSigner};
 use fedimint_server_core::migration::ServerModuleDbMigrationFn;
 use fedimint_server_core::{
     ConfigGenModuleArgs, EnvVarDoc, ServerModule, ServerModuleInit, ServerModuleInitArgs,
@@ -67,7 +68,7 @@
 use tpe::{
     AggregatePublicKey, DecryptionKeyShare, PublicKeyShare, SecretKeyShare, derive_pk_share,
 };
-use tracing::trace;
+use tracing::{trace, warn};
 
 use crate::db::{
     BlockCountVoteKey, BlockCountVotePrefix, DbKeyPrefix, DecryptionKeyShareKey,
@@ -251,6 +252,8 @@ async fn init(&self, args: &ServerModuleInitArgs<Self>) -> anyhow::Result<Self::
             cfg: args.cfg().to_typed()?,
             db: args.db().clone(),
             server_bitcoin_rpc_monitor: args.server_bitcoin_rpc_monitor(),
+            our_id: args.our_peer_id(),
+            guardian_signer: args.guardian_signer().clone(),
         })
     }
 
@@ -279,7 +282,7 @@ fn trusted_dealer_gen(
                         network: args.network,
                     },
                     private: LightningConfigPrivate {
-                        sk: dealer_sk(peers.to_num_peers(), *peer),
+                        sk: Some(dealer_sk(peers.to_num_peers(), *peer)),
                     },
                 };
 
@@ -311,7 +314,7 @@ async fn distributed_gen(
                 network: args.network,
             },
             private: LightningConfigPrivate {
-                sk: SecretKeyShare(sks),
+                sk: Some(SecretKeyShare(sks)),
             },
         };
 
@@ -321,8 +324,14 @@ async fn distributed_gen(
     fn validate_config(&self, identity: &PeerId, config: ServerModuleConfig) -> anyhow::Result<()> {
         let config = config.to_typed::<LightningConfig>()?;
 
+        // The secret key is missing once it has been exported to a remote
+        // guardian signer, which verifies it on startup instead
+        let Some(sk) = config.private.sk else {
+            return Ok(());
+        };
+
         ensure!(
-            tpe::derive_pk_share(&config.private.sk)
+            tpe::derive_pk_share(&sk)
                 == *config
                     .consensus
                     .tpe_pks
@@ -334,6 +343,19 @@ fn validate_config(&self, identity: &PeerId, config: ServerModuleConfig) -> anyh
         Ok(())
     }
 
+    fn guardian_keys(
+        &self,
+        config: &ServerModuleConfig,
+    ) -> anyhow::Result<BTreeMap<String, GuardianSecret>> {
+        Ok(config
+            .to_typed::<LightningConfig>()?
+            .private
+            .sk
+            .into_iter()
+            .map(|sk| (TPE_KEY_NAME.to_string(), GuardianSecret::Bls12381(sk.0)))
+            .collect())
+    }
+
     fn get_client_config(
         &self,
         config: &ServerModuleConsensusConfig,
@@ -395,11 +417,52 @@ fn coefficient(index: u64) -> Scalar {
     ))
 }
 
+/// Name of the preimage encryption secret key share in the guardian signer
+const TPE_KEY_NAME: &str = "tpe_sk";
+
+/// Every guardian has to create its decryption key share to stay in consensus,
+/// so if the signer is unavailable or returns an invalid share we keep retrying
+/// until it is back.
+async fn create_dk_share_with_guardian_signer(
+    signer: &ModuleGuardianSigner,
+    contract: &IncomingContract,
+    pk_share: &PublicKeyShare,
+) -> DecryptionKeyShare {
+    loop {
+        match signer
+            .multiply_g1(TPE_KEY_NAME, contract.ciphertext.pk.0)
+            .await
+        {
+            Ok(point) if contract.verify_decryption_share(pk_share, &DecryptionKeyShare(point)) => {
+                return DecryptionKeyShare(point);
+            }
+            Ok(_) => {
+                warn!(
+                    target: LOG_MODULE_LNV2,
+                    "Guardian signer returned an invalid decryption key share, retrying..."
+                );
+            }
+            Err(e) => {
+                warn!(
+                    target: LOG_MODULE_LNV2,
+                    err = %e.fmt_compact_anyhow(),
+                    "Failed to create decryption key share with guardian signer, retrying..."
+                );
+            }
+        }
+
+        sleep(Duration::from_secs(1)).await;
+    }
+}
+
 #[derive(Debug)]
 pub struct Lightning {
     cfg: LightningConfig,
     db: Database,
     server_bitcoin_rpc_monitor: ServerBitcoinRpcMonitor,
+    our_id: PeerId,
+    /// Creates the decryption key shares with our secret key share
+    guardian_signer: ModuleGuardianSigner,
 }
 
 #[apply(async_trait_maybe_send!)]
@@ -587,7 +650,12 @@ async fn process_output<'a, 'b>(
                 dbtx.insert_entry(&IncomingContractStreamIndexKey, &(stream_index + 1))
                     .await;
 
-                let dk_share = contract.create_decryption_key_share(&self.cfg.private.sk);
+                let dk_share = create_dk_share_with_guardian_signer(
+                    &self.guardian_signer,
+                    contract,
+                    &self.cfg.consensus.tpe_pks[&self.our_id],
+                )
+                .await;
 
                 dbtx.insert_entry(&DecryptionKeyShareKey(outpoint), &dk_share)
                     .await;
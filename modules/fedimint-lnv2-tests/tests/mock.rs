@@ -8,11 +8,16 @@
 use fedimint_core::secp256k1::Keypair;
 use fedimint_core::secp256k1::rand::rngs::OsRng;
 use fedimint_core::secp256k1::schnorr::Signature;
+use fedimint_core::task::sleep;
 use fedimint_core::util::SafeUrl;
 use fedimint_core::{Amount, OutPoint, apply, async_trait_maybe_send};
 use fedimint_ln_common::bitcoin;
 use fedimint_lnv2_common::contracts::{IncomingContract, OutgoingContract, PaymentImage};
-use fedimint_lnv2_common::gateway_api::{GatewayConnection, PaymentFee, RoutingInfo};
+use fedimint_lnv2_common::gateway_api::{
+    CancelHoldInvoicePayload, CreateBolt12OfferPayload, CreateHoldInvoicePayload,
+    GatewayConnection, HoldInvoiceStatus, PaymentEstimate, PaymentFee, RoutingInfo,
+    SettleHoldInvoicePayload,
+};
 use fedimint_lnv2_common::{Bolt11InvoiceDescription, LightningInvoice};
 use lightning_invoice::{
     Bolt11Invoice, Currency, DEFAULT_EXPIRY_TIME, InvoiceBuilder, PaymentSecret,
@@ -32,6 +37,17 @@ pub fn gateway() -> SafeUrl {
     SafeUrl::parse("https://gateway.xyz").expect("Valid Url")
 }
 
+/// A gateway that is able to probe the route to the payee of an invoice and
+/// charges less than its default send fee for routes it found.
+pub fn probing_gateway() -> SafeUrl {
+    SafeUrl::parse("https://probing-gateway.xyz").expect("Valid Url")
+}
+
+/// A gateway that would charge no fee at all, but never finishes probing.
+pub fn slow_probing_gateway() -> SafeUrl {
+    SafeUrl::parse("https://slow-probing-gateway.xyz").expect("Valid Url")
+}
+
 pub fn gateway_keypair() -> Keypair {
     SecretKey::from_slice(&GATEWAY_SECRET)
         .expect("32 bytes; within curve order")
@@ -128,6 +144,98 @@ async fn bolt11_invoice(
             .unwrap())
     }
 
+    async fn bolt12_offer(
+        &self,
+        _gateway_api: SafeUrl,
+        _payload: CreateBolt12OfferPayload,
+    ) -> Result<String, ServerError> {
+        Err(ServerError::InvalidRequest(anyhow!(
+            "Mock gateway does not support Bolt12 offers"
+        )))
+    }
+
+    async fn probe_invoice(
+        &self,
+        gateway_api: SafeUrl,
+        _federation_id: FederationId,
+        invoice: Bolt11Invoice,
+    ) -> Result<PaymentEstimate, ServerError> {
+        if gateway_api == slow_probing_gateway() {
+            sleep(Duration::from_secs(60)).await;
+
+            return Ok(PaymentEstimate {
+                lightning_fee: Some(Amount::ZERO),
+                send_fee: PaymentFee {
+                    base: Amount::ZERO,
+                    parts_per_million: 0,
+                },
+                success_likelihood: Some(100),
+            });
+        }
+
+        if gateway_api != probing_gateway() {
+            return Err(ServerError::InvalidRequest(anyhow!(
+                "Mock gateway does not support probing invoices"
+            )));
+        }
+
+        if *invoice.payment_secret() == PaymentSecret(UNPAYABLE_PAYMENT_SECRET) {
+            return Ok(PaymentEstimate {
+                lightning_fee: None,
+                send_fee: PaymentFee::TRANSACTION_FEE_DEFAULT,
+                success_likelihood: Some(0),
+            });
+        }
+
+        Ok(PaymentEstimate {
+            lightning_fee: Some(Amount::from_sats(1)),
+            send_fee: PaymentFee {
+                base: Amount::from_sats(1),
+                parts_per_million: 0,
+            },
+            success_likelihood: Some(90),
+        })
+    }
+
+    async fn hold_invoice(
+        &self,
+        _gateway_api: SafeUrl,
+        _payload: CreateHoldInvoicePayload,
+    ) -> Result<Bolt11Invoice, ServerError> {
+        Err(ServerError::InvalidRequest(anyhow!(
+            "Mock gateway does not support hold invoices"
+        )))
+    }
+
+    async fn settle_hold_invoice(
+        &self,
+        _gateway_api: SafeUrl,
+        _payload: SettleHoldInvoicePayload,
+    ) -> Result<(), ServerError> {
+        Err(ServerError::InvalidRequest(anyhow!(
+            "Mock gateway does not support hold invoices"
+        )))
+    }
+
+    async fn cancel_hold_invoice(
+        &self,
+        _gateway_api: SafeUrl,
+        _payload: CancelHoldInvoicePayload,
+    ) -> Result<(), ServerError> {
+        Err(ServerError::InvalidRequest(anyhow!(
+            "Mock gateway does not support hold invoices"
+        )))
+    }
+
+    async fn hold_invoice_status(
+        &self,
+        _gateway_api: SafeUrl,
+        _federation_id: FederationId,
+        _payment_hash: sha256::Hash,
+    ) -> Result<Option<HoldInvoiceStatus>, ServerError> {
+        Ok(None)
+    }
+
     async fn send_payment(
         &self,
         _gateway_api: SafeUrl,
@@ -3,13 +3,15 @@
 use std::pin::pin;
 use std::sync::Arc;
 
+use anyhow::anyhow;
 use async_stream::stream;
+use fedimint_api_client::api::FederationApiExt as _;
 use fedimint_client::ClientHandleArc;
 use fedimint_client::transaction::{ClientInput, ClientInputBundle, TransactionBuilder};
 use fedimint_client_module::module::ClientModule;
 use fedimint_core::core::{IntoDynInstance, OperationId};
-use fedimint_core::module::{AmountUnit, Amounts};
-use fedimint_core::util::NextOrPending as _;
+use fedimint_core::module::{AmountUnit, Amounts, ApiRequestErased};
+use fedimint_core::util::{NextOrPending as _, SafeUrl};
 use fedimint_core::{Amount, OutPoint, sats};
 use fedimint_dummy_client::{DummyClientInit, DummyClientModule};
 use fedimint_dummy_server::DummyInit;
@@ -21,12 +23,15 @@
     LightningClientInit, LightningClientModule, LightningOperationMeta, ReceiveOperationState,
     SendOperationState, SendPaymentError,
 };
+use fedimint_lnv2_common::endpoint_constants::ADD_GATEWAY_ENDPOINT;
 use fedimint_lnv2_common::{
     Bolt11InvoiceDescription, KIND, LightningInput, LightningInputV0, OutgoingWitness,
 };
 use fedimint_lnv2_server::LightningInit;
 use fedimint_logging::LOG_TEST;
+use fedimint_testing::federation::FederationTest;
 use fedimint_testing::fixtures::Fixtures;
+use fedimint_testing_core::config::API_AUTH;
 use futures::StreamExt;
 use serde_json::Value;
 use tracing::warn;
@@ -314,6 +319,9 @@ async fn claiming_outgoing_contract_triggers_success() -> anyhow::Result<()> {
         LightningOperationMeta::LnurlReceive(..) => {
             panic!("Operation Meta is a LnurlReceive variant")
         }
+        LightningOperationMeta::HoldReceive(..) => {
+            panic!("Operation Meta is a HoldReceive variant")
+        }
     };
 
     let client_input = ClientInput::<LightningInput> {
@@ -412,3 +420,70 @@ async fn rejects_wrong_network_invoice() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+async fn register_gateways(
+    client: &ClientHandleArc,
+    fed: &FederationTest,
+    gateways: &[SafeUrl],
+) -> anyhow::Result<()> {
+    let module_id = client.get_first_module::<LightningClientModule>()?.id;
+
+    for peer_id in fed.online_peer_ids() {
+        let module_api = fed.new_admin_api(peer_id).await?.with_module(module_id);
+
+        for gateway in gateways {
+            module_api
+                .request_admin::<bool>(
+                    ADD_GATEWAY_ENDPOINT,
+                    ApiRequestErased::new(gateway.clone()),
+                    API_AUTH.clone(),
+                )
+                .await
+                .map_err(|e| anyhow!("{e:?}"))?;
+        }
+    }
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn select_gateway_by_probed_cost() -> anyhow::Result<()> {
+    let fixtures = fixtures();
+    let fed = fixtures.new_fed_degraded().await;
+    let client = fed.new_client().await;
+
+    let gateways = vec![
+        mock::gateway(),
+        mock::probing_gateway(),
+        mock::slow_probing_gateway(),
+    ];
+
+    register_gateways(&client, &fed, &gateways).await?;
+
+    let module = client.get_first_module::<LightningClientModule>()?;
+
+    // The slow gateway would be free, but we do not wait for its probe and the
+    // probing gateway undercuts the default send fee of the other gateways.
+    let (gateway, _, estimate) = module
+        .select_gateway_by_cost(gateways.clone(), &mock::payable_invoice())
+        .await?;
+    assert_eq!(gateway, mock::probing_gateway());
+    assert!(estimate.is_some());
+
+    // A gateway that was unable to find a route is ranked last.
+    let (gateway, _, _) = module
+        .select_gateway_by_cost(gateways, &mock::unpayable_invoice())
+        .await?;
+    assert_ne!(gateway, mock::probing_gateway());
+
+    // Gateways the caller did not pass are never asked to probe the route
+    let (gateway, _, _) = module
+        .select_gateway_by_cost(
+            vec![mock::gateway(), mock::slow_probing_gateway()],
+            &mock::payable_invoice(),
+        )
+        .await?;
+    assert_eq!(gateway, mock::gateway());
+
+    Ok(())
+}
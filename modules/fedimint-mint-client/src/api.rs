@@ -1,12 +1,15 @@
 use fedimint_api_client::api::{FederationApiExt, FederationResult, IModuleFederationApi};
 use fedimint_core::bitcoin::hashes::sha256;
+use fedimint_core::module::liabilities::{LiabilityProof, LiabilityRoot};
 use fedimint_core::module::registry::ModuleRegistry;
-use fedimint_core::module::{ApiRequestErased, SerdeModuleEncodingBase64};
+use fedimint_core::module::{ApiRequestErased, ModuleConsensusVersion, SerdeModuleEncodingBase64};
 use fedimint_core::task::{MaybeSend, MaybeSync};
 use fedimint_core::{OutPoint, PeerId, apply, async_trait_maybe_send};
 use fedimint_mint_common::endpoint_constants::{
-    BLIND_NONCE_USED_ENDPOINT, NOTE_SPENT_ENDPOINT, RECOVERY_BLIND_NONCE_OUTPOINTS_ENDPOINT,
-    RECOVERY_COUNT_ENDPOINT, RECOVERY_SLICE_ENDPOINT, RECOVERY_SLICE_HASH_ENDPOINT,
+    BLIND_NONCE_USED_ENDPOINT, LIABILITY_PROOF_ENDPOINT, LIABILITY_ROOT_ENDPOINT,
+    MODULE_CONSENSUS_VERSION_ENDPOINT, NOTE_SPENT_ENDPOINT,
+    RECOVERY_BLIND_NONCE_OUTPOINTS_ENDPOINT, RECOVERY_COUNT_ENDPOINT, RECOVERY_SLICE_ENDPOINT,
+    RECOVERY_SLICE_HASH_ENDPOINT,
 };
 use fedimint_mint_common::{BlindNonce, Nonce, RecoveryItem};
 
@@ -36,6 +39,20 @@ async fn fetch_blind_nonce_outpoints(
         &self,
         blind_nonces: Vec<BlindNonce>,
     ) -> anyhow::Result<Vec<OutPoint>>;
+
+    /// Returns the last commitment of the mint to its liabilities the
+    /// federation agreed on in consensus.
+    async fn fetch_liability_root(&self) -> FederationResult<Option<LiabilityRoot>>;
+
+    /// Returns a proof that the note issued for the given blind nonce is
+    /// included in the last committed liabilities of the mint.
+    async fn fetch_liability_proof(
+        &self,
+        blind_nonce: BlindNonce,
+    ) -> FederationResult<Option<(LiabilityRoot, LiabilityProof)>>;
+
+    /// Returns the module consensus version the federation activated.
+    async fn fetch_module_consensus_version(&self) -> FederationResult<ModuleConsensusVersion>;
 }
 
 #[apply(async_trait_maybe_send!)]
@@ -104,4 +121,31 @@ async fn fetch_blind_nonce_outpoints(
         .await
         .map_err(|e| anyhow::anyhow!("{e}"))
     }
+
+    async fn fetch_liability_root(&self) -> FederationResult<Option<LiabilityRoot>> {
+        self.request_current_consensus(
+            LIABILITY_ROOT_ENDPOINT.to_string(),
+            ApiRequestErased::default(),
+        )
+        .await
+    }
+
+    async fn fetch_liability_proof(
+        &self,
+        blind_nonce: BlindNonce,
+    ) -> FederationResult<Option<(LiabilityRoot, LiabilityProof)>> {
+        self.request_current_consensus(
+            LIABILITY_PROOF_ENDPOINT.to_string(),
+            ApiRequestErased::new(blind_nonce),
+        )
+        .await
+    }
+
+    async fn fetch_module_consensus_version(&self) -> FederationResult<ModuleConsensusVersion> {
+        self.request_current_consensus(
+            MODULE_CONSENSUS_VERSION_ENDPOINT.to_string(),
+            ApiRequestErased::default(),
+        )
+        .await
+    }
 }
@@ -444,6 +444,10 @@ pub fn handle_input(&mut self, input: &MintInput) {
                 self.pending_outputs.remove(&input.note.nonce);
                 self.spendable_notes.remove(&input.note.nonce);
             }
+            MintInput::V1(_) => {
+                // Notes bound to a spending condition are never derived from
+                // our secret, so they can't be part of the recovered state
+            }
             MintInput::Default { variant, .. } => {
                 trace!("Ignoring future mint input variant {variant}");
             }
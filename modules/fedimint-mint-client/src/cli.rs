@@ -3,13 +3,19 @@
 use std::{ffi, iter};
 
 use anyhow::bail;
-use clap::Parser;
+use clap::{Parser, Subcommand};
+use fedimint_core::bitcoin::hashes::sha256;
+use fedimint_core::secp256k1::{Keypair, PublicKey, SECP256K1, SecretKey};
 use fedimint_core::{Amount, TieredMulti};
+use fedimint_mint_common::condition::{SpendingCondition, SpendingWitness};
 use futures::StreamExt;
+use hex::FromHex as _;
 use serde::Serialize;
 use serde_json::json;
 use tracing::{info, warn};
 
+use crate::conditional::{ConditionalNotes, MultisigDelegation};
+use crate::strategy::NoteSelectionStrategyConfig;
 use crate::{
     MintClientModule, OOBNotes, ReissueExternalNotesState, SelectNotesWithAtleastAmount,
     SelectNotesWithExactAmount,
@@ -54,6 +60,130 @@ enum Opts {
         /// E-Cash note to validate
         oob_notes: OOBNotes,
     },
+    /// Shows the strategy used to pick the denominations of issued and spent
+    /// notes, or persists a new one if given
+    NoteStrategy {
+        #[command(subcommand)]
+        strategy: Option<NoteStrategyOpts>,
+    },
+    /// Accepts e-cash without contacting the federation, it is reissued
+    /// automatically once the federation is reachable again
+    ReceiveOffline {
+        oob_notes: OOBNotes,
+        /// Who paid the e-cash, to track the value at risk per payer
+        #[clap(long)]
+        payer: Option<String>,
+    },
+    /// Shows the value of e-cash received offline that is still at risk and
+    /// the outcome of past offline receives per payer
+    OfflineSummary,
+    /// Issues e-cash that can only be redeemed by satisfying a spending
+    /// condition
+    SendConditional {
+        amount: Amount,
+        #[command(subcommand)]
+        condition: ConditionOpts,
+    },
+    /// Delegates e-cash bound to a multisig to the key of the redeemer as one
+    /// of its signers
+    DelegateConditional {
+        notes: ConditionalNotes,
+        /// Key the e-cash is delegated to
+        delegate: PublicKey,
+        /// Our secret key of the multisig
+        secret_key: SecretKey,
+    },
+    /// Redeems e-cash bound to a spending condition into our wallet
+    RedeemConditional {
+        notes: ConditionalNotes,
+        /// Secret keys satisfying the condition, for a multisig the key the
+        /// e-cash was delegated to
+        #[clap(long = "secret-key", required = true)]
+        secret_keys: Vec<SecretKey>,
+        /// Preimage of a hash lock as hex
+        #[clap(long, value_parser = parse_preimage, conflicts_with_all = ["refund", "delegations"])]
+        preimage: Option<[u8; 32]>,
+        /// Refund an expired time lock
+        #[clap(long, conflicts_with = "delegations")]
+        refund: bool,
+        /// Delegations of a threshold of the multisig signers
+        #[clap(long = "delegation")]
+        delegations: Vec<MultisigDelegation>,
+    },
+}
+
+#[derive(Subcommand, Serialize)]
+enum ConditionOpts {
+    /// Spendable by the owner of the key
+    Pubkey { pubkey: PublicKey },
+    /// Spendable by the owner of the key revealing the preimage of the hash
+    HashLock {
+        hash: sha256::Hash,
+        pubkey: PublicKey,
+    },
+    /// Spendable by the owner of the key and, once the federation's unix time
+    /// reached the locktime, by the owner of the refund key
+    TimeLock {
+        pubkey: PublicKey,
+        locktime: u64,
+        refund_pubkey: PublicKey,
+    },
+    /// Spendable by any threshold of the keys signing together
+    Multisig {
+        threshold: u16,
+        #[clap(required = true)]
+        pubkeys: Vec<PublicKey>,
+    },
+}
+
+impl From<ConditionOpts> for SpendingCondition {
+    fn from(opts: ConditionOpts) -> Self {
+        match opts {
+            ConditionOpts::Pubkey { pubkey } => Self::PublicKey(pubkey),
+            ConditionOpts::HashLock { hash, pubkey } => Self::HashLock { hash, pubkey },
+            ConditionOpts::TimeLock {
+                pubkey,
+                locktime,
+                refund_pubkey,
+            } => Self::TimeLock {
+                pubkey,
+                locktime,
+                refund_pubkey,
+            },
+            ConditionOpts::Multisig { threshold, pubkeys } => Self::Multisig { threshold, pubkeys },
+        }
+    }
+}
+
+fn parse_preimage(s: &str) -> anyhow::Result<[u8; 32]> {
+    Ok(<[u8; 32]>::from_hex(s)?)
+}
+
+#[derive(Subcommand, Serialize)]
+enum NoteStrategyOpts {
+    /// Hold a number of notes of every denomination to spend offline
+    KeepNotesPerTier { notes_per_tier: u16 },
+    /// Hold as few notes as possible
+    MinimalNotes,
+    /// Never hold notes above a maximum denomination
+    StandardAmounts { max_denomination: Amount },
+    /// Pay as little fees as possible
+    FeeMinimizing,
+}
+
+impl From<NoteStrategyOpts> for NoteSelectionStrategyConfig {
+    fn from(opts: NoteStrategyOpts) -> Self {
+        match opts {
+            NoteStrategyOpts::KeepNotesPerTier { notes_per_tier } => {
+                Self::KeepNotesPerTier { notes_per_tier }
+            }
+            NoteStrategyOpts::MinimalNotes => Self::MinimalNotes,
+            NoteStrategyOpts::StandardAmounts { max_denomination } => {
+                Self::StandardAmounts { max_denomination }
+            }
+            NoteStrategyOpts::FeeMinimizing => Self::FeeMinimizing,
+        }
+    }
 }
 
 async fn spend(
@@ -194,5 +324,91 @@ pub(crate) async fn handle_cli_command(
                 Ok(json!({ "amount_msat": amount }))
             }
         }
+        Opts::NoteStrategy { strategy } => {
+            if let Some(strategy) = strategy {
+                mint.set_note_selection_strategy(strategy.into()).await;
+            }
+
+            Ok(
+                serde_json::to_value(mint.get_note_selection_strategy().await)
+                    .expect("JSON serialization failed"),
+            )
+        }
+        Opts::ReceiveOffline { oob_notes, payer } => {
+            let receipt = mint.receive_offline(oob_notes, payer).await?;
+
+            Ok(serde_json::to_value(receipt).expect("JSON serialization failed"))
+        }
+        Opts::OfflineSummary => Ok(
+            serde_json::to_value(mint.get_offline_receive_summary().await)
+                .expect("JSON serialization failed"),
+        ),
+        Opts::SendConditional { amount, condition } => {
+            let operation_id = mint
+                .send_conditional_notes(amount, condition.into(), ())
+                .await?;
+
+            let notes = mint.await_conditional_notes(operation_id).await?;
+
+            Ok(json!({
+                "operation_id": operation_id,
+                "notes": notes,
+            }))
+        }
+        Opts::DelegateConditional {
+            notes,
+            delegate,
+            secret_key,
+        } => {
+            let delegation =
+                notes.delegate(delegate, &Keypair::from_secret_key(SECP256K1, &secret_key))?;
+
+            Ok(json!({
+                "delegation": delegation,
+            }))
+        }
+        Opts::RedeemConditional {
+            notes,
+            secret_keys,
+            preimage,
+            refund,
+            delegations,
+        } => {
+            let amount = notes.total_amount();
+
+            let operation_id = match (preimage, refund) {
+                (None, false) if !delegations.is_empty() => {
+                    let [delegate_key] = secret_keys[..] else {
+                        bail!("Multisig e-cash is redeemed with the single delegate key");
+                    };
+
+                    mint.redeem_multisig_notes(notes, delegations, delegate_key, ())
+                        .await?
+                }
+                (preimage, refund) => {
+                    let witness = match (preimage, refund) {
+                        (Some(preimage), _) => SpendingWitness::Preimage(preimage),
+                        (None, true) => SpendingWitness::Refund,
+                        (None, false) => SpendingWitness::Key,
+                    };
+
+                    mint.redeem_conditional_notes(notes, witness, secret_keys, ())
+                        .await?
+                }
+            };
+
+            let mut updates = mint
+                .subscribe_reissue_external_notes(operation_id)
+                .await?
+                .into_stream();
+
+            while let Some(update) = updates.next().await {
+                if let ReissueExternalNotesState::Failed(e) = update {
+                    bail!("Redeeming conditional notes failed: {e}");
+                }
+            }
+
+            Ok(serde_json::to_value(amount).expect("JSON serialization failed"))
+        }
     }
 }
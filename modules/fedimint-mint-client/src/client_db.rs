@@ -14,9 +14,12 @@
 use tracing::debug;
 
 use crate::backup::recovery::MintRecoveryState;
+use crate::conditional::ConditionalIssuance;
 use crate::input::{MintInputCommon, MintInputStateMachine, MintInputStateMachineV0};
+use crate::offline::{OfflineReceivePayerStats, UnconfirmedReceive};
 use crate::oob::{MintOOBStateMachine, MintOOBStateMachineV0, MintOOBStates, MintOOBStatesV0};
 use crate::output::{MintOutputCommon, MintOutputStateMachine, MintOutputStateMachineV0};
+use crate::strategy::NoteSelectionStrategyConfig;
 use crate::{MintClientStateMachines, NoteIndex, SpendableNoteUndecoded};
 
 #[repr(u8)]
@@ -29,6 +32,11 @@ pub enum DbKeyPrefix {
     RecoveryFinalized = 0x2d,
     ReusedNoteIndices = 0x2e,
     RecoveryStateV2 = 0x2f,
+    NoteSelectionStrategy = 0x30,
+    UnconfirmedReceive = 0x31,
+    OfflineReceivedNonce = 0x32,
+    OfflineReceivePayerStats = 0x33,
+    ConditionalIssuance = 0x34,
     /// Prefixes between 0xb0..=0xcf shall all be considered allocated for
     /// historical and future external use
     ExternalReservedStart = 0xb0,
@@ -136,6 +144,90 @@ pub struct NoteKey {
     db_prefix = DbKeyPrefix::RecoveryStateV2,
 );
 
+/// The built-in note selection strategy chosen by the user, the default
+/// strategy is used if absent
+#[derive(Debug, Clone, Encodable, Decodable, Serialize)]
+pub struct NoteSelectionStrategyKey;
+
+impl_db_record!(
+    key = NoteSelectionStrategyKey,
+    value = NoteSelectionStrategyConfig,
+    db_prefix = DbKeyPrefix::NoteSelectionStrategy,
+);
+
+/// E-cash received offline, keyed by the operation it will be reissued under
+#[derive(Debug, Clone, Encodable, Decodable, Serialize)]
+pub struct UnconfirmedReceiveKey(pub OperationId);
+
+#[derive(Debug, Clone, Encodable, Decodable)]
+pub struct UnconfirmedReceivePrefix;
+
+impl_db_record!(
+    key = UnconfirmedReceiveKey,
+    value = UnconfirmedReceive,
+    db_prefix = DbKeyPrefix::UnconfirmedReceive,
+);
+
+impl_db_lookup!(
+    key = UnconfirmedReceiveKey,
+    query_prefix = UnconfirmedReceivePrefix
+);
+
+/// Nonces of all notes ever received offline, used to reject notes received
+/// twice before they could be reissued
+#[derive(Debug, Clone, Encodable, Decodable, Serialize)]
+pub struct OfflineReceivedNonceKey(pub Nonce);
+
+#[derive(Debug, Clone, Encodable, Decodable)]
+pub struct OfflineReceivedNonceKeyPrefix;
+
+impl_db_record!(
+    key = OfflineReceivedNonceKey,
+    value = OperationId,
+    db_prefix = DbKeyPrefix::OfflineReceivedNonce,
+);
+
+impl_db_lookup!(
+    key = OfflineReceivedNonceKey,
+    query_prefix = OfflineReceivedNonceKeyPrefix
+);
+
+#[derive(Debug, Clone, Encodable, Decodable, Serialize)]
+pub struct OfflineReceivePayerStatsKey(pub Option<String>);
+
+#[derive(Debug, Clone, Encodable, Decodable)]
+pub struct OfflineReceivePayerStatsKeyPrefix;
+
+impl_db_record!(
+    key = OfflineReceivePayerStatsKey,
+    value = OfflineReceivePayerStats,
+    db_prefix = DbKeyPrefix::OfflineReceivePayerStats,
+);
+
+impl_db_lookup!(
+    key = OfflineReceivePayerStatsKey,
+    query_prefix = OfflineReceivePayerStatsKeyPrefix
+);
+
+/// Conditional notes we issued, kept to fetch their signatures once the
+/// issuing transaction was accepted
+#[derive(Debug, Clone, Encodable, Decodable, Serialize)]
+pub struct ConditionalIssuanceKey(pub OperationId);
+
+#[derive(Debug, Clone, Encodable, Decodable)]
+pub struct ConditionalIssuancePrefix;
+
+impl_db_record!(
+    key = ConditionalIssuanceKey,
+    value = ConditionalIssuance,
+    db_prefix = DbKeyPrefix::ConditionalIssuance,
+);
+
+impl_db_lookup!(
+    key = ConditionalIssuanceKey,
+    query_prefix = ConditionalIssuancePrefix
+);
+
 pub async fn migrate_to_v1(
     dbtx: &mut DatabaseTransaction<'_>,
 ) -> anyhow::Result<Option<(Vec<(Vec<u8>, OperationId)>, Vec<(Vec<u8>, OperationId)>)>> {
@@ -0,0 +1,372 @@
+//! Sending and redeeming e-cash bound to spending conditions
+//!
+//! Conditional notes are issued by a transaction funded from our wallet whose
+//! outputs are blinded nonces committing to a [`SpendingCondition`]. We don't
+//! hold a spend key for these notes, so they are not tracked by the output
+//! state machines. Instead the issuance is persisted until the federation
+//! signed the notes, so they can still be fetched after a restart.
+
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use anyhow::{anyhow, bail, ensure};
+use base64::Engine as _;
+use fedimint_api_client::api::{DynGlobalApi, FederationApiExt, ServerError};
+use fedimint_api_client::query::FilterMapThreshold;
+use fedimint_core::config::FederationIdPrefix;
+use fedimint_core::core::Decoder;
+use fedimint_core::encoding::{Decodable, Encodable};
+use fedimint_core::module::ApiRequestErased;
+use fedimint_core::module::registry::ModuleDecoderRegistry;
+use fedimint_core::secp256k1::rand::{RngCore, thread_rng};
+use fedimint_core::secp256k1::{Keypair, PublicKey, SECP256K1, SecretKey, schnorr};
+use fedimint_core::{Amount, NumPeersExt, OutPoint, PeerId, Tiered, TransactionId};
+use fedimint_mint_common::condition::{
+    SpendingCondition, SpendingWitness, sign_multisig_delegation,
+};
+use fedimint_mint_common::endpoint_constants::AWAIT_OUTPUT_OUTCOME_ENDPOINT;
+use fedimint_mint_common::{BlindNonce, Note};
+use futures::future::join_all;
+use serde::{Deserialize, Serialize};
+use tbs::{
+    AggregatePublicKey, BlindedMessage, BlindedSignature, BlindingKey, PublicKeyShare,
+    aggregate_signature_shares, blind_message, unblind_signature,
+};
+
+use crate::BASE64_URL_SAFE;
+use crate::output::verify_blind_share;
+
+/// Keeps the data to unblind the signature of a conditional note once the
+/// federation processed the transaction issuing it
+#[derive(Debug, Clone, PartialEq, Eq, Encodable, Decodable, Serialize, Deserialize)]
+pub struct ConditionalNoteRequest {
+    pub amount: Amount,
+    pub condition: SpendingCondition,
+    pub salt: [u8; 32],
+    blinding_key: BlindingKey,
+}
+
+impl ConditionalNoteRequest {
+    pub fn new(amount: Amount, condition: SpendingCondition) -> Self {
+        let mut salt = [0; 32];
+        thread_rng().fill_bytes(&mut salt);
+
+        ConditionalNoteRequest {
+            amount,
+            condition,
+            salt,
+            blinding_key: BlindingKey::random(),
+        }
+    }
+
+    pub fn blinded_message(&self) -> BlindedMessage {
+        blind_message(
+            self.condition.nonce(&self.salt).to_message(),
+            self.blinding_key,
+        )
+    }
+
+    pub fn blind_nonce(&self) -> BlindNonce {
+        BlindNonce(self.blinded_message())
+    }
+
+    pub fn finalize(&self, blinded_signature: BlindedSignature) -> ConditionalNote {
+        ConditionalNote {
+            amount: self.amount,
+            note: Note {
+                nonce: self.condition.nonce(&self.salt),
+                signature: unblind_signature(self.blinding_key, blinded_signature),
+            },
+            condition: self.condition.clone(),
+            salt: self.salt,
+        }
+    }
+}
+
+/// The issuance of conditional notes by a transaction of ours
+#[derive(Debug, Clone, PartialEq, Eq, Encodable, Decodable, Serialize, Deserialize)]
+pub struct ConditionalIssuance {
+    pub txid: TransactionId,
+    /// The issuance requests by output index
+    pub requests: Vec<(u64, ConditionalNoteRequest)>,
+}
+
+/// A note that can only be spent by satisfying its spending condition
+#[derive(Debug, Clone, PartialEq, Eq, Encodable, Decodable, Serialize, Deserialize)]
+pub struct ConditionalNote {
+    pub amount: Amount,
+    pub note: Note,
+    pub condition: SpendingCondition,
+    pub salt: [u8; 32],
+}
+
+impl ConditionalNote {
+    /// Checks the signature of the federation and that the note's nonce
+    /// commits to its condition
+    pub fn verify(&self, tbs_pks: &Tiered<AggregatePublicKey>) -> anyhow::Result<()> {
+        let key = tbs_pks
+            .get(self.amount)
+            .ok_or(anyhow!("Invalid amount tier: {}", self.amount))?;
+
+        ensure!(self.note.verify(*key), "Invalid note signature");
+
+        ensure!(
+            self.condition.nonce(&self.salt) == self.note.nonce,
+            "The note's nonce does not commit to its spending condition"
+        );
+
+        Ok(())
+    }
+
+    /// Returns the key signing the transaction that spends this note with the
+    /// given witness, picked from the secret keys. For a multisig this is the
+    /// key the signers delegated the note to.
+    pub fn spend_keypair(
+        &self,
+        witness: &SpendingWitness,
+        secret_keys: &[SecretKey],
+    ) -> anyhow::Result<Keypair> {
+        // The federation checks the time lock of a refund against its
+        // consensus unix time
+        let spend_key = self
+            .condition
+            .spend_key(&self.note.nonce, witness, u64::MAX)?;
+
+        let keypair = secret_keys
+            .iter()
+            .find(|secret_key| secret_key.public_key(SECP256K1) == spend_key)
+            .map(|secret_key| Keypair::from_secret_key(SECP256K1, secret_key))
+            .ok_or(anyhow!(
+                "The secret keys don't match the spending condition"
+            ))?;
+
+        Ok(keypair)
+    }
+
+    /// Returns the index of our key in the note's multisig condition
+    fn multisig_signer(&self, keypair: &Keypair) -> anyhow::Result<u16> {
+        let SpendingCondition::Multisig { pubkeys, .. } = &self.condition else {
+            bail!("The note is not bound to a multisig");
+        };
+
+        let signer = pubkeys
+            .iter()
+            .position(|pubkey| *pubkey == keypair.public_key())
+            .ok_or(anyhow!("Our key is not a signer of the multisig"))?;
+
+        Ok(u16::try_from(signer).expect("Multisigs have at most 16 keys"))
+    }
+}
+
+/// Conditional notes to transfer out-of-band
+#[derive(Debug, Clone, PartialEq, Eq, Encodable, Decodable)]
+pub struct ConditionalNotes {
+    pub federation_id_prefix: FederationIdPrefix,
+    pub notes: Vec<ConditionalNote>,
+}
+
+impl ConditionalNotes {
+    pub fn total_amount(&self) -> Amount {
+        self.notes.iter().map(|note| note.amount).sum()
+    }
+
+    /// Delegates the multisig notes to the key `delegate` as one of their
+    /// signers. Every signer delegates independently, the holder of the
+    /// delegate key then redeems the notes once a threshold of signers did
+    /// so, see [`ConditionalNotes::delegation_witnesses`].
+    pub fn delegate(
+        &self,
+        delegate: PublicKey,
+        keypair: &Keypair,
+    ) -> anyhow::Result<MultisigDelegation> {
+        let signatures = self
+            .notes
+            .iter()
+            .map(|note| {
+                Ok((
+                    note.multisig_signer(keypair)?,
+                    sign_multisig_delegation(&note.note.nonce, &delegate, keypair),
+                ))
+            })
+            .collect::<anyhow::Result<Vec<(u16, schnorr::Signature)>>>()?;
+
+        Ok(MultisigDelegation {
+            delegate,
+            signatures,
+        })
+    }
+
+    /// Combines the delegations of the signers into a witness for every note
+    pub fn delegation_witnesses(
+        &self,
+        delegations: &[MultisigDelegation],
+    ) -> anyhow::Result<Vec<SpendingWitness>> {
+        let delegate = delegations
+            .first()
+            .ok_or(anyhow!("No delegations were given"))?
+            .delegate;
+
+        ensure!(
+            delegations
+                .iter()
+                .all(|delegation| delegation.delegate == delegate
+                    && delegation.signatures.len() == self.notes.len()),
+            "The delegations are not for the same key and notes"
+        );
+
+        (0..self.notes.len())
+            .map(|index| {
+                let signatures = delegations
+                    .iter()
+                    .map(|delegation| delegation.signatures[index])
+                    .collect::<BTreeMap<u16, schnorr::Signature>>();
+
+                ensure!(
+                    signatures.len() == delegations.len(),
+                    "Multiple delegations of the same signer were given"
+                );
+
+                Ok(SpendingWitness::Delegation {
+                    delegate,
+                    signatures: signatures.into_iter().collect(),
+                })
+            })
+            .collect()
+    }
+}
+
+/// A multisig signer's delegation of conditional notes to a key, with one
+/// signature per note in the order of the notes
+#[derive(Debug, Clone, PartialEq, Eq, Encodable, Decodable)]
+pub struct MultisigDelegation {
+    pub delegate: PublicKey,
+    pub signatures: Vec<(u16, schnorr::Signature)>,
+}
+
+impl FromStr for MultisigDelegation {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bytes = BASE64_URL_SAFE.decode(s.trim())?;
+
+        Ok(MultisigDelegation::consensus_decode_whole(
+            &bytes,
+            &ModuleDecoderRegistry::default(),
+        )?)
+    }
+}
+
+impl Display for MultisigDelegation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&BASE64_URL_SAFE.encode(self.consensus_encode_to_vec()))
+    }
+}
+
+impl Serialize for MultisigDelegation {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl FromStr for ConditionalNotes {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bytes = BASE64_URL_SAFE.decode(s.trim())?;
+
+        let notes =
+            ConditionalNotes::consensus_decode_whole(&bytes, &ModuleDecoderRegistry::default())?;
+
+        ensure!(!notes.notes.is_empty(), "ConditionalNotes cannot be empty");
+
+        Ok(notes)
+    }
+}
+
+impl Display for ConditionalNotes {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&BASE64_URL_SAFE.encode(self.consensus_encode_to_vec()))
+    }
+}
+
+impl Serialize for ConditionalNotes {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for ConditionalNotes {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        FromStr::from_str(&s).map_err(serde::de::Error::custom)
+    }
+}
+
+/// Fetches the signature shares for an accepted issuance and combines them
+/// into conditional notes
+pub(crate) async fn fetch_conditional_notes(
+    api: &DynGlobalApi,
+    decoder: &Decoder,
+    peer_tbs_pks: &BTreeMap<PeerId, Tiered<PublicKeyShare>>,
+    tbs_pks: &Tiered<AggregatePublicKey>,
+    issuance: &ConditionalIssuance,
+) -> anyhow::Result<Vec<ConditionalNote>> {
+    let notes = join_all(issuance.requests.iter().map(|(out_idx, request)| {
+        let decoder = decoder.clone();
+        let peer_tbs_pks = peer_tbs_pks.clone();
+        let amount = request.amount;
+        let blinded_message = request.blinded_message();
+
+        async move {
+            let shares = api
+                .request_with_strategy_retry(
+                    FilterMapThreshold::new(
+                        move |peer, outcome| {
+                            verify_blind_share(
+                                peer,
+                                &outcome,
+                                amount,
+                                blinded_message,
+                                &decoder,
+                                &peer_tbs_pks,
+                            )
+                            .map_err(ServerError::InvalidResponse)
+                        },
+                        api.all_peers().to_num_peers(),
+                    ),
+                    AWAIT_OUTPUT_OUTCOME_ENDPOINT.to_owned(),
+                    ApiRequestErased::new(OutPoint {
+                        txid: issuance.txid,
+                        out_idx: *out_idx,
+                    }),
+                )
+                .await;
+
+            let blinded_signature = aggregate_signature_shares(
+                &shares
+                    .into_iter()
+                    .map(|(peer, share)| (peer.to_usize() as u64, share))
+                    .collect(),
+            );
+
+            request.finalize(blinded_signature)
+        }
+    }))
+    .await;
+
+    for note in &notes {
+        note.verify(tbs_pks)?;
+    }
+
+    Ok(notes)
+}
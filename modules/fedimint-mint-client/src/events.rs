@@ -120,3 +120,65 @@ impl Event for ReceivePaymentUpdateEvent {
     const KIND: EventKind = EventKind::from_static("payment-receive-update");
     const PERSISTENCE: EventPersistence = EventPersistence::Persistent;
 }
+
+/// Event emitted when e-cash is accepted while the federation may be
+/// unreachable. The notes are only verified against the mint's public keys
+/// and may still turn out to be double-spent.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct OfflineNotesReceived {
+    pub operation_id: OperationId,
+    pub payer: Option<String>,
+    pub amount: Amount,
+}
+
+impl Event for OfflineNotesReceived {
+    const MODULE: Option<ModuleKind> = Some(KIND);
+    const KIND: EventKind = EventKind::from_static("offline-notes-received");
+    const PERSISTENCE: EventPersistence = EventPersistence::Persistent;
+}
+
+/// Event emitted when e-cash received offline was reissued successfully.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct OfflineReceiveConfirmed {
+    pub operation_id: OperationId,
+    pub payer: Option<String>,
+    pub amount: Amount,
+}
+
+impl Event for OfflineReceiveConfirmed {
+    const MODULE: Option<ModuleKind> = Some(KIND);
+    const KIND: EventKind = EventKind::from_static("offline-receive-confirmed");
+    const PERSISTENCE: EventPersistence = EventPersistence::Persistent;
+}
+
+/// Event emitted when the federation rejected the reissue of e-cash received
+/// offline, usually because the payer spent the notes elsewhere. The amount is
+/// lost.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct OfflineReceiveFailed {
+    pub operation_id: OperationId,
+    pub payer: Option<String>,
+    pub amount: Amount,
+    /// Why the reissue failed
+    pub reason: String,
+}
+
+impl Event for OfflineReceiveFailed {
+    const MODULE: Option<ModuleKind> = Some(KIND);
+    const KIND: EventKind = EventKind::from_static("offline-receive-failed");
+    const PERSISTENCE: EventPersistence = EventPersistence::Persistent;
+}
+
+/// Event emitted when e-cash bound to a spending condition is issued to be
+/// sent out-of-band
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ConditionalNotesSent {
+    pub operation_id: OperationId,
+    pub amount: Amount,
+}
+
+impl Event for ConditionalNotesSent {
+    const MODULE: Option<ModuleKind> = Some(KIND);
+    const KIND: EventKind = EventKind::from_static("conditional-notes-sent");
+    const PERSISTENCE: EventPersistence = EventPersistence::Persistent;
+}
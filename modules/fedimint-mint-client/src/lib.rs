@@ -13,8 +13,12 @@
 mod cli;
 /// Database keys used throughout the mint client module
 pub mod client_db;
+/// Sending and redeeming e-cash bound to spending conditions
+pub mod conditional;
 /// State machines for mint inputs
 mod input;
+/// Receiving e-cash while the federation is unreachable
+pub mod offline;
 /// State machines for out-of-band transmitted e-cash notes
 mod oob;
 /// State machines for mint outputs
@@ -29,6 +33,9 @@
 
 pub mod visualize;
 
+/// Strategies for choosing the denominations of issued and spent notes
+pub mod strategy;
+
 use std::cmp::{Ordering, min};
 use std::collections::{BTreeMap, BTreeSet};
 use std::fmt;
@@ -48,8 +55,11 @@
     DbKeyPrefix, NoteKeyPrefix, RecoveryFinalizedKey, RecoveryStateKey, RecoveryStateV2Key,
     ReusedNoteIndices, migrate_state_to_v2, migrate_to_v1,
 };
-use events::{NoteSpent, OOBNotesReissued, OOBNotesSpent, ReceivePaymentEvent, SendPaymentEvent};
-use fedimint_api_client::api::DynModuleApi;
+use events::{
+    ConditionalNotesSent, NoteSpent, OOBNotesReissued, OOBNotesSpent, OfflineNotesReceived,
+    ReceivePaymentEvent, SendPaymentEvent,
+};
+use fedimint_api_client::api::{DynModuleApi, FederationResult};
 use fedimint_client_module::db::{ClientModuleMigrationFn, migrate_state};
 use fedimint_client_module::module::init::{
     ClientModuleInit, ClientModuleInitArgs, ClientModuleRecoverArgs,
@@ -75,13 +85,15 @@
 };
 use fedimint_core::encoding::{Decodable, DecodeError, Encodable};
 use fedimint_core::invite_code::InviteCode;
+use fedimint_core::module::liabilities::{LiabilityNode, LiabilityRoot};
 use fedimint_core::module::registry::{ModuleDecoderRegistry, ModuleRegistry};
 use fedimint_core::module::{
     AmountUnit, Amounts, ApiVersion, CommonModuleInit, ModuleCommon, ModuleInit, MultiApiVersion,
 };
 use fedimint_core::secp256k1::rand::prelude::IteratorRandom;
 use fedimint_core::secp256k1::rand::thread_rng;
-use fedimint_core::secp256k1::{All, Keypair, Secp256k1};
+use fedimint_core::secp256k1::{All, Keypair, Secp256k1, SecretKey};
+use fedimint_core::task::TaskGroup;
 use fedimint_core::util::{BoxFuture, BoxStream, NextOrPending, SafeUrl};
 use fedimint_core::{
     Amount, OutPoint, PeerId, Tiered, TieredCounts, TieredMulti, TransactionId, apply,
@@ -90,6 +102,7 @@
 use fedimint_derive_secret::{ChildId, DerivableSecret};
 use fedimint_logging::LOG_CLIENT_MODULE_MINT;
 pub use fedimint_mint_common as common;
+use fedimint_mint_common::condition::{SpendingCondition, SpendingWitness};
 use fedimint_mint_common::config::{FeeConsensus, MintClientConfig};
 pub use fedimint_mint_common::*;
 use futures::future::try_join_all;
@@ -107,14 +120,26 @@
 
 use crate::backup::EcashBackup;
 use crate::client_db::{
-    CancelledOOBSpendKey, CancelledOOBSpendKeyPrefix, NextECashNoteIndexKey,
-    NextECashNoteIndexKeyPrefix, NoteKey,
+    CancelledOOBSpendKey, CancelledOOBSpendKeyPrefix, ConditionalIssuanceKey,
+    ConditionalIssuancePrefix, NextECashNoteIndexKey, NextECashNoteIndexKeyPrefix, NoteKey,
+    NoteSelectionStrategyKey, OfflineReceivePayerStatsKey, OfflineReceivePayerStatsKeyPrefix,
+    OfflineReceivedNonceKey, OfflineReceivedNonceKeyPrefix, UnconfirmedReceiveKey,
+    UnconfirmedReceivePrefix,
+};
+use crate::conditional::{
+    ConditionalIssuance, ConditionalNoteRequest, ConditionalNotes, MultisigDelegation,
+    fetch_conditional_notes,
 };
 use crate::input::{MintInputCommon, MintInputStateMachine, MintInputStates};
+use crate::offline::{
+    OfflineReceipt, OfflineReceiveError, OfflineReceivePayerStats, PayerRiskSummary,
+    UnconfirmedReceive,
+};
 use crate::oob::{MintOOBStateMachine, MintOOBStates};
 use crate::output::{
     MintOutputCommon, MintOutputStateMachine, MintOutputStates, NoteIssuanceRequest,
 };
+use crate::strategy::{NoteSelectionStrategy, NoteSelectionStrategyConfig};
 
 const MINT_E_CASH_TYPE_CHILD_ID: ChildId = ChildId(0);
 
@@ -551,6 +576,12 @@ pub enum MintOperationMetaVariant {
         requested_amount: Amount,
         oob_notes: OOBNotes,
     },
+    /// Issuance of notes bound to a spending condition, see
+    /// [`MintClientModule::send_conditional_notes`]
+    ConditionalSend {
+        txid: TransactionId,
+        condition: SpendingCondition,
+    },
 }
 
 #[derive(Debug, Clone)]
@@ -760,6 +791,52 @@ async fn dump_database(
                         mint_client_items.insert("RecoveryFinalized".to_string(), Box::new(val));
                     }
                 }
+                DbKeyPrefix::NoteSelectionStrategy => {
+                    if let Some(val) = dbtx.get_value(&NoteSelectionStrategyKey).await {
+                        mint_client_items
+                            .insert("NoteSelectionStrategy".to_string(), Box::new(val));
+                    }
+                }
+                DbKeyPrefix::UnconfirmedReceive => {
+                    push_db_pair_items!(
+                        dbtx,
+                        UnconfirmedReceivePrefix,
+                        UnconfirmedReceiveKey,
+                        UnconfirmedReceive,
+                        mint_client_items,
+                        "UnconfirmedReceive"
+                    );
+                }
+                DbKeyPrefix::OfflineReceivedNonce => {
+                    push_db_pair_items!(
+                        dbtx,
+                        OfflineReceivedNonceKeyPrefix,
+                        OfflineReceivedNonceKey,
+                        OperationId,
+                        mint_client_items,
+                        "OfflineReceivedNonce"
+                    );
+                }
+                DbKeyPrefix::OfflineReceivePayerStats => {
+                    push_db_pair_items!(
+                        dbtx,
+                        OfflineReceivePayerStatsKeyPrefix,
+                        OfflineReceivePayerStatsKey,
+                        OfflineReceivePayerStats,
+                        mint_client_items,
+                        "OfflineReceivePayerStats"
+                    );
+                }
+                DbKeyPrefix::ConditionalIssuance => {
+                    push_db_pair_items!(
+                        dbtx,
+                        ConditionalIssuancePrefix,
+                        ConditionalIssuanceKey,
+                        ConditionalIssuance,
+                        mint_client_items,
+                        "ConditionalIssuance"
+                    );
+                }
                 DbKeyPrefix::RecoveryState
                 | DbKeyPrefix::ReusedNoteIndices
                 | DbKeyPrefix::RecoveryStateV2
@@ -783,6 +860,9 @@ fn supported_api_versions(&self) -> MultiApiVersion {
     }
 
     async fn init(&self, args: &ClientModuleInitArgs<Self>) -> anyhow::Result<Self::Module> {
+        let (offline_reissue_wakeup_sender, offline_reissue_wakeup_receiver) =
+            tokio::sync::watch::channel(());
+
         Ok(MintClientModule {
             federation_id: *args.federation_id(),
             cfg: args.cfg().clone(),
@@ -791,6 +871,10 @@ async fn init(&self, args: &ClientModuleInitArgs<Self>) -> anyhow::Result<Self::
             notifier: args.notifier().clone(),
             client_ctx: args.context(),
             balance_update_sender: tokio::sync::watch::channel(()).0,
+            custom_note_selection_strategy: RwLock::new(None),
+            task_group: args.task_group().clone(),
+            offline_reissue_wakeup_sender,
+            offline_reissue_wakeup_receiver,
         })
     }
 
@@ -883,6 +967,10 @@ pub struct MintClientModule {
     notifier: ModuleNotifier<MintClientStateMachines>,
     pub client_ctx: ClientContext<Self>,
     balance_update_sender: tokio::sync::watch::Sender<()>,
+    custom_note_selection_strategy: RwLock<Option<Arc<dyn NoteSelectionStrategy>>>,
+    task_group: TaskGroup,
+    offline_reissue_wakeup_sender: tokio::sync::watch::Sender<()>,
+    offline_reissue_wakeup_receiver: tokio::sync::watch::Receiver<()>,
 }
 
 impl fmt::Debug for MintClientModule {
@@ -975,6 +1063,16 @@ fn output_fee(
         ))
     }
 
+    async fn start(&self) {
+        self.task_group.spawn_cancellable(
+            "offline receive reissuer",
+            offline::run_offline_reissuer(
+                self.client_ctx.clone(),
+                self.offline_reissue_wakeup_receiver.clone(),
+            ),
+        );
+    }
+
     #[cfg(feature = "cli")]
     async fn handle_cli_command(
         &self,
@@ -1050,13 +1148,15 @@ async fn create_final_inputs_and_outputs(
             .map(|input| self.cfg.fee_consensus.fee(input.0.amounts.get_bitcoin()))
             .sum();
 
+        let denominations = self.note_selection_strategy(dbtx).await.represent_amount(
+            input_amount.saturating_sub(output_amount),
+            &self.get_note_counts_by_denomination(dbtx).await,
+            &self.cfg.tbs_pks.tiers().copied().collect::<Vec<Amount>>(),
+            &self.cfg.fee_consensus,
+        );
+
         let outputs = self
-            .create_output(
-                dbtx,
-                operation_id,
-                2,
-                input_amount.saturating_sub(output_amount),
-            )
+            .create_output_for_denominations(dbtx, operation_id, denominations)
             .await;
 
         Ok((
@@ -1256,13 +1356,31 @@ async fn create_sufficient_input(
             return Ok(vec![]);
         }
 
-        let selected_notes = Self::select_notes(
-            dbtx,
-            &SelectNotesWithAtleastAmount,
+        let counts = self.note_selection_strategy(dbtx).await.select_inputs(
             min_amount,
-            self.cfg.fee_consensus.clone(),
-        )
-        .await?;
+            &self.get_note_counts_by_denomination(dbtx).await,
+            &self.cfg.tbs_pks.tiers().copied().collect::<Vec<Amount>>(),
+            &self.cfg.fee_consensus,
+        )?;
+
+        let (selected_notes, unavailable) =
+            self.get_available_notes_by_tier_counts(dbtx, counts).await;
+
+        // A custom strategy may select notes we do not hold
+        if !unavailable.is_empty() {
+            warn!(target: LOG_CLIENT_MODULE_MINT, ?unavailable, "Note selection strategy selected notes we don't hold");
+
+            return Err(InsufficientBalanceError {
+                requested_amount: min_amount,
+                total_amount: selected_notes.total_amount(),
+            }
+            .into());
+        }
+
+        let selected_notes = selected_notes
+            .into_iter_items()
+            .map(|(amount, note)| Ok((amount, note.decode()?)))
+            .collect::<anyhow::Result<TieredMulti<SpendableNote>>>()?;
 
         for (amount, note) in selected_notes.iter_items() {
             debug!(target: LOG_CLIENT_MODULE_MINT, %amount, %note, "Spending note as sufficient input to fund a tx");
@@ -1336,6 +1454,16 @@ pub async fn create_output(
             &self.cfg.fee_consensus,
         );
 
+        self.create_output_for_denominations(dbtx, operation_id, denominations)
+            .await
+    }
+
+    async fn create_output_for_denominations(
+        &self,
+        dbtx: &mut DatabaseTransaction<'_>,
+        operation_id: OperationId,
+        denominations: TieredCounts,
+    ) -> ClientOutputBundle<MintOutput, MintClientStateMachines> {
         let mut outputs = Vec::new();
         let mut issuance_requests = Vec::new();
 
@@ -1382,6 +1510,60 @@ pub async fn create_output(
         )
     }
 
+    /// Persists the built-in [`NoteSelectionStrategy`] used to fund
+    /// transactions from now on
+    pub async fn set_note_selection_strategy(&self, config: NoteSelectionStrategyConfig) {
+        let mut dbtx = self.client_ctx.module_db().begin_transaction().await;
+
+        dbtx.insert_entry(&NoteSelectionStrategyKey, &config).await;
+
+        dbtx.commit_tx().await;
+    }
+
+    /// Returns the persisted built-in [`NoteSelectionStrategy`], which is
+    /// ignored while a custom strategy is set
+    pub async fn get_note_selection_strategy(&self) -> NoteSelectionStrategyConfig {
+        self.client_ctx
+            .module_db()
+            .begin_transaction_nc()
+            .await
+            .get_value(&NoteSelectionStrategyKey)
+            .await
+            .unwrap_or_default()
+    }
+
+    /// Uses a custom [`NoteSelectionStrategy`] instead of the persisted one
+    /// until it is reset to `None` or the client is restarted
+    pub fn set_custom_note_selection_strategy(
+        &self,
+        strategy: Option<Arc<dyn NoteSelectionStrategy>>,
+    ) {
+        *self
+            .custom_note_selection_strategy
+            .write()
+            .expect("Locking can't fail") = strategy;
+    }
+
+    async fn note_selection_strategy(
+        &self,
+        dbtx: &mut DatabaseTransaction<'_>,
+    ) -> Arc<dyn NoteSelectionStrategy> {
+        let custom = self
+            .custom_note_selection_strategy
+            .read()
+            .expect("Locking can't fail")
+            .clone();
+
+        match custom {
+            Some(strategy) => strategy,
+            None => dbtx
+                .get_value(&NoteSelectionStrategyKey)
+                .await
+                .unwrap_or_default()
+                .to_strategy(),
+        }
+    }
+
     /// Returns the number of held e-cash notes per denomination
     pub async fn get_note_counts_by_denomination(
         &self,
@@ -1474,60 +1656,38 @@ pub async fn await_output_finalized(
         stream.next_or_pending().await
     }
 
-    /// Provisional implementation of note consolidation
+    /// Note consolidation as decided by the [`NoteSelectionStrategy`]
     ///
-    /// When a certain denomination crosses the threshold of notes allowed,
-    /// spend some chunk of them as inputs.
+    /// When the strategy considers some of the held notes excessive, spend
+    /// them as inputs.
     ///
     /// Return notes and the sume of their amount.
     pub async fn consolidate_notes(
         &self,
         dbtx: &mut DatabaseTransaction<'_>,
     ) -> anyhow::Result<Vec<(ClientInput<MintInput>, SpendableNote)>> {
-        /// At how many notes of the same denomination should we try to
-        /// consolidate
-        const MAX_NOTES_PER_TIER_TRIGGER: usize = 8;
-        /// Number of notes per tier to leave after threshold was crossed
-        const MIN_NOTES_PER_TIER: usize = 4;
-        /// Maximum number of notes to consolidate per one tx,
-        /// to limit the size of a transaction produced.
-        const MAX_NOTES_TO_CONSOLIDATE_IN_TX: usize = 20;
-        // it's fine, it's just documentation
-        #[allow(clippy::assertions_on_constants)]
-        {
-            assert!(MIN_NOTES_PER_TIER <= MAX_NOTES_PER_TIER_TRIGGER);
-        }
-
         let counts = self.get_note_counts_by_denomination(dbtx).await;
 
-        let should_consolidate = counts
-            .iter()
-            .any(|(_, count)| MAX_NOTES_PER_TIER_TRIGGER < count);
+        let excessive_counts = self
+            .note_selection_strategy(dbtx)
+            .await
+            .consolidate(&counts);
 
-        if !should_consolidate {
+        if excessive_counts.is_empty() {
             return Ok(vec![]);
         }
 
-        let mut max_count = MAX_NOTES_TO_CONSOLIDATE_IN_TX;
-
-        let excessive_counts: TieredCounts = counts
-            .iter()
-            .map(|(amount, count)| {
-                let take = (count.saturating_sub(MIN_NOTES_PER_TIER)).min(max_count);
-
-                max_count -= take;
-                (amount, take)
-            })
-            .collect();
-
         let (selected_notes, unavailable) = self
             .get_available_notes_by_tier_counts(dbtx, excessive_counts)
             .await;
 
-        debug_assert!(
-            unavailable.is_empty(),
-            "Can't have unavailable notes on a subset of all notes: {unavailable:?}"
-        );
+        // A custom strategy may consider notes excessive that we do not hold, in
+        // which case we skip the consolidation rather than failing the transaction
+        if !unavailable.is_empty() {
+            warn!(target: LOG_CLIENT_MODULE_MINT, ?unavailable, "Note selection strategy consolidates notes we don't hold");
+
+            return Ok(vec![]);
+        }
 
         if !selected_notes.is_empty() {
             debug!(target: LOG_CLIENT_MODULE_MINT, note_num=selected_notes.count_items(), denominations_msats=?selected_notes.iter_items().map(|(amount, _)| amount.msats).collect::<Vec<_>>(), "Will consolidate excessive notes");
@@ -1779,11 +1939,7 @@ pub async fn reissue_external_notes<M: Serialize + Send>(
             bail!(ReissueExternalNotesError::WrongFederationId);
         }
 
-        let operation_id = OperationId(
-            notes
-                .consensus_hash::<sha256t::Hash<OOBReissueTag>>()
-                .to_byte_array(),
-        );
+        let operation_id = reissue_operation_id(&notes);
 
         let amount = notes.total_amount();
         let mint_inputs = self.create_input_from_notes(notes)?;
@@ -1866,7 +2022,10 @@ pub async fn subscribe_reissue_external_notes(
 
                 (txid, out_points)
             }
-            MintOperationMetaVariant::SpendOOB { .. } => bail!("Operation is not a reissuance"),
+            MintOperationMetaVariant::SpendOOB { .. }
+            | MintOperationMetaVariant::ConditionalSend { .. } => {
+                bail!("Operation is not a reissuance")
+            }
         };
 
         let client_ctx = self.client_ctx.clone();
@@ -2273,6 +2432,426 @@ pub fn validate_notes(&self, oob_notes: &OOBNotes) -> anyhow::Result<Amount> {
         Ok(notes.total_amount())
     }
 
+    /// Accepts e-cash without contacting the federation. The notes are
+    /// validated locally with [`MintClientModule::validate_notes`] and kept as
+    /// unconfirmed until they can be reissued, which happens automatically in
+    /// the background once the federation is reachable. Notes that were
+    /// already received before are rejected.
+    ///
+    /// **Caution:** The payer can still double-spend the notes elsewhere
+    /// before they are reissued, see
+    /// [`MintClientModule::get_offline_receive_summary`] for the amount at
+    /// risk per payer.
+    pub async fn receive_offline(
+        &self,
+        oob_notes: OOBNotes,
+        payer: Option<String>,
+    ) -> anyhow::Result<OfflineReceipt> {
+        let amount = self.validate_notes(&oob_notes)?;
+
+        ensure!(
+            amount > Amount::ZERO,
+            "Receiving zero-amount e-cash isn't supported"
+        );
+
+        let operation_id = reissue_operation_id(oob_notes.notes());
+
+        if self.client_ctx.operation_exists(operation_id).await {
+            bail!(OfflineReceiveError::AlreadyReissued);
+        }
+
+        let mut dbtx = self.client_ctx.module_db().begin_transaction().await;
+
+        for (_, note) in oob_notes.notes().iter_items() {
+            let nonce_key = OfflineReceivedNonceKey(note.nonce());
+
+            if let Some(previous) = dbtx.get_value(&nonce_key).await {
+                bail!(OfflineReceiveError::AlreadyReceived(previous));
+            }
+
+            dbtx.insert_new_entry(&nonce_key, &operation_id).await;
+        }
+
+        dbtx.insert_new_entry(
+            &UnconfirmedReceiveKey(operation_id),
+            &UnconfirmedReceive {
+                oob_notes,
+                payer: payer.clone(),
+                received_at: fedimint_core::time::now(),
+            },
+        )
+        .await;
+
+        self.client_ctx
+            .log_event(
+                &mut dbtx,
+                OfflineNotesReceived {
+                    operation_id,
+                    payer: payer.clone(),
+                    amount,
+                },
+            )
+            .await;
+
+        dbtx.commit_tx_result().await?;
+
+        self.offline_reissue_wakeup_sender.send_replace(());
+
+        Ok(OfflineReceipt {
+            operation_id,
+            amount,
+            payer,
+        })
+    }
+
+    /// Returns the e-cash received offline that wasn't reissued yet
+    pub async fn get_unconfirmed_receives(&self) -> BTreeMap<OperationId, UnconfirmedReceive> {
+        self.client_ctx
+            .module_db()
+            .begin_transaction_nc()
+            .await
+            .find_by_prefix(&UnconfirmedReceivePrefix)
+            .await
+            .map(|(key, receive)| (key.0, receive))
+            .collect()
+            .await
+    }
+
+    /// Summarizes the value at risk from unconfirmed offline receives and the
+    /// outcome of past offline receives per payer
+    pub async fn get_offline_receive_summary(&self) -> Vec<PayerRiskSummary> {
+        let mut dbtx = self.client_ctx.module_db().begin_transaction_nc().await;
+
+        let mut summaries = dbtx
+            .find_by_prefix(&OfflineReceivePayerStatsKeyPrefix)
+            .await
+            .map(|(key, stats)| {
+                (
+                    key.0.clone(),
+                    PayerRiskSummary {
+                        payer: key.0,
+                        pending_amount: Amount::ZERO,
+                        pending_receives: 0,
+                        confirmed_amount: stats.confirmed_amount,
+                        confirmed_receives: stats.confirmed_receives,
+                        failed_amount: stats.failed_amount,
+                        failed_receives: stats.failed_receives,
+                    },
+                )
+            })
+            .collect::<BTreeMap<Option<String>, PayerRiskSummary>>()
+            .await;
+
+        for receive in self.get_unconfirmed_receives().await.into_values() {
+            let summary =
+                summaries
+                    .entry(receive.payer.clone())
+                    .or_insert_with(|| PayerRiskSummary {
+                        payer: receive.payer.clone(),
+                        ..PayerRiskSummary::default()
+                    });
+
+            summary.pending_amount += receive.amount();
+            summary.pending_receives += 1;
+        }
+
+        summaries.into_values().collect()
+    }
+
+    /// Issues e-cash of the given amount bound to a [`SpendingCondition`],
+    /// funded from our wallet. Once the federation signed the notes they can
+    /// be fetched with [`MintClientModule::await_conditional_notes`] and
+    /// transferred out-of-band to the recipient, who redeems them with
+    /// [`MintClientModule::redeem_conditional_notes`].
+    pub async fn send_conditional_notes<M: Serialize + Send>(
+        &self,
+        amount: Amount,
+        condition: SpendingCondition,
+        extra_meta: M,
+    ) -> anyhow::Result<OperationId> {
+        ensure!(
+            amount > Amount::ZERO,
+            "Sending zero-amount e-cash isn't supported"
+        );
+
+        // Don't lock funds in a multisig nobody can ever spend
+        condition.validate()?;
+
+        // Notes bound to a condition can't be redeemed before the federation
+        // activated spending conditions
+        let consensus_version = self
+            .client_ctx
+            .module_api()
+            .fetch_module_consensus_version()
+            .await
+            .map_err(|e| anyhow!("Failed to fetch the module consensus version: {e}"))?;
+
+        ensure!(
+            consensus_version >= SPENDING_CONDITIONS_MODULE_CONSENSUS_VERSION,
+            "The federation does not support spending conditions yet"
+        );
+
+        let denominations = represent_amount(
+            amount,
+            &TieredCounts::default(),
+            &self.cfg.tbs_pks,
+            0,
+            &FeeConsensus::zero(),
+        );
+
+        let mut requests = Vec::new();
+
+        for (amount, num) in denominations.iter() {
+            for _ in 0..num {
+                requests.push(ConditionalNoteRequest::new(amount, condition.clone()));
+            }
+        }
+
+        let outputs = requests
+            .iter()
+            .map(|request| ClientOutput {
+                output: MintOutput::new_v0(request.amount, request.blind_nonce()),
+                amounts: Amounts::new_bitcoin(request.amount),
+            })
+            .collect();
+
+        let tx = TransactionBuilder::new().with_outputs(self.client_ctx.make_client_outputs(
+            ClientOutputBundle::<MintOutput, MintClientStateMachines>::new_no_sm(outputs),
+        ));
+
+        let operation_id = OperationId::new_random();
+        let extra_meta = serde_json::to_value(extra_meta)
+            .expect("MintClientModule::send_conditional_notes extra_meta is serializable");
+
+        self.client_ctx
+            .module_db()
+            .autocommit(
+                |dbtx, _| {
+                    let tx = tx.clone();
+                    let requests = requests.clone();
+                    let condition = condition.clone();
+                    let extra_meta = extra_meta.clone();
+
+                    Box::pin(async move {
+                        let operation_meta_gen =
+                            move |change_range: OutPointRange| MintOperationMeta {
+                                variant: MintOperationMetaVariant::ConditionalSend {
+                                    txid: change_range.txid(),
+                                    condition: condition.clone(),
+                                },
+                                amount,
+                                extra_meta: extra_meta.clone(),
+                            };
+
+                        let change_range = self
+                            .client_ctx
+                            .finalize_and_submit_transaction_dbtx(
+                                dbtx,
+                                operation_id,
+                                MintCommonInit::KIND.as_str(),
+                                operation_meta_gen,
+                                tx,
+                            )
+                            .await?;
+
+                        // Our outputs precede the change outputs
+                        dbtx.insert_new_entry(
+                            &ConditionalIssuanceKey(operation_id),
+                            &ConditionalIssuance {
+                                txid: change_range.txid(),
+                                requests: (0..).zip(requests).collect(),
+                            },
+                        )
+                        .await;
+
+                        self.client_ctx
+                            .log_event(
+                                dbtx,
+                                ConditionalNotesSent {
+                                    operation_id,
+                                    amount,
+                                },
+                            )
+                            .await;
+
+                        Ok(())
+                    })
+                },
+                Some(100),
+            )
+            .await
+            .map_err(|e| match e {
+                AutocommitError::ClosureError { error, .. } => error,
+                AutocommitError::CommitFailed { last_error, .. } => {
+                    anyhow!("Commit to DB failed: {last_error}")
+                }
+            })?;
+
+        Ok(operation_id)
+    }
+
+    /// Waits for the federation to sign the notes issued by
+    /// [`MintClientModule::send_conditional_notes`] and returns them
+    pub async fn await_conditional_notes(
+        &self,
+        operation_id: OperationId,
+    ) -> anyhow::Result<ConditionalNotes> {
+        let issuance = self
+            .client_ctx
+            .module_db()
+            .begin_transaction_nc()
+            .await
+            .get_value(&ConditionalIssuanceKey(operation_id))
+            .await
+            .context("Operation is not a conditional send")?;
+
+        self.client_ctx
+            .transaction_updates(operation_id)
+            .await
+            .await_tx_accepted(issuance.txid)
+            .await
+            .map_err(|e| anyhow!("Transaction not accepted: {e}"))?;
+
+        let notes = fetch_conditional_notes(
+            &self.client_ctx.global_api(),
+            &self.decoder(),
+            &self.cfg.peer_tbs_pks,
+            &self.cfg.tbs_pks,
+            &issuance,
+        )
+        .await?;
+
+        Ok(ConditionalNotes {
+            federation_id_prefix: self.federation_id.to_prefix(),
+            notes,
+        })
+    }
+
+    /// Redeems notes bound to a spending condition into our wallet by
+    /// satisfying the condition with the given witness. The secret keys have
+    /// to contain the key selected by the witness. Notes bound to a multisig
+    /// are redeemed with [`MintClientModule::redeem_multisig_notes`]. The
+    /// operation can be tracked with
+    /// [`MintClientModule::subscribe_reissue_external_notes`].
+    pub async fn redeem_conditional_notes<M: Serialize + Send>(
+        &self,
+        notes: ConditionalNotes,
+        witness: SpendingWitness,
+        secret_keys: Vec<SecretKey>,
+        extra_meta: M,
+    ) -> anyhow::Result<OperationId> {
+        let witnesses = vec![witness; notes.notes.len()];
+
+        self.redeem_conditional_notes_with_witnesses(notes, witnesses, secret_keys, extra_meta)
+            .await
+    }
+
+    /// Redeems notes bound to a multisig into our wallet with the delegations
+    /// of a threshold of its signers to our key `delegate_key`, see
+    /// [`ConditionalNotes::delegate`]. The operation can be tracked with
+    /// [`MintClientModule::subscribe_reissue_external_notes`].
+    pub async fn redeem_multisig_notes<M: Serialize + Send>(
+        &self,
+        notes: ConditionalNotes,
+        delegations: Vec<MultisigDelegation>,
+        delegate_key: SecretKey,
+        extra_meta: M,
+    ) -> anyhow::Result<OperationId> {
+        let witnesses = notes.delegation_witnesses(&delegations)?;
+
+        self.redeem_conditional_notes_with_witnesses(
+            notes,
+            witnesses,
+            vec![delegate_key],
+            extra_meta,
+        )
+        .await
+    }
+
+    async fn redeem_conditional_notes_with_witnesses<M: Serialize + Send>(
+        &self,
+        notes: ConditionalNotes,
+        witnesses: Vec<SpendingWitness>,
+        secret_keys: Vec<SecretKey>,
+        extra_meta: M,
+    ) -> anyhow::Result<OperationId> {
+        if notes.federation_id_prefix != self.federation_id.to_prefix() {
+            bail!(ReissueExternalNotesError::WrongFederationId);
+        }
+
+        let amount = notes.total_amount();
+
+        ensure!(
+            amount > Amount::ZERO,
+            "Redeeming zero-amount e-cash isn't supported"
+        );
+
+        let mut inputs = Vec::new();
+
+        for (note, witness) in notes.notes.into_iter().zip(witnesses) {
+            note.verify(&self.cfg.tbs_pks)?;
+
+            let keypair = note.spend_keypair(&witness, &secret_keys)?;
+
+            inputs.push(ClientInput {
+                input: MintInput::new_v1(
+                    note.amount,
+                    note.note,
+                    note.condition,
+                    note.salt,
+                    witness,
+                ),
+                keys: vec![keypair],
+                amounts: Amounts::new_bitcoin(note.amount),
+            });
+        }
+
+        let tx = TransactionBuilder::new().with_inputs(self.client_ctx.make_client_inputs(
+            ClientInputBundle::<MintInput, MintClientStateMachines>::new_no_sm(inputs),
+        ));
+
+        let operation_id = OperationId::new_random();
+        let extra_meta = serde_json::to_value(extra_meta)
+            .expect("MintClientModule::redeem_conditional_notes extra_meta is serializable");
+        let operation_meta_gen = move |change_range: OutPointRange| MintOperationMeta {
+            variant: MintOperationMetaVariant::Reissuance {
+                legacy_out_point: None,
+                txid: Some(change_range.txid()),
+                out_point_indices: change_range
+                    .into_iter()
+                    .map(|out_point| out_point.out_idx)
+                    .collect(),
+            },
+            amount,
+            extra_meta: extra_meta.clone(),
+        };
+
+        self.client_ctx
+            .finalize_and_submit_transaction(
+                operation_id,
+                MintCommonInit::KIND.as_str(),
+                operation_meta_gen,
+                tx,
+            )
+            .await?;
+
+        let mut dbtx = self.client_ctx.module_db().begin_transaction().await;
+
+        self.client_ctx
+            .log_event(
+                &mut dbtx,
+                ReceivePaymentEvent {
+                    operation_id,
+                    amount,
+                },
+            )
+            .await;
+
+        dbtx.commit_tx().await;
+
+        Ok(operation_id)
+    }
+
     /// Contacts the mint and checks if the supplied notes were already spent.
     ///
     /// **Caution:** This reduces privacy and can lead to race conditions. **DO
@@ -2294,6 +2873,42 @@ pub async fn check_note_spent(&self, oob_notes: &OOBNotes) -> anyhow::Result<boo
         Ok(any_spent)
     }
 
+    /// Checks that the note issued for the given blind nonce is counted
+    /// towards the liabilities the federation last committed to and returns
+    /// the commitment. Notes issued since then can not be verified until the
+    /// next commitment.
+    pub async fn verify_liability_inclusion(
+        &self,
+        blind_nonce: BlindNonce,
+        amount: Amount,
+    ) -> anyhow::Result<LiabilityRoot> {
+        let (root, proof) = self
+            .client_ctx
+            .module_api()
+            .fetch_liability_proof(blind_nonce)
+            .await?
+            .context("The mint has not committed to a note for this blind nonce")?;
+
+        ensure!(
+            root.is_well_formed(),
+            "The liabilities of the mint are malformed"
+        );
+
+        ensure!(
+            proof.verify(&root, &LiabilityNode::leaf(&blind_nonce, amount)),
+            "The note is not included in the liabilities of the mint"
+        );
+
+        Ok(root)
+    }
+
+    /// Fetches the liabilities the federation last committed to, or `None` if
+    /// the mint has not committed to its liabilities yet. The caller has to
+    /// check that the root is well formed.
+    pub async fn committed_liability_root(&self) -> FederationResult<Option<LiabilityRoot>> {
+        self.client_ctx.module_api().fetch_liability_root().await
+    }
+
     /// Try to cancel a spend operation started with
     /// [`MintClientModule::spend_notes_with_selector`]. If the e-cash notes
     /// have already been spent this operation will fail which can be
@@ -2537,7 +3152,7 @@ async fn select_notes(
 // But there is a catch: we don't know if there are enough notes in the lowest
 // tiers, so we need to save a big note in case the sum of the following
 // small notes are not enough.
-async fn select_notes_from_stream<Note>(
+pub(crate) async fn select_notes_from_stream<Note>(
     stream: impl futures::Stream<Item = (Amount, Note)>,
     requested_amount: Amount,
     fee_consensus: FeeConsensus,
@@ -2896,6 +3511,15 @@ fn engine() -> sha256::HashEngine {
 
 struct OOBReissueTag;
 
+/// The operation id under which the given notes are reissued
+fn reissue_operation_id(notes: &TieredMulti<SpendableNote>) -> OperationId {
+    OperationId(
+        notes
+            .consensus_hash::<sha256t::Hash<OOBReissueTag>>()
+            .to_byte_array(),
+    )
+}
+
 impl sha256t::Tag for OOBReissueTag {
     fn engine() -> sha256::HashEngine {
         let mut engine = sha256::HashEngine::default();
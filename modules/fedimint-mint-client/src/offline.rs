@@ -0,0 +1,288 @@
+//! Receiving e-cash while the federation is unreachable
+//!
+//! Notes received offline are only checked against the mint's public keys,
+//! so the payer may have already spent them elsewhere. They are kept as
+//! unconfirmed receives and reissued in the background as soon as the
+//! federation can be reached again, each receive ending with either an
+//! [`OfflineReceiveConfirmed`] or an [`OfflineReceiveFailed`] event. The notes
+//! are only discarded once the federation rejected them, a reissue that fails
+//! for any other reason is retried later. Notes already received once are
+//! rejected locally, so the same payer can't double-spend them to us while we
+//! are offline.
+
+use std::time::{Duration, SystemTime};
+
+use fedimint_client_module::module::ClientContext;
+use fedimint_core::Amount;
+use fedimint_core::core::OperationId;
+use fedimint_core::db::IDatabaseTransactionOpsCoreTyped;
+use fedimint_core::encoding::{Decodable, Encodable};
+use fedimint_core::envs::is_running_in_test_env;
+use fedimint_core::task::sleep;
+use fedimint_logging::LOG_CLIENT_MODULE_MINT;
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use tokio::sync::watch;
+use tracing::{debug, info, warn};
+
+use crate::client_db::{
+    OfflineReceivePayerStatsKey, UnconfirmedReceiveKey, UnconfirmedReceivePrefix,
+};
+use crate::events::{OfflineReceiveConfirmed, OfflineReceiveFailed};
+use crate::{MintClientModule, OOBNotes, ReissueExternalNotesState};
+
+/// E-cash accepted while offline that wasn't reissued yet
+#[derive(Debug, Clone, Encodable, Decodable, Serialize)]
+pub struct UnconfirmedReceive {
+    pub oob_notes: OOBNotes,
+    /// Who paid us, as given by the user when receiving the notes
+    pub payer: Option<String>,
+    pub received_at: SystemTime,
+}
+
+impl UnconfirmedReceive {
+    pub fn amount(&self) -> Amount {
+        self.oob_notes.total_amount()
+    }
+}
+
+/// Outcomes of the past offline receives from a payer
+#[derive(Debug, Clone, Default, PartialEq, Eq, Encodable, Decodable, Serialize, Deserialize)]
+pub struct OfflineReceivePayerStats {
+    pub confirmed_amount: Amount,
+    pub confirmed_receives: u64,
+    pub failed_amount: Amount,
+    pub failed_receives: u64,
+}
+
+/// Proof that notes were accepted offline, their value is at risk until the
+/// receive is confirmed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OfflineReceipt {
+    /// The operation the notes will be reissued under
+    pub operation_id: OperationId,
+    pub amount: Amount,
+    pub payer: Option<String>,
+}
+
+/// The value at risk from a payer together with how their previous offline
+/// payments turned out
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PayerRiskSummary {
+    pub payer: Option<String>,
+    /// Value of the notes from this payer that weren't reissued yet
+    pub pending_amount: Amount,
+    pub pending_receives: u64,
+    pub confirmed_amount: Amount,
+    pub confirmed_receives: u64,
+    /// Value of the notes from this payer the federation refused to reissue,
+    /// usually because they were already spent
+    pub failed_amount: Amount,
+    pub failed_receives: u64,
+}
+
+#[derive(thiserror::Error, Debug, Clone)]
+pub enum OfflineReceiveError {
+    #[error("A note was already received in operation {}", .0.fmt_full())]
+    AlreadyReceived(OperationId),
+    #[error("We already reissued these notes")]
+    AlreadyReissued,
+}
+
+/// Reissues the unconfirmed receives whenever the federation is reachable,
+/// woken up early by new receives
+pub(crate) async fn run_offline_reissuer(
+    client_ctx: ClientContext<MintClientModule>,
+    mut wakeup_receiver: watch::Receiver<()>,
+) {
+    let retry_interval = if is_running_in_test_env() {
+        Duration::from_millis(100)
+    } else {
+        Duration::from_secs(30)
+    };
+
+    loop {
+        let pending = client_ctx
+            .module_db()
+            .begin_transaction_nc()
+            .await
+            .find_by_prefix(&UnconfirmedReceivePrefix)
+            .await
+            .collect::<Vec<_>>()
+            .await;
+
+        if !pending.is_empty() {
+            match client_ctx.global_api().session_count().await {
+                Ok(_) => {
+                    for (key, receive) in pending {
+                        reissue_unconfirmed_receive(&client_ctx, key.0, receive).await;
+                    }
+                }
+                Err(err) => {
+                    debug!(
+                        target: LOG_CLIENT_MODULE_MINT,
+                        %err,
+                        "Federation unreachable, postponing reissue of offline receives"
+                    );
+                }
+            }
+        }
+
+        tokio::select! {
+            () = sleep(retry_interval) => {},
+            res = wakeup_receiver.changed() => {
+                if res.is_err() {
+                    debug!(target: LOG_CLIENT_MODULE_MINT, "Terminating offline reissuer");
+                    return;
+                }
+            }
+        }
+    }
+}
+
+/// How an attempt to reissue an unconfirmed receive ended
+enum ReissueOutcome {
+    /// The federation accepted the transaction spending the notes
+    Confirmed,
+    /// The federation rejected the transaction, usually because the payer
+    /// already spent the notes. Only then are the notes discarded.
+    Rejected(String),
+    /// We couldn't learn the outcome, the receive is kept and retried
+    Interrupted(String),
+}
+
+async fn reissue_unconfirmed_receive(
+    client_ctx: &ClientContext<MintClientModule>,
+    operation_id: OperationId,
+    receive: UnconfirmedReceive,
+) {
+    let mint = client_ctx.self_ref();
+
+    // We may have submitted the reissue before being interrupted. The notes
+    // were validated when we received them, so failing to submit the
+    // transaction doesn't tell us anything about them.
+    if !client_ctx.operation_exists(operation_id).await
+        && let Err(err) = mint
+            .reissue_external_notes(receive.oob_notes.clone(), receive.payer.clone())
+            .await
+    {
+        log_interrupted_reissue(operation_id, &err.to_string());
+        return;
+    }
+
+    let outcome = match mint.subscribe_reissue_external_notes(operation_id).await {
+        Ok(updates) => {
+            let mut updates = updates.into_stream();
+            let mut outcome = ReissueOutcome::Interrupted("Reissue did not complete".to_string());
+
+            while let Some(update) = updates.next().await {
+                match update {
+                    ReissueExternalNotesState::Created => {}
+                    // Once the transaction is accepted the notes are ours, issuing
+                    // the new notes is up to the output state machines
+                    ReissueExternalNotesState::Issuing | ReissueExternalNotesState::Done => {
+                        outcome = ReissueOutcome::Confirmed;
+                    }
+                    ReissueExternalNotesState::Failed(err) => {
+                        if !matches!(outcome, ReissueOutcome::Confirmed) {
+                            outcome = ReissueOutcome::Rejected(err);
+                        }
+                    }
+                }
+            }
+
+            outcome
+        }
+        Err(err) => ReissueOutcome::Interrupted(err.to_string()),
+    };
+
+    match outcome {
+        ReissueOutcome::Confirmed => {
+            finalize_unconfirmed_receive(client_ctx, operation_id, receive, Ok(())).await;
+        }
+        ReissueOutcome::Rejected(reason) => {
+            finalize_unconfirmed_receive(client_ctx, operation_id, receive, Err(reason)).await;
+        }
+        ReissueOutcome::Interrupted(reason) => log_interrupted_reissue(operation_id, &reason),
+    }
+}
+
+fn log_interrupted_reissue(operation_id: OperationId, reason: &str) {
+    warn!(
+        target: LOG_CLIENT_MODULE_MINT,
+        operation_id = %operation_id.fmt_short(),
+        %reason,
+        "Failed to reissue offline receive, retrying later"
+    );
+}
+
+/// Removes a receive once the federation either confirmed or rejected it
+async fn finalize_unconfirmed_receive(
+    client_ctx: &ClientContext<MintClientModule>,
+    operation_id: OperationId,
+    receive: UnconfirmedReceive,
+    outcome: Result<(), String>,
+) {
+    let amount = receive.amount();
+    let stats_key = OfflineReceivePayerStatsKey(receive.payer.clone());
+
+    let mut dbtx = client_ctx.module_db().begin_transaction().await;
+
+    dbtx.remove_entry(&UnconfirmedReceiveKey(operation_id))
+        .await;
+
+    let mut stats = dbtx.get_value(&stats_key).await.unwrap_or_default();
+
+    match outcome {
+        Ok(()) => {
+            info!(
+                target: LOG_CLIENT_MODULE_MINT,
+                operation_id = %operation_id.fmt_short(),
+                %amount,
+                "Confirmed offline receive"
+            );
+
+            stats.confirmed_amount += amount;
+            stats.confirmed_receives += 1;
+
+            client_ctx
+                .log_event(
+                    &mut dbtx,
+                    OfflineReceiveConfirmed {
+                        operation_id,
+                        payer: receive.payer,
+                        amount,
+                    },
+                )
+                .await;
+        }
+        Err(reason) => {
+            warn!(
+                target: LOG_CLIENT_MODULE_MINT,
+                operation_id = %operation_id.fmt_short(),
+                %amount,
+                %reason,
+                "Federation rejected offline receive"
+            );
+
+            stats.failed_amount += amount;
+            stats.failed_receives += 1;
+
+            client_ctx
+                .log_event(
+                    &mut dbtx,
+                    OfflineReceiveFailed {
+                        operation_id,
+                        payer: receive.payer,
+                        amount,
+                        reason,
+                    },
+                )
+                .await;
+        }
+    }
+
+    dbtx.insert_entry(&stats_key, &stats).await;
+
+    dbtx.commit_tx().await;
+}
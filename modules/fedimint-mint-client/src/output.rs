@@ -627,7 +627,11 @@ async fn await_outcome_ready_batch(
                                     }
                                 }
                             } else {
-                                None
+                                // The guardian has not created its share yet, for example
+                                // since its guardian signer is unavailable
+                                return Err(ServerError::InvalidResponse(anyhow::anyhow!(
+                                    "Peer {peer} returned no outcome for output {out_idx}"
+                                )));
                             };
 
                             verified_shares.push(share);
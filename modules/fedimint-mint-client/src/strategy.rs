@@ -0,0 +1,394 @@
+//! Strategies deciding which e-cash notes the client issues and spends
+//!
+//! Every transaction funded by the mint client spends some of the notes it
+//! holds and reissues the change. Which denominations are issued, which notes
+//! are spent and whether surplus notes are consolidated along the way trades
+//! off the number of notes held, the fees paid and how well the client can
+//! spend offline. A [`NoteSelectionStrategy`] makes these decisions based on
+//! the note counts alone, the client then picks the actual notes.
+
+use std::fmt;
+use std::sync::Arc;
+
+use fedimint_core::encoding::{Decodable, Encodable};
+use fedimint_core::{Amount, Tiered, TieredCounts};
+use fedimint_mint_common::config::FeeConsensus;
+use futures::FutureExt as _;
+use serde::{Deserialize, Serialize};
+
+use crate::{InsufficientBalanceError, represent_amount, select_notes_from_stream};
+
+/// Maximum number of notes to consolidate per one tx, to limit the size of a
+/// transaction produced.
+const MAX_NOTES_TO_CONSOLIDATE_IN_TX: usize = 20;
+
+/// Decides the denominations the mint client issues and which of its notes it
+/// spends to fund transactions.
+pub trait NoteSelectionStrategy: fmt::Debug + Send + Sync {
+    /// Returns the denominations to issue for `amount`, which has to cover the
+    /// fees of the issued notes as well. The client keeps holding the `held`
+    /// notes in addition to the issued ones.
+    fn represent_amount(
+        &self,
+        amount: Amount,
+        held: &TieredCounts,
+        tiers: &[Amount],
+        fee_consensus: &FeeConsensus,
+    ) -> TieredCounts;
+
+    /// Returns the notes to spend in order to fund `amount` plus the fees of
+    /// the spent notes themselves.
+    fn select_inputs(
+        &self,
+        amount: Amount,
+        held: &TieredCounts,
+        _tiers: &[Amount],
+        fee_consensus: &FeeConsensus,
+    ) -> Result<TieredCounts, InsufficientBalanceError> {
+        select_largest_first(amount, held, fee_consensus)
+    }
+
+    /// Returns the notes to reissue in the next transaction in addition to the
+    /// ones needed to fund it, in order to keep the held notes in shape.
+    fn consolidate(&self, _held: &TieredCounts) -> TieredCounts {
+        TieredCounts::default()
+    }
+}
+
+/// The built-in strategies, which can be persisted as the client's choice
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Encodable, Decodable, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NoteSelectionStrategyConfig {
+    KeepNotesPerTier { notes_per_tier: u16 },
+    MinimalNotes,
+    StandardAmounts { max_denomination: Amount },
+    FeeMinimizing,
+}
+
+impl Default for NoteSelectionStrategyConfig {
+    fn default() -> Self {
+        Self::KeepNotesPerTier { notes_per_tier: 2 }
+    }
+}
+
+impl NoteSelectionStrategyConfig {
+    pub fn to_strategy(self) -> Arc<dyn NoteSelectionStrategy> {
+        match self {
+            Self::KeepNotesPerTier { notes_per_tier } => {
+                Arc::new(KeepNotesPerTier { notes_per_tier })
+            }
+            Self::MinimalNotes => Arc::new(MinimalNotes),
+            Self::StandardAmounts { max_denomination } => {
+                Arc::new(StandardAmounts { max_denomination })
+            }
+            Self::FeeMinimizing => Arc::new(FeeMinimizing),
+        }
+    }
+}
+
+/// Tries to hold `notes_per_tier` notes of every denomination, so the client
+/// can make exact change for most amounts without having to reissue first,
+/// which is what matters for spending offline. Once a denomination accumulates
+/// more than four times as many notes they are consolidated down to twice the
+/// target.
+///
+/// With two notes per tier this is the client's default behavior.
+#[derive(Debug, Clone)]
+pub struct KeepNotesPerTier {
+    pub notes_per_tier: u16,
+}
+
+impl NoteSelectionStrategy for KeepNotesPerTier {
+    fn represent_amount(
+        &self,
+        amount: Amount,
+        held: &TieredCounts,
+        tiers: &[Amount],
+        fee_consensus: &FeeConsensus,
+    ) -> TieredCounts {
+        represent_amount(
+            amount,
+            held,
+            &to_tiered(tiers),
+            self.notes_per_tier,
+            fee_consensus,
+        )
+    }
+
+    fn consolidate(&self, held: &TieredCounts) -> TieredCounts {
+        let notes_per_tier = usize::from(self.notes_per_tier);
+
+        consolidate_excess(held, |_| 4 * notes_per_tier, |_| 2 * notes_per_tier)
+    }
+}
+
+/// Issues as few notes as possible and consolidates duplicate denominations,
+/// minimizing the number of notes held at the cost of reissuing them often.
+#[derive(Debug, Clone)]
+pub struct MinimalNotes;
+
+impl NoteSelectionStrategy for MinimalNotes {
+    fn represent_amount(
+        &self,
+        amount: Amount,
+        held: &TieredCounts,
+        tiers: &[Amount],
+        fee_consensus: &FeeConsensus,
+    ) -> TieredCounts {
+        represent_amount(amount, held, &to_tiered(tiers), 0, fee_consensus)
+    }
+
+    fn consolidate(&self, held: &TieredCounts) -> TieredCounts {
+        consolidate_excess(held, |_| 1, |_| 1)
+    }
+}
+
+/// Never issues notes above `max_denomination` and reissues any such notes it
+/// receives. Large denominations are rarely used, so the few guardians
+/// observing them can link their issuance and redemption more easily than for
+/// the common small denominations. Larger amounts are held as multiple notes
+/// of the standard denominations instead.
+#[derive(Debug, Clone)]
+pub struct StandardAmounts {
+    pub max_denomination: Amount,
+}
+
+impl NoteSelectionStrategy for StandardAmounts {
+    fn represent_amount(
+        &self,
+        amount: Amount,
+        held: &TieredCounts,
+        tiers: &[Amount],
+        fee_consensus: &FeeConsensus,
+    ) -> TieredCounts {
+        let mut standard = tiers
+            .iter()
+            .copied()
+            .filter(|tier| *tier <= self.max_denomination)
+            .collect::<Vec<Amount>>();
+
+        // We always need the smallest denomination to represent any amount
+        if standard.is_empty() {
+            standard.extend(tiers.iter().min().copied());
+        }
+
+        represent_amount(amount, held, &to_tiered(&standard), 2, fee_consensus)
+    }
+
+    fn consolidate(&self, held: &TieredCounts) -> TieredCounts {
+        let max_denomination = self.max_denomination;
+
+        consolidate_excess(
+            held,
+            |tier| if tier <= max_denomination { 8 } else { 0 },
+            |tier| if tier <= max_denomination { 4 } else { 0 },
+        )
+    }
+}
+
+/// Minimizes the fees paid per transaction: issues as few notes as possible,
+/// never consolidates and spends whichever notes lead to the lowest combined
+/// input and change fees.
+#[derive(Debug, Clone)]
+pub struct FeeMinimizing;
+
+impl FeeMinimizing {
+    fn estimate_fees(
+        &self,
+        amount: Amount,
+        inputs: &TieredCounts,
+        held: &TieredCounts,
+        tiers: &[Amount],
+        fee_consensus: &FeeConsensus,
+    ) -> Amount {
+        let input_fees = inputs
+            .iter()
+            .map(|(tier, count)| fee_consensus.fee(tier) * count as u64)
+            .sum::<Amount>();
+
+        let change = inputs
+            .total_amount()
+            .saturating_sub(amount)
+            .saturating_sub(input_fees);
+
+        let output_fees = self
+            .represent_amount(change, held, tiers, fee_consensus)
+            .iter()
+            .map(|(tier, count)| fee_consensus.fee(tier) * count as u64)
+            .sum::<Amount>();
+
+        input_fees + output_fees
+    }
+}
+
+impl NoteSelectionStrategy for FeeMinimizing {
+    fn represent_amount(
+        &self,
+        amount: Amount,
+        held: &TieredCounts,
+        tiers: &[Amount],
+        fee_consensus: &FeeConsensus,
+    ) -> TieredCounts {
+        represent_amount(amount, held, &to_tiered(tiers), 0, fee_consensus)
+    }
+
+    fn select_inputs(
+        &self,
+        amount: Amount,
+        held: &TieredCounts,
+        tiers: &[Amount],
+        fee_consensus: &FeeConsensus,
+    ) -> Result<TieredCounts, InsufficientBalanceError> {
+        let largest_first = select_largest_first(amount, held, fee_consensus)?;
+
+        // A single note only pays one input fee but might need more change
+        let single_note = held
+            .iter()
+            .map(|(tier, _)| tier)
+            .find(|tier| amount + fee_consensus.fee(*tier) <= *tier)
+            .map(|tier| std::iter::once((tier, 1)).collect::<TieredCounts>());
+
+        Ok(std::iter::once(largest_first)
+            .chain(single_note)
+            .min_by_key(|inputs| self.estimate_fees(amount, inputs, held, tiers, fee_consensus))
+            .expect("We have at least one candidate"))
+    }
+}
+
+/// Selects notes greedily starting with the largest denomination, which is how
+/// the client funds transactions unless a strategy decides otherwise.
+pub fn select_largest_first(
+    amount: Amount,
+    held: &TieredCounts,
+    fee_consensus: &FeeConsensus,
+) -> Result<TieredCounts, InsufficientBalanceError> {
+    let notes = held
+        .iter()
+        .collect::<Vec<(Amount, usize)>>()
+        .into_iter()
+        .rev()
+        .flat_map(|(tier, count)| std::iter::repeat_n((tier, ()), count));
+
+    let selected =
+        select_notes_from_stream(futures::stream::iter(notes), amount, fee_consensus.clone())
+            .now_or_never()
+            .expect("The stream of notes is always ready")?;
+
+    Ok(selected.summary())
+}
+
+/// Once any denomination holds more notes than `max_per_tier` allows, spends
+/// the notes of every denomination in excess of `keep_per_tier`.
+fn consolidate_excess(
+    held: &TieredCounts,
+    max_per_tier: impl Fn(Amount) -> usize,
+    keep_per_tier: impl Fn(Amount) -> usize,
+) -> TieredCounts {
+    let should_consolidate = held.iter().any(|(tier, count)| max_per_tier(tier) < count);
+
+    if !should_consolidate {
+        return TieredCounts::default();
+    }
+
+    let mut max_count = MAX_NOTES_TO_CONSOLIDATE_IN_TX;
+
+    held.iter()
+        .map(|(tier, count)| {
+            let take = count.saturating_sub(keep_per_tier(tier)).min(max_count);
+
+            max_count -= take;
+            (tier, take)
+        })
+        .collect()
+}
+
+fn to_tiered(tiers: &[Amount]) -> Tiered<()> {
+    tiers.iter().map(|tier| (*tier, ())).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn counts(counts: &[(u64, usize)]) -> TieredCounts {
+        counts
+            .iter()
+            .map(|(msats, count)| (Amount::from_msats(*msats), *count))
+            .collect()
+    }
+
+    fn tiers() -> Vec<Amount> {
+        (0..10).map(|exp| Amount::from_msats(1 << exp)).collect()
+    }
+
+    #[test]
+    fn default_strategy_consolidates_like_before() {
+        let strategy = NoteSelectionStrategyConfig::default().to_strategy();
+
+        assert!(strategy.consolidate(&counts(&[(1, 8), (2, 3)])).is_empty());
+
+        assert_eq!(
+            strategy.consolidate(&counts(&[(1, 9), (2, 5), (4, 2)])),
+            counts(&[(1, 5), (2, 1)])
+        );
+    }
+
+    #[test]
+    fn minimal_notes_issues_one_note_per_tier() {
+        let strategy = MinimalNotes;
+        let issued = strategy.represent_amount(
+            Amount::from_msats(1023),
+            &TieredCounts::default(),
+            &tiers(),
+            &FeeConsensus::zero(),
+        );
+
+        assert_eq!(issued.count_items(), 10);
+        assert_eq!(
+            strategy.consolidate(&counts(&[(1, 3), (2, 1)])),
+            counts(&[(1, 2)])
+        );
+    }
+
+    #[test]
+    fn standard_amounts_caps_denominations() {
+        let strategy = StandardAmounts {
+            max_denomination: Amount::from_msats(64),
+        };
+        let issued = strategy.represent_amount(
+            Amount::from_msats(1000),
+            &TieredCounts::default(),
+            &tiers(),
+            &FeeConsensus::zero(),
+        );
+
+        assert_eq!(issued.total_amount(), Amount::from_msats(1000));
+        assert!(
+            issued
+                .iter()
+                .all(|(tier, _)| tier <= Amount::from_msats(64))
+        );
+        assert_eq!(
+            strategy.consolidate(&counts(&[(64, 1), (512, 1)])),
+            counts(&[(512, 1)])
+        );
+    }
+
+    #[test]
+    fn fee_minimizing_prefers_fewer_inputs() {
+        let fee_consensus = FeeConsensus::new(0).unwrap();
+        let held = counts(&[(256, 4), (1024, 1)]);
+
+        assert_eq!(
+            select_largest_first(Amount::from_msats(600), &held, &fee_consensus).unwrap(),
+            counts(&[(256, 4)])
+        );
+
+        assert_eq!(
+            FeeMinimizing
+                .select_inputs(Amount::from_msats(600), &held, &tiers(), &fee_consensus)
+                .unwrap(),
+            counts(&[(1024, 1)])
+        );
+    }
+}
@@ -0,0 +1,372 @@
+//! Spending conditions binding e-cash notes to keys, hash locks and time locks
+//!
+//! A note bound to a [`SpendingCondition`] is issued for a nonce committing to
+//! the condition, see [`SpendingCondition::nonce`]. Since the nonce is blinded
+//! during issuance the federation only learns about the condition once the
+//! note is spent by a [`MintInputV1`](crate::MintInputV1) revealing it. The
+//! nonce is a point without a known discrete logarithm, so a bound note can't
+//! be spent as a bearer note by a [`MintInputV0`](crate::MintInputV0).
+
+use fedimint_core::bitcoin::hashes::{Hash as _, HashEngine as _, sha256};
+use fedimint_core::encoding::{Decodable, Encodable};
+use fedimint_core::secp256k1::{self, Keypair, Message, PublicKey, schnorr};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::Nonce;
+
+/// Maximum number of keys of a [`SpendingCondition::Multisig`], bounding the
+/// cost of verifying a spend
+pub const MAX_MULTISIG_KEYS: usize = 16;
+
+const NONCE_TAG: &[u8] = b"fedimint-mint-spending-condition-nonce";
+
+const MULTISIG_TAG: &[u8] = b"fedimint-mint-multisig-delegation";
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Deserialize, Serialize, Encodable, Decodable)]
+pub enum SpendingCondition {
+    /// Spendable by the owner of the key
+    PublicKey(PublicKey),
+    /// Spendable by the owner of the key revealing the preimage of the hash
+    HashLock {
+        hash: sha256::Hash,
+        pubkey: PublicKey,
+    },
+    /// Spendable by the owner of `pubkey` and, once the consensus unix time of
+    /// the federation reached `locktime`, by the owner of `refund_pubkey`
+    TimeLock {
+        pubkey: PublicKey,
+        locktime: u64,
+        refund_pubkey: PublicKey,
+    },
+    /// Spendable by any key that `threshold` of the keys delegated the note
+    /// to, see [`SpendingWitness::Delegation`]
+    Multisig {
+        threshold: u16,
+        pubkeys: Vec<PublicKey>,
+    },
+}
+
+/// Reveals which branch of a [`SpendingCondition`] is used to spend a note
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Deserialize, Serialize, Encodable, Decodable)]
+pub enum SpendingWitness {
+    /// Spends with the key of a public key condition or with the receiver key
+    /// of a time lock
+    Key,
+    /// Spends a hash lock by revealing the preimage
+    Preimage([u8; 32]),
+    /// Spends an expired time lock with the refund key
+    Refund,
+    /// Spends a multisig with the key `delegate`, to which the signers at the
+    /// given indices delegated the note. The signers sign independently of
+    /// each other with [`sign_multisig_delegation`] and have to be given in
+    /// ascending order.
+    Delegation {
+        delegate: PublicKey,
+        signatures: Vec<(u16, schnorr::Signature)>,
+    },
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Error, Encodable, Decodable)]
+pub enum SpendingConditionError {
+    #[error("The note's nonce does not commit to the spending condition")]
+    NonceMismatch,
+    #[error("The witness does not match the spending condition")]
+    WitnessMismatch,
+    #[error("The preimage does not match the hash lock")]
+    InvalidPreimage,
+    #[error("The time lock only expires at unix time {0}")]
+    TimeLocked(u64),
+    #[error("The multisig condition is malformed")]
+    InvalidMultisig,
+    #[error("The signers are invalid or do not reach the multisig threshold")]
+    InvalidSigners,
+    #[error("The delegation signature of multisig signer {0} is invalid")]
+    InvalidDelegationSignature(u16),
+}
+
+impl SpendingCondition {
+    /// The nonce of a note bound to this condition. A random `salt` keeps
+    /// notes bound to the same condition unlinkable until they are spent.
+    pub fn nonce(&self, salt: &[u8; 32]) -> Nonce {
+        let condition = self.consensus_encode_to_vec();
+
+        // About every second x coordinate is on the curve
+        for counter in 0u32.. {
+            let mut engine = sha256::Hash::engine();
+            engine.input(NONCE_TAG);
+            engine.input(&condition);
+            engine.input(salt);
+            engine.input(&counter.to_be_bytes());
+
+            let mut key = [0x02; 33];
+            key[1..].copy_from_slice(&sha256::Hash::from_engine(engine).to_byte_array());
+
+            if let Ok(pubkey) = PublicKey::from_slice(&key) {
+                return Nonce(pubkey);
+            }
+        }
+
+        unreachable!("We find a point on the curve long before the counter overflows")
+    }
+
+    /// Checks that the condition can be satisfied at all, so no funds are
+    /// locked in a multisig nobody can ever spend
+    pub fn validate(&self) -> Result<(), SpendingConditionError> {
+        match self {
+            Self::Multisig { threshold, pubkeys } => validate_multisig(*threshold, pubkeys),
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns the key that has to sign the transaction spending the note with
+    /// the given nonce, which is bound to this condition, if the witness
+    /// satisfies the condition at the given consensus unix time.
+    pub fn spend_key(
+        &self,
+        nonce: &Nonce,
+        witness: &SpendingWitness,
+        unix_time: u64,
+    ) -> Result<PublicKey, SpendingConditionError> {
+        match (self, witness) {
+            (Self::PublicKey(pubkey), SpendingWitness::Key)
+            | (Self::TimeLock { pubkey, .. }, SpendingWitness::Key) => Ok(*pubkey),
+            (Self::HashLock { hash, pubkey }, SpendingWitness::Preimage(preimage)) => {
+                if sha256::Hash::hash(preimage) != *hash {
+                    return Err(SpendingConditionError::InvalidPreimage);
+                }
+
+                Ok(*pubkey)
+            }
+            (
+                Self::TimeLock {
+                    locktime,
+                    refund_pubkey,
+                    ..
+                },
+                SpendingWitness::Refund,
+            ) => {
+                if unix_time < *locktime {
+                    return Err(SpendingConditionError::TimeLocked(*locktime));
+                }
+
+                Ok(*refund_pubkey)
+            }
+            (
+                Self::Multisig { threshold, pubkeys },
+                SpendingWitness::Delegation {
+                    delegate,
+                    signatures,
+                },
+            ) => {
+                validate_multisig(*threshold, pubkeys)?;
+
+                if signatures.len() < usize::from(*threshold)
+                    || !signatures.windows(2).all(|pair| pair[0].0 < pair[1].0)
+                    || signatures
+                        .iter()
+                        .any(|(signer, _)| usize::from(*signer) >= pubkeys.len())
+                {
+                    return Err(SpendingConditionError::InvalidSigners);
+                }
+
+                let message = multisig_delegation_message(nonce, delegate);
+
+                for (signer, signature) in signatures {
+                    secp256k1::SECP256K1
+                        .verify_schnorr(
+                            signature,
+                            &message,
+                            &pubkeys[usize::from(*signer)].x_only_public_key().0,
+                        )
+                        .map_err(|_| SpendingConditionError::InvalidDelegationSignature(*signer))?;
+                }
+
+                Ok(*delegate)
+            }
+            _ => Err(SpendingConditionError::WitnessMismatch),
+        }
+    }
+}
+
+fn validate_multisig(threshold: u16, pubkeys: &[PublicKey]) -> Result<(), SpendingConditionError> {
+    if threshold == 0 || usize::from(threshold) > pubkeys.len() || MAX_MULTISIG_KEYS < pubkeys.len()
+    {
+        return Err(SpendingConditionError::InvalidMultisig);
+    }
+
+    if pubkeys
+        .iter()
+        .enumerate()
+        .any(|(i, pubkey)| pubkeys[..i].contains(pubkey))
+    {
+        return Err(SpendingConditionError::InvalidMultisig);
+    }
+
+    Ok(())
+}
+
+/// The message a multisig signer signs to delegate the note with the given
+/// nonce to the key `delegate`. Committing to the nonce prevents the
+/// delegation from being replayed for other notes bound to the same
+/// condition.
+pub fn multisig_delegation_message(nonce: &Nonce, delegate: &PublicKey) -> Message {
+    let mut engine = sha256::Hash::engine();
+    engine.input(MULTISIG_TAG);
+    engine.input(&nonce.0.serialize());
+    engine.input(&delegate.serialize());
+
+    Message::from_digest(sha256::Hash::from_engine(engine).to_byte_array())
+}
+
+/// Delegates the note with the given nonce to the key `delegate` as one of the
+/// signers of its multisig condition
+pub fn sign_multisig_delegation(
+    nonce: &Nonce,
+    delegate: &PublicKey,
+    keypair: &Keypair,
+) -> schnorr::Signature {
+    secp256k1::SECP256K1.sign_schnorr(&multisig_delegation_message(nonce, delegate), keypair)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keypair(byte: u8) -> Keypair {
+        Keypair::from_seckey_slice(secp256k1::SECP256K1, &[byte; 32]).unwrap()
+    }
+
+    #[test]
+    fn nonce_commits_to_condition_and_salt() {
+        let condition = SpendingCondition::PublicKey(keypair(1).public_key());
+        let other = SpendingCondition::PublicKey(keypair(2).public_key());
+
+        assert_eq!(condition.nonce(&[0; 32]), condition.nonce(&[0; 32]));
+        assert_ne!(condition.nonce(&[0; 32]), condition.nonce(&[1; 32]));
+        assert_ne!(condition.nonce(&[0; 32]), other.nonce(&[0; 32]));
+    }
+
+    #[test]
+    fn hash_lock_requires_preimage() {
+        let pubkey = keypair(1).public_key();
+        let condition = SpendingCondition::HashLock {
+            hash: sha256::Hash::hash(&[42; 32]),
+            pubkey,
+        };
+        let nonce = condition.nonce(&[0; 32]);
+
+        assert_eq!(
+            condition.spend_key(&nonce, &SpendingWitness::Preimage([42; 32]), 0),
+            Ok(pubkey)
+        );
+        assert_eq!(
+            condition.spend_key(&nonce, &SpendingWitness::Preimage([0; 32]), 0),
+            Err(SpendingConditionError::InvalidPreimage)
+        );
+        assert_eq!(
+            condition.spend_key(&nonce, &SpendingWitness::Key, 0),
+            Err(SpendingConditionError::WitnessMismatch)
+        );
+    }
+
+    #[test]
+    fn time_lock_refunds_after_locktime() {
+        let pubkey = keypair(1).public_key();
+        let refund_pubkey = keypair(2).public_key();
+        let condition = SpendingCondition::TimeLock {
+            pubkey,
+            locktime: 1000,
+            refund_pubkey,
+        };
+        let nonce = condition.nonce(&[0; 32]);
+
+        assert_eq!(
+            condition.spend_key(&nonce, &SpendingWitness::Key, 0),
+            Ok(pubkey)
+        );
+        assert_eq!(
+            condition.spend_key(&nonce, &SpendingWitness::Refund, 999),
+            Err(SpendingConditionError::TimeLocked(1000))
+        );
+        assert_eq!(
+            condition.spend_key(&nonce, &SpendingWitness::Refund, 1000),
+            Ok(refund_pubkey)
+        );
+    }
+
+    #[test]
+    fn multisig_requires_threshold_of_delegations() {
+        let keypairs = (1..=3).map(keypair).collect::<Vec<Keypair>>();
+        let condition = SpendingCondition::Multisig {
+            threshold: 2,
+            pubkeys: keypairs.iter().map(Keypair::public_key).collect(),
+        };
+        let nonce = condition.nonce(&[0; 32]);
+        let delegate = keypair(4).public_key();
+
+        let delegation = |signers: &[u16]| SpendingWitness::Delegation {
+            delegate,
+            signatures: signers
+                .iter()
+                .map(|signer| {
+                    let keypair = &keypairs[usize::from(*signer)];
+
+                    (
+                        *signer,
+                        sign_multisig_delegation(&nonce, &delegate, keypair),
+                    )
+                })
+                .collect(),
+        };
+
+        // Any subset reaching the threshold can delegate the note
+        for signers in [vec![0, 1], vec![0, 2], vec![1, 2], vec![0, 1, 2]] {
+            assert_eq!(
+                condition.spend_key(&nonce, &delegation(&signers), 0),
+                Ok(delegate)
+            );
+        }
+
+        for signers in [vec![0], vec![2, 0], vec![0, 0]] {
+            assert_eq!(
+                condition.spend_key(&nonce, &delegation(&signers), 0),
+                Err(SpendingConditionError::InvalidSigners)
+            );
+        }
+
+        // A delegation can neither be redirected to another key nor replayed
+        // for another note bound to the same condition
+        let witness = delegation(&[0, 2]);
+
+        let SpendingWitness::Delegation { signatures, .. } = witness.clone() else {
+            unreachable!()
+        };
+
+        assert_eq!(
+            condition.spend_key(
+                &nonce,
+                &SpendingWitness::Delegation {
+                    delegate: keypair(5).public_key(),
+                    signatures,
+                },
+                0
+            ),
+            Err(SpendingConditionError::InvalidDelegationSignature(0))
+        );
+        assert_eq!(
+            condition.spend_key(&condition.nonce(&[1; 32]), &witness, 0),
+            Err(SpendingConditionError::InvalidDelegationSignature(0))
+        );
+
+        let duplicate = SpendingCondition::Multisig {
+            threshold: 1,
+            pubkeys: vec![keypairs[0].public_key(), keypairs[0].public_key()],
+        };
+
+        assert_eq!(
+            duplicate.validate(),
+            Err(SpendingConditionError::InvalidMultisig)
+        );
+    }
+}
@@ -31,7 +31,9 @@ pub struct MintConfigConsensus {
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct MintConfigPrivate {
-    /// Secret keys for blind-signing ecash of varying note denominations
+    /// Secret keys for blind-signing ecash of varying note denominations,
+    /// empty once they have been exported to a remote guardian signer
+    #[serde(default)]
     pub tbs_sks: Tiered<tbs::SecretKeyShare>,
 }
 
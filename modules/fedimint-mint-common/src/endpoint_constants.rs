@@ -5,3 +5,7 @@
 pub const RECOVERY_SLICE_ENDPOINT: &str = "recovery_slice";
 pub const RECOVERY_SLICE_HASH_ENDPOINT: &str = "recovery_slice_hash";
 pub const RECOVERY_BLIND_NONCE_OUTPOINTS_ENDPOINT: &str = "recovery_blind_nonce_outpoints";
+pub const LIABILITY_ROOT_ENDPOINT: &str = "liability_root";
+pub const LIABILITY_PROOF_ENDPOINT: &str = "liability_proof";
+pub const MODULE_CONSENSUS_VERSION_ENDPOINT: &str = "module_consensus_version";
+pub const SUPPORTED_MODULE_CONSENSUS_VERSION_ENDPOINT: &str = "supported_module_consensus_version";
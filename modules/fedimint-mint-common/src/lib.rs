@@ -11,9 +11,11 @@
 use bitcoin_hashes::Hash as _;
 use bitcoin_hashes::hex::DisplayHex;
 pub use common::{BackupRequest, SignedBackupRequest};
+use condition::{SpendingCondition, SpendingConditionError, SpendingWitness};
 use config::MintClientConfig;
 use fedimint_core::core::{Decoder, ModuleInstanceId, ModuleKind};
 use fedimint_core::encoding::{Decodable, Encodable};
+use fedimint_core::module::liabilities::LiabilityRoot;
 use fedimint_core::module::{CommonModuleInit, ModuleCommon, ModuleConsensusVersion};
 use fedimint_core::{
     Amount, extensible_associated_module_type, plugin_types_trait_impl_common, secp256k1,
@@ -23,23 +25,43 @@
 use thiserror::Error;
 
 pub mod common;
+pub mod condition;
 pub mod config;
 pub mod endpoint_constants;
 
 pub const KIND: ModuleKind = ModuleKind::from_static_str("mint");
-pub const MODULE_CONSENSUS_VERSION: ModuleConsensusVersion = ModuleConsensusVersion::new(2, 0);
+pub const MODULE_CONSENSUS_VERSION: ModuleConsensusVersion = ModuleConsensusVersion::new(2, 2);
+
+/// The module consensus version that introduced [`MintInputV1`] and the unix
+/// time votes. Before it was activated for a federation neither is accepted,
+/// so not yet upgraded guardians stay in consensus.
+pub const SPENDING_CONDITIONS_MODULE_CONSENSUS_VERSION: ModuleConsensusVersion =
+    ModuleConsensusVersion::new(2, 1);
+
+/// The module consensus version that introduced the commitment to the
+/// liabilities of the mint via [`MintConsensusItem::LiabilityRoot`]
+pub const LIABILITY_COMMITMENT_MODULE_CONSENSUS_VERSION: ModuleConsensusVersion =
+    ModuleConsensusVersion::new(2, 2);
 
 /// By default, the maximum notes per denomination when change-making for users
 pub const DEFAULT_MAX_NOTES_PER_DENOMINATION: u16 = 3;
 
-/// The mint module currently doesn't define any consensus items and generally
-/// throws an error on encountering one. To allow old clients to still decode
-/// blocks in the future, should we decide to add consensus items, this has to
-/// be an enum with only a default variant.
+/// The guardians vote on the current unix time, which decides when the time
+/// locks of [`SpendingCondition`]s expire, and on upgrading the module
+/// consensus version. Once the liability commitment is active the guardians
+/// also agree on the [`LiabilityRoot`] of the mint, which ends up in the signed
+/// session outcome. Unknown variants are decoded as the default variant to
+/// allow old clients to decode future consensus items.
 #[derive(Debug, Clone, Eq, PartialEq, Hash, Serialize, Deserialize, Encodable, Decodable)]
 pub enum MintConsensusItem {
+    UnixTimeVote(u64),
+    ModuleConsensusVersion(ModuleConsensusVersion),
+    LiabilityRoot(LiabilityRoot),
     #[encodable_default]
-    Default { variant: u64, bytes: Vec<u8> },
+    Default {
+        variant: u64,
+        bytes: Vec<u8>,
+    },
 }
 
 impl std::fmt::Display for MintConsensusItem {
@@ -198,12 +220,100 @@ fn decoder() -> Decoder {
     }
 }
 
-extensible_associated_module_type!(MintInput, MintInputV0, UnknownMintInputVariantError);
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Deserialize, Serialize, Encodable, Decodable)]
+pub enum MintInput {
+    V0(MintInputV0),
+    V1(MintInputV1),
+    #[encodable_default]
+    Default {
+        variant: u64,
+        bytes: Vec<u8>,
+    },
+}
 
 impl MintInput {
     pub fn new_v0(amount: Amount, note: Note) -> MintInput {
         MintInput::V0(MintInputV0 { amount, note })
     }
+
+    pub fn new_v1(
+        amount: Amount,
+        note: Note,
+        condition: SpendingCondition,
+        salt: [u8; 32],
+        witness: SpendingWitness,
+    ) -> MintInput {
+        MintInput::V1(MintInputV1 {
+            amount,
+            note,
+            condition,
+            salt,
+            witness,
+        })
+    }
+
+    pub fn maybe_v0_ref(&self) -> Option<&MintInputV0> {
+        match self {
+            MintInput::V0(v0) => Some(v0),
+            _ => None,
+        }
+    }
+
+    pub fn ensure_v0_ref(&self) -> Result<&MintInputV0, UnknownMintInputVariantError> {
+        match self {
+            MintInput::V0(v0) => Ok(v0),
+            MintInput::V1(_) => Err(UnknownMintInputVariantError { variant: 1 }),
+            MintInput::Default { variant, .. } => {
+                Err(UnknownMintInputVariantError { variant: *variant })
+            }
+        }
+    }
+
+    /// The amount and the note spent by any known input variant
+    pub fn amount_and_note(&self) -> Result<(Amount, Note), UnknownMintInputVariantError> {
+        match self {
+            MintInput::V0(v0) => Ok((v0.amount, v0.note)),
+            MintInput::V1(v1) => Ok((v1.amount, v1.note)),
+            MintInput::Default { variant, .. } => {
+                Err(UnknownMintInputVariantError { variant: *variant })
+            }
+        }
+    }
+}
+
+impl From<MintInputV0> for MintInput {
+    fn from(v0: MintInputV0) -> Self {
+        Self::V0(v0)
+    }
+}
+
+#[derive(
+    Debug,
+    thiserror::Error,
+    Clone,
+    Eq,
+    PartialEq,
+    Hash,
+    serde::Deserialize,
+    serde::Serialize,
+    fedimint_core::encoding::Encodable,
+    fedimint_core::encoding::Decodable,
+)]
+#[error("Unknown MintInput variant {variant}")]
+pub struct UnknownMintInputVariantError {
+    pub variant: u64,
+}
+
+impl std::fmt::Display for MintInput {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MintInput::V0(inner) => std::fmt::Display::fmt(inner, f),
+            MintInput::V1(inner) => std::fmt::Display::fmt(inner, f),
+            MintInput::Default { variant, .. } => {
+                write!(f, "Unknown MintInput (variant={variant})")
+            }
+        }
+    }
 }
 
 #[derive(Debug, Clone, Eq, PartialEq, Hash, Deserialize, Serialize, Encodable, Decodable)]
@@ -223,6 +333,30 @@ fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
     }
 }
 
+/// Spends a note bound to a [`SpendingCondition`]. The transaction has to be
+/// signed by the key the witness selects from the condition instead of the
+/// note's nonce.
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Deserialize, Serialize, Encodable, Decodable)]
+pub struct MintInputV1 {
+    pub amount: Amount,
+    pub note: Note,
+    pub condition: SpendingCondition,
+    /// Salt of the note's nonce, see [`SpendingCondition::nonce`]
+    pub salt: [u8; 32],
+    pub witness: SpendingWitness,
+}
+
+impl std::fmt::Display for MintInputV1 {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Conditional Mint Note {} nonce={}",
+            self.amount,
+            self.note.nonce.fmt_short()
+        )
+    }
+}
+
 extensible_associated_module_type!(MintOutput, MintOutputV0, UnknownMintOutputVariantError);
 
 impl MintOutput {
@@ -327,6 +461,8 @@ pub enum MintInputError {
     InvalidSignature,
     #[error("The mint input version is not supported by this federation")]
     UnknownInputVariant(#[from] UnknownMintInputVariantError),
+    #[error("The spending condition of the note is not satisfied: {0}")]
+    SpendingCondition(#[from] SpendingConditionError),
 }
 
 #[derive(Debug, Clone, Eq, PartialEq, Hash, Error, Encodable, Decodable)]
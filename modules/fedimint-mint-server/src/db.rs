@@ -1,6 +1,8 @@
 use fedimint_core::encoding::{Decodable, Encodable};
-use fedimint_core::{Amount, OutPoint, impl_db_lookup, impl_db_record};
-use fedimint_mint_common::{BlindNonce, MintOutputOutcome, Nonce, RecoveryItem};
+use fedimint_core::module::ModuleConsensusVersion;
+use fedimint_core::module::liabilities::{LiabilityNode, LiabilityRoot, NodePosition};
+use fedimint_core::{Amount, OutPoint, PeerId, impl_db_lookup, impl_db_record};
+use fedimint_mint_common::{BlindNonce, MintOutputOutcome, MintOutputV0, Nonce, RecoveryItem};
 use serde::Serialize;
 use strum_macros::EnumIter;
 
@@ -14,6 +16,14 @@ pub enum DbKeyPrefix {
     BlindNonce = 0x16,
     RecoveryItem = 0x17,
     RecoveryBlindNonceOutpoint = 0x18,
+    LiabilityNode = 0x19,
+    LiabilityLeaf = 0x1a,
+    LiabilityTier = 0x1b,
+    UnixTimeVote = 0x1c,
+    ConsensusVersionVote = 0x1d,
+    LiabilityEvent = 0x1e,
+    CommittedLiabilityRoot = 0x1f,
+    PendingBlindSignature = 0x20,
 }
 
 impl std::fmt::Display for DbKeyPrefix {
@@ -69,6 +79,24 @@ fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
     query_prefix = MintOutputOutcomePrefix
 );
 
+/// An output that has been accepted by consensus but whose blind signature
+/// share has not been created by the guardian signer yet
+#[derive(Debug, Clone, Copy, Encodable, Decodable, Serialize)]
+pub struct PendingBlindSignatureKey(pub OutPoint);
+
+#[derive(Debug, Encodable, Decodable)]
+pub struct PendingBlindSignaturePrefix;
+
+impl_db_record!(
+    key = PendingBlindSignatureKey,
+    value = MintOutputV0,
+    db_prefix = DbKeyPrefix::PendingBlindSignature,
+);
+impl_db_lookup!(
+    key = PendingBlindSignatureKey,
+    query_prefix = PendingBlindSignaturePrefix
+);
+
 /// Represents the amounts of issued (signed) and redeemed (verified) notes for
 /// auditing
 #[derive(Debug, Clone, Encodable, Decodable, Serialize)]
@@ -121,3 +149,136 @@ pub enum MintAuditItemKey {
     key = RecoveryBlindNonceOutpointKey,
     query_prefix = RecoveryBlindNonceOutpointKeyPrefix
 );
+
+/// The nodes of the Merkle sum forests over the issued notes of every
+/// denomination, see [`fedimint_core::module::liabilities`]
+#[derive(Debug, Clone, Copy, Encodable, Decodable, Serialize)]
+pub struct LiabilityNodeKey(pub Amount, pub NodePosition);
+
+#[derive(Debug, Encodable, Decodable)]
+pub struct LiabilityNodeKeyPrefix;
+
+impl_db_record!(
+    key = LiabilityNodeKey,
+    value = LiabilityNode,
+    db_prefix = DbKeyPrefix::LiabilityNode,
+);
+impl_db_lookup!(
+    key = LiabilityNodeKey,
+    query_prefix = LiabilityNodeKeyPrefix
+);
+
+/// Maps a blind nonce to the denomination and index of its leaf in the
+/// liability forests
+#[derive(Debug, Encodable, Decodable, Serialize)]
+pub struct LiabilityLeafKey(pub BlindNonce);
+
+#[derive(Debug, Encodable, Decodable)]
+pub struct LiabilityLeafKeyPrefix;
+
+impl_db_record!(
+    key = LiabilityLeafKey,
+    value = (Amount, u64),
+    db_prefix = DbKeyPrefix::LiabilityLeaf,
+);
+impl_db_lookup!(
+    key = LiabilityLeafKey,
+    query_prefix = LiabilityLeafKeyPrefix
+);
+
+/// The number of notes issued and redeemed in a denomination
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Encodable, Decodable, Serialize)]
+pub struct LiabilityTier {
+    pub issued: u64,
+    pub redeemed: u64,
+}
+
+#[derive(Debug, Encodable, Decodable, Serialize)]
+pub struct LiabilityTierKey(pub Amount);
+
+#[derive(Debug, Encodable, Decodable)]
+pub struct LiabilityTierKeyPrefix;
+
+impl_db_record!(
+    key = LiabilityTierKey,
+    value = LiabilityTier,
+    db_prefix = DbKeyPrefix::LiabilityTier,
+);
+impl_db_lookup!(
+    key = LiabilityTierKey,
+    query_prefix = LiabilityTierKeyPrefix
+);
+
+/// An issuance or redemption that changed the liabilities of the mint
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Encodable, Decodable, Serialize)]
+pub struct LiabilityEvent {
+    pub denomination: Amount,
+    pub redemption: bool,
+}
+
+/// The log of liability events since the last committed liability root, which
+/// allows us to recompute the root a peer proposed at an earlier event count
+#[derive(Debug, Clone, Copy, Encodable, Decodable, Serialize)]
+pub struct LiabilityEventKey(pub u64);
+
+#[derive(Debug, Encodable, Decodable)]
+pub struct LiabilityEventKeyPrefix;
+
+impl_db_record!(
+    key = LiabilityEventKey,
+    value = LiabilityEvent,
+    db_prefix = DbKeyPrefix::LiabilityEvent,
+);
+impl_db_lookup!(
+    key = LiabilityEventKey,
+    query_prefix = LiabilityEventKeyPrefix
+);
+
+/// The latest liability root the federation agreed on in consensus
+#[derive(Debug, Encodable, Decodable, Serialize)]
+pub struct CommittedLiabilityRootKey;
+
+#[derive(Debug, Encodable, Decodable)]
+pub struct CommittedLiabilityRootKeyPrefix;
+
+impl_db_record!(
+    key = CommittedLiabilityRootKey,
+    value = LiabilityRoot,
+    db_prefix = DbKeyPrefix::CommittedLiabilityRoot,
+);
+impl_db_lookup!(
+    key = CommittedLiabilityRootKey,
+    query_prefix = CommittedLiabilityRootKeyPrefix
+);
+
+/// The latest unix time vote of every peer, the federation's consensus unix time
+/// decides when time locked notes can be refunded
+#[derive(Debug, Encodable, Decodable, Serialize)]
+pub struct UnixTimeVoteKey(pub PeerId);
+
+#[derive(Debug, Encodable, Decodable)]
+pub struct UnixTimeVotePrefix;
+
+impl_db_record!(
+    key = UnixTimeVoteKey,
+    value = u64,
+    db_prefix = DbKeyPrefix::UnixTimeVote,
+);
+impl_db_lookup!(key = UnixTimeVoteKey, query_prefix = UnixTimeVotePrefix);
+
+/// The module consensus version every peer voted to upgrade to
+#[derive(Debug, Encodable, Decodable, Serialize)]
+pub struct ConsensusVersionVoteKey(pub PeerId);
+
+#[derive(Debug, Encodable, Decodable)]
+pub struct ConsensusVersionVotePrefix;
+
+impl_db_record!(
+    key = ConsensusVersionVoteKey,
+    value = ModuleConsensusVersion,
+    db_prefix = DbKeyPrefix::ConsensusVersionVote,
+);
+impl_db_lookup!(
+    key = ConsensusVersionVoteKey,
+    query_prefix = ConsensusVersionVotePrefix
+);
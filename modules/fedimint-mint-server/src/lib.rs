@@ -8,8 +8,9 @@
 mod metrics;
 
 use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::time::Duration;
 
-use anyhow::bail;
+use anyhow::{bail, ensure};
 use fedimint_core::bitcoin::hashes::sha256;
 use fedimint_core::config::{
     ServerModuleConfig, ServerModuleConsensusConfig, TypedServerModuleConfig,
@@ -17,33 +18,46 @@
 };
 use fedimint_core::core::ModuleInstanceId;
 use fedimint_core::db::{
-    DatabaseTransaction, DatabaseVersion, IDatabaseTransactionOpsCore,
+    Database, DatabaseTransaction, DatabaseVersion, IDatabaseTransactionOpsCore,
     IDatabaseTransactionOpsCoreTyped,
 };
 use fedimint_core::encoding::Encodable;
-use fedimint_core::envs::{FM_ENABLE_MODULE_MINT_ENV, is_env_var_set_opt};
+use fedimint_core::envs::{
+    FM_ENABLE_MODULE_MINT_ENV, is_automatic_consensus_version_voting_disabled, is_env_var_set_opt,
+};
 use fedimint_core::module::audit::Audit;
+use fedimint_core::module::liabilities::{
+    LiabilityNode, LiabilityProof, LiabilityRoot, NodePosition, TierLiabilities, peak_positions,
+    proof_positions,
+};
 use fedimint_core::module::{
     Amounts, ApiEndpoint, ApiError, ApiVersion, CORE_CONSENSUS_VERSION, CoreConsensusVersion,
     InputMeta, ModuleConsensusVersion, ModuleInit, SerdeModuleEncodingBase64,
     SupportedModuleApiVersions, TransactionItemAmounts, api_endpoint,
 };
+use fedimint_core::task::{TaskGroup, sleep};
+use fedimint_core::time::duration_since_epoch;
+use fedimint_core::util::FmtCompactAnyhow as _;
 use fedimint_core::{
     Amount, InPoint, NumPeersExt, OutPoint, PeerId, Tiered, TieredMulti, apply,
     async_trait_maybe_send, push_db_key_items, push_db_pair_items,
 };
 use fedimint_logging::LOG_MODULE_MINT;
 pub use fedimint_mint_common as common;
+use fedimint_mint_common::condition::SpendingConditionError;
 use fedimint_mint_common::config::{
     FeeConsensus, MintClientConfig, MintConfig, MintConfigConsensus, MintConfigPrivate,
 };
 pub use fedimint_mint_common::{BackupRequest, SignedBackupRequest};
 use fedimint_mint_common::{
-    DEFAULT_MAX_NOTES_PER_DENOMINATION, MODULE_CONSENSUS_VERSION, MintCommonInit,
-    MintConsensusItem, MintInput, MintInputError, MintModuleTypes, MintOutput, MintOutputError,
-    MintOutputOutcome,
+    DEFAULT_MAX_NOTES_PER_DENOMINATION, LIABILITY_COMMITMENT_MODULE_CONSENSUS_VERSION,
+    MODULE_CONSENSUS_VERSION, MintCommonInit, MintConsensusItem, MintInput, MintInputError,
+    MintModuleTypes, MintOutput, MintOutputError, MintOutputOutcome, MintOutputV0,
+    SPENDING_CONDITIONS_MODULE_CONSENSUS_VERSION, UnknownMintInputVariantError,
 };
 use fedimint_server_core::config::{PeerHandleOps, eval_poly_g2};
+use fedimint_server_core::consensus_version::spawn_peer_supported_consensus_version_task;
+use fedimint_server_core::guardian_signer::{GuardianSecret, ModuleGuardianSigner};
 use fedimint_server_core::migration::{
     ModuleHistoryItem, ServerModuleDbMigrationFn, ServerModuleDbMigrationFnContext,
     ServerModuleDbMigrationFnContextExt as _,
@@ -60,24 +74,32 @@
 use rand::rngs::OsRng;
 use strum::IntoEnumIterator;
 use tbs::{
-    AggregatePublicKey, PublicKeyShare, SecretKeyShare, aggregate_public_key_shares,
-    derive_pk_share, sign_message,
+    AggregatePublicKey, BlindedMessage, BlindedSignatureShare, PublicKeyShare, SecretKeyShare,
+    aggregate_public_key_shares, derive_pk_share, verify_signature_share,
 };
 use threshold_crypto::ff::Field;
 use threshold_crypto::group::Curve;
 use threshold_crypto::{G2Projective, Scalar};
+use tokio::sync::watch;
 use tracing::{debug, info, warn};
 
 use crate::common::endpoint_constants::{
-    BLIND_NONCE_USED_ENDPOINT, NOTE_SPENT_ENDPOINT, RECOVERY_BLIND_NONCE_OUTPOINTS_ENDPOINT,
-    RECOVERY_COUNT_ENDPOINT, RECOVERY_SLICE_ENDPOINT, RECOVERY_SLICE_HASH_ENDPOINT,
+    BLIND_NONCE_USED_ENDPOINT, LIABILITY_PROOF_ENDPOINT, LIABILITY_ROOT_ENDPOINT,
+    MODULE_CONSENSUS_VERSION_ENDPOINT, NOTE_SPENT_ENDPOINT,
+    RECOVERY_BLIND_NONCE_OUTPOINTS_ENDPOINT, RECOVERY_COUNT_ENDPOINT, RECOVERY_SLICE_ENDPOINT,
+    RECOVERY_SLICE_HASH_ENDPOINT, SUPPORTED_MODULE_CONSENSUS_VERSION_ENDPOINT,
 };
 use crate::common::{BlindNonce, Nonce, RecoveryItem};
 use crate::db::{
-    BlindNonceKey, BlindNonceKeyPrefix, DbKeyPrefix, MintAuditItemKey, MintAuditItemKeyPrefix,
-    MintOutputOutcomeKey, MintOutputOutcomePrefix, NonceKey, NonceKeyPrefix,
-    RecoveryBlindNonceOutpointKey, RecoveryBlindNonceOutpointKeyPrefix, RecoveryItemKey,
-    RecoveryItemKeyPrefix,
+    BlindNonceKey, BlindNonceKeyPrefix, CommittedLiabilityRootKey, CommittedLiabilityRootKeyPrefix,
+    ConsensusVersionVoteKey, ConsensusVersionVotePrefix, DbKeyPrefix, LiabilityEvent,
+    LiabilityEventKey, LiabilityEventKeyPrefix, LiabilityLeafKey, LiabilityLeafKeyPrefix,
+    LiabilityNodeKey, LiabilityNodeKeyPrefix, LiabilityTier, LiabilityTierKey,
+    LiabilityTierKeyPrefix, MintAuditItemKey, MintAuditItemKeyPrefix, MintOutputOutcomeKey,
+    MintOutputOutcomePrefix, NonceKey, NonceKeyPrefix, PendingBlindSignatureKey,
+    PendingBlindSignaturePrefix, RecoveryBlindNonceOutpointKey,
+    RecoveryBlindNonceOutpointKeyPrefix, RecoveryItemKey, RecoveryItemKeyPrefix, UnixTimeVoteKey,
+    UnixTimeVotePrefix,
 };
 
 #[derive(Debug, Clone)]
@@ -139,6 +161,66 @@ async fn dump_database(
                         "Recovery Items"
                     );
                 }
+                DbKeyPrefix::LiabilityNode => {
+                    push_db_pair_items!(
+                        dbtx,
+                        LiabilityNodeKeyPrefix,
+                        LiabilityNodeKey,
+                        LiabilityNode,
+                        mint,
+                        "Liability Nodes"
+                    );
+                }
+                DbKeyPrefix::LiabilityLeaf => {
+                    push_db_pair_items!(
+                        dbtx,
+                        LiabilityLeafKeyPrefix,
+                        LiabilityLeafKey,
+                        (Amount, u64),
+                        mint,
+                        "Liability Leaves"
+                    );
+                }
+                DbKeyPrefix::LiabilityTier => {
+                    push_db_pair_items!(
+                        dbtx,
+                        LiabilityTierKeyPrefix,
+                        LiabilityTierKey,
+                        LiabilityTier,
+                        mint,
+                        "Liability Tiers"
+                    );
+                }
+                DbKeyPrefix::LiabilityEvent => {
+                    push_db_pair_items!(
+                        dbtx,
+                        LiabilityEventKeyPrefix,
+                        LiabilityEventKey,
+                        LiabilityEvent,
+                        mint,
+                        "Liability Events"
+                    );
+                }
+                DbKeyPrefix::CommittedLiabilityRoot => {
+                    push_db_pair_items!(
+                        dbtx,
+                        CommittedLiabilityRootKeyPrefix,
+                        CommittedLiabilityRootKey,
+                        LiabilityRoot,
+                        mint,
+                        "Committed Liability Root"
+                    );
+                }
+                DbKeyPrefix::PendingBlindSignature => {
+                    push_db_pair_items!(
+                        dbtx,
+                        PendingBlindSignaturePrefix,
+                        PendingBlindSignatureKey,
+                        MintOutputV0,
+                        mint,
+                        "Pending Blind Signatures"
+                    );
+                }
                 DbKeyPrefix::RecoveryBlindNonceOutpoint => {
                     push_db_pair_items!(
                         dbtx,
@@ -149,6 +231,26 @@ async fn dump_database(
                         "Recovery Blind Nonce Outpoints"
                     );
                 }
+                DbKeyPrefix::UnixTimeVote => {
+                    push_db_pair_items!(
+                        dbtx,
+                        UnixTimeVotePrefix,
+                        UnixTimeVoteKey,
+                        u64,
+                        mint,
+                        "Unix Time Votes"
+                    );
+                }
+                DbKeyPrefix::ConsensusVersionVote => {
+                    push_db_pair_items!(
+                        dbtx,
+                        ConsensusVersionVotePrefix,
+                        ConsensusVersionVoteKey,
+                        ModuleConsensusVersion,
+                        mint,
+                        "Consensus Version Votes"
+                    );
+                }
             }
         }
 
@@ -201,7 +303,40 @@ fn get_documented_env_vars(&self) -> Vec<EnvVarDoc> {
     }
 
     async fn init(&self, args: &ServerModuleInitArgs<Self>) -> anyhow::Result<Self::Module> {
-        Ok(Mint::new(args.cfg().to_typed()?))
+        let peer_supported_consensus_version = spawn_peer_supported_consensus_version_task(
+            args.module_api().clone(),
+            args.task_group(),
+            args.our_peer_id(),
+            MODULE_CONSENSUS_VERSION,
+            SUPPORTED_MODULE_CONSENSUS_VERSION_ENDPOINT,
+        );
+
+        let mint = Mint::new(
+            args.cfg().to_typed()?,
+            args.our_peer_id(),
+            args.guardian_signer().clone(),
+        )
+        .with_consensus_version(
+            args.cfg().consensus.version,
+            peer_supported_consensus_version,
+        );
+
+        mint.spawn_pending_blind_signatures_task(args.db().clone(), args.task_group());
+
+        Ok(mint)
+    }
+
+    fn guardian_keys(
+        &self,
+        config: &ServerModuleConfig,
+    ) -> anyhow::Result<BTreeMap<String, GuardianSecret>> {
+        Ok(config
+            .to_typed::<MintConfig>()?
+            .private
+            .tbs_sks
+            .iter()
+            .map(|(amount, sk)| (tbs_key_name(amount), GuardianSecret::Bls12381(sk.0)))
+            .collect())
     }
 
     fn trusted_dealer_gen(
@@ -325,10 +460,12 @@ fn validate_config(&self, identity: &PeerId, config: ServerModuleConfig) -> anyh
             .iter()
             .map(|(k, v)| (*k, *v))
             .collect();
-        if sks != pks {
+        // The secret keys are missing once they have been exported to a remote
+        // guardian signer, which verifies them on startup instead
+        if !sks.is_empty() && sks != pks {
             bail!("Mint private key doesn't match pubkey share");
         }
-        if !sks.keys().contains(&Amount::from_msats(1)) {
+        if !pks.keys().contains(&Amount::from_msats(1)) {
             bail!("No msat 1 denomination");
         }
 
@@ -381,6 +518,10 @@ fn get_database_migrations(
             DatabaseVersion(2),
             Box::new(|ctx| migrate_db_v2(ctx).boxed()),
         );
+        migrations.insert(
+            DatabaseVersion(3),
+            Box::new(|ctx| migrate_db_v3(ctx).boxed()),
+        );
         migrations
     }
 
@@ -509,6 +650,61 @@ async fn migrate_db_v2(mut ctx: ServerModuleDbMigrationFnContext<'_, Mint>) -> a
     Ok(())
 }
 
+// Rebuild the liabilities per denomination from the module history, since the
+// liability forest used to span all denominations and redemptions were not
+// counted. The event log is replayed in full such that every guardian can
+// recompute the same liability roots until the first one is committed.
+async fn migrate_db_v3(mut ctx: ServerModuleDbMigrationFnContext<'_, Mint>) -> anyhow::Result<()> {
+    for prefix in [
+        DbKeyPrefix::LiabilityNode,
+        DbKeyPrefix::LiabilityLeaf,
+        DbKeyPrefix::LiabilityTier,
+        DbKeyPrefix::LiabilityEvent,
+        DbKeyPrefix::CommittedLiabilityRoot,
+    ] {
+        ctx.dbtx()
+            .raw_remove_by_prefix(&[prefix as u8])
+            .await
+            .expect("DB error");
+    }
+
+    let mut events = Vec::new();
+    let mut stream = ctx.get_typed_module_history_stream().await;
+
+    while let Some(history_item) = stream.next().await {
+        match history_item {
+            ModuleHistoryItem::Output(mint_output, _) => {
+                let output = mint_output
+                    .ensure_v0_ref()
+                    .expect("This migration only runs while we only have v0 outputs");
+
+                events.push((output.amount, Some(output.blind_nonce)));
+            }
+            ModuleHistoryItem::Input(mint_input) => {
+                let (amount, _) = mint_input
+                    .amount_and_note()
+                    .expect("Only inputs of known variants have been accepted");
+
+                events.push((amount, None));
+            }
+            ModuleHistoryItem::ConsensusItem(_) => {}
+        }
+    }
+
+    drop(stream);
+
+    info!(target: LOG_MODULE_MINT, "Replaying {} liability events from history", events.len());
+
+    for (amount, blind_nonce) in events {
+        match blind_nonce {
+            Some(blind_nonce) => append_liability(&mut ctx.dbtx(), blind_nonce, amount).await,
+            None => redeem_liability(&mut ctx.dbtx(), amount).await,
+        }
+    }
+
+    Ok(())
+}
+
 fn dealer_keygen(
     threshold: usize,
     keys: usize,
@@ -539,12 +735,52 @@ fn eval_polynomial(coefficients: &[Scalar], x: &Scalar) -> Scalar {
         .expect("We have at least one coefficient")
 }
 
+/// How often we persist the blind signature shares of accepted outputs, which
+/// are created on demand until then
+const PENDING_BLIND_SIGNATURES_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Creates our blind signature share for an output with the guardian signer,
+/// whose requests are bounded by a timeout. This is never called by consensus,
+/// such that an unavailable signer only delays the issuance of the e-cash.
+async fn blind_sign_with_guardian_signer(
+    signer: &ModuleGuardianSigner,
+    amount: Amount,
+    message: BlindedMessage,
+    pk_share: PublicKeyShare,
+) -> anyhow::Result<BlindedSignatureShare> {
+    let signature =
+        BlindedSignatureShare(signer.multiply_g1(&tbs_key_name(amount), message.0).await?);
+
+    ensure!(
+        verify_signature_share(message, signature, pk_share),
+        "Guardian signer returned an invalid blind signature share"
+    );
+
+    Ok(signature)
+}
+
+/// Name of the secret key share of an amount tier in the guardian signer
+fn tbs_key_name(amount: Amount) -> String {
+    format!("tbs_sk/{}", amount.msats)
+}
+
 /// Federated mint member mint
 #[derive(Debug)]
 pub struct Mint {
     cfg: MintConfig,
-    sec_key: Tiered<SecretKeyShare>,
+    /// Our public key shares, used to verify the shares created by the
+    /// guardian signer
+    our_pub_key: Tiered<PublicKeyShare>,
     pub_key: HashMap<Amount, AggregatePublicKey>,
+    /// Creates the blind signatures with our secret key shares
+    guardian_signer: ModuleGuardianSigner,
+    /// Consensus version the federation was created with, which is active
+    /// until the peers vote to upgrade
+    genesis_consensus_version: ModuleConsensusVersion,
+    /// Maximum consensus version supported by *all* our peers. Used to
+    /// automatically activate new consensus versions as soon as everyone
+    /// upgrades.
+    peer_supported_consensus_version: watch::Receiver<Option<ModuleConsensusVersion>>,
 }
 #[apply(async_trait_maybe_send!)]
 impl ServerModule for Mint {
@@ -553,32 +789,153 @@ impl ServerModule for Mint {
 
     async fn consensus_proposal(
         &self,
-        _dbtx: &mut DatabaseTransaction<'_>,
+        dbtx: &mut DatabaseTransaction<'_>,
     ) -> Vec<MintConsensusItem> {
-        Vec::new()
+        let active_consensus_version = self.consensus_module_consensus_version(dbtx).await;
+
+        // Guardians running a version without spending conditions reject any
+        // consensus item, so we only vote once all of them support the upgrade
+        let mut items = if is_automatic_consensus_version_voting_disabled() {
+            vec![]
+        } else {
+            self.peer_supported_consensus_version
+                .borrow()
+                .filter(|supported| active_consensus_version < *supported)
+                .map(MintConsensusItem::ModuleConsensusVersion)
+                .into_iter()
+                .collect()
+        };
+
+        if active_consensus_version < SPENDING_CONDITIONS_MODULE_CONSENSUS_VERSION {
+            return items;
+        }
+
+        // We round the time to the minute to limit the number of votes
+        items.push(MintConsensusItem::UnixTimeVote(
+            60 * (duration_since_epoch().as_secs() / 60),
+        ));
+
+        if active_consensus_version >= LIABILITY_COMMITMENT_MODULE_CONSENSUS_VERSION {
+            let events = get_liability_event_count(dbtx).await;
+
+            if get_committed_liability_event_count(dbtx).await < events {
+                items.push(MintConsensusItem::LiabilityRoot(
+                    get_liability_root(dbtx, events)
+                        .await
+                        .expect("Uncommitted liability events are not pruned"),
+                ));
+            }
+        }
+
+        items
     }
 
     async fn process_consensus_item<'a, 'b>(
         &'a self,
-        _dbtx: &mut DatabaseTransaction<'b>,
-        _consensus_item: MintConsensusItem,
-        _peer_id: PeerId,
+        dbtx: &mut DatabaseTransaction<'b>,
+        consensus_item: MintConsensusItem,
+        peer_id: PeerId,
     ) -> anyhow::Result<()> {
-        bail!("Mint does not process consensus items");
+        match consensus_item {
+            MintConsensusItem::UnixTimeVote(vote) => {
+                ensure!(
+                    self.consensus_module_consensus_version(dbtx).await
+                        >= SPENDING_CONDITIONS_MODULE_CONSENSUS_VERSION,
+                    "Unix time votes are not active yet"
+                );
+
+                let current_vote = dbtx.insert_entry(&UnixTimeVoteKey(peer_id), &vote).await;
+
+                ensure!(current_vote < Some(vote), "Unix time vote is redundant");
+
+                Ok(())
+            }
+            MintConsensusItem::ModuleConsensusVersion(module_consensus_version) => {
+                let current_vote = dbtx
+                    .get_value(&ConsensusVersionVoteKey(peer_id))
+                    .await
+                    .unwrap_or(self.genesis_consensus_version);
+
+                ensure!(
+                    module_consensus_version > current_vote,
+                    "Module consensus version vote is redundant"
+                );
+
+                dbtx.insert_entry(&ConsensusVersionVoteKey(peer_id), &module_consensus_version)
+                    .await;
+
+                assert!(
+                    self.consensus_module_consensus_version(dbtx).await <= MODULE_CONSENSUS_VERSION,
+                    "Mint module does not support new consensus version, please upgrade the module"
+                );
+
+                Ok(())
+            }
+            MintConsensusItem::LiabilityRoot(root) => {
+                ensure!(
+                    self.consensus_module_consensus_version(dbtx).await
+                        >= LIABILITY_COMMITMENT_MODULE_CONSENSUS_VERSION,
+                    "Liability commitments are not active yet"
+                );
+
+                ensure!(
+                    get_committed_liability_event_count(dbtx).await < root.events,
+                    "Liability root is redundant"
+                );
+
+                ensure!(
+                    get_liability_root(dbtx, root.events).await.as_ref() == Some(&root),
+                    "Liability root does not match our liabilities"
+                );
+
+                dbtx.insert_entry(&CommittedLiabilityRootKey, &root).await;
+
+                // Events before the committed root are never needed again,
+                // since later roots have to account for more events
+                let pruned = dbtx
+                    .find_by_prefix(&LiabilityEventKeyPrefix)
+                    .await
+                    .map(|(key, _)| key)
+                    .filter(|key| std::future::ready(key.0 < root.events))
+                    .collect::<Vec<_>>()
+                    .await;
+
+                for key in pruned {
+                    dbtx.remove_entry(&key).await;
+                }
+
+                Ok(())
+            }
+            MintConsensusItem::Default { variant, .. } => {
+                bail!("Received unknown consensus item variant {variant}");
+            }
+        }
     }
 
     fn verify_input(&self, input: &MintInput) -> Result<(), MintInputError> {
-        let input = input.ensure_v0_ref()?;
+        let (amount, note) = input.amount_and_note()?;
 
         let amount_key = self
             .pub_key
-            .get(&input.amount)
-            .ok_or(MintInputError::InvalidAmountTier(input.amount))?;
+            .get(&amount)
+            .ok_or(MintInputError::InvalidAmountTier(amount))?;
 
-        if !input.note.verify(*amount_key) {
+        if !note.verify(*amount_key) {
             return Err(MintInputError::InvalidSignature);
         }
 
+        if let MintInput::V1(input) = input {
+            if input.condition.nonce(&input.salt) != note.nonce {
+                return Err(SpendingConditionError::NonceMismatch.into());
+            }
+
+            // Time locks are checked against the consensus unix time in
+            // process_input
+            input
+                .condition
+                .spend_key(&note.nonce, &input.witness, u64::MAX)?;
+        }
+
         Ok(())
     }
 
@@ -588,34 +945,49 @@ async fn process_input<'a, 'b, 'c>(
         input: &'b MintInput,
         _in_point: InPoint,
     ) -> Result<InputMeta, MintInputError> {
-        let input = input.ensure_v0_ref()?;
+        let (amount, note) = input.amount_and_note()?;
+
+        let pub_key = match input {
+            MintInput::V1(input) => {
+                if self.consensus_module_consensus_version(dbtx).await
+                    < SPENDING_CONDITIONS_MODULE_CONSENSUS_VERSION
+                {
+                    return Err(UnknownMintInputVariantError { variant: 1 }.into());
+                }
 
-        debug!(target: LOG_MODULE_MINT, nonce=%(input.note.nonce.fmt_short()), "Marking note as spent");
+                input.condition.spend_key(
+                    &note.nonce,
+                    &input.witness,
+                    self.consensus_unix_time(dbtx).await,
+                )?
+            }
+            _ => *note.spend_key(),
+        };
+
+        debug!(target: LOG_MODULE_MINT, nonce=%(note.nonce.fmt_short()), "Marking note as spent");
 
         if dbtx
-            .insert_entry(&NonceKey(input.note.nonce), &())
+            .insert_entry(&NonceKey(note.nonce), &())
             .await
             .is_some()
         {
             return Err(MintInputError::SpentCoin);
         }
 
-        dbtx.insert_new_entry(
-            &MintAuditItemKey::Redemption(NonceKey(input.note.nonce)),
-            &input.amount,
-        )
-        .await;
+        dbtx.insert_new_entry(&MintAuditItemKey::Redemption(NonceKey(note.nonce)), &amount)
+            .await;
+
+        redeem_liability(dbtx, amount).await;
 
         let next_index = get_recovery_count(dbtx).await;
         dbtx.insert_new_entry(
             &RecoveryItemKey(next_index),
             &RecoveryItem::Input {
-                nonce: input.note.nonce.consensus_hash(),
+                nonce: note.nonce.consensus_hash(),
             },
         )
         .await;
 
-        let amount = input.amount;
         let fee = self.cfg.consensus.fee_consensus.fee(amount);
 
         calculate_mint_redeemed_ecash_metrics(dbtx, amount, fee);
@@ -625,7 +997,7 @@ async fn process_input<'a, 'b, 'c>(
                 amounts: Amounts::new_bitcoin(amount),
                 fees: Amounts::new_bitcoin(fee),
             },
-            pub_key: *input.note.spend_key(),
+            pub_key,
         })
     }
 
@@ -637,16 +1009,14 @@ async fn process_output<'a, 'b>(
     ) -> Result<TransactionItemAmounts, MintOutputError> {
         let output = output.ensure_v0_ref()?;
 
-        let amount_key = self
-            .sec_key
-            .get(output.amount)
-            .ok_or(MintOutputError::InvalidAmountTier(output.amount))?;
+        if self.our_pub_key.get(output.amount).is_none() {
+            return Err(MintOutputError::InvalidAmountTier(output.amount));
+        }
 
-        dbtx.insert_new_entry(
-            &MintOutputOutcomeKey(out_point),
-            &MintOutputOutcome::new_v0(sign_message(output.blind_nonce.0, *amount_key)),
-        )
-        .await;
+        // Consensus must not wait for the guardian signer, hence we only record
+        // the output here and create our blind signature share on demand.
+        dbtx.insert_new_entry(&PendingBlindSignatureKey(out_point), output)
+            .await;
 
         dbtx.insert_new_entry(&MintAuditItemKey::Issuance(out_point), &output.amount)
             .await;
@@ -665,6 +1035,8 @@ async fn process_output<'a, 'b>(
             );
         }
 
+        append_liability(dbtx, output.blind_nonce, output.amount).await;
+
         let next_index = get_recovery_count(dbtx).await;
         dbtx.insert_new_entry(
             &RecoveryItemKey(next_index),
@@ -706,7 +1078,32 @@ async fn output_status(
         dbtx: &mut DatabaseTransaction<'_>,
         out_point: OutPoint,
     ) -> Option<MintOutputOutcome> {
-        dbtx.get_value(&MintOutputOutcomeKey(out_point)).await
+        if let Some(outcome) = dbtx.get_value(&MintOutputOutcomeKey(out_point)).await {
+            return Some(outcome);
+        }
+
+        let output = dbtx.get_value(&PendingBlindSignatureKey(out_point)).await?;
+
+        match blind_sign_with_guardian_signer(
+            &self.guardian_signer,
+            output.amount,
+            output.blind_nonce.0,
+            *self.our_pub_key.get(output.amount)?,
+        )
+        .await
+        {
+            Ok(signature) => Some(MintOutputOutcome::new_v0(signature)),
+            Err(e) => {
+                warn!(
+                    target: LOG_MODULE_MINT,
+                    err = %e.fmt_compact_anyhow(),
+                    %out_point,
+                    "Failed to create blind signature with guardian signer"
+                );
+
+                None
+            }
+        }
     }
 
     #[doc(hidden)]
@@ -844,6 +1241,40 @@ fn api_endpoints(&self) -> Vec<ApiEndpoint<Self>> {
                     Ok(result)
                 }
             },
+            api_endpoint! {
+                MODULE_CONSENSUS_VERSION_ENDPOINT,
+                ApiVersion::new(0, 1),
+                async |module: &Mint, context, _params: ()| -> ModuleConsensusVersion {
+                    let db = context.db();
+                    let mut dbtx = db.begin_transaction_nc().await;
+                    Ok(module.consensus_module_consensus_version(&mut dbtx).await)
+                }
+            },
+            api_endpoint! {
+                SUPPORTED_MODULE_CONSENSUS_VERSION_ENDPOINT,
+                ApiVersion::new(0, 1),
+                async |_module: &Mint, _context, _params: ()| -> ModuleConsensusVersion {
+                    Ok(MODULE_CONSENSUS_VERSION)
+                }
+            },
+            api_endpoint! {
+                LIABILITY_ROOT_ENDPOINT,
+                ApiVersion::new(0, 1),
+                async |_module: &Mint, context, _params: ()| -> Option<LiabilityRoot> {
+                    let db = context.db();
+                    let mut dbtx = db.begin_transaction_nc().await;
+                    Ok(dbtx.get_value(&CommittedLiabilityRootKey).await)
+                }
+            },
+            api_endpoint! {
+                LIABILITY_PROOF_ENDPOINT,
+                ApiVersion::new(0, 1),
+                async |_module: &Mint, context, blind_nonce: BlindNonce| -> Option<(LiabilityRoot, LiabilityProof)> {
+                    let db = context.db();
+                    let mut dbtx = db.begin_transaction_nc().await;
+                    Ok(get_liability_proof(&mut dbtx, blind_nonce).await)
+                }
+            },
         ]
     }
 }
@@ -882,6 +1313,208 @@ fn calculate_mint_redeemed_ecash_metrics(
     });
 }
 
+/// Appends the leaf for an issued note to the liability forest of its
+/// denomination and completes all parent nodes for which the leaf was the last
+/// missing descendant.
+async fn append_liability(
+    dbtx: &mut DatabaseTransaction<'_>,
+    blind_nonce: BlindNonce,
+    amount: Amount,
+) {
+    let index = record_liability_event(
+        dbtx,
+        LiabilityEvent {
+            denomination: amount,
+            redemption: false,
+        },
+    )
+    .await
+    .issued;
+
+    dbtx.insert_entry(&LiabilityLeafKey(blind_nonce), &(amount, index))
+        .await;
+
+    let mut position = NodePosition::leaf(index);
+    let mut node = LiabilityNode::leaf(&blind_nonce, amount);
+
+    dbtx.insert_new_entry(&LiabilityNodeKey(amount, position), &node)
+        .await;
+
+    while position.is_right_child() {
+        let sibling = dbtx
+            .get_value(&LiabilityNodeKey(amount, position.sibling()))
+            .await
+            .expect("Left sibling has been completed before");
+
+        node = LiabilityNode::parent(&sibling, &node).expect("Total issuance overflowed");
+        position = position.parent();
+
+        dbtx.insert_new_entry(&LiabilityNodeKey(amount, position), &node)
+            .await;
+    }
+}
+
+/// Counts a redeemed note towards the liabilities of its denomination. Since a
+/// spent note can not be linked to its issuance its leaf stays in the forest.
+async fn redeem_liability(dbtx: &mut DatabaseTransaction<'_>, amount: Amount) {
+    record_liability_event(
+        dbtx,
+        LiabilityEvent {
+            denomination: amount,
+            redemption: true,
+        },
+    )
+    .await;
+}
+
+/// Appends the event to the event log and updates the counts of its
+/// denomination, returning the counts before the event.
+async fn record_liability_event(
+    dbtx: &mut DatabaseTransaction<'_>,
+    event: LiabilityEvent,
+) -> LiabilityTier {
+    let index = get_liability_event_count(dbtx).await;
+
+    dbtx.insert_new_entry(&LiabilityEventKey(index), &event)
+        .await;
+
+    let tier = dbtx
+        .get_value(&LiabilityTierKey(event.denomination))
+        .await
+        .unwrap_or_default();
+
+    let mut updated = tier;
+
+    if event.redemption {
+        updated.redeemed += 1;
+    } else {
+        updated.issued += 1;
+    }
+
+    dbtx.insert_entry(&LiabilityTierKey(event.denomination), &updated)
+        .await;
+
+    tier
+}
+
+async fn get_liability_tiers(
+    dbtx: &mut DatabaseTransaction<'_>,
+) -> BTreeMap<Amount, LiabilityTier> {
+    dbtx.find_by_prefix(&LiabilityTierKeyPrefix)
+        .await
+        .map(|(key, tier)| (key.0, tier))
+        .collect()
+        .await
+}
+
+async fn get_liability_event_count(dbtx: &mut DatabaseTransaction<'_>) -> u64 {
+    get_liability_tiers(dbtx)
+        .await
+        .values()
+        .map(|tier| tier.issued + tier.redeemed)
+        .sum()
+}
+
+async fn get_committed_liability_event_count(dbtx: &mut DatabaseTransaction<'_>) -> u64 {
+    dbtx.get_value(&CommittedLiabilityRootKey)
+        .await
+        .map_or(0, |root| root.events)
+}
+
+/// Computes the liability root after the first `events` liability events by
+/// reverting all later events from the current counts. Since the forests are
+/// append-only their peaks at the reverted counts are still stored. Returns
+/// `None` if we have not processed that many events yet or the later events
+/// have been pruned already.
+async fn get_liability_root(
+    dbtx: &mut DatabaseTransaction<'_>,
+    events: u64,
+) -> Option<LiabilityRoot> {
+    let mut tiers = get_liability_tiers(dbtx).await;
+
+    let later_events = tiers
+        .values()
+        .map(|tier| tier.issued + tier.redeemed)
+        .sum::<u64>()
+        .checked_sub(events)?;
+
+    let reverted = dbtx
+        .find_by_prefix_sorted_descending(&LiabilityEventKeyPrefix)
+        .await
+        .take_while(|(key, _)| std::future::ready(events <= key.0))
+        .map(|(_, event)| event)
+        .collect::<Vec<_>>()
+        .await;
+
+    if reverted.len() as u64 != later_events {
+        return None;
+    }
+
+    for event in reverted {
+        let tier = tiers.get_mut(&event.denomination)?;
+
+        if event.redemption {
+            tier.redeemed = tier.redeemed.checked_sub(1)?;
+        } else {
+            tier.issued = tier.issued.checked_sub(1)?;
+        }
+    }
+
+    let mut liabilities = BTreeMap::new();
+
+    for (denomination, tier) in tiers {
+        if tier == LiabilityTier::default() {
+            continue;
+        }
+
+        let mut peaks = Vec::new();
+
+        for position in peak_positions(tier.issued) {
+            peaks.push(
+                dbtx.get_value(&LiabilityNodeKey(denomination, position))
+                    .await
+                    .expect("Peak has been completed"),
+            );
+        }
+
+        liabilities.insert(
+            denomination,
+            TierLiabilities {
+                issued: tier.issued,
+                redeemed: tier.redeemed,
+                peaks,
+            },
+        );
+    }
+
+    Some(LiabilityRoot {
+        events,
+        tiers: liabilities,
+    })
+}
+
+/// Proves the inclusion of a note in the last committed liability root, notes
+/// issued after it was committed can not be proven yet.
+async fn get_liability_proof(
+    dbtx: &mut DatabaseTransaction<'_>,
+    blind_nonce: BlindNonce,
+) -> Option<(LiabilityRoot, LiabilityProof)> {
+    let (denomination, index) = dbtx.get_value(&LiabilityLeafKey(blind_nonce)).await?;
+
+    let root = dbtx.get_value(&CommittedLiabilityRootKey).await?;
+
+    let mut siblings = Vec::new();
+
+    for position in proof_positions(index, root.tiers.get(&denomination)?.issued)? {
+        siblings.push(
+            dbtx.get_value(&LiabilityNodeKey(denomination, position))
+                .await?,
+        );
+    }
+
+    Some((root, LiabilityProof { index, siblings }))
+}
+
 async fn get_recovery_count(dbtx: &mut DatabaseTransaction<'_>) -> u64 {
     dbtx.find_by_prefix_sorted_descending(&RecoveryItemKeyPrefix)
         .await
@@ -902,15 +1535,22 @@ async fn get_recovery_slice(
 }
 
 impl Mint {
-    /// Constructs a new mint
+    /// Constructs a new mint creating its blind signatures with the given
+    /// guardian signer
     ///
     /// # Panics
     /// * If there are no amount tiers
-    /// * If the amount tiers for secret and public keys are inconsistent
-    /// * If the pub key belonging to the secret key share is not in the pub key
-    ///   list.
-    pub fn new(cfg: MintConfig) -> Mint {
-        assert!(cfg.private.tbs_sks.tiers().count() > 0);
+    /// * If the amount tiers of the public key shares are inconsistent
+    /// * If our peer id is not in the pub key list.
+    pub fn new(cfg: MintConfig, our_id: PeerId, guardian_signer: ModuleGuardianSigner) -> Mint {
+        let our_pub_key = cfg
+            .consensus
+            .peer_tbs_pks
+            .get(&our_id)
+            .expect("Own key not found among pub keys.")
+            .clone();
+
+        assert!(our_pub_key.tiers().count() > 0);
 
         // The amount tiers are implicitly provided by the key sets, make sure they are
         // internally consistent.
@@ -918,32 +1558,7 @@ pub fn new(cfg: MintConfig) -> Mint {
             cfg.consensus
                 .peer_tbs_pks
                 .values()
-                .all(|pk| pk.structural_eq(&cfg.private.tbs_sks))
-        );
-
-        let ref_pub_key = cfg
-            .private
-            .tbs_sks
-            .iter()
-            .map(|(amount, sk)| (amount, derive_pk_share(sk)))
-            .collect();
-
-        // Find our key index and make sure we know the private key for all our public
-        // key shares
-        let our_id = cfg
-            .consensus // FIXME: make sure we use id instead of idx everywhere
-            .peer_tbs_pks
-            .iter()
-            .find_map(|(&id, pk)| if *pk == ref_pub_key { Some(id) } else { None })
-            .expect("Own key not found among pub keys.");
-
-        assert_eq!(
-            cfg.consensus.peer_tbs_pks[&our_id],
-            cfg.private
-                .tbs_sks
-                .iter()
-                .map(|(amount, sk)| (amount, derive_pk_share(sk)))
-                .collect()
+                .all(|pk| pk.structural_eq(&our_pub_key))
         );
 
         // TODO: the aggregate pks should become part of the MintConfigConsensus as they
@@ -965,14 +1580,135 @@ pub fn new(cfg: MintConfig) -> Mint {
 
         Mint {
             cfg: cfg.clone(),
-            sec_key: cfg.private.tbs_sks,
+            our_pub_key,
             pub_key: aggregate_pub_keys,
+            guardian_signer,
+            genesis_consensus_version: MODULE_CONSENSUS_VERSION,
+            peer_supported_consensus_version: watch::channel(None).1,
+        }
+    }
+
+    /// Sets the consensus version the federation was created with and the
+    /// consensus version supported by all peers, which is voted for once it
+    /// is higher than the active one
+    #[must_use]
+    pub fn with_consensus_version(
+        self,
+        genesis_consensus_version: ModuleConsensusVersion,
+        peer_supported_consensus_version: watch::Receiver<Option<ModuleConsensusVersion>>,
+    ) -> Mint {
+        Mint {
+            genesis_consensus_version,
+            peer_supported_consensus_version,
+            ..self
         }
     }
 
     pub fn pub_key(&self) -> HashMap<Amount, AggregatePublicKey> {
         self.pub_key.clone()
     }
+
+    /// Persists the blind signature shares of the outputs accepted by
+    /// consensus, such that we only need the guardian signer once per output
+    pub fn spawn_pending_blind_signatures_task(&self, db: Database, task_group: &TaskGroup) {
+        let guardian_signer = self.guardian_signer.clone();
+        let our_pub_key = self.our_pub_key.clone();
+
+        task_group.spawn_cancellable("mint_pending_blind_signatures", async move {
+            loop {
+                let pending = db
+                    .begin_transaction_nc()
+                    .await
+                    .find_by_prefix(&PendingBlindSignaturePrefix)
+                    .await
+                    .collect::<Vec<_>>()
+                    .await;
+
+                for (PendingBlindSignatureKey(out_point), output) in pending {
+                    let pk_share = *our_pub_key
+                        .get(output.amount)
+                        .expect("Accepted outputs have a valid amount tier");
+
+                    match blind_sign_with_guardian_signer(
+                        &guardian_signer,
+                        output.amount,
+                        output.blind_nonce.0,
+                        pk_share,
+                    )
+                    .await
+                    {
+                        Ok(signature) => {
+                            let mut dbtx = db.begin_transaction().await;
+
+                            dbtx.insert_entry(
+                                &MintOutputOutcomeKey(out_point),
+                                &MintOutputOutcome::new_v0(signature),
+                            )
+                            .await;
+
+                            dbtx.remove_entry(&PendingBlindSignatureKey(out_point))
+                                .await;
+
+                            dbtx.commit_tx().await;
+                        }
+                        Err(e) => {
+                            warn!(
+                                target: LOG_MODULE_MINT,
+                                err = %e.fmt_compact_anyhow(),
+                                %out_point,
+                                "Failed to create blind signature with guardian signer, retrying..."
+                            );
+
+                            break;
+                        }
+                    }
+                }
+
+                sleep(PENDING_BLIND_SIGNATURES_INTERVAL).await;
+            }
+        });
+    }
+
+    /// The latest unix time at least a threshold of peers voted for, so a
+    /// malicious minority can't move it forward
+    async fn consensus_unix_time(&self, dbtx: &mut DatabaseTransaction<'_>) -> u64 {
+        let num_peers = self.cfg.consensus.peer_tbs_pks.to_num_peers();
+
+        let mut times: Vec<u64> = dbtx
+            .find_by_prefix(&UnixTimeVotePrefix)
+            .await
+            .map(|entry| entry.1)
+            .collect()
+            .await;
+
+        times.sort_by(|a, b| b.cmp(a));
+
+        times.get(num_peers.threshold() - 1).copied().unwrap_or(0)
+    }
+
+    /// The highest consensus version at least a threshold of peers voted for,
+    /// peers that did not vote count as voting for the genesis version
+    async fn consensus_module_consensus_version(
+        &self,
+        dbtx: &mut DatabaseTransaction<'_>,
+    ) -> ModuleConsensusVersion {
+        let num_peers = self.cfg.consensus.peer_tbs_pks.to_num_peers();
+
+        let mut versions = dbtx
+            .find_by_prefix(&ConsensusVersionVotePrefix)
+            .await
+            .map(|entry| entry.1)
+            .collect::<Vec<ModuleConsensusVersion>>()
+            .await;
+
+        while versions.len() < num_peers.total() {
+            versions.push(self.genesis_consensus_version);
+        }
+
+        versions.sort_unstable();
+
+        versions[num_peers.max_evil()]
+    }
 }
 
 #[cfg(test)]
@@ -1,16 +1,25 @@
+use std::collections::BTreeMap;
+
 use assert_matches::assert_matches;
 use fedimint_core::config::{ClientModuleConfig, ServerModuleConfig};
-use fedimint_core::db::Database;
 use fedimint_core::db::mem_impl::MemDatabase;
+use fedimint_core::db::{Database, DatabaseTransaction, IDatabaseTransactionOpsCoreTyped as _};
 use fedimint_core::module::ModuleConsensusVersion;
+use fedimint_core::module::liabilities::{LiabilityNode, LiabilityRoot};
 use fedimint_core::module::registry::ModuleRegistry;
-use fedimint_core::{Amount, BitcoinHash, InPoint, PeerId, TransactionId, secp256k1};
-use fedimint_mint_common::config::FeeConsensus;
-use fedimint_mint_common::{MintInput, Nonce, Note};
+use fedimint_core::{Amount, BitcoinHash, InPoint, OutPoint, PeerId, TransactionId, secp256k1};
+use fedimint_mint_common::condition::{SpendingCondition, SpendingWitness};
+use fedimint_mint_common::{
+    BlindNonce, MODULE_CONSENSUS_VERSION, MintConsensusItem, MintInput, MintInputError, MintOutput,
+    MintOutputOutcome, MintOutputOutcomeV0, Nonce, Note,
+};
+use fedimint_server_core::guardian_signer::ModuleGuardianSigner;
 use fedimint_server_core::{ConfigGenModuleArgs, ServerModule, ServerModuleInit};
 use tbs::blind_message;
+use tokio::sync::watch;
 
-use crate::{Mint, MintConfig, MintConfigConsensus, MintConfigPrivate, MintInit};
+use crate::db::{CommittedLiabilityRootKey, MintOutputOutcomeKey};
+use crate::{Mint, MintConfig, MintInit, append_liability, get_liability_proof, redeem_liability};
 
 const MINTS: u16 = 5;
 
@@ -34,30 +43,20 @@ fn build_configs() -> (Vec<ServerModuleConfig>, ClientModuleConfig) {
     (mint_cfg.into_values().collect(), client_cfg)
 }
 
+fn guardian_signer(server_cfg: &ServerModuleConfig) -> ModuleGuardianSigner {
+    ModuleGuardianSigner::local(0, MintInit.guardian_keys(server_cfg).unwrap())
+}
+
 #[test_log::test]
 #[should_panic(expected = "Own key not found among pub keys.")]
 fn test_new_panic_without_own_pub_key() {
-    let (mint_server_cfg1, _) = build_configs();
-    let (mint_server_cfg2, _) = build_configs();
+    let (mint_server_cfg, _) = build_configs();
 
-    Mint::new(MintConfig {
-        consensus: MintConfigConsensus {
-            peer_tbs_pks: mint_server_cfg2[0]
-                .to_typed::<MintConfig>()
-                .unwrap()
-                .consensus
-                .peer_tbs_pks,
-            fee_consensus: FeeConsensus::new(1000).expect("Relative fee is within range"),
-            max_notes_per_denomination: 0,
-        },
-        private: MintConfigPrivate {
-            tbs_sks: mint_server_cfg1[0]
-                .to_typed::<MintConfig>()
-                .unwrap()
-                .private
-                .tbs_sks,
-        },
-    });
+    Mint::new(
+        mint_server_cfg[0].to_typed().unwrap(),
+        PeerId::from(MINTS),
+        guardian_signer(&mint_server_cfg[0]),
+    );
 }
 
 fn issue_note(
@@ -65,7 +64,14 @@ fn issue_note(
     denomination: Amount,
 ) -> (secp256k1::Keypair, Note) {
     let note_key = secp256k1::Keypair::new(secp256k1::SECP256K1, &mut rand::thread_rng());
-    let nonce = Nonce(note_key.public_key());
+
+    (
+        note_key,
+        sign_nonce(server_cfgs, denomination, Nonce(note_key.public_key())),
+    )
+}
+
+fn sign_nonce(server_cfgs: &[ServerModuleConfig], denomination: Amount, nonce: Nonce) -> Note {
     let message = nonce.to_message();
     let blinding_key = tbs::BlindingKey::random();
     let blind_msg = blind_message(message, blinding_key);
@@ -87,13 +93,17 @@ fn issue_note(
     let blind_signature = tbs::aggregate_signature_shares(&bsig_shares);
     let signature = tbs::unblind_signature(blinding_key, blind_signature);
 
-    (note_key, Note { nonce, signature })
+    Note { nonce, signature }
 }
 
 #[test_log::test(tokio::test)]
 async fn test_detect_double_spends() {
     let (mint_server_cfg, _) = build_configs();
-    let mint = Mint::new(mint_server_cfg[0].to_typed().unwrap());
+    let mint = Mint::new(
+        mint_server_cfg[0].to_typed().unwrap(),
+        PeerId::from(0),
+        guardian_signer(&mint_server_cfg[0]),
+    );
     let (_, tiered) = mint
         .cfg
         .consensus
@@ -132,3 +142,293 @@ async fn test_detect_double_spends() {
         Err(_)
     );
 }
+
+#[test_log::test(tokio::test)]
+async fn outputs_are_blind_signed_by_guardian_signer() {
+    let (mint_server_cfg, _) = build_configs();
+    let mint_cfg = mint_server_cfg[0].to_typed::<MintConfig>().unwrap();
+    let mint = Mint::new(
+        mint_cfg.clone(),
+        PeerId::from(0),
+        guardian_signer(&mint_server_cfg[0]),
+    );
+
+    let denomination = Amount::from_msats(1024);
+    let note_key = secp256k1::Keypair::new(secp256k1::SECP256K1, &mut rand::thread_rng());
+    let blind_msg = blind_message(
+        Nonce(note_key.public_key()).to_message(),
+        tbs::BlindingKey::random(),
+    );
+    let out_point = OutPoint {
+        txid: TransactionId::all_zeros(),
+        out_idx: 0,
+    };
+
+    let db = Database::new(MemDatabase::new(), ModuleRegistry::default());
+    let mut dbtx = db.begin_transaction_nc().await;
+    let mut dbtx = dbtx.to_ref_with_prefix_module_id(42).0.into_nc();
+
+    mint.process_output(
+        &mut dbtx,
+        &MintOutput::new_v0(denomination, BlindNonce(blind_msg)),
+        out_point,
+    )
+    .await
+    .expect("Output of a known denomination is accepted");
+
+    let MintOutputOutcome::V0(MintOutputOutcomeV0(share)) = mint
+        .output_status(&mut dbtx, out_point)
+        .await
+        .expect("Outcome is created on demand")
+    else {
+        panic!("Unexpected outcome version");
+    };
+
+    let pk_share = *mint_cfg.consensus.peer_tbs_pks[&PeerId::from(0)]
+        .get(denomination)
+        .unwrap();
+
+    assert!(tbs::verify_signature_share(blind_msg, share, pk_share));
+}
+
+#[test_log::test(tokio::test)]
+async fn outputs_are_accepted_while_guardian_signer_is_unavailable() {
+    let (mint_server_cfg, _) = build_configs();
+
+    // The signer does not hold our keys, so every signing request fails
+    let mint = Mint::new(
+        mint_server_cfg[0].to_typed().unwrap(),
+        PeerId::from(0),
+        ModuleGuardianSigner::local(0, BTreeMap::new()),
+    );
+
+    let blind_msg = blind_message(
+        Nonce(secp256k1::Keypair::new(secp256k1::SECP256K1, &mut rand::thread_rng()).public_key())
+            .to_message(),
+        tbs::BlindingKey::random(),
+    );
+    let out_point = OutPoint {
+        txid: TransactionId::all_zeros(),
+        out_idx: 0,
+    };
+
+    let db = Database::new(MemDatabase::new(), ModuleRegistry::default());
+    let mut dbtx = db.begin_transaction_nc().await;
+    let mut dbtx = dbtx.to_ref_with_prefix_module_id(42).0.into_nc();
+
+    mint.process_output(
+        &mut dbtx,
+        &MintOutput::new_v0(Amount::from_msats(1024), BlindNonce(blind_msg)),
+        out_point,
+    )
+    .await
+    .expect("Consensus does not depend on the guardian signer");
+
+    assert!(
+        dbtx.get_value(&MintOutputOutcomeKey(out_point))
+            .await
+            .is_none()
+    );
+    assert!(mint.output_status(&mut dbtx, out_point).await.is_none());
+}
+
+#[test_log::test(tokio::test)]
+async fn spending_conditions_activate_once_all_peers_upgraded() {
+    let (mint_server_cfg, _) = build_configs();
+
+    // The federation was created before spending conditions and not all
+    // guardians reported that they support them yet
+    let (sender, receiver) = watch::channel(None);
+    let mint = Mint::new(
+        mint_server_cfg[0].to_typed().unwrap(),
+        PeerId::from(0),
+        guardian_signer(&mint_server_cfg[0]),
+    )
+    .with_consensus_version(ModuleConsensusVersion::new(2, 0), receiver);
+
+    let denomination = Amount::from_msats(1024);
+    let spend_key = secp256k1::Keypair::new(secp256k1::SECP256K1, &mut rand::thread_rng());
+    let condition = SpendingCondition::PublicKey(spend_key.public_key());
+    let note = sign_nonce(&mint_server_cfg, denomination, condition.nonce(&[0; 32]));
+    let input = MintInput::new_v1(denomination, note, condition, [0; 32], SpendingWitness::Key);
+    let in_point = InPoint {
+        txid: TransactionId::all_zeros(),
+        in_idx: 0,
+    };
+
+    let db = Database::new(MemDatabase::new(), ModuleRegistry::default());
+    let mut dbtx = db.begin_transaction_nc().await;
+    let mut dbtx = dbtx.to_ref_with_prefix_module_id(42).0.into_nc();
+
+    // Guardians that did not upgrade reject every consensus item, so none are
+    // proposed, and neither time votes nor conditional spends are accepted
+    assert_eq!(mint.consensus_proposal(&mut dbtx).await, vec![]);
+    assert!(
+        mint.process_consensus_item(
+            &mut dbtx,
+            MintConsensusItem::UnixTimeVote(60),
+            PeerId::from(1)
+        )
+        .await
+        .is_err()
+    );
+    assert_matches!(
+        mint.process_input(&mut dbtx, &input, in_point).await,
+        Err(MintInputError::UnknownInputVariant(_))
+    );
+
+    // Once all guardians support the new version we vote for it, and it is
+    // activated once a threshold of votes was processed
+    sender.send(Some(MODULE_CONSENSUS_VERSION)).unwrap();
+
+    assert_eq!(
+        mint.consensus_proposal(&mut dbtx).await,
+        vec![MintConsensusItem::ModuleConsensusVersion(
+            MODULE_CONSENSUS_VERSION
+        )]
+    );
+
+    for peer in 0..MINTS - 1 {
+        assert_matches!(
+            mint.process_input(&mut dbtx, &input, in_point).await,
+            Err(MintInputError::UnknownInputVariant(_))
+        );
+
+        mint.process_consensus_item(
+            &mut dbtx,
+            MintConsensusItem::ModuleConsensusVersion(MODULE_CONSENSUS_VERSION),
+            PeerId::from(peer),
+        )
+        .await
+        .expect("Vote is valid");
+    }
+
+    assert_matches!(
+        mint.consensus_proposal(&mut dbtx).await.as_slice(),
+        [MintConsensusItem::UnixTimeVote(_)]
+    );
+    assert_eq!(
+        mint.process_input(&mut dbtx, &input, in_point)
+            .await
+            .expect("Conditional spend is accepted")
+            .pub_key,
+        spend_key.public_key()
+    );
+}
+
+async fn proposed_liability_root(
+    mint: &Mint,
+    dbtx: &mut DatabaseTransaction<'_>,
+) -> Option<LiabilityRoot> {
+    mint.consensus_proposal(dbtx)
+        .await
+        .into_iter()
+        .find_map(|item| match item {
+            MintConsensusItem::LiabilityRoot(root) => Some(root),
+            _ => None,
+        })
+}
+
+fn random_blind_nonce() -> BlindNonce {
+    let note_key = secp256k1::Keypair::new(secp256k1::SECP256K1, &mut rand::thread_rng());
+
+    BlindNonce(blind_message(
+        Nonce(note_key.public_key()).to_message(),
+        tbs::BlindingKey::random(),
+    ))
+}
+
+#[test_log::test(tokio::test)]
+async fn liability_root_is_committed_once_it_matches_our_liabilities() {
+    let (mint_server_cfg, _) = build_configs();
+    let mint = Mint::new(
+        mint_server_cfg[0].to_typed().unwrap(),
+        PeerId::from(0),
+        guardian_signer(&mint_server_cfg[0]),
+    );
+
+    let db = Database::new(MemDatabase::new(), ModuleRegistry::default());
+    let mut dbtx = db.begin_transaction_nc().await;
+    let mut dbtx = dbtx.to_ref_with_prefix_module_id(42).0.into_nc();
+
+    let denomination = Amount::from_msats(1024);
+    let blind_nonce = random_blind_nonce();
+
+    append_liability(&mut dbtx, blind_nonce, denomination).await;
+
+    for _ in 0..2 {
+        append_liability(&mut dbtx, random_blind_nonce(), denomination).await;
+    }
+
+    redeem_liability(&mut dbtx, denomination).await;
+
+    let root = proposed_liability_root(&mint, &mut dbtx)
+        .await
+        .expect("Uncommitted liabilities are proposed");
+
+    assert!(root.is_well_formed());
+    assert_eq!(root.events, 4);
+    assert_eq!(root.outstanding(), Some(denomination * 2));
+
+    // Notes can only be proven to be included once a root has been committed
+    assert_eq!(get_liability_proof(&mut dbtx, blind_nonce).await, None);
+
+    // Events processed after the root was proposed are reverted to check it
+    append_liability(&mut dbtx, random_blind_nonce(), denomination).await;
+
+    let mut understated = root.clone();
+
+    understated
+        .tiers
+        .get_mut(&denomination)
+        .expect("Tier has been issued in")
+        .redeemed += 1;
+
+    assert!(
+        mint.process_consensus_item(
+            &mut dbtx,
+            MintConsensusItem::LiabilityRoot(understated),
+            PeerId::from(1)
+        )
+        .await
+        .is_err()
+    );
+
+    mint.process_consensus_item(
+        &mut dbtx,
+        MintConsensusItem::LiabilityRoot(root.clone()),
+        PeerId::from(1),
+    )
+    .await
+    .expect("Root matches our liabilities");
+
+    assert_eq!(
+        dbtx.get_value(&CommittedLiabilityRootKey).await,
+        Some(root.clone())
+    );
+
+    assert!(
+        mint.process_consensus_item(
+            &mut dbtx,
+            MintConsensusItem::LiabilityRoot(root.clone()),
+            PeerId::from(2)
+        )
+        .await
+        .is_err()
+    );
+
+    let (proof_root, proof) = get_liability_proof(&mut dbtx, blind_nonce)
+        .await
+        .expect("Note was issued before the root was committed");
+
+    assert_eq!(proof_root, root);
+    assert!(proof.verify(&root, &LiabilityNode::leaf(&blind_nonce, denomination)));
+
+    // Only the issuance after the committed root remains to be committed
+    assert_eq!(
+        proposed_liability_root(&mint, &mut dbtx)
+            .await
+            .map(|root| root.events),
+        Some(5)
+    );
+}
@@ -1,3 +1,5 @@
+use std::collections::BTreeSet;
+use std::sync::Arc;
 use std::time::Duration;
 
 use assert_matches::assert_matches;
@@ -11,19 +13,23 @@
 use fedimint_core::module::{AmountUnit, Amounts};
 use fedimint_core::task::sleep_in_test;
 use fedimint_core::util::NextOrPending;
-use fedimint_core::{Amount, TieredMulti, sats, secp256k1};
+use fedimint_core::{Amount, PeerId, TieredCounts, TieredMulti, sats, secp256k1};
 use fedimint_dummy_client::{DummyClientInit, DummyClientModule};
 use fedimint_dummy_server::DummyInit;
 use fedimint_logging::LOG_TEST;
 use fedimint_mint_client::api::MintFederationApi;
 use fedimint_mint_client::client_db::{NextECashNoteIndexKey, NoteKey};
+use fedimint_mint_client::strategy::{NoteSelectionStrategy, NoteSelectionStrategyConfig};
 use fedimint_mint_client::{
-    MintClientInit, MintClientModule, Note, OOBNotes, ReissueExternalNotesState,
-    SelectNotesWithAtleastAmount, SelectNotesWithExactAmount, SpendOOBState,
-    SpendableNoteUndecoded,
+    InsufficientBalanceError, MintClientInit, MintClientModule, Note, OOBNotes,
+    ReissueExternalNotesState, SelectNotesWithAtleastAmount, SelectNotesWithExactAmount,
+    SpendOOBState, SpendableNoteUndecoded,
 };
+use fedimint_mint_common::condition::{SpendingCondition, SpendingWitness};
+use fedimint_mint_common::config::FeeConsensus;
 use fedimint_mint_common::{MintInput, MintInputV0, Nonce};
 use fedimint_mint_server::MintInit;
+use fedimint_testing::faults::{Fault, FaultSchedule};
 use fedimint_testing::fixtures::{Fixtures, TIMEOUT};
 use futures::StreamExt;
 use secp256k1::Keypair;
@@ -155,6 +161,281 @@ async fn sends_ecash_out_of_band() -> anyhow::Result<()> {
     Ok(())
 }
 
+#[tokio::test(flavor = "multi_thread")]
+async fn ecash_transfer_converges_under_network_faults() -> anyhow::Result<()> {
+    let fed = fixtures().new_fed_not_degraded().await;
+    let (client1, client2) = fed.two_clients().await;
+    issue_ecash(&client1, sats(1000)).await?;
+
+    let session_count = fed.session_count(PeerId::from(0)).await;
+
+    let peers = |peers: &[u16]| {
+        peers
+            .iter()
+            .copied()
+            .map(PeerId::from)
+            .collect::<BTreeSet<_>>()
+    };
+
+    // Every fault leaves a threshold of guardians able to reach consensus, such
+    // that the schedule advances with the sessions they complete
+    let schedule = FaultSchedule::new()
+        .at(
+            0,
+            Fault::Latency {
+                latency: Duration::from_millis(20),
+                jitter: Duration::from_millis(50),
+            },
+        )
+        .at(0, Fault::DropRate(0.05))
+        .at(0, Fault::Partition(vec![peers(&[0, 1, 2]), peers(&[3])]))
+        .at(1, Fault::Heal)
+        .at(1, Fault::IsolateFromClients(2))
+        .at(1, Fault::Crash(PeerId::from(1)))
+        .at(2, Fault::Restart(PeerId::from(1)))
+        .at(3, Fault::Heal);
+
+    let transfer = async {
+        let client1_mint = client1.get_first_module::<MintClientModule>()?;
+        let client2_mint = client2.get_first_module::<MintClientModule>()?;
+
+        let (_, notes) = client1_mint
+            .spend_notes_with_selector(&SelectNotesWithAtleastAmount, sats(750), TIMEOUT, false, ())
+            .await?;
+
+        let op = client2_mint.reissue_external_notes(notes, ()).await?;
+        let mut sub = client2_mint
+            .subscribe_reissue_external_notes(op)
+            .await?
+            .into_stream();
+
+        assert_eq!(sub.ok().await?, ReissueExternalNotesState::Created);
+        assert_eq!(sub.ok().await?, ReissueExternalNotesState::Issuing);
+        assert_eq!(sub.ok().await?, ReissueExternalNotesState::Done);
+
+        anyhow::Ok(())
+    };
+
+    let ((), transfer) = tokio::join!(fed.run_fault_schedule(schedule), transfer);
+    transfer?;
+
+    // All guardians, including the restarted one, keep completing sessions
+    fed.await_session_count(session_count + 4).await;
+
+    assert!(client1.get_balance_for_btc().await? >= sats(250).saturating_sub(EXPECTED_MAXIMUM_FEE));
+    assert!(client2.get_balance_for_btc().await? >= sats(750).saturating_sub(EXPECTED_MAXIMUM_FEE));
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn offline_receives_are_reissued_in_background() -> anyhow::Result<()> {
+    let fed = fixtures().new_fed_degraded().await;
+    let (client1, client2) = fed.two_clients().await;
+    issue_ecash(&client1, sats(2000)).await?;
+
+    let client1_mint = client1.get_first_module::<MintClientModule>()?;
+    let client2_mint = client2.get_first_module::<MintClientModule>()?;
+
+    // The payer reclaims these notes before we can reissue them
+    let (op, double_spent_notes) = client1_mint
+        .spend_notes_with_selector(&SelectNotesWithAtleastAmount, sats(500), TIMEOUT, false, ())
+        .await?;
+    let sub = &mut client1_mint.subscribe_spend_notes(op).await?.into_stream();
+    assert_eq!(sub.ok().await?, SpendOOBState::Created);
+    client1_mint.try_cancel_spend_notes(op).await;
+    assert_eq!(sub.ok().await?, SpendOOBState::UserCanceledProcessing);
+    assert_eq!(sub.ok().await?, SpendOOBState::UserCanceledSuccess);
+
+    let (_, notes) = client1_mint
+        .spend_notes_with_selector(&SelectNotesWithAtleastAmount, sats(750), TIMEOUT, false, ())
+        .await?;
+
+    let receipt = client2_mint
+        .receive_offline(notes.clone(), Some("alice".to_string()))
+        .await?;
+    assert_eq!(receipt.amount, notes.total_amount());
+
+    assert!(
+        client2_mint
+            .receive_offline(notes.clone(), Some("alice".to_string()))
+            .await
+            .is_err(),
+        "Receiving the same notes twice must fail"
+    );
+
+    client2_mint
+        .receive_offline(double_spent_notes.clone(), Some("mallory".to_string()))
+        .await?;
+
+    for _ in 0..120 {
+        let summary = client2_mint.get_offline_receive_summary().await;
+
+        if summary.iter().all(|payer| payer.pending_receives == 0) {
+            let alice = summary
+                .iter()
+                .find(|payer| payer.payer.as_deref() == Some("alice"))
+                .expect("Alice paid us");
+            assert_eq!(alice.confirmed_amount, notes.total_amount());
+            assert_eq!(alice.confirmed_receives, 1);
+            assert_eq!(alice.failed_receives, 0);
+
+            let mallory = summary
+                .iter()
+                .find(|payer| payer.payer.as_deref() == Some("mallory"))
+                .expect("Mallory paid us");
+            assert_eq!(mallory.failed_amount, double_spent_notes.total_amount());
+            assert_eq!(mallory.failed_receives, 1);
+            assert_eq!(mallory.confirmed_receives, 0);
+
+            assert!(
+                client2.get_balance_for_btc().await?
+                    >= sats(750).saturating_sub(EXPECTED_MAXIMUM_FEE)
+            );
+
+            return Ok(());
+        }
+
+        sleep_in_test("waiting for offline receives", Duration::from_millis(500)).await;
+    }
+
+    panic!("Offline receives were not reissued in time");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn conditional_notes_enforce_spending_conditions() -> anyhow::Result<()> {
+    let fed = fixtures().new_fed_degraded().await;
+    let (client1, client2) = fed.two_clients().await;
+    issue_ecash(&client1, sats(3000)).await?;
+
+    let client1_mint = client1.get_first_module::<MintClientModule>()?;
+    let client2_mint = client2.get_first_module::<MintClientModule>()?;
+
+    let receiver = Keypair::new(secp256k1::SECP256K1, &mut rand::thread_rng());
+    let sender = Keypair::new(secp256k1::SECP256K1, &mut rand::thread_rng());
+
+    let condition = SpendingCondition::TimeLock {
+        pubkey: receiver.public_key(),
+        locktime: u64::MAX,
+        refund_pubkey: sender.public_key(),
+    };
+
+    let op = client1_mint
+        .send_conditional_notes(sats(1000), condition, ())
+        .await?;
+    let notes = client1_mint.await_conditional_notes(op).await?;
+    assert_eq!(notes.total_amount(), sats(1000));
+
+    // The refund is rejected by the federation while the time lock holds
+    let op = client1_mint
+        .redeem_conditional_notes(
+            notes.clone(),
+            SpendingWitness::Refund,
+            vec![sender.secret_key()],
+            (),
+        )
+        .await?;
+    assert_matches!(
+        client1_mint
+            .subscribe_reissue_external_notes(op)
+            .await?
+            .await_outcome()
+            .await,
+        Some(ReissueExternalNotesState::Failed(_))
+    );
+
+    assert!(
+        client2_mint
+            .redeem_conditional_notes(
+                notes.clone(),
+                SpendingWitness::Key,
+                vec![sender.secret_key()],
+                (),
+            )
+            .await
+            .is_err(),
+        "Redeeming with the wrong key must fail"
+    );
+
+    let op = client2_mint
+        .redeem_conditional_notes(notes, SpendingWitness::Key, vec![receiver.secret_key()], ())
+        .await?;
+    assert_eq!(
+        client2_mint
+            .subscribe_reissue_external_notes(op)
+            .await?
+            .await_outcome()
+            .await,
+        Some(ReissueExternalNotesState::Done)
+    );
+    assert!(
+        client2.get_balance_for_btc().await? >= sats(1000).saturating_sub(EXPECTED_MAXIMUM_FEE)
+    );
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn multisig_notes_are_redeemed_with_threshold_of_delegations() -> anyhow::Result<()> {
+    let fed = fixtures().new_fed_degraded().await;
+    let (client1, client2) = fed.two_clients().await;
+    issue_ecash(&client1, sats(3000)).await?;
+
+    let client1_mint = client1.get_first_module::<MintClientModule>()?;
+    let client2_mint = client2.get_first_module::<MintClientModule>()?;
+
+    let signers = (0..3)
+        .map(|_| Keypair::new(secp256k1::SECP256K1, &mut rand::thread_rng()))
+        .collect::<Vec<Keypair>>();
+    let delegate = Keypair::new(secp256k1::SECP256K1, &mut rand::thread_rng());
+
+    let condition = SpendingCondition::Multisig {
+        threshold: 2,
+        pubkeys: signers.iter().map(Keypair::public_key).collect(),
+    };
+
+    let op = client1_mint
+        .send_conditional_notes(sats(1000), condition, ())
+        .await?;
+    let notes = client1_mint.await_conditional_notes(op).await?;
+
+    // Every signer delegates with their own key only
+    let delegations = [&signers[0], &signers[2]]
+        .into_iter()
+        .map(|signer| notes.delegate(delegate.public_key(), signer))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    assert!(
+        client2_mint
+            .redeem_multisig_notes(
+                notes.clone(),
+                delegations[..1].to_vec(),
+                delegate.secret_key(),
+                ()
+            )
+            .await
+            .is_err(),
+        "A single delegation does not reach the threshold"
+    );
+
+    let op = client2_mint
+        .redeem_multisig_notes(notes, delegations, delegate.secret_key(), ())
+        .await?;
+    assert_eq!(
+        client2_mint
+            .subscribe_reissue_external_notes(op)
+            .await?
+            .await_outcome()
+            .await,
+        Some(ReissueExternalNotesState::Done)
+    );
+    assert!(
+        client2.get_balance_for_btc().await? >= sats(1000).saturating_sub(EXPECTED_MAXIMUM_FEE)
+    );
+
+    Ok(())
+}
+
 #[tokio::test(flavor = "multi_thread")]
 async fn blind_nonce_index() -> anyhow::Result<()> {
     // Give client initial balance
@@ -202,6 +483,188 @@ async fn blind_nonce_index() -> anyhow::Result<()> {
     Ok(())
 }
 
+#[tokio::test(flavor = "multi_thread")]
+async fn note_selection_strategy_is_persisted_and_applied() -> anyhow::Result<()> {
+    let fed = fixtures().new_fed_degraded().await;
+    let client = fed.new_client().await;
+    let client_mint = client.get_first_module::<MintClientModule>()?;
+
+    assert_eq!(
+        client_mint.get_note_selection_strategy().await,
+        NoteSelectionStrategyConfig::default()
+    );
+
+    client_mint
+        .set_note_selection_strategy(NoteSelectionStrategyConfig::MinimalNotes)
+        .await;
+
+    assert_eq!(
+        client_mint.get_note_selection_strategy().await,
+        NoteSelectionStrategyConfig::MinimalNotes
+    );
+
+    issue_ecash(&client, sats(1000)).await?;
+
+    let counts = client_mint
+        .get_note_counts_by_denomination(&mut client_mint.db.begin_transaction_nc().await)
+        .await;
+
+    assert!(!counts.is_empty());
+    assert!(
+        counts.iter().all(|(_, count)| count == 1),
+        "Expected at most one note per denomination, got {counts:?}"
+    );
+
+    Ok(())
+}
+
+/// Selects far more notes of the smallest denomination than the client holds
+#[derive(Debug)]
+struct OverspendingStrategy;
+
+impl NoteSelectionStrategy for OverspendingStrategy {
+    fn represent_amount(
+        &self,
+        amount: Amount,
+        held: &TieredCounts,
+        tiers: &[Amount],
+        fee_consensus: &FeeConsensus,
+    ) -> TieredCounts {
+        NoteSelectionStrategyConfig::default()
+            .to_strategy()
+            .represent_amount(amount, held, tiers, fee_consensus)
+    }
+
+    fn select_inputs(
+        &self,
+        _amount: Amount,
+        _held: &TieredCounts,
+        tiers: &[Amount],
+        _fee_consensus: &FeeConsensus,
+    ) -> Result<TieredCounts, InsufficientBalanceError> {
+        Ok(std::iter::once((tiers[0], 1000)).collect())
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn strategy_selecting_notes_we_do_not_hold_fails_with_insufficient_balance()
+-> anyhow::Result<()> {
+    let fed = fixtures().new_fed_degraded().await;
+    let client = fed.new_client().await;
+    let client_mint = client.get_first_module::<MintClientModule>()?;
+
+    issue_ecash(&client, sats(1000)).await?;
+
+    let balance = client.get_balance_for_btc().await?;
+
+    client_mint.set_custom_note_selection_strategy(Some(Arc::new(OverspendingStrategy)));
+
+    let operation_id = OperationId::new_random();
+    let mut dbtx = client_mint.db.begin_transaction().await;
+    let issuance_req = client_mint
+        .create_output(
+            &mut dbtx.to_ref_nc(),
+            operation_id,
+            1,
+            Amount::from_sats(100),
+        )
+        .await;
+    dbtx.commit_tx().await;
+
+    let tx = TransactionBuilder::new().with_outputs(client_mint.client_ctx.make_dyn(issuance_req));
+
+    let error = client_mint
+        .client_ctx
+        .finalize_and_submit_transaction(operation_id, "mint", |_| (), tx)
+        .await
+        .expect_err("Funding with notes we don't hold must fail");
+
+    assert!(
+        format!("{error:#}").contains("Insufficient balance"),
+        "Unexpected error: {error:#}"
+    );
+
+    client_mint.set_custom_note_selection_strategy(None);
+
+    assert_eq!(client.get_balance_for_btc().await?, balance);
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn issued_note_is_included_in_liabilities() -> anyhow::Result<()> {
+    let fed = fixtures().new_fed_degraded().await;
+    let client = fed.new_client().await;
+    issue_ecash(&client, sats(1000)).await?;
+
+    let client_mint = client.get_first_module::<MintClientModule>()?;
+
+    let mut dbtx = client_mint.db.begin_transaction().await;
+    let operation_id = OperationId::new_random();
+    let issuance_req = client_mint
+        .create_output(&mut dbtx.to_ref_nc(), operation_id, 1, Amount::from_sats(1))
+        .await;
+    dbtx.commit_tx().await;
+
+    let output = issuance_req
+        .outputs()
+        .first()
+        .expect("There should be at least one note in here")
+        .output
+        .ensure_v0_ref()?
+        .clone();
+
+    let tx = TransactionBuilder::new().with_outputs(client_mint.client_ctx.make_dyn(issuance_req));
+
+    let change_range = client_mint
+        .client_ctx
+        .finalize_and_submit_transaction(operation_id, "mint", |_| (), tx)
+        .await?;
+
+    client.api().await_transaction(change_range.txid()).await;
+
+    // The note can only be verified once the federation committed to a root
+    // that includes it, which happens in one of the following sessions
+    let root = loop {
+        if let Ok(root) = client_mint
+            .verify_liability_inclusion(output.blind_nonce, output.amount)
+            .await
+        {
+            break root;
+        }
+
+        sleep_in_test(
+            "Waiting for the liabilities to be committed",
+            Duration::from_millis(100),
+        )
+        .await;
+    };
+
+    assert!(root.outstanding().expect("No overflow") >= sats(1000));
+
+    // Later commitments account for at least the same issuances and
+    // redemptions
+    let latest_root = client_mint
+        .client_ctx
+        .module_api()
+        .fetch_liability_root()
+        .await?
+        .expect("Liabilities have been committed");
+
+    assert!(latest_root.is_well_formed());
+    assert!(latest_root.events >= root.events);
+
+    assert!(
+        client_mint
+            .verify_liability_inclusion(output.blind_nonce, output.amount + Amount::from_sats(1))
+            .await
+            .is_err(),
+        "Inflated note amount should not verify"
+    );
+
+    Ok(())
+}
+
 #[tokio::test(flavor = "multi_thread")]
 #[ignore] // TODO: flaky https://github.com/fedimint/fedimint/issues/4508
 async fn sends_ecash_oob_highly_parallel() -> anyhow::Result<()> {
@@ -802,8 +1265,9 @@ mod fedimint_migration_tests {
     use fedimint_mint_client::{MintClientInit, MintClientModule, NoteIndex, SpendableNote};
     use fedimint_mint_common::{MintCommonInit, MintOutputOutcome, Nonce};
     use fedimint_mint_server::db::{
-        DbKeyPrefix, MintAuditItemKey, MintAuditItemKeyPrefix, MintOutputOutcomeKey,
-        MintOutputOutcomePrefix, NonceKey, NonceKeyPrefix,
+        DbKeyPrefix, LiabilityEventKeyPrefix, LiabilityLeafKeyPrefix, LiabilityTierKeyPrefix,
+        MintAuditItemKey, MintAuditItemKeyPrefix, MintOutputOutcomeKey, MintOutputOutcomePrefix,
+        NonceKey, NonceKeyPrefix,
     };
     use fedimint_server::core::DynServerModuleInit;
     use fedimint_testing::db::{
@@ -1039,6 +1503,59 @@ async fn test_server_db_migrations() -> anyhow::Result<()> {
                         // New prefix for slice-based recovery, no migration
                         // needed
                     }
+                    DbKeyPrefix::LiabilityTier => {
+                        // The liabilities are replayed from the module history,
+                        // so every issuance has a leaf unless a blind nonce was
+                        // reused, and every issuance and redemption is logged
+                        // until the first liability root is committed
+                        let tiers = dbtx
+                            .find_by_prefix(&LiabilityTierKeyPrefix)
+                            .await
+                            .map(|(_, tier)| tier)
+                            .collect::<Vec<_>>()
+                            .await;
+                        let num_leaves = dbtx
+                            .find_by_prefix(&LiabilityLeafKeyPrefix)
+                            .await
+                            .count()
+                            .await as u64;
+                        let num_events = dbtx
+                            .find_by_prefix(&LiabilityEventKeyPrefix)
+                            .await
+                            .count()
+                            .await as u64;
+                        ensure!(
+                            num_leaves <= tiers.iter().map(|tier| tier.issued).sum::<u64>(),
+                            "validate_migrations found more liability leaves than issuances"
+                        );
+                        ensure!(
+                            num_events
+                                == tiers
+                                    .iter()
+                                    .map(|tier| tier.issued + tier.redeemed)
+                                    .sum::<u64>(),
+                            "validate_migrations found an incomplete liability event log"
+                        );
+                        info!("Validated LiabilityTier");
+                    }
+                    DbKeyPrefix::LiabilityNode
+                    | DbKeyPrefix::LiabilityLeaf
+                    | DbKeyPrefix::LiabilityEvent => {
+                        // Validated together with the liability tiers
+                    }
+                    DbKeyPrefix::CommittedLiabilityRoot => {
+                        // Liability roots are only committed in consensus after
+                        // the upgrade
+                    }
+                    DbKeyPrefix::UnixTimeVote | DbKeyPrefix::ConsensusVersionVote => {
+                        // New prefixes for the time locks of spending
+                        // conditions and the consensus version upgrade, votes
+                        // are only cast after the upgrade
+                    }
+                    DbKeyPrefix::PendingBlindSignature => {
+                        // Outputs are only pending until the guardian signer
+                        // created our blind signature share
+                    }
                 }
             }
 
@@ -1126,6 +1643,11 @@ async fn test_client_db_migrations() -> anyhow::Result<()> {
                             info!("Validated RecoveryFinalized");
                         }
                         fedimint_mint_client::client_db::DbKeyPrefix::ReusedNoteIndices => {}
+                        fedimint_mint_client::client_db::DbKeyPrefix::NoteSelectionStrategy => {}
+                        fedimint_mint_client::client_db::DbKeyPrefix::UnconfirmedReceive => {}
+                        fedimint_mint_client::client_db::DbKeyPrefix::OfflineReceivedNonce => {}
+                        fedimint_mint_client::client_db::DbKeyPrefix::OfflineReceivePayerStats => {}
+                        fedimint_mint_client::client_db::DbKeyPrefix::ConditionalIssuance => {}
                         fedimint_mint_client::client_db::DbKeyPrefix::RecoveryStateV2 => {
                             // New prefix for slice-based recovery, no migration
                             // needed
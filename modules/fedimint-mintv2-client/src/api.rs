@@ -2,11 +2,13 @@
 use std::time::Duration;
 
 use bitcoin_hashes::sha256;
-use fedimint_api_client::api::{DynModuleApi, FederationApiExt, ServerError};
+use fedimint_api_client::api::{DynModuleApi, FederationApiExt, FederationResult, ServerError};
 use fedimint_api_client::query::FilterMapThreshold;
-use fedimint_core::module::ApiRequestErased;
+use fedimint_core::module::liabilities::{LiabilityProof, LiabilityRoot};
+use fedimint_core::module::{ApiRequestErased, ModuleConsensusVersion};
 use fedimint_core::{NumPeersExt, OutPointRange, PeerId};
 use fedimint_mintv2_common::endpoint_constants::{
+    LIABILITY_PROOF_ENDPOINT, LIABILITY_ROOT_ENDPOINT, MODULE_CONSENSUS_VERSION_ENDPOINT,
     RECOVERY_COUNT_ENDPOINT, RECOVERY_SLICE_ENDPOINT, RECOVERY_SLICE_HASH_ENDPOINT,
     SIGNATURE_SHARES_ENDPOINT, SIGNATURE_SHARES_RECOVERY_ENDPOINT,
 };
@@ -21,7 +23,7 @@ pub trait MintV2ModuleApi {
     async fn fetch_signature_shares(
         &self,
         range: OutPointRange,
-        issuance_requests: Vec<NoteIssuanceRequest>,
+        blinded_messages: Vec<(Denomination, BlindedMessage)>,
         tbs_pks: BTreeMap<Denomination, BTreeMap<PeerId, PublicKeyShare>>,
     ) -> BTreeMap<PeerId, Vec<BlindedSignatureShare>>;
 
@@ -42,6 +44,15 @@ async fn fetch_recovery_slice(
         start: u64,
         end: u64,
     ) -> anyhow::Result<Vec<RecoveryItem>>;
+
+    async fn fetch_liability_root(&self) -> FederationResult<Option<LiabilityRoot>>;
+
+    async fn fetch_module_consensus_version(&self) -> FederationResult<ModuleConsensusVersion>;
+
+    async fn fetch_liability_proof(
+        &self,
+        message: BlindedMessage,
+    ) -> FederationResult<Option<(LiabilityRoot, LiabilityProof)>>;
 }
 
 #[async_trait::async_trait]
@@ -49,14 +60,14 @@ impl MintV2ModuleApi for DynModuleApi {
     async fn fetch_signature_shares(
         &self,
         range: OutPointRange,
-        issuance_requests: Vec<NoteIssuanceRequest>,
+        blinded_messages: Vec<(Denomination, BlindedMessage)>,
         tbs_pks: BTreeMap<Denomination, BTreeMap<PeerId, PublicKeyShare>>,
     ) -> BTreeMap<PeerId, Vec<BlindedSignatureShare>> {
         self.request_with_strategy_retry(
             // This query collects a threshold of 2f + 1 valid blind signature shares
             FilterMapThreshold::new(
                 move |peer, signature_shares| {
-                    verify_blind_shares(peer, signature_shares, &issuance_requests, &tbs_pks)
+                    verify_blind_shares(peer, signature_shares, &blinded_messages, &tbs_pks)
                         .map_err(ServerError::InvalidResponse)
                 },
                 self.all_peers().to_num_peers(),
@@ -72,22 +83,26 @@ async fn fetch_signature_shares_recovery(
         issuance_requests: Vec<NoteIssuanceRequest>,
         tbs_pks: BTreeMap<Denomination, BTreeMap<PeerId, PublicKeyShare>>,
     ) -> BTreeMap<PeerId, Vec<BlindedSignatureShare>> {
-        let blinded_messages: Vec<BlindedMessage> = issuance_requests
+        let blinded_messages: Vec<(Denomination, BlindedMessage)> = issuance_requests
             .iter()
-            .map(NoteIssuanceRequest::blinded_message)
+            .map(|request| (request.denomination, request.blinded_message()))
+            .collect();
+        let messages: Vec<BlindedMessage> = blinded_messages
+            .iter()
+            .map(|(_, message)| *message)
             .collect();
 
         self.request_with_strategy_retry(
             // This query collects a threshold of 2f + 1 valid blind signature shares
             FilterMapThreshold::new(
                 move |peer, signature_shares| {
-                    verify_blind_shares(peer, signature_shares, &issuance_requests, &tbs_pks)
+                    verify_blind_shares(peer, signature_shares, &blinded_messages, &tbs_pks)
                         .map_err(ServerError::InvalidResponse)
                 },
                 self.all_peers().to_num_peers(),
             ),
             SIGNATURE_SHARES_RECOVERY_ENDPOINT.to_owned(),
-            ApiRequestErased::new(blinded_messages),
+            ApiRequestErased::new(messages),
         )
         .await
     }
@@ -128,4 +143,31 @@ async fn fetch_recovery_slice(
 
         Ok(result)
     }
+
+    async fn fetch_liability_root(&self) -> FederationResult<Option<LiabilityRoot>> {
+        self.request_current_consensus(
+            LIABILITY_ROOT_ENDPOINT.to_string(),
+            ApiRequestErased::default(),
+        )
+        .await
+    }
+
+    async fn fetch_module_consensus_version(&self) -> FederationResult<ModuleConsensusVersion> {
+        self.request_current_consensus(
+            MODULE_CONSENSUS_VERSION_ENDPOINT.to_string(),
+            ApiRequestErased::default(),
+        )
+        .await
+    }
+
+    async fn fetch_liability_proof(
+        &self,
+        message: BlindedMessage,
+    ) -> FederationResult<Option<(LiabilityRoot, LiabilityProof)>> {
+        self.request_current_consensus(
+            LIABILITY_PROOF_ENDPOINT.to_string(),
+            ApiRequestErased::new(message),
+        )
+        .await
+    }
 }
@@ -1,12 +1,19 @@
 use std::{ffi, iter};
 
-use clap::Parser;
+use anyhow::bail;
+use clap::{Parser, Subcommand};
 use fedimint_core::Amount;
 use fedimint_core::base32::{self, FEDIMINT_PREFIX};
+use fedimint_core::bitcoin::hashes::sha256;
+use fedimint_core::secp256k1::{Keypair, PublicKey, SECP256K1, SecretKey};
+use fedimint_mintv2_common::condition::{SpendingCondition, SpendingWitness};
+use hex::FromHex as _;
 use serde::Serialize;
-use serde_json::Value;
+use serde_json::{Value, json};
 
-use crate::MintClientModule;
+use crate::conditional::{ConditionalNotes, MultisigDelegation};
+use crate::strategy::NoteSelectionStrategyConfig;
+use crate::{FinalReceiveOperationState, MintClientModule};
 
 #[derive(Parser, Serialize)]
 enum Opts {
@@ -16,6 +23,118 @@ enum Opts {
     Send { amount: Amount },
     /// Receive the `ECash` by reissuing the notes and return the amount.
     Receive { ecash: String },
+    /// Show the strategy used to pick the denominations of issued and spent
+    /// notes, or persist a new one if given.
+    NoteStrategy {
+        #[command(subcommand)]
+        strategy: Option<NoteStrategyOpts>,
+    },
+    /// Send `ECash` bound to a spending condition for the given amount.
+    SendConditional {
+        amount: Amount,
+        #[command(subcommand)]
+        condition: ConditionOpts,
+    },
+    /// Delegate `ECash` bound to a multisig to the key of the redeemer as one
+    /// of its signers.
+    DelegateConditional {
+        notes: ConditionalNotes,
+        /// Key the `ECash` is delegated to
+        delegate: PublicKey,
+        /// Our secret key of the multisig
+        secret_key: SecretKey,
+    },
+    /// Redeem `ECash` bound to a spending condition and return the amount.
+    RedeemConditional {
+        notes: ConditionalNotes,
+        /// Secret keys satisfying the condition, for a multisig the key the
+        /// `ECash` was delegated to
+        #[clap(long = "secret-key", required = true)]
+        secret_keys: Vec<SecretKey>,
+        /// Preimage of a hash lock as hex
+        #[clap(long, value_parser = parse_preimage, conflicts_with_all = ["refund", "delegations"])]
+        preimage: Option<[u8; 32]>,
+        /// Refund an expired time lock
+        #[clap(long, conflicts_with = "delegations")]
+        refund: bool,
+        /// Delegations of a threshold of the multisig signers
+        #[clap(long = "delegation")]
+        delegations: Vec<MultisigDelegation>,
+    },
+}
+
+#[derive(Subcommand, Serialize)]
+enum ConditionOpts {
+    /// Spendable by the owner of the key.
+    Pubkey { pubkey: PublicKey },
+    /// Spendable by the owner of the key revealing the preimage of the hash.
+    HashLock {
+        hash: sha256::Hash,
+        pubkey: PublicKey,
+    },
+    /// Spendable by the owner of the key and, once the federation's unix time
+    /// reached the locktime, by the owner of the refund key.
+    TimeLock {
+        pubkey: PublicKey,
+        locktime: u64,
+        refund_pubkey: PublicKey,
+    },
+    /// Spendable by any key that a threshold of the keys delegated to.
+    Multisig {
+        threshold: u16,
+        #[clap(required = true)]
+        pubkeys: Vec<PublicKey>,
+    },
+}
+
+impl From<ConditionOpts> for SpendingCondition {
+    fn from(opts: ConditionOpts) -> Self {
+        match opts {
+            ConditionOpts::Pubkey { pubkey } => Self::PublicKey(pubkey),
+            ConditionOpts::HashLock { hash, pubkey } => Self::HashLock { hash, pubkey },
+            ConditionOpts::TimeLock {
+                pubkey,
+                locktime,
+                refund_pubkey,
+            } => Self::TimeLock {
+                pubkey,
+                locktime,
+                refund_pubkey,
+            },
+            ConditionOpts::Multisig { threshold, pubkeys } => Self::Multisig { threshold, pubkeys },
+        }
+    }
+}
+
+fn parse_preimage(s: &str) -> anyhow::Result<[u8; 32]> {
+    Ok(<[u8; 32]>::from_hex(s)?)
+}
+
+#[derive(Subcommand, Serialize)]
+enum NoteStrategyOpts {
+    /// Hold a number of notes of every denomination to send without reissuing.
+    KeepNotesPerTier { notes_per_tier: u16 },
+    /// Hold as few notes as possible.
+    MinimalNotes,
+    /// Never hold notes above a maximum denomination.
+    StandardAmounts { max_denomination: Amount },
+    /// Pay as little fees as possible.
+    FeeMinimizing,
+}
+
+impl From<NoteStrategyOpts> for NoteSelectionStrategyConfig {
+    fn from(opts: NoteStrategyOpts) -> Self {
+        match opts {
+            NoteStrategyOpts::KeepNotesPerTier { notes_per_tier } => {
+                Self::KeepNotesPerTier { notes_per_tier }
+            }
+            NoteStrategyOpts::MinimalNotes => Self::MinimalNotes,
+            NoteStrategyOpts::StandardAmounts { max_denomination } => {
+                Self::StandardAmounts { max_denomination }
+            }
+            NoteStrategyOpts::FeeMinimizing => Self::FeeMinimizing,
+        }
+    }
 }
 
 pub(crate) async fn handle_cli_command(
@@ -43,6 +162,75 @@ pub(crate) async fn handle_cli_command(
 
             Ok(json(state))
         }
+        Opts::NoteStrategy { strategy } => {
+            if let Some(strategy) = strategy {
+                mint.set_note_selection_strategy(strategy.into()).await;
+            }
+
+            Ok(json(mint.get_note_selection_strategy().await))
+        }
+        Opts::SendConditional { amount, condition } => {
+            let operation_id = mint
+                .send_conditional_notes(amount, condition.into(), Value::Null)
+                .await?;
+
+            let notes = mint.await_conditional_notes(operation_id).await?;
+
+            Ok(json!({
+                "operation_id": operation_id,
+                "notes": notes,
+            }))
+        }
+        Opts::DelegateConditional {
+            notes,
+            delegate,
+            secret_key,
+        } => {
+            let delegation =
+                notes.delegate(delegate, &Keypair::from_secret_key(SECP256K1, &secret_key))?;
+
+            Ok(json!({
+                "delegation": delegation,
+            }))
+        }
+        Opts::RedeemConditional {
+            notes,
+            secret_keys,
+            preimage,
+            refund,
+            delegations,
+        } => {
+            let amount = notes.total_amount();
+
+            let operation_id = match (preimage, refund) {
+                (None, false) if !delegations.is_empty() => {
+                    let [delegate_key] = secret_keys[..] else {
+                        bail!("Multisig ECash is redeemed with the single delegate key");
+                    };
+
+                    mint.redeem_multisig_notes(notes, delegations, delegate_key, Value::Null)
+                        .await?
+                }
+                (preimage, refund) => {
+                    let witness = match (preimage, refund) {
+                        (Some(preimage), _) => SpendingWitness::Preimage(preimage),
+                        (None, true) => SpendingWitness::Refund,
+                        (None, false) => SpendingWitness::Key,
+                    };
+
+                    mint.redeem_conditional_notes(notes, witness, secret_keys, Value::Null)
+                        .await?
+                }
+            };
+
+            if mint.await_final_receive_operation_state(operation_id).await
+                == FinalReceiveOperationState::Rejected
+            {
+                bail!("Redeeming the conditional ECash was rejected");
+            }
+
+            Ok(json(amount))
+        }
     }
 }
 
@@ -1,6 +1,7 @@
 use std::collections::{BTreeMap, BTreeSet};
 
 use bitcoin_hashes::hash160;
+use fedimint_core::core::OperationId;
 use fedimint_core::encoding::{Decodable, Encodable};
 use fedimint_core::{impl_db_lookup, impl_db_record};
 use fedimint_mintv2_common::Denomination;
@@ -8,13 +9,17 @@
 use strum_macros::EnumIter;
 
 use crate::SpendableNote;
+use crate::conditional::ConditionalIssuance;
 use crate::issuance::NoteIssuanceRequest;
+use crate::strategy::NoteSelectionStrategyConfig;
 
 #[repr(u8)]
 #[derive(Clone, Display, EnumIter, Debug)]
 pub enum DbKeyPrefix {
     Note = 0x20,
     RecoveryState = 0x21,
+    NoteSelectionStrategy = 0x22,
+    ConditionalIssuance = 0x23,
 }
 
 #[derive(Debug, Clone, Encodable, Decodable)]
@@ -62,3 +67,23 @@ pub struct RecoveryState {
     value = RecoveryState,
     db_prefix = DbKeyPrefix::RecoveryState,
 );
+
+/// The note selection strategy chosen for this client
+#[derive(Debug, Clone, Encodable, Decodable)]
+pub struct NoteSelectionStrategyKey;
+
+impl_db_record!(
+    key = NoteSelectionStrategyKey,
+    value = NoteSelectionStrategyConfig,
+    db_prefix = DbKeyPrefix::NoteSelectionStrategy,
+);
+
+/// Conditional notes we issued, by the operation issuing them
+#[derive(Debug, Clone, Encodable, Decodable)]
+pub struct ConditionalIssuanceKey(pub OperationId);
+
+impl_db_record!(
+    key = ConditionalIssuanceKey,
+    value = ConditionalIssuance,
+    db_prefix = DbKeyPrefix::ConditionalIssuance,
+);
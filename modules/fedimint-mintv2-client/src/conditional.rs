@@ -0,0 +1,291 @@
+//! Sending and redeeming e-cash bound to spending conditions
+//!
+//! Conditional notes are issued by a transaction funded from our wallet whose
+//! outputs are blinded nonces committing to a [`SpendingCondition`]. We don't
+//! hold a spend key for these notes, so they are not tracked by the output
+//! state machines. Instead the issuance is persisted until the federation
+//! signed the notes, so they can still be fetched after a restart.
+
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use anyhow::{anyhow, bail, ensure};
+use fedimint_core::base32::{self, FEDIMINT_PREFIX};
+use fedimint_core::config::FederationId;
+use fedimint_core::encoding::{Decodable, Encodable};
+use fedimint_core::secp256k1::rand::RngCore;
+use fedimint_core::secp256k1::{Keypair, PublicKey, SECP256K1, SecretKey, schnorr};
+use fedimint_core::{Amount, OutPointRange};
+use fedimint_mintv2_common::condition::{
+    SpendingCondition, SpendingWitness, sign_multisig_delegation,
+};
+use fedimint_mintv2_common::{Denomination, MintOutput, Note, nonce_message, verify_note};
+use serde::{Deserialize, Serialize};
+use tbs::{
+    AggregatePublicKey, BlindedMessage, BlindedSignature, BlindingKey, blind_message,
+    unblind_signature,
+};
+
+use crate::thread_rng;
+
+/// Keeps the data to unblind the signature of a conditional note once the
+/// federation processed the transaction issuing it
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Encodable, Decodable)]
+pub struct ConditionalNoteRequest {
+    pub denomination: Denomination,
+    pub condition: SpendingCondition,
+    pub salt: [u8; 32],
+    blinding_key: BlindingKey,
+}
+
+impl ConditionalNoteRequest {
+    pub fn new(denomination: Denomination, condition: SpendingCondition) -> Self {
+        let mut salt = [0; 32];
+        thread_rng().fill_bytes(&mut salt);
+
+        ConditionalNoteRequest {
+            denomination,
+            condition,
+            salt,
+            blinding_key: BlindingKey::random(),
+        }
+    }
+
+    /// The tweak is not ground against our recovery filter since we can't
+    /// recover notes we don't hold the spend key for anyways
+    pub fn output(&self) -> MintOutput {
+        MintOutput::new_v0(self.denomination, self.blinded_message(), [0; 16])
+    }
+
+    pub fn blinded_message(&self) -> BlindedMessage {
+        blind_message(
+            nonce_message(self.condition.nonce(&self.salt).0),
+            self.blinding_key,
+        )
+    }
+
+    pub fn finalize(&self, signature: BlindedSignature) -> ConditionalNote {
+        ConditionalNote {
+            note: Note {
+                denomination: self.denomination,
+                nonce: self.condition.nonce(&self.salt).0,
+                signature: unblind_signature(self.blinding_key, signature),
+            },
+            condition: self.condition.clone(),
+            salt: self.salt,
+        }
+    }
+}
+
+/// The issuance of conditional notes by a transaction of ours
+#[derive(Debug, Clone, PartialEq, Eq, Encodable, Decodable)]
+pub struct ConditionalIssuance {
+    pub range: OutPointRange,
+    pub requests: Vec<ConditionalNoteRequest>,
+}
+
+/// A note that can only be spent by satisfying its spending condition
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Encodable, Decodable)]
+pub struct ConditionalNote {
+    pub note: Note,
+    pub condition: SpendingCondition,
+    pub salt: [u8; 32],
+}
+
+impl ConditionalNote {
+    pub fn amount(&self) -> Amount {
+        self.note.amount()
+    }
+
+    /// Checks the signature of the federation and that the note's nonce
+    /// commits to its condition
+    pub fn verify(&self, tbs_agg_pks: &BTreeMap<Denomination, AggregatePublicKey>) -> bool {
+        tbs_agg_pks
+            .get(&self.note.denomination)
+            .is_some_and(|pk| verify_note(self.note, *pk))
+            && self.condition.nonce(&self.salt).0 == self.note.nonce
+    }
+
+    /// Returns the key signing the transaction that spends this note with the
+    /// given witness, picked from the secret keys. For a multisig this is the
+    /// key the signers delegated the note to.
+    pub fn spend_keypair(
+        &self,
+        witness: &SpendingWitness,
+        secret_keys: &[SecretKey],
+    ) -> anyhow::Result<Keypair> {
+        // The federation checks the time lock of a refund against its
+        // consensus unix time
+        let spend_key =
+            self.condition
+                .spend_key(&self.condition.nonce(&self.salt), witness, u64::MAX)?;
+
+        secret_keys
+            .iter()
+            .find(|secret_key| secret_key.public_key(SECP256K1) == spend_key)
+            .map(|secret_key| Keypair::from_secret_key(SECP256K1, secret_key))
+            .ok_or(anyhow!(
+                "The secret keys don't match the spending condition"
+            ))
+    }
+
+    /// Returns the index of our key in the note's multisig condition
+    fn multisig_signer(&self, keypair: &Keypair) -> anyhow::Result<u16> {
+        let SpendingCondition::Multisig { pubkeys, .. } = &self.condition else {
+            bail!("The note is not bound to a multisig");
+        };
+
+        let signer = pubkeys
+            .iter()
+            .position(|pubkey| *pubkey == keypair.public_key())
+            .ok_or(anyhow!("Our key is not a signer of the multisig"))?;
+
+        Ok(u16::try_from(signer).expect("Multisigs have at most 16 keys"))
+    }
+}
+
+/// Conditional notes to transfer out-of-band
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Encodable, Decodable)]
+pub struct ConditionalNotes {
+    pub federation_id: FederationId,
+    pub notes: Vec<ConditionalNote>,
+}
+
+impl ConditionalNotes {
+    pub fn total_amount(&self) -> Amount {
+        self.notes.iter().map(ConditionalNote::amount).sum()
+    }
+
+    /// Delegates the multisig notes to the key `delegate` as one of their
+    /// signers. Every signer delegates independently, the holder of the
+    /// delegate key then redeems the notes once a threshold of signers did
+    /// so, see [`ConditionalNotes::delegation_witnesses`].
+    pub fn delegate(
+        &self,
+        delegate: PublicKey,
+        keypair: &Keypair,
+    ) -> anyhow::Result<MultisigDelegation> {
+        let signatures = self
+            .notes
+            .iter()
+            .map(|note| {
+                Ok((
+                    note.multisig_signer(keypair)?,
+                    sign_multisig_delegation(&note.condition.nonce(&note.salt), &delegate, keypair),
+                ))
+            })
+            .collect::<anyhow::Result<Vec<(u16, schnorr::Signature)>>>()?;
+
+        Ok(MultisigDelegation {
+            delegate,
+            signatures,
+        })
+    }
+
+    /// Combines the delegations of the signers into a witness for every note
+    pub fn delegation_witnesses(
+        &self,
+        delegations: &[MultisigDelegation],
+    ) -> anyhow::Result<Vec<SpendingWitness>> {
+        let delegate = delegations
+            .first()
+            .ok_or(anyhow!("No delegations were given"))?
+            .delegate;
+
+        ensure!(
+            delegations
+                .iter()
+                .all(|delegation| delegation.delegate == delegate
+                    && delegation.signatures.len() == self.notes.len()),
+            "The delegations are not for the same key and notes"
+        );
+
+        (0..self.notes.len())
+            .map(|index| {
+                let signatures = delegations
+                    .iter()
+                    .map(|delegation| delegation.signatures[index])
+                    .collect::<BTreeMap<u16, schnorr::Signature>>();
+
+                ensure!(
+                    signatures.len() == delegations.len(),
+                    "Multiple delegations of the same signer were given"
+                );
+
+                Ok(SpendingWitness::Delegation {
+                    delegate,
+                    signatures: signatures.into_iter().collect(),
+                })
+            })
+            .collect()
+    }
+}
+
+/// A multisig signer's delegation of conditional notes to a key, with one
+/// signature per note in the order of the notes
+#[derive(Debug, Clone, PartialEq, Eq, Encodable, Decodable)]
+pub struct MultisigDelegation {
+    pub delegate: PublicKey,
+    pub signatures: Vec<(u16, schnorr::Signature)>,
+}
+
+impl FromStr for ConditionalNotes {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let notes: ConditionalNotes = base32::decode_prefixed(FEDIMINT_PREFIX, s.trim())?;
+
+        ensure!(!notes.notes.is_empty(), "ConditionalNotes cannot be empty");
+
+        Ok(notes)
+    }
+}
+
+impl Display for ConditionalNotes {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&base32::encode_prefixed(FEDIMINT_PREFIX, self))
+    }
+}
+
+impl FromStr for MultisigDelegation {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(base32::decode_prefixed(FEDIMINT_PREFIX, s.trim())?)
+    }
+}
+
+impl Display for MultisigDelegation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&base32::encode_prefixed(FEDIMINT_PREFIX, self))
+    }
+}
+
+impl Serialize for ConditionalNotes {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for ConditionalNotes {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        FromStr::from_str(&s).map_err(serde::de::Error::custom)
+    }
+}
+
+impl Serialize for MultisigDelegation {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str(&self.to_string())
+    }
+}
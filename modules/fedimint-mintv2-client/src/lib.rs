@@ -13,23 +13,30 @@
 #[cfg(feature = "cli")]
 mod cli;
 mod client_db;
+/// E-cash bound to spending conditions
+pub mod conditional;
 mod ecash;
 mod events;
 mod input;
 pub mod issuance;
 mod output;
 mod receive;
+/// Strategies for choosing the denominations of issued and spent notes
+pub mod strategy;
 
 use std::collections::{BTreeMap, BTreeSet};
 use std::convert::Infallible;
 use std::sync::{Arc, RwLock};
 use std::time::Duration;
 
-use anyhow::{Context as _, anyhow};
+use anyhow::{Context as _, anyhow, ensure};
 use bitcoin_hashes::sha256;
-use client_db::{RecoveryState, RecoveryStateKey, SpendableNoteAmountPrefix, SpendableNotePrefix};
+use client_db::{
+    ConditionalIssuanceKey, NoteSelectionStrategyKey, RecoveryState, RecoveryStateKey,
+    SpendableNoteAmountPrefix, SpendableNotePrefix,
+};
 pub use events::*;
-use fedimint_api_client::api::DynModuleApi;
+use fedimint_api_client::api::{DynModuleApi, FederationResult};
 use fedimint_client::module::ClientModule;
 use fedimint_client::transaction::{
     ClientInput, ClientInputBundle, ClientInputSM, ClientOutput, ClientOutputBundle,
@@ -50,17 +57,20 @@
 use fedimint_core::core::{IntoDynInstance, ModuleInstanceId, ModuleKind, OperationId};
 use fedimint_core::db::{DatabaseTransaction, DatabaseVersion, IDatabaseTransactionOpsCoreTyped};
 use fedimint_core::encoding::{Decodable, Encodable};
+use fedimint_core::module::liabilities::{LiabilityNode, LiabilityRoot};
 use fedimint_core::module::{
     AmountUnit, Amounts, ApiVersion, CommonModuleInit, ModuleCommon, ModuleInit, MultiApiVersion,
 };
 use fedimint_core::secp256k1::rand::{Rng, thread_rng};
-use fedimint_core::secp256k1::{Keypair, PublicKey};
+use fedimint_core::secp256k1::{Keypair, PublicKey, SecretKey};
 use fedimint_core::util::{BoxStream, NextOrPending};
-use fedimint_core::{Amount, OutPoint, PeerId, apply, async_trait_maybe_send};
+use fedimint_core::{Amount, IdxRange, OutPoint, PeerId, apply, async_trait_maybe_send};
 use fedimint_derive_secret::DerivableSecret;
+use fedimint_mintv2_common::condition::{SpendingCondition, SpendingWitness};
 use fedimint_mintv2_common::config::{FeeConsensus, MintClientConfig, client_denominations};
 use fedimint_mintv2_common::{
     Denomination, KIND, MintCommonInit, MintInput, MintModuleTypes, MintOutput, Note, RecoveryItem,
+    SPENDING_CONDITIONS_MODULE_CONSENSUS_VERSION,
 };
 use futures::{StreamExt, pin_mut};
 use itertools::Itertools;
@@ -72,13 +82,16 @@
 
 use crate::api::MintV2ModuleApi;
 use crate::client_db::SpendableNoteKey;
+use crate::conditional::{
+    ConditionalIssuance, ConditionalNoteRequest, ConditionalNotes, MultisigDelegation,
+};
 pub use crate::ecash::ECash;
 use crate::input::{InputSMCommon, InputSMState, InputStateMachine};
 use crate::issuance::NoteIssuanceRequest;
 use crate::output::{MintOutputStateMachine, OutputSMCommon, OutputSMState};
 use crate::receive::{ReceiveSMState, ReceiveStateMachine};
+use crate::strategy::{NoteSelectionStrategy, NoteSelectionStrategyConfig};
 
-const TARGET_PER_DENOMINATION: usize = 3;
 const SLICE_SIZE: u64 = 10000;
 const PARALLEL_HASH_REQUESTS: usize = 10;
 const PARALLEL_SLICE_REQUESTS: usize = 10;
@@ -126,6 +139,17 @@ pub enum MintOperationMeta {
         ecash: String,
         custom_meta: Value,
     },
+    ConditionalSend {
+        change_outpoint_range: OutPointRange,
+        amount: Amount,
+        condition: SpendingCondition,
+        custom_meta: Value,
+    },
+    ConditionalReceive {
+        change_outpoint_range: OutPointRange,
+        amount: Amount,
+        custom_meta: Value,
+    },
 }
 
 #[derive(Debug, Clone)]
@@ -319,6 +343,7 @@ async fn init(&self, args: &ClientModuleInitArgs<Self>) -> anyhow::Result<Self::
             client_ctx: args.context(),
             balance_update_sender: tokio::sync::watch::channel(()).0,
             tweak_receiver,
+            custom_note_selection_strategy: RwLock::new(None),
         })
     }
 
@@ -336,6 +361,7 @@ pub struct MintClientModule {
     client_ctx: ClientContext<Self>,
     balance_update_sender: tokio::sync::watch::Sender<()>,
     tweak_receiver: async_channel::Receiver<[u8; 16]>,
+    custom_note_selection_strategy: RwLock<Option<Arc<dyn NoteSelectionStrategy>>>,
 }
 
 #[derive(Debug, Clone)]
@@ -418,14 +444,17 @@ async fn create_final_inputs_and_outputs(
             anyhow::bail!("Module can only handle its configured amount unit");
         }
 
-        let funding_notes = self
-            .select_funding_input(dbtx, output_amount.saturating_sub(input_amount))
-            .await
+        let strategy = self.note_selection_strategy(dbtx).await;
+
+        let funding_counts = strategy
+            .select_inputs(
+                output_amount.saturating_sub(input_amount),
+                &self.get_count_by_denomination_dbtx(dbtx).await,
+                &self.cfg.fee_consensus,
+            )
             .context("Insufficient funds")?;
 
-        for note in &funding_notes {
-            self.remove_spendable_note(dbtx, note).await;
-        }
+        let funding_notes = self.take_notes_by_count(dbtx, &funding_counts).await?;
 
         input_amount += funding_notes.iter().map(SpendableNote::amount).sum();
 
@@ -434,15 +463,15 @@ async fn create_final_inputs_and_outputs(
             .map(|input| self.cfg.fee_consensus.fee(input.amount()))
             .sum();
 
-        assert!(output_amount <= input_amount);
+        anyhow::ensure!(output_amount <= input_amount, "Insufficient funds");
 
-        let (input_notes, output_amounts) = self
-            .rebalance(dbtx, &self.cfg.fee_consensus, input_amount - output_amount)
-            .await;
+        let (input_counts, output_amounts) = strategy.rebalance(
+            input_amount - output_amount,
+            &self.get_count_by_denomination_dbtx(dbtx).await,
+            &self.cfg.fee_consensus,
+        );
 
-        for note in &input_notes {
-            self.remove_spendable_note(dbtx, note).await;
-        }
+        let input_notes = self.take_notes_by_count(dbtx, &input_counts).await?;
 
         input_amount += input_notes.iter().map(SpendableNote::amount).sum();
 
@@ -458,7 +487,7 @@ async fn create_final_inputs_and_outputs(
             })
             .sum();
 
-        assert!(output_amount <= input_amount);
+        anyhow::ensure!(output_amount <= input_amount, "Insufficient funds");
 
         let mut spendable_notes = funding_notes
             .into_iter()
@@ -471,13 +500,14 @@ async fn create_final_inputs_and_outputs(
         let input_bundle =
             Self::create_input_bundle(operation_id, spendable_notes, false, self.cfg.amount_unit);
 
-        let mut denominations = represent_amount_with_fees(
-            input_amount.saturating_sub(output_amount),
-            &self.cfg.fee_consensus,
-        )
-        .into_iter()
-        .chain(output_amounts)
-        .collect::<Vec<Denomination>>();
+        let mut denominations = strategy
+            .represent_amount(
+                input_amount.saturating_sub(output_amount),
+                &self.cfg.fee_consensus,
+            )
+            .into_iter()
+            .chain(output_amounts)
+            .collect::<Vec<Denomination>>();
 
         // We sort the amounts to minimize the leaked information.
         denominations.sort();
@@ -518,109 +548,88 @@ async fn subscribe_balance_changes(&self) -> BoxStream<'static, ()> {
 }
 
 impl MintClientModule {
-    async fn select_funding_input(
+    /// Removes the given number of spendable notes per denomination from the
+    /// database and returns them. Fails if we hold fewer notes of a
+    /// denomination than requested, which a custom strategy may ask for.
+    async fn take_notes_by_count(
         &self,
         dbtx: &mut DatabaseTransaction<'_>,
-        mut excess_output: Amount,
-    ) -> Option<Vec<SpendableNote>> {
-        let mut selected_notes = Vec::new();
-        let mut target_notes = Vec::new();
-        let mut excess_notes = Vec::new();
-
-        for amount in client_denominations().rev() {
-            let notes_amount = dbtx
-                .find_by_prefix(&SpendableNoteAmountPrefix(amount))
+        counts: &BTreeMap<Denomination, u64>,
+    ) -> anyhow::Result<Vec<SpendableNote>> {
+        let mut notes = Vec::new();
+
+        for (denomination, count) in counts {
+            let taken = dbtx
+                .find_by_prefix(&SpendableNoteAmountPrefix(*denomination))
                 .await
                 .map(|entry| entry.0.0)
+                .take(*count as usize)
                 .collect::<Vec<SpendableNote>>()
                 .await;
 
-            target_notes.extend(notes_amount.iter().take(TARGET_PER_DENOMINATION).cloned());
-
-            if notes_amount.len() > 2 * TARGET_PER_DENOMINATION {
-                for note in notes_amount.into_iter().skip(TARGET_PER_DENOMINATION) {
-                    let note_fee = self.cfg.fee_consensus.fee(note.amount());
-
-                    let note_value = note
-                        .amount()
-                        .checked_sub(note_fee)
-                        .expect("All our notes are economical");
+            anyhow::ensure!(taken.len() as u64 == *count, "Insufficient funds");
 
-                    excess_output = excess_output.saturating_sub(note_value);
-
-                    selected_notes.push(note);
-                }
-            } else {
-                excess_notes.extend(notes_amount.into_iter().skip(TARGET_PER_DENOMINATION));
-            }
+            notes.extend(taken);
         }
 
-        if excess_output == Amount::ZERO {
-            return Some(selected_notes);
+        for note in &notes {
+            self.remove_spendable_note(dbtx, note).await;
         }
 
-        for note in excess_notes.into_iter().chain(target_notes) {
-            let note_amount = note.amount();
-            let note_value = note_amount
-                .checked_sub(self.cfg.fee_consensus.fee(note_amount))
-                .expect("All our notes are economical");
+        Ok(notes)
+    }
 
-            excess_output = excess_output.saturating_sub(note_value);
+    /// Persists the strategy used to select the notes spent and issued by
+    /// this client.
+    pub async fn set_note_selection_strategy(&self, config: NoteSelectionStrategyConfig) {
+        let mut dbtx = self.client_ctx.module_db().begin_transaction().await;
 
-            selected_notes.push(note);
+        dbtx.insert_entry(&NoteSelectionStrategyKey, &config).await;
 
-            if excess_output == Amount::ZERO {
-                return Some(selected_notes);
-            }
-        }
-
-        None
+        dbtx.commit_tx().await;
     }
 
-    async fn rebalance(
-        &self,
-        dbtx: &mut DatabaseTransaction<'_>,
-        fee: &FeeConsensus,
-        mut excess_input: Amount,
-    ) -> (Vec<SpendableNote>, Vec<Denomination>) {
-        let n_denominations = self.get_count_by_denomination_dbtx(dbtx).await;
-
-        let mut notes = dbtx
-            .find_by_prefix_sorted_descending(&SpendableNotePrefix)
+    /// Returns the persisted note selection strategy or the default one.
+    pub async fn get_note_selection_strategy(&self) -> NoteSelectionStrategyConfig {
+        self.client_ctx
+            .module_db()
+            .begin_transaction_nc()
             .await
-            .map(|entry| entry.0.0)
-            .fuse();
-
-        let mut input_notes = Vec::new();
-        let mut output_denominations = Vec::new();
-
-        for d in client_denominations() {
-            let n_denomination = n_denominations.get(&d).copied().unwrap_or(0);
-
-            let n_missing = TARGET_PER_DENOMINATION.saturating_sub(n_denomination as usize);
-
-            for _ in 0..n_missing {
-                match excess_input.checked_sub(d.amount() + fee.fee(d.amount())) {
-                    Some(remaining_excess) => excess_input = remaining_excess,
-                    None => match notes.next().await {
-                        Some(note) => {
-                            if note.amount() <= d.amount() + fee.fee(d.amount()) {
-                                break;
-                            }
-
-                            excess_input += note.amount() - (d.amount() + fee.fee(d.amount()));
+            .get_value(&NoteSelectionStrategyKey)
+            .await
+            .unwrap_or_default()
+    }
 
-                            input_notes.push(note);
-                        }
-                        None => break,
-                    },
-                }
+    /// Overrides the persisted note selection strategy with a custom
+    /// implementation until the client is restarted or `None` is set.
+    pub fn set_custom_note_selection_strategy(
+        &self,
+        strategy: Option<Arc<dyn NoteSelectionStrategy>>,
+    ) {
+        *self
+            .custom_note_selection_strategy
+            .write()
+            .expect("Locking can't fail") = strategy;
+    }
 
-                output_denominations.push(d);
-            }
+    async fn note_selection_strategy(
+        &self,
+        dbtx: &mut DatabaseTransaction<'_>,
+    ) -> Arc<dyn NoteSelectionStrategy> {
+        let custom = self
+            .custom_note_selection_strategy
+            .read()
+            .expect("Locking can't fail")
+            .clone();
+
+        if let Some(strategy) = custom {
+            return strategy;
         }
 
-        (input_notes, output_denominations)
+        dbtx.get_value(&NoteSelectionStrategyKey)
+            .await
+            .unwrap_or_default()
+            .to_strategy()
     }
 
     fn create_input_bundle(
@@ -733,6 +742,42 @@ async fn await_output_sm_success(
         stream.next_or_pending().await
     }
 
+    /// Checks that the note issued for the given blinded message is counted
+    /// towards the liabilities the federation last committed to and returns
+    /// the commitment. Notes issued since then can not be verified until the
+    /// next commitment.
+    pub async fn verify_liability_inclusion(
+        &self,
+        message: tbs::BlindedMessage,
+        denomination: Denomination,
+    ) -> anyhow::Result<LiabilityRoot> {
+        let (root, proof) = self
+            .client_ctx
+            .module_api()
+            .fetch_liability_proof(message)
+            .await?
+            .context("The mint has not committed to a note for this blinded message")?;
+
+        ensure!(
+            root.is_well_formed(),
+            "The liabilities of the mint are malformed"
+        );
+
+        ensure!(
+            proof.verify(&root, &LiabilityNode::leaf(&message, denomination.amount())),
+            "The note is not included in the liabilities of the mint"
+        );
+
+        Ok(root)
+    }
+
+    /// Fetches the liabilities the federation last committed to, or `None` if
+    /// the mint has not committed to its liabilities yet. The caller has to
+    /// check that the root is well formed.
+    pub async fn committed_liability_root(&self) -> FederationResult<Option<LiabilityRoot>> {
+        self.client_ctx.module_api().fetch_liability_root().await
+    }
+
     /// Count the `ECash` notes in the client's database by denomination.
     pub async fn get_count_by_denomination(&self) -> BTreeMap<Denomination, u64> {
         self.get_count_by_denomination_dbtx(
@@ -966,6 +1011,288 @@ pub async fn await_final_receive_operation_state(
         }
     }
 
+    /// Issues e-cash of the given amount bound to a [`SpendingCondition`],
+    /// funded from our wallet. Once the federation signed the notes they can
+    /// be fetched with [`MintClientModule::await_conditional_notes`] and
+    /// transferred out-of-band to the recipient, who redeems them with
+    /// [`MintClientModule::redeem_conditional_notes`].
+    pub async fn send_conditional_notes(
+        &self,
+        amount: Amount,
+        condition: SpendingCondition,
+        custom_meta: Value,
+    ) -> anyhow::Result<OperationId> {
+        let amount = round_to_multiple(amount, client_denominations().next().unwrap().amount());
+
+        ensure!(
+            amount > Amount::ZERO,
+            "Sending zero-amount e-cash isn't supported"
+        );
+
+        // Don't lock funds in a multisig nobody can ever spend
+        condition.validate()?;
+
+        // Notes bound to a condition can't be redeemed before the federation
+        // activated spending conditions
+        let consensus_version = self
+            .client_ctx
+            .module_api()
+            .fetch_module_consensus_version()
+            .await
+            .map_err(|e| anyhow!("Failed to fetch the module consensus version: {e}"))?;
+
+        ensure!(
+            consensus_version >= SPENDING_CONDITIONS_MODULE_CONSENSUS_VERSION,
+            "The federation does not support spending conditions yet"
+        );
+
+        let requests = represent_amount(amount)
+            .into_iter()
+            .map(|denomination| ConditionalNoteRequest::new(denomination, condition.clone()))
+            .collect::<Vec<ConditionalNoteRequest>>();
+
+        let amount_unit = self.cfg.amount_unit;
+        let outputs = requests
+            .iter()
+            .map(|request| ClientOutput {
+                output: request.output(),
+                amounts: Amounts::new_custom(amount_unit, request.denomination.amount()),
+            })
+            .collect();
+
+        let output = self
+            .client_ctx
+            .make_client_outputs(
+                ClientOutputBundle::<MintOutput, MintClientStateMachines>::new_no_sm(outputs),
+            );
+
+        let operation_id = OperationId::new_random();
+
+        let change_outpoint_range = self
+            .client_ctx
+            .finalize_and_submit_transaction(
+                operation_id,
+                MintCommonInit::KIND.as_str(),
+                move |change_outpoint_range| MintOperationMeta::ConditionalSend {
+                    change_outpoint_range,
+                    amount,
+                    condition: condition.clone(),
+                    custom_meta: custom_meta.clone(),
+                },
+                TransactionBuilder::new().with_outputs(output),
+            )
+            .await?;
+
+        // Our outputs precede the change outputs
+        let range = OutPointRange::new(
+            change_outpoint_range.txid(),
+            IdxRange::from(0..requests.len() as u64),
+        );
+
+        let mut dbtx = self.client_ctx.module_db().begin_transaction().await;
+
+        dbtx.insert_new_entry(
+            &ConditionalIssuanceKey(operation_id),
+            &ConditionalIssuance { range, requests },
+        )
+        .await;
+
+        dbtx.commit_tx().await;
+
+        Ok(operation_id)
+    }
+
+    /// Waits for the federation to sign the notes issued by
+    /// [`MintClientModule::send_conditional_notes`] and returns them
+    pub async fn await_conditional_notes(
+        &self,
+        operation_id: OperationId,
+    ) -> anyhow::Result<ConditionalNotes> {
+        let issuance = self
+            .client_ctx
+            .module_db()
+            .begin_transaction_nc()
+            .await
+            .get_value(&ConditionalIssuanceKey(operation_id))
+            .await
+            .context("Operation is not a conditional send")?;
+
+        self.client_ctx
+            .transaction_updates(operation_id)
+            .await
+            .await_tx_accepted(issuance.range.txid())
+            .await
+            .map_err(|e| anyhow!("Transaction not accepted: {e}"))?;
+
+        let signature_shares = self
+            .client_ctx
+            .module_api()
+            .fetch_signature_shares(
+                issuance.range,
+                issuance
+                    .requests
+                    .iter()
+                    .map(|request| (request.denomination, request.blinded_message()))
+                    .collect(),
+                self.cfg.tbs_pks.clone(),
+            )
+            .await;
+
+        let mut notes = Vec::new();
+
+        for (i, request) in issuance.requests.iter().enumerate() {
+            let signature = tbs::aggregate_signature_shares(
+                &signature_shares
+                    .iter()
+                    .map(|(peer, shares)| (peer.to_usize() as u64, shares[i]))
+                    .collect(),
+            );
+
+            let note = request.finalize(signature);
+
+            ensure!(
+                note.verify(&self.cfg.tbs_agg_pks),
+                "The federation issued an invalid note"
+            );
+
+            notes.push(note);
+        }
+
+        Ok(ConditionalNotes {
+            federation_id: self.federation_id,
+            notes,
+        })
+    }
+
+    /// Redeems notes bound to a spending condition into our wallet by
+    /// satisfying the condition with the given witness. The secret keys have
+    /// to contain the key selected by the witness. Notes bound to a multisig
+    /// are redeemed with [`MintClientModule::redeem_multisig_notes`]. The
+    /// operation can be tracked with
+    /// [`MintClientModule::await_final_receive_operation_state`].
+    pub async fn redeem_conditional_notes(
+        &self,
+        notes: ConditionalNotes,
+        witness: SpendingWitness,
+        secret_keys: Vec<SecretKey>,
+        custom_meta: Value,
+    ) -> anyhow::Result<OperationId> {
+        let witnesses = vec![witness; notes.notes.len()];
+
+        self.redeem_conditional_notes_with_witnesses(notes, witnesses, secret_keys, custom_meta)
+            .await
+    }
+
+    /// Redeems notes bound to a multisig into our wallet with the delegations
+    /// of a threshold of its signers to our key `delegate_key`, see
+    /// [`ConditionalNotes::delegate`]. The operation can be tracked with
+    /// [`MintClientModule::await_final_receive_operation_state`].
+    pub async fn redeem_multisig_notes(
+        &self,
+        notes: ConditionalNotes,
+        delegations: Vec<MultisigDelegation>,
+        delegate_key: SecretKey,
+        custom_meta: Value,
+    ) -> anyhow::Result<OperationId> {
+        let witnesses = notes.delegation_witnesses(&delegations)?;
+
+        self.redeem_conditional_notes_with_witnesses(
+            notes,
+            witnesses,
+            vec![delegate_key],
+            custom_meta,
+        )
+        .await
+    }
+
+    async fn redeem_conditional_notes_with_witnesses(
+        &self,
+        notes: ConditionalNotes,
+        witnesses: Vec<SpendingWitness>,
+        secret_keys: Vec<SecretKey>,
+        custom_meta: Value,
+    ) -> anyhow::Result<OperationId> {
+        ensure!(
+            notes.federation_id == self.federation_id,
+            ReceiveECashError::WrongFederation
+        );
+
+        let amount = notes.total_amount();
+
+        ensure!(
+            amount > Amount::ZERO,
+            "Redeeming zero-amount e-cash isn't supported"
+        );
+
+        let operation_id = OperationId::from_encodable(&notes);
+
+        if self.client_ctx.operation_exists(operation_id).await {
+            return Ok(operation_id);
+        }
+
+        let mut inputs = Vec::new();
+
+        for (note, witness) in notes.notes.into_iter().zip(witnesses) {
+            ensure!(
+                note.verify(&self.cfg.tbs_agg_pks),
+                "The note is not signed by the federation or does not commit to its condition"
+            );
+
+            let keypair = note.spend_keypair(&witness, &secret_keys)?;
+
+            inputs.push(ClientInput {
+                input: MintInput::new_v1(note.note, note.condition, note.salt, witness),
+                keys: vec![keypair],
+                amounts: Amounts::new_custom(self.cfg.amount_unit, note.note.amount()),
+            });
+        }
+
+        let input_sms = vec![ClientInputSM {
+            state_machines: Arc::new(move |range: OutPointRange| {
+                vec![MintClientStateMachines::Receive(ReceiveStateMachine {
+                    common: crate::receive::ReceiveSMCommon {
+                        operation_id,
+                        txid: range.txid(),
+                    },
+                    state: ReceiveSMState::Pending,
+                })]
+            }),
+        }];
+
+        let input = self
+            .client_ctx
+            .make_client_inputs(ClientInputBundle::new(inputs, input_sms));
+
+        self.client_ctx
+            .finalize_and_submit_transaction(
+                operation_id,
+                MintCommonInit::KIND.as_str(),
+                move |change_outpoint_range| MintOperationMeta::ConditionalReceive {
+                    change_outpoint_range,
+                    amount,
+                    custom_meta: custom_meta.clone(),
+                },
+                TransactionBuilder::new().with_inputs(input),
+            )
+            .await?;
+
+        let mut dbtx = self.client_ctx.module_db().begin_transaction().await;
+
+        self.client_ctx
+            .log_event(
+                &mut dbtx,
+                ReceivePaymentEvent {
+                    operation_id,
+                    amount,
+                },
+            )
+            .await;
+
+        dbtx.commit_tx().await;
+
+        Ok(operation_id)
+    }
+
     async fn remove_spendable_note(
         &self,
         dbtx: &mut DatabaseTransaction<'_>,
@@ -9,7 +9,10 @@
 use fedimint_core::db::IDatabaseTransactionOpsCoreTyped;
 use fedimint_core::encoding::{Decodable, Encodable};
 use fedimint_mintv2_common::{Denomination, verify_note};
-use tbs::{AggregatePublicKey, BlindedSignatureShare, PublicKeyShare, aggregate_signature_shares};
+use tbs::{
+    AggregatePublicKey, BlindedMessage, BlindedSignatureShare, PublicKeyShare,
+    aggregate_signature_shares,
+};
 
 use crate::api::MintV2ModuleApi;
 use crate::client_db::SpendableNoteKey;
@@ -101,7 +104,14 @@ async fn await_signature_shares(
 
             let shares = global_context
                 .module_api()
-                .fetch_signature_shares(range, issuance_requests, tbs_pks)
+                .fetch_signature_shares(
+                    range,
+                    issuance_requests
+                        .iter()
+                        .map(|request| (request.denomination, request.blinded_message()))
+                        .collect(),
+                    tbs_pks,
+                )
                 .await;
 
             Ok(shares)
@@ -164,23 +174,23 @@ async fn transition_outcome_ready(
 pub fn verify_blind_shares(
     peer: PeerId,
     signature_shares: Vec<BlindedSignatureShare>,
-    issuance_requests: &[NoteIssuanceRequest],
+    blinded_messages: &[(Denomination, BlindedMessage)],
     tbs_pks: &BTreeMap<Denomination, BTreeMap<PeerId, PublicKeyShare>>,
 ) -> anyhow::Result<Vec<BlindedSignatureShare>> {
     ensure!(
-        signature_shares.len() == issuance_requests.len(),
+        signature_shares.len() == blinded_messages.len(),
         "Invalid number of signatures shares"
     );
 
-    for (request, share) in issuance_requests.iter().zip(signature_shares.iter()) {
+    for ((denomination, message), share) in blinded_messages.iter().zip(signature_shares.iter()) {
         let amount_key = tbs_pks
-            .get(&request.denomination)
+            .get(denomination)
             .expect("No pk shares found for denomination")
             .get(&peer)
             .expect("No pk share found for peer");
 
         ensure!(
-            tbs::verify_signature_share(request.blinded_message(), *share, *amount_key),
+            tbs::verify_signature_share(*message, *share, *amount_key),
             "Invalid blind signature"
         );
     }
@@ -0,0 +1,407 @@
+//! Strategies deciding which ecash notes the client issues and spends
+//!
+//! Every transaction funded by the mint client spends some of the notes it
+//! holds and reissues the change. Which notes are spent, whether held notes are
+//! broken up to refill denominations and which denominations are issued trades
+//! off the number of notes held, the fees paid and how well the client can
+//! send ecash without reissuing first. A [`NoteSelectionStrategy`] makes these
+//! decisions based on the note counts alone, the client then picks the actual
+//! notes.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::Arc;
+
+use fedimint_core::Amount;
+use fedimint_core::encoding::{Decodable, Encodable};
+use fedimint_mintv2_common::Denomination;
+use fedimint_mintv2_common::config::{FeeConsensus, client_denominations};
+use serde::{Deserialize, Serialize};
+
+use crate::represent_amount_with_fees;
+
+/// Decides which notes the mint client spends to fund transactions and which
+/// denominations it issues as change.
+pub trait NoteSelectionStrategy: fmt::Debug + Send + Sync {
+    /// Returns the notes to spend in order to fund `amount` plus the fees of
+    /// the spent notes themselves or `None` if the held notes are insufficient.
+    fn select_inputs(
+        &self,
+        amount: Amount,
+        held: &BTreeMap<Denomination, u64>,
+        fee_consensus: &FeeConsensus,
+    ) -> Option<BTreeMap<Denomination, u64>>;
+
+    /// Returns held notes to break up and the denominations to issue in order
+    /// to bring the held notes into shape, using up to `excess` of the change
+    /// in addition to the value of the broken notes.
+    fn rebalance(
+        &self,
+        _excess: Amount,
+        _held: &BTreeMap<Denomination, u64>,
+        _fee_consensus: &FeeConsensus,
+    ) -> (BTreeMap<Denomination, u64>, Vec<Denomination>) {
+        (BTreeMap::new(), Vec::new())
+    }
+
+    /// Returns the denominations to issue for the remaining change, which has
+    /// to cover the fees of the issued notes as well.
+    fn represent_amount(&self, amount: Amount, fee_consensus: &FeeConsensus) -> Vec<Denomination> {
+        represent_amount_with_fees(amount, fee_consensus)
+    }
+}
+
+/// The built-in strategies, which can be persisted as the client's choice
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Encodable, Decodable, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NoteSelectionStrategyConfig {
+    KeepNotesPerTier { notes_per_tier: u16 },
+    MinimalNotes,
+    StandardAmounts { max_denomination: Amount },
+    FeeMinimizing,
+}
+
+impl Default for NoteSelectionStrategyConfig {
+    fn default() -> Self {
+        Self::KeepNotesPerTier { notes_per_tier: 3 }
+    }
+}
+
+impl NoteSelectionStrategyConfig {
+    pub fn to_strategy(self) -> Arc<dyn NoteSelectionStrategy> {
+        match self {
+            Self::KeepNotesPerTier { notes_per_tier } => {
+                Arc::new(KeepNotesPerTier { notes_per_tier })
+            }
+            Self::MinimalNotes => Arc::new(MinimalNotes),
+            Self::StandardAmounts { max_denomination } => {
+                Arc::new(StandardAmounts { max_denomination })
+            }
+            Self::FeeMinimizing => Arc::new(FeeMinimizing),
+        }
+    }
+}
+
+/// Tries to hold `notes_per_tier` notes of every denomination, so the client
+/// can send most amounts without having to reissue first. Notes of a
+/// denomination with more than twice as many notes are spent first and held
+/// notes are broken up to refill missing denominations.
+///
+/// With three notes per tier this is the client's default behavior.
+#[derive(Debug, Clone)]
+pub struct KeepNotesPerTier {
+    pub notes_per_tier: u16,
+}
+
+impl NoteSelectionStrategy for KeepNotesPerTier {
+    fn select_inputs(
+        &self,
+        amount: Amount,
+        held: &BTreeMap<Denomination, u64>,
+        fee_consensus: &FeeConsensus,
+    ) -> Option<BTreeMap<Denomination, u64>> {
+        select_towards_target(amount, held, fee_consensus, |_| {
+            u64::from(self.notes_per_tier)
+        })
+    }
+
+    fn rebalance(
+        &self,
+        excess: Amount,
+        held: &BTreeMap<Denomination, u64>,
+        fee_consensus: &FeeConsensus,
+    ) -> (BTreeMap<Denomination, u64>, Vec<Denomination>) {
+        rebalance_towards_target(excess, held, fee_consensus, |_| {
+            u64::from(self.notes_per_tier)
+        })
+    }
+}
+
+/// Holds as few notes as possible: spends duplicate denominations first,
+/// never breaks up notes and issues the change with as few notes as possible.
+#[derive(Debug, Clone)]
+pub struct MinimalNotes;
+
+impl NoteSelectionStrategy for MinimalNotes {
+    fn select_inputs(
+        &self,
+        amount: Amount,
+        held: &BTreeMap<Denomination, u64>,
+        fee_consensus: &FeeConsensus,
+    ) -> Option<BTreeMap<Denomination, u64>> {
+        select_towards_target(amount, held, fee_consensus, |_| 1)
+    }
+}
+
+/// Never issues notes above `max_denomination` and spends any such notes it
+/// holds first. Large denominations are rarely used, so their issuance and
+/// redemption is easier to link than for the common small denominations.
+/// Larger amounts are held as multiple notes of the standard denominations
+/// instead.
+#[derive(Debug, Clone)]
+pub struct StandardAmounts {
+    pub max_denomination: Amount,
+}
+
+impl StandardAmounts {
+    fn target(&self, denomination: Denomination) -> u64 {
+        if denomination.amount() <= self.max_denomination {
+            3
+        } else {
+            0
+        }
+    }
+}
+
+impl NoteSelectionStrategy for StandardAmounts {
+    fn select_inputs(
+        &self,
+        amount: Amount,
+        held: &BTreeMap<Denomination, u64>,
+        fee_consensus: &FeeConsensus,
+    ) -> Option<BTreeMap<Denomination, u64>> {
+        select_towards_target(amount, held, fee_consensus, |d| self.target(d))
+    }
+
+    fn rebalance(
+        &self,
+        excess: Amount,
+        held: &BTreeMap<Denomination, u64>,
+        fee_consensus: &FeeConsensus,
+    ) -> (BTreeMap<Denomination, u64>, Vec<Denomination>) {
+        rebalance_towards_target(excess, held, fee_consensus, |d| self.target(d))
+    }
+
+    fn represent_amount(
+        &self,
+        mut remaining_amount: Amount,
+        fee_consensus: &FeeConsensus,
+    ) -> Vec<Denomination> {
+        let smallest = client_denominations()
+            .next()
+            .expect("There is at least one denomination");
+
+        let mut denominations = Vec::new();
+
+        for denomination in client_denominations()
+            .rev()
+            .filter(|d| d.amount() <= self.max_denomination || *d == smallest)
+        {
+            let cost = denomination.amount() + fee_consensus.fee(denomination.amount());
+            let n_add = remaining_amount / cost;
+
+            denominations.extend(std::iter::repeat_n(denomination, n_add as usize));
+
+            remaining_amount -= n_add * cost;
+        }
+
+        denominations.sort();
+
+        denominations
+    }
+}
+
+/// Minimizes the fees paid per transaction: spends as few notes as possible
+/// by starting with the largest ones and never breaks up notes.
+#[derive(Debug, Clone)]
+pub struct FeeMinimizing;
+
+impl NoteSelectionStrategy for FeeMinimizing {
+    fn select_inputs(
+        &self,
+        mut amount: Amount,
+        held: &BTreeMap<Denomination, u64>,
+        fee_consensus: &FeeConsensus,
+    ) -> Option<BTreeMap<Denomination, u64>> {
+        let mut selected = BTreeMap::new();
+
+        if amount == Amount::ZERO {
+            return Some(selected);
+        }
+
+        for (denomination, count) in held.iter().rev() {
+            for _ in 0..*count {
+                amount = amount.saturating_sub(note_value(*denomination, fee_consensus));
+
+                *selected.entry(*denomination).or_default() += 1;
+
+                if amount == Amount::ZERO {
+                    return Some(selected);
+                }
+            }
+        }
+
+        None
+    }
+}
+
+fn note_value(denomination: Denomination, fee_consensus: &FeeConsensus) -> Amount {
+    denomination
+        .amount()
+        .checked_sub(fee_consensus.fee(denomination.amount()))
+        .expect("All our notes are economical")
+}
+
+/// Spends all notes of denominations holding more than twice their target
+/// first, then the notes in excess of the target and finally the targeted
+/// notes themselves, starting with the largest denomination each time.
+fn select_towards_target(
+    mut excess_output: Amount,
+    held: &BTreeMap<Denomination, u64>,
+    fee_consensus: &FeeConsensus,
+    target: impl Fn(Denomination) -> u64,
+) -> Option<BTreeMap<Denomination, u64>> {
+    let mut selected = BTreeMap::<Denomination, u64>::new();
+    let mut target_notes = Vec::new();
+    let mut excess_notes = Vec::new();
+
+    for denomination in client_denominations().rev() {
+        let count = held.get(&denomination).copied().unwrap_or(0);
+        let target = target(denomination);
+
+        target_notes.push((denomination, count.min(target)));
+
+        if count > 2 * target {
+            for _ in target..count {
+                excess_output =
+                    excess_output.saturating_sub(note_value(denomination, fee_consensus));
+            }
+
+            selected.insert(denomination, count - target);
+        } else {
+            excess_notes.push((denomination, count.saturating_sub(target)));
+        }
+    }
+
+    if excess_output == Amount::ZERO {
+        return Some(selected);
+    }
+
+    for (denomination, count) in excess_notes.into_iter().chain(target_notes) {
+        for _ in 0..count {
+            excess_output = excess_output.saturating_sub(note_value(denomination, fee_consensus));
+
+            *selected.entry(denomination).or_default() += 1;
+
+            if excess_output == Amount::ZERO {
+                return Some(selected);
+            }
+        }
+    }
+
+    None
+}
+
+/// Issues the notes missing to reach the target of every denomination, starting
+/// with the smallest one, paid for by the excess first and by breaking up the
+/// largest held notes once the excess is used up.
+fn rebalance_towards_target(
+    mut excess_input: Amount,
+    held: &BTreeMap<Denomination, u64>,
+    fee_consensus: &FeeConsensus,
+    target: impl Fn(Denomination) -> u64,
+) -> (BTreeMap<Denomination, u64>, Vec<Denomination>) {
+    let mut notes = held
+        .iter()
+        .rev()
+        .flat_map(|(denomination, count)| std::iter::repeat_n(*denomination, *count as usize));
+
+    let mut input_notes = BTreeMap::<Denomination, u64>::new();
+    let mut output_denominations = Vec::new();
+
+    for d in client_denominations() {
+        let n_missing = target(d).saturating_sub(held.get(&d).copied().unwrap_or(0));
+
+        for _ in 0..n_missing {
+            match excess_input.checked_sub(d.amount() + fee_consensus.fee(d.amount())) {
+                Some(remaining_excess) => excess_input = remaining_excess,
+                None => match notes.next() {
+                    Some(note) => {
+                        if note.amount() <= d.amount() + fee_consensus.fee(d.amount()) {
+                            break;
+                        }
+
+                        excess_input +=
+                            note.amount() - (d.amount() + fee_consensus.fee(d.amount()));
+
+                        *input_notes.entry(note).or_default() += 1;
+                    }
+                    None => break,
+                },
+            }
+
+            output_denominations.push(d);
+        }
+    }
+
+    (input_notes, output_denominations)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn held(counts: &[(usize, u64)]) -> BTreeMap<Denomination, u64> {
+        let denominations = client_denominations().collect::<Vec<Denomination>>();
+
+        counts
+            .iter()
+            .map(|(index, count)| (denominations[*index], *count))
+            .collect()
+    }
+
+    #[test]
+    fn default_strategy_spends_excess_notes_first() {
+        let fee_consensus = FeeConsensus::zero();
+        let strategy = NoteSelectionStrategyConfig::default().to_strategy();
+        let smallest = client_denominations().next().unwrap().amount();
+
+        assert_eq!(
+            strategy.select_inputs(smallest, &held(&[(0, 7), (1, 3)]), &fee_consensus),
+            Some(held(&[(0, 4)]))
+        );
+
+        assert_eq!(
+            strategy.select_inputs(smallest, &held(&[(0, 4), (1, 3)]), &fee_consensus),
+            Some(held(&[(0, 1)]))
+        );
+
+        assert_eq!(
+            strategy.select_inputs(smallest * 100, &held(&[(0, 4)]), &fee_consensus),
+            None
+        );
+    }
+
+    #[test]
+    fn fee_minimizing_spends_fewest_notes() {
+        let fee_consensus = FeeConsensus::zero();
+        let smallest = client_denominations().next().unwrap().amount();
+
+        assert_eq!(
+            FeeMinimizing.select_inputs(smallest * 3, &held(&[(0, 3), (2, 1)]), &fee_consensus),
+            Some(held(&[(2, 1)]))
+        );
+    }
+
+    #[test]
+    fn standard_amounts_caps_denominations() {
+        let fee_consensus = FeeConsensus::zero();
+        let denominations = client_denominations().collect::<Vec<Denomination>>();
+        let strategy = StandardAmounts {
+            max_denomination: denominations[2].amount(),
+        };
+
+        let issued = strategy.represent_amount(denominations[5].amount(), &fee_consensus);
+
+        assert!(issued.iter().all(|d| *d <= denominations[2]));
+        assert_eq!(
+            issued.iter().map(|d| d.amount()).sum::<Amount>(),
+            denominations[5].amount()
+        );
+
+        assert_eq!(
+            strategy.select_inputs(Amount::ZERO, &held(&[(2, 3), (4, 1)]), &fee_consensus),
+            Some(held(&[(4, 1)]))
+        );
+    }
+}
@@ -38,6 +38,8 @@ pub struct MintConfigConsensus {
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct MintConfigPrivate {
+    /// Empty once the keys have been exported to a remote guardian signer
+    #[serde(default)]
     pub tbs_sks: BTreeMap<Denomination, tbs::SecretKeyShare>,
 }
 
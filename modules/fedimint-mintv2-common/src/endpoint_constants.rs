@@ -3,3 +3,7 @@
 pub const RECOVERY_SLICE_ENDPOINT: &str = "recovery_slice";
 pub const RECOVERY_SLICE_HASH_ENDPOINT: &str = "recovery_slice_hash";
 pub const RECOVERY_COUNT_ENDPOINT: &str = "recovery_count";
+pub const LIABILITY_ROOT_ENDPOINT: &str = "liability_root";
+pub const LIABILITY_PROOF_ENDPOINT: &str = "liability_proof";
+pub const MODULE_CONSENSUS_VERSION_ENDPOINT: &str = "module_consensus_version";
+pub const SUPPORTED_MODULE_CONSENSUS_VERSION_ENDPOINT: &str = "supported_module_consensus_version";
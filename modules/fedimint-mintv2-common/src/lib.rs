@@ -9,9 +9,11 @@
 use std::hash::Hash;
 
 use bitcoin_hashes::hash160;
+use condition::{SpendingCondition, SpendingConditionError, SpendingWitness};
 use config::MintClientConfig;
 use fedimint_core::core::{Decoder, ModuleInstanceId, ModuleKind};
 use fedimint_core::encoding::{Decodable, Encodable};
+use fedimint_core::module::liabilities::LiabilityRoot;
 use fedimint_core::module::{CommonModuleInit, ModuleCommon, ModuleConsensusVersion};
 use fedimint_core::secp256k1::PublicKey;
 use fedimint_core::{Amount, extensible_associated_module_type, plugin_types_trait_impl_common};
@@ -22,8 +24,22 @@
 pub mod config;
 pub mod endpoint_constants;
 
+/// Notes are bound to spending conditions the same way as in the mint module
+pub use fedimint_mint_common::condition;
+
 pub const KIND: ModuleKind = ModuleKind::from_static_str("mintv2");
-pub const MODULE_CONSENSUS_VERSION: ModuleConsensusVersion = ModuleConsensusVersion::new(1, 0);
+pub const MODULE_CONSENSUS_VERSION: ModuleConsensusVersion = ModuleConsensusVersion::new(1, 2);
+
+/// The module consensus version that introduced [`MintInputV1`] and the unix
+/// time votes. Before it was activated for a federation neither is accepted,
+/// so not yet upgraded guardians stay in consensus.
+pub const SPENDING_CONDITIONS_MODULE_CONSENSUS_VERSION: ModuleConsensusVersion =
+    ModuleConsensusVersion::new(1, 1);
+
+/// The module consensus version that introduced the commitment to the
+/// liabilities of the mint via [`MintConsensusItem::LiabilityRoot`]
+pub const LIABILITY_COMMITMENT_MODULE_CONSENSUS_VERSION: ModuleConsensusVersion =
+    ModuleConsensusVersion::new(1, 2);
 
 /// Compact representation of a power-of-2 amount denomination
 /// Represents 2^denomination msats
@@ -56,14 +72,22 @@ fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
     }
 }
 
-/// The mint module currently doesn't define any consensus items and generally
-/// throws an error on encountering one. To allow old clients to still decode
-/// blocks in the future, should we decide to add consensus items, this has to
-/// be an enum with only a default variant.
+/// The guardians vote on the current unix time, which decides when the time
+/// locks of [`SpendingCondition`]s expire, and on upgrading the module
+/// consensus version. Once the liability commitment is active the guardians
+/// also agree on the [`LiabilityRoot`] of the mint, which ends up in the signed
+/// session outcome. Unknown variants are decoded as the default variant to
+/// allow old clients to decode future consensus items.
 #[derive(Debug, Clone, Eq, PartialEq, Hash, Serialize, Deserialize, Encodable, Decodable)]
 pub enum MintConsensusItem {
+    UnixTimeVote(u64),
+    ModuleConsensusVersion(ModuleConsensusVersion),
+    LiabilityRoot(LiabilityRoot),
     #[encodable_default]
-    Default { variant: u64, bytes: Vec<u8> },
+    Default {
+        variant: u64,
+        bytes: Vec<u8>,
+    },
 }
 
 impl std::fmt::Display for MintConsensusItem {
@@ -111,12 +135,89 @@ fn decoder() -> Decoder {
     }
 }
 
-extensible_associated_module_type!(MintInput, MintInputV0, UnknownMintInputVariantError);
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Deserialize, Serialize, Encodable, Decodable)]
+pub enum MintInput {
+    V0(MintInputV0),
+    V1(MintInputV1),
+    #[encodable_default]
+    Default {
+        variant: u64,
+        bytes: Vec<u8>,
+    },
+}
 
 impl MintInput {
     pub fn new_v0(note: Note) -> Self {
         Self::V0(MintInputV0 { note })
     }
+
+    pub fn new_v1(
+        note: Note,
+        condition: SpendingCondition,
+        salt: [u8; 32],
+        witness: SpendingWitness,
+    ) -> Self {
+        Self::V1(MintInputV1 {
+            note,
+            condition,
+            salt,
+            witness,
+        })
+    }
+
+    pub fn maybe_v0_ref(&self) -> Option<&MintInputV0> {
+        match self {
+            MintInput::V0(v0) => Some(v0),
+            _ => None,
+        }
+    }
+
+    pub fn ensure_v0_ref(&self) -> Result<&MintInputV0, UnknownMintInputVariantError> {
+        match self {
+            MintInput::V0(v0) => Ok(v0),
+            MintInput::V1(_) => Err(UnknownMintInputVariantError { variant: 1 }),
+            MintInput::Default { variant, .. } => {
+                Err(UnknownMintInputVariantError { variant: *variant })
+            }
+        }
+    }
+
+    /// The note spent by any known input variant
+    pub fn note(&self) -> Result<Note, UnknownMintInputVariantError> {
+        match self {
+            MintInput::V0(v0) => Ok(v0.note),
+            MintInput::V1(v1) => Ok(v1.note),
+            MintInput::Default { variant, .. } => {
+                Err(UnknownMintInputVariantError { variant: *variant })
+            }
+        }
+    }
+}
+
+impl From<MintInputV0> for MintInput {
+    fn from(v0: MintInputV0) -> Self {
+        Self::V0(v0)
+    }
+}
+
+#[derive(
+    Debug, Error, Clone, Eq, PartialEq, Hash, Deserialize, Serialize, Encodable, Decodable,
+)]
+#[error("Unknown MintInput variant {variant}")]
+pub struct UnknownMintInputVariantError {
+    pub variant: u64,
+}
+
+impl std::fmt::Display for MintInput {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MintInput::V0(inner) => std::fmt::Display::fmt(inner, f),
+            MintInput::V1(inner) => std::fmt::Display::fmt(inner, f),
+            MintInput::Default { variant, .. } => {
+                write!(f, "Unknown MintInput (variant={variant})")
+            }
+        }
+    }
 }
 
 #[derive(Debug, Clone, Eq, PartialEq, Hash, Deserialize, Serialize, Encodable, Decodable)]
@@ -130,6 +231,23 @@ fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
     }
 }
 
+/// Spends a note bound to a [`SpendingCondition`]. The note's nonce has to
+/// be the condition's nonce for the salt, and the transaction has to be
+/// signed by the key the witness selects from the condition.
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Deserialize, Serialize, Encodable, Decodable)]
+pub struct MintInputV1 {
+    pub note: Note,
+    pub condition: SpendingCondition,
+    pub salt: [u8; 32],
+    pub witness: SpendingWitness,
+}
+
+impl std::fmt::Display for MintInputV1 {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Conditional Mint Note {}", self.note.denomination)
+    }
+}
+
 extensible_associated_module_type!(MintOutput, MintOutputV0, UnknownMintOutputVariantError);
 
 impl MintOutput {
@@ -215,6 +333,8 @@ pub enum MintInputError {
     InvalidDenomination,
     #[error("The note has an invalid signature")]
     InvalidSignature,
+    #[error("The spending condition of the note is not satisfied: {0}")]
+    SpendingCondition(#[from] SpendingConditionError),
 }
 
 #[derive(Debug, Clone, Eq, PartialEq, Hash, Error, Encodable, Decodable)]
@@ -1,6 +1,8 @@
 use fedimint_core::encoding::{Decodable, Encodable};
+use fedimint_core::module::ModuleConsensusVersion;
+use fedimint_core::module::liabilities::{LiabilityNode, LiabilityRoot, NodePosition};
 use fedimint_core::secp256k1::PublicKey;
-use fedimint_core::{OutPoint, impl_db_lookup, impl_db_record};
+use fedimint_core::{Amount, OutPoint, PeerId, impl_db_lookup, impl_db_record};
 use fedimint_mintv2_common::{Denomination, RecoveryItem};
 use serde::Serialize;
 use strum_macros::EnumIter;
@@ -14,6 +16,13 @@ pub enum DbKeyPrefix {
     BlindedSignatureShareRecovery = 0x12,
     MintAuditItem = 0x13,
     RecoveryItem = 0x14,
+    LiabilityNode = 0x15,
+    LiabilityLeaf = 0x16,
+    LiabilityTier = 0x17,
+    UnixTimeVote = 0x18,
+    ConsensusVersionVote = 0x19,
+    LiabilityEvent = 0x1a,
+    CommittedLiabilityRoot = 0x1b,
 }
 
 impl std::fmt::Display for DbKeyPrefix {
@@ -95,3 +104,123 @@ fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
     db_prefix = DbKeyPrefix::RecoveryItem,
 );
 impl_db_lookup!(key = RecoveryItemKey, query_prefix = RecoveryItemPrefix);
+
+/// The nodes of the Merkle sum forests over the issued notes of every
+/// denomination, see [`fedimint_core::module::liabilities`]
+#[derive(Debug, Clone, Copy, Encodable, Decodable, Serialize)]
+pub struct LiabilityNodeKey(pub Amount, pub NodePosition);
+
+#[derive(Debug, Encodable, Decodable)]
+pub struct LiabilityNodePrefix;
+
+impl_db_record!(
+    key = LiabilityNodeKey,
+    value = LiabilityNode,
+    db_prefix = DbKeyPrefix::LiabilityNode,
+);
+impl_db_lookup!(key = LiabilityNodeKey, query_prefix = LiabilityNodePrefix);
+
+/// Maps a blinded message to the denomination and index of its leaf in the
+/// liability forests
+#[derive(Debug, Clone, Copy, Encodable, Decodable, Serialize)]
+pub struct LiabilityLeafKey(pub BlindedMessage);
+
+#[derive(Debug, Encodable, Decodable)]
+pub struct LiabilityLeafPrefix;
+
+impl_db_record!(
+    key = LiabilityLeafKey,
+    value = (Amount, u64),
+    db_prefix = DbKeyPrefix::LiabilityLeaf,
+);
+impl_db_lookup!(key = LiabilityLeafKey, query_prefix = LiabilityLeafPrefix);
+
+/// The number of notes issued and redeemed in a denomination
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Encodable, Decodable, Serialize)]
+pub struct LiabilityTier {
+    pub issued: u64,
+    pub redeemed: u64,
+}
+
+#[derive(Debug, Clone, Encodable, Decodable, Serialize)]
+pub struct LiabilityTierKey(pub Amount);
+
+#[derive(Debug, Encodable, Decodable)]
+pub struct LiabilityTierPrefix;
+
+impl_db_record!(
+    key = LiabilityTierKey,
+    value = LiabilityTier,
+    db_prefix = DbKeyPrefix::LiabilityTier,
+);
+impl_db_lookup!(key = LiabilityTierKey, query_prefix = LiabilityTierPrefix);
+
+/// An issuance or redemption that changed the liabilities of the mint
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Encodable, Decodable, Serialize)]
+pub struct LiabilityEvent {
+    pub denomination: Amount,
+    pub redemption: bool,
+}
+
+/// The log of liability events since the last committed liability root, which
+/// allows us to recompute the root a peer proposed at an earlier event count
+#[derive(Debug, Clone, Copy, Encodable, Decodable, Serialize)]
+pub struct LiabilityEventKey(pub u64);
+
+#[derive(Debug, Encodable, Decodable)]
+pub struct LiabilityEventPrefix;
+
+impl_db_record!(
+    key = LiabilityEventKey,
+    value = LiabilityEvent,
+    db_prefix = DbKeyPrefix::LiabilityEvent,
+);
+impl_db_lookup!(key = LiabilityEventKey, query_prefix = LiabilityEventPrefix);
+
+/// The latest liability root the federation agreed on in consensus
+#[derive(Debug, Clone, Encodable, Decodable, Serialize)]
+pub struct CommittedLiabilityRootKey;
+
+#[derive(Debug, Encodable, Decodable)]
+pub struct CommittedLiabilityRootPrefix;
+
+impl_db_record!(
+    key = CommittedLiabilityRootKey,
+    value = LiabilityRoot,
+    db_prefix = DbKeyPrefix::CommittedLiabilityRoot,
+);
+impl_db_lookup!(
+    key = CommittedLiabilityRootKey,
+    query_prefix = CommittedLiabilityRootPrefix
+);
+
+/// The unix time every peer voted for, used to evaluate time locks
+#[derive(Debug, Encodable, Decodable, Serialize)]
+pub struct UnixTimeVoteKey(pub PeerId);
+
+#[derive(Debug, Encodable, Decodable)]
+pub struct UnixTimeVotePrefix;
+
+impl_db_record!(
+    key = UnixTimeVoteKey,
+    value = u64,
+    db_prefix = DbKeyPrefix::UnixTimeVote,
+);
+impl_db_lookup!(key = UnixTimeVoteKey, query_prefix = UnixTimeVotePrefix);
+
+/// The module consensus version every peer voted to upgrade to
+#[derive(Debug, Encodable, Decodable, Serialize)]
+pub struct ConsensusVersionVoteKey(pub PeerId);
+
+#[derive(Debug, Encodable, Decodable)]
+pub struct ConsensusVersionVotePrefix;
+
+impl_db_record!(
+    key = ConsensusVersionVoteKey,
+    value = ModuleConsensusVersion,
+    db_prefix = DbKeyPrefix::ConsensusVersionVote,
+);
+impl_db_lookup!(
+    key = ConsensusVersionVoteKey,
+    query_prefix = ConsensusVersionVotePrefix
+);
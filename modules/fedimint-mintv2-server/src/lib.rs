@@ -7,6 +7,7 @@
 mod db;
 
 use std::collections::BTreeMap;
+use std::time::Duration;
 
 use anyhow::{bail, ensure};
 use bitcoin::hashes::sha256;
@@ -19,50 +20,76 @@
     Database, DatabaseTransaction, DatabaseVersion, IDatabaseTransactionOpsCoreTyped,
 };
 use fedimint_core::encoding::Encodable;
-use fedimint_core::envs::{FM_ENABLE_MODULE_MINTV2_ENV, is_env_var_set_opt};
+use fedimint_core::envs::{
+    FM_ENABLE_MODULE_MINTV2_ENV, is_automatic_consensus_version_voting_disabled, is_env_var_set_opt,
+};
 use fedimint_core::module::audit::Audit;
+use fedimint_core::module::liabilities::{
+    LiabilityNode, LiabilityProof, LiabilityRoot, NodePosition, TierLiabilities, peak_positions,
+    proof_positions,
+};
 use fedimint_core::module::{
     AmountUnit, Amounts, ApiEndpoint, ApiError, ApiVersion, CORE_CONSENSUS_VERSION,
     CoreConsensusVersion, InputMeta, ModuleConsensusVersion, ModuleInit,
     SupportedModuleApiVersions, TransactionItemAmounts, api_endpoint,
 };
+use fedimint_core::task::sleep;
+use fedimint_core::time::duration_since_epoch;
+use fedimint_core::util::FmtCompactAnyhow as _;
 use fedimint_core::{
     Amount, BitcoinHash, InPoint, NumPeers, NumPeersExt, OutPoint, PeerId, apply,
     async_trait_maybe_send, push_db_key_items, push_db_pair_items,
 };
+use fedimint_logging::LOG_MODULE_MINT;
+use fedimint_mintv2_common::condition::SpendingConditionError;
 use fedimint_mintv2_common::config::{
     FeeConsensus, MintClientConfig, MintConfig, MintConfigConsensus, MintConfigPrivate,
     consensus_denominations,
 };
 use fedimint_mintv2_common::endpoint_constants::{
+    LIABILITY_PROOF_ENDPOINT, LIABILITY_ROOT_ENDPOINT, MODULE_CONSENSUS_VERSION_ENDPOINT,
     RECOVERY_COUNT_ENDPOINT, RECOVERY_SLICE_ENDPOINT, RECOVERY_SLICE_HASH_ENDPOINT,
     SIGNATURE_SHARES_ENDPOINT, SIGNATURE_SHARES_RECOVERY_ENDPOINT,
+    SUPPORTED_MODULE_CONSENSUS_VERSION_ENDPOINT,
 };
 use fedimint_mintv2_common::{
-    Denomination, MODULE_CONSENSUS_VERSION, MintCommonInit, MintConsensusItem, MintInput,
-    MintInputError, MintModuleTypes, MintOutput, MintOutputError, MintOutputOutcome, RecoveryItem,
-    verify_note,
+    Denomination, LIABILITY_COMMITMENT_MODULE_CONSENSUS_VERSION, MODULE_CONSENSUS_VERSION,
+    MintCommonInit, MintConsensusItem, MintInput, MintInputError, MintModuleTypes, MintOutput,
+    MintOutputError, MintOutputOutcome, RecoveryItem, SPENDING_CONDITIONS_MODULE_CONSENSUS_VERSION,
+    UnknownMintInputVariantError, verify_note,
 };
 use fedimint_server_core::config::{PeerHandleOps, eval_poly_g2};
-use fedimint_server_core::migration::ServerModuleDbMigrationFn;
+use fedimint_server_core::consensus_version::spawn_peer_supported_consensus_version_task;
+use fedimint_server_core::guardian_signer::{GuardianSecret, ModuleGuardianSigner};
+use fedimint_server_core::migration::{
+    ModuleHistoryItem, ServerModuleDbMigrationFn, ServerModuleDbMigrationFnContext,
+    ServerModuleDbMigrationFnContextExt as _,
+};
 use fedimint_server_core::{
     ConfigGenModuleArgs, EnvVarDoc, ServerModule, ServerModuleInit, ServerModuleInitArgs,
 };
-use futures::StreamExt;
+use futures::{FutureExt as _, StreamExt};
 use rand::SeedableRng;
 use rand_chacha::ChaChaRng;
 use strum::IntoEnumIterator;
 use tbs::{
-    AggregatePublicKey, BlindedSignatureShare, PublicKeyShare, SecretKeyShare, derive_pk_share,
+    AggregatePublicKey, BlindedMessage, BlindedSignatureShare, PublicKeyShare, SecretKeyShare,
+    derive_pk_share, verify_signature_share,
 };
 use threshold_crypto::ff::Field;
 use threshold_crypto::group::Curve;
 use threshold_crypto::{G2Projective, Scalar};
+use tokio::sync::watch;
+use tracing::{info, warn};
 
 use crate::db::{
     BlindedSignatureShareKey, BlindedSignatureSharePrefix, BlindedSignatureShareRecoveryKey,
-    BlindedSignatureShareRecoveryPrefix, DbKeyPrefix, IssuanceCounterKey, IssuanceCounterPrefix,
-    NonceKey, NonceKeyPrefix, RecoveryItemKey, RecoveryItemPrefix,
+    BlindedSignatureShareRecoveryPrefix, CommittedLiabilityRootKey, CommittedLiabilityRootPrefix,
+    ConsensusVersionVoteKey, ConsensusVersionVotePrefix, DbKeyPrefix, IssuanceCounterKey,
+    IssuanceCounterPrefix, LiabilityEvent, LiabilityEventKey, LiabilityEventPrefix,
+    LiabilityLeafKey, LiabilityLeafPrefix, LiabilityNodeKey, LiabilityNodePrefix, LiabilityTier,
+    LiabilityTierKey, LiabilityTierPrefix, NonceKey, NonceKeyPrefix, RecoveryItemKey,
+    RecoveryItemPrefix, UnixTimeVoteKey, UnixTimeVotePrefix,
 };
 
 #[derive(Debug, Clone)]
@@ -115,6 +142,56 @@ async fn dump_database(
                         "Issuance Counter"
                     );
                 }
+                DbKeyPrefix::LiabilityNode => {
+                    push_db_pair_items!(
+                        dbtx,
+                        LiabilityNodePrefix,
+                        LiabilityNodeKey,
+                        LiabilityNode,
+                        mint,
+                        "Liability Nodes"
+                    );
+                }
+                DbKeyPrefix::LiabilityLeaf => {
+                    push_db_pair_items!(
+                        dbtx,
+                        LiabilityLeafPrefix,
+                        LiabilityLeafKey,
+                        (Amount, u64),
+                        mint,
+                        "Liability Leaves"
+                    );
+                }
+                DbKeyPrefix::LiabilityTier => {
+                    push_db_pair_items!(
+                        dbtx,
+                        LiabilityTierPrefix,
+                        LiabilityTierKey,
+                        LiabilityTier,
+                        mint,
+                        "Liability Tiers"
+                    );
+                }
+                DbKeyPrefix::LiabilityEvent => {
+                    push_db_pair_items!(
+                        dbtx,
+                        LiabilityEventPrefix,
+                        LiabilityEventKey,
+                        LiabilityEvent,
+                        mint,
+                        "Liability Events"
+                    );
+                }
+                DbKeyPrefix::CommittedLiabilityRoot => {
+                    push_db_pair_items!(
+                        dbtx,
+                        CommittedLiabilityRootPrefix,
+                        CommittedLiabilityRootKey,
+                        LiabilityRoot,
+                        mint,
+                        "Committed Liability Root"
+                    );
+                }
                 DbKeyPrefix::RecoveryItem => {
                     push_db_pair_items!(
                         dbtx,
@@ -125,6 +202,26 @@ async fn dump_database(
                         "Recovery Items"
                     );
                 }
+                DbKeyPrefix::UnixTimeVote => {
+                    push_db_pair_items!(
+                        dbtx,
+                        UnixTimeVotePrefix,
+                        UnixTimeVoteKey,
+                        u64,
+                        mint,
+                        "Unix Time Votes"
+                    );
+                }
+                DbKeyPrefix::ConsensusVersionVote => {
+                    push_db_pair_items!(
+                        dbtx,
+                        ConsensusVersionVotePrefix,
+                        ConsensusVersionVoteKey,
+                        ModuleConsensusVersion,
+                        mint,
+                        "Consensus Version Votes"
+                    );
+                }
             }
         }
 
@@ -163,9 +260,21 @@ fn get_documented_env_vars(&self) -> Vec<EnvVarDoc> {
     }
 
     async fn init(&self, args: &ServerModuleInitArgs<Self>) -> anyhow::Result<Self::Module> {
+        let peer_supported_consensus_version = spawn_peer_supported_consensus_version_task(
+            args.module_api().clone(),
+            args.task_group(),
+            args.our_peer_id(),
+            MODULE_CONSENSUS_VERSION,
+            SUPPORTED_MODULE_CONSENSUS_VERSION_ENDPOINT,
+        );
+
         args.cfg().to_typed().map(|cfg| Mint {
             cfg,
             db: args.db().clone(),
+            our_id: args.our_peer_id(),
+            guardian_signer: args.guardian_signer().clone(),
+            genesis_consensus_version: args.cfg().consensus.version,
+            peer_supported_consensus_version,
         })
     }
 
@@ -274,6 +383,12 @@ async fn distributed_gen(
     fn validate_config(&self, identity: &PeerId, config: ServerModuleConfig) -> anyhow::Result<()> {
         let config = config.to_typed::<MintConfig>()?;
 
+        // The secret keys are missing once they have been exported to a remote
+        // guardian signer, which verifies them on startup instead
+        if config.private.tbs_sks.is_empty() {
+            return Ok(());
+        }
+
         for denomination in consensus_denominations() {
             let pk = derive_pk_share(&config.private.tbs_sks[&denomination]);
 
@@ -286,6 +401,19 @@ fn validate_config(&self, identity: &PeerId, config: ServerModuleConfig) -> anyh
         Ok(())
     }
 
+    fn guardian_keys(
+        &self,
+        config: &ServerModuleConfig,
+    ) -> anyhow::Result<BTreeMap<String, GuardianSecret>> {
+        Ok(config
+            .to_typed::<MintConfig>()?
+            .private
+            .tbs_sks
+            .iter()
+            .map(|(denomination, sk)| (tbs_key_name(*denomination), GuardianSecret::Bls12381(sk.0)))
+            .collect())
+    }
+
     fn get_client_config(
         &self,
         config: &ServerModuleConsensusConfig,
@@ -303,8 +431,69 @@ fn get_client_config(
     fn get_database_migrations(
         &self,
     ) -> BTreeMap<DatabaseVersion, ServerModuleDbMigrationFn<Mint>> {
-        BTreeMap::new()
+        let mut migrations: BTreeMap<DatabaseVersion, ServerModuleDbMigrationFn<_>> =
+            BTreeMap::new();
+        migrations.insert(
+            DatabaseVersion(0),
+            Box::new(|ctx| migrate_db_v0(ctx).boxed()),
+        );
+        migrations
+    }
+}
+
+// Rebuild the liabilities per denomination from the module history, since the
+// liability forest used to span all denominations and redemptions were not
+// counted. The event log is replayed in full such that every guardian can
+// recompute the same liability roots until the first one is committed.
+async fn migrate_db_v0(mut ctx: ServerModuleDbMigrationFnContext<'_, Mint>) -> anyhow::Result<()> {
+    for prefix in [
+        DbKeyPrefix::LiabilityNode,
+        DbKeyPrefix::LiabilityLeaf,
+        DbKeyPrefix::LiabilityTier,
+        DbKeyPrefix::LiabilityEvent,
+        DbKeyPrefix::CommittedLiabilityRoot,
+    ] {
+        ctx.dbtx()
+            .raw_remove_by_prefix(&[prefix as u8])
+            .await
+            .expect("DB error");
+    }
+
+    let mut events = Vec::new();
+    let mut stream = ctx.get_typed_module_history_stream().await;
+
+    while let Some(history_item) = stream.next().await {
+        match history_item {
+            ModuleHistoryItem::Output(mint_output, _) => {
+                let output = mint_output
+                    .ensure_v0_ref()
+                    .expect("This migration only runs while we only have v0 outputs");
+
+                events.push((output.amount(), Some(output.nonce)));
+            }
+            ModuleHistoryItem::Input(mint_input) => {
+                let note = mint_input
+                    .note()
+                    .expect("Only inputs of known variants have been accepted");
+
+                events.push((note.amount(), None));
+            }
+            ModuleHistoryItem::ConsensusItem(_) => {}
+        }
+    }
+
+    drop(stream);
+
+    info!(target: LOG_MODULE_MINT, "Replaying {} liability events from history", events.len());
+
+    for (amount, message) in events {
+        match message {
+            Some(message) => append_liability(&mut ctx.dbtx(), message, amount).await,
+            None => redeem_liability(&mut ctx.dbtx(), amount).await,
+        }
     }
+
+    Ok(())
 }
 
 fn dealer_agg_pk(amount: Amount) -> AggregatePublicKey {
@@ -338,10 +527,65 @@ fn coefficient(amount: Amount, index: u64) -> Scalar {
     ))
 }
 
+/// Name of the secret key share of a denomination in the guardian signer
+fn tbs_key_name(denomination: Denomination) -> String {
+    format!("tbs_sk/{}", denomination.0)
+}
+
+/// Every guardian has to sign the output to stay in consensus, so if the signer
+/// is unavailable or returns an invalid share we keep retrying until it is
+/// back.
+async fn blind_sign_with_guardian_signer(
+    signer: &ModuleGuardianSigner,
+    denomination: Denomination,
+    message: BlindedMessage,
+    pk_share: PublicKeyShare,
+) -> BlindedSignatureShare {
+    loop {
+        match signer
+            .multiply_g1(&tbs_key_name(denomination), message.0)
+            .await
+        {
+            Ok(signature)
+                if verify_signature_share(message, BlindedSignatureShare(signature), pk_share) =>
+            {
+                return BlindedSignatureShare(signature);
+            }
+            Ok(_) => {
+                warn!(
+                    target: LOG_MODULE_MINT,
+                    ?denomination,
+                    "Guardian signer returned an invalid blind signature share, retrying..."
+                );
+            }
+            Err(e) => {
+                warn!(
+                    target: LOG_MODULE_MINT,
+                    err = %e.fmt_compact_anyhow(),
+                    ?denomination,
+                    "Failed to create blind signature with guardian signer, retrying..."
+                );
+            }
+        }
+
+        sleep(Duration::from_secs(1)).await;
+    }
+}
+
 #[derive(Debug)]
 pub struct Mint {
     cfg: MintConfig,
     db: Database,
+    our_id: PeerId,
+    /// Creates the blind signatures with our secret key shares
+    guardian_signer: ModuleGuardianSigner,
+    /// Consensus version the federation was created with, which is active
+    /// until the peers vote to upgrade
+    genesis_consensus_version: ModuleConsensusVersion,
+    /// Maximum consensus version supported by *all* our peers. Used to
+    /// automatically activate new consensus versions as soon as everyone
+    /// upgrades.
+    peer_supported_consensus_version: watch::Receiver<Option<ModuleConsensusVersion>>,
 }
 
 impl Mint {
@@ -356,6 +600,57 @@ pub async fn note_distribution_ui(&self) -> BTreeMap<Denomination, u64> {
             .collect()
             .await
     }
+
+    fn num_peers(&self) -> NumPeers {
+        self.cfg
+            .consensus
+            .tbs_pks
+            .values()
+            .next()
+            .expect("There is at least one denomination")
+            .to_num_peers()
+    }
+
+    /// The unix time a threshold of peers agrees has passed, used to evaluate
+    /// the time locks of spending conditions
+    async fn consensus_unix_time(&self, dbtx: &mut DatabaseTransaction<'_>) -> u64 {
+        let num_peers = self.num_peers();
+
+        let mut times: Vec<u64> = dbtx
+            .find_by_prefix(&UnixTimeVotePrefix)
+            .await
+            .map(|entry| entry.1)
+            .collect()
+            .await;
+
+        times.sort_by(|a, b| b.cmp(a));
+
+        times.get(num_peers.threshold() - 1).copied().unwrap_or(0)
+    }
+
+    /// The highest consensus version at least a threshold of peers voted for,
+    /// peers that did not vote count as voting for the genesis version
+    async fn consensus_module_consensus_version(
+        &self,
+        dbtx: &mut DatabaseTransaction<'_>,
+    ) -> ModuleConsensusVersion {
+        let num_peers = self.num_peers();
+
+        let mut versions = dbtx
+            .find_by_prefix(&ConsensusVersionVotePrefix)
+            .await
+            .map(|entry| entry.1)
+            .collect::<Vec<ModuleConsensusVersion>>()
+            .await;
+
+        while versions.len() < num_peers.total() {
+            versions.push(self.genesis_consensus_version);
+        }
+
+        versions.sort_unstable();
+
+        versions[num_peers.max_evil()]
+    }
 }
 
 #[apply(async_trait_maybe_send!)]
@@ -365,18 +660,127 @@ impl ServerModule for Mint {
 
     async fn consensus_proposal(
         &self,
-        _dbtx: &mut DatabaseTransaction<'_>,
+        dbtx: &mut DatabaseTransaction<'_>,
     ) -> Vec<MintConsensusItem> {
-        Vec::new()
+        let active_consensus_version = self.consensus_module_consensus_version(dbtx).await;
+
+        // Guardians running a version without spending conditions reject any
+        // consensus item, so we only vote once all of them support the upgrade
+        let mut items = if is_automatic_consensus_version_voting_disabled() {
+            vec![]
+        } else {
+            self.peer_supported_consensus_version
+                .borrow()
+                .filter(|supported| active_consensus_version < *supported)
+                .map(MintConsensusItem::ModuleConsensusVersion)
+                .into_iter()
+                .collect()
+        };
+
+        if active_consensus_version < SPENDING_CONDITIONS_MODULE_CONSENSUS_VERSION {
+            return items;
+        }
+
+        // We round the time to the minute to limit the number of votes
+        items.push(MintConsensusItem::UnixTimeVote(
+            60 * (duration_since_epoch().as_secs() / 60),
+        ));
+
+        if active_consensus_version >= LIABILITY_COMMITMENT_MODULE_CONSENSUS_VERSION {
+            let events = get_liability_event_count(dbtx).await;
+
+            if get_committed_liability_event_count(dbtx).await < events {
+                items.push(MintConsensusItem::LiabilityRoot(
+                    get_liability_root(dbtx, events)
+                        .await
+                        .expect("Uncommitted liability events are not pruned"),
+                ));
+            }
+        }
+
+        items
     }
 
     async fn process_consensus_item<'a, 'b>(
         &'a self,
-        _dbtx: &mut DatabaseTransaction<'b>,
-        _consensus_item: MintConsensusItem,
-        _peer_id: PeerId,
+        dbtx: &mut DatabaseTransaction<'b>,
+        consensus_item: MintConsensusItem,
+        peer_id: PeerId,
     ) -> anyhow::Result<()> {
-        bail!("Mint does not process consensus items");
+        match consensus_item {
+            MintConsensusItem::UnixTimeVote(vote) => {
+                ensure!(
+                    self.consensus_module_consensus_version(dbtx).await
+                        >= SPENDING_CONDITIONS_MODULE_CONSENSUS_VERSION,
+                    "Unix time votes are not active yet"
+                );
+
+                let current_vote = dbtx.insert_entry(&UnixTimeVoteKey(peer_id), &vote).await;
+
+                ensure!(current_vote < Some(vote), "Unix time vote is redundant");
+
+                Ok(())
+            }
+            MintConsensusItem::ModuleConsensusVersion(module_consensus_version) => {
+                let current_vote = dbtx
+                    .get_value(&ConsensusVersionVoteKey(peer_id))
+                    .await
+                    .unwrap_or(self.genesis_consensus_version);
+
+                ensure!(
+                    module_consensus_version > current_vote,
+                    "Module consensus version vote is redundant"
+                );
+
+                dbtx.insert_entry(&ConsensusVersionVoteKey(peer_id), &module_consensus_version)
+                    .await;
+
+                assert!(
+                    self.consensus_module_consensus_version(dbtx).await <= MODULE_CONSENSUS_VERSION,
+                    "Mint module does not support new consensus version, please upgrade the module"
+                );
+
+                Ok(())
+            }
+            MintConsensusItem::LiabilityRoot(root) => {
+                ensure!(
+                    self.consensus_module_consensus_version(dbtx).await
+                        >= LIABILITY_COMMITMENT_MODULE_CONSENSUS_VERSION,
+                    "Liability commitments are not active yet"
+                );
+
+                ensure!(
+                    get_committed_liability_event_count(dbtx).await < root.events,
+                    "Liability root is redundant"
+                );
+
+                ensure!(
+                    get_liability_root(dbtx, root.events).await.as_ref() == Some(&root),
+                    "Liability root does not match our liabilities"
+                );
+
+                dbtx.insert_entry(&CommittedLiabilityRootKey, &root).await;
+
+                // Events before the committed root are never needed again,
+                // since later roots have to account for more events
+                let pruned = dbtx
+                    .find_by_prefix(&LiabilityEventPrefix)
+                    .await
+                    .map(|(key, _)| key)
+                    .filter(|key| std::future::ready(key.0 < root.events))
+                    .collect::<Vec<_>>()
+                    .await;
+
+                for key in pruned {
+                    dbtx.remove_entry(&key).await;
+                }
+
+                Ok(())
+            }
+            MintConsensusItem::Default { variant, .. } => {
+                bail!("Received unknown consensus item variant {variant}");
+            }
+        }
     }
 
     async fn process_input<'a, 'b, 'c>(
@@ -385,21 +789,44 @@ async fn process_input<'a, 'b, 'c>(
         input: &'b MintInput,
         _in_point: InPoint,
     ) -> Result<InputMeta, MintInputError> {
-        let input = input.ensure_v0_ref()?;
+        let note = input.note()?;
+
+        let pub_key = match input {
+            MintInput::V1(input) => {
+                if self.consensus_module_consensus_version(dbtx).await
+                    < SPENDING_CONDITIONS_MODULE_CONSENSUS_VERSION
+                {
+                    return Err(UnknownMintInputVariantError { variant: 1 }.into());
+                }
+
+                let nonce = input.condition.nonce(&input.salt);
+
+                if nonce.0 != note.nonce {
+                    return Err(SpendingConditionError::NonceMismatch.into());
+                }
+
+                input.condition.spend_key(
+                    &nonce,
+                    &input.witness,
+                    self.consensus_unix_time(dbtx).await,
+                )?
+            }
+            _ => note.nonce,
+        };
 
         let pk = self
             .cfg
             .consensus
             .tbs_agg_pks
-            .get(&input.note.denomination)
+            .get(&note.denomination)
             .ok_or(MintInputError::InvalidDenomination)?;
 
-        if !verify_note(input.note, *pk) {
+        if !verify_note(note, *pk) {
             return Err(MintInputError::InvalidSignature);
         }
 
         if dbtx
-            .insert_entry(&NonceKey(input.note.nonce), &())
+            .insert_entry(&NonceKey(note.nonce), &())
             .await
             .is_some()
         {
@@ -407,26 +834,28 @@ async fn process_input<'a, 'b, 'c>(
         }
 
         let new_count = dbtx
-            .remove_entry(&IssuanceCounterKey(input.note.denomination))
+            .remove_entry(&IssuanceCounterKey(note.denomination))
             .await
             .unwrap_or(0)
             .checked_sub(1)
             .expect("Failed to decrement issuance counter");
 
-        dbtx.insert_new_entry(&IssuanceCounterKey(input.note.denomination), &new_count)
+        dbtx.insert_new_entry(&IssuanceCounterKey(note.denomination), &new_count)
             .await;
 
+        redeem_liability(dbtx, note.amount()).await;
+
         let next_index = get_recovery_count(dbtx).await;
 
         dbtx.insert_new_entry(
             &RecoveryItemKey(next_index),
             &RecoveryItem::Input {
-                nonce_hash: input.note.nonce.consensus_hash(),
+                nonce_hash: note.nonce.consensus_hash(),
             },
         )
         .await;
 
-        let amount = input.note.amount();
+        let amount = note.amount();
         let unit = self.cfg.consensus.amount_unit;
 
         Ok(InputMeta {
@@ -434,7 +863,7 @@ async fn process_input<'a, 'b, 'c>(
                 amounts: Amounts::new_custom(unit, amount),
                 fees: Amounts::new_custom(unit, self.cfg.consensus.fee_consensus.fee(amount)),
             },
-            pub_key: input.note.nonce,
+            pub_key,
         })
     }
 
@@ -446,14 +875,22 @@ async fn process_output<'a, 'b>(
     ) -> Result<TransactionItemAmounts, MintOutputError> {
         let output = output.ensure_v0_ref()?;
 
-        let signature = self
+        let pk_share = *self
             .cfg
-            .private
-            .tbs_sks
+            .consensus
+            .tbs_pks
             .get(&output.denomination)
-            .map(|key| tbs::sign_message(output.nonce, *key))
+            .and_then(|pks| pks.get(&self.our_id))
             .ok_or(MintOutputError::InvalidDenomination)?;
 
+        let signature = blind_sign_with_guardian_signer(
+            &self.guardian_signer,
+            output.denomination,
+            output.nonce,
+            pk_share,
+        )
+        .await;
+
         // Store by outpoint for efficient range-based retrieval
         dbtx.insert_entry(&BlindedSignatureShareKey(outpoint), &signature)
             .await;
@@ -472,6 +909,8 @@ async fn process_output<'a, 'b>(
         dbtx.insert_new_entry(&IssuanceCounterKey(output.denomination), &new_count)
             .await;
 
+        append_liability(dbtx, output.nonce, output.amount()).await;
+
         let next_index = get_recovery_count(dbtx).await;
 
         dbtx.insert_new_entry(
@@ -561,6 +1000,40 @@ fn api_endpoints(&self) -> Vec<ApiEndpoint<Self>> {
                     Ok(get_recovery_count(&mut dbtx).await)
                 }
             },
+            api_endpoint! {
+                MODULE_CONSENSUS_VERSION_ENDPOINT,
+                ApiVersion::new(0, 1),
+                async |module: &Mint, context, _params: ()| -> ModuleConsensusVersion {
+                    let db = context.db();
+                    let mut dbtx = db.begin_transaction_nc().await;
+                    Ok(module.consensus_module_consensus_version(&mut dbtx).await)
+                }
+            },
+            api_endpoint! {
+                SUPPORTED_MODULE_CONSENSUS_VERSION_ENDPOINT,
+                ApiVersion::new(0, 1),
+                async |_module: &Mint, _context, _params: ()| -> ModuleConsensusVersion {
+                    Ok(MODULE_CONSENSUS_VERSION)
+                }
+            },
+            api_endpoint! {
+                LIABILITY_ROOT_ENDPOINT,
+                ApiVersion::new(0, 1),
+                async |_module: &Mint, context, _params: ()| -> Option<LiabilityRoot> {
+                    let db = context.db();
+                    let mut dbtx = db.begin_transaction_nc().await;
+                    Ok(dbtx.get_value(&CommittedLiabilityRootKey).await)
+                }
+            },
+            api_endpoint! {
+                LIABILITY_PROOF_ENDPOINT,
+                ApiVersion::new(0, 1),
+                async |_module: &Mint, context, message: tbs::BlindedMessage| -> Option<(LiabilityRoot, LiabilityProof)> {
+                    let db = context.db();
+                    let mut dbtx = db.begin_transaction_nc().await;
+                    Ok(get_liability_proof(&mut dbtx, message).await)
+                }
+            },
         ]
     }
 }
@@ -599,6 +1072,208 @@ async fn get_signature_shares_recovery(
     Ok(shares)
 }
 
+/// Appends the leaf for an issued note to the liability forest of its
+/// denomination and completes all parent nodes for which the leaf was the last
+/// missing descendant.
+async fn append_liability(
+    dbtx: &mut DatabaseTransaction<'_>,
+    message: tbs::BlindedMessage,
+    amount: Amount,
+) {
+    let index = record_liability_event(
+        dbtx,
+        LiabilityEvent {
+            denomination: amount,
+            redemption: false,
+        },
+    )
+    .await
+    .issued;
+
+    dbtx.insert_entry(&LiabilityLeafKey(message), &(amount, index))
+        .await;
+
+    let mut position = NodePosition::leaf(index);
+    let mut node = LiabilityNode::leaf(&message, amount);
+
+    dbtx.insert_new_entry(&LiabilityNodeKey(amount, position), &node)
+        .await;
+
+    while position.is_right_child() {
+        let sibling = dbtx
+            .get_value(&LiabilityNodeKey(amount, position.sibling()))
+            .await
+            .expect("Left sibling has been completed before");
+
+        node = LiabilityNode::parent(&sibling, &node).expect("Total issuance overflowed");
+        position = position.parent();
+
+        dbtx.insert_new_entry(&LiabilityNodeKey(amount, position), &node)
+            .await;
+    }
+}
+
+/// Counts a redeemed note towards the liabilities of its denomination. Since a
+/// spent note can not be linked to its issuance its leaf stays in the forest.
+async fn redeem_liability(dbtx: &mut DatabaseTransaction<'_>, amount: Amount) {
+    record_liability_event(
+        dbtx,
+        LiabilityEvent {
+            denomination: amount,
+            redemption: true,
+        },
+    )
+    .await;
+}
+
+/// Appends the event to the event log and updates the counts of its
+/// denomination, returning the counts before the event.
+async fn record_liability_event(
+    dbtx: &mut DatabaseTransaction<'_>,
+    event: LiabilityEvent,
+) -> LiabilityTier {
+    let index = get_liability_event_count(dbtx).await;
+
+    dbtx.insert_new_entry(&LiabilityEventKey(index), &event)
+        .await;
+
+    let tier = dbtx
+        .get_value(&LiabilityTierKey(event.denomination))
+        .await
+        .unwrap_or_default();
+
+    let mut updated = tier;
+
+    if event.redemption {
+        updated.redeemed += 1;
+    } else {
+        updated.issued += 1;
+    }
+
+    dbtx.insert_entry(&LiabilityTierKey(event.denomination), &updated)
+        .await;
+
+    tier
+}
+
+async fn get_liability_tiers(
+    dbtx: &mut DatabaseTransaction<'_>,
+) -> BTreeMap<Amount, LiabilityTier> {
+    dbtx.find_by_prefix(&LiabilityTierPrefix)
+        .await
+        .map(|(key, tier)| (key.0, tier))
+        .collect()
+        .await
+}
+
+async fn get_liability_event_count(dbtx: &mut DatabaseTransaction<'_>) -> u64 {
+    get_liability_tiers(dbtx)
+        .await
+        .values()
+        .map(|tier| tier.issued + tier.redeemed)
+        .sum()
+}
+
+async fn get_committed_liability_event_count(dbtx: &mut DatabaseTransaction<'_>) -> u64 {
+    dbtx.get_value(&CommittedLiabilityRootKey)
+        .await
+        .map_or(0, |root| root.events)
+}
+
+/// Computes the liability root after the first `events` liability events by
+/// reverting all later events from the current counts. Since the forests are
+/// append-only their peaks at the reverted counts are still stored. Returns
+/// `None` if we have not processed that many events yet or the later events
+/// have been pruned already.
+async fn get_liability_root(
+    dbtx: &mut DatabaseTransaction<'_>,
+    events: u64,
+) -> Option<LiabilityRoot> {
+    let mut tiers = get_liability_tiers(dbtx).await;
+
+    let later_events = tiers
+        .values()
+        .map(|tier| tier.issued + tier.redeemed)
+        .sum::<u64>()
+        .checked_sub(events)?;
+
+    let reverted = dbtx
+        .find_by_prefix_sorted_descending(&LiabilityEventPrefix)
+        .await
+        .take_while(|(key, _)| std::future::ready(events <= key.0))
+        .map(|(_, event)| event)
+        .collect::<Vec<_>>()
+        .await;
+
+    if reverted.len() as u64 != later_events {
+        return None;
+    }
+
+    for event in reverted {
+        let tier = tiers.get_mut(&event.denomination)?;
+
+        if event.redemption {
+            tier.redeemed = tier.redeemed.checked_sub(1)?;
+        } else {
+            tier.issued = tier.issued.checked_sub(1)?;
+        }
+    }
+
+    let mut liabilities = BTreeMap::new();
+
+    for (denomination, tier) in tiers {
+        if tier == LiabilityTier::default() {
+            continue;
+        }
+
+        let mut peaks = Vec::new();
+
+        for position in peak_positions(tier.issued) {
+            peaks.push(
+                dbtx.get_value(&LiabilityNodeKey(denomination, position))
+                    .await
+                    .expect("Peak has been completed"),
+            );
+        }
+
+        liabilities.insert(
+            denomination,
+            TierLiabilities {
+                issued: tier.issued,
+                redeemed: tier.redeemed,
+                peaks,
+            },
+        );
+    }
+
+    Some(LiabilityRoot {
+        events,
+        tiers: liabilities,
+    })
+}
+
+/// Proves the inclusion of a note in the last committed liability root, notes
+/// issued after it was committed can not be proven yet.
+async fn get_liability_proof(
+    dbtx: &mut DatabaseTransaction<'_>,
+    message: tbs::BlindedMessage,
+) -> Option<(LiabilityRoot, LiabilityProof)> {
+    let (denomination, index) = dbtx.get_value(&LiabilityLeafKey(message)).await?;
+
+    let root = dbtx.get_value(&CommittedLiabilityRootKey).await?;
+
+    let mut siblings = Vec::new();
+
+    for position in proof_positions(index, root.tiers.get(&denomination)?.issued)? {
+        siblings.push(
+            dbtx.get_value(&LiabilityNodeKey(denomination, position))
+                .await?,
+        );
+    }
+
+    Some((root, LiabilityProof { index, siblings }))
+}
+
 async fn get_recovery_count(dbtx: &mut DatabaseTransaction<'_>) -> u64 {
     dbtx.find_by_prefix_sorted_descending(&RecoveryItemPrefix)
         .await
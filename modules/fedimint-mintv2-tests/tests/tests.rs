@@ -1,4 +1,6 @@
+use std::collections::BTreeMap;
 use std::pin::pin;
+use std::sync::Arc;
 
 use anyhow::ensure;
 use async_stream::stream;
@@ -9,14 +11,19 @@
 use fedimint_core::base32::{self, FEDIMINT_PREFIX};
 use fedimint_core::core::OperationId;
 use fedimint_core::db::mem_impl::MemDatabase;
+use fedimint_core::secp256k1::rand::thread_rng;
+use fedimint_core::secp256k1::{Keypair, SECP256K1};
 use fedimint_dummy_client::{DummyClientInit, DummyClientModule};
 use fedimint_dummy_server::DummyInit;
 use fedimint_eventlog::{Event, EventLogEntry, EventLogId};
+use fedimint_mintv2_client::strategy::NoteSelectionStrategy;
 use fedimint_mintv2_client::{
     ECash, FinalReceiveOperationState, MintClientInit, MintClientModule, ReceivePaymentEvent,
     ReceivePaymentStatus, ReceivePaymentUpdateEvent, SendPaymentEvent,
 };
-use fedimint_mintv2_common::KIND;
+use fedimint_mintv2_common::condition::SpendingCondition;
+use fedimint_mintv2_common::config::{FeeConsensus, client_denominations};
+use fedimint_mintv2_common::{Denomination, KIND};
 use fedimint_mintv2_server::MintInit;
 use fedimint_testing::federation::FederationTest;
 use fedimint_testing::fixtures::Fixtures;
@@ -210,6 +217,126 @@ async fn test_client_recovery(
     Ok(())
 }
 
+/// Selects far more notes of the smallest denomination than the client holds
+#[derive(Debug)]
+struct OverspendingStrategy;
+
+impl NoteSelectionStrategy for OverspendingStrategy {
+    fn select_inputs(
+        &self,
+        _amount: Amount,
+        _held: &BTreeMap<Denomination, u64>,
+        _fee_consensus: &FeeConsensus,
+    ) -> Option<BTreeMap<Denomination, u64>> {
+        Some(BTreeMap::from([(
+            client_denominations().next().expect("Non-empty"),
+            1000,
+        )]))
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn strategy_selecting_notes_we_do_not_hold_fails_with_insufficient_funds()
+-> anyhow::Result<()> {
+    let fixtures = fixtures();
+    let fed = fixtures.new_fed_not_degraded().await;
+    let client = fed.new_client().await;
+
+    issue_ecash(&client, Amount::from_sats(10_000)).await?;
+
+    let balance = client.get_balance_for_btc().await?;
+
+    let module = client.get_first_module::<MintClientModule>()?;
+
+    module.set_custom_note_selection_strategy(Some(Arc::new(OverspendingStrategy)));
+
+    let condition = SpendingCondition::Multisig {
+        threshold: 1,
+        pubkeys: vec![Keypair::new(SECP256K1, &mut thread_rng()).public_key()],
+    };
+
+    let error = module
+        .send_conditional_notes(Amount::from_sats(1_000), condition, Value::Null)
+        .await
+        .expect_err("Funding with notes we don't hold must fail");
+
+    ensure!(
+        format!("{error:#}").contains("Insufficient funds"),
+        "Unexpected error: {error:#}"
+    );
+
+    module.set_custom_note_selection_strategy(None);
+
+    assert_eq!(client.get_balance_for_btc().await?, balance);
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn multisig_notes_are_redeemed_with_threshold_of_delegations() -> anyhow::Result<()> {
+    let fixtures = fixtures();
+    let fed = fixtures.new_fed_not_degraded().await;
+
+    let (client_send, client_receive) = fed.two_clients().await;
+
+    issue_ecash(&client_send, Amount::from_sats(10_000)).await?;
+
+    let signers = (0..3)
+        .map(|_| Keypair::new(SECP256K1, &mut thread_rng()))
+        .collect::<Vec<Keypair>>();
+
+    let condition = SpendingCondition::Multisig {
+        threshold: 2,
+        pubkeys: signers.iter().map(Keypair::public_key).collect(),
+    };
+
+    let send_module = client_send.get_first_module::<MintClientModule>()?;
+
+    let operation_id = send_module
+        .send_conditional_notes(Amount::from_sats(1_000), condition, Value::Null)
+        .await?;
+
+    let notes = send_module.await_conditional_notes(operation_id).await?;
+
+    assert_eq!(notes.total_amount(), Amount::from_sats(1_000));
+
+    let delegate = Keypair::new(SECP256K1, &mut thread_rng());
+
+    let delegations = [&signers[0], &signers[2]]
+        .into_iter()
+        .map(|signer| notes.delegate(delegate.public_key(), signer))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    let receive_module = client_receive.get_first_module::<MintClientModule>()?;
+
+    assert!(
+        receive_module
+            .redeem_multisig_notes(
+                notes.clone(),
+                delegations[..1].to_vec(),
+                delegate.secret_key(),
+                Value::Null,
+            )
+            .await
+            .is_err()
+    );
+
+    let operation_id = receive_module
+        .redeem_multisig_notes(notes, delegations, delegate.secret_key(), Value::Null)
+        .await?;
+
+    assert_eq!(
+        receive_module
+            .await_final_receive_operation_state(operation_id)
+            .await,
+        FinalReceiveOperationState::Success
+    );
+
+    ensure!(client_receive.get_balance_for_btc().await? >= Amount::from_sats(990));
+
+    Ok(())
+}
+
 #[tokio::test(flavor = "multi_thread")]
 async fn double_spend_is_rejected() -> anyhow::Result<()> {
     let fixtures = fixtures();
@@ -12,9 +12,9 @@
     ACTIVATE_CONSENSUS_VERSION_VOTING_ENDPOINT, BITCOIN_KIND_ENDPOINT, BITCOIN_RPC_CONFIG_ENDPOINT,
     BLOCK_COUNT_ENDPOINT, BLOCK_COUNT_LOCAL_ENDPOINT, MODULE_CONSENSUS_VERSION_ENDPOINT,
     PEG_OUT_FEES_ENDPOINT, RECOVERY_COUNT_ENDPOINT, RECOVERY_SLICE_ENDPOINT,
-    UTXO_CONFIRMED_ENDPOINT, WALLET_SUMMARY_ENDPOINT,
+    UTXO_CONFIRMED_ENDPOINT, WALLET_SUMMARY_ENDPOINT, WATCH_ONLY_DESCRIPTORS_ENDPOINT,
 };
-use fedimint_wallet_common::{PegOutFees, RecoveryItem, WalletSummary};
+use fedimint_wallet_common::{PegOutFees, RecoveryItem, TweakedDescriptor, WalletSummary};
 
 #[apply(async_trait_maybe_send!)]
 pub trait WalletFederationApi {
@@ -34,6 +34,8 @@ async fn fetch_peg_out_fees(
 
     async fn fetch_wallet_summary(&self) -> FederationResult<WalletSummary>;
 
+    async fn fetch_watch_only_descriptors(&self) -> FederationResult<Vec<TweakedDescriptor>>;
+
     async fn fetch_block_count_local(&self) -> FederationResult<u32>;
 
     async fn is_utxo_confirmed(&self, outpoint: bitcoin::OutPoint) -> FederationResult<bool>;
@@ -165,6 +167,14 @@ async fn fetch_wallet_summary(&self) -> FederationResult<WalletSummary> {
         .await
     }
 
+    async fn fetch_watch_only_descriptors(&self) -> FederationResult<Vec<TweakedDescriptor>> {
+        self.request_current_consensus(
+            WATCH_ONLY_DESCRIPTORS_ENDPOINT.to_string(),
+            ApiRequestErased::default(),
+        )
+        .await
+    }
+
     async fn activate_consensus_version_voting(&self, auth: ApiAuth) -> FederationResult<()> {
         self.request_admin(
             ACTIVATE_CONSENSUS_VERSION_VOTING_ENDPOINT,
@@ -0,0 +1,85 @@
+use std::fmt::Write as _;
+
+use bitcoin::{Address, Amount, Denomination};
+
+/// Builds a [BIP21](https://github.com/bitcoin/bips/blob/master/bip-0021.mediawiki)
+/// payment URI for a deposit address, optionally requesting an amount and
+/// attaching a label the payer's wallet can display. BIP78 payjoin is not
+/// supported, hence the URI never carries a `pj` endpoint.
+pub fn deposit_uri(address: &Address, amount: Option<Amount>, label: Option<&str>) -> String {
+    let mut uri = format!("bitcoin:{address}");
+    let mut separator = '?';
+
+    if let Some(amount) = amount {
+        write!(
+            uri,
+            "{separator}amount={}",
+            amount.to_string_in(Denomination::Bitcoin)
+        )
+        .expect("Writing to a string can't fail");
+        separator = '&';
+    }
+
+    if let Some(label) = label {
+        write!(uri, "{separator}label={}", percent_encode(label))
+            .expect("Writing to a string can't fail");
+    }
+
+    uri
+}
+
+/// Percent-encodes everything except the unreserved characters of RFC 3986
+fn percent_encode(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+
+    for byte in value.bytes() {
+        if byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'.' | b'_' | b'~') {
+            encoded.push(char::from(byte));
+        } else {
+            write!(encoded, "%{byte:02X}").expect("Writing to a string can't fail");
+        }
+    }
+
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use bitcoin::address::NetworkUnchecked;
+
+    use super::*;
+
+    #[test]
+    fn test_deposit_uri() {
+        let address =
+            Address::<NetworkUnchecked>::from_str("bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq")
+                .unwrap()
+                .assume_checked();
+
+        assert_eq!(
+            deposit_uri(&address, None, None),
+            "bitcoin:bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq"
+        );
+
+        assert_eq!(
+            deposit_uri(&address, Some(Amount::from_sat(150_000)), None),
+            "bitcoin:bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq?amount=0.0015"
+        );
+
+        assert_eq!(
+            deposit_uri(&address, None, Some("Savings")),
+            "bitcoin:bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq?label=Savings"
+        );
+
+        assert_eq!(
+            deposit_uri(
+                &address,
+                Some(Amount::from_sat(100_000_000)),
+                Some("Rent & bills/März")
+            ),
+            "bitcoin:bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq?amount=1&label=Rent%20%26%20bills%2FM%C3%A4rz"
+        );
+    }
+}
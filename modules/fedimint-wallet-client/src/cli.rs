@@ -14,6 +14,7 @@
 use super::WalletClientModule;
 use crate::WithdrawState;
 use crate::api::WalletFederationApi;
+use crate::bip21;
 use crate::client_db::TweakIdx;
 
 #[derive(Parser, Serialize)]
@@ -37,7 +38,20 @@ enum Opts {
     /// Returns the Bitcoin RPC kind and URL, if authenticated
     GetBitcoinRpcConfig,
 
-    NewDepositAddress,
+    /// Allocate a new deposit address and a BIP21 URI paying to it
+    NewDepositAddress {
+        /// Label shown to the payer and attached to the deposit events
+        #[arg(long)]
+        label: Option<String>,
+        /// Amount requested from the payer in the BIP21 URI
+        #[arg(long)]
+        amount: Option<bitcoin::Amount>,
+    },
+    /// Set or, if no label is given, remove the label of a deposit address
+    SetDepositLabel {
+        tweak_idx: TweakIdx,
+        label: Option<String>,
+    },
     /// Withdraw funds from the federation
     Withdraw {
         #[clap(long)]
@@ -200,17 +214,24 @@ pub(crate) async fn handle_cli_command(
             }
             serde_json::Value::Bool(true)
         }
-        Opts::NewDepositAddress => {
-            let (operation_id, address, tweak_idx) =
-                module.allocate_deposit_address_expert_only(()).await?;
+        Opts::NewDepositAddress { label, amount } => {
+            let (operation_id, address, tweak_idx) = module
+                .allocate_labeled_deposit_address(label.clone(), ())
+                .await?;
+            let uri = bip21::deposit_uri(&address, amount, label.as_deref());
             serde_json::json! {
                 {
                     "address": address,
+                    "uri": uri,
                     "operation_id": operation_id,
                     "tweak_idx": tweak_idx.0
                 }
             }
         }
+        Opts::SetDepositLabel { tweak_idx, label } => {
+            module.set_deposit_label(tweak_idx, label).await?;
+            serde_json::Value::Bool(true)
+        }
         Opts::Withdraw { amount, address } => return withdraw(module, amount, address).await,
     };
 
@@ -20,6 +20,7 @@ pub enum DbKeyPrefix {
     RecoveryFinalized = 0x2f,
     RecoveryState = 0x30,
     SupportsSafeDeposit = 0x31,
+    PegInLabel = 0x32,
     /// Prefixes between 0xb0..=0xcf shall all be considered allocated for
     /// historical and future external use
     ExternalReservedStart = 0xb0,
@@ -143,6 +144,22 @@ pub struct PegInTweakIndexData {
     query_prefix = PegInTweakIndexPrefix
 );
 
+/// A user provided label of a peg-in address, kept separate from
+/// [`PegInTweakIndexData`] so existing records stay decodable
+#[derive(Clone, Debug, Encodable, Decodable, Serialize)]
+pub struct PegInLabelKey(pub TweakIdx);
+
+#[derive(Clone, Debug, Encodable, Decodable, Serialize)]
+pub struct PegInLabelPrefix;
+
+impl_db_record!(
+    key = PegInLabelKey,
+    value = String,
+    db_prefix = DbKeyPrefix::PegInLabel,
+);
+
+impl_db_lookup!(key = PegInLabelKey, query_prefix = PegInLabelPrefix);
+
 #[derive(Clone, Debug, Encodable, Decodable, Serialize)]
 pub struct ClaimedPegInKey {
     pub peg_in_index: TweakIdx,
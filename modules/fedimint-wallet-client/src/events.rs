@@ -29,6 +29,10 @@ pub struct DepositConfirmed {
 
     /// The amount being deposited
     pub amount: Amount,
+
+    /// The label of the deposit address at the time of the deposit
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
 }
 
 impl Event for DepositConfirmed {
@@ -6,6 +6,7 @@
 #![allow(clippy::must_use_candidate)]
 
 pub mod api;
+pub mod bip21;
 #[cfg(feature = "cli")]
 mod cli;
 
@@ -80,9 +81,9 @@
 use crate::api::WalletFederationApi;
 use crate::backup::{FEDERATION_RECOVER_MAX_GAP, RecoveryStateV2, WalletRecovery};
 use crate::client_db::{
-    ClaimedPegInData, ClaimedPegInKey, ClaimedPegInPrefix, NextPegInTweakIndexKey,
-    PegInTweakIndexData, PegInTweakIndexPrefix, RecoveryFinalizedKey, RecoveryStateKey,
-    SupportsSafeDepositPrefix,
+    ClaimedPegInData, ClaimedPegInKey, ClaimedPegInPrefix, NextPegInTweakIndexKey, PegInLabelKey,
+    PegInLabelPrefix, PegInTweakIndexData, PegInTweakIndexPrefix, RecoveryFinalizedKey,
+    RecoveryStateKey, SupportsSafeDepositPrefix,
 };
 use crate::deposit::DepositStateMachine;
 use crate::withdraw::{CreatedWithdrawState, WithdrawStateMachine, WithdrawStates};
@@ -285,6 +286,16 @@ async fn dump_database(
                         "Supports Safe Deposit"
                     );
                 }
+                DbKeyPrefix::PegInLabel => {
+                    push_db_pair_items!(
+                        dbtx,
+                        PegInLabelPrefix,
+                        PegInLabelKey,
+                        String,
+                        wallet_client_items,
+                        "Peg-In Label"
+                    );
+                }
                 DbKeyPrefix::RecoveryState
                 | DbKeyPrefix::ExternalReservedStart
                 | DbKeyPrefix::CoreInternalReservedStart
@@ -754,6 +765,7 @@ pub fn get_fee_consensus(&self) -> FeeConsensus {
     async fn allocate_deposit_address_inner(
         &self,
         dbtx: &mut DatabaseTransaction<'_>,
+        label: Option<String>,
     ) -> (OperationId, Address, TweakIdx) {
         dbtx.ensure_isolated().expect("Must be isolated db");
 
@@ -775,6 +787,10 @@ async fn allocate_deposit_address_inner(
         )
         .await;
 
+        if let Some(label) = label {
+            dbtx.insert_entry(&PegInLabelKey(tweak_idx), &label).await;
+        }
+
         (operation_id, address, tweak_idx)
     }
 
@@ -800,6 +816,37 @@ pub async fn get_wallet_summary(&self) -> anyhow::Result<WalletSummary> {
         Ok(self.module_api.fetch_wallet_summary().await?)
     }
 
+    /// Fetch the descriptors of all tweaks that hold funds of the federation,
+    /// such that they can be imported into a watch-only wallet. Every
+    /// descriptor is checked against the one derived from our config, hence we
+    /// do not have to trust the federation for them.
+    pub async fn watch_only_descriptors(&self) -> anyhow::Result<Vec<TweakedDescriptor>> {
+        let descriptors = self.module_api.fetch_watch_only_descriptors().await?;
+
+        for descriptor in &descriptors {
+            ensure!(
+                descriptor.descriptor
+                    == self
+                        .cfg
+                        .peg_in_descriptor
+                        .tweak(&descriptor.tweak, SECP256K1)
+                        .to_string(),
+                "Federation returned an invalid descriptor for tweak {}",
+                descriptor.tweak.consensus_encode_to_hex()
+            );
+        }
+
+        Ok(descriptors)
+    }
+
+    /// Returns the script of the federation wallet for the given tweak.
+    pub fn tweaked_script(&self, tweak: &[u8; 33]) -> ScriptBuf {
+        self.cfg
+            .peg_in_descriptor
+            .tweak(tweak, SECP256K1)
+            .script_pubkey()
+    }
+
     pub async fn get_block_count_local(&self) -> anyhow::Result<u32> {
         Ok(self.module_api.fetch_block_count_local().await?)
     }
@@ -979,6 +1026,49 @@ pub async fn allocate_deposit_address_expert_only<M>(
         &self,
         extra_meta: M,
     ) -> anyhow::Result<(OperationId, Address, TweakIdx)>
+    where
+        M: Serialize + MaybeSend + MaybeSync,
+    {
+        self.allocate_labeled_deposit_address(None, extra_meta)
+            .await
+    }
+
+    /// Allocates a deposit address like [`Self::safe_allocate_deposit_address`]
+    /// and attaches `label` to it in the same database transaction. Returns a
+    /// BIP21 payment URI for the address that includes the label and, if
+    /// given, the amount to pay, see [`Self::deposit_uri`].
+    pub async fn safe_allocate_deposit_uri<M>(
+        &self,
+        label: Option<String>,
+        amount: Option<bitcoin::Amount>,
+        extra_meta: M,
+    ) -> anyhow::Result<(OperationId, String, TweakIdx)>
+    where
+        M: Serialize + MaybeSend + MaybeSync,
+    {
+        ensure!(
+            self.supports_safe_deposit().await,
+            "Wallet module consensus version doesn't support safe deposits",
+        );
+
+        let (operation_id, address, tweak_idx) = self
+            .allocate_labeled_deposit_address(label.clone(), extra_meta)
+            .await?;
+
+        Ok((
+            operation_id,
+            bip21::deposit_uri(&address, amount, label.as_deref()),
+            tweak_idx,
+        ))
+    }
+
+    /// Allocates a deposit address with an optional label, see
+    /// [`Self::allocate_deposit_address_expert_only`] for its limitations.
+    pub(crate) async fn allocate_labeled_deposit_address<M>(
+        &self,
+        label: Option<String>,
+        extra_meta: M,
+    ) -> anyhow::Result<(OperationId, Address, TweakIdx)>
     where
         M: Serialize + MaybeSend + MaybeSync,
     {
@@ -989,9 +1079,10 @@ pub async fn allocate_deposit_address_expert_only<M>(
             .autocommit(
                 move |dbtx, _| {
                     let extra_meta_value_inner = extra_meta_value.clone();
+                    let label = label.clone();
                     Box::pin(async move {
                         let (operation_id, address, tweak_idx) = self
-                            .allocate_deposit_address_inner(dbtx)
+                            .allocate_deposit_address_inner(dbtx, label)
                             .await;
 
                         self.client_ctx.manual_operation_start_dbtx(
@@ -1223,6 +1314,60 @@ pub async fn get_pegin_tweak_idx(
             .ok_or_else(|| anyhow::format_err!("TweakIdx not found"))
     }
 
+    /// Attaches a label to a deposit address, replacing any previous label, or
+    /// removes it if `label` is `None`. Labels are only stored locally and are
+    /// not part of the client's backup.
+    pub async fn set_deposit_label(
+        &self,
+        tweak_idx: TweakIdx,
+        label: Option<String>,
+    ) -> anyhow::Result<()> {
+        let mut dbtx = self.client_ctx.module_db().begin_transaction().await;
+
+        ensure!(
+            dbtx.get_value(&PegInTweakIndexKey(tweak_idx))
+                .await
+                .is_some(),
+            "TweakIdx not found"
+        );
+
+        match label {
+            Some(label) => dbtx.insert_entry(&PegInLabelKey(tweak_idx), &label).await,
+            None => dbtx.remove_entry(&PegInLabelKey(tweak_idx)).await,
+        };
+
+        dbtx.commit_tx().await;
+
+        Ok(())
+    }
+
+    pub async fn get_deposit_label(&self, tweak_idx: TweakIdx) -> Option<String> {
+        self.client_ctx
+            .module_db()
+            .begin_transaction_nc()
+            .await
+            .get_value(&PegInLabelKey(tweak_idx))
+            .await
+    }
+
+    /// Returns a BIP21 payment URI for a previously allocated deposit address
+    /// that includes its label, if any, and optionally the amount to pay.
+    ///
+    /// The peg-in fee is not added to the amount, callers requesting a
+    /// specific amount of ecash need to account for it themselves.
+    pub async fn deposit_uri(
+        &self,
+        tweak_idx: TweakIdx,
+        amount: Option<bitcoin::Amount>,
+    ) -> anyhow::Result<String> {
+        self.get_pegin_tweak_idx(tweak_idx).await?;
+
+        let (_, _, address, _) = self.data.derive_deposit_address(tweak_idx);
+        let label = self.get_deposit_label(tweak_idx).await;
+
+        Ok(bip21::deposit_uri(&address, amount, label.as_deref()))
+    }
+
     pub async fn get_claimed_pegins(
         &self,
         dbtx: &mut DatabaseTransaction<'_>,
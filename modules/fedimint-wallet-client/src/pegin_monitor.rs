@@ -27,7 +27,7 @@
 
 use crate::api::WalletFederationApi as _;
 use crate::client_db::{
-    ClaimedPegInData, ClaimedPegInKey, PegInTweakIndexData, PegInTweakIndexKey,
+    ClaimedPegInData, ClaimedPegInKey, PegInLabelKey, PegInTweakIndexData, PegInTweakIndexKey,
     PegInTweakIndexPrefix, TweakIdx,
 };
 use crate::events::{DepositConfirmed, ReceivePaymentEvent};
@@ -417,6 +417,7 @@ async fn claim_peg_in_inner(
         dbtx: &mut DatabaseTransaction<'_>,
         btc_transaction: &bitcoin::Transaction,
         out_idx: u32,
+        tweak_idx: TweakIdx,
         tweak_key: Keypair,
         txout_proof: TxOutProof,
         operation_id: OperationId,
@@ -449,6 +450,7 @@ async fn claim_peg_in_inner(
         }
 
         let txid = btc_transaction.compute_txid();
+        let label = dbtx.get_value(&PegInLabelKey(tweak_idx)).await;
 
         client_ctx
             .log_event(
@@ -457,6 +459,7 @@ async fn claim_peg_in_inner(
                     txid,
                     out_idx,
                     amount,
+                    label,
                 },
             )
             .await;
@@ -498,6 +501,7 @@ async fn claim_peg_in_inner(
                         dbtx,
                         transaction,
                         out_point.vout,
+                        tweak_idx,
                         tweak_key,
                         tx_out_proof.clone(),
                         operation_id,
@@ -32,8 +32,10 @@ pub struct WalletConfigLocal {
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct WalletConfigPrivate {
-    /// Secret key for signing bitcoin multisig transactions
-    pub peg_in_key: SecretKey,
+    /// Secret key for signing bitcoin multisig transactions, missing once it
+    /// has been exported to a remote guardian signer
+    #[serde(default)]
+    pub peg_in_key: Option<SecretKey>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, Encodable, Decodable)]
@@ -131,7 +133,9 @@ pub fn new(
         };
 
         Self {
-            private: WalletConfigPrivate { peg_in_key: sk },
+            private: WalletConfigPrivate {
+                peg_in_key: Some(sk),
+            },
             consensus: WalletConfigConsensus {
                 network: NetworkLegacyEncodingWrapper(network),
                 peg_in_descriptor,
@@ -10,3 +10,4 @@
 pub const UTXO_CONFIRMED_ENDPOINT: &str = "utxo_confirmed";
 pub const RECOVERY_COUNT_ENDPOINT: &str = "recovery_count";
 pub const RECOVERY_SLICE_ENDPOINT: &str = "recovery_slice";
+pub const WATCH_ONLY_DESCRIPTORS_ENDPOINT: &str = "watch_only_descriptors";
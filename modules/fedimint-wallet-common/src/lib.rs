@@ -219,6 +219,16 @@ pub fn pending_change_utxos(&self) -> Vec<TxOutputSummary> {
     }
 }
 
+/// The descriptor of the federation wallet for one of the tweaks that holds or
+/// will hold funds of the federation.
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Deserialize, Serialize, Encodable, Decodable)]
+pub struct TweakedDescriptor {
+    #[serde(with = "::fedimint_core::encoding::as_hex")]
+    pub tweak: [u8; 33],
+    /// The descriptor including its checksum
+    pub descriptor: String,
+}
+
 /// Recovery data for slice-based client recovery
 #[derive(Debug, Clone, Eq, PartialEq, Hash, Deserialize, Serialize, Encodable, Decodable)]
 pub enum RecoveryItem {
@@ -20,15 +20,22 @@ fn tweak<Ctx: Verification + Signing, Ctr: Contract>(
     ) -> Self;
 }
 
+/// The scalar added to the key pair with public key `pub_key` when tweaking
+/// it with a `tweak` contract, such that the secret key can be tweaked by a
+/// signer that only reveals the public key
+pub fn contract_tweak<Ctr: Contract>(pub_key: &PublicKey, tweak: &Ctr) -> [u8; 32] {
+    let mut hasher = HmacEngine::<sha256::Hash>::new(&pub_key.serialize()[..]);
+    tweak.encode(&mut hasher).expect("hashing is infallible");
+    Hmac::from_engine(hasher).to_byte_array()
+}
+
 impl Tweakable for PublicKey {
     fn tweak<Ctx: Verification + Signing, Ctr: Contract>(
         &self,
         tweak: &Ctr,
         secp: &Secp256k1<Ctx>,
     ) -> Self {
-        let mut hasher = HmacEngine::<sha256::Hash>::new(&self.serialize()[..]);
-        tweak.encode(&mut hasher).expect("hashing is infallible");
-        let tweak = Hmac::from_engine(hasher).to_byte_array();
+        let tweak = contract_tweak(self, tweak);
 
         self.add_exp_tweak(secp, &Scalar::from_be_bytes(tweak).expect("can't fail"))
             .expect("tweak is always 32 bytes, other failure modes are negligible")
@@ -41,13 +48,7 @@ fn tweak<Ctx: Verification + Signing, Ctr: Contract>(
         tweak_in: &Ctr,
         secp: &Secp256k1<Ctx>,
     ) -> Self {
-        let pub_key = PublicKey::from_secret_key(secp, self);
-
-        let tweak = {
-            let mut hasher = HmacEngine::<sha256::Hash>::new(&pub_key.serialize()[..]);
-            tweak_in.encode(&mut hasher).expect("hashing is infallible");
-            Hmac::from_engine(hasher).to_byte_array()
-        };
+        let tweak = contract_tweak(&PublicKey::from_secret_key(secp, self), tweak_in);
 
         self.add_tweak(&Scalar::from_be_bytes(tweak).expect("can't fail"))
             .expect("Tweaking priv key failed") // TODO: why could this happen?
@@ -76,6 +76,7 @@
 use fedimint_logging::LOG_MODULE_WALLET;
 use fedimint_server_core::bitcoin_rpc::ServerBitcoinRpcMonitor;
 use fedimint_server_core::config::{PeerHandleOps, PeerHandleOpsExt};
+use fedimint_server_core::guardian_signer::{GuardianSecret, ModuleGuardianSigner};
 use fedimint_server_core::migration::ServerModuleDbMigrationFn;
 use fedimint_server_core::{
     ConfigGenModuleArgs, EnvVarDoc, ServerModule, ServerModuleInit, ServerModuleInitArgs,
@@ -87,13 +88,14 @@
     BLOCK_COUNT_ENDPOINT, BLOCK_COUNT_LOCAL_ENDPOINT, MODULE_CONSENSUS_VERSION_ENDPOINT,
     PEG_OUT_FEES_ENDPOINT, RECOVERY_COUNT_ENDPOINT, RECOVERY_SLICE_ENDPOINT,
     SUPPORTED_MODULE_CONSENSUS_VERSION_ENDPOINT, UTXO_CONFIRMED_ENDPOINT, WALLET_SUMMARY_ENDPOINT,
+    WATCH_ONLY_DESCRIPTORS_ENDPOINT,
 };
 use fedimint_wallet_common::envs::FM_PORT_ESPLORA_ENV;
 use fedimint_wallet_common::keys::CompressedPublicKey;
-use fedimint_wallet_common::tweakable::Tweakable;
+use fedimint_wallet_common::tweakable::{Tweakable, contract_tweak};
 use fedimint_wallet_common::{
-    MODULE_CONSENSUS_VERSION, Rbf, RecoveryItem, UnknownWalletInputVariantError, WalletInputError,
-    WalletOutputError, WalletOutputV0,
+    MODULE_CONSENSUS_VERSION, Rbf, RecoveryItem, TweakedDescriptor, UnknownWalletInputVariantError,
+    WalletInputError, WalletOutputError, WalletOutputV0,
 };
 use futures::future::join_all;
 use futures::{FutureExt, StreamExt};
@@ -327,7 +329,7 @@ fn supported_api_versions(&self) -> SupportedModuleApiVersions {
                 MODULE_CONSENSUS_VERSION.major,
                 MODULE_CONSENSUS_VERSION.minor,
             ),
-            &[(0, 2)],
+            &[(0, 3)],
         )
     }
 
@@ -378,6 +380,7 @@ async fn init(&self, args: &ServerModuleInitArgs<Self>) -> anyhow::Result<Self::
             args.our_peer_id(),
             args.module_api().clone(),
             args.server_bitcoin_rpc_monitor(),
+            args.guardian_signer().clone(),
         )
         .await?)
     }
@@ -454,7 +457,14 @@ async fn distributed_gen(
 
     fn validate_config(&self, identity: &PeerId, config: ServerModuleConfig) -> anyhow::Result<()> {
         let config = config.to_typed::<WalletConfig>()?;
-        let pubkey = secp256k1::PublicKey::from_secret_key_global(&config.private.peg_in_key);
+
+        // The secret key is missing once it has been exported to a remote
+        // guardian signer, which verifies it on startup instead
+        let Some(peg_in_key) = config.private.peg_in_key else {
+            return Ok(());
+        };
+
+        let pubkey = secp256k1::PublicKey::from_secret_key_global(&peg_in_key);
 
         if config
             .consensus
@@ -469,6 +479,19 @@ fn validate_config(&self, identity: &PeerId, config: ServerModuleConfig) -> anyh
         Ok(())
     }
 
+    fn guardian_keys(
+        &self,
+        config: &ServerModuleConfig,
+    ) -> anyhow::Result<BTreeMap<String, GuardianSecret>> {
+        Ok(config
+            .to_typed::<WalletConfig>()?
+            .private
+            .peg_in_key
+            .into_iter()
+            .map(|sk| (PEG_IN_KEY_NAME.to_string(), GuardianSecret::Secp256k1(sk)))
+            .collect())
+    }
+
     fn get_client_config(
         &self,
         config: &ServerModuleConsensusConfig,
@@ -866,7 +889,7 @@ async fn process_output<'a, 'b>(
 
         StatelessWallet::validate_tx(&tx, output, fee_rate, self.cfg.consensus.network.0)?;
 
-        self.offline_wallet().sign_psbt(&mut tx.psbt);
+        self.sign_psbt(&mut tx.psbt).await;
 
         let txid = tx.psbt.unsigned_tx.compute_txid();
 
@@ -1057,6 +1080,15 @@ fn api_endpoints(&self) -> Vec<ApiEndpoint<Self>> {
                     Ok(module.get_wallet_summary(&mut dbtx).await)
                 }
             },
+            api_endpoint! {
+                WATCH_ONLY_DESCRIPTORS_ENDPOINT,
+                ApiVersion::new(0, 3),
+                async |module: &Wallet, context, _params: ()| -> Vec<TweakedDescriptor> {
+                    let db = context.db();
+                    let mut dbtx = db.begin_transaction_nc().await;
+                    Ok(module.watch_only_descriptors(&mut dbtx).await)
+                }
+            },
             api_endpoint! {
                 MODULE_CONSENSUS_VERSION_ENDPOINT,
                 ApiVersion::new(0, 2),
@@ -1170,6 +1202,9 @@ fn calculate_pegout_metrics(
     });
 }
 
+/// Name of the peg-in key in the guardian signer
+const PEG_IN_KEY_NAME: &str = "peg_in_key";
+
 #[derive(Debug)]
 pub struct Wallet {
     cfg: WalletConfig,
@@ -1177,6 +1212,8 @@ pub struct Wallet {
     secp: Secp256k1<All>,
     btc_rpc: ServerBitcoinRpcMonitor,
     our_peer_id: PeerId,
+    /// Signs our inputs of peg-out transactions with our tweaked peg-in key
+    guardian_signer: ModuleGuardianSigner,
     /// Broadcasting pending txes can be triggered immediately with this
     broadcast_pending: Arc<Notify>,
     task_group: TaskGroup,
@@ -1194,6 +1231,7 @@ pub async fn new(
         our_peer_id: PeerId,
         module_api: DynModuleApi,
         server_bitcoin_rpc_monitor: ServerBitcoinRpcMonitor,
+        guardian_signer: ModuleGuardianSigner,
     ) -> anyhow::Result<Wallet> {
         let broadcast_pending = Arc::new(Notify::new());
         Self::spawn_broadcast_pending_task(
@@ -1223,6 +1261,7 @@ pub async fn new(
             secp: Default::default(),
             btc_rpc: server_bitcoin_rpc_monitor,
             our_peer_id,
+            guardian_signer,
             task_group: task_group.clone(),
             peer_supported_consensus_version,
             broadcast_pending,
@@ -1801,6 +1840,64 @@ fn partition_peg_out_and_change(
         }
     }
 
+    /// Returns the descriptors of all tweaks that hold funds of the federation
+    /// on-chain or will do so once our pending transactions confirm. This
+    /// includes the UTXOs spent by pending transactions since they remain
+    /// on-chain until their spending transaction confirms.
+    async fn watch_only_descriptors(
+        &self,
+        dbtx: &mut DatabaseTransaction<'_>,
+    ) -> Vec<TweakedDescriptor> {
+        let mut tweaks = self
+            .available_utxos(dbtx)
+            .await
+            .into_iter()
+            .map(|(_, utxo)| utxo.tweak)
+            .collect::<BTreeSet<[u8; 33]>>();
+
+        for unsigned in dbtx
+            .find_by_prefix(&UnsignedTransactionPrefixKey)
+            .await
+            .map(|(_, unsigned)| unsigned)
+            .collect::<Vec<UnsignedTransaction>>()
+            .await
+        {
+            tweaks.extend(unsigned.selected_utxos.iter().map(|(_, utxo)| utxo.tweak));
+
+            tweaks.extend(unsigned.psbt.outputs.iter().filter_map(|output| {
+                output
+                    .proprietary
+                    .get(&proprietary_tweak_key())
+                    .and_then(|tweak| <[u8; 33]>::try_from(tweak.as_slice()).ok())
+            }));
+        }
+
+        for pending in dbtx
+            .find_by_prefix(&PendingTransactionPrefixKey)
+            .await
+            .map(|(_, pending)| pending)
+            .collect::<Vec<PendingTransaction>>()
+            .await
+        {
+            tweaks.extend(pending.selected_utxos.iter().map(|(_, utxo)| utxo.tweak));
+
+            tweaks.insert(pending.tweak);
+        }
+
+        tweaks
+            .into_iter()
+            .map(|tweak| TweakedDescriptor {
+                tweak,
+                descriptor: self
+                    .cfg
+                    .consensus
+                    .peg_in_descriptor
+                    .tweak(&tweak, &self.secp)
+                    .to_string(),
+            })
+            .collect()
+    }
+
     async fn is_utxo_confirmed(
         &self,
         dbtx: &mut DatabaseTransaction<'_>,
@@ -1812,11 +1909,101 @@ async fn is_utxo_confirmed(
     fn offline_wallet(&'_ self) -> StatelessWallet<'_> {
         StatelessWallet {
             descriptor: &self.cfg.consensus.peg_in_descriptor,
-            secret_key: &self.cfg.private.peg_in_key,
             secp: &self.secp,
         }
     }
 
+    /// Signs all inputs of the psbt with our peg-in key tweaked by the
+    /// contract of the spent output
+    async fn sign_psbt(&self, psbt: &mut Psbt) {
+        let our_key = self.cfg.consensus.peer_peg_in_keys[&self.our_peer_id].key;
+
+        let mut tx_hasher = SighashCache::new(&psbt.unsigned_tx);
+
+        for (idx, psbt_input) in psbt.inputs.iter_mut().enumerate() {
+            let tweak = psbt_input
+                .proprietary
+                .get(&proprietary_tweak_key())
+                .expect("Malformed PSBT: expected tweak");
+
+            let tx_hash = tx_hasher
+                .p2wsh_signature_hash(
+                    idx,
+                    psbt_input
+                        .witness_script
+                        .as_ref()
+                        .expect("Missing witness script"),
+                    psbt_input
+                        .witness_utxo
+                        .as_ref()
+                        .expect("Missing UTXO")
+                        .value,
+                    EcdsaSighashType::All,
+                )
+                .expect("Failed to create segwit sighash");
+
+            let tweaked_key = our_key.tweak(tweak, &self.secp);
+
+            let signature = self
+                .sign_ecdsa_with_guardian_signer(
+                    contract_tweak(&our_key, tweak),
+                    tx_hash.to_byte_array(),
+                    &tweaked_key,
+                )
+                .await;
+
+            psbt_input.partial_sigs.insert(
+                bitcoin::PublicKey {
+                    compressed: true,
+                    inner: tweaked_key,
+                },
+                EcdsaSig::sighash_all(signature),
+            );
+        }
+    }
+
+    /// Every guardian has to sign the peg-out to stay in consensus, so if the
+    /// signer is unavailable or returns an invalid signature we keep retrying
+    /// until it is back.
+    async fn sign_ecdsa_with_guardian_signer(
+        &self,
+        tweak: [u8; 32],
+        digest: [u8; 32],
+        tweaked_key: &secp256k1::PublicKey,
+    ) -> secp256k1::ecdsa::Signature {
+        loop {
+            match self
+                .guardian_signer
+                .sign_ecdsa(PEG_IN_KEY_NAME, Some(tweak), digest)
+                .await
+            {
+                Ok(signature)
+                    if self
+                        .secp
+                        .verify_ecdsa(&Message::from_digest(digest), &signature, tweaked_key)
+                        .is_ok() =>
+                {
+                    return signature;
+                }
+                Ok(_) => {
+                    warn!(
+                        target: LOG_MODULE_WALLET,
+                        "Guardian signer returned an invalid peg-out signature, retrying..."
+                    );
+                }
+                Err(e) => {
+                    warn!(
+                        target: LOG_MODULE_WALLET,
+                        err = %e.fmt_compact_anyhow(),
+                        "Failed to sign peg-out with guardian signer, retrying..."
+                    );
+                }
+            }
+
+            sleep(Duration::from_secs(1)).await;
+        }
+    }
+
     fn spawn_broadcast_pending_task(
         task_group: &TaskGroup,
         server_bitcoin_rpc_monitor: &ServerBitcoinRpcMonitor,
@@ -2057,7 +2244,6 @@ pub async fn broadcast_pending_tx(
 
 struct StatelessWallet<'a> {
     descriptor: &'a Descriptor<CompressedPublicKey>,
-    secret_key: &'a secp256k1::SecretKey,
     secp: &'a secp256k1::Secp256k1<secp256k1::All>,
 }
 
@@ -2305,55 +2491,6 @@ fn create_tx(
         })
     }
 
-    fn sign_psbt(&self, psbt: &mut Psbt) {
-        let mut tx_hasher = SighashCache::new(&psbt.unsigned_tx);
-
-        for (idx, (psbt_input, _tx_input)) in psbt
-            .inputs
-            .iter_mut()
-            .zip(psbt.unsigned_tx.input.iter())
-            .enumerate()
-        {
-            let tweaked_secret = {
-                let tweak = psbt_input
-                    .proprietary
-                    .get(&proprietary_tweak_key())
-                    .expect("Malformed PSBT: expected tweak");
-
-                self.secret_key.tweak(tweak, self.secp)
-            };
-
-            let tx_hash = tx_hasher
-                .p2wsh_signature_hash(
-                    idx,
-                    psbt_input
-                        .witness_script
-                        .as_ref()
-                        .expect("Missing witness script"),
-                    psbt_input
-                        .witness_utxo
-                        .as_ref()
-                        .expect("Missing UTXO")
-                        .value,
-                    EcdsaSighashType::All,
-                )
-                .expect("Failed to create segwit sighash");
-
-            let signature = self.secp.sign_ecdsa(
-                &Message::from_digest_slice(&tx_hash[..]).unwrap(),
-                &tweaked_secret,
-            );
-
-            psbt_input.partial_sigs.insert(
-                bitcoin::PublicKey {
-                    compressed: true,
-                    inner: secp256k1::PublicKey::from_secret_key(self.secp, &tweaked_secret),
-                },
-                EcdsaSig::sighash_all(signature),
-            );
-        }
-    }
-
     fn derive_script(&self, tweak: &[u8]) -> ScriptBuf {
         struct CompressedPublicKeyTranslator<'t, 's, Ctx: Verification> {
             tweak: &'t [u8],
@@ -2492,11 +2629,8 @@ fn create_tx_should_validate_amounts() {
             .unwrap(),
         );
 
-        let (secret_key, _) = secp.generate_keypair(&mut OsRng);
-
         let wallet = StatelessWallet {
             descriptor: &descriptor,
-            secret_key: &secret_key,
             secp: &secp,
         };
 
@@ -5,21 +5,25 @@
 
 use anyhow::{Context, anyhow, bail};
 use assert_matches::assert_matches;
+use bitcoin::merkle_tree::PartialMerkleTree;
 use bitcoin::secp256k1;
 use fedimint_api_client::api::DynGlobalApi;
 use fedimint_client::ClientHandleArc;
 use fedimint_client::secret::{PlainRootSecretStrategy, RootSecretStrategy};
 use fedimint_connectors::ConnectorRegistry;
+use fedimint_core::core::ModuleInstanceId;
 use fedimint_core::db::mem_impl::MemDatabase;
-use fedimint_core::db::{DatabaseTransaction, IRawDatabaseExt};
+use fedimint_core::db::{Database, DatabaseTransaction, IRawDatabaseExt};
 use fedimint_core::module::{AmountUnit, serde_json};
 use fedimint_core::task::{TaskGroup, sleep_in_test};
+use fedimint_core::txoproof::TxOutProof;
 use fedimint_core::util::{BoxStream, NextOrPending, SafeUrl, retry};
 use fedimint_core::{Amount, BitcoinHash, Feerate, InPoint, PeerId, TransactionId, sats};
 use fedimint_dummy_client::DummyClientInit;
 use fedimint_dummy_server::DummyInit;
 use fedimint_server::core::ServerModule;
 use fedimint_server_core::bitcoin_rpc::ServerBitcoinRpcMonitor;
+use fedimint_server_core::guardian_signer::ModuleGuardianSigner;
 use fedimint_testing::btc::BitcoinTest;
 use fedimint_testing::envs::{FM_TEST_BACKEND_BITCOIN_RPC_KIND_ENV, FM_TEST_USE_REAL_DAEMONS_ENV};
 use fedimint_testing::federation::FederationTest;
@@ -212,6 +216,106 @@ async fn sanity_check_bitcoin_blocks() -> anyhow::Result<()> {
     Ok(())
 }
 
+#[tokio::test(flavor = "multi_thread")]
+async fn labeled_deposit_uri_is_claimable() -> anyhow::Result<()> {
+    let fixtures = fixtures();
+    let fed = fixtures.new_fed_degraded().await;
+    let client = fed.new_client().await;
+    let wallet_module = client.get_first_module::<WalletClientModule>()?;
+    let bitcoin = fixtures.bitcoin();
+    let bitcoin = bitcoin.lock_exclusive().await;
+
+    let finality_delay = 10;
+    bitcoin.mine_blocks(finality_delay).await;
+    await_consensus_to_catch_up(&client, 1).await?;
+    await_consensus_upgrade(&client, &fed).await?;
+
+    let (op, address, tweak_idx) = wallet_module
+        .allocate_deposit_address_expert_only(())
+        .await?;
+
+    assert_eq!(
+        wallet_module.deposit_uri(tweak_idx, None).await?,
+        format!("bitcoin:{address}")
+    );
+
+    wallet_module
+        .set_deposit_label(tweak_idx, Some("Coffee fund".to_string()))
+        .await?;
+
+    assert_eq!(
+        wallet_module.get_deposit_label(tweak_idx).await.as_deref(),
+        Some("Coffee fund")
+    );
+
+    let amount = bsats(PEG_IN_AMOUNT_SATS)
+        + bsats(wallet_module.get_fee_consensus().peg_in_abs.msats / 1000);
+
+    assert_eq!(
+        wallet_module.deposit_uri(tweak_idx, Some(amount)).await?,
+        format!(
+            "bitcoin:{address}?amount={}&label=Coffee%20fund",
+            amount.to_string_in(bitcoin::Denomination::Bitcoin)
+        )
+    );
+
+    assert!(
+        wallet_module
+            .set_deposit_label(tweak_idx.next(), Some("Unallocated".to_string()))
+            .await
+            .is_err()
+    );
+
+    bitcoin.send_and_mine_block(&address, amount).await;
+    bitcoin.mine_blocks(finality_delay).await;
+
+    wallet_module
+        .await_num_deposits_by_operation_id(op, 1)
+        .await?;
+
+    assert_eq!(
+        client.get_balance_for_btc().await?,
+        sats(PEG_IN_AMOUNT_SATS)
+    );
+
+    wallet_module.set_deposit_label(tweak_idx, None).await?;
+
+    assert_eq!(wallet_module.get_deposit_label(tweak_idx).await, None);
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn deposit_uri_is_allocated_with_label() -> anyhow::Result<()> {
+    let fixtures = fixtures();
+    let fed = fixtures.new_fed_degraded().await;
+    let client = fed.new_client().await;
+    let wallet_module = client.get_first_module::<WalletClientModule>()?;
+    let bitcoin = fixtures.bitcoin();
+    let bitcoin = bitcoin.lock_exclusive().await;
+
+    bitcoin.mine_blocks(10).await;
+    await_consensus_to_catch_up(&client, 1).await?;
+    await_consensus_upgrade(&client, &fed).await?;
+
+    let amount = bsats(150_000);
+    let (_, uri, tweak_idx) = wallet_module
+        .safe_allocate_deposit_uri(Some("Savings".to_string()), Some(amount), ())
+        .await?;
+
+    assert!(uri.ends_with("?amount=0.0015&label=Savings"), "{uri}");
+    assert_eq!(
+        uri,
+        wallet_module.deposit_uri(tweak_idx, Some(amount)).await?
+    );
+    assert_eq!(
+        wallet_module.get_deposit_label(tweak_idx).await.as_deref(),
+        Some("Savings")
+    );
+
+    Ok(())
+}
+
 #[tokio::test(flavor = "multi_thread")]
 async fn on_chain_peg_in_and_peg_out_happy_case() -> anyhow::Result<()> {
     let fixtures = fixtures();
@@ -721,6 +825,13 @@ async fn peg_ins_that_are_unconfirmed_are_rejected() -> anyhow::Result<()> {
             Duration::from_secs(1),
             &TaskGroup::new(),
         ),
+        ModuleGuardianSigner::local(
+            module_instance_id,
+            fedimint_server::core::ServerModuleInit::guardian_keys(
+                &WalletInit,
+                &wallet_server_cfg[0],
+            )?,
+        ),
     )
     .await?;
 
@@ -1100,6 +1211,249 @@ fn sum_utxos<'a>(utxos: impl Iterator<Item = &'a TxOutputSummary>) -> bitcoin::A
     Ok(())
 }
 
+#[tokio::test(flavor = "multi_thread")]
+async fn peg_ins_reorged_out_before_finality_are_rejected() -> anyhow::Result<()> {
+    let fixtures = fixtures();
+    let bitcoin = fixtures.bitcoin();
+    let bitcoin = bitcoin.lock_exclusive().await;
+    let dyn_bitcoin_rpc = fixtures.server_bitcoin_rpc();
+    let db = MemDatabase::new().into_database();
+    info!("Starting test peg_ins_reorged_out_before_finality_are_rejected");
+
+    let module_instance_id = 1;
+    let (mut wallet, wallet_config) =
+        build_standalone_wallet(&fixtures, &db, module_instance_id).await?;
+    let (pk, peg_in_address) = new_peg_in_address(&wallet_config)?;
+    let finality_delay = wallet_config.consensus.finality_delay;
+
+    let mut dbtx = db.begin_transaction().await;
+
+    // Generate a minimum number of blocks before sending transactions
+    bitcoin.mine_blocks(finality_delay.into()).await;
+
+    let block_count = dyn_bitcoin_rpc.get_block_count().await? as u32;
+    sync_wallet_to_block(
+        &mut dbtx
+            .to_ref_with_prefix_module_id(module_instance_id)
+            .0
+            .into_nc(),
+        &mut wallet,
+        block_count - finality_delay,
+    )
+    .await?;
+
+    // Send peg-in transaction
+    let (stale_proof, transaction) = bitcoin
+        .send_and_mine_block(&peg_in_address, bsats(PEG_IN_AMOUNT_SATS))
+        .await;
+    let txid = transaction.compute_txid();
+    let output_index: u32 = transaction
+        .output
+        .iter()
+        .position(|o| o.script_pubkey == peg_in_address.script_pubkey())
+        .context("expected to find peg-in output")?
+        .try_into()?;
+
+    // Replace the block confirming the peg-in before the federation reaches it
+    bitcoin.reorg(1).await;
+    let height = bitcoin
+        .get_tx_block_height(&txid)
+        .await
+        .context("expected peg-in to be mined again")?;
+    let block_hash = dyn_bitcoin_rpc.get_block_hash(height).await?;
+    assert_ne!(block_hash, stale_proof.block_header.block_hash());
+
+    bitcoin.mine_blocks(finality_delay.into()).await;
+    let block_count = dyn_bitcoin_rpc.get_block_count().await? as u32;
+    sync_wallet_to_block(
+        &mut dbtx
+            .to_ref_with_prefix_module_id(module_instance_id)
+            .0
+            .into_nc(),
+        &mut wallet,
+        block_count - finality_delay,
+    )
+    .await?;
+
+    let stale_input = fedimint_wallet_common::WalletInput::new_v0(PegInProof::new(
+        stale_proof,
+        transaction.clone(),
+        output_index,
+        pk,
+    )?);
+
+    match wallet
+        .process_input(
+            &mut dbtx
+                .to_ref_with_prefix_module_id(module_instance_id)
+                .0
+                .into_nc(),
+            &stale_input,
+            InPoint {
+                txid: TransactionId::all_zeros(),
+                in_idx: 0,
+            },
+        )
+        .await
+    {
+        Ok(_) => bail!("Expected peg-in with a proof for the reorged out block to fail"),
+        Err(e) => {
+            assert!(e.to_string().contains("Unknown block hash in peg-in proof"));
+        }
+    }
+
+    // A proof for the block that replaced it is accepted
+    let block = dyn_bitcoin_rpc.get_block(&block_hash).await?;
+    let txids = block
+        .txdata
+        .iter()
+        .map(bitcoin::Transaction::compute_txid)
+        .collect::<Vec<_>>();
+    let matches = txids.iter().map(|id| *id == txid).collect::<Vec<_>>();
+    let proof = TxOutProof {
+        block_header: block.header,
+        merkle_proof: PartialMerkleTree::from_txids(&txids, &matches),
+    };
+    let input = fedimint_wallet_common::WalletInput::new_v0(PegInProof::new(
+        proof,
+        transaction,
+        output_index,
+        pk,
+    )?);
+
+    assert_matches!(
+        wallet
+            .process_input(
+                &mut dbtx
+                    .to_ref_with_prefix_module_id(module_instance_id)
+                    .0
+                    .into_nc(),
+                &input,
+                InPoint {
+                    txid: TransactionId::all_zeros(),
+                    in_idx: 0,
+                },
+            )
+            .await,
+        Ok(_)
+    );
+    dbtx.commit_tx().await;
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn consensus_block_count_does_not_go_backwards_on_reorg() -> anyhow::Result<()> {
+    let fixtures = fixtures();
+    let fed = fixtures.new_fed_degraded().await;
+    let client = fed.new_client().await;
+    let wallet_module = client.get_first_module::<WalletClientModule>()?;
+    let bitcoin = fixtures.bitcoin();
+    let bitcoin = bitcoin.lock_exclusive().await;
+    info!("Starting test consensus_block_count_does_not_go_backwards_on_reorg");
+
+    let finality_delay = 10;
+    bitcoin.mine_blocks(finality_delay).await;
+    await_consensus_to_catch_up(&client, 1).await?;
+
+    // A reorg deeper than the finality delay would replace blocks the federation
+    // already processed, which is not supported
+    let reorg_depth = finality_delay - 1;
+    bitcoin.mine_blocks(reorg_depth).await;
+    let block_count = bitcoin.get_block_count().await;
+    let consensus_block_count =
+        await_consensus_to_catch_up(&client, block_count - finality_delay).await?;
+
+    info!("Invalidating the last {reorg_depth} blocks");
+    bitcoin.invalidate_blocks(reorg_depth).await;
+    assert_eq!(bitcoin.get_block_count().await, block_count - reorg_depth);
+
+    for _ in 0..5 {
+        sleep_in_test(
+            "Waiting for the guardians to observe the shorter chain",
+            Duration::from_secs(1),
+        )
+        .await;
+        assert_eq!(
+            client
+                .api()
+                .with_module(wallet_module.id)
+                .fetch_consensus_block_count()
+                .await?,
+            consensus_block_count
+        );
+    }
+
+    info!("Mining a longer chain replacing the invalidated blocks");
+    bitcoin.mine_blocks(reorg_depth + 1).await;
+    await_consensus_to_catch_up(&client, consensus_block_count + 1).await?;
+
+    // The federation keeps processing peg-ins on the new chain
+    peg_in(&client, bitcoin.as_ref(), finality_delay, &fed).await?;
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn peg_out_reorged_out_of_the_chain_is_confirmed_again() -> anyhow::Result<()> {
+    let fixtures = fixtures();
+    let fed = fixtures.new_fed_degraded().await;
+    let client = fed.new_client().await;
+    let wallet_module = client.get_first_module::<WalletClientModule>()?;
+    let bitcoin = fixtures.bitcoin();
+    let bitcoin = bitcoin.lock_exclusive().await;
+    info!("Starting test peg_out_reorged_out_of_the_chain_is_confirmed_again");
+
+    let finality_delay = 10;
+    bitcoin.mine_blocks(finality_delay).await;
+    await_consensus_to_catch_up(&client, 1).await?;
+
+    peg_in(&client, bitcoin.as_ref(), finality_delay, &fed).await?;
+
+    let address = bitcoin.get_new_address().await;
+    let peg_out = bsats(PEG_OUT_AMOUNT_SATS);
+    let fees = wallet_module.get_withdraw_fees(&address, peg_out).await?;
+    let op = wallet_module.withdraw(&address, peg_out, fees, ()).await?;
+
+    let sub = wallet_module.subscribe_withdraw_updates(op).await?;
+    let mut sub = sub.into_stream();
+    assert_eq!(sub.ok().await?, WithdrawState::Created);
+    let txid = match sub.ok().await? {
+        WithdrawState::Succeeded(txid) => txid,
+        other => panic!("Unexpected state: {other:?}"),
+    };
+
+    // Waits for the peg-out to enter the mempool
+    bitcoin.get_mempool_tx_fee(&txid).await;
+    bitcoin.mine_blocks(1).await;
+    assert!(bitcoin.get_tx_block_height(&txid).await.is_some());
+
+    info!("Reorging the peg-out out of the chain");
+    bitcoin.invalidate_blocks(1).await;
+    assert_eq!(bitcoin.get_tx_block_height(&txid).await, None);
+
+    let wallet_summary = wallet_module.get_wallet_summary().await?;
+    assert_eq!(wallet_summary.pending_peg_out_txos().len(), 1);
+    assert_eq!(wallet_summary.pending_change_utxos().len(), 1);
+
+    bitcoin.get_mempool_tx_fee(&txid).await;
+    bitcoin.mine_blocks(finality_delay + 1).await;
+    let block_count = bitcoin.get_block_count().await;
+    await_consensus_to_catch_up(&client, block_count - finality_delay).await?;
+    assert!(bitcoin.get_tx_block_height(&txid).await.is_some());
+
+    let wallet_summary = wallet_module.get_wallet_summary().await?;
+    assert_eq!(wallet_summary.pending_peg_out_txos(), vec![]);
+    assert_eq!(wallet_summary.pending_change_utxos(), vec![]);
+    assert!(
+        wallet_summary
+            .spendable_utxos
+            .iter()
+            .any(|utxo| utxo.outpoint.txid == txid)
+    );
+
+    Ok(())
+}
+
 #[tokio::test(flavor = "multi_thread")]
 async fn verify_auto_consensus_voting() -> anyhow::Result<()> {
     let fixtures = fixtures();
@@ -1110,6 +1464,67 @@ async fn verify_auto_consensus_voting() -> anyhow::Result<()> {
     Ok(())
 }
 
+// Runs the wallet module of the first peer on its own, such that the test can
+// drive its consensus directly
+async fn build_standalone_wallet(
+    fixtures: &Fixtures,
+    db: &Database,
+    module_instance_id: ModuleInstanceId,
+) -> anyhow::Result<(fedimint_wallet_server::Wallet, WalletConfig)> {
+    let (wallet_server_cfg, _) = build_wallet_server_configs()?;
+    let wallet_config: WalletConfig = wallet_server_cfg[0].to_typed()?;
+
+    let wallet = fedimint_wallet_server::Wallet::new(
+        wallet_config.clone(),
+        db,
+        &TaskGroup::new(),
+        PeerId::from(0),
+        // FIXME: use proper mock
+        DynGlobalApi::new(
+            ConnectorRegistry::build_from_testing_env()?.bind().await?,
+            [(
+                PeerId::from(0),
+                SafeUrl::from_str("ws://dummy.xyz").unwrap(),
+            )]
+            .into(),
+            None,
+        )?
+        .with_module(module_instance_id),
+        ServerBitcoinRpcMonitor::new(
+            fixtures.server_bitcoin_rpc(),
+            Duration::from_secs(1),
+            &TaskGroup::new(),
+        ),
+        ModuleGuardianSigner::local(
+            module_instance_id,
+            fedimint_server::core::ServerModuleInit::guardian_keys(
+                &WalletInit,
+                &wallet_server_cfg[0],
+            )?,
+        ),
+    )
+    .await?;
+
+    Ok((wallet, wallet_config))
+}
+
+fn new_peg_in_address(
+    wallet_config: &WalletConfig,
+) -> anyhow::Result<(fedimint_core::secp256k1::PublicKey, bitcoin::Address)> {
+    let root_secret =
+        PlainRootSecretStrategy::to_root_secret(&PlainRootSecretStrategy::random(&mut OsRng));
+    let secp = fedimint_core::secp256k1::Secp256k1::new();
+    let pk = root_secret.to_secp_key(&secp).public_key();
+
+    let peg_in_address = wallet_config
+        .consensus
+        .peg_in_descriptor
+        .tweak(&pk, secp256k1::SECP256K1)
+        .address(wallet_config.consensus.network.0)?;
+
+    Ok((pk, peg_in_address))
+}
+
 async fn sync_wallet_to_block(
     dbtx: &mut DatabaseTransaction<'_>,
     wallet: &mut fedimint_wallet_server::Wallet,
@@ -1652,6 +2067,7 @@ async fn test_client_db_migrations() -> anyhow::Result<()> {
                         client_db::DbKeyPrefix::RecoveryFinalized => {}
                         client_db::DbKeyPrefix::RecoveryState => {}
                         client_db::DbKeyPrefix::SupportsSafeDeposit => {}
+                        client_db::DbKeyPrefix::PegInLabel => {}
                         client_db::DbKeyPrefix::ExternalReservedStart
                         | client_db::DbKeyPrefix::CoreInternalReservedStart
                         | client_db::DbKeyPrefix::CoreInternalReservedEnd => {}
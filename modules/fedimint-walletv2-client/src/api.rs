@@ -1,13 +1,21 @@
 use fedimint_api_client::api::{FederationApiExt, FederationResult, IModuleFederationApi};
-use fedimint_core::module::ApiRequestErased;
+use fedimint_core::module::{ApiRequestErased, ApiVersion};
 use fedimint_core::task::{MaybeSend, MaybeSync};
 use fedimint_core::{OutPoint, apply, async_trait_maybe_send};
 use fedimint_walletv2_common::endpoint_constants::{
     CONSENSUS_BLOCK_COUNT_ENDPOINT, CONSENSUS_FEERATE_ENDPOINT, FEDERATION_WALLET_ENDPOINT,
-    OUTPUT_INFO_SLICE_ENDPOINT, PENDING_TRANSACTION_CHAIN_ENDPOINT, RECEIVE_FEE_ENDPOINT,
-    SEND_FEE_ENDPOINT, TRANSACTION_CHAIN_ENDPOINT, TRANSACTION_ID_ENDPOINT,
+    OUTPUT_INFO_SLICE_ENDPOINT, PEGOUT_STATUS_ENDPOINT, PENDING_TRANSACTION_CHAIN_ENDPOINT,
+    RECEIVE_FEE_ENDPOINT, SEND_FEE_ENDPOINT, SILENT_PAYMENT_PROOF_ENDPOINT,
+    TRANSACTION_CHAIN_ENDPOINT, TRANSACTION_ID_ENDPOINT, WATCH_ONLY_DESCRIPTORS_ENDPOINT,
 };
-use fedimint_walletv2_common::{FederationWallet, OutputInfo, TxInfo};
+use fedimint_walletv2_common::silent_payments::SilentPaymentProof;
+use fedimint_walletv2_common::{
+    FederationWallet, OutputInfo, PegOutStatus, TxInfo, WatchOnlyDescriptors,
+};
+
+/// Module API version that introduced the peg-out status endpoint. Older
+/// guardians do not batch peg-outs.
+pub const VERSION_THAT_INTRODUCED_PEGOUT_STATUS: ApiVersion = ApiVersion::new(0, 1);
 
 #[apply(async_trait_maybe_send!)]
 pub trait WalletFederationApi {
@@ -32,6 +40,15 @@ async fn output_info_slice(
     ) -> FederationResult<Vec<OutputInfo>>;
 
     async fn tx_id(&self, outpoint: OutPoint) -> Option<bitcoin::Txid>;
+
+    async fn pegout_status(&self, outpoint: OutPoint) -> Option<PegOutStatus>;
+
+    async fn silent_payment_proof(
+        &self,
+        outpoint: OutPoint,
+    ) -> FederationResult<Option<SilentPaymentProof>>;
+
+    async fn watch_only_descriptors(&self) -> FederationResult<WatchOnlyDescriptors>;
 }
 
 #[apply(async_trait_maybe_send!)]
@@ -108,4 +125,31 @@ async fn tx_id(&self, outpoint: OutPoint) -> Option<bitcoin::Txid> {
         )
         .await
     }
+
+    async fn pegout_status(&self, outpoint: OutPoint) -> Option<PegOutStatus> {
+        self.request_current_consensus_retry(
+            PEGOUT_STATUS_ENDPOINT.to_string(),
+            ApiRequestErased::new(outpoint),
+        )
+        .await
+    }
+
+    async fn silent_payment_proof(
+        &self,
+        outpoint: OutPoint,
+    ) -> FederationResult<Option<SilentPaymentProof>> {
+        self.request_current_consensus(
+            SILENT_PAYMENT_PROOF_ENDPOINT.to_string(),
+            ApiRequestErased::new(outpoint),
+        )
+        .await
+    }
+
+    async fn watch_only_descriptors(&self) -> FederationResult<WatchOnlyDescriptors> {
+        self.request_current_consensus(
+            WATCH_ONLY_DESCRIPTORS_ENDPOINT.to_string(),
+            ApiRequestErased::new(()),
+        )
+        .await
+    }
 }
@@ -6,7 +6,9 @@
 use serde::Serialize;
 use serde_json::Value;
 
-use crate::WalletClientModule;
+use fedimint_walletv2_common::silent_payments::SilentPaymentAddress;
+
+use crate::{FinalSendOperationState, WalletClientModule};
 
 #[derive(Parser, Serialize)]
 enum Opts {
@@ -22,6 +24,14 @@ enum Opts {
         #[arg(long)]
         fee: Option<bitcoin::Amount>,
     },
+    /// Send an onchain payment to a silent payment address and verify the
+    /// output key derived by the federation.
+    SendSilentPayment {
+        address: String,
+        value: bitcoin::Amount,
+        #[arg(long)]
+        fee: Option<bitcoin::Amount>,
+    },
     /// Return the next unused receive address.
     Receive,
 }
@@ -38,6 +48,9 @@ enum InfoOpts {
     PendingTxChain,
     /// Display the chain of bitcoin transactions.
     TxChain,
+    /// Export the descriptors of the federation wallet in the format of the
+    /// `importdescriptors` RPC of Bitcoin Core for a watch-only wallet.
+    WatchOnly,
 }
 
 pub(crate) async fn handle_cli_command(
@@ -53,6 +66,27 @@ pub(crate) async fn handle_cli_command(
             InfoOpts::Feerate => json(wallet.feerate().await?),
             InfoOpts::PendingTxChain => json(wallet.pending_tx_chain().await?),
             InfoOpts::TxChain => json(wallet.tx_chain().await?),
+            InfoOpts::WatchOnly => {
+                let descriptors = wallet.watch_only_descriptors().await?;
+
+                let tweaked = descriptors.tweaked.into_iter().map(|descriptor| {
+                    serde_json::json!({
+                        "desc": descriptor.descriptor,
+                        "timestamp": 0,
+                        "label": format!("fedimint-{}", descriptor.tweak),
+                    })
+                });
+
+                let unclaimed = descriptors.unclaimed.into_iter().map(|deposit| {
+                    serde_json::json!({
+                        "desc": deposit.descriptor,
+                        "timestamp": 0,
+                        "label": format!("fedimint-unclaimed-{}", deposit.outpoint),
+                    })
+                });
+
+                Value::Array(tweaked.chain(unclaimed).collect())
+            }
         },
         Opts::SendFee => json(wallet.send_fee().await?),
         Opts::Send {
@@ -64,6 +98,26 @@ pub(crate) async fn handle_cli_command(
                 .await_final_send_operation_state(wallet.send(address, value, fee).await?)
                 .await,
         ),
+        Opts::SendSilentPayment {
+            address,
+            value,
+            fee,
+        } => {
+            let address = SilentPaymentAddress::parse(&address, wallet.cfg.network)?;
+
+            let operation_id = wallet
+                .send_silent_payment(address.clone(), value, fee)
+                .await?;
+
+            match wallet.await_final_send_operation_state(operation_id).await {
+                FinalSendOperationState::Success(txid) => {
+                    wallet.verify_silent_payment(operation_id, &address).await?;
+
+                    json(FinalSendOperationState::Success(txid))
+                }
+                state => json(state),
+            }
+        }
         Opts::Receive => json(wallet.receive().await),
     };
 
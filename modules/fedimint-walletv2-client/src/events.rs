@@ -19,6 +19,21 @@ impl Event for SendPaymentEvent {
     const PERSISTENCE: EventPersistence = EventPersistence::Persistent;
 }
 
+/// Event emitted when a pegout to a silent payment address is initiated.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SendSilentPaymentEvent {
+    pub operation_id: OperationId,
+    pub address: String,
+    pub value: bitcoin::Amount,
+    pub fee: bitcoin::Amount,
+}
+
+impl Event for SendSilentPaymentEvent {
+    const MODULE: Option<ModuleKind> = Some(fedimint_walletv2_common::KIND);
+    const KIND: EventKind = EventKind::from_static("silent-payment-send");
+    const PERSISTENCE: EventPersistence = EventPersistence::Persistent;
+}
+
 /// Status of a send (pegout) operation.
 #[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
 pub enum SendPaymentStatus {
@@ -26,6 +41,8 @@ pub enum SendPaymentStatus {
     Success(Txid),
     /// The pegout was aborted.
     Aborted,
+    /// The pegout was queued and will be sent once its batch is closed.
+    Queued,
 }
 
 /// Event emitted when a send (pegout) operation reaches a final state.
@@ -18,12 +18,13 @@
 use std::sync::Arc;
 use std::time::Duration;
 
-use anyhow::anyhow;
+use anyhow::{Context as _, anyhow, bail, ensure};
 use api::WalletFederationApi;
 use bitcoin::address::NetworkUnchecked;
+use bitcoin::hashes::sha256;
 use bitcoin::{Address, ScriptBuf};
 use db::{NextOutputIndexKey, ValidAddressIndexKey, ValidAddressIndexPrefix};
-use events::{ReceivePaymentEvent, SendPaymentEvent};
+use events::{ReceivePaymentEvent, SendPaymentEvent, SendSilentPaymentEvent};
 use fedimint_api_client::api::{DynModuleApi, FederationResult};
 use fedimint_client::DynGlobalClientContext;
 use fedimint_client::transaction::{
@@ -44,14 +45,15 @@
 use fedimint_core::module::{
     AmountUnit, Amounts, ApiVersion, CommonModuleInit, ModuleCommon, ModuleInit, MultiApiVersion,
 };
-use fedimint_core::task::{TaskGroup, block_in_place, sleep};
-use fedimint_core::{Amount, OutPoint, TransactionId, apply, async_trait_maybe_send};
+use fedimint_core::task::{MaybeSend, MaybeSync, TaskGroup, block_in_place, sleep};
+use fedimint_core::{Amount, NumPeersExt, OutPoint, TransactionId, apply, async_trait_maybe_send};
 use fedimint_derive_secret::{ChildId, DerivableSecret};
 use fedimint_logging::LOG_CLIENT_MODULE_WALLETV2;
-use fedimint_walletv2_common::config::WalletClientConfig;
+use fedimint_walletv2_common::config::{WalletClientConfig, WalletDescriptor};
+use fedimint_walletv2_common::silent_payments::{self, SilentPaymentAddress, SilentPaymentProof};
 use fedimint_walletv2_common::{
     KIND, StandardScript, TxInfo, WalletCommonInit, WalletInput, WalletInputV0, WalletModuleTypes,
-    WalletOutput, WalletOutputV0, descriptor, is_potential_receive,
+    WalletOutput, WalletOutputV0, WatchOnlyDescriptors, is_potential_receive, raw_descriptor,
 };
 use futures::StreamExt;
 use receive_sm::{ReceiveSMCommon, ReceiveSMState, ReceiveStateMachine};
@@ -69,6 +71,7 @@
 pub enum WalletOperationMeta {
     Send(SendMeta),
     Receive(ReceiveMeta),
+    SendSilentPayment(SendSilentPaymentMeta),
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -79,6 +82,14 @@ pub struct SendMeta {
     pub fee: bitcoin::Amount,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SendSilentPaymentMeta {
+    pub change_outpoint_range: OutPointRange,
+    pub address: String,
+    pub value: bitcoin::Amount,
+    pub fee: bitcoin::Amount,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ReceiveMeta {
     pub change_outpoint_range: OutPointRange,
@@ -105,11 +116,13 @@ pub struct WalletClientModule {
     client_ctx: ClientContext<Self>,
     db: Database,
     module_api: DynModuleApi,
+    module_api_version: ApiVersion,
 }
 
 #[derive(Debug, Clone)]
 pub struct WalletClientContext {
     pub client_ctx: ClientContext<WalletClientModule>,
+    pub module_api_version: ApiVersion,
 }
 
 impl Context for WalletClientContext {
@@ -127,6 +140,7 @@ impl ClientModule for WalletClientModule {
     fn context(&self) -> Self::ModuleStateMachineContext {
         WalletClientContext {
             client_ctx: self.client_ctx.clone(),
+            module_api_version: self.module_api_version,
         }
     }
 
@@ -179,7 +193,7 @@ impl ClientModuleInit for WalletClientInit {
     type Module = WalletClientModule;
 
     fn supported_api_versions(&self) -> MultiApiVersion {
-        MultiApiVersion::try_from_iter([ApiVersion { major: 0, minor: 0 }])
+        MultiApiVersion::try_from_iter([ApiVersion { major: 0, minor: 1 }])
             .expect("no version conflicts")
     }
 
@@ -191,6 +205,7 @@ async fn init(&self, args: &ClientModuleInitArgs<Self>) -> anyhow::Result<Self::
             client_ctx: args.context(),
             db: args.db().clone(),
             module_api: args.module_api().clone(),
+            module_api_version: *args.module_api_version(),
         };
 
         module.spawn_output_scanner(args.task_group());
@@ -221,6 +236,47 @@ pub async fn total_value(&self) -> FederationResult<bitcoin::Amount> {
             .map(|tx_out| tx_out.map_or(bitcoin::Amount::ZERO, |tx_out| tx_out.value))
     }
 
+    /// Fetch the descriptors of all outputs the federation has ever received
+    /// funds to, such that they can be imported into a watch-only wallet. Every
+    /// tweaked descriptor is checked against the one derived from our config,
+    /// hence we do not have to trust the federation for them. This does not
+    /// apply to the unclaimed deposits, since only the depositing client knows
+    /// their tweak.
+    pub async fn watch_only_descriptors(&self) -> anyhow::Result<WatchOnlyDescriptors> {
+        let descriptors = self.module_api.watch_only_descriptors().await?;
+
+        for descriptor in &descriptors.tweaked {
+            ensure!(
+                descriptor.descriptor
+                    == self
+                        .cfg
+                        .descriptor
+                        .descriptor(&self.cfg.bitcoin_pks, &descriptor.tweak)
+                        .to_string(),
+                "Federation returned an invalid descriptor for tweak {}",
+                descriptor.tweak
+            );
+        }
+
+        for deposit in &descriptors.unclaimed {
+            ensure!(
+                deposit.descriptor == raw_descriptor(&deposit.script_pubkey),
+                "Federation returned an invalid descriptor for unclaimed deposit {}",
+                deposit.outpoint
+            );
+        }
+
+        Ok(descriptors)
+    }
+
+    /// Returns the script of the federation wallet for the given tweak.
+    pub fn tweaked_script(&self, tweak: &sha256::Hash) -> ScriptBuf {
+        self.cfg
+            .descriptor
+            .descriptor(&self.cfg.bitcoin_pks, tweak)
+            .script_pubkey()
+    }
+
     /// Fetch the consensus block count of the federation.
     pub async fn block_count(&self) -> FederationResult<u64> {
         self.module_api.consensus_block_count().await
@@ -232,7 +288,7 @@ pub async fn feerate(&self) -> FederationResult<Option<u64>> {
     }
 
     /// Fetch information on the chain of pending bitcoin transactions.
-    async fn pending_tx_chain(&self) -> FederationResult<Vec<TxInfo>> {
+    pub async fn pending_tx_chain(&self) -> FederationResult<Vec<TxInfo>> {
         self.module_api.pending_tx_chain().await
     }
 
@@ -261,25 +317,126 @@ pub async fn send(
             return Err(SendError::WrongNetwork);
         }
 
+        let destination = StandardScript::from_address(&address.clone().assume_checked())
+            .ok_or(SendError::UnsupportedAddress)?;
+
+        let fee = self.resolve_send_fee(value, fee).await?;
+
+        let address_clone = address.clone();
+
+        let operation_id = self
+            .submit_send(destination, value, fee, move |change_outpoint_range| {
+                WalletOperationMeta::Send(SendMeta {
+                    change_outpoint_range,
+                    address: address_clone.clone(),
+                    value,
+                    fee,
+                })
+            })
+            .await?;
+
+        let mut dbtx = self.client_ctx.module_db().begin_transaction().await;
+
+        self.client_ctx
+            .log_event(
+                &mut dbtx,
+                SendPaymentEvent {
+                    operation_id,
+                    address,
+                    value,
+                    fee,
+                },
+            )
+            .await;
+
+        dbtx.commit_tx().await;
+
+        Ok(operation_id)
+    }
+
+    /// Send an onchain payment to a silent payment address with the given fee.
+    /// The federation derives the output key once a threshold of guardians
+    /// has contributed their share of the ECDH secret.
+    pub async fn send_silent_payment(
+        &self,
+        address: SilentPaymentAddress,
+        value: bitcoin::Amount,
+        fee: Option<bitcoin::Amount>,
+    ) -> Result<OperationId, SendError> {
+        let fee = self.resolve_send_fee(value, fee).await?;
+
+        let encoded = address.encode(self.cfg.network);
+
+        let encoded_clone = encoded.clone();
+
+        let operation_id = self
+            .submit_send(
+                StandardScript::SilentPayment(address),
+                value,
+                fee,
+                move |change_outpoint_range| {
+                    WalletOperationMeta::SendSilentPayment(SendSilentPaymentMeta {
+                        change_outpoint_range,
+                        address: encoded_clone.clone(),
+                        value,
+                        fee,
+                    })
+                },
+            )
+            .await?;
+
+        let mut dbtx = self.client_ctx.module_db().begin_transaction().await;
+
+        self.client_ctx
+            .log_event(
+                &mut dbtx,
+                SendSilentPaymentEvent {
+                    operation_id,
+                    address: encoded,
+                    value,
+                    fee,
+                },
+            )
+            .await;
+
+        dbtx.commit_tx().await;
+
+        Ok(operation_id)
+    }
+
+    async fn resolve_send_fee(
+        &self,
+        value: bitcoin::Amount,
+        fee: Option<bitcoin::Amount>,
+    ) -> Result<bitcoin::Amount, SendError> {
         if value < self.cfg.dust_limit {
             return Err(SendError::DustValue);
         }
 
-        let fee = match fee {
-            Some(value) => value,
+        match fee {
+            Some(value) => Ok(value),
             None => self
                 .module_api
                 .send_fee()
                 .await
                 .map_err(|e| SendError::FederationError(e.to_string()))?
-                .ok_or(SendError::NoConsensusFeerateAvailable)?,
-        };
+                .ok_or(SendError::NoConsensusFeerateAvailable),
+        }
+    }
 
+    async fn submit_send(
+        &self,
+        destination: StandardScript,
+        value: bitcoin::Amount,
+        fee: bitcoin::Amount,
+        meta_gen: impl Fn(OutPointRange) -> WalletOperationMeta
+        + Clone
+        + MaybeSend
+        + MaybeSync
+        + 'static,
+    ) -> Result<OperationId, SendError> {
         let operation_id = OperationId::new_random();
 
-        let destination = StandardScript::from_address(&address.clone().assume_checked())
-            .ok_or(SendError::UnsupportedAddress)?;
-
         let client_output = ClientOutput::<WalletOutput> {
             output: WalletOutput::V0(WalletOutputV0 {
                 destination,
@@ -311,42 +468,79 @@ pub async fn send(
             vec![client_output_sm],
         ));
 
-        let address_clone = address.clone();
-
         self.client_ctx
             .finalize_and_submit_transaction(
                 operation_id,
                 WalletCommonInit::KIND.as_str(),
-                move |change_outpoint_range| {
-                    WalletOperationMeta::Send(SendMeta {
-                        change_outpoint_range,
-                        address: address_clone.clone(),
-                        value,
-                        fee,
-                    })
-                },
+                meta_gen,
                 TransactionBuilder::new().with_outputs(client_output_bundle),
             )
             .await
             .map_err(|_| SendError::InsufficientFunds)?;
 
-        let mut dbtx = self.client_ctx.module_db().begin_transaction().await;
+        Ok(operation_id)
+    }
 
-        self.client_ctx
-            .log_event(
-                &mut dbtx,
-                SendPaymentEvent {
-                    operation_id,
-                    address,
-                    value,
-                    fee,
-                },
-            )
-            .await;
+    /// Verify that the federation paid a silent payment to the given address
+    /// by checking the DLEQ proofs of the guardians' ECDH shares and
+    /// recomputing the output key they derived from them.
+    pub async fn verify_silent_payment(
+        &self,
+        operation_id: OperationId,
+        address: &SilentPaymentAddress,
+    ) -> anyhow::Result<SilentPaymentProof> {
+        let WalletDescriptor::Tr { aggregate_pk } = self.cfg.descriptor else {
+            bail!("The federation does not use a taproot descriptor");
+        };
 
-        dbtx.commit_tx().await;
+        let operation = self.client_ctx.get_operation(operation_id).await?;
 
-        Ok(operation_id)
+        let WalletOperationMeta::SendSilentPayment(meta) = operation.meta::<WalletOperationMeta>()
+        else {
+            bail!("Operation is not a silent payment");
+        };
+
+        let outpoint = OutPoint {
+            txid: meta.change_outpoint_range.txid(),
+            out_idx: 0,
+        };
+
+        let proof = self
+            .module_api
+            .silent_payment_proof(outpoint)
+            .await?
+            .context("The federation has not derived the silent payment yet")?;
+
+        ensure!(
+            proof.shares.len() == self.cfg.bitcoin_pks.to_num_peers().threshold(),
+            "Proof does not contain a threshold of ECDH shares"
+        );
+
+        for (peer, share) in &proof.shares {
+            let pk = self
+                .cfg
+                .bitcoin_pks
+                .get(peer)
+                .context("ECDH share of unknown guardian")?;
+
+            share.verify(pk, &address.scan)?;
+        }
+
+        let script_pubkey = silent_payments::script_pubkey(
+            address,
+            &proof.shares,
+            &self.cfg.bitcoin_pks,
+            &aggregate_pk,
+            &proof.input,
+            &proof.tweak,
+        );
+
+        ensure!(
+            script_pubkey == proof.script_pubkey,
+            "Federation paid to a different output key"
+        );
+
+        Ok(proof)
     }
 
     /// Await the final state of the send operation.
@@ -362,7 +556,7 @@ pub async fn await_final_send_operation_state(
             };
 
             match state.state {
-                SendSMState::Funding => {}
+                SendSMState::Funding | SendSMState::Queued => {}
                 SendSMState::Success(txid) => return FinalSendOperationState::Success(txid),
                 SendSMState::Aborted(..) => return FinalSendOperationState::Aborted,
                 SendSMState::Failure => return FinalSendOperationState::Failure,
@@ -391,11 +585,14 @@ pub async fn receive(&self) -> Address {
     }
 
     fn derive_address(&self, index: u64) -> Address {
-        descriptor(
-            &self.cfg.bitcoin_pks,
-            &self.derive_tweak(index).public_key().consensus_hash(),
-        )
-        .address(self.cfg.network)
+        self.cfg
+            .descriptor
+            .descriptor(
+                &self.cfg.bitcoin_pks,
+                &self.derive_tweak(index).public_key().consensus_hash(),
+            )
+            .address(self.cfg.network)
+            .expect("Federation descriptor has an address")
     }
 
     fn derive_tweak(&self, index: u64) -> Keypair {
@@ -3,9 +3,12 @@
 use fedimint_core::OutPoint;
 use fedimint_core::core::OperationId;
 use fedimint_core::encoding::{Decodable, Encodable};
+use fedimint_core::module::ApiVersion;
+use fedimint_core::task::sleep;
+use fedimint_walletv2_common::PegOutStatus;
 
 use crate::WalletClientContext;
-use crate::api::WalletFederationApi;
+use crate::api::{VERSION_THAT_INTRODUCED_PEGOUT_STATUS, WalletFederationApi};
 use crate::events::{SendPaymentStatus, SendPaymentUpdateEvent};
 
 #[derive(Debug, Clone, Eq, PartialEq, Hash, Decodable, Encodable)]
@@ -37,6 +40,7 @@ pub enum SendSMState {
     Success(bitcoin::Txid),
     Aborted(String),
     Failure,
+    Queued,
 }
 
 impl State for SendStateMachine {
@@ -52,7 +56,11 @@ fn transitions(
         match &self.state {
             SendSMState::Funding => {
                 vec![StateTransition::new(
-                    Self::await_funding(global_context.clone(), self.common.outpoint),
+                    Self::await_funding(
+                        global_context.clone(),
+                        context.module_api_version,
+                        self.common.outpoint,
+                    ),
                     move |dbtx, result, old_state| {
                         Box::pin(Self::transition_funding(
                             ctx.clone(),
@@ -63,6 +71,14 @@ fn transitions(
                     },
                 )]
             }
+            SendSMState::Queued => {
+                vec![StateTransition::new(
+                    Self::await_batch(global_context.clone(), self.common.outpoint),
+                    move |dbtx, txid, old_state| {
+                        Box::pin(Self::transition_batch(ctx.clone(), dbtx, txid, old_state))
+                    },
+                )]
+            }
             SendSMState::Success(_) | SendSMState::Aborted(_) | SendSMState::Failure => vec![],
         }
     }
@@ -77,19 +93,33 @@ enum AwaitFundingResult {
     Success(bitcoin::Txid),
     Aborted(String),
     Failure,
+    Queued,
 }
 
 impl SendStateMachine {
     async fn await_funding(
         global_context: DynGlobalClientContext,
+        module_api_version: ApiVersion,
         outpoint: OutPoint,
     ) -> AwaitFundingResult {
         if let Err(error) = global_context.await_tx_accepted(outpoint.txid).await {
             return AwaitFundingResult::Aborted(error);
         }
 
-        match global_context.module_api().tx_id(outpoint).await {
-            Some(txid) => AwaitFundingResult::Success(txid),
+        // Older guardians do not batch peg-outs, hence the federation
+        // transaction already exists once our transaction has been accepted
+        if module_api_version < VERSION_THAT_INTRODUCED_PEGOUT_STATUS {
+            return match global_context.module_api().tx_id(outpoint).await {
+                Some(txid) => AwaitFundingResult::Success(txid),
+                None => AwaitFundingResult::Failure,
+            };
+        }
+
+        match global_context.module_api().pegout_status(outpoint).await {
+            Some(PegOutStatus::Sent(txid)) => AwaitFundingResult::Success(txid),
+            Some(PegOutStatus::Queued { .. } | PegOutStatus::Deriving) => {
+                AwaitFundingResult::Queued
+            }
             None => AwaitFundingResult::Failure,
         }
     }
@@ -130,6 +160,59 @@ async fn transition_funding(
                 old_state.update(SendSMState::Aborted(error))
             }
             AwaitFundingResult::Failure => old_state.update(SendSMState::Failure),
+            AwaitFundingResult::Queued => {
+                context
+                    .client_ctx
+                    .log_event(
+                        &mut dbtx.module_tx(),
+                        SendPaymentUpdateEvent {
+                            operation_id: old_state.common.operation_id,
+                            status: SendPaymentStatus::Queued,
+                        },
+                    )
+                    .await;
+
+                old_state.update(SendSMState::Queued)
+            }
+        }
+    }
+
+    async fn await_batch(
+        global_context: DynGlobalClientContext,
+        outpoint: OutPoint,
+    ) -> Option<bitcoin::Txid> {
+        loop {
+            match global_context.module_api().pegout_status(outpoint).await {
+                Some(PegOutStatus::Sent(txid)) => return Some(txid),
+                Some(PegOutStatus::Queued { .. } | PegOutStatus::Deriving) => {}
+                None => return None,
+            }
+
+            sleep(fedimint_walletv2_common::sleep_duration()).await;
         }
     }
+
+    async fn transition_batch(
+        context: WalletClientContext,
+        dbtx: &mut ClientSMDatabaseTransaction<'_, '_>,
+        txid: Option<bitcoin::Txid>,
+        old_state: SendStateMachine,
+    ) -> SendStateMachine {
+        let Some(txid) = txid else {
+            return old_state.update(SendSMState::Failure);
+        };
+
+        context
+            .client_ctx
+            .log_event(
+                &mut dbtx.module_tx(),
+                SendPaymentUpdateEvent {
+                    operation_id: old_state.common.operation_id,
+                    status: SendPaymentStatus::Success(txid),
+                },
+            )
+            .await;
+
+        old_state.update(SendSMState::Success(txid))
+    }
 }
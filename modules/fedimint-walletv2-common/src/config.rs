@@ -3,12 +3,14 @@
 use bitcoin::Network;
 use bitcoin::hashes::{Hash, sha256};
 use fedimint_core::core::ModuleKind;
-use fedimint_core::encoding::{Decodable, Encodable};
+use fedimint_core::encoding::{Decodable, DecodeError, Encodable};
+use fedimint_core::module::registry::ModuleDecoderRegistry;
 use fedimint_core::{Amount, PeerId, plugin_types_trait_impl_config, weight_to_vbytes};
+use miniscript::Descriptor;
 use secp256k1::{PublicKey, SecretKey};
 use serde::{Deserialize, Serialize};
 
-use crate::{WalletCommonInit, descriptor};
+use crate::{WalletCommonInit, descriptor, tr_descriptor};
 
 plugin_types_trait_impl_config!(
     WalletCommonInit,
@@ -26,10 +28,13 @@ pub struct WalletConfig {
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct WalletConfigPrivate {
-    pub bitcoin_sk: SecretKey,
+    /// Our bitcoin key share, missing once it has been exported to a remote
+    /// guardian signer
+    #[serde(default)]
+    pub bitcoin_sk: Option<SecretKey>,
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize, Encodable, Decodable)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct WalletConfigConsensus {
     /// The public keys for the bitcoin multisig
     pub bitcoin_pks: BTreeMap<PeerId, PublicKey>,
@@ -48,6 +53,116 @@ pub struct WalletConfigConsensus {
     pub fee_consensus: FeeConsensus,
     /// Bitcoin network (e.g. testnet, bitcoin)
     pub network: Network,
+    /// If set, peg-outs are collected and sent in a single transaction
+    #[serde(default)]
+    pub pegout_batching: Option<PegOutBatching>,
+    /// Number of consensus blocks a transaction has to remain unconfirmed
+    /// before the guardians may bump its fee
+    #[serde(default = "default_fee_bump_delay")]
+    pub fee_bump_delay: u64,
+}
+
+/// Number of consensus blocks a transaction has to remain unconfirmed before
+/// its fee is bumped, unless configured otherwise.
+pub const DEFAULT_FEE_BUMP_DELAY: u64 = 6;
+
+fn default_fee_bump_delay() -> u64 {
+    DEFAULT_FEE_BUMP_DELAY
+}
+
+impl Encodable for WalletConfigConsensus {
+    fn consensus_encode<W: std::io::Write>(&self, writer: &mut W) -> Result<(), std::io::Error> {
+        self.bitcoin_pks.consensus_encode(writer)?;
+        self.descriptor.consensus_encode(writer)?;
+        self.send_tx_vbytes.consensus_encode(writer)?;
+        self.receive_tx_vbytes.consensus_encode(writer)?;
+        self.feerate_base.consensus_encode(writer)?;
+        self.dust_limit.consensus_encode(writer)?;
+        self.fee_consensus.consensus_encode(writer)?;
+        self.network.consensus_encode(writer)?;
+
+        encode_extension(
+            &(self.pegout_batching.clone(), self.fee_bump_delay),
+            &(None, DEFAULT_FEE_BUMP_DELAY),
+            writer,
+        )
+    }
+}
+
+impl Decodable for WalletConfigConsensus {
+    fn consensus_decode_partial_from_finite_reader<R: std::io::Read>(
+        r: &mut R,
+        modules: &ModuleDecoderRegistry,
+    ) -> Result<Self, DecodeError> {
+        let bitcoin_pks = Decodable::consensus_decode_partial_from_finite_reader(r, modules)?;
+        let descriptor = Decodable::consensus_decode_partial_from_finite_reader(r, modules)?;
+        let send_tx_vbytes = Decodable::consensus_decode_partial_from_finite_reader(r, modules)?;
+        let receive_tx_vbytes = Decodable::consensus_decode_partial_from_finite_reader(r, modules)?;
+        let feerate_base = Decodable::consensus_decode_partial_from_finite_reader(r, modules)?;
+        let dust_limit = Decodable::consensus_decode_partial_from_finite_reader(r, modules)?;
+        let fee_consensus = Decodable::consensus_decode_partial_from_finite_reader(r, modules)?;
+        let network = Decodable::consensus_decode_partial_from_finite_reader(r, modules)?;
+
+        let (pegout_batching, fee_bump_delay) =
+            decode_extension(r, modules, (None, DEFAULT_FEE_BUMP_DELAY))?;
+
+        Ok(Self {
+            bitcoin_pks,
+            descriptor,
+            send_tx_vbytes,
+            receive_tx_vbytes,
+            feerate_base,
+            dust_limit,
+            fee_consensus,
+            network,
+            pegout_batching,
+            fee_bump_delay,
+        })
+    }
+}
+
+/// Marks the start of the extension of a config, see [`encode_extension`]
+const CONFIG_EXTENSION_MARKER: u8 = 1;
+
+/// Fields added to a config after the module was first released are encoded
+/// as an extension following the original fields. The extension is omitted as
+/// long as it equals its default, such that the configs of existing
+/// federations keep their encoding and still decode.
+fn encode_extension<E: Encodable + PartialEq, W: std::io::Write>(
+    extension: &E,
+    default: &E,
+    writer: &mut W,
+) -> Result<(), std::io::Error> {
+    if extension != default {
+        CONFIG_EXTENSION_MARKER.consensus_encode(writer)?;
+        extension.consensus_encode(writer)?;
+    }
+
+    Ok(())
+}
+
+/// Decodes the extension of a config as encoded by [`encode_extension`]. A
+/// config is always decoded from a reader that is limited to its encoding,
+/// hence a missing extension is indicated by the end of the reader.
+fn decode_extension<E: Decodable, R: std::io::Read>(
+    r: &mut R,
+    modules: &ModuleDecoderRegistry,
+    default: E,
+) -> Result<E, DecodeError> {
+    let mut marker = [0; 1];
+
+    if r.read(&mut marker).map_err(DecodeError::from_err)? == 0 {
+        return Ok(default);
+    }
+
+    if marker[0] != CONFIG_EXTENSION_MARKER {
+        return Err(DecodeError::new_custom(anyhow::anyhow!(
+            "Invalid config extension marker {}",
+            marker[0]
+        )));
+    }
+
+    E::consensus_decode_partial_from_finite_reader(r, modules)
 }
 
 impl WalletConfigConsensus {
@@ -74,32 +189,19 @@ impl WalletConfigConsensus {
     /// | 18        | 530  | 920     |
     /// | 19        | 539  | 937     |
     /// | 20        | 565  | 991     |
+    ///
+    /// With the taproot descriptor the federation spends via the key path
+    /// such that a send and receive transaction are always 154 and 169 vbytes
+    /// respectively, independent of the number of guardians.
     pub fn new(
         bitcoin_pks: BTreeMap<PeerId, PublicKey>,
+        descriptor: WalletDescriptor,
         fee_consensus: FeeConsensus,
         network: Network,
+        pegout_batching: Option<PegOutBatching>,
+        fee_bump_delay: u64,
     ) -> Self {
-        let tx_overhead_weight = 4 * 4 // nVersion
-            + 1 // SegWit marker
-            + 1 // SegWit flag
-            + 4 // up to 2 inputs
-            + 4 // up to 2 outputs
-            + 4 * 4; // nLockTime
-
-        let change_witness_weight = descriptor(&bitcoin_pks, &sha256::Hash::all_zeros())
-            .max_weight_to_satisfy()
-            .expect("Cannot satisfy the change descriptor.")
-            .to_wu();
-
-        let change_input_weight = 32 * 4 // txid
-            + 4 * 4 // vout
-            + 4 // Script length
-            + 4 * 4 // nSequence
-            + change_witness_weight;
-
-        let change_output_weight = 8 * 4 // nValue
-            + 4 // scriptPubKey length
-            + 34 * 4; // scriptPubKey
+        let change_input_weight = change_input_weight(&bitcoin_pks, &descriptor);
 
         let destination_output_weight = 8 * 4 // nValue
             + 4 // scriptPubKey length
@@ -107,18 +209,18 @@ pub fn new(
 
         Self {
             bitcoin_pks,
-            descriptor: WalletDescriptor::Wsh,
+            descriptor,
             send_tx_vbytes: weight_to_vbytes(
-                tx_overhead_weight
+                TX_OVERHEAD_WEIGHT
                     + change_input_weight
-                    + change_output_weight
+                    + CHANGE_OUTPUT_WEIGHT
                     + destination_output_weight,
             ),
             receive_tx_vbytes: weight_to_vbytes(
-                tx_overhead_weight
+                TX_OVERHEAD_WEIGHT
                     + change_input_weight
                     + change_input_weight
-                    + change_output_weight,
+                    + CHANGE_OUTPUT_WEIGHT,
             ),
             // This is intentionally lower than the 1 sat/vB minimum feerate
             // vote floor. This allows for at least three pending transactions
@@ -128,8 +230,72 @@ pub fn new(
             dust_limit: bitcoin::Amount::from_sat(10_000),
             fee_consensus,
             network,
+            pegout_batching,
+            fee_bump_delay,
         }
     }
+
+    /// Total vbytes of a child transaction bumping the fee of the pending
+    /// transactions by spending the change output back to the federation.
+    /// This is derived from the descriptor instead of being part of the config,
+    /// such that it is available to federations created before fee bumping.
+    pub fn bump_tx_vbytes(&self) -> u64 {
+        weight_to_vbytes(
+            TX_OVERHEAD_WEIGHT
+                + change_input_weight(&self.bitcoin_pks, &self.descriptor)
+                + CHANGE_OUTPUT_WEIGHT,
+        )
+    }
+}
+
+const TX_OVERHEAD_WEIGHT: u64 = 4 * 4 // nVersion
+    + 1 // SegWit marker
+    + 1 // SegWit flag
+    + 4 // up to 2 inputs
+    + 4 // up to 2 outputs
+    + 4 * 4; // nLockTime
+
+const CHANGE_OUTPUT_WEIGHT: u64 = 8 * 4 // nValue
+    + 4 // scriptPubKey length
+    + 34 * 4; // scriptPubKey
+
+fn change_input_weight(
+    bitcoin_pks: &BTreeMap<PeerId, PublicKey>,
+    descriptor: &WalletDescriptor,
+) -> u64 {
+    let change_witness_weight = match descriptor {
+        WalletDescriptor::Wsh => descriptor_weight(bitcoin_pks),
+        WalletDescriptor::Tr { .. } => {
+            1 // Witness item count
+            + 1 // Signature length
+            + 64 // BIP340 signature with default sighash type
+        }
+    };
+
+    32 * 4 // txid
+        + 4 * 4 // vout
+        + 4 // Script length
+        + 4 * 4 // nSequence
+        + change_witness_weight
+}
+
+/// Peg-outs are queued until the batch is closed, either once the window of
+/// consensus blocks since the first queued peg-out has passed or the maximum
+/// number of peg-outs has been reached. The federation then sends all queued
+/// peg-outs in a single transaction, amortizing its overhead.
+#[derive(Clone, Debug, Eq, PartialEq, Hash, Serialize, Deserialize, Encodable, Decodable)]
+pub struct PegOutBatching {
+    /// Number of consensus blocks a batch collects peg-outs
+    pub window: u64,
+    /// Maximum number of peg-outs in a single batch
+    pub max_pegouts: u64,
+}
+
+fn descriptor_weight(bitcoin_pks: &BTreeMap<PeerId, PublicKey>) -> u64 {
+    descriptor(bitcoin_pks, &sha256::Hash::all_zeros())
+        .max_weight_to_satisfy()
+        .expect("Cannot satisfy the change descriptor.")
+        .to_wu()
 }
 
 #[derive(Debug, Clone, Eq, PartialEq, Hash, Serialize, Deserialize, Encodable, Decodable)]
@@ -204,14 +370,35 @@ fn test_fee_consensus() {
     );
 }
 
-/// Which kind of bitcoin descriptor the federation uses. Currently only `Wsh`
-/// is defined, we can expand in the future.
+/// Which kind of bitcoin descriptor the federation uses. The kind is chosen
+/// once during config generation and can not be changed afterwards.
 #[derive(Clone, Debug, Eq, PartialEq, Hash, Serialize, Deserialize, Encodable, Decodable)]
 pub enum WalletDescriptor {
+    /// A P2WSH threshold multisig over the guardians' public keys signed with
+    /// one ECDSA signature per guardian.
     Wsh,
+    /// A P2TR output spent via the key path with a threshold Schnorr signature
+    /// for the aggregate of the guardians' key shares. The script path holds a
+    /// threshold multisig over the key shares as a fallback.
+    Tr { aggregate_pk: PublicKey },
 }
 
-#[derive(Clone, Debug, Eq, PartialEq, Hash, Serialize, Deserialize, Encodable, Decodable)]
+impl WalletDescriptor {
+    pub fn descriptor(
+        &self,
+        bitcoin_pks: &BTreeMap<PeerId, PublicKey>,
+        tweak: &sha256::Hash,
+    ) -> Descriptor<PublicKey> {
+        match self {
+            WalletDescriptor::Wsh => Descriptor::Wsh(descriptor(bitcoin_pks, tweak)),
+            WalletDescriptor::Tr { aggregate_pk } => {
+                Descriptor::Tr(tr_descriptor(bitcoin_pks, aggregate_pk, tweak))
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
 pub struct WalletClientConfig {
     /// The public keys for the bitcoin multisig
     pub bitcoin_pks: BTreeMap<PeerId, PublicKey>,
@@ -230,6 +417,43 @@ pub struct WalletClientConfig {
     pub fee_consensus: FeeConsensus,
     /// Bitcoin network (e.g. testnet, bitcoin)
     pub network: Network,
+    /// If set, peg-outs are collected and sent in a single transaction
+    #[serde(default)]
+    pub pegout_batching: Option<PegOutBatching>,
+}
+
+impl Encodable for WalletClientConfig {
+    fn consensus_encode<W: std::io::Write>(&self, writer: &mut W) -> Result<(), std::io::Error> {
+        self.bitcoin_pks.consensus_encode(writer)?;
+        self.descriptor.consensus_encode(writer)?;
+        self.send_tx_vbytes.consensus_encode(writer)?;
+        self.receive_tx_vbytes.consensus_encode(writer)?;
+        self.feerate_base.consensus_encode(writer)?;
+        self.dust_limit.consensus_encode(writer)?;
+        self.fee_consensus.consensus_encode(writer)?;
+        self.network.consensus_encode(writer)?;
+
+        encode_extension(&self.pegout_batching, &None, writer)
+    }
+}
+
+impl Decodable for WalletClientConfig {
+    fn consensus_decode_partial_from_finite_reader<R: std::io::Read>(
+        r: &mut R,
+        modules: &ModuleDecoderRegistry,
+    ) -> Result<Self, DecodeError> {
+        Ok(Self {
+            bitcoin_pks: Decodable::consensus_decode_partial_from_finite_reader(r, modules)?,
+            descriptor: Decodable::consensus_decode_partial_from_finite_reader(r, modules)?,
+            send_tx_vbytes: Decodable::consensus_decode_partial_from_finite_reader(r, modules)?,
+            receive_tx_vbytes: Decodable::consensus_decode_partial_from_finite_reader(r, modules)?,
+            feerate_base: Decodable::consensus_decode_partial_from_finite_reader(r, modules)?,
+            dust_limit: Decodable::consensus_decode_partial_from_finite_reader(r, modules)?,
+            fee_consensus: Decodable::consensus_decode_partial_from_finite_reader(r, modules)?,
+            network: Decodable::consensus_decode_partial_from_finite_reader(r, modules)?,
+            pegout_batching: decode_extension(r, modules, None)?,
+        })
+    }
 }
 
 impl std::fmt::Display for WalletClientConfig {
@@ -237,3 +461,140 @@ fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "WalletClientConfig {self:?}")
     }
 }
+
+#[test]
+fn test_client_config_extension() {
+    #[derive(Encodable)]
+    struct LegacyWalletClientConfig {
+        bitcoin_pks: BTreeMap<PeerId, PublicKey>,
+        descriptor: WalletDescriptor,
+        send_tx_vbytes: u64,
+        receive_tx_vbytes: u64,
+        feerate_base: u64,
+        dust_limit: bitcoin::Amount,
+        fee_consensus: FeeConsensus,
+        network: Network,
+    }
+
+    let config = WalletClientConfig {
+        bitcoin_pks: BTreeMap::from([(
+            PeerId::from(0),
+            SecretKey::from_slice(&[1; 32])
+                .expect("Valid secret key")
+                .public_key(secp256k1::SECP256K1),
+        )]),
+        descriptor: WalletDescriptor::Wsh,
+        send_tx_vbytes: 166,
+        receive_tx_vbytes: 192,
+        feerate_base: 250,
+        dust_limit: bitcoin::Amount::from_sat(10_000),
+        fee_consensus: FeeConsensus::new(0).expect("Relative fee is within range"),
+        network: Network::Regtest,
+        pegout_batching: None,
+    };
+
+    let legacy = LegacyWalletClientConfig {
+        bitcoin_pks: config.bitcoin_pks.clone(),
+        descriptor: config.descriptor.clone(),
+        send_tx_vbytes: config.send_tx_vbytes,
+        receive_tx_vbytes: config.receive_tx_vbytes,
+        feerate_base: config.feerate_base,
+        dust_limit: config.dust_limit,
+        fee_consensus: config.fee_consensus.clone(),
+        network: config.network,
+    }
+    .consensus_encode_to_vec();
+
+    // Configs of existing federations keep their encoding and still decode
+    assert_eq!(config.consensus_encode_to_vec(), legacy);
+
+    assert_eq!(
+        WalletClientConfig::consensus_decode_whole(&legacy, &ModuleDecoderRegistry::default())
+            .expect("Failed to decode legacy config"),
+        config
+    );
+
+    let batching = WalletClientConfig {
+        pegout_batching: Some(PegOutBatching {
+            window: 6,
+            max_pegouts: 100,
+        }),
+        ..config
+    };
+
+    assert_eq!(
+        WalletClientConfig::consensus_decode_whole(
+            &batching.consensus_encode_to_vec(),
+            &ModuleDecoderRegistry::default()
+        )
+        .expect("Failed to decode config"),
+        batching
+    );
+}
+
+#[test]
+fn test_consensus_config_extension() {
+    #[derive(Encodable)]
+    struct LegacyWalletConfigConsensus {
+        bitcoin_pks: BTreeMap<PeerId, PublicKey>,
+        descriptor: WalletDescriptor,
+        send_tx_vbytes: u64,
+        receive_tx_vbytes: u64,
+        feerate_base: u64,
+        dust_limit: bitcoin::Amount,
+        fee_consensus: FeeConsensus,
+        network: Network,
+    }
+
+    let bitcoin_pks = BTreeMap::from([(
+        PeerId::from(0),
+        SecretKey::from_slice(&[1; 32])
+            .expect("Valid secret key")
+            .public_key(secp256k1::SECP256K1),
+    )]);
+
+    let config = WalletConfigConsensus::new(
+        bitcoin_pks,
+        WalletDescriptor::Wsh,
+        FeeConsensus::new(0).expect("Relative fee is within range"),
+        Network::Regtest,
+        None,
+        DEFAULT_FEE_BUMP_DELAY,
+    );
+
+    let legacy = LegacyWalletConfigConsensus {
+        bitcoin_pks: config.bitcoin_pks.clone(),
+        descriptor: config.descriptor.clone(),
+        send_tx_vbytes: config.send_tx_vbytes,
+        receive_tx_vbytes: config.receive_tx_vbytes,
+        feerate_base: config.feerate_base,
+        dust_limit: config.dust_limit,
+        fee_consensus: config.fee_consensus.clone(),
+        network: config.network,
+    }
+    .consensus_encode_to_vec();
+
+    // Configs of existing federations keep their encoding and still decode
+    assert_eq!(config.consensus_encode_to_vec(), legacy);
+
+    let decoded =
+        WalletConfigConsensus::consensus_decode_whole(&legacy, &ModuleDecoderRegistry::default())
+            .expect("Failed to decode legacy config");
+
+    assert_eq!(decoded.pegout_batching, None);
+    assert_eq!(decoded.fee_bump_delay, DEFAULT_FEE_BUMP_DELAY);
+
+    let config = WalletConfigConsensus {
+        fee_bump_delay: 12,
+        ..config
+    };
+
+    let decoded = WalletConfigConsensus::consensus_decode_whole(
+        &config.consensus_encode_to_vec(),
+        &ModuleDecoderRegistry::default(),
+    )
+    .expect("Failed to decode config");
+
+    assert_eq!(decoded.pegout_batching, None);
+    assert_eq!(decoded.fee_bump_delay, 12);
+}
@@ -4,6 +4,11 @@
 pub const RECEIVE_FEE_ENDPOINT: &str = "receive_fee";
 pub const SEND_FEE_ENDPOINT: &str = "send_fee";
 pub const TRANSACTION_ID_ENDPOINT: &str = "transaction_id";
+pub const PEGOUT_STATUS_ENDPOINT: &str = "pegout_status";
+pub const SILENT_PAYMENT_PROOF_ENDPOINT: &str = "silent_payment_proof";
 pub const OUTPUT_INFO_SLICE_ENDPOINT: &str = "output_info_slice";
 pub const PENDING_TRANSACTION_CHAIN_ENDPOINT: &str = "pending_transaction_chain";
 pub const TRANSACTION_CHAIN_ENDPOINT: &str = "transaction_chain";
+pub const WATCH_ONLY_DESCRIPTORS_ENDPOINT: &str = "watch_only_descriptors";
+pub const MODULE_CONSENSUS_VERSION_ENDPOINT: &str = "module_consensus_version";
+pub const SUPPORTED_MODULE_CONSENSUS_VERSION_ENDPOINT: &str = "supported_module_consensus_version";
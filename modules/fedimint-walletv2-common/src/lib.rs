@@ -6,10 +6,12 @@
 #![allow(clippy::return_self_not_must_use)]
 
 use std::collections::BTreeMap;
+use std::sync::Arc;
 use std::time::Duration;
 
 use bitcoin::hashes::{Hash, hash160, sha256};
-use bitcoin::key::TapTweak;
+use bitcoin::key::{Parity, TapTweak};
+use bitcoin::taproot::{TapNodeHash, TapTweakHash};
 use bitcoin::{Address, PubkeyHash, ScriptBuf, ScriptHash, Txid, WPubkeyHash, WScriptHash};
 use config::WalletClientConfig;
 use fedimint_core::core::{Decoder, ModuleInstanceId, ModuleKind};
@@ -18,18 +20,49 @@
 use fedimint_core::{
     NumPeersExt, PeerId, extensible_associated_module_type, plugin_types_trait_impl_common,
 };
-use miniscript::descriptor::Wsh;
+use miniscript::descriptor::{TapTree, Tr, Wsh};
+use miniscript::{Miniscript, Tap, Terminal, Threshold};
 use secp256k1::ecdsa::Signature;
-use secp256k1::{PublicKey, Scalar, XOnlyPublicKey};
+use secp256k1::{PublicKey, Scalar, SecretKey, XOnlyPublicKey};
 use serde::{Deserialize, Serialize};
+use silent_payments::{EcdhShare, SilentPaymentAddress};
 use thiserror::Error;
 
 pub mod config;
 pub mod endpoint_constants;
+pub mod silent_payments;
+pub mod threshold;
 
 pub const KIND: ModuleKind = ModuleKind::from_static_str("walletv2");
 
-pub const MODULE_CONSENSUS_VERSION: ModuleConsensusVersion = ModuleConsensusVersion::new(1, 0);
+pub const MODULE_CONSENSUS_VERSION: ModuleConsensusVersion = ModuleConsensusVersion::new(1, 4);
+
+/// The module consensus version that introduced the taproot descriptor and the
+/// threshold signing sessions via [`WalletConsensusItem::Nonces`] and
+/// [`WalletConsensusItem::PartialSignatures`]. Before it was activated for a
+/// federation neither is accepted, so not yet upgraded guardians stay in
+/// consensus.
+pub const TAPROOT_MODULE_CONSENSUS_VERSION: ModuleConsensusVersion =
+    ModuleConsensusVersion::new(1, 1);
+
+/// The module consensus version that introduced the batching of peg-outs.
+/// Before it was activated for a federation every peg-out is sent in its own
+/// transaction, even if the config enables batching.
+pub const PEGOUT_BATCHING_MODULE_CONSENSUS_VERSION: ModuleConsensusVersion =
+    ModuleConsensusVersion::new(1, 2);
+
+/// The module consensus version that introduced bumping the fee of stuck
+/// transactions via [`WalletConsensusItem::FeeBump`]. Before it was activated
+/// for a federation fee bump votes are not accepted.
+pub const FEE_BUMP_MODULE_CONSENSUS_VERSION: ModuleConsensusVersion =
+    ModuleConsensusVersion::new(1, 3);
+
+/// The module consensus version that introduced peg-outs to a
+/// [`StandardScript::SilentPayment`] and the [`WalletConsensusItem::EcdhShare`]
+/// deriving their output keys. Before it was activated for a federation
+/// neither is accepted.
+pub const SILENT_PAYMENTS_MODULE_CONSENSUS_VERSION: ModuleConsensusVersion =
+    ModuleConsensusVersion::new(1, 4);
 
 /// Returns a sleep duration of 1 second in test environments or 60 seconds in
 /// production. Used for polling intervals where faster feedback is needed
@@ -52,6 +85,34 @@ pub fn descriptor(pks: &BTreeMap<PeerId, PublicKey>, tweak: &sha256::Hash) -> Ws
     .expect("Failed to construct Descriptor")
 }
 
+/// The taproot descriptor spends via the key path with a threshold Schnorr
+/// signature for the aggregate public key of the guardians' key shares. The
+/// single script leaf is a threshold multisig over the individual key shares
+/// which serves as a fallback should the federation ever be unable to complete
+/// a threshold signing session.
+pub fn tr_descriptor(
+    pks: &BTreeMap<PeerId, PublicKey>,
+    aggregate_pk: &PublicKey,
+    tweak: &sha256::Hash,
+) -> Tr<PublicKey> {
+    let multi_a = Threshold::new(
+        pks.to_num_peers().threshold(),
+        pks.values()
+            .map(|pk| tweak_public_key(pk, tweak))
+            .collect::<Vec<PublicKey>>(),
+    )
+    .expect("Failed to construct threshold");
+
+    let leaf = Miniscript::<PublicKey, Tap>::from_ast(Terminal::MultiA(multi_a))
+        .expect("Failed to construct Miniscript");
+
+    Tr::new(
+        tweak_public_key(aggregate_pk, tweak),
+        Some(TapTree::Leaf(Arc::new(leaf))),
+    )
+    .expect("Failed to construct Descriptor")
+}
+
 pub fn tweak_public_key(pk: &PublicKey, tweak: &sha256::Hash) -> PublicKey {
     pk.add_exp_tweak(
         secp256k1::SECP256K1,
@@ -60,6 +121,61 @@ pub fn tweak_public_key(pk: &PublicKey, tweak: &sha256::Hash) -> PublicKey {
     .expect("Failed to tweak bitcoin public key")
 }
 
+/// Relates the aggregate secret key of the guardians to the secret key of the
+/// output key of a federation UTXO locked to the taproot descriptor with the
+/// given tweak.
+pub struct KeyPathTweak {
+    /// The tweaked output key the UTXO is locked to
+    pub output_key: XOnlyPublicKey,
+    /// Whether the BIP340 secret key for the output key contains the negated
+    /// aggregate secret key
+    pub negate: bool,
+    /// The public difference between the BIP340 secret key for the output key
+    /// and the possibly negated aggregate secret key
+    pub tweak: SecretKey,
+}
+
+impl KeyPathTweak {
+    pub fn new(
+        aggregate_pk: &PublicKey,
+        tweak: &sha256::Hash,
+        merkle_root: Option<TapNodeHash>,
+    ) -> Self {
+        let (internal_key, internal_parity) =
+            tweak_public_key(aggregate_pk, tweak).x_only_public_key();
+
+        let (output_key, output_parity) = internal_key.tap_tweak(secp256k1::SECP256K1, merkle_root);
+
+        let tap_tweak = TapTweakHash::from_key_and_tweak(internal_key, merkle_root).to_scalar();
+
+        let negate_internal = internal_parity == Parity::Odd;
+        let negate_output = output_parity == Parity::Odd;
+
+        // The secret key for the output key is
+        // g_out * (g_in * (x + tweak) + tap_tweak)
+        let tweak =
+            SecretKey::from_slice(&tweak.to_byte_array()).expect("Hash is within field order");
+
+        let tweak = if negate_internal {
+            tweak.negate()
+        } else {
+            tweak
+        };
+
+        let tweak = tweak
+            .add_tweak(&tap_tweak)
+            .expect("Sum of scalars is non-zero");
+
+        let tweak = if negate_output { tweak.negate() } else { tweak };
+
+        Self {
+            output_key: output_key.to_inner(),
+            negate: negate_internal != negate_output,
+            tweak,
+        }
+    }
+}
+
 /// Returns true if the script pubkey potentially belongs to the federation.
 /// This uses a probabilistic filter - only ~1/65536 of P2WSH scripts pass.
 pub fn is_potential_receive(script_pubkey: &ScriptBuf, pks_hash: &sha256::Hash) -> bool {
@@ -78,6 +194,58 @@ pub struct FederationWallet {
     pub tweak: sha256::Hash,
 }
 
+/// The descriptor of the federation wallet for one of the tweaks it has
+/// received funds to.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize, Encodable, Decodable)]
+pub struct TweakedDescriptor {
+    pub tweak: sha256::Hash,
+    /// The descriptor including its checksum
+    pub descriptor: String,
+    /// The consensus block count when the tweak was first used
+    pub first_used: u64,
+}
+
+/// A tracked output that no client has claimed yet. Its tweak is only known to
+/// the depositing client, hence its descriptor is the raw script and cannot be
+/// verified against the keys of the federation.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize, Encodable, Decodable)]
+pub struct UnclaimedDeposit {
+    pub outpoint: bitcoin::OutPoint,
+    pub value: bitcoin::Amount,
+    pub script_pubkey: ScriptBuf,
+    /// The raw descriptor including its checksum
+    pub descriptor: String,
+}
+
+impl UnclaimedDeposit {
+    pub fn new(outpoint: bitcoin::OutPoint, tx_out: &bitcoin::TxOut) -> Self {
+        Self {
+            outpoint,
+            value: tx_out.value,
+            script_pubkey: tx_out.script_pubkey.clone(),
+            descriptor: raw_descriptor(&tx_out.script_pubkey),
+        }
+    }
+}
+
+/// Returns the raw descriptor of the script including its checksum.
+pub fn raw_descriptor(script_pubkey: &ScriptBuf) -> String {
+    let descriptor = format!("raw({})", script_pubkey.to_hex_string());
+
+    let checksum = miniscript::descriptor::checksum::desc_checksum(&descriptor)
+        .expect("Raw descriptor only contains valid characters");
+
+    format!("{descriptor}#{checksum}")
+}
+
+/// The descriptors required to track all on-chain funds of the federation with
+/// a watch-only wallet.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize, Encodable, Decodable)]
+pub struct WatchOnlyDescriptors {
+    pub tweaked: Vec<TweakedDescriptor>,
+    pub unclaimed: Vec<UnclaimedDeposit>,
+}
+
 #[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize, Encodable, Decodable)]
 pub struct TxInfo {
     pub index: u64,
@@ -95,6 +263,19 @@ pub fn feerate(&self) -> u64 {
     }
 }
 
+/// The status of a peg-out accepted by the federation.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub enum PegOutStatus {
+    /// The peg-out waits for its batch to be closed, which happens at the
+    /// latest once the consensus block count reaches `closes`.
+    Queued { closes: u64 },
+    /// The peg-out is part of the federation transaction with this txid.
+    Sent(bitcoin::Txid),
+    /// The peg-out pays a silent payment address and waits for the guardians
+    /// to derive its output key.
+    Deriving,
+}
+
 #[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
 pub struct OutputInfo {
     pub index: u64,
@@ -136,6 +317,11 @@ pub enum WalletConsensusItem {
     BlockCount(u64),
     Feerate(Option<u64>),
     Signatures(Txid, Vec<Signature>),
+    Nonces(Txid, u64, Vec<NonceCommitment>),
+    PartialSignatures(Txid, Vec<PartialSignature>),
+    FeeBump(Txid),
+    EcdhShare(fedimint_core::OutPoint, EcdhShare),
+    ModuleConsensusVersion(ModuleConsensusVersion),
     #[encodable_default]
     Default {
         variant: u64,
@@ -155,6 +341,25 @@ fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
             WalletConsensusItem::Signatures(..) => {
                 write!(f, "Wallet Signatures")
             }
+            WalletConsensusItem::Nonces(_, session, _) => {
+                write!(f, "Wallet Nonces for Signing Session {session}")
+            }
+            WalletConsensusItem::PartialSignatures(..) => {
+                write!(f, "Wallet Partial Signatures")
+            }
+            WalletConsensusItem::FeeBump(txid) => {
+                write!(f, "Wallet Fee Bump Vote for {txid}")
+            }
+            WalletConsensusItem::EcdhShare(outpoint, _) => {
+                write!(f, "Wallet ECDH Share for Silent Payment {outpoint}")
+            }
+            WalletConsensusItem::ModuleConsensusVersion(version) => {
+                write!(
+                    f,
+                    "Wallet Consensus Version {}.{}",
+                    version.major, version.minor
+                )
+            }
             WalletConsensusItem::Default { variant, .. } => {
                 write!(f, "Unknown Wallet CI variant={variant}")
             }
@@ -162,6 +367,19 @@ fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
     }
 }
 
+/// The public nonce commitments of a guardian for one input of a transaction
+/// signed via the taproot key path.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize, Encodable, Decodable)]
+pub struct NonceCommitment {
+    pub hiding: PublicKey,
+    pub binding: PublicKey,
+}
+
+/// A guardian's share of the Schnorr signature for one input of a transaction
+/// signed via the taproot key path.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize, Encodable, Decodable)]
+pub struct PartialSignature(pub [u8; 32]);
+
 extensible_associated_module_type!(WalletInput, WalletInputV0, UnknownWalletInputVariantError);
 
 #[derive(Debug, Clone, Eq, PartialEq, Hash, Deserialize, Serialize, Encodable, Decodable)]
@@ -241,6 +459,8 @@ pub enum WalletOutputError {
     ArithmeticOverflow,
     #[error("Unknown script variant")]
     UnknownScriptVariant,
+    #[error("The federation does not support silent payments")]
+    SilentPaymentsUnsupported,
 }
 
 #[derive(Debug, Clone, Eq, PartialEq, Hash, Encodable, Decodable, Serialize, Deserialize)]
@@ -250,6 +470,9 @@ pub enum StandardScript {
     P2WPKH(hash160::Hash),
     P2WSH(sha256::Hash),
     P2TR(XOnlyPublicKey),
+    /// The output key is derived by the federation from the input of the
+    /// peg-out transaction, hence there is no script pubkey up front.
+    SilentPayment(SilentPaymentAddress),
     #[encodable_default]
     Default {
         variant: u64,
@@ -300,7 +523,7 @@ pub fn script_pubkey(&self) -> Option<ScriptBuf> {
             Self::P2WPKH(hash) => Some(ScriptBuf::new_p2wpkh(&WPubkeyHash::from_raw_hash(*hash))),
             Self::P2WSH(hash) => Some(ScriptBuf::new_p2wsh(&WScriptHash::from_raw_hash(*hash))),
             Self::P2TR(pk) => Some(ScriptBuf::new_p2tr_tweaked(pk.dangerous_assume_tweaked())),
-            Self::Default { .. } => None,
+            Self::SilentPayment(..) | Self::Default { .. } => None,
         }
     }
 }
@@ -0,0 +1,520 @@
+//! Silent payment (BIP352) peg-outs for federations with a taproot descriptor.
+//!
+//! The output key of a silent payment depends on the secret key of the
+//! federation UTXO spent by the peg-out transaction. The guardians therefore
+//! run a threshold ECDH with the scan key of the recipient: every guardian
+//! multiplies the scan key with its key share and proves the result correct
+//! with a DLEQ proof. Any threshold of these shares interpolates to the ECDH
+//! with the aggregate secret key, from which everyone, including the client,
+//! can derive the output key for the federation UTXO.
+
+use std::collections::BTreeMap;
+
+use anyhow::{Context, ensure};
+use bech32::primitives::decode::CheckedHrpstring;
+use bech32::{Bech32m, ByteIterExt, Fe32, Fe32IterExt, Hrp};
+use bitcoin::hashes::{Hash, HashEngine, sha256};
+use bitcoin::key::{Parity, TweakedPublicKey};
+use bitcoin::{Network, ScriptBuf};
+use fedimint_core::PeerId;
+use fedimint_core::encoding::{Decodable, Encodable};
+use secp256k1::{PublicKey, SECP256K1, Scalar, SecretKey};
+use serde::{Deserialize, Serialize};
+
+use crate::threshold::{interpolate, small};
+use crate::{KeyPathTweak, tr_descriptor};
+
+/// The scan and spend public keys of a silent payment address. The network is
+/// not part of the address but is checked when parsing its string encoding.
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Encodable, Decodable, Serialize, Deserialize)]
+pub struct SilentPaymentAddress {
+    pub scan: PublicKey,
+    pub spend: PublicKey,
+}
+
+fn hrp(network: Network) -> Hrp {
+    let hrp = match network {
+        Network::Bitcoin => "sp",
+        Network::Regtest => "sprt",
+        _ => "tsp",
+    };
+
+    Hrp::parse(hrp).expect("Valid silent payment hrp")
+}
+
+impl SilentPaymentAddress {
+    pub fn parse(address: &str, network: Network) -> anyhow::Result<Self> {
+        let mut address = CheckedHrpstring::new::<Bech32m>(address)
+            .context("Invalid bech32m encoding of silent payment address")?;
+
+        ensure!(
+            address.hrp() == hrp(network),
+            "Silent payment address is for a different network"
+        );
+
+        ensure!(
+            address.remove_witness_version() == Some(Fe32::Q),
+            "Unsupported silent payment address version"
+        );
+
+        let bytes = address.byte_iter().collect::<Vec<u8>>();
+
+        ensure!(bytes.len() == 66, "Invalid silent payment address length");
+
+        Ok(Self {
+            scan: PublicKey::from_slice(&bytes[..33]).context("Invalid scan key")?,
+            spend: PublicKey::from_slice(&bytes[33..]).context("Invalid spend key")?,
+        })
+    }
+
+    pub fn encode(&self, network: Network) -> String {
+        self.scan
+            .serialize()
+            .into_iter()
+            .chain(self.spend.serialize())
+            .bytes_to_fes()
+            .with_checksum::<Bech32m>(&hrp(network))
+            .with_witness_version(Fe32::Q)
+            .chars()
+            .collect()
+    }
+}
+
+/// A guardian's share of the ECDH between the aggregate secret key and the
+/// scan key of a silent payment address, with a DLEQ proof that the share uses
+/// the same secret key as the guardian's public key share.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Encodable, Decodable, Serialize, Deserialize)]
+pub struct EcdhShare {
+    pub point: PublicKey,
+    pub challenge: [u8; 32],
+    pub response: [u8; 32],
+}
+
+/// Domain separation tag of the DLEQ proofs of ECDH shares
+pub const DLEQ_TAG: &str = "fedimint-walletv2-dleq";
+
+fn dleq_challenge(
+    pk: &PublicKey,
+    point: &PublicKey,
+    scan: &PublicKey,
+    nonce_g: &PublicKey,
+    nonce_scan: &PublicKey,
+) -> [u8; 32] {
+    (DLEQ_TAG, pk, point, scan, nonce_g, nonce_scan)
+        .consensus_hash::<sha256::Hash>()
+        .to_byte_array()
+}
+
+impl EcdhShare {
+    /// The nonce is derived from our key share and the scan key, hence
+    /// creating the share for the same scan key twice yields the same proof.
+    pub fn new(sk: &SecretKey, scan: &PublicKey) -> Self {
+        let point = scan
+            .mul_tweak(SECP256K1, &Scalar::from(*sk))
+            .expect("Product of non-zero scalars is non-zero");
+
+        let nonce = SecretKey::from_slice(
+            &("fedimint-walletv2-dleq-nonce", sk, scan)
+                .consensus_hash::<sha256::Hash>()
+                .to_byte_array(),
+        )
+        .expect("Hash is within field order");
+
+        let nonce_scan = scan
+            .mul_tweak(SECP256K1, &Scalar::from(nonce))
+            .expect("Product of non-zero scalars is non-zero");
+
+        let challenge = dleq_challenge(
+            &sk.public_key(SECP256K1),
+            &point,
+            scan,
+            &nonce.public_key(SECP256K1),
+            &nonce_scan,
+        );
+
+        let response = sk
+            .mul_tweak(&Scalar::from_be_bytes(challenge).expect("Hash is within field order"))
+            .expect("Product of non-zero scalars is non-zero")
+            .add_tweak(&Scalar::from(nonce))
+            .expect("Sum of scalars is non-zero");
+
+        Self {
+            point,
+            challenge,
+            response: response.secret_bytes(),
+        }
+    }
+
+    pub fn verify(&self, pk: &PublicKey, scan: &PublicKey) -> anyhow::Result<()> {
+        let response = SecretKey::from_slice(&self.response).context("Invalid response")?;
+
+        let challenge = SecretKey::from_slice(&self.challenge)
+            .context("Invalid challenge")?
+            .negate();
+
+        // The nonces are recovered as response * G - challenge * pk and
+        // response * scan - challenge * point respectively.
+        let recover = |base: &PublicKey, point: &PublicKey| {
+            base.mul_tweak(SECP256K1, &Scalar::from(response))
+                .expect("Product of non-zero scalars is non-zero")
+                .combine(
+                    &point
+                        .mul_tweak(SECP256K1, &Scalar::from(challenge))
+                        .expect("Product of non-zero scalars is non-zero"),
+                )
+                .context("Nonce is the point at infinity")
+        };
+
+        let nonce_g = recover(&small(1).public_key(SECP256K1), pk)?;
+
+        let nonce_scan = recover(scan, &self.point)?;
+
+        ensure!(
+            dleq_challenge(pk, &self.point, scan, &nonce_g, &nonce_scan) == self.challenge,
+            "Invalid DLEQ proof"
+        );
+
+        Ok(())
+    }
+}
+
+/// Everything a client needs to verify the output key the federation derived
+/// for a silent payment peg-out.
+#[derive(Debug, Clone, Eq, PartialEq, Encodable, Decodable, Serialize, Deserialize)]
+pub struct SilentPaymentProof {
+    /// The verified ECDH shares of a threshold of guardians
+    pub shares: BTreeMap<PeerId, EcdhShare>,
+    /// The federation UTXO spent by the peg-out transaction
+    pub input: bitcoin::OutPoint,
+    /// The tweak of the descriptor for the federation UTXO
+    pub tweak: sha256::Hash,
+    /// The script pubkey of the peg-out
+    pub script_pubkey: ScriptBuf,
+}
+
+fn tagged_hash(tag: &str, data: &[&[u8]]) -> Scalar {
+    let tag = sha256::Hash::hash(tag.as_bytes());
+
+    let mut engine = sha256::Hash::engine();
+
+    engine.input(tag.as_ref());
+    engine.input(tag.as_ref());
+
+    for data in data {
+        engine.input(data);
+    }
+
+    Scalar::from_be_bytes(sha256::Hash::from_engine(engine).to_byte_array())
+        .expect("Hash is within field order")
+}
+
+/// The input hash for a transaction given its smallest outpoint and the sum of
+/// its input keys, which for a peg-out is the single taproot input.
+fn input_hash(input: &bitcoin::OutPoint, input_key: &PublicKey) -> Scalar {
+    tagged_hash(
+        "BIP0352/Inputs",
+        &[
+            &bitcoin::consensus::serialize(input),
+            &input_key.serialize(),
+        ],
+    )
+}
+
+/// Derives the output key of the first output for the recipient from the
+/// shared secret, which is the ECDH of the scan key and the input key scaled
+/// by the input hash.
+fn output_key(address: &SilentPaymentAddress, shared_secret: &PublicKey) -> TweakedPublicKey {
+    let tweak = tagged_hash(
+        "BIP0352/SharedSecret",
+        &[&shared_secret.serialize(), &0_u32.to_be_bytes()],
+    );
+
+    let output_key = address
+        .spend
+        .add_exp_tweak(SECP256K1, &tweak)
+        .expect("Sum of points is not the point at infinity")
+        .x_only_public_key()
+        .0;
+
+    TweakedPublicKey::dangerous_assume_tweaked(output_key)
+}
+
+/// Derives the script pubkey of a silent payment for a peg-out transaction
+/// spending the federation UTXO locked to the given tweak of the taproot
+/// descriptor.
+pub fn script_pubkey(
+    address: &SilentPaymentAddress,
+    shares: &BTreeMap<PeerId, EcdhShare>,
+    pks: &BTreeMap<PeerId, PublicKey>,
+    aggregate_pk: &PublicKey,
+    input: &bitcoin::OutPoint,
+    tweak: &sha256::Hash,
+) -> ScriptBuf {
+    let merkle_root = tr_descriptor(pks, aggregate_pk, tweak)
+        .spend_info()
+        .merkle_root();
+
+    let key = KeyPathTweak::new(aggregate_pk, tweak, merkle_root);
+
+    let ecdh = interpolate(
+        &shares
+            .iter()
+            .map(|(peer, share)| (*peer, share.point))
+            .collect(),
+    );
+
+    // BIP352 uses the secret key for the even output key of the input, which
+    // is the possibly negated aggregate secret key plus the public tweak.
+    let ecdh = if key.negate {
+        ecdh.negate(SECP256K1)
+    } else {
+        ecdh
+    };
+
+    let ecdh = ecdh
+        .combine(
+            &address
+                .scan
+                .mul_tweak(SECP256K1, &Scalar::from(key.tweak))
+                .expect("Product of non-zero scalars is non-zero"),
+        )
+        .expect("Sum of points is not the point at infinity");
+
+    let input_key = key.output_key.public_key(Parity::Even);
+
+    let shared_secret = ecdh
+        .mul_tweak(SECP256K1, &input_hash(input, &input_key))
+        .expect("Product of non-zero scalars is non-zero");
+
+    ScriptBuf::new_p2tr_tweaked(output_key(address, &shared_secret))
+}
+
+#[cfg(test)]
+fn secret_key(seed: &str) -> SecretKey {
+    SecretKey::from_slice(&sha256::Hash::hash(seed.as_bytes()).to_byte_array())
+        .expect("Hash is within field order")
+}
+
+#[test]
+fn test_address_roundtrip() {
+    let address = SilentPaymentAddress {
+        scan: secret_key("scan").public_key(SECP256K1),
+        spend: secret_key("spend").public_key(SECP256K1),
+    };
+
+    for (network, prefix) in [
+        (Network::Bitcoin, "sp1q"),
+        (Network::Signet, "tsp1q"),
+        (Network::Regtest, "sprt1q"),
+    ] {
+        let encoded = address.encode(network);
+
+        assert!(encoded.starts_with(prefix));
+
+        assert_eq!(
+            SilentPaymentAddress::parse(&encoded, network).expect("Address is valid"),
+            address
+        );
+    }
+
+    assert!(
+        SilentPaymentAddress::parse(&address.encode(Network::Bitcoin), Network::Regtest).is_err()
+    );
+}
+
+#[test]
+fn test_threshold_ecdh_matches_recipient() {
+    let peers = (0..4).map(PeerId::from).collect::<Vec<PeerId>>();
+
+    let coefficients = ["a", "b", "c"].map(secret_key);
+
+    let shares = peers
+        .iter()
+        .map(|peer| {
+            let share = coefficients
+                .iter()
+                .rev()
+                .copied()
+                .reduce(|acc, coefficient| {
+                    acc.mul_tweak(&crate::threshold::scalar(*peer))
+                        .expect("Product of non-zero scalars is non-zero")
+                        .add_tweak(&Scalar::from(coefficient))
+                        .expect("Sum of scalars is non-zero")
+                })
+                .expect("We have at least one coefficient");
+
+            (*peer, share)
+        })
+        .collect::<BTreeMap<PeerId, SecretKey>>();
+
+    let pks = shares
+        .iter()
+        .map(|(peer, sk)| (*peer, sk.public_key(SECP256K1)))
+        .collect::<BTreeMap<PeerId, PublicKey>>();
+
+    let aggregate_pk = coefficients[0].public_key(SECP256K1);
+
+    let (scan_sk, spend_sk) = (secret_key("scan"), secret_key("spend"));
+
+    let address = SilentPaymentAddress {
+        scan: scan_sk.public_key(SECP256K1),
+        spend: spend_sk.public_key(SECP256K1),
+    };
+
+    let input = bitcoin::OutPoint {
+        txid: bitcoin::Txid::from_byte_array([1; 32]),
+        vout: 3,
+    };
+
+    let tweak = sha256::Hash::hash(b"tweak");
+
+    for signers in [[0, 1, 2], [1, 2, 3]] {
+        let ecdh_shares = signers
+            .into_iter()
+            .map(PeerId::from)
+            .map(|peer| {
+                let share = EcdhShare::new(&shares[&peer], &address.scan);
+
+                share
+                    .verify(&pks[&peer], &address.scan)
+                    .expect("ECDH share is valid");
+
+                assert!(share.verify(&pks[&peer], &address.spend).is_err());
+
+                (peer, share)
+            })
+            .collect::<BTreeMap<PeerId, EcdhShare>>();
+
+        let script = script_pubkey(&address, &ecdh_shares, &pks, &aggregate_pk, &input, &tweak);
+
+        // The recipient computes the shared secret from its scan key and the
+        // input key as it appears on chain.
+        let merkle_root = tr_descriptor(&pks, &aggregate_pk, &tweak)
+            .spend_info()
+            .merkle_root();
+
+        let input_key = KeyPathTweak::new(&aggregate_pk, &tweak, merkle_root)
+            .output_key
+            .public_key(Parity::Even);
+
+        let shared_secret = input_key
+            .mul_tweak(SECP256K1, &input_hash(&input, &input_key))
+            .expect("Product of non-zero scalars is non-zero")
+            .mul_tweak(SECP256K1, &Scalar::from(scan_sk))
+            .expect("Product of non-zero scalars is non-zero");
+
+        assert_eq!(
+            script,
+            ScriptBuf::new_p2tr_tweaked(output_key(&address, &shared_secret))
+        );
+    }
+}
+
+/// Sending test vectors with a single recipient and output from
+/// bip-0352/send_and_receive_test_vectors.json, each given as the inputs
+/// (txid, vout, secret key, whether the input is taproot) and the expected
+/// x-only output key.
+#[test]
+fn test_bip352_sending_vectors() {
+    use std::str::FromStr;
+
+    const ADDRESS: &str = "sp1qqgste7k9hx0qftg6qmwlkqtwuy6cycyavzmzj85c6qdfhjdpdjtdgqjuexzk6murw56suy3e0rd2cgqvycxttddwsvgxe2usfpxumr70xc9pkqwv";
+
+    const TXID_1: &str = "f4184fc596403b9d638783cf57adfe4c75c605f6356fbc91338530e9831e9e16";
+    const TXID_2: &str = "a1075db55d416d3ca199f55b6084e2115b9345e16c5cf302fc80e9d5fbf5d48d";
+
+    const KEY_1: &str = "eadc78165ff1f8ea94ad7cfdc54990738a4c53f6e0507b42154201b8e5dff3b1";
+    const KEY_2: &str = "93f5ed907ad5b2bdbbdcb5d9116ebc0a4e1f92f910d5260237fa45a9408aad16";
+    const KEY_3: &str = "fc8716a97a48ba9a05a98ae47b5cd201a25a7fd5d8b73c203c5f7b6b6b3b6ad7";
+    const KEY_4: &str = "1d37787c2b7116ee983e9f9c13269df29091b391c04db94239e0d2bc2182c3bf";
+    const KEY_5: &str = "8d4751f6e8a3586880fb66c19ae277969bd5aa06f61c4ee2f1e2486efdf666d3";
+
+    let vectors: [(&str, &[(&str, u32, &str, bool)], &str); 8] = [
+        (
+            "Simple send: two inputs",
+            &[(TXID_1, 0, KEY_1, false), (TXID_2, 0, KEY_2, false)],
+            "3e9fce73d4e77a4809908e3c3a2e54ee147b9312dc5044a193d1fc85de46e3c1",
+        ),
+        (
+            "Simple send: two inputs, order reversed",
+            &[(TXID_2, 0, KEY_2, false), (TXID_1, 0, KEY_1, false)],
+            "3e9fce73d4e77a4809908e3c3a2e54ee147b9312dc5044a193d1fc85de46e3c1",
+        ),
+        (
+            "Simple send: two inputs from the same transaction",
+            &[(TXID_1, 3, KEY_1, false), (TXID_1, 7, KEY_2, false)],
+            "79e71baa2ba3fc66396de3a04f168c7bf24d6870ec88ca877754790c1db357b6",
+        ),
+        (
+            "Outpoint ordering byte-lexicographically vs. vout integer",
+            &[(TXID_1, 1, KEY_1, false), (TXID_1, 256, KEY_2, false)],
+            "a85ef8701394b517a4b35217c4bd37ac01ebeed4b008f8d0879f9e09ba95319c",
+        ),
+        (
+            "Single recipient: taproot only inputs with even y-values",
+            &[(TXID_1, 0, KEY_1, true), (TXID_2, 0, KEY_3, true)],
+            "de88bea8e7ffc9ce1af30d1132f910323c505185aec8eae361670421e749a1fb",
+        ),
+        (
+            "Single recipient: taproot only with mixed even/odd y-values",
+            &[(TXID_1, 0, KEY_1, true), (TXID_2, 0, KEY_4, true)],
+            "77cab7dd12b10259ee82c6ea4b509774e33e7078e7138f568092241bf26b99f1",
+        ),
+        (
+            "Single recipient: taproot input with even y-value and non-taproot input",
+            &[(TXID_1, 0, KEY_1, true), (TXID_2, 0, KEY_5, false)],
+            "30523cca96b2a9ae3c98beb5e60f7d190ec5bc79b2d11a0b2d4d09a608c448f0",
+        ),
+        (
+            "Single recipient: taproot input with odd y-value and non-taproot input",
+            &[(TXID_1, 0, KEY_4, true), (TXID_2, 0, KEY_5, false)],
+            "359358f59ee9e9eec3f00bdf4882570fd5c182e451aa2650b788544aff012a3a",
+        ),
+    ];
+
+    let address = SilentPaymentAddress::parse(ADDRESS, Network::Bitcoin).expect("Address is valid");
+
+    for (name, inputs, expected) in vectors {
+        // Taproot inputs commit to the even output key, so the sender has to
+        // negate the secret key of an input with an odd output key.
+        let input_sk = inputs
+            .iter()
+            .map(|(_, _, sk, taproot)| {
+                let sk = SecretKey::from_str(sk).expect("Secret key is valid");
+
+                match sk.x_only_public_key(SECP256K1).1 {
+                    Parity::Odd if *taproot => sk.negate(),
+                    _ => sk,
+                }
+            })
+            .reduce(|acc, sk| {
+                acc.add_tweak(&Scalar::from(sk))
+                    .expect("Sum of scalars is non-zero")
+            })
+            .expect("We have at least one input");
+
+        let smallest_input = inputs
+            .iter()
+            .map(|(txid, vout, _, _)| bitcoin::OutPoint {
+                txid: bitcoin::Txid::from_str(txid).expect("Txid is valid"),
+                vout: *vout,
+            })
+            .min_by_key(bitcoin::consensus::serialize)
+            .expect("We have at least one input");
+
+        let input_hash = input_hash(&smallest_input, &input_sk.public_key(SECP256K1));
+
+        let shared_secret = address
+            .scan
+            .mul_tweak(SECP256K1, &Scalar::from(input_sk))
+            .expect("Product of non-zero scalars is non-zero")
+            .mul_tweak(SECP256K1, &input_hash)
+            .expect("Product of non-zero scalars is non-zero");
+
+        assert_eq!(
+            output_key(&address, &shared_secret).to_x_only_public_key(),
+            bitcoin::XOnlyPublicKey::from_str(expected).expect("Output key is valid"),
+            "{name}"
+        );
+    }
+}
@@ -0,0 +1,93 @@
+//! Shamir secret sharing over the secp256k1 scalar field. The guardians of a
+//! federation with a taproot descriptor hold shares of the secret key for
+//! their aggregate public key, which are combined via Lagrange interpolation.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use fedimint_core::PeerId;
+use secp256k1::{PublicKey, SECP256K1, Scalar, SecretKey};
+
+/// Offset by 1, since evaluating a poly at 0 reveals the secret
+pub fn scalar(peer: PeerId) -> Scalar {
+    Scalar::from(small(peer.to_usize() as u64 + 1))
+}
+
+pub fn small(value: u64) -> SecretKey {
+    let mut bytes = [0; 32];
+
+    bytes[24..].copy_from_slice(&value.to_be_bytes());
+
+    SecretKey::from_slice(&bytes).expect("Value is non-zero and within field order")
+}
+
+fn mul(a: SecretKey, b: SecretKey) -> SecretKey {
+    a.mul_tweak(&Scalar::from(b))
+        .expect("Product of non-zero scalars is non-zero")
+}
+
+/// Inverts a scalar via Fermat's little theorem as the curve order is prime.
+fn invert(value: SecretKey) -> SecretKey {
+    let mut exponent = secp256k1::constants::CURVE_ORDER;
+
+    exponent[31] -= 2;
+
+    let mut result: Option<SecretKey> = None;
+
+    for byte in exponent {
+        for bit in (0..8).rev() {
+            result = result.map(|r| mul(r, r));
+
+            if (byte >> bit) & 1 == 1 {
+                result = Some(result.map_or(value, |r| mul(r, value)));
+            }
+        }
+    }
+
+    result.expect("Exponent is non-zero")
+}
+
+/// The Lagrange coefficient of a guardian's key share for interpolating the
+/// aggregate secret key from the key shares of the signers.
+pub fn lagrange_coefficient(peer: PeerId, signers: &BTreeSet<PeerId>) -> SecretKey {
+    let mut numerator = small(1);
+    let mut denominator = small(1);
+
+    for signer in signers.iter().filter(|signer| **signer != peer) {
+        numerator = numerator
+            .mul_tweak(&scalar(*signer))
+            .expect("Product of non-zero scalars is non-zero");
+
+        let difference = small(signer.to_usize().abs_diff(peer.to_usize()) as u64);
+
+        let difference = if *signer < peer {
+            difference.negate()
+        } else {
+            difference
+        };
+
+        denominator = mul(denominator, difference);
+    }
+
+    mul(numerator, invert(denominator))
+}
+
+/// Interpolates the point for the aggregate secret key from the points for the
+/// key shares of the given guardians, which have to be a threshold.
+pub fn interpolate(points: &BTreeMap<PeerId, PublicKey>) -> PublicKey {
+    let signers = points.keys().copied().collect::<BTreeSet<PeerId>>();
+
+    let points = points
+        .iter()
+        .map(|(peer, point)| {
+            point
+                .mul_tweak(
+                    SECP256K1,
+                    &Scalar::from(lagrange_coefficient(*peer, &signers)),
+                )
+                .expect("Product of non-zero scalars is non-zero")
+        })
+        .collect::<Vec<PublicKey>>();
+
+    PublicKey::combine_keys(&points.iter().collect::<Vec<&PublicKey>>())
+        .expect("Sum of points is not the point at infinity")
+}
@@ -1,12 +1,24 @@
-use bitcoin::{TxOut, Txid};
+use std::collections::BTreeSet;
+
+use bitcoin::hashes::sha256;
+use bitcoin::{ScriptBuf, TxOut, Txid};
+use fedimint_core::db::IDatabaseTransactionOpsCoreTyped;
 use fedimint_core::encoding::{Decodable, Encodable};
+use fedimint_core::module::ModuleConsensusVersion;
 use fedimint_core::{PeerId, impl_db_lookup, impl_db_record};
-use fedimint_walletv2_common::TxInfo;
+use fedimint_server_core::migration::{
+    ModuleHistoryItem, ServerModuleDbMigrationFnContext, ServerModuleDbMigrationFnContextExt as _,
+};
+use fedimint_walletv2_common::silent_payments::{
+    EcdhShare, SilentPaymentAddress, SilentPaymentProof,
+};
+use fedimint_walletv2_common::{NonceCommitment, PartialSignature, TxInfo};
+use futures::StreamExt;
 use secp256k1::ecdsa::Signature;
 use serde::Serialize;
 use strum_macros::EnumIter;
 
-use crate::{FederationTx, FederationWallet};
+use crate::{FederationTx, FederationWallet, Wallet};
 
 #[repr(u8)]
 #[derive(Clone, EnumIter, Debug)]
@@ -21,6 +33,20 @@ pub enum DbKeyPrefix {
     Signatures = 0x37,
     UnconfirmedTx = 0x38,
     FederationWallet = 0x39,
+    SigningSession = 0x3a,
+    Nonces = 0x3b,
+    PartialSignatures = 0x3c,
+    PegOutBatch = 0x3d,
+    QueuedPegOut = 0x3e,
+    FeeBumpVote = 0x3f,
+    FeeBump = 0x40,
+    PendingSilentPayment = 0x41,
+    EcdhShare = 0x42,
+    SilentPaymentProof = 0x43,
+    UsedTweak = 0x44,
+    FeeBumpTx = 0x45,
+    ReplacedTx = 0x46,
+    ConsensusVersionVote = 0x47,
 }
 
 impl std::fmt::Display for DbKeyPrefix {
@@ -138,6 +164,246 @@ fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
 
 impl_db_lookup!(key = SignaturesKey, query_prefix = SignaturesPrefix);
 
+/// A threshold signing session for a transaction spent via the taproot key
+/// path. The session is restarted with fresh nonces if it does not complete in
+/// time, for example because a guardian that committed to its nonces went
+/// offline.
+#[derive(Clone, Debug, Eq, PartialEq, Encodable, Decodable, Serialize)]
+pub struct SigningSession {
+    pub index: u64,
+    pub started: u64,
+}
+
+#[derive(Clone, Debug, Encodable, Decodable, Serialize)]
+pub struct SigningSessionKey(pub Txid);
+
+#[derive(Clone, Debug, Encodable, Decodable)]
+pub struct SigningSessionPrefix;
+
+impl_db_record!(
+    key = SigningSessionKey,
+    value = SigningSession,
+    db_prefix = DbKeyPrefix::SigningSession,
+);
+
+impl_db_lookup!(key = SigningSessionKey, query_prefix = SigningSessionPrefix);
+
+#[derive(Clone, Debug, Encodable, Decodable, Serialize)]
+pub struct NoncesKey(pub Txid, pub PeerId);
+
+#[derive(Clone, Debug, Encodable, Decodable)]
+pub struct NoncesTxidPrefix(pub Txid);
+
+#[derive(Clone, Debug, Encodable, Decodable)]
+pub struct NoncesPrefix;
+
+impl_db_record!(
+    key = NoncesKey,
+    value = Vec<NonceCommitment>,
+    db_prefix = DbKeyPrefix::Nonces,
+);
+
+impl_db_lookup!(key = NoncesKey, query_prefix = NoncesTxidPrefix);
+
+impl_db_lookup!(key = NoncesKey, query_prefix = NoncesPrefix);
+
+#[derive(Clone, Debug, Encodable, Decodable, Serialize)]
+pub struct PartialSignaturesKey(pub Txid, pub PeerId);
+
+#[derive(Clone, Debug, Encodable, Decodable)]
+pub struct PartialSignaturesTxidPrefix(pub Txid);
+
+#[derive(Clone, Debug, Encodable, Decodable)]
+pub struct PartialSignaturesPrefix;
+
+impl_db_record!(
+    key = PartialSignaturesKey,
+    value = Vec<PartialSignature>,
+    db_prefix = DbKeyPrefix::PartialSignatures,
+);
+
+impl_db_lookup!(
+    key = PartialSignaturesKey,
+    query_prefix = PartialSignaturesTxidPrefix
+);
+
+impl_db_lookup!(
+    key = PartialSignaturesKey,
+    query_prefix = PartialSignaturesPrefix
+);
+
+/// Maps to the consensus block count at which the first peg-out of the
+/// current batch has been queued.
+#[derive(Clone, Debug, Encodable, Decodable, Serialize)]
+pub struct PegOutBatchKey;
+
+#[derive(Clone, Debug, Encodable, Decodable)]
+pub struct PegOutBatchPrefix;
+
+impl_db_record!(
+    key = PegOutBatchKey,
+    value = u64,
+    db_prefix = DbKeyPrefix::PegOutBatch,
+);
+
+impl_db_lookup!(key = PegOutBatchKey, query_prefix = PegOutBatchPrefix);
+
+#[derive(Clone, Debug, Encodable, Decodable, Serialize)]
+pub struct QueuedPegOutKey(pub fedimint_core::OutPoint);
+
+#[derive(Clone, Debug, Encodable, Decodable)]
+pub struct QueuedPegOutPrefix;
+
+#[derive(Clone, Debug, Eq, PartialEq, Encodable, Decodable, Serialize)]
+pub struct QueuedPegOut {
+    pub script_pubkey: ScriptBuf,
+    pub value: bitcoin::Amount,
+    pub fee: bitcoin::Amount,
+}
+
+impl_db_record!(
+    key = QueuedPegOutKey,
+    value = QueuedPegOut,
+    db_prefix = DbKeyPrefix::QueuedPegOut,
+);
+
+impl_db_lookup!(key = QueuedPegOutKey, query_prefix = QueuedPegOutPrefix);
+
+#[derive(Clone, Debug, Encodable, Decodable, Serialize)]
+pub struct FeeBumpVoteKey(pub Txid, pub PeerId);
+
+#[derive(Clone, Debug, Encodable, Decodable)]
+pub struct FeeBumpVoteTxidPrefix(pub Txid);
+
+#[derive(Clone, Debug, Encodable, Decodable)]
+pub struct FeeBumpVotePrefix;
+
+impl_db_record!(
+    key = FeeBumpVoteKey,
+    value = (),
+    db_prefix = DbKeyPrefix::FeeBumpVote,
+);
+
+impl_db_lookup!(key = FeeBumpVoteKey, query_prefix = FeeBumpVoteTxidPrefix);
+
+impl_db_lookup!(key = FeeBumpVoteKey, query_prefix = FeeBumpVotePrefix);
+
+/// Maps an unconfirmed transaction to the consensus block count at which its
+/// fee has last been bumped.
+#[derive(Clone, Debug, Encodable, Decodable, Serialize)]
+pub struct FeeBumpKey(pub Txid);
+
+#[derive(Clone, Debug, Encodable, Decodable)]
+pub struct FeeBumpPrefix;
+
+impl_db_record!(
+    key = FeeBumpKey,
+    value = u64,
+    db_prefix = DbKeyPrefix::FeeBump,
+);
+
+impl_db_lookup!(key = FeeBumpKey, query_prefix = FeeBumpPrefix);
+
+/// Maps a pending transaction that has been created to bump the fee of its
+/// ancestors to its index in the transaction log.
+#[derive(Clone, Debug, Encodable, Decodable, Serialize)]
+pub struct FeeBumpTxKey(pub Txid);
+
+#[derive(Clone, Debug, Encodable, Decodable)]
+pub struct FeeBumpTxPrefix;
+
+impl_db_record!(
+    key = FeeBumpTxKey,
+    value = u64,
+    db_prefix = DbKeyPrefix::FeeBumpTx,
+);
+
+impl_db_lookup!(key = FeeBumpTxKey, query_prefix = FeeBumpTxPrefix);
+
+/// Maps a fee bump transaction that has been replaced by one paying a higher
+/// fee to the state we have to restore if it confirms nonetheless.
+#[derive(Clone, Debug, Encodable, Decodable, Serialize)]
+pub struct ReplacedTxKey(pub Txid);
+
+#[derive(Clone, Debug, Encodable, Decodable)]
+pub struct ReplacedTxPrefix;
+
+#[derive(Clone, Debug, Encodable, Decodable, Serialize)]
+pub struct ReplacedTx {
+    pub tx_info: TxInfo,
+    pub wallet: FederationWallet,
+}
+
+impl_db_record!(
+    key = ReplacedTxKey,
+    value = ReplacedTx,
+    db_prefix = DbKeyPrefix::ReplacedTx,
+);
+
+impl_db_lookup!(key = ReplacedTxKey, query_prefix = ReplacedTxPrefix);
+
+/// A peg-out to a silent payment address that waits for a threshold of ECDH
+/// shares to derive its output key.
+#[derive(Clone, Debug, Encodable, Decodable, Serialize)]
+pub struct PendingSilentPaymentKey(pub fedimint_core::OutPoint);
+
+#[derive(Clone, Debug, Encodable, Decodable)]
+pub struct PendingSilentPaymentPrefix;
+
+#[derive(Clone, Debug, Eq, PartialEq, Encodable, Decodable, Serialize)]
+pub struct PendingSilentPayment {
+    pub address: SilentPaymentAddress,
+    pub value: bitcoin::Amount,
+    pub fee: bitcoin::Amount,
+}
+
+impl_db_record!(
+    key = PendingSilentPaymentKey,
+    value = PendingSilentPayment,
+    db_prefix = DbKeyPrefix::PendingSilentPayment,
+);
+
+impl_db_lookup!(
+    key = PendingSilentPaymentKey,
+    query_prefix = PendingSilentPaymentPrefix
+);
+
+#[derive(Clone, Debug, Encodable, Decodable, Serialize)]
+pub struct EcdhShareKey(pub fedimint_core::OutPoint, pub PeerId);
+
+#[derive(Clone, Debug, Encodable, Decodable)]
+pub struct EcdhShareOutPointPrefix(pub fedimint_core::OutPoint);
+
+#[derive(Clone, Debug, Encodable, Decodable)]
+pub struct EcdhSharePrefix;
+
+impl_db_record!(
+    key = EcdhShareKey,
+    value = EcdhShare,
+    db_prefix = DbKeyPrefix::EcdhShare,
+);
+
+impl_db_lookup!(key = EcdhShareKey, query_prefix = EcdhShareOutPointPrefix);
+
+impl_db_lookup!(key = EcdhShareKey, query_prefix = EcdhSharePrefix);
+
+#[derive(Clone, Debug, Encodable, Decodable, Serialize)]
+pub struct SilentPaymentProofKey(pub fedimint_core::OutPoint);
+
+#[derive(Clone, Debug, Encodable, Decodable)]
+pub struct SilentPaymentProofPrefix;
+
+impl_db_record!(
+    key = SilentPaymentProofKey,
+    value = SilentPaymentProof,
+    db_prefix = DbKeyPrefix::SilentPaymentProof,
+);
+
+impl_db_lookup!(
+    key = SilentPaymentProofKey,
+    query_prefix = SilentPaymentProofPrefix
+);
+
 #[derive(Clone, Debug, Encodable, Decodable, Serialize)]
 pub struct UnconfirmedTxKey(pub Txid);
 
@@ -179,3 +445,92 @@ fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
 );
 
 impl_db_lookup!(key = FeeRateVoteKey, query_prefix = FeeRateVotePrefix);
+
+#[derive(Clone, Debug, Encodable, Decodable, Serialize)]
+pub struct ConsensusVersionVoteKey(pub PeerId);
+
+#[derive(Clone, Debug, Encodable, Decodable)]
+pub struct ConsensusVersionVotePrefix;
+
+impl_db_record!(
+    key = ConsensusVersionVoteKey,
+    value = ModuleConsensusVersion,
+    db_prefix = DbKeyPrefix::ConsensusVersionVote
+);
+
+impl_db_lookup!(
+    key = ConsensusVersionVoteKey,
+    query_prefix = ConsensusVersionVotePrefix
+);
+
+/// Every tweak the federation has received funds to, such that a watch-only
+/// wallet can track all of its outputs. The value is the consensus block count
+/// when the tweak was first used.
+#[derive(Clone, Debug, Eq, PartialEq, Encodable, Decodable, Serialize)]
+pub struct UsedTweakKey(pub sha256::Hash);
+
+#[derive(Clone, Debug, Encodable, Decodable)]
+pub struct UsedTweakPrefix;
+
+impl_db_record!(
+    key = UsedTweakKey,
+    value = u64,
+    db_prefix = DbKeyPrefix::UsedTweak,
+);
+
+impl_db_lookup!(key = UsedTweakKey, query_prefix = UsedTweakPrefix);
+
+/// Migrate to v1, backfilling the tweaks used before they were recorded. Next
+/// to the tweaks of all claimed deposits we recover the tweaks of the change
+/// output and of the outputs spent by pending transactions, since these are
+/// the only outputs of the federation that may still be unspent. As we do not
+/// know when these tweaks were first used we record them at block count zero.
+pub async fn migrate_to_v1(
+    mut ctx: ServerModuleDbMigrationFnContext<'_, Wallet>,
+) -> Result<(), anyhow::Error> {
+    let mut tweaks = ctx
+        .get_typed_module_history_stream()
+        .await
+        .filter_map(|item| async move {
+            match item {
+                ModuleHistoryItem::Input(input) => input
+                    .maybe_v0_ref()
+                    .map(|input| input.tweak.consensus_hash::<sha256::Hash>()),
+                ModuleHistoryItem::Output(..) | ModuleHistoryItem::ConsensusItem(_) => None,
+            }
+        })
+        .collect::<BTreeSet<sha256::Hash>>()
+        .await;
+
+    let mut dbtx = ctx.dbtx();
+
+    if let Some(wallet) = dbtx.get_value(&FederationWalletKey).await {
+        tweaks.insert(wallet.tweak);
+    }
+
+    let unsigned = dbtx
+        .find_by_prefix(&UnsignedTxPrefix)
+        .await
+        .map(|entry| entry.1)
+        .collect::<Vec<FederationTx>>()
+        .await;
+
+    let unconfirmed = dbtx
+        .find_by_prefix(&UnconfirmedTxPrefix)
+        .await
+        .map(|entry| entry.1)
+        .collect::<Vec<FederationTx>>()
+        .await;
+
+    for tx in unsigned.into_iter().chain(unconfirmed) {
+        tweaks.extend(tx.spent_tx_outs.iter().map(|spent| spent.tweak));
+    }
+
+    for tweak in tweaks {
+        if dbtx.get_value(&UsedTweakKey(tweak)).await.is_none() {
+            dbtx.insert_new_entry(&UsedTweakKey(tweak), &0).await;
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,894 @@
+//! Threshold Schnorr signatures for the taproot key path of the federation
+//! wallet following FROST (RFC 9591) over secp256k1 with BIP340 challenges.
+//!
+//! Every guardian holds a Shamir share of the secret key for the aggregate
+//! public key. Signing a transaction takes two consensus rounds: first a
+//! threshold of guardians commit to their nonces, then exactly those guardians
+//! submit their partial signatures which are aggregated into a single BIP340
+//! signature per input.
+//!
+//! The nonces never leave the guardian signer, which draws them at random for
+//! every signing session, keeps them in memory only and erases them after the
+//! first partial signature. A guardian whose signer restarted during a session
+//! cannot contribute to it anymore and the session is restarted once it
+//! expires.
+//!
+//! There is no audited FROST implementation for secp256k1 among our
+//! dependencies, hence this module follows the RFC closely and keeps the
+//! secret operations in the guardian signer.
+
+use std::collections::BTreeMap;
+
+use anyhow::{Context, bail, ensure};
+use bitcoin::hashes::{Hash, HashEngine, sha256};
+use bitcoin::key::Parity;
+use bitcoin::secp256k1::ecdh::SharedSecret;
+use bitcoin::secp256k1::schnorr;
+use bitcoin::secp256k1::{
+    Keypair, Message, PublicKey, SECP256K1, Scalar, SecretKey, XOnlyPublicKey,
+};
+use bitcoin::taproot::TapNodeHash;
+use fedimint_aead::LessSafeKey;
+use fedimint_core::PeerId;
+use fedimint_core::encoding::{Decodable, Encodable};
+use fedimint_derive_secret::DerivableSecret;
+use fedimint_server_core::config::{PeerHandleOps, PeerHandleOpsExt};
+use fedimint_walletv2_common::threshold::{interpolate, lagrange_coefficient, scalar};
+use fedimint_walletv2_common::{KeyPathTweak, NonceCommitment, PartialSignature};
+use rand::rngs::OsRng;
+
+fn hash_to_scalar(hash: sha256::Hash) -> Scalar {
+    Scalar::from_be_bytes(hash.to_byte_array()).expect("Hash is within field order")
+}
+
+fn add(a: SecretKey, b: SecretKey) -> SecretKey {
+    a.add_tweak(&Scalar::from(b))
+        .expect("Sum of scalars is non-zero")
+}
+
+fn eval_poly(coefficients: &[SecretKey], peer: PeerId) -> SecretKey {
+    coefficients
+        .iter()
+        .copied()
+        .rev()
+        .reduce(|acc, coefficient| {
+            add(
+                acc.mul_tweak(&scalar(peer))
+                    .expect("Product of non-zero scalars is non-zero"),
+                coefficient,
+            )
+        })
+        .expect("We have at least one coefficient")
+}
+
+fn eval_poly_pk(commitments: &[PublicKey], peer: PeerId) -> PublicKey {
+    commitments
+        .iter()
+        .copied()
+        .rev()
+        .reduce(|acc, commitment| {
+            acc.mul_tweak(SECP256K1, &scalar(peer))
+                .expect("Product of non-zero scalars is non-zero")
+                .combine(&commitment)
+                .expect("Sum of points is not the point at infinity")
+        })
+        .expect("We have at least one commitment")
+}
+
+fn combine(pks: &[PublicKey]) -> PublicKey {
+    PublicKey::combine_keys(&pks.iter().collect::<Vec<&PublicKey>>())
+        .expect("Sum of points is not the point at infinity")
+}
+
+/// Generates the key shares for the given peers in a trusted setup and returns
+/// them together with the aggregate public key.
+pub fn dealer_keygen(
+    peers: &[PeerId],
+    threshold: usize,
+) -> (BTreeMap<PeerId, SecretKey>, PublicKey) {
+    let coefficients = (0..threshold)
+        .map(|_| SecretKey::new(&mut OsRng))
+        .collect::<Vec<SecretKey>>();
+
+    let shares = peers
+        .iter()
+        .map(|peer| (*peer, eval_poly(&coefficients, *peer)))
+        .collect();
+
+    (shares, coefficients[0].public_key(SECP256K1))
+}
+
+#[derive(Debug, Clone, Encodable, Decodable)]
+struct DkgCommitment {
+    /// Commitments to the coefficients of our polynomial
+    commitments: Vec<PublicKey>,
+    /// Proof of knowledge of the secret key of our first commitment
+    proof: schnorr::Signature,
+    /// Ephemeral public key to encrypt the key shares sent to us
+    ecdh_pk: PublicKey,
+}
+
+fn dkg_proof_message(commitments: &[PublicKey], ecdh_pk: &PublicKey) -> Message {
+    Message::from_digest(
+        ("fedimint-walletv2-frost-dkg", commitments, ecdh_pk)
+            .consensus_hash::<sha256::Hash>()
+            .to_byte_array(),
+    )
+}
+
+/// The key to encrypt the key share sent from `sender` to `receiver`, which is
+/// unique per run of the key generation since the ECDH keys are ephemeral.
+fn dkg_share_key(shared_secret: &SharedSecret, sender: PeerId, receiver: PeerId) -> LessSafeKey {
+    LessSafeKey::new(
+        DerivableSecret::new_root(
+            &shared_secret.secret_bytes(),
+            b"fedimint-walletv2-frost-dkg-share",
+        )
+        .tweak(&(sender, receiver).consensus_encode_to_vec())
+        .to_chacha20_poly1305_key(),
+    )
+}
+
+/// Our secrets for one run of the distributed key generation
+struct DkgSecrets {
+    coefficients: Vec<SecretKey>,
+    ecdh_sk: SecretKey,
+}
+
+fn dkg_commitment(threshold: usize) -> (DkgSecrets, DkgCommitment) {
+    let coefficients = (0..threshold)
+        .map(|_| SecretKey::new(&mut OsRng))
+        .collect::<Vec<SecretKey>>();
+
+    let commitments = coefficients
+        .iter()
+        .map(|c| c.public_key(SECP256K1))
+        .collect::<Vec<PublicKey>>();
+
+    let (ecdh_sk, ecdh_pk) = secp256k1::generate_keypair(&mut OsRng);
+
+    let proof = SECP256K1.sign_schnorr_with_rng(
+        &dkg_proof_message(&commitments, &ecdh_pk),
+        &Keypair::from_secret_key(SECP256K1, &coefficients[0]),
+        &mut OsRng,
+    );
+
+    let commitment = DkgCommitment {
+        commitments,
+        proof,
+        ecdh_pk,
+    };
+
+    (
+        DkgSecrets {
+            coefficients,
+            ecdh_sk,
+        },
+        commitment,
+    )
+}
+
+fn verify_dkg_commitments(
+    dkg_commitments: &BTreeMap<PeerId, DkgCommitment>,
+    threshold: usize,
+) -> anyhow::Result<()> {
+    for (peer, commitment) in dkg_commitments {
+        ensure!(
+            commitment.commitments.len() == threshold,
+            "Peer {peer} sent an invalid number of commitments"
+        );
+
+        SECP256K1
+            .verify_schnorr(
+                &commitment.proof,
+                &dkg_proof_message(&commitment.commitments, &commitment.ecdh_pk),
+                &commitment.commitments[0].x_only_public_key().0,
+            )
+            .with_context(|| format!("Peer {peer} sent an invalid proof of knowledge"))?;
+    }
+
+    Ok(())
+}
+
+/// Encrypts the key share of every guardian to its ephemeral key
+fn dkg_encrypted_shares(
+    secrets: &DkgSecrets,
+    identity: PeerId,
+    dkg_commitments: &BTreeMap<PeerId, DkgCommitment>,
+) -> anyhow::Result<BTreeMap<PeerId, Vec<u8>>> {
+    let mut encrypted_shares = BTreeMap::new();
+
+    for (peer, commitment) in dkg_commitments {
+        let shared_secret = SharedSecret::new(&commitment.ecdh_pk, &secrets.ecdh_sk);
+
+        let share = eval_poly(&secrets.coefficients, *peer).secret_bytes();
+
+        let encrypted_share = fedimint_aead::encrypt(
+            share.to_vec(),
+            &dkg_share_key(&shared_secret, identity, *peer),
+        )?;
+
+        encrypted_shares.insert(*peer, encrypted_share);
+    }
+
+    Ok(encrypted_shares)
+}
+
+/// Decrypts and verifies the key shares sent to us and returns our key share,
+/// the public key shares of all guardians and the aggregate public key.
+fn dkg_key_shares(
+    secrets: &DkgSecrets,
+    identity: PeerId,
+    dkg_commitments: &BTreeMap<PeerId, DkgCommitment>,
+    encrypted_shares: &BTreeMap<PeerId, BTreeMap<PeerId, Vec<u8>>>,
+) -> anyhow::Result<(SecretKey, BTreeMap<PeerId, PublicKey>, PublicKey)> {
+    let mut sk = None;
+
+    for (peer, commitment) in dkg_commitments {
+        let mut encrypted_share = encrypted_shares
+            .get(peer)
+            .and_then(|shares| shares.get(&identity))
+            .with_context(|| format!("Peer {peer} did not send us a key share"))?
+            .clone();
+
+        let shared_secret = SharedSecret::new(&commitment.ecdh_pk, &secrets.ecdh_sk);
+
+        let share = fedimint_aead::decrypt(
+            &mut encrypted_share,
+            &dkg_share_key(&shared_secret, *peer, identity),
+        )
+        .with_context(|| format!("Peer {peer} sent a key share we cannot decrypt"))?;
+
+        let share = SecretKey::from_slice(share)
+            .with_context(|| format!("Peer {peer} sent an invalid key share"))?;
+
+        if share.public_key(SECP256K1) != eval_poly_pk(&commitment.commitments, identity) {
+            bail!("Peer {peer} sent a key share inconsistent with its commitments");
+        }
+
+        sk = Some(sk.map_or(share, |sk| add(sk, share)));
+    }
+
+    let pks = dkg_commitments
+        .keys()
+        .map(|peer| {
+            let pks = dkg_commitments
+                .values()
+                .map(|commitment| eval_poly_pk(&commitment.commitments, *peer))
+                .collect::<Vec<PublicKey>>();
+
+            (*peer, combine(&pks))
+        })
+        .collect();
+
+    let aggregate_pk = combine(
+        &dkg_commitments
+            .values()
+            .map(|commitment| commitment.commitments[0])
+            .collect::<Vec<PublicKey>>(),
+    );
+
+    Ok((sk.context("No key shares received")?, pks, aggregate_pk))
+}
+
+/// Runs a Pedersen distributed key generation with the other guardians and
+/// returns our key share, the public key shares of all guardians and the
+/// aggregate public key. Since the peer to peer messages are broadcast the key
+/// shares are encrypted to an ephemeral key of the receiving guardian.
+pub async fn run_dkg(
+    peers: &(dyn PeerHandleOps + Send + Sync),
+) -> anyhow::Result<(SecretKey, BTreeMap<PeerId, PublicKey>, PublicKey)> {
+    let threshold = peers.num_peers().threshold();
+
+    let (secrets, commitment) = dkg_commitment(threshold);
+
+    let ecdh_pk = commitment.ecdh_pk;
+
+    let dkg_commitments: BTreeMap<PeerId, DkgCommitment> =
+        peers.exchange_encodable(commitment).await?;
+
+    let identity = *dkg_commitments
+        .iter()
+        .find(|(_, commitment)| commitment.ecdh_pk == ecdh_pk)
+        .context("Our own commitment is missing")?
+        .0;
+
+    verify_dkg_commitments(&dkg_commitments, threshold)?;
+
+    let encrypted_shares: BTreeMap<PeerId, BTreeMap<PeerId, Vec<u8>>> = peers
+        .exchange_encodable(dkg_encrypted_shares(&secrets, identity, &dkg_commitments)?)
+        .await?;
+
+    dkg_key_shares(&secrets, identity, &dkg_commitments, &encrypted_shares)
+}
+
+/// Interpolates the aggregate public key from the public key shares of a
+/// threshold of guardians.
+pub fn interpolate_aggregate_pk(pks: &BTreeMap<PeerId, PublicKey>, threshold: usize) -> PublicKey {
+    interpolate(
+        &pks.iter()
+            .take(threshold)
+            .map(|(peer, pk)| (*peer, *pk))
+            .collect(),
+    )
+}
+
+/// Everything needed to sign one input of a transaction via the taproot key
+/// path of the federation descriptor.
+pub struct KeySpend {
+    /// The taproot key spend sighash of the input
+    message: [u8; 32],
+    /// The tweaked output key the input is locked to
+    output_key: XOnlyPublicKey,
+    /// Whether the key shares have to be negated since the BIP340 secret key
+    /// for the output key is the negated interpolated secret key
+    negate: bool,
+    /// The public difference between the secret key for the output key and
+    /// the interpolated secret key of the signers
+    tweak: SecretKey,
+}
+
+impl KeySpend {
+    pub fn new(
+        aggregate_pk: &PublicKey,
+        tweak: &sha256::Hash,
+        merkle_root: Option<TapNodeHash>,
+        message: [u8; 32],
+    ) -> Self {
+        let key = KeyPathTweak::new(aggregate_pk, tweak, merkle_root);
+
+        Self {
+            message,
+            output_key: key.output_key,
+            negate: key.negate,
+            tweak: key.tweak,
+        }
+    }
+
+    pub fn output_key(&self) -> XOnlyPublicKey {
+        self.output_key
+    }
+}
+
+/// Identifies the nonces for one input of a signing session in the guardian
+/// signer
+pub fn session_id(txid: &bitcoin::Txid, session: u64, index: usize) -> [u8; 32] {
+    (
+        "fedimint-walletv2-frost-session",
+        txid,
+        session,
+        index as u64,
+    )
+        .consensus_hash::<sha256::Hash>()
+        .to_byte_array()
+}
+
+/// The public factors of our partial signature `±(hiding + binding_factor *
+/// binding) + key_factor * sk`, which the guardian signer combines with our
+/// secret nonces and key share
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SigningFactors {
+    pub binding_factor: [u8; 32],
+    pub negate_nonce: bool,
+    pub key_factor: [u8; 32],
+}
+
+pub fn signing_factors(
+    spend: &KeySpend,
+    peer: PeerId,
+    commitments: &BTreeMap<PeerId, NonceCommitment>,
+) -> SigningFactors {
+    SigningFactors {
+        binding_factor: binding_factor(peer, &spend.message, commitments).to_be_bytes(),
+        negate_nonce: group_commitment(&spend.message, commitments).1,
+        key_factor: key_factor(spend, peer, commitments).to_be_bytes(),
+    }
+}
+
+fn binding_factor(
+    peer: PeerId,
+    message: &[u8; 32],
+    commitments: &BTreeMap<PeerId, NonceCommitment>,
+) -> Scalar {
+    hash_to_scalar(
+        (
+            "fedimint-walletv2-frost-binding",
+            peer,
+            message,
+            commitments,
+        )
+            .consensus_hash(),
+    )
+}
+
+fn binding_commitment(
+    peer: PeerId,
+    message: &[u8; 32],
+    commitments: &BTreeMap<PeerId, NonceCommitment>,
+) -> PublicKey {
+    let commitment = commitments[&peer];
+
+    commitment
+        .binding
+        .mul_tweak(SECP256K1, &binding_factor(peer, message, commitments))
+        .expect("Product of non-zero scalars is non-zero")
+        .combine(&commitment.hiding)
+        .expect("Sum of points is not the point at infinity")
+}
+
+/// Returns the x-only group commitment and whether the nonces have to be
+/// negated to obtain a group commitment with even y coordinate.
+fn group_commitment(
+    message: &[u8; 32],
+    commitments: &BTreeMap<PeerId, NonceCommitment>,
+) -> (XOnlyPublicKey, bool) {
+    let commitment = combine(
+        &commitments
+            .keys()
+            .map(|peer| binding_commitment(*peer, message, commitments))
+            .collect::<Vec<PublicKey>>(),
+    );
+
+    let (commitment, parity) = commitment.x_only_public_key();
+
+    (commitment, parity == Parity::Odd)
+}
+
+fn challenge(
+    commitment: &XOnlyPublicKey,
+    output_key: &XOnlyPublicKey,
+    message: &[u8; 32],
+) -> Scalar {
+    let tag = sha256::Hash::hash(b"BIP0340/challenge");
+
+    let mut engine = sha256::Hash::engine();
+
+    engine.input(tag.as_ref());
+    engine.input(tag.as_ref());
+    engine.input(&commitment.serialize());
+    engine.input(&output_key.serialize());
+    engine.input(message);
+
+    hash_to_scalar(sha256::Hash::from_engine(engine))
+}
+
+/// The challenge scaled by the Lagrange coefficient of the signer, negated if
+/// required by the parity of the output key.
+fn key_factor(
+    spend: &KeySpend,
+    peer: PeerId,
+    commitments: &BTreeMap<PeerId, NonceCommitment>,
+) -> Scalar {
+    let (group_commitment, _) = group_commitment(&spend.message, commitments);
+
+    let factor = lagrange_coefficient(peer, &commitments.keys().copied().collect())
+        .mul_tweak(&challenge(
+            &group_commitment,
+            &spend.output_key,
+            &spend.message,
+        ))
+        .expect("Product of non-zero scalars is non-zero");
+
+    Scalar::from(if spend.negate {
+        factor.negate()
+    } else {
+        factor
+    })
+}
+
+pub fn verify(
+    spend: &KeySpend,
+    pk: &PublicKey,
+    peer: PeerId,
+    commitments: &BTreeMap<PeerId, NonceCommitment>,
+    signature: &PartialSignature,
+) -> anyhow::Result<()> {
+    let signature = SecretKey::from_slice(&signature.0).context("Invalid partial signature")?;
+
+    let nonce = binding_commitment(peer, &spend.message, commitments);
+
+    let nonce = if group_commitment(&spend.message, commitments).1 {
+        nonce.negate(SECP256K1)
+    } else {
+        nonce
+    };
+
+    let key = pk
+        .mul_tweak(SECP256K1, &key_factor(spend, peer, commitments))
+        .expect("Product of non-zero scalars is non-zero");
+
+    ensure!(
+        signature.public_key(SECP256K1) == combine(&[nonce, key]),
+        "Invalid partial signature"
+    );
+
+    Ok(())
+}
+
+pub fn aggregate(
+    spend: &KeySpend,
+    commitments: &BTreeMap<PeerId, NonceCommitment>,
+    signatures: &[PartialSignature],
+) -> schnorr::Signature {
+    let (group_commitment, _) = group_commitment(&spend.message, commitments);
+
+    let challenge = challenge(&group_commitment, &spend.output_key, &spend.message);
+
+    let s = signatures
+        .iter()
+        .map(|s| SecretKey::from_slice(&s.0).expect("Partial signatures have been verified"))
+        .fold(
+            spend
+                .tweak
+                .mul_tweak(&challenge)
+                .expect("Product of non-zero scalars is non-zero"),
+            add,
+        );
+
+    let mut signature = [0; 64];
+
+    signature[..32].copy_from_slice(&group_commitment.serialize());
+    signature[32..].copy_from_slice(&s.secret_bytes());
+
+    let signature = schnorr::Signature::from_slice(&signature).expect("Signature has 64 bytes");
+
+    SECP256K1
+        .verify_schnorr(
+            &signature,
+            &Message::from_digest(spend.message),
+            &spend.output_key,
+        )
+        .expect("Aggregate of valid partial signatures is valid");
+
+    signature
+}
+
+#[cfg(test)]
+fn local_signer(sk: SecretKey) -> fedimint_server_core::guardian_signer::ModuleGuardianSigner {
+    use fedimint_server_core::guardian_signer::{GuardianSecret, ModuleGuardianSigner};
+
+    ModuleGuardianSigner::local(
+        0,
+        BTreeMap::from([(
+            crate::BITCOIN_KEY_NAME.to_string(),
+            GuardianSecret::Secp256k1(sk),
+        )]),
+    )
+}
+
+/// Signs with every subset of signers via their guardian signers and checks
+/// the partial and aggregate signatures
+#[cfg(test)]
+fn sign_with_subsets(
+    sks: &BTreeMap<PeerId, SecretKey>,
+    aggregate_pk: PublicKey,
+    subsets: &[Vec<u16>],
+) {
+    use fedimint_server_core::guardian_signer::ModuleGuardianSigner;
+    use futures::executor::block_on;
+
+    use crate::BITCOIN_KEY_NAME;
+
+    let pks = sks
+        .iter()
+        .map(|(peer, sk)| (*peer, sk.public_key(SECP256K1)))
+        .collect::<BTreeMap<PeerId, PublicKey>>();
+
+    let signers_by_peer = sks
+        .iter()
+        .map(|(peer, sk)| (*peer, local_signer(*sk)))
+        .collect::<BTreeMap<PeerId, ModuleGuardianSigner>>();
+
+    let txid = bitcoin::Txid::all_zeros();
+
+    for (index, signers) in subsets.iter().enumerate() {
+        let tweak = sha256::Hash::hash(&[index as u8]);
+
+        let spend = KeySpend::new(
+            &aggregate_pk,
+            &tweak,
+            Some(TapNodeHash::from_byte_array([index as u8; 32])),
+            [42; 32],
+        );
+
+        let session = session_id(&txid, 0, index);
+
+        let commitments = signers
+            .iter()
+            .map(|peer| {
+                let peer = PeerId::from(*peer);
+
+                let (hiding, binding) =
+                    block_on(signers_by_peer[&peer].nonce_commitment(BITCOIN_KEY_NAME, session))
+                        .expect("Signer holds the key");
+
+                (peer, NonceCommitment { hiding, binding })
+            })
+            .collect::<BTreeMap<PeerId, NonceCommitment>>();
+
+        let signatures = commitments
+            .keys()
+            .map(|peer| {
+                let factors = signing_factors(&spend, *peer, &commitments);
+
+                let signature = PartialSignature(
+                    block_on(signers_by_peer[peer].partial_signature(
+                        BITCOIN_KEY_NAME,
+                        session,
+                        factors.binding_factor,
+                        factors.negate_nonce,
+                        factors.key_factor,
+                    ))
+                    .expect("Signer holds the key"),
+                );
+
+                verify(&spend, &pks[peer], *peer, &commitments, &signature)
+                    .expect("Partial signature is valid");
+
+                signature
+            })
+            .collect::<Vec<PartialSignature>>();
+
+        aggregate(&spend, &commitments, &signatures);
+    }
+}
+
+#[cfg(test)]
+type EncryptedShares = BTreeMap<PeerId, BTreeMap<PeerId, Vec<u8>>>;
+
+/// Runs the key generation for four guardians with a threshold of three,
+/// letting `tamper` modify the encrypted key shares before they are received
+#[cfg(test)]
+fn run_dkg_with(
+    tamper: impl Fn(
+        &BTreeMap<PeerId, DkgSecrets>,
+        &BTreeMap<PeerId, DkgCommitment>,
+        &mut EncryptedShares,
+    ),
+) -> BTreeMap<PeerId, anyhow::Result<(SecretKey, BTreeMap<PeerId, PublicKey>, PublicKey)>> {
+    let peers = (0..4).map(PeerId::from).collect::<Vec<PeerId>>();
+
+    let (secrets, commitments): (BTreeMap<_, _>, BTreeMap<_, _>) = peers
+        .iter()
+        .map(|peer| {
+            let (secrets, commitment) = dkg_commitment(3);
+
+            ((*peer, secrets), (*peer, commitment))
+        })
+        .unzip();
+
+    verify_dkg_commitments(&commitments, 3).expect("Commitments are valid");
+
+    let mut encrypted_shares = peers
+        .iter()
+        .map(|peer| {
+            let shares = dkg_encrypted_shares(&secrets[peer], *peer, &commitments)
+                .expect("Encryption succeeds");
+
+            (*peer, shares)
+        })
+        .collect::<EncryptedShares>();
+
+    tamper(&secrets, &commitments, &mut encrypted_shares);
+
+    peers
+        .iter()
+        .map(|peer| {
+            let result = dkg_key_shares(&secrets[peer], *peer, &commitments, &encrypted_shares);
+
+            (*peer, result)
+        })
+        .collect()
+}
+
+#[test]
+fn test_threshold_signature_for_output_key() {
+    let peers = (0..4).map(PeerId::from).collect::<Vec<PeerId>>();
+
+    let (sks, aggregate_pk) = dealer_keygen(&peers, 3);
+
+    let pks = sks
+        .iter()
+        .map(|(peer, sk)| (*peer, sk.public_key(SECP256K1)))
+        .collect::<BTreeMap<PeerId, PublicKey>>();
+
+    assert_eq!(interpolate_aggregate_pk(&pks, 3), aggregate_pk);
+
+    sign_with_subsets(
+        &sks,
+        aggregate_pk,
+        &[vec![0, 1, 2], vec![0, 2, 3], vec![1, 2, 3]],
+    );
+}
+
+#[test]
+fn test_dkg_key_shares_sign_with_every_subset_of_signers() {
+    let results = run_dkg_with(|_, _, _| {});
+
+    let (_, pks, aggregate_pk) = results[&PeerId::from(0)]
+        .as_ref()
+        .expect("Key generation succeeds")
+        .clone();
+
+    let sks = results
+        .into_iter()
+        .map(|(peer, result)| {
+            let (sk, peer_pks, peer_aggregate_pk) = result.expect("Key generation succeeds");
+
+            assert_eq!(peer_pks, pks);
+            assert_eq!(peer_aggregate_pk, aggregate_pk);
+            assert_eq!(sk.public_key(SECP256K1), pks[&peer]);
+
+            (peer, sk)
+        })
+        .collect::<BTreeMap<PeerId, SecretKey>>();
+
+    assert_eq!(interpolate_aggregate_pk(&pks, 3), aggregate_pk);
+
+    sign_with_subsets(
+        &sks,
+        aggregate_pk,
+        &[
+            vec![0, 1, 2],
+            vec![0, 1, 3],
+            vec![0, 2, 3],
+            vec![1, 2, 3],
+            vec![0, 1, 2, 3],
+        ],
+    );
+}
+
+#[test]
+fn test_dkg_rejects_tampered_key_share() {
+    let results = run_dkg_with(|_, _, shares| {
+        let share = shares
+            .get_mut(&PeerId::from(1))
+            .and_then(|shares| shares.get_mut(&PeerId::from(0)))
+            .expect("Share exists");
+
+        *share.last_mut().expect("Share is not empty") ^= 1;
+    });
+
+    let err = results[&PeerId::from(0)]
+        .as_ref()
+        .expect_err("Tampered share is rejected");
+
+    assert!(format!("{err:#}").contains("Peer 1 sent a key share we cannot decrypt"));
+
+    for peer in [1, 2, 3] {
+        assert!(results[&PeerId::from(peer)].is_ok());
+    }
+}
+
+#[test]
+fn test_dkg_rejects_key_share_encrypted_to_another_peer() {
+    let results = run_dkg_with(|_, _, shares| {
+        let shares = shares.get_mut(&PeerId::from(1)).expect("Shares exist");
+
+        let share = shares[&PeerId::from(2)].clone();
+
+        shares.insert(PeerId::from(0), share);
+    });
+
+    let err = results[&PeerId::from(0)]
+        .as_ref()
+        .expect_err("Share for another peer is rejected");
+
+    assert!(format!("{err:#}").contains("Peer 1 sent a key share we cannot decrypt"));
+}
+
+#[test]
+fn test_dkg_rejects_key_share_inconsistent_with_commitments() {
+    let results = run_dkg_with(|secrets, commitments, shares| {
+        let shared_secret = SharedSecret::new(
+            &commitments[&PeerId::from(0)].ecdh_pk,
+            &secrets[&PeerId::from(1)].ecdh_sk,
+        );
+
+        let share = fedimint_aead::encrypt(
+            SecretKey::new(&mut OsRng).secret_bytes().to_vec(),
+            &dkg_share_key(&shared_secret, PeerId::from(1), PeerId::from(0)),
+        )
+        .expect("Encryption succeeds");
+
+        shares
+            .get_mut(&PeerId::from(1))
+            .expect("Shares exist")
+            .insert(PeerId::from(0), share);
+    });
+
+    let err = results[&PeerId::from(0)]
+        .as_ref()
+        .expect_err("Inconsistent share is rejected");
+
+    assert!(
+        format!("{err:#}").contains("Peer 1 sent a key share inconsistent with its commitments")
+    );
+}
+
+#[test]
+fn test_dkg_rejects_invalid_proof_of_knowledge() {
+    let mut commitments = (0..4)
+        .map(|peer| (PeerId::from(peer), dkg_commitment(3).1))
+        .collect::<BTreeMap<PeerId, DkgCommitment>>();
+
+    verify_dkg_commitments(&commitments, 3).expect("Commitments are valid");
+
+    // Peer 1 claims the constant term of peer 2 without knowing its secret
+    let stolen = commitments[&PeerId::from(2)].commitments[0];
+
+    commitments
+        .get_mut(&PeerId::from(1))
+        .expect("Commitment exists")
+        .commitments[0] = stolen;
+
+    let err = verify_dkg_commitments(&commitments, 3).expect_err("Invalid proof is rejected");
+
+    assert!(format!("{err:#}").contains("Peer 1 sent an invalid proof of knowledge"));
+}
+
+#[test]
+fn test_guardian_signer_uses_nonces_once() {
+    use futures::executor::block_on;
+
+    use crate::BITCOIN_KEY_NAME;
+
+    let peers = (0..4).map(PeerId::from).collect::<Vec<PeerId>>();
+
+    let (sks, aggregate_pk) = dealer_keygen(&peers, 3);
+
+    let peer = PeerId::from(0);
+
+    let signer = local_signer(sks[&peer]);
+
+    let session = session_id(&bitcoin::Txid::all_zeros(), 0, 0);
+
+    let (hiding, binding) =
+        block_on(signer.nonce_commitment(BITCOIN_KEY_NAME, session)).expect("Signer holds the key");
+
+    // Consensus may ask us for our nonces until it has received them
+    assert_eq!(
+        block_on(signer.nonce_commitment(BITCOIN_KEY_NAME, session)).expect("Signer holds the key"),
+        (hiding, binding)
+    );
+
+    let commitments = peers[..3]
+        .iter()
+        .map(|peer| (*peer, NonceCommitment { hiding, binding }))
+        .collect::<BTreeMap<PeerId, NonceCommitment>>();
+
+    let sign = |message: [u8; 32]| {
+        let spend = KeySpend::new(&aggregate_pk, &sha256::Hash::hash(&[0]), None, message);
+
+        let factors = signing_factors(&spend, peer, &commitments);
+
+        block_on(signer.partial_signature(
+            BITCOIN_KEY_NAME,
+            session,
+            factors.binding_factor,
+            factors.negate_nonce,
+            factors.key_factor,
+        ))
+    };
+
+    let signature = sign([42; 32]).expect("First signature succeeds");
+
+    // Repeating the exact same request returns the same signature
+    assert_eq!(
+        sign([42; 32]).expect("Repeated request succeeds"),
+        signature
+    );
+
+    // Signing a different message would reveal our key share
+    assert!(sign([43; 32]).is_err());
+
+    assert!(block_on(signer.nonce_commitment(BITCOIN_KEY_NAME, session)).is_err());
+
+    // A restarted signer has lost the nonces it committed to
+    let restarted = local_signer(sks[&peer]);
+
+    assert!(
+        block_on(restarted.partial_signature(BITCOIN_KEY_NAME, session, [1; 32], false, [1; 32]))
+            .is_err()
+    );
+}
@@ -11,25 +11,27 @@
 #![allow(clippy::too_many_lines)]
 
 pub mod db;
+mod frost;
 
 use std::collections::{BTreeMap, BTreeSet};
 
 use anyhow::{Context, anyhow, bail, ensure};
 use bitcoin::absolute::LockTime;
 use bitcoin::hashes::{Hash, sha256};
-use bitcoin::secp256k1::Secp256k1;
-use bitcoin::sighash::{EcdsaSighashType, SighashCache};
+use bitcoin::sighash::{EcdsaSighashType, Prevouts, SighashCache, TapSighashType};
 use bitcoin::transaction::Version;
-use bitcoin::{Amount, Network, Sequence, Transaction, TxIn, TxOut, Txid};
-use common::config::WalletConfigConsensus;
+use bitcoin::{Amount, Network, ScriptBuf, Sequence, Transaction, TxIn, TxOut, Txid};
+use common::config::{PegOutBatching, WalletConfigConsensus, WalletDescriptor};
 use common::{
-    OutputInfo, WalletCommonInit, WalletConsensusItem, WalletInput, WalletModuleTypes,
-    WalletOutput, WalletOutputOutcome,
+    NonceCommitment, OutputInfo, PartialSignature, StandardScript, WalletCommonInit,
+    WalletConsensusItem, WalletInput, WalletModuleTypes, WalletOutput, WalletOutputOutcome,
 };
 use db::{
-    DbKeyPrefix, FederationWalletKey, FederationWalletPrefix, Output, OutputKey, OutputPrefix,
-    SignaturesKey, SignaturesPrefix, SignaturesTxidPrefix, SpentOutputKey, SpentOutputPrefix,
-    TxInfoIndexKey, TxInfoIndexPrefix,
+    DbKeyPrefix, FederationWalletKey, FederationWalletPrefix, NoncesKey, NoncesPrefix,
+    NoncesTxidPrefix, Output, OutputKey, OutputPrefix, PartialSignaturesKey,
+    PartialSignaturesPrefix, PartialSignaturesTxidPrefix, SignaturesKey, SignaturesPrefix,
+    SignaturesTxidPrefix, SigningSession, SigningSessionKey, SigningSessionPrefix, SpentOutputKey,
+    SpentOutputPrefix, TxInfoIndexKey, TxInfoIndexPrefix, migrate_to_v1,
 };
 use fedimint_core::config::{
     ServerModuleConfig, ServerModuleConsensusConfig, TypedServerModuleConfig,
@@ -40,7 +42,12 @@
     Database, DatabaseTransaction, DatabaseVersion, IDatabaseTransactionOpsCoreTyped,
 };
 use fedimint_core::encoding::{Decodable, Encodable};
-use fedimint_core::envs::{FM_ENABLE_MODULE_WALLETV2_ENV, is_env_var_set_opt};
+use fedimint_core::envs::{
+    FM_ENABLE_MODULE_WALLETV2_ENV, FM_WALLETV2_FEE_BUMP_DELAY_ENV,
+    FM_WALLETV2_PEGOUT_BATCH_MAX_PEGOUTS_ENV, FM_WALLETV2_PEGOUT_BATCH_WINDOW_ENV,
+    FM_WALLETV2_TAPROOT_ENV, is_automatic_consensus_version_voting_disabled, is_env_var_set,
+    is_env_var_set_opt,
+};
 use fedimint_core::module::audit::Audit;
 use fedimint_core::module::{
     Amounts, ApiEndpoint, ApiVersion, CORE_CONSENSUS_VERSION, CoreConsensusVersion, InputMeta,
@@ -57,35 +64,50 @@
 use fedimint_logging::LOG_MODULE_WALLETV2;
 use fedimint_server_core::bitcoin_rpc::ServerBitcoinRpcMonitor;
 use fedimint_server_core::config::{PeerHandleOps, PeerHandleOpsExt};
+use fedimint_server_core::consensus_version::spawn_peer_supported_consensus_version_task;
+use fedimint_server_core::guardian_signer::{GuardianSecret, ModuleGuardianSigner};
 use fedimint_server_core::migration::ServerModuleDbMigrationFn;
 use fedimint_server_core::{
     ConfigGenModuleArgs, EnvVarDoc, ServerModule, ServerModuleInit, ServerModuleInitArgs,
 };
 pub use fedimint_walletv2_common as common;
 use fedimint_walletv2_common::config::{
-    FeeConsensus, WalletClientConfig, WalletConfig, WalletConfigPrivate,
+    DEFAULT_FEE_BUMP_DELAY, FeeConsensus, WalletClientConfig, WalletConfig, WalletConfigPrivate,
 };
 use fedimint_walletv2_common::endpoint_constants::{
     CONSENSUS_BLOCK_COUNT_ENDPOINT, CONSENSUS_FEERATE_ENDPOINT, FEDERATION_WALLET_ENDPOINT,
-    OUTPUT_INFO_SLICE_ENDPOINT, PENDING_TRANSACTION_CHAIN_ENDPOINT, RECEIVE_FEE_ENDPOINT,
-    SEND_FEE_ENDPOINT, TRANSACTION_CHAIN_ENDPOINT, TRANSACTION_ID_ENDPOINT,
+    MODULE_CONSENSUS_VERSION_ENDPOINT, OUTPUT_INFO_SLICE_ENDPOINT, PEGOUT_STATUS_ENDPOINT,
+    PENDING_TRANSACTION_CHAIN_ENDPOINT, RECEIVE_FEE_ENDPOINT, SEND_FEE_ENDPOINT,
+    SILENT_PAYMENT_PROOF_ENDPOINT, SUPPORTED_MODULE_CONSENSUS_VERSION_ENDPOINT,
+    TRANSACTION_CHAIN_ENDPOINT, TRANSACTION_ID_ENDPOINT, WATCH_ONLY_DESCRIPTORS_ENDPOINT,
 };
+use fedimint_walletv2_common::silent_payments::{self, EcdhShare, SilentPaymentProof};
 use fedimint_walletv2_common::{
-    FederationWallet, MODULE_CONSENSUS_VERSION, TxInfo, WalletInputError, WalletOutputError,
-    descriptor, is_potential_receive, tweak_public_key,
+    FEE_BUMP_MODULE_CONSENSUS_VERSION, FederationWallet, MODULE_CONSENSUS_VERSION,
+    PEGOUT_BATCHING_MODULE_CONSENSUS_VERSION, PegOutStatus,
+    SILENT_PAYMENTS_MODULE_CONSENSUS_VERSION, TAPROOT_MODULE_CONSENSUS_VERSION, TweakedDescriptor,
+    TxInfo, UnclaimedDeposit, WalletInputError, WalletOutputError, WatchOnlyDescriptors,
+    descriptor, is_potential_receive, tr_descriptor, tweak_public_key,
 };
-use futures::StreamExt;
-use miniscript::descriptor::Wsh;
+use futures::{FutureExt, StreamExt};
+use miniscript::Descriptor;
 use rand::rngs::OsRng;
 use secp256k1::ecdsa::Signature;
 use secp256k1::{PublicKey, Scalar, SecretKey};
 use serde::{Deserialize, Serialize};
 use strum::IntoEnumIterator;
-use tracing::{debug, info};
+use tokio::sync::watch;
+use tracing::{debug, error, info, warn};
 
 use crate::db::{
-    BlockCountVoteKey, BlockCountVotePrefix, FeeRateVoteKey, FeeRateVotePrefix, TxInfoKey,
-    TxInfoPrefix, UnconfirmedTxKey, UnconfirmedTxPrefix, UnsignedTxKey, UnsignedTxPrefix,
+    BlockCountVoteKey, BlockCountVotePrefix, ConsensusVersionVoteKey, ConsensusVersionVotePrefix,
+    EcdhShareKey, EcdhShareOutPointPrefix, EcdhSharePrefix, FeeBumpKey, FeeBumpPrefix,
+    FeeBumpTxKey, FeeBumpTxPrefix, FeeBumpVoteKey, FeeBumpVotePrefix, FeeBumpVoteTxidPrefix,
+    FeeRateVoteKey, FeeRateVotePrefix, PegOutBatchKey, PegOutBatchPrefix, PendingSilentPayment,
+    PendingSilentPaymentKey, PendingSilentPaymentPrefix, QueuedPegOut, QueuedPegOutKey,
+    QueuedPegOutPrefix, ReplacedTx, ReplacedTxKey, ReplacedTxPrefix, SilentPaymentProofKey,
+    SilentPaymentProofPrefix, TxInfoKey, TxInfoPrefix, UnconfirmedTxKey, UnconfirmedTxPrefix,
+    UnsignedTxKey, UnsignedTxPrefix, UsedTweakKey, UsedTweakPrefix,
 };
 
 /// Number of confirmations required for a transaction to be considered as
@@ -101,6 +123,36 @@
 /// below what Bitcoin Core will relay.
 const MIN_FEERATE_VOTE_SATS_PER_KVB: u64 = 1000;
 
+/// Number of consensus blocks after which a threshold signing session for the
+/// taproot key path is restarted with fresh nonces, such that a guardian going
+/// offline after committing to its nonces can not stall the federation.
+const SIGNING_SESSION_TIMEOUT: u64 = 3;
+
+/// Maximum number of peg-outs in a batch if batching is enabled, unless
+/// configured otherwise.
+const DEFAULT_MAX_BATCHED_PEGOUTS: u64 = 100;
+
+/// Upper bound for the configured number of peg-outs in a batch, such that the
+/// batch transaction remains well below the standard weight limit.
+const MAX_BATCHED_PEGOUTS: u64 = 1000;
+
+/// The vbytes of a peg-out output, assuming the largest standard
+/// scriptPubKey of 34 bytes.
+const PEGOUT_OUTPUT_VBYTES: u64 = 43;
+
+/// Maximum number of pending transactions in the stack, as asserted by
+/// `stack_fee`.
+const MAX_PENDING_TXS: usize = 32;
+
+/// Maximum number of fee bump transactions in the pending stack. Once reached
+/// the fee is only bumped by replacing the most recent pending transaction if
+/// it is a fee bump, which does not grow the stack.
+pub const MAX_FEE_BUMP_TXS: usize = 4;
+
+/// Bitcoin Core requires a replacement to pay for its own relay at the
+/// incremental relay feerate of 1 sat/vB on top of the replaced fee (BIP125).
+const INCREMENTAL_RELAY_FEERATE: u64 = 1;
+
 #[derive(Clone, Debug, Eq, PartialEq, Serialize, Encodable, Decodable)]
 pub struct FederationTx {
     pub tx: Transaction,
@@ -115,6 +167,16 @@ pub struct SpentTxOut {
     pub tweak: sha256::Hash,
 }
 
+fn input_nonces(
+    nonces: &BTreeMap<PeerId, Vec<NonceCommitment>>,
+    index: usize,
+) -> BTreeMap<PeerId, NonceCommitment> {
+    nonces
+        .iter()
+        .map(|(peer, nonces)| (*peer, nonces[index]))
+        .collect()
+}
+
 async fn pending_txs_unordered(dbtx: &mut DatabaseTransaction<'_>) -> Vec<FederationTx> {
     let unsigned: Vec<FederationTx> = dbtx
         .find_by_prefix(&UnsignedTxPrefix)
@@ -133,6 +195,45 @@ async fn pending_txs_unordered(dbtx: &mut DatabaseTransaction<'_>) -> Vec<Federa
     unsigned.into_iter().chain(unconfirmed).collect()
 }
 
+/// Removes a pending transaction that can no longer confirm together with its
+/// signing and fee bump state.
+async fn remove_pending_tx(dbtx: &mut DatabaseTransaction<'_>, txid: Txid) {
+    dbtx.remove_entry(&UnsignedTxKey(txid)).await;
+
+    dbtx.remove_entry(&UnconfirmedTxKey(txid)).await;
+
+    dbtx.remove_entry(&SigningSessionKey(txid)).await;
+
+    dbtx.remove_by_prefix(&NoncesTxidPrefix(txid)).await;
+
+    dbtx.remove_by_prefix(&PartialSignaturesTxidPrefix(txid))
+        .await;
+
+    dbtx.remove_by_prefix(&SignaturesTxidPrefix(txid)).await;
+
+    dbtx.remove_entry(&FeeBumpTxKey(txid)).await;
+
+    dbtx.remove_entry(&FeeBumpKey(txid)).await;
+
+    dbtx.remove_by_prefix(&FeeBumpVoteTxidPrefix(txid)).await;
+}
+
+/// Removes the replaced versions of the fee bump transaction at the given
+/// index of the transaction log once one of its versions has confirmed.
+async fn remove_replaced_txs(dbtx: &mut DatabaseTransaction<'_>, index: u64) {
+    let replaced = dbtx
+        .find_by_prefix(&ReplacedTxPrefix)
+        .await
+        .filter(|(_, replaced)| std::future::ready(replaced.tx_info.index == index))
+        .map(|(key, _)| key)
+        .collect::<Vec<ReplacedTxKey>>()
+        .await;
+
+    for key in replaced {
+        dbtx.remove_entry(&key).await;
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct WalletInit;
 
@@ -252,6 +353,146 @@ async fn dump_database(
                         "Federation Wallet"
                     );
                 }
+                DbKeyPrefix::SigningSession => {
+                    push_db_pair_items!(
+                        dbtx,
+                        SigningSessionPrefix,
+                        SigningSessionKey,
+                        SigningSession,
+                        wallet,
+                        "Wallet Signing Sessions"
+                    );
+                }
+                DbKeyPrefix::Nonces => {
+                    push_db_pair_items!(
+                        dbtx,
+                        NoncesPrefix,
+                        NoncesKey,
+                        Vec<NonceCommitment>,
+                        wallet,
+                        "Wallet Nonces"
+                    );
+                }
+                DbKeyPrefix::PartialSignatures => {
+                    push_db_pair_items!(
+                        dbtx,
+                        PartialSignaturesPrefix,
+                        PartialSignaturesKey,
+                        Vec<PartialSignature>,
+                        wallet,
+                        "Wallet Partial Signatures"
+                    );
+                }
+                DbKeyPrefix::FeeBumpVote => {
+                    push_db_pair_items!(
+                        dbtx,
+                        FeeBumpVotePrefix,
+                        FeeBumpVoteKey,
+                        (),
+                        wallet,
+                        "Wallet Fee Bump Votes"
+                    );
+                }
+                DbKeyPrefix::FeeBump => {
+                    push_db_pair_items!(
+                        dbtx,
+                        FeeBumpPrefix,
+                        FeeBumpKey,
+                        u64,
+                        wallet,
+                        "Wallet Fee Bumps"
+                    );
+                }
+                DbKeyPrefix::PendingSilentPayment => {
+                    push_db_pair_items!(
+                        dbtx,
+                        PendingSilentPaymentPrefix,
+                        PendingSilentPaymentKey,
+                        PendingSilentPayment,
+                        wallet,
+                        "Wallet Pending Silent Payments"
+                    );
+                }
+                DbKeyPrefix::EcdhShare => {
+                    push_db_pair_items!(
+                        dbtx,
+                        EcdhSharePrefix,
+                        EcdhShareKey,
+                        EcdhShare,
+                        wallet,
+                        "Wallet ECDH Shares"
+                    );
+                }
+                DbKeyPrefix::SilentPaymentProof => {
+                    push_db_pair_items!(
+                        dbtx,
+                        SilentPaymentProofPrefix,
+                        SilentPaymentProofKey,
+                        SilentPaymentProof,
+                        wallet,
+                        "Wallet Silent Payment Proofs"
+                    );
+                }
+                DbKeyPrefix::UsedTweak => {
+                    push_db_pair_items!(
+                        dbtx,
+                        UsedTweakPrefix,
+                        UsedTweakKey,
+                        u64,
+                        wallet,
+                        "Wallet Used Tweaks"
+                    );
+                }
+                DbKeyPrefix::FeeBumpTx => {
+                    push_db_pair_items!(
+                        dbtx,
+                        FeeBumpTxPrefix,
+                        FeeBumpTxKey,
+                        u64,
+                        wallet,
+                        "Wallet Fee Bump Transactions"
+                    );
+                }
+                DbKeyPrefix::ReplacedTx => {
+                    push_db_pair_items!(
+                        dbtx,
+                        ReplacedTxPrefix,
+                        ReplacedTxKey,
+                        ReplacedTx,
+                        wallet,
+                        "Wallet Replaced Transactions"
+                    );
+                }
+                DbKeyPrefix::ConsensusVersionVote => {
+                    push_db_pair_items!(
+                        dbtx,
+                        ConsensusVersionVotePrefix,
+                        ConsensusVersionVoteKey,
+                        ModuleConsensusVersion,
+                        wallet,
+                        "Wallet Consensus Version Votes"
+                    );
+                }
+                DbKeyPrefix::PegOutBatch => {
+                    push_db_pair_items!(
+                        dbtx,
+                        PegOutBatchPrefix,
+                        PegOutBatchKey,
+                        u64,
+                        wallet,
+                        "Wallet Peg-Out Batch"
+                    );
+                }
+                DbKeyPrefix::QueuedPegOut => {
+                    push_db_pair_items!(
+                        dbtx,
+                        QueuedPegOutPrefix,
+                        QueuedPegOutKey,
+                        QueuedPegOut,
+                        wallet,
+                        "Wallet Queued Peg-Outs"
+                    );
+                }
             }
         }
 
@@ -259,6 +500,40 @@ async fn dump_database(
     }
 }
 
+fn pegout_batching() -> anyhow::Result<Option<PegOutBatching>> {
+    let Ok(window) = std::env::var(FM_WALLETV2_PEGOUT_BATCH_WINDOW_ENV) else {
+        return Ok(None);
+    };
+
+    let max_pegouts = std::env::var(FM_WALLETV2_PEGOUT_BATCH_MAX_PEGOUTS_ENV)
+        .ok()
+        .map_or(Ok(DEFAULT_MAX_BATCHED_PEGOUTS), |max| max.parse())?;
+
+    let batching = PegOutBatching {
+        window: window.parse()?,
+        max_pegouts,
+    };
+
+    validate_pegout_batching(&batching)?;
+
+    Ok(Some(batching))
+}
+
+fn validate_pegout_batching(batching: &PegOutBatching) -> anyhow::Result<()> {
+    ensure!(
+        (1..=MAX_BATCHED_PEGOUTS).contains(&batching.max_pegouts),
+        "The maximum number of peg-outs in a batch has to be between 1 and {MAX_BATCHED_PEGOUTS}"
+    );
+
+    Ok(())
+}
+
+fn fee_bump_delay() -> anyhow::Result<u64> {
+    std::env::var(FM_WALLETV2_FEE_BUMP_DELAY_ENV)
+        .ok()
+        .map_or(Ok(DEFAULT_FEE_BUMP_DELAY), |delay| Ok(delay.parse()?))
+}
+
 #[apply(async_trait_maybe_send!)]
 impl ServerModuleInit for WalletInit {
     type Module = Wallet;
@@ -283,18 +558,48 @@ fn is_enabled_by_default(&self) -> bool {
     }
 
     fn get_documented_env_vars(&self) -> Vec<EnvVarDoc> {
-        vec![EnvVarDoc {
-            name: FM_ENABLE_MODULE_WALLETV2_ENV,
-            description: "Set to 1/true to enable the WalletV2 module (experimental). Disabled by default.",
-        }]
+        vec![
+            EnvVarDoc {
+                name: FM_ENABLE_MODULE_WALLETV2_ENV,
+                description: "Set to 1/true to enable the WalletV2 module (experimental). Disabled by default.",
+            },
+            EnvVarDoc {
+                name: FM_WALLETV2_TAPROOT_ENV,
+                description: "Set to 1/true during config generation to use a taproot descriptor with threshold Schnorr signatures for the WalletV2 module. All guardians have to agree.",
+            },
+            EnvVarDoc {
+                name: FM_WALLETV2_FEE_BUMP_DELAY_ENV,
+                description: "Set to a number of blocks during config generation after which the WalletV2 module bumps the fee of an unconfirmed transaction. Defaults to 6. All guardians have to agree.",
+            },
+            EnvVarDoc {
+                name: FM_WALLETV2_PEGOUT_BATCH_WINDOW_ENV,
+                description: "Set to a number of blocks during config generation to batch peg-outs of the WalletV2 module over that window. All guardians have to agree.",
+            },
+            EnvVarDoc {
+                name: FM_WALLETV2_PEGOUT_BATCH_MAX_PEGOUTS_ENV,
+                description: "Set to the maximum number of peg-outs in a single batch of the WalletV2 module during config generation. Defaults to 100. All guardians have to agree.",
+            },
+        ]
     }
 
     async fn init(&self, args: &ServerModuleInitArgs<Self>) -> anyhow::Result<Self::Module> {
+        let peer_supported_consensus_version = spawn_peer_supported_consensus_version_task(
+            args.module_api().clone(),
+            args.task_group(),
+            args.our_peer_id(),
+            MODULE_CONSENSUS_VERSION,
+            SUPPORTED_MODULE_CONSENSUS_VERSION_ENDPOINT,
+        );
+
         Ok(Wallet::new(
             args.cfg().to_typed()?,
             args.db(),
             args.task_group(),
+            args.our_peer_id(),
             args.server_bitcoin_rpc_monitor(),
+            args.guardian_signer().clone(),
+            args.cfg().consensus.version,
+            peer_supported_consensus_version,
         ))
     }
 
@@ -305,10 +610,23 @@ fn trusted_dealer_gen(
     ) -> BTreeMap<PeerId, ServerModuleConfig> {
         let fee_consensus = FeeConsensus::new(0).expect("Relative fee is within range");
 
-        let bitcoin_sks = peers
-            .iter()
-            .map(|peer| (*peer, SecretKey::new(&mut secp256k1::rand::thread_rng())))
-            .collect::<BTreeMap<PeerId, SecretKey>>();
+        let pegout_batching = pegout_batching().expect("Failed to parse peg-out batch window");
+
+        let fee_bump_delay = fee_bump_delay().expect("Failed to parse fee bump delay");
+
+        let (bitcoin_sks, descriptor) = if is_env_var_set(FM_WALLETV2_TAPROOT_ENV) {
+            let (bitcoin_sks, aggregate_pk) =
+                frost::dealer_keygen(peers, peers.to_num_peers().threshold());
+
+            (bitcoin_sks, WalletDescriptor::Tr { aggregate_pk })
+        } else {
+            let bitcoin_sks = peers
+                .iter()
+                .map(|peer| (*peer, SecretKey::new(&mut secp256k1::rand::thread_rng())))
+                .collect::<BTreeMap<PeerId, SecretKey>>();
+
+            (bitcoin_sks, WalletDescriptor::Wsh)
+        };
 
         let bitcoin_pks = bitcoin_sks
             .iter()
@@ -319,11 +637,16 @@ fn trusted_dealer_gen(
             .into_iter()
             .map(|(peer, bitcoin_sk)| {
                 let config = WalletConfig {
-                    private: WalletConfigPrivate { bitcoin_sk },
+                    private: WalletConfigPrivate {
+                        bitcoin_sk: Some(bitcoin_sk),
+                    },
                     consensus: WalletConfigConsensus::new(
                         bitcoin_pks.clone(),
+                        descriptor.clone(),
                         fee_consensus.clone(),
                         args.network,
+                        pegout_batching.clone(),
+                        fee_bump_delay,
                     ),
                 };
 
@@ -339,38 +662,122 @@ async fn distributed_gen(
     ) -> anyhow::Result<ServerModuleConfig> {
         let fee_consensus = FeeConsensus::new(0).expect("Relative fee is within range");
 
-        let (bitcoin_sk, bitcoin_pk) = secp256k1::generate_keypair(&mut OsRng);
+        let taproot = is_env_var_set(FM_WALLETV2_TAPROOT_ENV);
 
-        let bitcoin_pks: BTreeMap<PeerId, PublicKey> = peers
-            .exchange_encodable(bitcoin_pk)
-            .await?
-            .into_iter()
-            .collect();
+        let pegout_batching = pegout_batching()?;
+
+        let fee_bump_delay = fee_bump_delay()?;
+
+        let params = (taproot, pegout_batching.clone(), fee_bump_delay);
+
+        ensure!(
+            peers
+                .exchange_encodable(params.clone())
+                .await?
+                .values()
+                .all(|vote| *vote == params),
+            "Guardians disagree on the wallet parameters"
+        );
+
+        let (bitcoin_sk, bitcoin_pks, descriptor) = if taproot {
+            let (bitcoin_sk, bitcoin_pks, aggregate_pk) = frost::run_dkg(peers).await?;
+
+            (
+                bitcoin_sk,
+                bitcoin_pks,
+                WalletDescriptor::Tr { aggregate_pk },
+            )
+        } else {
+            let (bitcoin_sk, bitcoin_pk) = secp256k1::generate_keypair(&mut OsRng);
+
+            let bitcoin_pks: BTreeMap<PeerId, PublicKey> = peers
+                .exchange_encodable(bitcoin_pk)
+                .await?
+                .into_iter()
+                .collect();
+
+            (bitcoin_sk, bitcoin_pks, WalletDescriptor::Wsh)
+        };
 
         let config = WalletConfig {
-            private: WalletConfigPrivate { bitcoin_sk },
-            consensus: WalletConfigConsensus::new(bitcoin_pks, fee_consensus, args.network),
+            private: WalletConfigPrivate {
+                bitcoin_sk: Some(bitcoin_sk),
+            },
+            consensus: WalletConfigConsensus::new(
+                bitcoin_pks,
+                descriptor,
+                fee_consensus,
+                args.network,
+                pegout_batching,
+                fee_bump_delay,
+            ),
         };
 
         Ok(config.to_erased())
     }
 
     fn validate_config(&self, identity: &PeerId, config: ServerModuleConfig) -> anyhow::Result<()> {
+        let genesis_consensus_version = config.consensus.version;
+
         let config = config.to_typed::<WalletConfig>()?;
 
-        ensure!(
-            config
-                .consensus
-                .bitcoin_pks
-                .get(identity)
-                .ok_or(anyhow::anyhow!("No public key for our identity"))?
-                == &config.private.bitcoin_sk.public_key(secp256k1::SECP256K1),
-            "Bitcoin wallet private key doesn't match multisig pubkey"
-        );
+        let our_pk = config
+            .consensus
+            .bitcoin_pks
+            .get(identity)
+            .ok_or(anyhow::anyhow!("No public key for our identity"))?;
+
+        // The secret key is missing once it has been exported to a remote
+        // guardian signer, which verifies it on startup instead
+        if let Some(bitcoin_sk) = config.private.bitcoin_sk {
+            ensure!(
+                our_pk == &bitcoin_sk.public_key(secp256k1::SECP256K1),
+                "Bitcoin wallet private key doesn't match multisig pubkey"
+            );
+        }
+
+        if let Some(batching) = &config.consensus.pegout_batching {
+            ensure!(
+                genesis_consensus_version >= PEGOUT_BATCHING_MODULE_CONSENSUS_VERSION,
+                "Peg-out batching requires module consensus version {PEGOUT_BATCHING_MODULE_CONSENSUS_VERSION}"
+            );
+
+            validate_pegout_batching(batching)?;
+        }
+
+        if let WalletDescriptor::Tr { aggregate_pk } = config.consensus.descriptor {
+            ensure!(
+                genesis_consensus_version >= TAPROOT_MODULE_CONSENSUS_VERSION,
+                "The taproot descriptor requires module consensus version {TAPROOT_MODULE_CONSENSUS_VERSION}"
+            );
+
+            let bitcoin_pks = &config.consensus.bitcoin_pks;
+
+            ensure!(
+                frost::interpolate_aggregate_pk(
+                    bitcoin_pks,
+                    bitcoin_pks.to_num_peers().threshold()
+                ) == aggregate_pk,
+                "Bitcoin wallet key shares don't match the aggregate pubkey"
+            );
+        }
 
         Ok(())
     }
 
+    fn guardian_keys(
+        &self,
+        config: &ServerModuleConfig,
+    ) -> anyhow::Result<BTreeMap<String, GuardianSecret>> {
+        Ok(config
+            .to_typed::<WalletConfig>()?
+            .private
+            .bitcoin_sk
+            .into_iter()
+            .map(|sk| (BITCOIN_KEY_NAME.to_string(), GuardianSecret::Secp256k1(sk)))
+            .collect())
+    }
+
     fn get_client_config(
         &self,
         config: &ServerModuleConsensusConfig,
@@ -386,13 +793,22 @@ fn get_client_config(
             dust_limit: config.dust_limit,
             fee_consensus: config.fee_consensus,
             network: config.network,
+            pegout_batching: config.pegout_batching,
         })
     }
 
     fn get_database_migrations(
         &self,
     ) -> BTreeMap<DatabaseVersion, ServerModuleDbMigrationFn<Wallet>> {
-        BTreeMap::new()
+        let mut migrations: BTreeMap<DatabaseVersion, ServerModuleDbMigrationFn<Wallet>> =
+            BTreeMap::new();
+
+        migrations.insert(
+            DatabaseVersion(0),
+            Box::new(|ctx| migrate_to_v1(ctx).boxed()),
+        );
+
+        migrations
     }
 
     fn used_db_prefixes(&self) -> Option<BTreeSet<u8>> {
@@ -409,24 +825,93 @@ async fn consensus_proposal<'a>(
         &'a self,
         dbtx: &mut DatabaseTransaction<'_>,
     ) -> Vec<WalletConsensusItem> {
-        let mut items = dbtx
+        let unsigned_txs = dbtx
             .find_by_prefix(&UnsignedTxPrefix)
             .await
-            .map(|(key, unsigned_tx)| {
-                let signatures = self.sign_tx(&unsigned_tx);
+            .map(|(key, unsigned_tx)| (key.0, unsigned_tx))
+            .collect::<Vec<(Txid, FederationTx)>>()
+            .await;
 
-                self.verify_signatures(
-                    &unsigned_tx,
-                    &signatures,
-                    self.cfg.private.bitcoin_sk.public_key(secp256k1::SECP256K1),
-                )
-                .expect("Our signatures failed verification against our private key");
+        let active_consensus_version = self.consensus_module_consensus_version(dbtx).await;
 
-                WalletConsensusItem::Signatures(key.0, signatures)
-            })
-            .collect::<Vec<WalletConsensusItem>>()
+        // Guardians running a version from before the voting was introduced
+        // reject the vote, so we only vote once all of them support the upgrade
+        let mut items = if is_automatic_consensus_version_voting_disabled() {
+            vec![]
+        } else {
+            self.peer_supported_consensus_version
+                .borrow()
+                .filter(|supported| active_consensus_version < *supported)
+                .map(WalletConsensusItem::ModuleConsensusVersion)
+                .into_iter()
+                .collect()
+        };
+
+        for (txid, unsigned_tx) in unsigned_txs {
+            // If the guardian signer fails we propose the item again with the
+            // next consensus proposal
+            let item = match self.cfg.consensus.descriptor {
+                WalletDescriptor::Wsh => self
+                    .sign_tx(&unsigned_tx)
+                    .await
+                    .map(|signatures| Some(WalletConsensusItem::Signatures(txid, signatures))),
+                WalletDescriptor::Tr { .. }
+                    if active_consensus_version < TAPROOT_MODULE_CONSENSUS_VERSION =>
+                {
+                    Ok(None)
+                }
+                WalletDescriptor::Tr { aggregate_pk } => {
+                    self.signing_session_proposal(dbtx, &aggregate_pk, txid, &unsigned_tx)
+                        .await
+                }
+            };
+
+            match item {
+                Ok(item) => items.extend(item),
+                Err(e) => warn!(
+                    target: LOG_MODULE_WALLETV2,
+                    err = %e.fmt_compact_anyhow(),
+                    %txid,
+                    "Failed to sign transaction with guardian signer"
+                ),
+            }
+        }
+
+        let pending_silent_payments = dbtx
+            .find_by_prefix(&PendingSilentPaymentPrefix)
+            .await
+            .map(|(key, payment)| (key.0, payment))
+            .collect::<Vec<(OutPoint, PendingSilentPayment)>>()
             .await;
 
+        for (outpoint, payment) in pending_silent_payments {
+            if dbtx
+                .get_value(&EcdhShareKey(outpoint, self.our_peer_id))
+                .await
+                .is_none()
+            {
+                match self.ecdh_share(&payment.address.scan).await {
+                    Ok(share) => items.push(WalletConsensusItem::EcdhShare(outpoint, share)),
+                    Err(e) => warn!(
+                        target: LOG_MODULE_WALLETV2,
+                        err = %e.fmt_compact_anyhow(),
+                        %outpoint,
+                        "Failed to create ECDH share with guardian signer"
+                    ),
+                }
+            }
+        }
+
+        if active_consensus_version >= FEE_BUMP_MODULE_CONSENSUS_VERSION
+            && let Some(txid) = self.stuck_tx(dbtx).await
+            && dbtx
+                .get_value(&FeeBumpVoteKey(txid, self.our_peer_id))
+                .await
+                .is_none()
+        {
+            items.push(WalletConsensusItem::FeeBump(txid));
+        }
+
         if let Some(status) = self.btc_rpc.status() {
             assert_eq!(status.network, self.cfg.consensus.network);
 
@@ -477,6 +962,64 @@ async fn process_consensus_item<'a, 'b>(
             WalletConsensusItem::Signatures(txid, signatures) => {
                 self.process_signatures(dbtx, txid, signatures, peer).await
             }
+            WalletConsensusItem::Nonces(txid, session, nonces) => {
+                ensure!(
+                    self.consensus_module_consensus_version(dbtx).await
+                        >= TAPROOT_MODULE_CONSENSUS_VERSION,
+                    "Threshold signing sessions are not active yet"
+                );
+
+                self.process_nonces(dbtx, txid, session, nonces, peer).await
+            }
+            WalletConsensusItem::PartialSignatures(txid, signatures) => {
+                ensure!(
+                    self.consensus_module_consensus_version(dbtx).await
+                        >= TAPROOT_MODULE_CONSENSUS_VERSION,
+                    "Threshold signing sessions are not active yet"
+                );
+
+                self.process_partial_signatures(dbtx, txid, signatures, peer)
+                    .await
+            }
+            WalletConsensusItem::FeeBump(txid) => {
+                ensure!(
+                    self.consensus_module_consensus_version(dbtx).await
+                        >= FEE_BUMP_MODULE_CONSENSUS_VERSION,
+                    "Fee bumps are not active yet"
+                );
+
+                self.process_fee_bump(dbtx, txid, peer).await
+            }
+            WalletConsensusItem::EcdhShare(outpoint, share) => {
+                ensure!(
+                    self.consensus_module_consensus_version(dbtx).await
+                        >= SILENT_PAYMENTS_MODULE_CONSENSUS_VERSION,
+                    "Silent payments are not active yet"
+                );
+
+                self.process_ecdh_share(dbtx, outpoint, share, peer).await
+            }
+            WalletConsensusItem::ModuleConsensusVersion(module_consensus_version) => {
+                let current_vote = dbtx
+                    .get_value(&ConsensusVersionVoteKey(peer))
+                    .await
+                    .unwrap_or(self.genesis_consensus_version);
+
+                ensure!(
+                    module_consensus_version > current_vote,
+                    "Module consensus version vote is redundant"
+                );
+
+                dbtx.insert_entry(&ConsensusVersionVoteKey(peer), &module_consensus_version)
+                    .await;
+
+                assert!(
+                    self.consensus_module_consensus_version(dbtx).await <= MODULE_CONSENSUS_VERSION,
+                    "Wallet module does not support new consensus version, please upgrade the module"
+                );
+
+                Ok(())
+            }
             WalletConsensusItem::Default { variant, .. } => Err(anyhow!(
                 "Received wallet consensus item with unknown variant {variant}"
             )),
@@ -512,6 +1055,8 @@ async fn process_input<'a, 'b, 'c>(
             return Err(WalletInputError::WrongTweak);
         }
 
+        self.record_tweak(dbtx, input.tweak.consensus_hash()).await;
+
         let consensus_receive_fee = self
             .receive_fee(dbtx)
             .await
@@ -575,6 +1120,8 @@ async fn process_input<'a, 'b, 'c>(
             )
             .await;
 
+            self.record_tweak(dbtx, wallet.consensus_hash()).await;
+
             let tx_index = self.total_txs(dbtx).await;
 
             let created = self.consensus_block_count(dbtx).await;
@@ -652,7 +1199,7 @@ async fn process_output<'a, 'b>(
         }
 
         let wallet = dbtx
-            .remove_entry(&FederationWalletKey)
+            .get_value(&FederationWalletKey)
             .await
             .ok_or(WalletOutputError::NoFederationUTXO)?;
 
@@ -674,88 +1221,68 @@ async fn process_output<'a, 'b>(
             .checked_add(output.fee)
             .ok_or(WalletOutputError::ArithmeticOverflow)?;
 
-        let change_value = wallet
-            .value
-            .checked_sub(output_value)
+        // The change has to remain above the dust limit once all queued and
+        // pending peg-outs have been sent as well.
+        let change_value = self
+            .reserved_value(dbtx)
+            .await
+            .and_then(|reserved| reserved.checked_add(output_value))
+            .and_then(|value| wallet.value.checked_sub(value))
             .ok_or(WalletOutputError::ArithmeticOverflow)?;
 
         if change_value < self.cfg.consensus.dust_limit {
             return Err(WalletOutputError::ChangeUnderDustLimit);
         }
 
-        let script_pubkey = output
-            .destination
-            .script_pubkey()
-            .ok_or(WalletOutputError::UnknownScriptVariant)?;
+        // The output key of a silent payment depends on the federation UTXO
+        // spent, hence we send it on its own once it has been derived.
+        if let StandardScript::SilentPayment(address) = &output.destination {
+            if self.cfg.consensus.descriptor == WalletDescriptor::Wsh
+                || self.consensus_module_consensus_version(dbtx).await
+                    < SILENT_PAYMENTS_MODULE_CONSENSUS_VERSION
+            {
+                return Err(WalletOutputError::SilentPaymentsUnsupported);
+            }
 
-        let tx = Transaction {
-            version: Version(2),
-            lock_time: LockTime::ZERO,
-            input: vec![TxIn {
-                previous_output: wallet.outpoint,
-                script_sig: Default::default(),
-                sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
-                witness: bitcoin::Witness::new(),
-            }],
-            output: vec![
-                TxOut {
-                    value: change_value,
-                    script_pubkey: self.descriptor(&wallet.consensus_hash()).script_pubkey(),
-                },
-                TxOut {
+            dbtx.insert_new_entry(
+                &PendingSilentPaymentKey(outpoint),
+                &PendingSilentPayment {
+                    address: address.clone(),
                     value: output.value,
-                    script_pubkey,
+                    fee: output.fee,
                 },
-            ],
-        };
+            )
+            .await;
+        } else {
+            let pegout = QueuedPegOut {
+                script_pubkey: output
+                    .destination
+                    .script_pubkey()
+                    .ok_or(WalletOutputError::UnknownScriptVariant)?,
+                value: output.value,
+                fee: output.fee,
+            };
 
-        dbtx.insert_new_entry(
-            &FederationWalletKey,
-            &FederationWallet {
-                value: change_value,
-                outpoint: bitcoin::OutPoint {
-                    txid: tx.compute_txid(),
-                    vout: 0,
-                },
-                tweak: wallet.consensus_hash(),
-            },
-        )
-        .await;
-
-        let tx_index = self.total_txs(dbtx).await;
+            let queued_pegouts = self.queued_pegouts(dbtx).await;
 
-        let created = self.consensus_block_count(dbtx).await;
+            match self.pegout_batching(dbtx).await {
+                Some(batching) => {
+                    if queued_pegouts.is_empty() {
+                        let opened = self.consensus_block_count(dbtx).await;
 
-        dbtx.insert_new_entry(
-            &TxInfoKey(tx_index),
-            &TxInfo {
-                index: tx_index,
-                txid: tx.compute_txid(),
-                input: wallet.value,
-                output: change_value,
-                vbytes: self.cfg.consensus.send_tx_vbytes,
-                fee: output.fee,
-                created,
-            },
-        )
-        .await;
+                        dbtx.insert_entry(&PegOutBatchKey, &opened).await;
+                    }
 
-        dbtx.insert_new_entry(&TxInfoIndexKey(outpoint), &tx_index)
-            .await;
+                    dbtx.insert_new_entry(&QueuedPegOutKey(outpoint), &pegout)
+                        .await;
 
-        dbtx.insert_new_entry(
-            &UnsignedTxKey(tx.compute_txid()),
-            &FederationTx {
-                tx,
-                spent_tx_outs: vec![SpentTxOut {
-                    value: wallet.value,
-                    tweak: wallet.tweak,
-                }],
-                vbytes: self.cfg.consensus.send_tx_vbytes,
-                fee: output.fee,
-            },
-        )
-        .await;
+                    if queued_pegouts.len() as u64 + 1 >= batching.max_pegouts {
+                        self.close_pegout_batch(dbtx).await;
+                    }
+                }
+                None => self.send_pegouts(dbtx, vec![(outpoint, pegout)]).await,
+            }
+        }
 
         let amount = output_value
             .to_sat()
@@ -791,6 +1318,26 @@ async fn audit(
                 |_, wallet| 1000 * wallet.value.to_sat() as i64,
             )
             .await;
+
+        // Queued peg-outs have already been paid for with ecash but are still
+        // part of the federation wallet until their batch is sent.
+        audit
+            .add_items(
+                dbtx,
+                module_instance_id,
+                &QueuedPegOutPrefix,
+                |_, pegout| -1000 * (pegout.value.to_sat() + pegout.fee.to_sat()) as i64,
+            )
+            .await;
+
+        audit
+            .add_items(
+                dbtx,
+                module_instance_id,
+                &PendingSilentPaymentPrefix,
+                |_, payment| -1000 * (payment.value.to_sat() + payment.fee.to_sat()) as i64,
+            )
+            .await;
     }
 
     fn api_endpoints(&self) -> Vec<ApiEndpoint<Self>> {
@@ -849,6 +1396,33 @@ fn api_endpoints(&self) -> Vec<ApiEndpoint<Self>> {
                     Ok(module.tx_id(&mut dbtx, params).await)
                 }
             },
+            api_endpoint! {
+                PEGOUT_STATUS_ENDPOINT,
+                ApiVersion::new(0, 1),
+                async |module: &Wallet, context, params: OutPoint| -> Option<PegOutStatus> {
+                    let db = context.db();
+                    let mut dbtx = db.begin_transaction_nc().await;
+                    Ok(module.pegout_status(&mut dbtx, params).await)
+                }
+            },
+            api_endpoint! {
+                SILENT_PAYMENT_PROOF_ENDPOINT,
+                ApiVersion::new(0, 1),
+                async |_module: &Wallet, context, params: OutPoint| -> Option<SilentPaymentProof> {
+                    let db = context.db();
+                    let mut dbtx = db.begin_transaction_nc().await;
+                    Ok(dbtx.get_value(&SilentPaymentProofKey(params)).await)
+                }
+            },
+            api_endpoint! {
+                WATCH_ONLY_DESCRIPTORS_ENDPOINT,
+                ApiVersion::new(0, 1),
+                async |module: &Wallet, context, _params: ()| -> WatchOnlyDescriptors {
+                    let db = context.db();
+                    let mut dbtx = db.begin_transaction_nc().await;
+                    Ok(module.watch_only_descriptors(&mut dbtx).await)
+                }
+            },
             api_endpoint! {
                 OUTPUT_INFO_SLICE_ENDPOINT,
                 ApiVersion::new(0, 0),
@@ -876,23 +1450,57 @@ fn api_endpoints(&self) -> Vec<ApiEndpoint<Self>> {
                     Ok(module.tx_chain(&mut dbtx).await)
                 }
             },
+            api_endpoint! {
+                MODULE_CONSENSUS_VERSION_ENDPOINT,
+                ApiVersion::new(0, 0),
+                async |module: &Wallet, context, _params: ()| -> ModuleConsensusVersion {
+                    let db = context.db();
+                    let mut dbtx = db.begin_transaction_nc().await;
+                    Ok(module.consensus_module_consensus_version(&mut dbtx).await)
+                }
+            },
+            api_endpoint! {
+                SUPPORTED_MODULE_CONSENSUS_VERSION_ENDPOINT,
+                ApiVersion::new(0, 0),
+                async |_module: &Wallet, _context, _params: ()| -> ModuleConsensusVersion {
+                    Ok(MODULE_CONSENSUS_VERSION)
+                }
+            },
         ]
     }
 }
 
+/// Name of the bitcoin key share in the guardian signer
+pub(crate) const BITCOIN_KEY_NAME: &str = "bitcoin_sk";
+
 #[derive(Debug)]
 pub struct Wallet {
     cfg: WalletConfig,
     db: Database,
+    our_peer_id: PeerId,
     btc_rpc: ServerBitcoinRpcMonitor,
+    /// Performs all operations with our bitcoin key share
+    guardian_signer: ModuleGuardianSigner,
+    /// Consensus version the federation was created with, which is active
+    /// until the peers vote to upgrade
+    genesis_consensus_version: ModuleConsensusVersion,
+    /// Maximum consensus version supported by *all* our peers. Used to
+    /// automatically activate new consensus versions as soon as everyone
+    /// upgrades.
+    peer_supported_consensus_version: watch::Receiver<Option<ModuleConsensusVersion>>,
 }
 
 impl Wallet {
+    #[allow(clippy::too_many_arguments)]
     fn new(
         cfg: WalletConfig,
         db: &Database,
         task_group: &TaskGroup,
+        our_peer_id: PeerId,
         btc_rpc: ServerBitcoinRpcMonitor,
+        guardian_signer: ModuleGuardianSigner,
+        genesis_consensus_version: ModuleConsensusVersion,
+        peer_supported_consensus_version: watch::Receiver<Option<ModuleConsensusVersion>>,
     ) -> Wallet {
         Self::spawn_broadcast_unconfirmed_txs_task(btc_rpc.clone(), db.clone(), task_group);
 
@@ -900,9 +1508,38 @@ fn new(
             cfg,
             btc_rpc,
             db: db.clone(),
+            our_peer_id,
+            guardian_signer,
+            genesis_consensus_version,
+            peer_supported_consensus_version,
         }
     }
 
+    fn our_pk(&self) -> PublicKey {
+        self.cfg.consensus.bitcoin_pks[&self.our_peer_id]
+    }
+
+    /// Multiplies the scan key of a silent payment with our key share and
+    /// proves that we used the key share behind our public key
+    async fn ecdh_share(&self, scan: &PublicKey) -> anyhow::Result<EcdhShare> {
+        let product = self
+            .guardian_signer
+            .multiply_secp256k1_with_proof(BITCOIN_KEY_NAME, *scan, silent_payments::DLEQ_TAG)
+            .await?;
+
+        let share = EcdhShare {
+            point: product.product,
+            challenge: product.challenge,
+            response: product.response,
+        };
+
+        share
+            .verify(&self.our_pk(), scan)
+            .context("Guardian signer returned an invalid ECDH share")?;
+
+        Ok(share)
+    }
+
     fn spawn_broadcast_unconfirmed_txs_task(
         btc_rpc: ServerBitcoinRpcMonitor,
         db: Database,
@@ -956,6 +1593,18 @@ async fn process_block_count(
 
         assert!(old_consensus_block_count <= new_consensus_block_count);
 
+        self.restart_expired_signing_sessions(dbtx, new_consensus_block_count)
+            .await;
+
+        if let Some(batching) = self.pegout_batching(dbtx).await
+            && dbtx
+                .get_value(&PegOutBatchKey)
+                .await
+                .is_some_and(|opened| opened + batching.window <= new_consensus_block_count)
+        {
+            self.close_pegout_batch(dbtx).await;
+        }
+
         // We do not sync blocks that predate the federation itself.
         if old_consensus_block_count == 0 {
             return Ok(());
@@ -998,6 +1647,19 @@ async fn process_block_count(
                 dbtx.remove_entry(&UnconfirmedTxKey(tx.compute_txid()))
                     .await;
 
+                dbtx.remove_entry(&FeeBumpKey(tx.compute_txid())).await;
+
+                dbtx.remove_by_prefix(&FeeBumpVoteTxidPrefix(tx.compute_txid()))
+                    .await;
+
+                if let Some(index) = dbtx.remove_entry(&FeeBumpTxKey(tx.compute_txid())).await {
+                    remove_replaced_txs(dbtx, index).await;
+                }
+
+                if let Some(replaced) = dbtx.get_value(&ReplacedTxKey(tx.compute_txid())).await {
+                    self.restore_replaced_tx(dbtx, replaced).await;
+                }
+
                 // We maintain an append-only log of transaction outputs that pass
                 // the probabilistic receive filter created since the federation was
                 // established. This is downloaded by clients to detect pegins and
@@ -1035,6 +1697,11 @@ async fn process_signatures(
         signatures: Vec<Signature>,
         peer: PeerId,
     ) -> anyhow::Result<()> {
+        ensure!(
+            self.cfg.consensus.descriptor == WalletDescriptor::Wsh,
+            "The federation does not use ECDSA signatures"
+        );
+
         let mut unsigned = dbtx
             .get_value(&UnsignedTxKey(txid))
             .await
@@ -1065,172 +1732,1287 @@ async fn process_signatures(
             .await;
 
         if signatures.len() == self.cfg.consensus.bitcoin_pks.to_num_peers().threshold() {
-            dbtx.remove_entry(&UnsignedTxKey(txid)).await;
-
             dbtx.remove_by_prefix(&SignaturesTxidPrefix(txid)).await;
 
             self.finalize_tx(&mut unsigned, &signatures);
 
-            dbtx.insert_new_entry(&UnconfirmedTxKey(txid), &unsigned)
-                .await;
-
-            if let Err(err) = self.btc_rpc.submit_transaction(unsigned.tx).await {
-                debug!(
-                    target: LOG_MODULE_WALLETV2,
-                    err = %err.fmt_compact_anyhow(),
-                    "Error broadcasting finalized transaction"
-                );
-            }
+            self.broadcast_finalized_tx(dbtx, txid, unsigned).await;
         }
 
         Ok(())
     }
 
-    async fn await_local_sync_to_block_count(&self, block_count: u64) {
-        loop {
-            if self
-                .btc_rpc
-                .status()
-                .is_some_and(|status| status.block_count >= block_count)
-            {
-                break;
-            }
-
-            info!(target: LOG_MODULE_WALLETV2, "Waiting for local bitcoin backend to sync to block count {block_count}");
-
-            sleep(common::sleep_duration()).await;
-        }
-    }
-
-    pub async fn consensus_block_count(&self, dbtx: &mut DatabaseTransaction<'_>) -> u64 {
-        let num_peers = self.cfg.consensus.bitcoin_pks.to_num_peers();
+    async fn process_nonces(
+        &self,
+        dbtx: &mut DatabaseTransaction<'_>,
+        txid: bitcoin::Txid,
+        session: u64,
+        nonces: Vec<NonceCommitment>,
+        peer: PeerId,
+    ) -> anyhow::Result<()> {
+        ensure!(
+            matches!(self.cfg.consensus.descriptor, WalletDescriptor::Tr { .. }),
+            "The federation does not use threshold Schnorr signatures"
+        );
 
-        let mut counts = dbtx
-            .find_by_prefix(&BlockCountVotePrefix)
+        let unsigned = dbtx
+            .get_value(&UnsignedTxKey(txid))
             .await
-            .map(|entry| entry.1)
-            .collect::<Vec<u64>>()
-            .await;
-
-        assert!(counts.len() <= num_peers.total());
-
-        counts.sort_unstable();
-
-        counts.reverse();
-
-        assert!(counts.last() <= counts.first());
+            .context("Unsigned transaction does not exist")?;
 
-        // The block count we select guarantees that any threshold of correct peers can
-        // increase the consensus block count and any consensus block count has been
-        // confirmed by a threshold of peers.
+        ensure!(
+            session == self.signing_session(dbtx, txid).await,
+            "Nonces are for a different signing session"
+        );
 
-        counts.get(num_peers.threshold() - 1).copied().unwrap_or(0)
-    }
+        ensure!(
+            unsigned.spent_tx_outs.len() == nonces.len(),
+            "Incorrect number of nonces"
+        );
 
-    pub async fn consensus_feerate(&self, dbtx: &mut DatabaseTransaction<'_>) -> Option<u64> {
-        let num_peers = self.cfg.consensus.bitcoin_pks.to_num_peers();
+        ensure!(
+            self.session_nonces(dbtx, txid).await.len()
+                < self.cfg.consensus.bitcoin_pks.to_num_peers().threshold(),
+            "Signing session already has a threshold of nonces"
+        );
 
-        let mut rates = dbtx
-            .find_by_prefix(&FeeRateVotePrefix)
+        if dbtx
+            .insert_entry(&NoncesKey(txid, peer), &nonces)
             .await
-            .filter_map(|entry| async move { entry.1 })
-            .collect::<Vec<u64>>()
-            .await;
-
-        assert!(rates.len() <= num_peers.total());
+            .is_some()
+        {
+            bail!("Already received nonces from this peer")
+        }
 
-        rates.sort_unstable();
+        if dbtx.get_value(&SigningSessionKey(txid)).await.is_none() {
+            let started = self.consensus_block_count(dbtx).await;
 
-        assert!(rates.first() <= rates.last());
+            dbtx.insert_new_entry(
+                &SigningSessionKey(txid),
+                &SigningSession { index: 0, started },
+            )
+            .await;
+        }
 
-        rates.get(num_peers.threshold() - 1).copied()
+        Ok(())
     }
 
-    pub async fn consensus_fee(
+    async fn process_partial_signatures(
         &self,
         dbtx: &mut DatabaseTransaction<'_>,
-        tx_vbytes: u64,
-    ) -> Option<Amount> {
-        // The minimum feerate is a protection against a catastrophic error in the
-        // feerate estimation and limits the length of the pending transaction stack.
+        txid: bitcoin::Txid,
+        signatures: Vec<PartialSignature>,
+        peer: PeerId,
+    ) -> anyhow::Result<()> {
+        let WalletDescriptor::Tr { aggregate_pk } = self.cfg.consensus.descriptor else {
+            bail!("The federation does not use threshold Schnorr signatures")
+        };
 
-        let pending_txs = pending_txs_unordered(dbtx).await;
+        let mut unsigned = dbtx
+            .get_value(&UnsignedTxKey(txid))
+            .await
+            .context("Unsigned transaction does not exist")?;
 
-        assert!(pending_txs.len() <= 32);
+        let nonces = self.session_nonces(dbtx, txid).await;
 
-        let feerate = self
-            .consensus_feerate(dbtx)
-            .await?
-            .max(self.cfg.consensus.feerate_base << pending_txs.len());
+        let threshold = self.cfg.consensus.bitcoin_pks.to_num_peers().threshold();
 
-        let tx_fee = tx_vbytes.saturating_mul(feerate).saturating_div(1000);
+        ensure!(
+            nonces.len() == threshold,
+            "Signing session does not have a threshold of nonces yet"
+        );
 
-        let stack_vbytes = pending_txs
-            .iter()
-            .map(|t| t.vbytes)
-            .try_fold(tx_vbytes, u64::checked_add)
-            .expect("Stack vbytes overflow with at most 32 pending txs");
+        ensure!(
+            nonces.contains_key(&peer),
+            "Peer is not a signer of the signing session"
+        );
 
-        let stack_fee = stack_vbytes.saturating_mul(feerate).saturating_div(1000);
+        ensure!(
+            unsigned.spent_tx_outs.len() == signatures.len(),
+            "Incorrect number of partial signatures"
+        );
 
-        // Deduct the fees already paid by currently pending transactions
-        let stack_fee = pending_txs
-            .iter()
-            .map(|t| t.fee.to_sat())
-            .fold(stack_fee, u64::saturating_sub);
+        let pk = self
+            .cfg
+            .consensus
+            .bitcoin_pks
+            .get(&peer)
+            .expect("Failed to get public key of peer from config");
 
-        Some(Amount::from_sat(tx_fee.max(stack_fee)))
-    }
+        let key_spends = self.key_spends(&aggregate_pk, &unsigned);
 
-    pub async fn send_fee(&self, dbtx: &mut DatabaseTransaction<'_>) -> Option<Amount> {
-        self.consensus_fee(dbtx, self.cfg.consensus.send_tx_vbytes)
+        for (index, (spend, signature)) in key_spends.iter().zip(signatures.iter()).enumerate() {
+            frost::verify(spend, pk, peer, &input_nonces(&nonces, index), signature)?;
+        }
+
+        if dbtx
+            .insert_entry(&PartialSignaturesKey(txid, peer), &signatures)
             .await
-    }
+            .is_some()
+        {
+            bail!("Already received valid partial signatures from this peer")
+        }
 
-    pub async fn receive_fee(&self, dbtx: &mut DatabaseTransaction<'_>) -> Option<Amount> {
-        self.consensus_fee(dbtx, self.cfg.consensus.receive_tx_vbytes)
+        let signatures = dbtx
+            .find_by_prefix(&PartialSignaturesTxidPrefix(txid))
             .await
-    }
+            .map(|(key, signatures)| (key.1, signatures))
+            .collect::<BTreeMap<PeerId, Vec<PartialSignature>>>()
+            .await;
 
-    fn descriptor(&self, tweak: &sha256::Hash) -> Wsh<secp256k1::PublicKey> {
-        descriptor(&self.cfg.consensus.bitcoin_pks, tweak)
-    }
+        if signatures.len() == threshold {
+            dbtx.remove_entry(&SigningSessionKey(txid)).await;
 
-    fn sign_tx(&self, unsigned_tx: &FederationTx) -> Vec<Signature> {
-        let mut sighash_cache = SighashCache::new(unsigned_tx.tx.clone());
+            dbtx.remove_by_prefix(&NoncesTxidPrefix(txid)).await;
 
-        unsigned_tx
-            .spent_tx_outs
-            .iter()
-            .enumerate()
-            .map(|(index, utxo)| {
-                let descriptor = self.descriptor(&utxo.tweak).ecdsa_sighash_script_code();
+            dbtx.remove_by_prefix(&PartialSignaturesTxidPrefix(txid))
+                .await;
 
-                let p2wsh_sighash = sighash_cache
-                    .p2wsh_signature_hash(index, &descriptor, utxo.value, EcdsaSighashType::All)
-                    .expect("Failed to compute P2WSH segwit sighash");
+            for (index, spend) in key_spends.iter().enumerate() {
+                let signatures = signatures
+                    .values()
+                    .map(|signatures| signatures[index])
+                    .collect::<Vec<PartialSignature>>();
 
-                let scalar = &Scalar::from_be_bytes(utxo.tweak.to_byte_array())
-                    .expect("Hash is within field order");
+                let signature = frost::aggregate(spend, &input_nonces(&nonces, index), &signatures);
 
-                let sk = self
-                    .cfg
-                    .private
-                    .bitcoin_sk
-                    .add_tweak(scalar)
-                    .expect("Failed to tweak bitcoin secret key");
+                unsigned.tx.input[index].witness =
+                    bitcoin::Witness::p2tr_key_spend(&bitcoin::taproot::Signature {
+                        signature,
+                        sighash_type: TapSighashType::Default,
+                    });
+            }
 
-                Secp256k1::new().sign_ecdsa(&p2wsh_sighash.into(), &sk)
-            })
-            .collect()
+            self.broadcast_finalized_tx(dbtx, txid, unsigned).await;
+        }
+
+        Ok(())
     }
 
-    fn verify_signatures(
+    async fn broadcast_finalized_tx(
         &self,
-        unsigned_tx: &FederationTx,
-        signatures: &[Signature],
-        pk: PublicKey,
-    ) -> anyhow::Result<()> {
+        dbtx: &mut DatabaseTransaction<'_>,
+        txid: bitcoin::Txid,
+        finalized: FederationTx,
+    ) {
+        dbtx.remove_entry(&UnsignedTxKey(txid)).await;
+
+        dbtx.insert_new_entry(&UnconfirmedTxKey(txid), &finalized)
+            .await;
+
+        if let Err(err) = self.btc_rpc.submit_transaction(finalized.tx).await {
+            debug!(
+                target: LOG_MODULE_WALLETV2,
+                err = %err.fmt_compact_anyhow(),
+                "Error broadcasting finalized transaction"
+            );
+        }
+    }
+
+    /// Returns the oldest pending transaction if it has been broadcast, remained
+    /// unconfirmed for the fee bump delay since it was created or its fee was
+    /// last bumped and the pending transactions pay less than the consensus
+    /// feerate.
+    async fn stuck_tx(&self, dbtx: &mut DatabaseTransaction<'_>) -> Option<Txid> {
+        let oldest = self.pending_tx_chain(dbtx).await.pop()?;
+
+        dbtx.get_value(&UnconfirmedTxKey(oldest.txid)).await?;
+
+        let since = dbtx
+            .get_value(&FeeBumpKey(oldest.txid))
+            .await
+            .unwrap_or(oldest.created);
+
+        if self.consensus_block_count(dbtx).await < since + self.cfg.consensus.fee_bump_delay {
+            return None;
+        }
+
+        // The fee of a transaction without any vbytes is the amount the pending
+        // transactions are short of paying the consensus feerate.
+        if self.consensus_fee(dbtx, 0).await? == Amount::ZERO {
+            return None;
+        }
+
+        Some(oldest.txid)
+    }
+
+    async fn process_fee_bump(
+        &self,
+        dbtx: &mut DatabaseTransaction<'_>,
+        txid: Txid,
+        peer: PeerId,
+    ) -> anyhow::Result<()> {
+        ensure!(
+            self.stuck_tx(dbtx).await == Some(txid),
+            "Transaction is not stuck"
+        );
+
+        if dbtx
+            .insert_entry(&FeeBumpVoteKey(txid, peer), &())
+            .await
+            .is_some()
+        {
+            bail!("Fee bump vote is redundant");
+        }
+
+        let votes = dbtx
+            .find_by_prefix(&FeeBumpVoteTxidPrefix(txid))
+            .await
+            .collect::<Vec<_>>()
+            .await;
+
+        if votes.len() == self.cfg.consensus.bitcoin_pks.to_num_peers().threshold() {
+            dbtx.remove_by_prefix(&FeeBumpVoteTxidPrefix(txid)).await;
+
+            let block_count = self.consensus_block_count(dbtx).await;
+
+            dbtx.insert_entry(&FeeBumpKey(txid), &block_count).await;
+
+            self.bump_fee(dbtx).await;
+        }
+
+        Ok(())
+    }
+
+    /// Bumps the fee of the pending transactions. If the most recent pending
+    /// transaction is a fee bump itself, nothing spends its change output yet
+    /// and we replace it with a version paying a higher fee (RBF). Otherwise we
+    /// add a child transaction that spends the federation's change output back
+    /// to the federation (CPFP), unless `MAX_FEE_BUMP_TXS` fee bumps are
+    /// pending already. We never replace any other transaction, as this would
+    /// invalidate every pending transaction spending its change output. The fee
+    /// is paid from the federation wallet and therefore reduces the audited
+    /// assets.
+    async fn bump_fee(&self, dbtx: &mut DatabaseTransaction<'_>) {
+        if let Some(newest) = self.pending_tx_chain(dbtx).await.into_iter().next()
+            && dbtx.get_value(&FeeBumpTxKey(newest.txid)).await.is_some()
+        {
+            self.replace_fee_bump_tx(dbtx, newest).await;
+
+            return;
+        }
+
+        if pending_txs_unordered(dbtx).await.len() >= MAX_PENDING_TXS {
+            warn!(target: LOG_MODULE_WALLETV2, "Cannot bump fee, too many pending transactions");
+
+            return;
+        }
+
+        let fee_bump_txs = dbtx
+            .find_by_prefix(&FeeBumpTxPrefix)
+            .await
+            .collect::<Vec<_>>()
+            .await;
+
+        if fee_bump_txs.len() >= MAX_FEE_BUMP_TXS {
+            warn!(target: LOG_MODULE_WALLETV2, "Cannot bump fee, too many pending fee bump transactions");
+
+            return;
+        }
+
+        let Some(wallet) = dbtx.get_value(&FederationWalletKey).await else {
+            return;
+        };
+
+        let vbytes = self.cfg.consensus.bump_tx_vbytes();
+
+        let Some(fee) = self.consensus_fee(dbtx, vbytes).await else {
+            return;
+        };
+
+        let Some(change_value) = self.fee_bump_change(dbtx, &wallet, fee).await else {
+            return;
+        };
+
+        let tx = self.fee_bump_tx(&wallet, change_value);
+
+        dbtx.insert_entry(
+            &FederationWalletKey,
+            &FederationWallet {
+                value: change_value,
+                outpoint: bitcoin::OutPoint {
+                    txid: tx.compute_txid(),
+                    vout: 0,
+                },
+                tweak: wallet.consensus_hash(),
+            },
+        )
+        .await;
+
+        self.record_tweak(dbtx, wallet.consensus_hash()).await;
+
+        let tx_index = self.total_txs(dbtx).await;
+
+        let created = self.consensus_block_count(dbtx).await;
+
+        dbtx.insert_new_entry(
+            &TxInfoKey(tx_index),
+            &TxInfo {
+                index: tx_index,
+                txid: tx.compute_txid(),
+                input: wallet.value,
+                output: change_value,
+                vbytes,
+                fee,
+                created,
+            },
+        )
+        .await;
+
+        dbtx.insert_new_entry(&FeeBumpTxKey(tx.compute_txid()), &tx_index)
+            .await;
+
+        dbtx.insert_new_entry(
+            &UnsignedTxKey(tx.compute_txid()),
+            &FederationTx {
+                tx,
+                spent_tx_outs: vec![SpentTxOut {
+                    value: wallet.value,
+                    tweak: wallet.tweak,
+                }],
+                vbytes,
+                fee,
+            },
+        )
+        .await;
+    }
+
+    /// Replaces the most recent pending transaction, which has to be a fee bump,
+    /// with one spending the same federation output at a higher fee. The
+    /// replacement takes over the index of the replaced transaction in the
+    /// transaction log, hence the pending stack does not grow.
+    async fn replace_fee_bump_tx(&self, dbtx: &mut DatabaseTransaction<'_>, replaced: TxInfo) {
+        let Some(wallet) = dbtx.get_value(&FederationWalletKey).await else {
+            return;
+        };
+
+        let (pending, other_pending): (Vec<FederationTx>, Vec<FederationTx>) =
+            pending_txs_unordered(dbtx)
+                .await
+                .into_iter()
+                .partition(|pending| pending.tx.compute_txid() == replaced.txid);
+
+        let pending = pending
+            .into_iter()
+            .next()
+            .expect("The replaced transaction is pending");
+
+        // The federation output spent by the replaced transaction
+        let spent = FederationWallet {
+            value: pending.spent_tx_outs[0].value,
+            outpoint: pending.tx.input[0].previous_output,
+            tweak: pending.spent_tx_outs[0].tweak,
+        };
+
+        let vbytes = self.cfg.consensus.bump_tx_vbytes();
+
+        let Some(fee) = self.stack_fee(dbtx, &other_pending, vbytes).await else {
+            return;
+        };
+
+        let fee = fee.max(replaced.fee + Amount::from_sat(vbytes * INCREMENTAL_RELAY_FEERATE));
+
+        let Some(change_value) = self.fee_bump_change(dbtx, &spent, fee).await else {
+            return;
+        };
+
+        let tx = self.fee_bump_tx(&spent, change_value);
+
+        info!(
+            target: LOG_MODULE_WALLETV2,
+            replaced = %replaced.txid,
+            replacement = %tx.compute_txid(),
+            %fee,
+            "Replacing fee bump transaction"
+        );
+
+        remove_pending_tx(dbtx, replaced.txid).await;
+
+        dbtx.insert_new_entry(
+            &ReplacedTxKey(replaced.txid),
+            &ReplacedTx {
+                tx_info: replaced.clone(),
+                wallet,
+            },
+        )
+        .await;
+
+        dbtx.insert_entry(
+            &FederationWalletKey,
+            &FederationWallet {
+                value: change_value,
+                outpoint: bitcoin::OutPoint {
+                    txid: tx.compute_txid(),
+                    vout: 0,
+                },
+                tweak: spent.consensus_hash(),
+            },
+        )
+        .await;
+
+        let created = self.consensus_block_count(dbtx).await;
+
+        dbtx.insert_entry(
+            &TxInfoKey(replaced.index),
+            &TxInfo {
+                index: replaced.index,
+                txid: tx.compute_txid(),
+                input: spent.value,
+                output: change_value,
+                vbytes,
+                fee,
+                created,
+            },
+        )
+        .await;
+
+        self.record_tweak(dbtx, spent.consensus_hash()).await;
+
+        dbtx.insert_new_entry(&FeeBumpTxKey(tx.compute_txid()), &replaced.index)
+            .await;
+
+        dbtx.insert_new_entry(
+            &UnsignedTxKey(tx.compute_txid()),
+            &FederationTx {
+                tx,
+                spent_tx_outs: pending.spent_tx_outs,
+                vbytes,
+                fee,
+            },
+        )
+        .await;
+    }
+
+    /// A replaced fee bump transaction confirms nonetheless if it propagated
+    /// to a miner before its replacement. In this case we drop the conflicting
+    /// replacement and restore the replaced transaction as the federation's
+    /// change, which is only possible while nothing spends the replacement.
+    async fn restore_replaced_tx(&self, dbtx: &mut DatabaseTransaction<'_>, replaced: ReplacedTx) {
+        let replacement = dbtx
+            .get_value(&TxInfoKey(replaced.tx_info.index))
+            .await
+            .expect("The replaced transaction has been logged");
+
+        let wallet = dbtx
+            .get_value(&FederationWalletKey)
+            .await
+            .expect("The federation wallet exists once a transaction has been replaced");
+
+        if wallet.outpoint.txid == replacement.txid {
+            info!(
+                target: LOG_MODULE_WALLETV2,
+                replaced = %replaced.tx_info.txid,
+                replacement = %replacement.txid,
+                "Replaced fee bump transaction confirmed, restoring it"
+            );
+
+            remove_pending_tx(dbtx, replacement.txid).await;
+
+            dbtx.insert_entry(&FederationWalletKey, &replaced.wallet)
+                .await;
+
+            dbtx.insert_entry(&TxInfoKey(replaced.tx_info.index), &replaced.tx_info)
+                .await;
+        } else {
+            error!(
+                target: LOG_MODULE_WALLETV2,
+                replaced = %replaced.tx_info.txid,
+                replacement = %replacement.txid,
+                "Replaced fee bump transaction confirmed after its replacement has been spent"
+            );
+        }
+
+        remove_replaced_txs(dbtx, replaced.tx_info.index).await;
+    }
+
+    /// Returns the change of a fee bump spending the given federation output,
+    /// which has to cover the queued and pending peg-outs since they have been
+    /// accepted already.
+    async fn fee_bump_change(
+        &self,
+        dbtx: &mut DatabaseTransaction<'_>,
+        spent: &FederationWallet,
+        fee: Amount,
+    ) -> Option<Amount> {
+        let required = self
+            .reserved_value(dbtx)
+            .await?
+            .checked_add(self.cfg.consensus.dust_limit)?;
+
+        let change_value = spent
+            .value
+            .checked_sub(fee)
+            .filter(|value| *value >= required);
+
+        if change_value.is_none() {
+            warn!(target: LOG_MODULE_WALLETV2, %fee, "Cannot bump fee, insufficient federation funds");
+        }
+
+        change_value
+    }
+
+    fn fee_bump_tx(&self, spent: &FederationWallet, change_value: Amount) -> Transaction {
+        Transaction {
+            version: Version(2),
+            lock_time: LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: spent.outpoint,
+                script_sig: Default::default(),
+                sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+                witness: bitcoin::Witness::new(),
+            }],
+            output: vec![TxOut {
+                value: change_value,
+                script_pubkey: self.descriptor(&spent.consensus_hash()).script_pubkey(),
+            }],
+        }
+    }
+
+    async fn signing_session(&self, dbtx: &mut DatabaseTransaction<'_>, txid: Txid) -> u64 {
+        dbtx.get_value(&SigningSessionKey(txid))
+            .await
+            .map_or(0, |session| session.index)
+    }
+
+    async fn session_nonces(
+        &self,
+        dbtx: &mut DatabaseTransaction<'_>,
+        txid: Txid,
+    ) -> BTreeMap<PeerId, Vec<NonceCommitment>> {
+        dbtx.find_by_prefix(&NoncesTxidPrefix(txid))
+            .await
+            .map(|(key, nonces)| (key.1, nonces))
+            .collect()
+            .await
+    }
+
+    /// Returns our nonces or partial signatures for the current signing
+    /// session of a transaction spent via the taproot key path, if the session
+    /// still requires them.
+    async fn signing_session_proposal(
+        &self,
+        dbtx: &mut DatabaseTransaction<'_>,
+        aggregate_pk: &PublicKey,
+        txid: Txid,
+        unsigned_tx: &FederationTx,
+    ) -> anyhow::Result<Option<WalletConsensusItem>> {
+        let session = self.signing_session(dbtx, txid).await;
+
+        let nonces = self.session_nonces(dbtx, txid).await;
+
+        if nonces.len() < self.cfg.consensus.bitcoin_pks.to_num_peers().threshold() {
+            if nonces.contains_key(&self.our_peer_id) {
+                return Ok(None);
+            }
+
+            let mut commitments = vec![];
+
+            for index in 0..unsigned_tx.spent_tx_outs.len() {
+                let (hiding, binding) = self
+                    .guardian_signer
+                    .nonce_commitment(BITCOIN_KEY_NAME, frost::session_id(&txid, session, index))
+                    .await?;
+
+                commitments.push(NonceCommitment { hiding, binding });
+            }
+
+            return Ok(Some(WalletConsensusItem::Nonces(
+                txid,
+                session,
+                commitments,
+            )));
+        }
+
+        if !nonces.contains_key(&self.our_peer_id)
+            || dbtx
+                .get_value(&PartialSignaturesKey(txid, self.our_peer_id))
+                .await
+                .is_some()
+        {
+            return Ok(None);
+        }
+
+        let mut signatures = vec![];
+
+        for (index, spend) in self
+            .key_spends(aggregate_pk, unsigned_tx)
+            .iter()
+            .enumerate()
+        {
+            let commitments = input_nonces(&nonces, index);
+
+            let factors = frost::signing_factors(spend, self.our_peer_id, &commitments);
+
+            let signature = PartialSignature(
+                self.guardian_signer
+                    .partial_signature(
+                        BITCOIN_KEY_NAME,
+                        frost::session_id(&txid, session, index),
+                        factors.binding_factor,
+                        factors.negate_nonce,
+                        factors.key_factor,
+                    )
+                    .await?,
+            );
+
+            frost::verify(
+                spend,
+                &self.our_pk(),
+                self.our_peer_id,
+                &commitments,
+                &signature,
+            )
+            .context("Guardian signer returned an invalid partial signature")?;
+
+            signatures.push(signature);
+        }
+
+        Ok(Some(WalletConsensusItem::PartialSignatures(
+            txid, signatures,
+        )))
+    }
+
+    async fn restart_expired_signing_sessions(
+        &self,
+        dbtx: &mut DatabaseTransaction<'_>,
+        block_count: u64,
+    ) {
+        let expired = dbtx
+            .find_by_prefix(&SigningSessionPrefix)
+            .await
+            .filter(|(_, session)| {
+                std::future::ready(session.started + SIGNING_SESSION_TIMEOUT <= block_count)
+            })
+            .collect::<Vec<(SigningSessionKey, SigningSession)>>()
+            .await;
+
+        for (key, session) in expired {
+            info!(
+                target: LOG_MODULE_WALLETV2,
+                txid = %key.0,
+                session = session.index,
+                "Restarting expired signing session"
+            );
+
+            dbtx.remove_by_prefix(&NoncesTxidPrefix(key.0)).await;
+
+            dbtx.remove_by_prefix(&PartialSignaturesTxidPrefix(key.0))
+                .await;
+
+            dbtx.insert_entry(
+                &key,
+                &SigningSession {
+                    index: session.index + 1,
+                    started: block_count,
+                },
+            )
+            .await;
+        }
+    }
+
+    async fn await_local_sync_to_block_count(&self, block_count: u64) {
+        loop {
+            if self
+                .btc_rpc
+                .status()
+                .is_some_and(|status| status.block_count >= block_count)
+            {
+                break;
+            }
+
+            info!(target: LOG_MODULE_WALLETV2, "Waiting for local bitcoin backend to sync to block count {block_count}");
+
+            sleep(common::sleep_duration()).await;
+        }
+    }
+
+    pub async fn consensus_block_count(&self, dbtx: &mut DatabaseTransaction<'_>) -> u64 {
+        let num_peers = self.cfg.consensus.bitcoin_pks.to_num_peers();
+
+        let mut counts = dbtx
+            .find_by_prefix(&BlockCountVotePrefix)
+            .await
+            .map(|entry| entry.1)
+            .collect::<Vec<u64>>()
+            .await;
+
+        assert!(counts.len() <= num_peers.total());
+
+        counts.sort_unstable();
+
+        counts.reverse();
+
+        assert!(counts.last() <= counts.first());
+
+        // The block count we select guarantees that any threshold of correct peers can
+        // increase the consensus block count and any consensus block count has been
+        // confirmed by a threshold of peers.
+
+        counts.get(num_peers.threshold() - 1).copied().unwrap_or(0)
+    }
+
+    /// The peg-out batching of our config, once the consensus version that
+    /// introduced it has been activated
+    async fn pegout_batching(&self, dbtx: &mut DatabaseTransaction<'_>) -> Option<&PegOutBatching> {
+        if self.consensus_module_consensus_version(dbtx).await
+            < PEGOUT_BATCHING_MODULE_CONSENSUS_VERSION
+        {
+            return None;
+        }
+
+        self.cfg.consensus.pegout_batching.as_ref()
+    }
+
+    /// The highest consensus version at least a threshold of peers voted for,
+    /// peers that did not vote count as voting for the genesis version
+    async fn consensus_module_consensus_version(
+        &self,
+        dbtx: &mut DatabaseTransaction<'_>,
+    ) -> ModuleConsensusVersion {
+        let num_peers = self.cfg.consensus.bitcoin_pks.to_num_peers();
+
+        let mut versions = dbtx
+            .find_by_prefix(&ConsensusVersionVotePrefix)
+            .await
+            .map(|entry| entry.1)
+            .collect::<Vec<ModuleConsensusVersion>>()
+            .await;
+
+        while versions.len() < num_peers.total() {
+            versions.push(self.genesis_consensus_version);
+        }
+
+        versions.sort_unstable();
+
+        versions[num_peers.max_evil()]
+    }
+
+    pub async fn consensus_feerate(&self, dbtx: &mut DatabaseTransaction<'_>) -> Option<u64> {
+        let num_peers = self.cfg.consensus.bitcoin_pks.to_num_peers();
+
+        let mut rates = dbtx
+            .find_by_prefix(&FeeRateVotePrefix)
+            .await
+            .filter_map(|entry| async move { entry.1 })
+            .collect::<Vec<u64>>()
+            .await;
+
+        assert!(rates.len() <= num_peers.total());
+
+        rates.sort_unstable();
+
+        assert!(rates.first() <= rates.last());
+
+        rates.get(num_peers.threshold() - 1).copied()
+    }
+
+    pub async fn consensus_fee(
+        &self,
+        dbtx: &mut DatabaseTransaction<'_>,
+        tx_vbytes: u64,
+    ) -> Option<Amount> {
+        let pending_txs = pending_txs_unordered(dbtx).await;
+
+        self.stack_fee(dbtx, &pending_txs, tx_vbytes).await
+    }
+
+    /// The fee a transaction of the given size has to pay such that it and
+    /// the given pending transactions pay the consensus feerate.
+    async fn stack_fee(
+        &self,
+        dbtx: &mut DatabaseTransaction<'_>,
+        pending_txs: &[FederationTx],
+        tx_vbytes: u64,
+    ) -> Option<Amount> {
+        // The minimum feerate is a protection against a catastrophic error in the
+        // feerate estimation and limits the length of the pending transaction stack.
+
+        assert!(pending_txs.len() <= MAX_PENDING_TXS);
+
+        let feerate = self
+            .consensus_feerate(dbtx)
+            .await?
+            .max(self.cfg.consensus.feerate_base << pending_txs.len());
+
+        let tx_fee = tx_vbytes.saturating_mul(feerate).saturating_div(1000);
+
+        let stack_vbytes = pending_txs
+            .iter()
+            .map(|t| t.vbytes)
+            .try_fold(tx_vbytes, u64::checked_add)
+            .expect("Stack vbytes overflow with at most 32 pending txs");
+
+        let stack_fee = stack_vbytes.saturating_mul(feerate).saturating_div(1000);
+
+        // Deduct the fees already paid by currently pending transactions
+        let stack_fee = pending_txs
+            .iter()
+            .map(|t| t.fee.to_sat())
+            .fold(stack_fee, u64::saturating_sub);
+
+        Some(Amount::from_sat(tx_fee.max(stack_fee)))
+    }
+
+    /// If peg-outs are batched this is the fee of an unbatched peg-out, which
+    /// is only an upper bound. Once the batch is sent, the part of the fee that
+    /// is not needed for the peg-out's share of the batch transaction is added
+    /// to its output, see [`Self::batch_fee_shares`].
+    pub async fn send_fee(&self, dbtx: &mut DatabaseTransaction<'_>) -> Option<Amount> {
+        self.consensus_fee(dbtx, self.cfg.consensus.send_tx_vbytes)
+            .await
+    }
+
+    /// Splits the batch transaction equally among its peg-outs: every peg-out
+    /// pays for its own output plus an equal share of the remaining
+    /// transaction, at the feerate it has agreed to with its fee. Since the
+    /// fee of a peg-out covers an entire transaction at that feerate, the
+    /// returned shares never exceed the fees paid and the sum of the shares
+    /// covers the batch transaction at the lowest of those feerates.
+    fn batch_fee_shares(&self, fees: &[Amount]) -> Vec<Amount> {
+        let send_tx_vbytes = self.cfg.consensus.send_tx_vbytes;
+
+        let overhead = send_tx_vbytes - PEGOUT_OUTPUT_VBYTES;
+
+        let share_vbytes = PEGOUT_OUTPUT_VBYTES + overhead.div_ceil(fees.len() as u64);
+
+        fees.iter()
+            .map(|fee| Amount::from_sat((fee.to_sat() * share_vbytes).div_ceil(send_tx_vbytes)))
+            .collect()
+    }
+
+    async fn queued_pegouts(
+        &self,
+        dbtx: &mut DatabaseTransaction<'_>,
+    ) -> Vec<(OutPoint, QueuedPegOut)> {
+        dbtx.find_by_prefix(&QueuedPegOutPrefix)
+            .await
+            .map(|(key, pegout)| (key.0, pegout))
+            .collect()
+            .await
+    }
+
+    /// The value of all accepted peg-outs that are not part of a federation
+    /// transaction yet, including their fees.
+    async fn reserved_value(&self, dbtx: &mut DatabaseTransaction<'_>) -> Option<Amount> {
+        let queued = self
+            .queued_pegouts(dbtx)
+            .await
+            .into_iter()
+            .map(|(_, pegout)| (pegout.value, pegout.fee));
+
+        let pending = dbtx
+            .find_by_prefix(&PendingSilentPaymentPrefix)
+            .await
+            .map(|(_, payment)| (payment.value, payment.fee))
+            .collect::<Vec<(Amount, Amount)>>()
+            .await;
+
+        queued
+            .chain(pending)
+            .try_fold(Amount::ZERO, |sum, (value, fee)| {
+                sum.checked_add(value)?.checked_add(fee)
+            })
+    }
+
+    async fn close_pegout_batch(&self, dbtx: &mut DatabaseTransaction<'_>) {
+        dbtx.remove_entry(&PegOutBatchKey).await;
+
+        let pegouts = self.queued_pegouts(dbtx).await;
+
+        dbtx.remove_by_prefix(&QueuedPegOutPrefix).await;
+
+        if !pegouts.is_empty() {
+            self.send_pegouts(dbtx, pegouts).await;
+        }
+    }
+
+    /// Creates the federation transaction for the given peg-outs, which pays
+    /// the sum of their fee shares. The remainder of the fee of every peg-out
+    /// is added to its output.
+    async fn send_pegouts(
+        &self,
+        dbtx: &mut DatabaseTransaction<'_>,
+        pegouts: Vec<(OutPoint, QueuedPegOut)>,
+    ) {
+        let wallet = dbtx
+            .remove_entry(&FederationWalletKey)
+            .await
+            .expect("Peg-outs are only accepted with a federation wallet");
+
+        let fee_shares = self.batch_fee_shares(
+            &pegouts
+                .iter()
+                .map(|(_, pegout)| pegout.fee)
+                .collect::<Vec<Amount>>(),
+        );
+
+        let fee = fee_shares.iter().copied().sum::<Amount>();
+
+        let output_value = pegouts
+            .iter()
+            .map(|(_, pegout)| pegout.value + pegout.fee)
+            .sum::<Amount>();
+
+        let change_value = wallet
+            .value
+            .checked_sub(output_value)
+            .expect("Peg-outs are only accepted if they leave change above the dust limit");
+
+        let vbytes =
+            self.cfg.consensus.send_tx_vbytes + (pegouts.len() as u64 - 1) * PEGOUT_OUTPUT_VBYTES;
+
+        let change_output = TxOut {
+            value: change_value,
+            script_pubkey: self.descriptor(&wallet.consensus_hash()).script_pubkey(),
+        };
+
+        let tx = Transaction {
+            version: Version(2),
+            lock_time: LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: wallet.outpoint,
+                script_sig: Default::default(),
+                sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+                witness: bitcoin::Witness::new(),
+            }],
+            output: std::iter::once(change_output)
+                .chain(
+                    pegouts
+                        .iter()
+                        .zip(&fee_shares)
+                        .map(|((_, pegout), share)| TxOut {
+                            value: pegout.value + pegout.fee - *share,
+                            script_pubkey: pegout.script_pubkey.clone(),
+                        }),
+                )
+                .collect(),
+        };
+
+        dbtx.insert_new_entry(
+            &FederationWalletKey,
+            &FederationWallet {
+                value: change_value,
+                outpoint: bitcoin::OutPoint {
+                    txid: tx.compute_txid(),
+                    vout: 0,
+                },
+                tweak: wallet.consensus_hash(),
+            },
+        )
+        .await;
+
+        self.record_tweak(dbtx, wallet.consensus_hash()).await;
+
+        let tx_index = self.total_txs(dbtx).await;
+
+        let created = self.consensus_block_count(dbtx).await;
+
+        dbtx.insert_new_entry(
+            &TxInfoKey(tx_index),
+            &TxInfo {
+                index: tx_index,
+                txid: tx.compute_txid(),
+                input: wallet.value,
+                output: change_value,
+                vbytes,
+                fee,
+                created,
+            },
+        )
+        .await;
+
+        for (outpoint, _) in &pegouts {
+            dbtx.insert_new_entry(&TxInfoIndexKey(*outpoint), &tx_index)
+                .await;
+        }
+
+        dbtx.insert_new_entry(
+            &UnsignedTxKey(tx.compute_txid()),
+            &FederationTx {
+                tx,
+                spent_tx_outs: vec![SpentTxOut {
+                    value: wallet.value,
+                    tweak: wallet.tweak,
+                }],
+                vbytes,
+                fee,
+            },
+        )
+        .await;
+    }
+
+    async fn process_ecdh_share(
+        &self,
+        dbtx: &mut DatabaseTransaction<'_>,
+        outpoint: OutPoint,
+        share: EcdhShare,
+        peer: PeerId,
+    ) -> anyhow::Result<()> {
+        let payment = dbtx
+            .get_value(&PendingSilentPaymentKey(outpoint))
+            .await
+            .context("Silent payment is not pending")?;
+
+        let pk = self
+            .cfg
+            .consensus
+            .bitcoin_pks
+            .get(&peer)
+            .expect("Failed to get public key of peer from config");
+
+        share.verify(pk, &payment.address.scan)?;
+
+        if dbtx
+            .insert_entry(&EcdhShareKey(outpoint, peer), &share)
+            .await
+            .is_some()
+        {
+            bail!("Already received a valid ECDH share from this peer")
+        }
+
+        let shares = dbtx
+            .find_by_prefix(&EcdhShareOutPointPrefix(outpoint))
+            .await
+            .map(|(key, share)| (key.1, share))
+            .collect::<BTreeMap<PeerId, EcdhShare>>()
+            .await;
+
+        if shares.len() == self.cfg.consensus.bitcoin_pks.to_num_peers().threshold() {
+            dbtx.remove_by_prefix(&EcdhShareOutPointPrefix(outpoint))
+                .await;
+
+            dbtx.remove_entry(&PendingSilentPaymentKey(outpoint)).await;
+
+            self.send_silent_payment(dbtx, outpoint, payment, shares)
+                .await;
+        }
+
+        Ok(())
+    }
+
+    /// Derives the output key of a silent payment for the current federation
+    /// UTXO and sends it in a transaction of its own.
+    async fn send_silent_payment(
+        &self,
+        dbtx: &mut DatabaseTransaction<'_>,
+        outpoint: OutPoint,
+        payment: PendingSilentPayment,
+        shares: BTreeMap<PeerId, EcdhShare>,
+    ) {
+        let WalletDescriptor::Tr { aggregate_pk } = self.cfg.consensus.descriptor else {
+            panic!("Silent payments are only accepted with a taproot descriptor");
+        };
+
+        let wallet = dbtx
+            .get_value(&FederationWalletKey)
+            .await
+            .expect("Peg-outs are only accepted with a federation wallet");
+
+        let script_pubkey = silent_payments::script_pubkey(
+            &payment.address,
+            &shares,
+            &self.cfg.consensus.bitcoin_pks,
+            &aggregate_pk,
+            &wallet.outpoint,
+            &wallet.tweak,
+        );
+
+        dbtx.insert_new_entry(
+            &SilentPaymentProofKey(outpoint),
+            &SilentPaymentProof {
+                shares,
+                input: wallet.outpoint,
+                tweak: wallet.tweak,
+                script_pubkey: script_pubkey.clone(),
+            },
+        )
+        .await;
+
+        let pegout = QueuedPegOut {
+            script_pubkey,
+            value: payment.value,
+            fee: payment.fee,
+        };
+
+        self.send_pegouts(dbtx, vec![(outpoint, pegout)]).await;
+    }
+
+    async fn pegout_status(
+        &self,
+        dbtx: &mut DatabaseTransaction<'_>,
+        outpoint: OutPoint,
+    ) -> Option<PegOutStatus> {
+        if let Some(txid) = self.tx_id(dbtx, outpoint).await {
+            return Some(PegOutStatus::Sent(txid));
+        }
+
+        if dbtx
+            .get_value(&PendingSilentPaymentKey(outpoint))
+            .await
+            .is_some()
+        {
+            return Some(PegOutStatus::Deriving);
+        }
+
+        dbtx.get_value(&QueuedPegOutKey(outpoint)).await?;
+
+        let opened = dbtx.get_value(&PegOutBatchKey).await?;
+
+        let window = self.pegout_batching(dbtx).await?.window;
+
+        Some(PegOutStatus::Queued {
+            closes: opened + window,
+        })
+    }
+
+    pub async fn receive_fee(&self, dbtx: &mut DatabaseTransaction<'_>) -> Option<Amount> {
+        self.consensus_fee(dbtx, self.cfg.consensus.receive_tx_vbytes)
+            .await
+    }
+
+    async fn record_tweak(&self, dbtx: &mut DatabaseTransaction<'_>, tweak: sha256::Hash) {
+        if dbtx.get_value(&UsedTweakKey(tweak)).await.is_none() {
+            let block_count = self.consensus_block_count(dbtx).await;
+
+            dbtx.insert_new_entry(&UsedTweakKey(tweak), &block_count)
+                .await;
+        }
+    }
+
+    async fn watch_only_descriptors(
+        &self,
+        dbtx: &mut DatabaseTransaction<'_>,
+    ) -> WatchOnlyDescriptors {
+        let mut tweaked = dbtx
+            .find_by_prefix(&UsedTweakPrefix)
+            .await
+            .map(|(key, first_used)| TweakedDescriptor {
+                tweak: key.0,
+                descriptor: self.descriptor(&key.0).to_string(),
+                first_used,
+            })
+            .collect::<Vec<TweakedDescriptor>>()
+            .await;
+
+        tweaked.sort_by_key(|descriptor| descriptor.first_used);
+
+        let tweaked_scripts = dbtx
+            .find_by_prefix(&UsedTweakPrefix)
+            .await
+            .map(|(key, _)| self.descriptor(&key.0).script_pubkey())
+            .collect::<BTreeSet<ScriptBuf>>()
+            .await;
+
+        let outputs = dbtx
+            .find_by_prefix(&OutputPrefix)
+            .await
+            .collect::<Vec<(OutputKey, Output)>>()
+            .await;
+
+        let mut unclaimed = vec![];
+
+        // Our own change outputs may pass the receive filter as well, but are
+        // covered by their tweaked descriptor already.
+        for (key, Output(outpoint, tx_out)) in outputs {
+            if dbtx.get_value(&SpentOutputKey(key.0)).await.is_none()
+                && !tweaked_scripts.contains(&tx_out.script_pubkey)
+            {
+                unclaimed.push(UnclaimedDeposit::new(outpoint, &tx_out));
+            }
+        }
+
+        WatchOnlyDescriptors { tweaked, unclaimed }
+    }
+
+    fn descriptor(&self, tweak: &sha256::Hash) -> Descriptor<secp256k1::PublicKey> {
+        self.cfg
+            .consensus
+            .descriptor
+            .descriptor(&self.cfg.consensus.bitcoin_pks, tweak)
+    }
+
+    fn key_spends(
+        &self,
+        aggregate_pk: &PublicKey,
+        unsigned_tx: &FederationTx,
+    ) -> Vec<frost::KeySpend> {
+        let prevouts = unsigned_tx
+            .spent_tx_outs
+            .iter()
+            .map(|utxo| TxOut {
+                value: utxo.value,
+                script_pubkey: self.descriptor(&utxo.tweak).script_pubkey(),
+            })
+            .collect::<Vec<TxOut>>();
+
+        let mut sighash_cache = SighashCache::new(unsigned_tx.tx.clone());
+
+        unsigned_tx
+            .spent_tx_outs
+            .iter()
+            .enumerate()
+            .map(|(index, utxo)| {
+                let sighash = sighash_cache
+                    .taproot_key_spend_signature_hash(
+                        index,
+                        &Prevouts::All(&prevouts),
+                        TapSighashType::Default,
+                    )
+                    .expect("Failed to compute taproot key spend sighash");
+
+                let merkle_root =
+                    tr_descriptor(&self.cfg.consensus.bitcoin_pks, aggregate_pk, &utxo.tweak)
+                        .spend_info()
+                        .merkle_root();
+
+                frost::KeySpend::new(
+                    aggregate_pk,
+                    &utxo.tweak,
+                    merkle_root,
+                    sighash.to_byte_array(),
+                )
+            })
+            .collect()
+    }
+
+    /// Signs every input with our key share tweaked by the spent output and
+    /// verifies the signatures returned by the guardian signer
+    async fn sign_tx(&self, unsigned_tx: &FederationTx) -> anyhow::Result<Vec<Signature>> {
+        let mut sighash_cache = SighashCache::new(unsigned_tx.tx.clone());
+
+        let mut signatures = vec![];
+
+        for (index, utxo) in unsigned_tx.spent_tx_outs.iter().enumerate() {
+            let descriptor = descriptor(&self.cfg.consensus.bitcoin_pks, &utxo.tweak)
+                .ecdsa_sighash_script_code();
+
+            let p2wsh_sighash = sighash_cache
+                .p2wsh_signature_hash(index, &descriptor, utxo.value, EcdsaSighashType::All)
+                .expect("Failed to compute P2WSH segwit sighash");
+
+            signatures.push(
+                self.guardian_signer
+                    .sign_ecdsa(
+                        BITCOIN_KEY_NAME,
+                        Some(utxo.tweak.to_byte_array()),
+                        p2wsh_sighash.to_byte_array(),
+                    )
+                    .await?,
+            );
+        }
+
+        self.verify_signatures(unsigned_tx, &signatures, self.our_pk())
+            .context("Guardian signer returned an invalid signature")?;
+
+        Ok(signatures)
+    }
+
+    fn verify_signatures(
+        &self,
+        unsigned_tx: &FederationTx,
+        signatures: &[Signature],
+        pk: PublicKey,
+    ) -> anyhow::Result<()> {
         ensure!(
             unsigned_tx.spent_tx_outs.len() == signatures.len(),
             "Incorrect number of signatures"
@@ -1244,7 +3026,8 @@ fn verify_signatures(
             .enumerate()
             .zip(signatures.iter())
         {
-            let code = self.descriptor(&utxo.tweak).ecdsa_sighash_script_code();
+            let code = descriptor(&self.cfg.consensus.bitcoin_pks, &utxo.tweak)
+                .ecdsa_sighash_script_code();
 
             let p2wsh_sighash = sighash_cache
                 .p2wsh_signature_hash(index, &code, utxo.value, EcdsaSighashType::All)
@@ -1287,7 +3070,7 @@ fn finalize_tx(
                 })
                 .collect();
 
-            miniscript::Descriptor::Wsh(self.descriptor(&utxo.tweak))
+            self.descriptor(&utxo.tweak)
                 .satisfy(&mut federation_tx.tx.input[index], satisfier)
                 .expect("Failed to satisfy descriptor");
         }
@@ -1314,14 +3097,22 @@ async fn get_outputs(
             .collect()
             .await;
 
+        let is_federation_script = |script: &bitcoin::ScriptBuf| match self.cfg.consensus.descriptor
+        {
+            WalletDescriptor::Wsh => script.is_p2wsh(),
+            WalletDescriptor::Tr { .. } => script.is_p2tr(),
+        };
+
         dbtx.find_by_range(OutputKey(start_index)..OutputKey(end_index))
             .await
             .filter_map(|entry| {
-                std::future::ready(entry.1.1.script_pubkey.is_p2wsh().then(|| OutputInfo {
-                    index: entry.0.0,
-                    script: entry.1.1.script_pubkey,
-                    value: entry.1.1.value,
-                    spent: spent.contains(&entry.0.0),
+                std::future::ready(is_federation_script(&entry.1.1.script_pubkey).then(|| {
+                    OutputInfo {
+                        index: entry.0.0,
+                        script: entry.1.1.script_pubkey,
+                        value: entry.1.1.value,
+                        spent: spent.contains(&entry.0.0),
+                    }
                 }))
             })
             .collect()
@@ -1407,7 +3198,8 @@ pub async fn tx_chain_ui(&self) -> Vec<TxInfo> {
     }
 
     /// Export recovery keys for federation shutdown. Returns None if the
-    /// federation wallet has not been initialized yet.
+    /// federation wallet has not been initialized yet or our key share has been
+    /// exported to a remote guardian signer.
     pub async fn recovery_keys_ui(&self) -> Option<(BTreeMap<PeerId, String>, String)> {
         let wallet = self.federation_wallet_ui().await?;
 
@@ -1425,7 +3217,7 @@ pub async fn recovery_keys_ui(&self) -> Option<(BTreeMap<PeerId, String>, String
         let sk = self
             .cfg
             .private
-            .bitcoin_sk
+            .bitcoin_sk?
             .add_tweak(tweak)
             .expect("Failed to tweak bitcoin secret key");
 
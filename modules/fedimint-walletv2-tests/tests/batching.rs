@@ -0,0 +1,296 @@
+use std::pin::pin;
+use std::sync::{Arc, Once};
+use std::time::Duration;
+
+use async_stream::stream;
+use bitcoin::Amount;
+use fedimint_client::ClientHandleArc;
+use fedimint_core::envs::{
+    FM_WALLETV2_PEGOUT_BATCH_MAX_PEGOUTS_ENV, FM_WALLETV2_PEGOUT_BATCH_WINDOW_ENV,
+};
+use fedimint_core::task::sleep_in_test;
+use fedimint_dummy_client::DummyClientInit;
+use fedimint_dummy_server::DummyInit;
+use fedimint_eventlog::{Event, EventLogId};
+use fedimint_testing::btc::BitcoinTest;
+use fedimint_testing::fixtures::Fixtures;
+use fedimint_walletv2_client::events::{SendPaymentStatus, SendPaymentUpdateEvent};
+use fedimint_walletv2_client::{FinalSendOperationState, WalletClientInit, WalletClientModule};
+use fedimint_walletv2_common::KIND;
+use fedimint_walletv2_server::{CONFIRMATION_FINALITY_DELAY, WalletInit};
+use futures::StreamExt;
+use tracing::info;
+
+/// Number of consensus blocks a batch collects peg-outs
+const BATCH_WINDOW: u64 = 2;
+
+/// Maximum number of peg-outs in a batch
+const MAX_PEGOUTS: usize = 3;
+
+const PEGOUT_VALUE: Amount = Amount::from_sat(10_000);
+
+fn fixtures() -> Fixtures {
+    static BATCHING: Once = Once::new();
+
+    // The config is generated from the environment, hence every test of this
+    // binary batches peg-outs with the same parameters.
+    BATCHING.call_once(|| unsafe {
+        std::env::set_var(
+            FM_WALLETV2_PEGOUT_BATCH_WINDOW_ENV,
+            BATCH_WINDOW.to_string(),
+        );
+        std::env::set_var(
+            FM_WALLETV2_PEGOUT_BATCH_MAX_PEGOUTS_ENV,
+            MAX_PEGOUTS.to_string(),
+        );
+    });
+
+    Fixtures::new_primary(DummyClientInit, DummyInit).with_module(WalletClientInit, WalletInit)
+}
+
+fn send_status_stream(client: &ClientHandleArc) -> impl futures::Stream<Item = SendPaymentStatus> {
+    let client = client.clone();
+    let mut log_rx = client.log_event_added_rx();
+    let mut next_id = EventLogId::LOG_START;
+
+    stream! {
+        loop {
+            let events = client.get_event_log(Some(next_id), 100).await;
+
+            for entry in events {
+                next_id = entry.id().saturating_add(1);
+
+                let entry = entry.as_raw();
+
+                if entry.module_kind() == Some(&KIND)
+                    && entry.kind == SendPaymentUpdateEvent::KIND
+                    && let Some(event) = entry.to_event::<SendPaymentUpdateEvent>()
+                {
+                    yield event.status;
+                }
+            }
+
+            let _ = log_rx.changed().await;
+        }
+    }
+}
+
+async fn await_consensus_block_count(
+    client: &ClientHandleArc,
+    block_count: u64,
+) -> anyhow::Result<()> {
+    loop {
+        if client
+            .get_first_module::<WalletClientModule>()?
+            .block_count()
+            .await?
+            >= block_count
+        {
+            return Ok(());
+        }
+
+        sleep_in_test(
+            format!("Waiting for consensus to reach block count {block_count}"),
+            Duration::from_secs(1),
+        )
+        .await;
+    }
+}
+
+async fn await_mempool_tx(bitcoin: &Arc<dyn BitcoinTest>, txid: &bitcoin::Txid) {
+    while bitcoin.get_mempool_tx(txid).await.is_none() {
+        sleep_in_test(
+            format!("Waiting for transaction {txid} to enter the mempool"),
+            Duration::from_millis(100),
+        )
+        .await;
+    }
+}
+
+/// Deposits one bitcoin into the federation and waits for the consensus block
+/// count to catch up with the chain tip, such that it only advances once we
+/// mine further blocks.
+async fn deposit(client: &ClientHandleArc, bitcoin: &Arc<dyn BitcoinTest>) -> anyhow::Result<()> {
+    bitcoin.mine_blocks(1 + CONFIRMATION_FINALITY_DELAY).await;
+
+    await_consensus_block_count(client, 1).await?;
+
+    info!("Deposit funds into the federation...");
+
+    let federation_address = client
+        .get_first_module::<WalletClientModule>()?
+        .receive()
+        .await;
+
+    bitcoin
+        .send_and_mine_block(&federation_address, Amount::from_int_btc(1))
+        .await;
+
+    bitcoin.mine_blocks(CONFIRMATION_FINALITY_DELAY).await;
+
+    let block_count = bitcoin.get_block_count().await;
+
+    await_consensus_block_count(client, block_count - CONFIRMATION_FINALITY_DELAY).await?;
+
+    loop {
+        let total_value = client
+            .get_first_module::<WalletClientModule>()?
+            .total_value()
+            .await?;
+
+        if total_value >= Amount::from_sat(90_000_000) {
+            return Ok(());
+        }
+
+        sleep_in_test(
+            "Waiting for the deposit to be claimed",
+            Duration::from_secs(1),
+        )
+        .await;
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn full_batch_is_sent_in_a_single_transaction() -> anyhow::Result<()> {
+    let fixtures = fixtures();
+
+    let fed = fixtures.new_fed_not_degraded().await;
+
+    let client = fed.new_client().await;
+
+    let bitcoin = fixtures.bitcoin();
+
+    deposit(&client, &bitcoin).await?;
+
+    let module = client.get_first_module::<WalletClientModule>()?;
+
+    // No blocks are mined while we send, hence the batch is only closed once
+    // it is full and all peg-outs pay the same fee.
+    let fee = module.send_fee().await?;
+
+    let mut addresses = vec![];
+    let mut operations = vec![];
+
+    for _ in 0..MAX_PEGOUTS {
+        let address = bitcoin.get_new_address().await;
+
+        operations.push(
+            module
+                .send(address.as_unchecked().clone(), PEGOUT_VALUE, Some(fee))
+                .await?,
+        );
+
+        addresses.push(address);
+    }
+
+    let mut txids = vec![];
+
+    for operation in operations {
+        let FinalSendOperationState::Success(txid) =
+            module.await_final_send_operation_state(operation).await
+        else {
+            panic!("Peg-out failed");
+        };
+
+        txids.push(txid);
+    }
+
+    assert!(txids.iter().all(|txid| *txid == txids[0]));
+
+    await_mempool_tx(&bitcoin, &txids[0]).await;
+
+    let tx = bitcoin
+        .get_mempool_tx(&txids[0])
+        .await
+        .expect("Transaction is in the mempool");
+
+    // The change output and one output per peg-out
+    assert_eq!(tx.output.len(), MAX_PEGOUTS + 1);
+
+    let pegout_values = addresses
+        .iter()
+        .map(|address| {
+            tx.output
+                .iter()
+                .find(|output| output.script_pubkey == address.script_pubkey())
+                .expect("Batch transaction pays every peg-out")
+                .value
+        })
+        .collect::<Vec<Amount>>();
+
+    // Every peg-out pays an equal share of the batch transaction and receives
+    // the remainder of its fee on-chain
+    assert!(pegout_values.iter().all(|value| *value == pegout_values[0]));
+
+    let rebate = pegout_values[0] - PEGOUT_VALUE;
+
+    assert!(Amount::ZERO < rebate && rebate < fee);
+
+    assert_eq!(
+        bitcoin.get_mempool_tx_fee(&txids[0]).await,
+        (fee - rebate) * MAX_PEGOUTS as u64
+    );
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn batch_is_sent_once_its_window_has_passed() -> anyhow::Result<()> {
+    let fixtures = fixtures();
+
+    let fed = fixtures.new_fed_not_degraded().await;
+
+    let client = fed.new_client().await;
+
+    let bitcoin = fixtures.bitcoin();
+
+    deposit(&client, &bitcoin).await?;
+
+    let module = client.get_first_module::<WalletClientModule>()?;
+
+    let mut statuses = pin!(send_status_stream(&client));
+
+    let address = bitcoin.get_new_address().await;
+
+    let operation = module
+        .send(address.as_unchecked().clone(), PEGOUT_VALUE, None)
+        .await?;
+
+    assert_eq!(statuses.next().await, Some(SendPaymentStatus::Queued));
+
+    info!("Mine blocks until the batch window has passed...");
+
+    let block_count = module.block_count().await?;
+
+    bitcoin.mine_blocks(BATCH_WINDOW).await;
+
+    await_consensus_block_count(&client, block_count + BATCH_WINDOW).await?;
+
+    let FinalSendOperationState::Success(txid) =
+        module.await_final_send_operation_state(operation).await
+    else {
+        panic!("Peg-out failed");
+    };
+
+    assert_eq!(
+        statuses.next().await,
+        Some(SendPaymentStatus::Success(txid))
+    );
+
+    await_mempool_tx(&bitcoin, &txid).await;
+
+    let tx = bitcoin
+        .get_mempool_tx(&txid)
+        .await
+        .expect("Transaction is in the mempool");
+
+    // A batch of a single peg-out pays the entire fee of an unbatched peg-out
+    assert!(
+        tx.output
+            .iter()
+            .any(|output| output.script_pubkey == address.script_pubkey()
+                && output.value == PEGOUT_VALUE)
+    );
+
+    Ok(())
+}
@@ -4,7 +4,12 @@
 
 use async_stream::stream;
 use bitcoin::Amount;
+use fedimint_api_client::api::FederationApiExt as _;
 use fedimint_client::ClientHandleArc;
+use fedimint_client::transaction::{ClientInput, ClientInputBundle, TransactionBuilder};
+use fedimint_core::core::{IntoDynInstance as _, OperationId};
+use fedimint_core::module::{Amounts, ApiRequestErased};
+use fedimint_core::secp256k1::{self, Keypair};
 use fedimint_core::task::sleep_in_test;
 use fedimint_dummy_client::DummyClientInit;
 use fedimint_dummy_server::DummyInit;
@@ -16,8 +21,12 @@
     SendPaymentUpdateEvent,
 };
 use fedimint_walletv2_client::{FinalSendOperationState, WalletClientInit, WalletClientModule};
-use fedimint_walletv2_common::KIND;
-use fedimint_walletv2_server::{CONFIRMATION_FINALITY_DELAY, WalletInit};
+use fedimint_walletv2_common::config::DEFAULT_FEE_BUMP_DELAY;
+use fedimint_walletv2_common::endpoint_constants::{
+    OUTPUT_INFO_SLICE_ENDPOINT, RECEIVE_FEE_ENDPOINT,
+};
+use fedimint_walletv2_common::{KIND, OutputInfo, TxInfo, WalletInput, WalletInputV0};
+use fedimint_walletv2_server::{CONFIRMATION_FINALITY_DELAY, MAX_FEE_BUMP_TXS, WalletInit};
 use futures::StreamExt;
 use tracing::info;
 
@@ -130,6 +139,93 @@ async fn await_consensus_block_count(
     }
 }
 
+// Mines enough blocks for the transactions in the mempool to pass the finality
+// delay and waits for the consensus to catch up with the chain tip.
+async fn mine_past_finality_delay(
+    client: &ClientHandleArc,
+    bitcoin: &Arc<dyn BitcoinTest>,
+) -> anyhow::Result<()> {
+    bitcoin.mine_blocks(CONFIRMATION_FINALITY_DELAY + 1).await;
+
+    let block_count = bitcoin.get_block_count().await;
+
+    await_consensus_block_count(client, block_count - CONFIRMATION_FINALITY_DELAY).await
+}
+
+async fn await_mempool_tx(bitcoin: &Arc<dyn BitcoinTest>, txid: &bitcoin::Txid) {
+    while bitcoin.get_mempool_tx(txid).await.is_none() {
+        sleep_in_test(
+            format!("Waiting for transaction {txid} to enter the mempool"),
+            Duration::from_millis(100),
+        )
+        .await;
+    }
+}
+
+async fn send_and_await_mempool_tx(
+    client: &ClientHandleArc,
+    bitcoin: &Arc<dyn BitcoinTest>,
+    address: &bitcoin::Address<bitcoin::address::NetworkUnchecked>,
+) -> anyhow::Result<bitcoin::Txid> {
+    let send_op = client
+        .get_first_module::<WalletClientModule>()?
+        .send(address.clone(), Amount::from_sat(10_000), None)
+        .await?;
+
+    let FinalSendOperationState::Success(txid) = client
+        .get_first_module::<WalletClientModule>()?
+        .await_final_send_operation_state(send_op)
+        .await
+    else {
+        panic!("Expected send to succeed");
+    };
+
+    await_mempool_tx(bitcoin, &txid).await;
+
+    Ok(txid)
+}
+
+// Mines blocks without confirming the pending transactions until the fee bump
+// delay has passed and waits for the federation to broadcast a new most recent
+// pending transaction, returning the pending transaction chain.
+async fn await_fee_bump(
+    client: &ClientHandleArc,
+    bitcoin: &Arc<dyn BitcoinTest>,
+) -> anyhow::Result<Vec<TxInfo>> {
+    let newest = client
+        .get_first_module::<WalletClientModule>()?
+        .pending_tx_chain()
+        .await?
+        .first()
+        .map(|tx| tx.txid);
+
+    bitcoin.mine_empty_blocks(DEFAULT_FEE_BUMP_DELAY).await;
+
+    let block_count = bitcoin.get_block_count().await;
+
+    await_consensus_block_count(client, block_count - CONFIRMATION_FINALITY_DELAY).await?;
+
+    loop {
+        let chain = client
+            .get_first_module::<WalletClientModule>()?
+            .pending_tx_chain()
+            .await?;
+
+        if let Some(bump) = chain.first()
+            && Some(bump.txid) != newest
+            && bitcoin.get_mempool_tx(&bump.txid).await.is_some()
+        {
+            return Ok(chain);
+        }
+
+        sleep_in_test(
+            "Waiting for the federation to bump the fee",
+            Duration::from_secs(1),
+        )
+        .await;
+    }
+}
+
 async fn await_federation_total_value(
     client: &ClientHandleArc,
     min_value: bitcoin::Amount,
@@ -230,3 +326,482 @@ async fn fee_exceeds_one_bitcoin_with_many_pending_txs() -> anyhow::Result<()> {
 
     panic!("Transaction fee did not exceed one bitcoin")
 }
+
+#[tokio::test(flavor = "multi_thread")]
+async fn watch_only_descriptors_track_deposits() -> anyhow::Result<()> {
+    let fixtures = fixtures();
+
+    let fed = fixtures.new_fed_not_degraded().await;
+
+    let client = fed.new_client().await;
+
+    let bitcoin = fixtures.bitcoin();
+
+    initialize_consensus(&client, &bitcoin).await?;
+
+    for deposits in 1..=2 {
+        info!("Deposit funds into the federation...");
+
+        let federation_address = client
+            .get_first_module::<WalletClientModule>()?
+            .receive()
+            .await;
+
+        bitcoin
+            .send_and_mine_block(&federation_address, Amount::from_int_btc(1))
+            .await;
+
+        await_finality_delay(&client, &bitcoin).await?;
+
+        await_federation_total_value(&client, Amount::from_sat(deposits * 90_000_000)).await?;
+    }
+
+    let descriptors = client
+        .get_first_module::<WalletClientModule>()?
+        .watch_only_descriptors()
+        .await?;
+
+    // Both deposits and the change output of the consolidating transaction
+    assert_eq!(descriptors.tweaked.len(), 3);
+
+    // Both deposits have been claimed by the client
+    assert!(descriptors.unclaimed.is_empty());
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn deposit_reorged_out_before_finality_is_claimed_once() -> anyhow::Result<()> {
+    let fixtures = fixtures();
+
+    let fed = fixtures.new_fed_not_degraded().await;
+
+    let client = fed.new_client().await;
+
+    // We invalidate blocks, so no other test may use the bitcoin node meanwhile
+    let bitcoin: Arc<dyn BitcoinTest> = Arc::from(fixtures.bitcoin().lock_exclusive().await);
+
+    initialize_consensus(&client, &bitcoin).await?;
+
+    info!("Deposit funds into the federation...");
+
+    let federation_address = client
+        .get_first_module::<WalletClientModule>()?
+        .receive()
+        .await;
+
+    let (_, deposit) = bitcoin
+        .send_and_mine_block(&federation_address, Amount::from_int_btc(1))
+        .await;
+
+    let deposit_txid = deposit.compute_txid();
+
+    info!("Reorg the deposit out of the chain...");
+
+    bitcoin.invalidate_blocks(1).await;
+
+    assert_eq!(bitcoin.get_tx_block_height(&deposit_txid).await, None);
+    assert!(bitcoin.get_mempool_tx(&deposit_txid).await.is_some());
+
+    info!("Confirm the deposit again...");
+
+    mine_past_finality_delay(&client, &bitcoin).await?;
+
+    assert!(bitcoin.get_tx_block_height(&deposit_txid).await.is_some());
+
+    await_federation_total_value(&client, Amount::from_sat(90_000_000)).await?;
+
+    // The deposit was confirmed twice but is only tracked and claimed once
+    let wallet_module = client.get_first_module::<WalletClientModule>()?;
+
+    let module_api = client.api().with_module(wallet_module.id);
+
+    let outputs = module_api
+        .request_current_consensus::<Vec<OutputInfo>>(
+            OUTPUT_INFO_SLICE_ENDPOINT.to_string(),
+            ApiRequestErased::new((0_u64, u64::MAX)),
+        )
+        .await?
+        .into_iter()
+        .filter(|output| output.script == federation_address.script_pubkey())
+        .collect::<Vec<_>>();
+
+    assert_eq!(outputs.len(), 1);
+    assert!(outputs[0].spent);
+
+    // The first deposit into the federation becomes its wallet without a fee
+    assert_eq!(wallet_module.total_value().await?, Amount::from_int_btc(1));
+
+    info!("Claim the deposit a second time...");
+
+    let receive_fee = module_api
+        .request_current_consensus::<Option<Amount>>(
+            RECEIVE_FEE_ENDPOINT.to_string(),
+            ApiRequestErased::new(()),
+        )
+        .await?
+        .expect("The consensus feerate is available");
+
+    // A random tweak suffices as the federation rejects the claim of a spent
+    // output before checking its tweak
+    let keypair = Keypair::new(secp256k1::SECP256K1, &mut rand::thread_rng());
+
+    let client_input = ClientInput::<WalletInput> {
+        input: WalletInput::V0(WalletInputV0 {
+            output_index: outputs[0].index,
+            tweak: keypair.public_key(),
+            fee: receive_fee,
+        }),
+        amounts: Amounts::new_bitcoin(fedimint_core::Amount::from_sats(
+            (outputs[0].value - receive_fee).to_sat(),
+        )),
+        keys: vec![keypair],
+    };
+
+    let operation_id = OperationId::new_random();
+
+    let txid = client
+        .finalize_and_submit_transaction(
+            operation_id,
+            "Claiming a claimed deposit",
+            |_| (),
+            TransactionBuilder::new().with_inputs(
+                ClientInputBundle::new_no_sm(vec![client_input]).into_dyn(wallet_module.id),
+            ),
+        )
+        .await?
+        .txid();
+
+    let error = client
+        .transaction_updates(operation_id)
+        .await
+        .await_tx_accepted(txid)
+        .await
+        .expect_err("Second claim of the deposit should be rejected");
+
+    assert!(error.contains("already been claimed"), "{error}");
+
+    assert_eq!(wallet_module.total_value().await?, Amount::from_int_btc(1));
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn consensus_block_count_does_not_go_backwards_on_reorg() -> anyhow::Result<()> {
+    let fixtures = fixtures();
+
+    let fed = fixtures.new_fed_not_degraded().await;
+
+    let client = fed.new_client().await;
+
+    // We invalidate blocks, so no other test may use the bitcoin node meanwhile
+    let bitcoin: Arc<dyn BitcoinTest> = Arc::from(fixtures.bitcoin().lock_exclusive().await);
+
+    initialize_consensus(&client, &bitcoin).await?;
+
+    // A reorg deeper than the finality delay would replace blocks that the
+    // federation has already processed, which we do not support.
+    let reorg_depth = CONFIRMATION_FINALITY_DELAY - 1;
+
+    bitcoin.mine_blocks(reorg_depth).await;
+
+    let block_count = bitcoin.get_block_count().await;
+
+    await_consensus_block_count(&client, block_count - CONFIRMATION_FINALITY_DELAY).await?;
+
+    let consensus_block_count = client
+        .get_first_module::<WalletClientModule>()?
+        .block_count()
+        .await?;
+
+    info!("Invalidate the last {reorg_depth} blocks...");
+
+    bitcoin.invalidate_blocks(reorg_depth).await;
+
+    assert_eq!(bitcoin.get_block_count().await, block_count - reorg_depth);
+
+    for _ in 0..5 {
+        sleep_in_test(
+            "Waiting for the guardians to observe the shorter chain",
+            Duration::from_secs(1),
+        )
+        .await;
+
+        assert_eq!(
+            client
+                .get_first_module::<WalletClientModule>()?
+                .block_count()
+                .await?,
+            consensus_block_count
+        );
+    }
+
+    info!("Mine a longer chain replacing the invalidated blocks...");
+
+    bitcoin.mine_blocks(reorg_depth + 1).await;
+
+    await_consensus_block_count(&client, consensus_block_count + 1).await?;
+
+    info!("Deposit funds into the federation after the reorg...");
+
+    let federation_address = client
+        .get_first_module::<WalletClientModule>()?
+        .receive()
+        .await;
+
+    bitcoin
+        .send_and_mine_block(&federation_address, Amount::from_int_btc(1))
+        .await;
+
+    mine_past_finality_delay(&client, &bitcoin).await?;
+
+    await_federation_total_value(&client, Amount::from_sat(90_000_000)).await?;
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn send_reorged_out_of_the_chain_is_confirmed_again() -> anyhow::Result<()> {
+    let fixtures = fixtures();
+
+    let fed = fixtures.new_fed_not_degraded().await;
+
+    let client = fed.new_client().await;
+
+    // We invalidate blocks, so no other test may use the bitcoin node meanwhile
+    let bitcoin: Arc<dyn BitcoinTest> = Arc::from(fixtures.bitcoin().lock_exclusive().await);
+
+    initialize_consensus(&client, &bitcoin).await?;
+
+    info!("Deposit funds into the federation...");
+
+    let federation_address = client
+        .get_first_module::<WalletClientModule>()?
+        .receive()
+        .await;
+
+    bitcoin
+        .send_and_mine_block(&federation_address, Amount::from_int_btc(1))
+        .await;
+
+    await_finality_delay(&client, &bitcoin).await?;
+
+    await_federation_total_value(&client, Amount::from_sat(90_000_000)).await?;
+
+    let address = bitcoin.get_new_address().await.as_unchecked().clone();
+
+    for _ in 0..2 {
+        let send_op = client
+            .get_first_module::<WalletClientModule>()?
+            .send(address.clone(), Amount::from_sat(10_000), None)
+            .await?;
+
+        let FinalSendOperationState::Success(txid) = client
+            .get_first_module::<WalletClientModule>()?
+            .await_final_send_operation_state(send_op)
+            .await
+        else {
+            panic!("Expected send to succeed");
+        };
+
+        await_mempool_tx(&bitcoin, &txid).await;
+
+        bitcoin.mine_blocks(1).await;
+
+        assert!(bitcoin.get_tx_block_height(&txid).await.is_some());
+
+        info!("Reorg the send transaction out of the chain...");
+
+        bitcoin.invalidate_blocks(1).await;
+
+        assert_eq!(bitcoin.get_tx_block_height(&txid).await, None);
+
+        await_mempool_tx(&bitcoin, &txid).await;
+
+        info!("Confirm the send transaction again...");
+
+        mine_past_finality_delay(&client, &bitcoin).await?;
+
+        assert!(bitcoin.get_tx_block_height(&txid).await.is_some());
+    }
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn stuck_fee_bump_is_replaced_instead_of_chained() -> anyhow::Result<()> {
+    let fixtures = fixtures();
+
+    let fed = fixtures.new_fed_not_degraded().await;
+
+    let client = fed.new_client().await;
+
+    // We mine blocks without the pending transactions, so no other test may
+    // use the bitcoin node meanwhile
+    let bitcoin: Arc<dyn BitcoinTest> = Arc::from(fixtures.bitcoin().lock_exclusive().await);
+
+    initialize_consensus(&client, &bitcoin).await?;
+
+    info!("Deposit funds into the federation...");
+
+    let federation_address = client
+        .get_first_module::<WalletClientModule>()?
+        .receive()
+        .await;
+
+    bitcoin
+        .send_and_mine_block(&federation_address, Amount::from_int_btc(1))
+        .await;
+
+    await_finality_delay(&client, &bitcoin).await?;
+
+    await_federation_total_value(&client, Amount::from_sat(90_000_000)).await?;
+
+    let address = bitcoin.get_new_address().await.as_unchecked().clone();
+
+    // With four pending transactions the minimum feerate of the stack exceeds
+    // the feerate they have paid, hence they are considered stuck.
+    for _ in 0..4 {
+        send_and_await_mempool_tx(&client, &bitcoin, &address).await?;
+    }
+
+    info!("Wait for the federation to bump the fee with a child transaction...");
+
+    let chain = await_fee_bump(&client, &bitcoin).await?;
+
+    assert_eq!(chain.len(), 5);
+
+    let bump = chain[0].clone();
+
+    info!("Wait for the federation to replace the fee bump...");
+
+    let chain = await_fee_bump(&client, &bitcoin).await?;
+
+    assert_eq!(chain.len(), 5);
+
+    let replacement = chain[0].clone();
+
+    assert_eq!(replacement.index, bump.index);
+    assert_eq!(replacement.input, bump.input);
+    assert!(replacement.fee >= bump.fee + Amount::from_sat(bump.vbytes));
+    assert!(bitcoin.get_mempool_tx(&bump.txid).await.is_none());
+
+    info!("Confirm the pending transactions...");
+
+    mine_past_finality_delay(&client, &bitcoin).await?;
+
+    assert!(
+        bitcoin
+            .get_tx_block_height(&replacement.txid)
+            .await
+            .is_some()
+    );
+    assert_eq!(bitcoin.get_tx_block_height(&bump.txid).await, None);
+
+    assert!(
+        client
+            .get_first_module::<WalletClientModule>()?
+            .pending_tx_chain()
+            .await?
+            .is_empty()
+    );
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn fee_bump_txs_are_capped() -> anyhow::Result<()> {
+    let fixtures = fixtures();
+
+    let fed = fixtures.new_fed_not_degraded().await;
+
+    let client = fed.new_client().await;
+
+    // We mine blocks without the pending transactions, so no other test may
+    // use the bitcoin node meanwhile
+    let bitcoin: Arc<dyn BitcoinTest> = Arc::from(fixtures.bitcoin().lock_exclusive().await);
+
+    initialize_consensus(&client, &bitcoin).await?;
+
+    info!("Deposit funds into the federation...");
+
+    let federation_address = client
+        .get_first_module::<WalletClientModule>()?
+        .receive()
+        .await;
+
+    bitcoin
+        .send_and_mine_block(&federation_address, Amount::from_int_btc(1))
+        .await;
+
+    await_finality_delay(&client, &bitcoin).await?;
+
+    await_federation_total_value(&client, Amount::from_sat(90_000_000)).await?;
+
+    let address = bitcoin.get_new_address().await.as_unchecked().clone();
+
+    for _ in 0..4 {
+        send_and_await_mempool_tx(&client, &bitcoin, &address).await?;
+    }
+
+    // A peg-out on top of every fee bump prevents its replacement, such that
+    // every fee bump adds a child transaction to the pending stack.
+    for _ in 0..MAX_FEE_BUMP_TXS {
+        let pending = client
+            .get_first_module::<WalletClientModule>()?
+            .pending_tx_chain()
+            .await?
+            .len();
+
+        info!("Wait for the federation to bump the fee with a child transaction...");
+
+        assert_eq!(await_fee_bump(&client, &bitcoin).await?.len(), pending + 1);
+
+        send_and_await_mempool_tx(&client, &bitcoin, &address).await?;
+    }
+
+    let pending = client
+        .get_first_module::<WalletClientModule>()?
+        .pending_tx_chain()
+        .await?
+        .len();
+
+    info!("Pass the fee bump delay once more...");
+
+    bitcoin.mine_empty_blocks(DEFAULT_FEE_BUMP_DELAY).await;
+
+    let block_count = bitcoin.get_block_count().await;
+
+    await_consensus_block_count(&client, block_count - CONFIRMATION_FINALITY_DELAY).await?;
+
+    for _ in 0..5 {
+        sleep_in_test(
+            "Waiting for the guardians to consider a fee bump",
+            Duration::from_secs(1),
+        )
+        .await;
+
+        assert_eq!(
+            client
+                .get_first_module::<WalletClientModule>()?
+                .pending_tx_chain()
+                .await?
+                .len(),
+            pending
+        );
+    }
+
+    info!("Confirm the pending transactions...");
+
+    mine_past_finality_delay(&client, &bitcoin).await?;
+
+    assert!(
+        client
+            .get_first_module::<WalletClientModule>()?
+            .pending_tx_chain()
+            .await?
+            .is_empty()
+    );
+
+    Ok(())
+}
@@ -0,0 +1,147 @@
+#!/usr/bin/env bash
+# Runs the cross-version compatibility matrix
+#
+# For a list of versions, ordered oldest first, this starts a federation for
+# every version and for every (older, newer) pair of versions, with the upper
+# half of the guardians upgraded to the newer one. Against each federation it
+# runs the standard operations for every pairing of fedimint-cli and gatewayd
+# versions and merges the reports into `$FM_COMPAT_REPORT_DIR/matrix.json`.
+#
+# ex: ./scripts/tests/compat-matrix.sh v0.9.1 v0.10.0 current
+
+set -euo pipefail
+
+export RUST_LOG="${RUST_LOG:-info}"
+
+# Older version might not support iroh
+export FM_ENABLE_IROH=false
+
+source scripts/_common.sh
+
+if [ "$#" -eq 0 ]; then
+  echo "Must provide at least one version"
+  exit 1
+fi
+
+PATH="$(pwd)/scripts/dev/run-test/:$PATH"
+
+# Every job runs all pairings against a federation, so it takes considerably
+# longer than a single upgrade test
+export FM_TEST_COMPAT_TIMEOUT=${FM_TEST_COMPAT_TIMEOUT:-1800}
+export FM_RUN_TEST_TIMEOUT=$((FM_TEST_COMPAT_TIMEOUT - 30))
+
+report_dir="$(realpath -m "${FM_COMPAT_REPORT_DIR:-target/compat-matrix}")"
+rm -rf "$report_dir/setups"
+mkdir -p "$report_dir/setups"
+
+build_workspace
+add_target_dir_to_path
+
+export FM_BACKWARDS_COMPATIBILITY_TEST=1
+
+versions=("$@")
+
+echo "## Running compatibility matrix
+versions: ${versions[*]}
+reports: $report_dir"
+
+function binary_path() {
+  binary="$1"
+  version="$2"
+  if [ "$version" == "current" ]; then
+    echo "$binary"
+  else
+    nix_build_binary_for_version "$binary" "$version"
+  fi
+}
+
+fedimint_cli_paths=()
+gatewayd_paths=()
+gateway_cli_paths=()
+for version in "${versions[@]}"; do
+  fedimint_cli_paths+=("$(binary_path fedimint-cli "$version")")
+  gatewayd_paths+=("$(binary_path gatewayd "$version")")
+  gateway_cli_paths+=("$(binary_path gateway-cli "$version")")
+
+  if [ "$version" != "current" ]; then
+    # for dkg we need to use the fedimint-cli version that matches fedimintd
+    var_name=$(nix_binary_version_var_name "fedimint-cli" "$version")
+    export "${var_name}=$(nix_build_binary_for_version "fedimint-cli" "$version")"
+  fi
+done
+
+# lnv2 can only be enabled if every version in the matrix supports it
+enable_lnv2=1
+if version_lt "${versions[0]}" "$LNV2_STABLE_VERSION"; then
+  enable_lnv2=0
+fi
+
+compat_tests=()
+
+function add_compat_test() {
+  dkg_version="$1"
+  upgrade_version="${2:-}"
+
+  setup="fedimintd-${dkg_version}"
+  upgrade_args=""
+  if [ -n "$upgrade_version" ]; then
+    setup="${setup}-${upgrade_version}"
+    upgrade_args="--fedimintd-upgrade $(binary_path fedimintd "$upgrade_version")"
+  fi
+
+  compat_tests+=(
+    "fm-run-test compat-${setup}-lnv2-${enable_lnv2} devimint compat-matrix --lnv2 $enable_lnv2 --fedimintd $(binary_path fedimintd "$dkg_version") $upgrade_args --fedimint-cli-paths $(printf "%s " "${fedimint_cli_paths[@]}") --gatewayd-paths $(printf "%s " "${gatewayd_paths[@]}") --gateway-cli-paths $(printf "%s " "${gateway_cli_paths[@]}") --report $report_dir/setups/${setup}.json"
+  )
+}
+
+for i in "${!versions[@]}"; do
+  add_compat_test "${versions[$i]}"
+  for newer_version in "${versions[@]:$((i + 1))}"; do
+    add_compat_test "${versions[$i]}" "$newer_version"
+  done
+done
+
+parsed_test_commands=$(printf "%s\n" "${compat_tests[@]}")
+
+tmpdir=$(mktemp --tmpdir -d XXXXX)
+trap 'rm -r $tmpdir' EXIT
+joblog="$tmpdir/joblog"
+
+parallel_args=()
+if [ -z "${CI:-}" ] && [[ -t 1 ]] && [ -z "${FM_TEST_CI_ALL_DISABLE_ETA:-}" ]; then
+  parallel_args+=(--eta)
+fi
+parallel_args+=(--jobs "${FM_TEST_CI_ALL_JOBS:-$(($(nproc) / 4 + 1))}")
+parallel_args+=(--load "${FM_TEST_CI_ALL_MAX_LOAD:-$(($(nproc) / 4 + 1))}")
+parallel_args+=(--delay "${FM_TEST_CI_ALL_DELAY:-$((64 / $(nproc) + 1))}")
+parallel_args+=(--timeout "$FM_TEST_COMPAT_TIMEOUT")
+parallel_args+=(
+  --joblog "$joblog"
+  --noswap
+  --memfree 2G
+  --nice 15
+)
+
+>&2 echo "## Starting all setups in parallel..."
+>&2 echo "parallel ${parallel_args[*]}"
+
+# unlike the upgrade tests we don't halt on the first failure, since the point
+# of the matrix is to report every incompatibility
+status=0
+echo "$parsed_test_commands" | parallel "${parallel_args[@]}" || status=$?
+
+# setups that failed to start the federation don't leave a report
+shopt -s nullglob
+reports=("$report_dir"/setups/*.json)
+jq -n 'reduce inputs as $report ({}; . + {(input_filename | split("/") | last | rtrimstr(".json")): $report})' \
+  "${reports[@]}" < /dev/null > "$report_dir/matrix.json"
+
+>&2 echo "## Compatibility matrix written to $report_dir/matrix.json"
+>&2 jq -r 'to_entries[] | .key as $setup | .value.pairings[] | "\($setup) fedimint-cli \(.fedimint_cli) gatewayd \(.gatewayd): \([.operations[] | select(.status == "failed") | .name] | join(", ") | if . == "" then "ok" else "failed: " + . end)"' \
+  "$report_dir/matrix.json"
+
+if [ "$status" -ne 0 ]; then
+  >&2 echo "Some setups failed:"
+  awk '{ if($7 != "0") print $0 "\n" }' < "$joblog"
+  exit 1
+fi